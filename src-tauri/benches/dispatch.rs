@@ -0,0 +1,77 @@
+// inbound gossip dispatch: decoding and checking what arrives from the mesh,
+// caching it for late joiners, and serializing what the node sends back.
+// run with `cargo bench --features bench`
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use libp2p::identity::Keypair;
+
+use dusk_chat_lib::bench::{self, Encoder, MessageCache};
+
+fn prevalidate(c: &mut Criterion) {
+    let keypair = Keypair::generate_ed25519();
+    let author = keypair.public().to_peer_id();
+    let mut group = c.benchmark_group("prevalidate");
+
+    for content_len in [64, 1024, 8192] {
+        let data = serde_json::to_vec(&bench::chat_message(&author, content_len)).unwrap();
+        let topic = bench::channel_topic();
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(format!("chat_{}b", content_len), |b| {
+            b.iter_batched(
+                || bench::inbound(&topic, author, data.clone()),
+                |message| assert!(bench::prevalidate(black_box(message))),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    let data = serde_json::to_vec(&bench::profile_announcement(&keypair)).unwrap();
+    let topic = bench::directory_topic();
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("signed_announcement", |b| {
+        b.iter_batched(
+            || bench::inbound(&topic, author, data.clone()),
+            |message| assert!(bench::prevalidate(black_box(message))),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn cache(c: &mut Criterion) {
+    let author = Keypair::generate_ed25519().public().to_peer_id();
+    let data = serde_json::to_vec(&bench::chat_message(&author, 256)).unwrap();
+    let topic = bench::channel_topic();
+    let mut cache = MessageCache::new();
+
+    c.bench_function("cache_insert", |b| {
+        b.iter_batched(
+            || data.clone(),
+            |data| cache.insert(black_box(&topic), data),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+// serde_json::to_vec allocates and grows a new buffer per message, the
+// encoder reuses one
+fn encode(c: &mut Criterion) {
+    let author = Keypair::generate_ed25519().public().to_peer_id();
+    let mut group = c.benchmark_group("encode");
+
+    for content_len in [64, 1024, 8192] {
+        let message = bench::chat_message(&author, content_len);
+        group.bench_function(format!("to_vec_{}b", content_len), |b| {
+            b.iter(|| serde_json::to_vec(black_box(&message)).unwrap())
+        });
+        let mut encoder = Encoder::new();
+        group.bench_function(format!("encoder_{}b", content_len), |b| {
+            b.iter(|| encoder.encode(black_box(&message)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, prevalidate, cache, encode);
+criterion_main!(benches);
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::node::{NodeCommand, NodeHandle};
+use crate::protocol::messages::clean_status_text;
+use crate::AppState;
+
+// longest activity text we send or accept from peers
+pub const MAX_ACTIVITY_LEN: usize = 128;
+
+#[cfg(windows)]
+const PIPE_NAME: &str = r"\\.\pipe\dusk-activity";
+
+// trim, strip control characters and bound the length, empty means no activity
+pub fn normalize(activity: &str) -> Option<String> {
+    let cleaned = clean_status_text(activity, MAX_ACTIVITY_LEN);
+    if cleaned.is_empty() {
+        None
+    } else {
+        Some(cleaned)
+    }
+}
+
+// remember the activity and hand it to the running node, which republishes presence
+pub async fn apply(
+    current: &Arc<Mutex<Option<String>>>,
+    node_handle: &Arc<Mutex<Option<NodeHandle>>>,
+    activity: Option<String>,
+) {
+    *current.lock().await = activity.clone();
+    if let Some(ref handle) = *node_handle.lock().await {
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::SetActivity { activity })
+            .await;
+    }
+}
+
+// local integration point for game launchers and scripts: every line written
+// to the pipe replaces the current activity, an empty line clears it.
+// on unix this is a fifo named `activity` in the data directory, on windows
+// the named pipe \\.\pipe\dusk-activity
+pub fn spawn_pipe_listener(state: &AppState) {
+    let current = state.activity.clone();
+    let node_handle = state.node_handle.clone();
+
+    #[cfg(unix)]
+    listen_fifo(state.storage.data_dir().join("activity"), current, node_handle);
+
+    #[cfg(windows)]
+    listen_named_pipe(current, node_handle);
+}
+
+#[cfg(unix)]
+fn listen_fifo(
+    path: std::path::PathBuf,
+    current: Arc<Mutex<Option<String>>>,
+    node_handle: Arc<Mutex<Option<NodeHandle>>>,
+) {
+    use std::io::BufRead;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::metadata(&path) {
+        Ok(meta) if meta.file_type().is_fifo() => {}
+        Ok(_) => {
+            log::warn!("activity pipe path {} exists and is not a fifo", path.display());
+            return;
+        }
+        Err(_) => {
+            let c_path = match std::ffi::CString::new(path.as_os_str().as_bytes()) {
+                Ok(p) => p,
+                Err(_) => return,
+            };
+            // owner-only, other local users must not be able to set our presence
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                log::warn!(
+                    "failed to create activity pipe at {}: {}",
+                    path.display(),
+                    std::io::Error::last_os_error()
+                );
+                return;
+            }
+        }
+    }
+
+    // opening a fifo for reading blocks until a writer shows up, so this
+    // lives on its own thread instead of the async runtime
+    let spawned = std::thread::Builder::new()
+        .name("activity-pipe".to_string())
+        .spawn(move || loop {
+            let file = match std::fs::File::open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    log::warn!("failed to open activity pipe: {}", e);
+                    return;
+                }
+            };
+            for line in std::io::BufReader::new(file).lines() {
+                let Ok(line) = line else { break };
+                tauri::async_runtime::block_on(apply(&current, &node_handle, normalize(&line)));
+            }
+        });
+    if let Err(e) = spawned {
+        log::warn!("failed to start activity pipe listener: {}", e);
+    }
+}
+
+#[cfg(windows)]
+fn listen_named_pipe(
+    current: Arc<Mutex<Option<String>>>,
+    node_handle: Arc<Mutex<Option<NodeHandle>>>,
+) {
+    use tokio::io::AsyncBufReadExt;
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let server = match ServerOptions::new().create(PIPE_NAME) {
+                Ok(server) => server,
+                Err(e) => {
+                    log::warn!("failed to create activity pipe: {}", e);
+                    return;
+                }
+            };
+            if server.connect().await.is_err() {
+                continue;
+            }
+
+            // one client at a time, the next instance is created once it disconnects
+            let mut lines = tokio::io::BufReader::new(server).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                apply(&current, &node_handle, normalize(&line)).await;
+            }
+        }
+    });
+}
@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+
+use nnnoiseless::DenoiseState;
+
+// rnnoise works on 10ms frames of 48khz mono audio
+pub const FRAME_SIZE: usize = DenoiseState::FRAME_SIZE;
+// rnnoise expects samples in 16-bit pcm range
+const PCM_SCALE: f32 = 32767.0;
+
+// rms level the gain stage steers speech towards
+const TARGET_RMS: f32 = 0.1;
+const MIN_GAIN: f32 = 0.5;
+const MAX_GAIN: f32 = 4.0;
+// frames quieter than this are treated as silence and leave the gain alone,
+// otherwise background hiss gets amplified between sentences
+const GATE_RMS: f32 = 0.005;
+// per-frame smoothing, lowering the gain reacts faster than raising it
+const GAIN_ATTACK: f32 = 0.3;
+const GAIN_RELEASE: f32 = 0.02;
+
+// optional microphone processing applied before webrtc encoding. frames of any
+// length go in and the same number of samples come out, delayed by at most one
+// rnnoise frame while the first full frame fills up
+pub struct AudioProcessor {
+    noise_suppression: bool,
+    auto_gain: bool,
+    denoise: Option<Box<DenoiseState<'static>>>,
+    gain: f32,
+    input: Vec<f32>,
+    output: VecDeque<f32>,
+}
+
+impl AudioProcessor {
+    pub fn new(noise_suppression: bool, auto_gain: bool) -> Self {
+        let mut processor = Self {
+            noise_suppression: false,
+            auto_gain: false,
+            denoise: None,
+            gain: 1.0,
+            input: Vec::with_capacity(FRAME_SIZE),
+            output: VecDeque::new(),
+        };
+        processor.configure(noise_suppression, auto_gain);
+        processor
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.noise_suppression || self.auto_gain
+    }
+
+    pub fn configure(&mut self, noise_suppression: bool, auto_gain: bool) {
+        if noise_suppression && self.denoise.is_none() {
+            self.denoise = Some(DenoiseState::new());
+        } else if !noise_suppression {
+            self.denoise = None;
+        }
+        if !auto_gain {
+            self.gain = 1.0;
+        }
+        self.noise_suppression = noise_suppression;
+        self.auto_gain = auto_gain;
+    }
+
+    // drop buffered audio and model state, called when a call starts so
+    // the previous call's tail never leaks into the next one
+    pub fn reset(&mut self) {
+        self.input.clear();
+        self.output.clear();
+        self.gain = 1.0;
+        if self.noise_suppression {
+            self.denoise = Some(DenoiseState::new());
+        }
+    }
+
+    // process mono 48khz samples in -1.0..1.0
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        if !self.is_enabled() {
+            return samples.to_vec();
+        }
+
+        // prime the output with one frame of silence so every call can
+        // return as many samples as it was given
+        if self.output.is_empty() && self.input.is_empty() {
+            self.output.extend(std::iter::repeat(0.0).take(FRAME_SIZE));
+        }
+
+        for &sample in samples {
+            self.input.push(sample);
+            if self.input.len() == FRAME_SIZE {
+                let frame = std::mem::replace(&mut self.input, Vec::with_capacity(FRAME_SIZE));
+                let processed = self.process_frame(&frame);
+                self.output.extend(processed);
+            }
+        }
+
+        let n = samples.len().min(self.output.len());
+        self.output.drain(..n).collect()
+    }
+
+    fn process_frame(&mut self, frame: &[f32]) -> Vec<f32> {
+        let mut out = frame.to_vec();
+
+        if let Some(denoise) = self.denoise.as_mut() {
+            let scaled: Vec<f32> = frame.iter().map(|s| s * PCM_SCALE).collect();
+            let mut denoised = vec![0.0f32; FRAME_SIZE];
+            denoise.process_frame(&mut denoised, &scaled);
+            out = denoised.iter().map(|s| s / PCM_SCALE).collect();
+        }
+
+        if self.auto_gain {
+            let rms = (out.iter().map(|s| s * s).sum::<f32>() / out.len() as f32).sqrt();
+            if rms > GATE_RMS {
+                let wanted = (TARGET_RMS / rms).clamp(MIN_GAIN, MAX_GAIN);
+                let rate = if wanted < self.gain { GAIN_ATTACK } else { GAIN_RELEASE };
+                self.gain += (wanted - self.gain) * rate;
+            }
+            for sample in out.iter_mut() {
+                *sample = (*sample * self.gain).clamp(-1.0, 1.0);
+            }
+        }
+
+        out
+    }
+}
@@ -0,0 +1,108 @@
+// entry points into the inbound gossip path for the criterion benches in
+// benches/, only built with the bench feature
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libp2p::gossipsub;
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+
+use crate::node::{cache, gossip, prevalidate};
+use crate::protocol::messages::{ChatMessage, GossipMessage, Hlc, ProfileAnnouncement};
+use crate::verification;
+
+pub use crate::node::encode::Encoder;
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+pub fn channel_topic() -> String {
+    gossip::topic_for_messages("bench-community", "general")
+}
+
+pub fn directory_topic() -> String {
+    gossip::topic_for_directory()
+}
+
+// a channel message with content of the given length
+pub fn chat_message(author: &PeerId, content_len: usize) -> GossipMessage {
+    let now = now_ms();
+    GossipMessage::Chat(ChatMessage {
+        id: format!("msg-{}", now),
+        channel_id: "general".to_string(),
+        author_id: author.to_string(),
+        author_name: "bench".to_string(),
+        content: "a".repeat(content_len),
+        timestamp: now,
+        edited: false,
+        hlc: Hlc {
+            wall: now,
+            counter: 0,
+        },
+        forwarded_from: None,
+        attachments: Vec::new(),
+        embeds: Vec::new(),
+        reactions: Vec::new(),
+        reply_to: None,
+    })
+}
+
+// a profile announcement signed by the keypair, checked on arrival
+pub fn profile_announcement(keypair: &Keypair) -> GossipMessage {
+    let mut announcement = ProfileAnnouncement {
+        peer_id: keypair.public().to_peer_id().to_string(),
+        display_name: "bench".to_string(),
+        bio: String::new(),
+        public_key: hex::encode(keypair.public().encode_protobuf()),
+        timestamp: now_ms(),
+        verification_proof: None,
+        signature: String::new(),
+        status_message: String::new(),
+        status_emoji: String::new(),
+    };
+    announcement.signature = verification::sign_announcement(keypair, &announcement);
+    GossipMessage::ProfileAnnounce(announcement)
+}
+
+// a message as gossipsub hands it to the node loop
+pub fn inbound(topic: &str, source: PeerId, data: Vec<u8>) -> gossipsub::Message {
+    gossipsub::Message {
+        source: Some(source),
+        data,
+        sequence_number: Some(1),
+        topic: gossipsub::TopicHash::from_raw(topic),
+    }
+}
+
+// decode and signature check a message as a prevalidation worker does,
+// true when it decoded and any signature it carries is valid
+pub fn prevalidate(message: gossipsub::Message) -> bool {
+    let source = message.source.unwrap_or_else(PeerId::random);
+    let message_id = gossipsub::MessageId::from(message.data.clone());
+    let validated = prevalidate::prevalidate(source, message_id, message);
+    validated.signature_valid && !matches!(validated.payload, prevalidate::Payload::Malformed)
+}
+
+// the cache every channel message passes through on arrival
+pub struct MessageCache(cache::MessageCache);
+
+impl MessageCache {
+    pub fn new() -> Self {
+        Self(cache::MessageCache::new())
+    }
+
+    pub fn insert(&mut self, topic: &str, data: Vec<u8>) {
+        if cache::MessageCache::is_cacheable(topic) {
+            self.0.insert(topic, data, now_ms());
+        }
+    }
+}
+
+impl Default for MessageCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,118 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::OwnedMutexGuard;
+
+use crate::crdt::CrdtEngine;
+use crate::node::DuskEvent;
+use crate::protocol::identity::DuskIdentity;
+use crate::storage::keystore;
+use crate::AppState;
+
+// startup progress reported to the frontend. storage opens before the window
+// since every command needs it, the rest runs in the background so the
+// window shows right away
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootPhase {
+    // loading community documents from disk
+    Documents,
+    // loading the stored identity
+    Identity,
+    // the stored identity is sealed, waiting for unlock_identity
+    Locked,
+    // no identity on disk, waiting for one to be created
+    Onboarding,
+    // identity loaded, the node starts once start_node is called
+    Node,
+    // node running
+    Ready,
+}
+
+pub async fn set_phase(app: &AppHandle, phase: BootPhase) {
+    let state = app.state::<AppState>();
+    *state.boot_phase.lock().await = phase;
+    let _ = app.emit("dusk-event", DuskEvent::BootPhase { phase });
+}
+
+// run the startup phases. `engine` is locked before the window shows and
+// held until the documents are loaded, so commands touching communities
+// wait for them instead of seeing an empty engine
+pub async fn run(app: AppHandle, mut engine: OwnedMutexGuard<CrdtEngine>) {
+    // encrypted documents can't be read before the passphrase is in,
+    // unlock_identity loads them instead
+    if app.state::<AppState>().storage.is_storage_locked() {
+        drop(engine);
+        set_phase(&app, BootPhase::Locked).await;
+        return;
+    }
+
+    set_phase(&app, BootPhase::Documents).await;
+    let loaded = tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = engine.load_all() {
+            log::warn!("failed to load persisted communities: {}", e);
+        }
+    })
+    .await;
+    if let Err(e) = loaded {
+        log::error!("community load task failed: {}", e);
+    }
+
+    set_phase(&app, BootPhase::Identity).await;
+    let state = app.state::<AppState>();
+
+    // strangers not seen for directory_ttl_days drop out of the directory
+    let ttl = state
+        .storage
+        .load_settings()
+        .unwrap_or_default()
+        .directory_ttl_days;
+    if ttl > 0 {
+        match crate::commands::identity::prune_stale_directory(&state, ttl).await {
+            Ok(0) => {}
+            Ok(pruned) => log::info!("pruned {} stale directory entries", pruned),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+    if !state.storage.has_identity() {
+        set_phase(&app, BootPhase::Onboarding).await;
+        return;
+    }
+
+    // the user may have unlocked while documents were loading, a sealed
+    // keypair only means locked while nothing is loaded. portable installs
+    // wait for a passphrase to seal a clear one with
+    let mut identity = state.identity.lock().await;
+    if identity.is_none() {
+        let sealed = state
+            .storage
+            .load_keypair()
+            .map(|bytes| keystore::is_sealed(&bytes) || crate::storage::portable_mode())
+            .unwrap_or(false);
+        // still holding the identity lock, so an unlock can't land between
+        // this check and the phase change and leave us stuck at locked
+        if sealed {
+            set_phase(&app, BootPhase::Locked).await;
+            return;
+        }
+        match DuskIdentity::load(&state.storage) {
+            Ok(loaded) => *identity = Some(loaded),
+            Err(e) => {
+                log::warn!("failed to load identity: {}", e);
+                drop(identity);
+                set_phase(&app, BootPhase::Onboarding).await;
+                return;
+            }
+        }
+    }
+    drop(identity);
+
+    set_phase(&app, BootPhase::Node).await;
+}
+
+// move past the identity gate once the user unlocks or creates an identity
+pub async fn identity_ready(app: &AppHandle) {
+    let phase = *app.state::<AppState>().boot_phase.lock().await;
+    if matches!(phase, BootPhase::Locked | BootPhase::Onboarding) {
+        set_phase(app, BootPhase::Node).await;
+    }
+}
@@ -0,0 +1,432 @@
+use std::sync::RwLock;
+
+use crate::locale::{split_tag, LocaleSettings};
+
+// text the backend generates for people to read, by message id. a bundle
+// missing an id falls back to english, so new ids only need adding here
+// first. placeholders are written {name} and filled from the args
+const EN: &[(&str, &str)] = &[
+    ("yes", "yes"),
+    ("no", "no"),
+    ("none", "none"),
+    ("unknown", "unknown"),
+    // membership log
+    ("membership.join", "{name} joined"),
+    ("membership.leave", "{name} left"),
+    ("membership.kick", "{name} was removed by {actor}"),
+    ("membership.set_roles", "{actor} set the roles of {name} to {roles}"),
+    ("membership.clear_roles", "{actor} removed all roles from {name}"),
+    // dm exports
+    (
+        "export.conversation_between",
+        "conversation between {local} ({local_id}) and {peer} ({peer_id})",
+    ),
+    ("export.conversation_with", "conversation with {peer}"),
+    ("export.exported", "exported {date}"),
+    // data report
+    ("report.title", "dusk data report"),
+    ("report.generated", "generated {date}"),
+    ("report.section.identity", "your identity"),
+    ("report.section.communities", "communities"),
+    ("report.section.dms", "direct messages"),
+    ("report.section.directory", "peer directory"),
+    ("report.section.peer", "data about {peer}"),
+    ("report.peer_id", "peer id"),
+    ("report.display_name", "display name"),
+    ("report.bio", "bio"),
+    ("report.created", "created"),
+    ("report.proof_stored", "verification proof stored"),
+    ("report.status", "status"),
+    ("report.status_message", "status message"),
+    ("report.relay_discoverable", "discoverable on relay directory"),
+    ("report.custom_relay", "custom relay"),
+    (
+        "report.community",
+        "{name} ({id}) - {members} members, your roles: [{roles}], messages you authored: {authored}",
+    ),
+    ("report.conversations", "conversations"),
+    ("report.stored_messages", "stored messages"),
+    ("report.known_peers", "known peers"),
+    ("report.friends", "friends"),
+    ("report.revoked_peers", "revoked identities remembered"),
+    ("report.public_key", "public key"),
+    ("report.last_seen", "last seen"),
+    ("report.friend", "friend"),
+    ("report.no_directory_entry", "no directory entry stored"),
+    ("report.shared_communities", "shared communities"),
+    ("report.dm_stored", "dm messages stored"),
+    ("report.unread", "unread"),
+    ("report.dm_history", "dm history: {first} to {last}"),
+    ("report.no_dm", "no dm conversation stored"),
+    // command errors, matched by their english text, see localize_error
+    ("error.no_identity", "no identity loaded"),
+    ("error.node_not_running", "node not running"),
+    ("error.insufficient_permissions", "insufficient permissions"),
+    ("error.verification_required", "verification required"),
+    ("error.member_not_found", "member not found"),
+    ("error.message_not_found", "message not found"),
+    ("error.page_not_found", "page not found"),
+    ("error.peer_not_in_directory", "peer not found in directory"),
+    ("error.peer_key_unknown", "public key for this peer is not known yet"),
+    ("error.display_name_empty", "display name cannot be empty"),
+    ("error.message_empty", "message content cannot be empty"),
+    ("error.profile_name_empty", "profile name cannot be empty"),
+    ("error.folder_name_empty", "folder name cannot be empty"),
+    ("error.edit_own_only", "only your own messages can be edited"),
+    ("error.delete_own_only", "only your own messages can be deleted for everyone"),
+    ("error.delete_too_old", "message is too old to delete for everyone"),
+    ("error.not_authorized_edit", "not authorized to edit this message"),
+    ("error.not_authorized_kick", "not authorized to kick members"),
+    (
+        "error.announcement_admins_only",
+        "only owners and admins can post in announcement channels",
+    ),
+    (
+        "error.stage_listener_audio",
+        "stage listeners can't send audio, raise your hand to speak",
+    ),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("yes", "ja"),
+    ("no", "nein"),
+    ("none", "keine"),
+    ("unknown", "unbekannt"),
+    ("membership.join", "{name} ist beigetreten"),
+    ("membership.leave", "{name} hat die Community verlassen"),
+    ("membership.kick", "{name} wurde von {actor} entfernt"),
+    ("membership.set_roles", "{actor} hat die Rollen von {name} auf {roles} gesetzt"),
+    ("membership.clear_roles", "{actor} hat {name} alle Rollen entzogen"),
+    (
+        "export.conversation_between",
+        "Unterhaltung zwischen {local} ({local_id}) und {peer} ({peer_id})",
+    ),
+    ("export.conversation_with", "Unterhaltung mit {peer}"),
+    ("export.exported", "exportiert am {date}"),
+    ("report.title", "Dusk-Datenbericht"),
+    ("report.generated", "erstellt am {date}"),
+    ("report.section.identity", "Deine Identität"),
+    ("report.section.communities", "Communities"),
+    ("report.section.dms", "Direktnachrichten"),
+    ("report.section.directory", "Peer-Verzeichnis"),
+    ("report.section.peer", "Daten über {peer}"),
+    ("report.peer_id", "Peer-ID"),
+    ("report.display_name", "Anzeigename"),
+    ("report.bio", "Bio"),
+    ("report.created", "erstellt"),
+    ("report.proof_stored", "Verifizierungsnachweis gespeichert"),
+    ("report.status", "Status"),
+    ("report.status_message", "Statusnachricht"),
+    ("report.relay_discoverable", "im Relay-Verzeichnis auffindbar"),
+    ("report.custom_relay", "eigenes Relay"),
+    (
+        "report.community",
+        "{name} ({id}) - {members} Mitglieder, deine Rollen: [{roles}], von dir verfasste Nachrichten: {authored}",
+    ),
+    ("report.conversations", "Unterhaltungen"),
+    ("report.stored_messages", "gespeicherte Nachrichten"),
+    ("report.known_peers", "bekannte Peers"),
+    ("report.friends", "Freunde"),
+    ("report.revoked_peers", "gemerkte widerrufene Identitäten"),
+    ("report.public_key", "öffentlicher Schlüssel"),
+    ("report.last_seen", "zuletzt gesehen"),
+    ("report.friend", "Freund"),
+    ("report.no_directory_entry", "kein Verzeichniseintrag gespeichert"),
+    ("report.shared_communities", "gemeinsame Communities"),
+    ("report.dm_stored", "gespeicherte Direktnachrichten"),
+    ("report.unread", "ungelesen"),
+    ("report.dm_history", "Verlauf: {first} bis {last}"),
+    ("report.no_dm", "keine Unterhaltung gespeichert"),
+    ("error.no_identity", "keine Identität geladen"),
+    ("error.node_not_running", "der Knoten läuft nicht"),
+    ("error.insufficient_permissions", "unzureichende Berechtigungen"),
+    ("error.verification_required", "Verifizierung erforderlich"),
+    ("error.member_not_found", "Mitglied nicht gefunden"),
+    ("error.message_not_found", "Nachricht nicht gefunden"),
+    ("error.page_not_found", "Seite nicht gefunden"),
+    ("error.peer_not_in_directory", "Peer nicht im Verzeichnis gefunden"),
+    ("error.peer_key_unknown", "der öffentliche Schlüssel dieses Peers ist noch nicht bekannt"),
+    ("error.display_name_empty", "der Anzeigename darf nicht leer sein"),
+    ("error.message_empty", "die Nachricht darf nicht leer sein"),
+    ("error.profile_name_empty", "der Profilname darf nicht leer sein"),
+    ("error.folder_name_empty", "der Ordnername darf nicht leer sein"),
+    ("error.edit_own_only", "nur eigene Nachrichten können bearbeitet werden"),
+    ("error.delete_own_only", "nur eigene Nachrichten können für alle gelöscht werden"),
+    ("error.delete_too_old", "die Nachricht ist zu alt, um sie für alle zu löschen"),
+    ("error.not_authorized_edit", "keine Berechtigung, diese Nachricht zu bearbeiten"),
+    ("error.not_authorized_kick", "keine Berechtigung, Mitglieder zu entfernen"),
+    (
+        "error.announcement_admins_only",
+        "nur Inhaber und Admins können in Ankündigungskanälen schreiben",
+    ),
+    (
+        "error.stage_listener_audio",
+        "Zuhörer einer Bühne können kein Audio senden, melde dich, um zu sprechen",
+    ),
+];
+
+const ES: &[(&str, &str)] = &[
+    ("yes", "sí"),
+    ("no", "no"),
+    ("none", "ninguna"),
+    ("unknown", "desconocida"),
+    ("membership.join", "{name} se unió"),
+    ("membership.leave", "{name} salió"),
+    ("membership.kick", "{actor} expulsó a {name}"),
+    ("membership.set_roles", "{actor} cambió los roles de {name} a {roles}"),
+    ("membership.clear_roles", "{actor} quitó todos los roles a {name}"),
+    (
+        "export.conversation_between",
+        "conversación entre {local} ({local_id}) y {peer} ({peer_id})",
+    ),
+    ("export.conversation_with", "conversación con {peer}"),
+    ("export.exported", "exportada el {date}"),
+    ("report.title", "informe de datos de dusk"),
+    ("report.generated", "generado el {date}"),
+    ("report.section.identity", "tu identidad"),
+    ("report.section.communities", "comunidades"),
+    ("report.section.dms", "mensajes directos"),
+    ("report.section.directory", "directorio de pares"),
+    ("report.section.peer", "datos sobre {peer}"),
+    ("report.peer_id", "id de par"),
+    ("report.display_name", "nombre visible"),
+    ("report.bio", "biografía"),
+    ("report.created", "creada"),
+    ("report.proof_stored", "prueba de verificación guardada"),
+    ("report.status", "estado"),
+    ("report.status_message", "mensaje de estado"),
+    ("report.relay_discoverable", "visible en el directorio del relé"),
+    ("report.custom_relay", "relé propio"),
+    (
+        "report.community",
+        "{name} ({id}) - {members} miembros, tus roles: [{roles}], mensajes escritos por ti: {authored}",
+    ),
+    ("report.conversations", "conversaciones"),
+    ("report.stored_messages", "mensajes guardados"),
+    ("report.known_peers", "pares conocidos"),
+    ("report.friends", "amigos"),
+    ("report.revoked_peers", "identidades revocadas recordadas"),
+    ("report.public_key", "clave pública"),
+    ("report.last_seen", "visto por última vez"),
+    ("report.friend", "amigo"),
+    ("report.no_directory_entry", "sin entrada en el directorio"),
+    ("report.shared_communities", "comunidades en común"),
+    ("report.dm_stored", "mensajes directos guardados"),
+    ("report.unread", "sin leer"),
+    ("report.dm_history", "historial: del {first} al {last}"),
+    ("report.no_dm", "sin conversación guardada"),
+    ("error.no_identity", "no hay ninguna identidad cargada"),
+    ("error.node_not_running", "el nodo no está en ejecución"),
+    ("error.insufficient_permissions", "permisos insuficientes"),
+    ("error.verification_required", "se requiere verificación"),
+    ("error.member_not_found", "miembro no encontrado"),
+    ("error.message_not_found", "mensaje no encontrado"),
+    ("error.page_not_found", "página no encontrada"),
+    ("error.peer_not_in_directory", "par no encontrado en el directorio"),
+    ("error.peer_key_unknown", "todavía no se conoce la clave pública de este par"),
+    ("error.display_name_empty", "el nombre visible no puede estar vacío"),
+    ("error.message_empty", "el mensaje no puede estar vacío"),
+    ("error.profile_name_empty", "el nombre del perfil no puede estar vacío"),
+    ("error.folder_name_empty", "el nombre de la carpeta no puede estar vacío"),
+    ("error.edit_own_only", "solo puedes editar tus propios mensajes"),
+    ("error.delete_own_only", "solo puedes eliminar para todos tus propios mensajes"),
+    ("error.delete_too_old", "el mensaje es demasiado antiguo para eliminarlo para todos"),
+    ("error.not_authorized_edit", "no tienes permiso para editar este mensaje"),
+    ("error.not_authorized_kick", "no tienes permiso para expulsar miembros"),
+    (
+        "error.announcement_admins_only",
+        "solo los propietarios y administradores pueden publicar en canales de anuncios",
+    ),
+    (
+        "error.stage_listener_audio",
+        "los oyentes de un escenario no pueden enviar audio, levanta la mano para hablar",
+    ),
+];
+
+const FR: &[(&str, &str)] = &[
+    ("yes", "oui"),
+    ("no", "non"),
+    ("none", "aucune"),
+    ("unknown", "inconnue"),
+    ("membership.join", "{name} a rejoint la communauté"),
+    ("membership.leave", "{name} a quitté la communauté"),
+    ("membership.kick", "{name} a été exclu par {actor}"),
+    ("membership.set_roles", "{actor} a défini les rôles de {name} : {roles}"),
+    ("membership.clear_roles", "{actor} a retiré tous les rôles de {name}"),
+    (
+        "export.conversation_between",
+        "conversation entre {local} ({local_id}) et {peer} ({peer_id})",
+    ),
+    ("export.conversation_with", "conversation avec {peer}"),
+    ("export.exported", "exportée le {date}"),
+    ("report.title", "rapport de données dusk"),
+    ("report.generated", "généré le {date}"),
+    ("report.section.identity", "votre identité"),
+    ("report.section.communities", "communautés"),
+    ("report.section.dms", "messages privés"),
+    ("report.section.directory", "annuaire des pairs"),
+    ("report.section.peer", "données sur {peer}"),
+    ("report.peer_id", "id de pair"),
+    ("report.display_name", "nom affiché"),
+    ("report.bio", "bio"),
+    ("report.created", "créée"),
+    ("report.proof_stored", "preuve de vérification enregistrée"),
+    ("report.status", "statut"),
+    ("report.status_message", "message de statut"),
+    ("report.relay_discoverable", "visible dans l'annuaire du relais"),
+    ("report.custom_relay", "relais personnalisé"),
+    (
+        "report.community",
+        "{name} ({id}) - {members} membres, vos rôles : [{roles}], messages écrits par vous : {authored}",
+    ),
+    ("report.conversations", "conversations"),
+    ("report.stored_messages", "messages enregistrés"),
+    ("report.known_peers", "pairs connus"),
+    ("report.friends", "amis"),
+    ("report.revoked_peers", "identités révoquées mémorisées"),
+    ("report.public_key", "clé publique"),
+    ("report.last_seen", "vu pour la dernière fois"),
+    ("report.friend", "ami"),
+    ("report.no_directory_entry", "aucune entrée d'annuaire enregistrée"),
+    ("report.shared_communities", "communautés en commun"),
+    ("report.dm_stored", "messages privés enregistrés"),
+    ("report.unread", "non lus"),
+    ("report.dm_history", "historique : du {first} au {last}"),
+    ("report.no_dm", "aucune conversation enregistrée"),
+    ("error.no_identity", "aucune identité chargée"),
+    ("error.node_not_running", "le nœud n'est pas démarré"),
+    ("error.insufficient_permissions", "permissions insuffisantes"),
+    ("error.verification_required", "vérification requise"),
+    ("error.member_not_found", "membre introuvable"),
+    ("error.message_not_found", "message introuvable"),
+    ("error.page_not_found", "page introuvable"),
+    ("error.peer_not_in_directory", "pair introuvable dans l'annuaire"),
+    ("error.peer_key_unknown", "la clé publique de ce pair n'est pas encore connue"),
+    ("error.display_name_empty", "le nom affiché ne peut pas être vide"),
+    ("error.message_empty", "le message ne peut pas être vide"),
+    ("error.profile_name_empty", "le nom du profil ne peut pas être vide"),
+    ("error.folder_name_empty", "le nom du dossier ne peut pas être vide"),
+    ("error.edit_own_only", "seuls vos propres messages peuvent être modifiés"),
+    ("error.delete_own_only", "seuls vos propres messages peuvent être supprimés pour tous"),
+    ("error.delete_too_old", "le message est trop ancien pour être supprimé pour tous"),
+    ("error.not_authorized_edit", "vous n'êtes pas autorisé à modifier ce message"),
+    ("error.not_authorized_kick", "vous n'êtes pas autorisé à exclure des membres"),
+    (
+        "error.announcement_admins_only",
+        "seuls les propriétaires et les admins peuvent publier dans les salons d'annonces",
+    ),
+    (
+        "error.stage_listener_audio",
+        "les auditeurs d'une scène ne peuvent pas envoyer d'audio, levez la main pour parler",
+    ),
+];
+
+// bundles by language or full tag, a full tag such as "pt-BR" is matched
+// before its language
+const BUNDLES: &[(&str, &[(&str, &str)])] = &[("en", EN), ("de", DE), ("es", ES), ("fr", FR)];
+
+// the bundles to look text up in for one locale, most specific first and
+// english last
+pub struct Catalog {
+    bundles: Vec<&'static [(&'static str, &'static str)]>,
+}
+
+impl Catalog {
+    pub fn new(locale: &str) -> Self {
+        let (language, _) = split_tag(locale);
+        let mut bundles = Vec::new();
+        for wanted in [locale, language] {
+            let found = BUNDLES
+                .iter()
+                .find(|(tag, _)| tag.eq_ignore_ascii_case(wanted))
+                .map(|(_, bundle)| *bundle);
+            if let Some(bundle) = found {
+                if !bundles.iter().any(|b| std::ptr::eq(*b, bundle)) {
+                    bundles.push(bundle);
+                }
+            }
+        }
+        if !bundles.iter().any(|b| std::ptr::eq(*b, EN)) {
+            bundles.push(EN);
+        }
+        Self { bundles }
+    }
+
+    pub fn from_settings(settings: &LocaleSettings) -> Self {
+        Self::new(&settings.resolve().locale)
+    }
+
+    // the text for id with its placeholders filled. an id no bundle has
+    // comes back as is, so a typo shows up instead of vanishing
+    pub fn text(&self, id: &str, args: &[(&str, &str)]) -> String {
+        let template = self
+            .bundles
+            .iter()
+            .find_map(|bundle| bundle.iter().find(|(key, _)| *key == id))
+            .map_or(id, |(_, text)| *text);
+        fill(template, args)
+    }
+
+    // "label: value", the shape of most lines in reports
+    pub fn labelled(&self, id: &str, value: impl std::fmt::Display) -> String {
+        format!("{}: {}", self.text(id, &[]), value)
+    }
+
+    pub fn yes_no(&self, value: bool) -> String {
+        self.text(if value { "yes" } else { "no" }, &[])
+    }
+}
+
+// the locale command errors are shown in, kept in step with the settings
+static ERROR_LOCALE: RwLock<String> = RwLock::new(String::new());
+
+pub fn apply(settings: &LocaleSettings) {
+    if let Ok(mut locale) = ERROR_LOCALE.write() {
+        *locale = settings.resolve().locale;
+    }
+}
+
+// commands write their errors in english and they are translated here on the
+// way out, so the ipc log keeps the english text. an error is matched to its
+// id by the english text, anything else (errors carrying detail from below)
+// goes out as is
+pub fn localize_error(error: String) -> String {
+    let id = EN
+        .iter()
+        .find(|(key, text)| key.starts_with("error.") && *text == error)
+        .map(|(key, _)| *key);
+    let Some(id) = id else {
+        return error;
+    };
+    match ERROR_LOCALE.read() {
+        Ok(locale) if !locale.is_empty() => Catalog::new(&locale).text(id, &[]),
+        _ => error,
+    }
+}
+
+// one pass over the template, so braces inside a filled value are left alone.
+// unknown placeholders stay as written
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}') {
+            Some(close) => {
+                let name = &after[..close];
+                match args.iter().find(|(key, _)| *key == name) {
+                    Some((_, value)) => out.push_str(value),
+                    None => out.push_str(&rest[open..open + close + 2]),
+                }
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
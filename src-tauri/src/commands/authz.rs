@@ -0,0 +1,161 @@
+use crate::crdt::CrdtEngine;
+use crate::protocol::community::{CapabilityToken, Member, ModerationCapability};
+use crate::AppState;
+
+// what the local identity may do in one community, resolved from the doc
+// once per command. the rules mirror what peers check on receipt, roles
+// from the signed membership log and capability tokens chained back to the
+// owner, so anything allowed here is accepted on their side too
+pub(crate) struct Permissions {
+    pub peer_id: String,
+    pub is_owner: bool,
+    pub is_admin: bool,
+    members: Vec<Member>,
+    // our own valid tokens
+    capabilities: Vec<CapabilityToken>,
+}
+
+// for commands that don't otherwise need the engine locked
+pub(crate) async fn resolve(state: &AppState, community_id: &str) -> Result<Permissions, String> {
+    let peer_id = {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        id.peer_id.to_string()
+    };
+    let engine = state.crdt_engine.lock().await;
+    resolve_with(&engine, community_id, &peer_id)
+}
+
+pub(crate) fn resolve_with(
+    engine: &CrdtEngine,
+    community_id: &str,
+    peer_id: &str,
+) -> Result<Permissions, String> {
+    let members = engine.get_members(community_id)?;
+    let roles = members
+        .iter()
+        .find(|m| m.peer_id == peer_id)
+        .map(|m| m.roles.clone())
+        .unwrap_or_default();
+    let is_owner = roles.iter().any(|r| r == "owner");
+    let capabilities = engine
+        .get_capabilities(community_id)?
+        .into_iter()
+        .filter(|token| token.holder == peer_id)
+        .collect();
+
+    Ok(Permissions {
+        peer_id: peer_id.to_string(),
+        is_owner,
+        is_admin: is_owner || roles.iter().any(|r| r == "admin"),
+        members,
+        capabilities,
+    })
+}
+
+impl Permissions {
+    pub fn member(&self, peer_id: &str) -> Option<&Member> {
+        self.members.iter().find(|m| m.peer_id == peer_id)
+    }
+
+    pub fn require_member(&self) -> Result<&Member, String> {
+        self.member(&self.peer_id)
+            .ok_or_else(|| "requester not found in community".to_string())
+    }
+
+    // owners and admins, for structure and settings changes
+    pub fn require_admin(&self) -> Result<(), String> {
+        self.require_member()?;
+        if !self.is_admin {
+            return Err("insufficient permissions".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn require_owner(&self) -> Result<(), String> {
+        self.require_member()?;
+        if !self.is_owner {
+            return Err("insufficient permissions".to_string());
+        }
+        Ok(())
+    }
+
+    // our longest lasting token for a capability covering the channel
+    pub fn capability(
+        &self,
+        capability: ModerationCapability,
+        channel_id: Option<&str>,
+    ) -> Option<&CapabilityToken> {
+        self.capabilities
+            .iter()
+            .filter(|token| {
+                token.capability == capability
+                    && (token.channel_id.is_none() || token.channel_id.as_deref() == channel_id)
+            })
+            .max_by_key(|token| token.expires_at)
+    }
+
+    // authors delete their own messages, anyone else needs to be the owner
+    // or hold a delete capability covering the channel. the token, if one
+    // was needed, goes out with the deletion
+    pub fn authorize_delete(
+        &self,
+        author_id: &str,
+        channel_id: &str,
+    ) -> Result<Option<CapabilityToken>, String> {
+        if author_id == self.peer_id || self.is_owner {
+            return Ok(None);
+        }
+        self.capability(ModerationCapability::DeleteMessages, Some(channel_id))
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| "not authorized to delete this message".to_string())
+    }
+
+    // admins or holders of a kick capability can kick anyone but the owner.
+    // non-owners send their token along so peers don't have to trust their
+    // copy of our roles
+    pub fn authorize_kick(&self, target_peer_id: &str) -> Result<Option<CapabilityToken>, String> {
+        self.require_member()?;
+        let capability = if self.is_owner {
+            None
+        } else {
+            self.capability(ModerationCapability::KickMembers, None)
+                .cloned()
+        };
+        if !self.is_admin && capability.is_none() {
+            return Err("not authorized to kick members".to_string());
+        }
+
+        let target = self.member(target_peer_id).ok_or("member not found")?;
+        if target.roles.iter().any(|r| r == "owner") {
+            return Err("cannot kick the community owner".to_string());
+        }
+        Ok(capability)
+    }
+
+    // admins or holders of a move capability covering the channel can take
+    // anyone but the owner out of a call, the token goes out with the move
+    pub fn authorize_voice_move(
+        &self,
+        target_peer_id: &str,
+        channel_id: &str,
+    ) -> Result<Option<CapabilityToken>, String> {
+        self.require_member()?;
+        let capability = if self.is_admin {
+            None
+        } else {
+            self.capability(ModerationCapability::MoveMembers, Some(channel_id))
+                .cloned()
+        };
+        if !self.is_admin && capability.is_none() {
+            return Err("not authorized to move voice participants".to_string());
+        }
+
+        let target = self.member(target_peer_id).ok_or("member not found")?;
+        if target.roles.iter().any(|r| r == "owner") && !self.is_owner {
+            return Err("cannot move the community owner".to_string());
+        }
+        Ok(capability)
+    }
+}
@@ -0,0 +1,206 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tauri::State;
+
+use super::ipc_log;
+use crate::node::gossip;
+use crate::node::NodeCommand;
+use crate::protocol::messages::{
+    CallDirection, CallRecord, CallStatus, DMCallSignal, DMCallSignalKind,
+};
+use crate::AppState;
+
+const DEFAULT_CALL_HISTORY_LIMIT: usize = 100;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+// hand a call signal to the node, which publishes it on the pair and inbox
+// topics and keeps the ring timeout for the call
+async fn send_call_signal(state: &AppState, signal: DMCallSignal) -> Result<(), String> {
+    let node_handle = state.node_handle.lock().await;
+    let handle = node_handle.as_ref().ok_or("node not running")?;
+    handle
+        .command_tx
+        .send(NodeCommand::SendCallSignal { signal })
+        .await
+        .map_err(|e| format!("failed to send call signal: {}", e))
+}
+
+// local peer id and display name
+async fn local_identity(state: &AppState) -> Result<(String, String), String> {
+    let identity = state.identity.lock().await;
+    let id = identity.as_ref().ok_or("no identity loaded")?;
+    Ok((id.peer_id.to_string(), id.display_name.clone()))
+}
+
+// ring a peer. media is negotiated afterwards with send_voice_sdp and
+// send_voice_ice_candidate using the "dm" community id and the call id as channel
+#[tauri::command]
+pub async fn start_dm_call(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<CallRecord, String> {
+    ipc_log!("start_dm_call", {
+        let (local_peer_id, display_name) = local_identity(&state).await?;
+        if peer_id == local_peer_id {
+            return Err("cannot call yourself".to_string());
+        }
+
+        let now = now_millis();
+        let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
+        let peer_display_name = state
+            .storage
+            .load_dm_conversation(&conversation_id)
+            .map(|m| m.display_name)
+            .ok()
+            .or_else(|| {
+                state
+                    .storage
+                    .load_directory_entry(&peer_id)
+                    .ok()
+                    .flatten()
+                    .map(|e| e.display_name)
+            })
+            .unwrap_or_else(|| peer_id.clone());
+
+        let record = CallRecord {
+            call_id: format!("call_{}_{}", local_peer_id, now),
+            peer_id: peer_id.clone(),
+            display_name: peer_display_name,
+            direction: CallDirection::Outgoing,
+            status: CallStatus::Ringing,
+            started_at: now,
+            answered_at: None,
+            ended_at: None,
+            duration_secs: 0,
+        };
+        state
+            .storage
+            .insert_call(&record)
+            .map_err(|e| format!("failed to record call: {}", e))?;
+
+        // the answer and media signaling come back on the pair topic
+        if let Some(ref handle) = *state.node_handle.lock().await {
+            for topic in gossip::topics_for_dm(&local_peer_id, &peer_id) {
+                let _ = handle
+                    .command_tx
+                    .send(NodeCommand::Subscribe { topic })
+                    .await;
+            }
+        }
+
+        send_call_signal(
+            &state,
+            DMCallSignal {
+                call_id: record.call_id.clone(),
+                from_peer: local_peer_id,
+                to_peer: peer_id,
+                from_display_name: display_name,
+                kind: DMCallSignalKind::Invite,
+                timestamp: now,
+            },
+        )
+        .await?;
+
+        Ok(record)
+    })
+}
+
+// pick up a ringing incoming call
+#[tauri::command]
+pub async fn accept_dm_call(state: State<'_, AppState>, call_id: String) -> Result<(), String> {
+    ipc_log!("accept_dm_call", {
+        let (local_peer_id, display_name) = local_identity(&state).await?;
+        let call = state
+            .storage
+            .load_call(&call_id)
+            .map_err(|e| format!("failed to load call: {}", e))?
+            .ok_or("unknown call")?;
+        if call.direction != CallDirection::Incoming {
+            return Err("only incoming calls can be accepted".to_string());
+        }
+
+        let now = now_millis();
+        let answered = state
+            .storage
+            .mark_call_answered(&call_id, now)
+            .map_err(|e| format!("failed to update call: {}", e))?;
+        if !answered {
+            return Err("call is no longer ringing".to_string());
+        }
+
+        send_call_signal(
+            &state,
+            DMCallSignal {
+                call_id,
+                from_peer: local_peer_id,
+                to_peer: call.peer_id,
+                from_display_name: display_name,
+                kind: DMCallSignalKind::Accept,
+                timestamp: now,
+            },
+        )
+        .await
+    })
+}
+
+// hang up, decline or cancel depending on where the call is
+#[tauri::command]
+pub async fn end_dm_call(state: State<'_, AppState>, call_id: String) -> Result<CallRecord, String> {
+    ipc_log!("end_dm_call", {
+        let (local_peer_id, display_name) = local_identity(&state).await?;
+        let call = state
+            .storage
+            .load_call(&call_id)
+            .map_err(|e| format!("failed to load call: {}", e))?
+            .ok_or("unknown call")?;
+
+        // a ringing call is declined or cancelled, a connected one is hung up
+        let (kind, unanswered) = match (call.answered_at, call.direction) {
+            (Some(_), _) => (DMCallSignalKind::End, CallStatus::Completed),
+            (None, CallDirection::Incoming) => (DMCallSignalKind::Decline, CallStatus::Declined),
+            (None, CallDirection::Outgoing) => (DMCallSignalKind::Cancel, CallStatus::Cancelled),
+        };
+        let now = now_millis();
+        let record = state
+            .storage
+            .finish_call(&call_id, now, unanswered)
+            .map_err(|e| format!("failed to update call: {}", e))?
+            .ok_or("call has already ended")?;
+
+        // the call is over locally even if the peer can't be reached
+        let _ = send_call_signal(
+            &state,
+            DMCallSignal {
+                call_id,
+                from_peer: local_peer_id,
+                to_peer: call.peer_id,
+                from_display_name: display_name,
+                kind,
+                timestamp: now,
+            },
+        )
+        .await;
+
+        Ok(record)
+    })
+}
+
+// recent calls, newest first
+#[tauri::command]
+pub async fn get_call_history(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<CallRecord>, String> {
+    ipc_log!("get_call_history", {
+        state
+            .storage
+            .load_call_history(limit.unwrap_or(DEFAULT_CALL_HISTORY_LIMIT))
+            .map_err(|e| format!("failed to load call history: {}", e))
+    })
+}
@@ -1,18 +1,25 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use tauri::State;
-use tokio::net::TcpStream;
+use serde::Serialize;
+use tauri::{Emitter, Manager, State};
 use tokio::time::{timeout, Duration};
 
+use crate::boot::{self, BootPhase};
+use crate::latency::LatencyStats;
+use crate::node::announce::AnnounceTrigger;
+use crate::node::connections::ConnectionReport;
 use crate::node::gossip;
-use crate::node::{self, NodeCommand};
+use crate::node::scoring::PeerScore;
+use crate::node::watchdog;
+use crate::node::{self, DuskEvent, NodeCommand};
+use crate::protocol::community::ChannelKind;
 use crate::protocol::messages::{
-    ChatMessage, GossipMessage, PeerStatus, ProfileAnnouncement, TypingIndicator,
+    validate_attachments, Attachment, ChatMessage, DMConversationMeta, Embed, GossipMessage,
+    MessageThread, PeerStatus, ThreadSummary, TypingIndicator,
 };
-use crate::verification;
 use crate::AppState;
 
-use super::ipc_log;
+use super::{authz, ipc_log};
 
 #[tauri::command]
 pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
@@ -29,32 +36,16 @@ pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Re
             .ok()
             .and_then(|s| s.custom_relay_addr);
 
-        let handle = node::start(
-            id.keypair.clone(),
-            state.crdt_engine.clone(),
-            state.storage.clone(),
-            app,
-            state.voice_channels.clone(),
-            state.pending_join_role_guard.clone(),
-            custom_relay,
-        )
-        .await?;
-
-        // capture profile info for announcement before dropping identity lock
-        let mut profile_announcement = ProfileAnnouncement {
-            peer_id: id.peer_id.to_string(),
-            display_name: id.display_name.clone(),
-            bio: id.bio.clone(),
-            public_key: hex::encode(id.keypair.public().encode_protobuf()),
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as u64,
-            verification_proof: id.verification_proof.clone(),
-            signature: String::new(),
+        boot::set_phase(&app, BootPhase::Node).await;
+        let shared = node::SharedState {
+            crdt_engine: state.crdt_engine.clone(),
+            storage: state.storage.clone(),
+            voice_channels: state.voice_channels.clone(),
+            hlc_clock: state.hlc_clock.clone(),
+            cover_traffic: state.cover_traffic.clone(),
+            latency: state.latency.clone(),
         };
-        profile_announcement.signature =
-            verification::sign_announcement(&id.keypair, &profile_announcement);
+        let handle = node::start(id.keypair.clone(), shared, app.clone(), custom_relay).await?;
         drop(identity);
 
         {
@@ -62,33 +53,31 @@ pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Re
             *node_handle = Some(handle);
         }
 
-        // subscribe to the global sync topic for document exchange
-        let sync_topic = gossip::topic_for_sync();
         let directory_topic = gossip::topic_for_directory();
         let handle_ref = state.node_handle.lock().await;
         if let Some(ref handle) = *handle_ref {
+            // subscribe to the directory topic for peer profile announcements
             let _ = handle
                 .command_tx
-                .send(NodeCommand::Subscribe { topic: sync_topic })
+                .send(NodeCommand::Subscribe {
+                    topic: directory_topic,
+                })
                 .await;
 
-            // subscribe to the directory topic for peer profile announcements
+            // announce our profile on the directory topics
             let _ = handle
                 .command_tx
-                .send(NodeCommand::Subscribe {
-                    topic: directory_topic.clone(),
+                .send(NodeCommand::AnnounceProfile {
+                    trigger: AnnounceTrigger::Startup,
                 })
                 .await;
 
-            // announce our profile on the directory topic
-            let announce_msg = GossipMessage::ProfileAnnounce(profile_announcement);
-            if let Ok(data) = serde_json::to_vec(&announce_msg) {
+            // carry rich presence over a node restart
+            let activity = state.activity.lock().await.clone();
+            if activity.is_some() {
                 let _ = handle
                     .command_tx
-                    .send(NodeCommand::SendMessage {
-                        topic: directory_topic,
-                        data,
-                    })
+                    .send(NodeCommand::SetActivity { activity })
                     .await;
             }
         }
@@ -129,6 +118,28 @@ pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Re
                     })
                     .await;
 
+                let _ = handle
+                    .command_tx
+                    .send(NodeCommand::Subscribe {
+                        topic: gossip::topic_for_notes(community_id),
+                    })
+                    .await;
+
+                let _ = handle
+                    .command_tx
+                    .send(NodeCommand::Subscribe {
+                        topic: gossip::topic_for_community_directory(community_id),
+                    })
+                    .await;
+
+                // document exchange for this community
+                let _ = handle
+                    .command_tx
+                    .send(NodeCommand::Subscribe {
+                        topic: gossip::topic_for_community_sync(community_id),
+                    })
+                    .await;
+
                 // register on rendezvous for each community so other peers can find us
                 let namespace = format!("dusk/community/{}", community_id);
                 let _ = handle
@@ -143,7 +154,8 @@ pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Re
                     .await;
             }
 
-            // subscribe to all existing dm conversation topics
+            // subscribe to the dm topics of every conversation and friend up
+            // front, so their first message arrives on the pair topic
             let local_peer_str = {
                 let identity = state.identity.lock().await;
                 identity
@@ -151,13 +163,14 @@ pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Re
                     .map(|i| i.peer_id.to_string())
                     .unwrap_or_default()
             };
-            if let Ok(conversations) = state.storage.load_all_dm_conversations() {
-                for (_, meta) in &conversations {
-                    let dm_topic = gossip::topic_for_dm(&local_peer_str, &meta.peer_id);
-                    let _ = handle
-                        .command_tx
-                        .send(NodeCommand::Subscribe { topic: dm_topic })
-                        .await;
+            if let Ok(peer_ids) = state.storage.load_dm_peer_ids() {
+                for peer_id in &peer_ids {
+                    for dm_topic in gossip::topics_for_dm(&local_peer_str, peer_id) {
+                        let _ = handle
+                            .command_tx
+                            .send(NodeCommand::Subscribe { topic: dm_topic })
+                            .await;
+                    }
                 }
             }
 
@@ -178,13 +191,14 @@ pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Re
                 })
                 .await;
 
-            // register under the global "dusk/peers" namespace so any peer can
-            // discover us via the relay tracker, enabling global peer discovery
-            // without exposing ip addresses (all connections use relay circuit)
+            // register under the global namespace so any peer can discover us
+            // via the relay tracker, enabling global peer discovery without
+            // exposing ip addresses (all connections use relay circuit). the
+            // node drops this while relay_discoverable is off
             let _ = handle
                 .command_tx
                 .send(NodeCommand::RegisterRendezvous {
-                    namespace: "dusk/peers".to_string(),
+                    namespace: crate::node::GLOBAL_PEERS_NAMESPACE.to_string(),
                 })
                 .await;
 
@@ -207,8 +221,8 @@ pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Re
                 .await;
 
             // start wandering rendezvous discovery for all friends
-            if let Ok(entries) = state.storage.load_directory() {
-                for peer in entries.values().filter(|e| e.is_friend) {
+            if let Ok(friends) = state.storage.load_friends() {
+                for peer in &friends {
                     let discover_ns = format!("dusk/peer/{}", peer.peer_id);
                     let _ = handle
                         .command_tx
@@ -219,13 +233,15 @@ pub async fn start_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Re
                 }
             }
         }
+        drop(handle_ref);
 
+        boot::set_phase(&app, BootPhase::Ready).await;
         Ok(())
     })
 }
 
 #[tauri::command]
-pub async fn stop_node(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn stop_node(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
     ipc_log!("stop_node", {
         let mut node_handle = state.node_handle.lock().await;
 
@@ -240,7 +256,9 @@ pub async fn stop_node(state: State<'_, AppState>) -> Result<(), String> {
             let _ = handle.command_tx.send(NodeCommand::Shutdown).await;
             let _ = handle.task.await;
         }
+        drop(node_handle);
 
+        boot::set_phase(&app, BootPhase::Node).await;
         Ok(())
     })
 }
@@ -250,61 +268,347 @@ pub async fn send_message(
     state: State<'_, AppState>,
     channel_id: String,
     content: String,
+    attachments: Option<Vec<Attachment>>,
+    embeds: Option<Vec<Embed>>,
+    reply_to: Option<String>,
+    idempotency_key: Option<String>,
 ) -> Result<ChatMessage, String> {
     ipc_log!("send_message", {
-        let identity = state.identity.lock().await;
-        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let claim = state.idempotency.claim("send_message", idempotency_key).await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        let started = Instant::now();
+        let msg = new_message(&state, channel_id, content, attachments, embeds, reply_to).await?;
+        let msg = commit_message(&state, msg, started).await?;
+        claim.complete(&msg);
+        Ok(msg)
+    })
+}
 
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+// local echo: returns the message before it is persisted or published, both
+// happen on a background task that reports back with message_committed,
+// carrying the message as stored, or message_failed. the ui shows it as
+// pending until then
+#[tauri::command]
+pub async fn send_message_optimistic(
+    app: tauri::AppHandle,
+    channel_id: String,
+    content: String,
+    attachments: Option<Vec<Attachment>>,
+    embeds: Option<Vec<Embed>>,
+    reply_to: Option<String>,
+    idempotency_key: Option<String>,
+) -> Result<ChatMessage, String> {
+    ipc_log!("send_message_optimistic", {
+        let state = app.state::<AppState>();
+        let claim = state
+            .idempotency
+            .claim("send_message_optimistic", idempotency_key)
+            .await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        let started = Instant::now();
+        let msg = new_message(&state, channel_id, content, attachments, embeds, reply_to).await?;
+        claim.complete(&msg);
+
+        let pending = msg.clone();
+        tauri::async_runtime::spawn(async move {
+            let state = app.state::<AppState>();
+            let message_id = pending.id.clone();
+            let channel_id = pending.channel_id.clone();
+            let event = match commit_message(&state, pending, started).await {
+                Ok(msg) => DuskEvent::MessageCommitted(msg),
+                Err(error) => DuskEvent::MessageFailed {
+                    message_id,
+                    channel_id,
+                    error,
+                },
+            };
+            let _ = app.emit("dusk-event", event);
+        });
 
-        let msg = ChatMessage {
-            id: format!("msg_{}_{}", id.peer_id, now),
-            channel_id: channel_id.clone(),
-            author_id: id.peer_id.to_string(),
-            author_name: id.display_name.clone(),
-            content,
-            timestamp: now,
-            edited: false,
-        };
+        Ok(msg)
+    })
+}
 
-        // figure out which community this channel belongs to
-        let mut engine = state.crdt_engine.lock().await;
-        let community_id = find_community_for_channel(&engine, &channel_id)?;
+// stamp a new message from us, nothing is stored or sent yet
+async fn new_message(
+    state: &AppState,
+    channel_id: String,
+    content: String,
+    attachments: Option<Vec<Attachment>>,
+    embeds: Option<Vec<Embed>>,
+    reply_to: Option<String>,
+) -> Result<ChatMessage, String> {
+    let attachments = attachments.unwrap_or_default();
+    let embeds = embeds.unwrap_or_default();
+    validate_attachments(&attachments, &embeds)?;
+
+    // replies point at the thread's first message, a reply to a reply joins
+    // the thread it is in
+    let reply_to = match reply_to {
+        Some(target_id) => {
+            let engine = state.crdt_engine.lock().await;
+            let community_id = find_community_for_channel(&engine, &channel_id)?;
+            let target = engine
+                .get_message(&community_id, &target_id)?
+                .filter(|m| m.channel_id == channel_id)
+                .ok_or("the message being replied to isn't in this channel")?;
+            Some(target.reply_to.unwrap_or(target.id))
+        }
+        None => None,
+    };
+
+    let identity = state.identity.lock().await;
+    let id = identity.as_ref().ok_or("no identity loaded")?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let hlc = state.hlc_clock.lock().await.now();
+
+    Ok(ChatMessage {
+        id: format!("msg_{}_{}", id.peer_id, now),
+        channel_id,
+        author_id: id.peer_id.to_string(),
+        author_name: id.display_name.clone(),
+        content,
+        timestamp: now,
+        edited: false,
+        hlc,
+        forwarded_from: None,
+        attachments,
+        embeds,
+        reactions: Vec::new(),
+        reply_to,
+    })
+}
 
-        engine.append_message(&community_id, &msg)?;
-        drop(engine);
+// write a message to its community doc and publish it, returns it as stored.
+// started is when the send command was called, for latency stats
+async fn commit_message(
+    state: &AppState,
+    mut msg: ChatMessage,
+    started: Instant,
+) -> Result<ChatMessage, String> {
+    // figure out which community this channel belongs to
+    let mut engine = state.crdt_engine.lock().await;
+    let community_id = find_community_for_channel(&engine, &msg.channel_id)?;
+
+    // announcement channels are read-only for regular members
+    let is_announcement = engine
+        .get_channels(&community_id)?
+        .iter()
+        .any(|ch| ch.id == msg.channel_id && matches!(ch.kind, ChannelKind::Announcement));
+    if is_announcement && !authz::resolve_with(&engine, &community_id, &msg.author_id)?.is_admin {
+        return Err("only owners and admins can post in announcement channels".to_string());
+    }
 
-        // publish to gossipsub
-        let node_handle = state.node_handle.lock().await;
-        if let Some(ref handle) = *node_handle {
-            let topic = gossip::topic_for_messages(&community_id, &channel_id);
-            let data = serde_json::to_vec(&GossipMessage::Chat(msg.clone()))
-                .map_err(|e| format!("serialize error: {}", e))?;
+    // post under the per-community name when one is set
+    msg.author_name = state
+        .storage
+        .community_display_name(&community_id, &msg.author_id, &msg.author_name);
+
+    engine.append_message(&community_id, &msg)?;
+    drop(engine);
+
+    // publish to gossipsub
+    let node_handle = state.node_handle.lock().await;
+    if let Some(ref handle) = *node_handle {
+        let topic = gossip::topic_for_messages(&community_id, &msg.channel_id);
+        let data = serde_json::to_vec(&GossipMessage::Chat(msg.clone()))
+            .map_err(|e| format!("serialize error: {}", e))?;
 
+        // members that only follow channels they use get mentions here
+        if msg.content.contains("<@") {
             let _ = handle
                 .command_tx
-                .send(NodeCommand::SendMessage { topic, data })
+                .send(NodeCommand::SendMessage {
+                    topic: gossip::topic_for_mentions(&community_id),
+                    data: data.clone(),
+                })
                 .await;
         }
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::SendMessage { topic, data })
+            .await;
+    }
+    state.latency.record_commit(&msg.id, started);
 
-        Ok(msg)
-    })
+    Ok(msg)
+}
+
+// how long our channel messages took to be stored and to reach other
+// members this session
+#[tauri::command]
+pub async fn get_latency_stats(state: State<'_, AppState>) -> Result<LatencyStats, String> {
+    ipc_log!("get_latency_stats", { Ok(state.latency.stats()) })
 }
 
 #[tauri::command]
 pub async fn get_messages(
-    state: State<'_, AppState>,
+    app: tauri::AppHandle,
     channel_id: String,
     before: Option<u64>,
     limit: Option<usize>,
 ) -> Result<Vec<ChatMessage>, String> {
     ipc_log!("get_messages", {
+        let state = app.state::<AppState>();
+        let limit = limit.unwrap_or(50);
+
         let engine = state.crdt_engine.lock().await;
         let community_id = find_community_for_channel(&engine, &channel_id)?;
-        engine.get_messages(&community_id, &channel_id, before, limit.unwrap_or(50))
+        let local = engine.get_messages(&community_id, &channel_id, before, limit)?;
+        drop(engine);
+
+        if local.len() >= limit {
+            return Ok(local);
+        }
+        // the local copy is sparse (e.g. just joined), backfill older messages
+        // from an online member instead of waiting for a full document merge.
+        // this runs once per page in the background, the ui reloads when
+        // history_backfilled arrives
+        let cursor = local.first().map(|m| m.timestamp).or(before);
+        let page = (channel_id.clone(), cursor);
+        let first_read = state.backfilled_channels.lock().await.insert(page.clone());
+        if !first_read {
+            return Ok(local);
+        }
+        let wanted = (limit - local.len()) as u32;
+        tauri::async_runtime::spawn(async move {
+            let state = app.state::<AppState>();
+            match backfill_history(&state, &community_id, &channel_id, cursor, wanted).await {
+                Some(0) => {}
+                Some(_) => {
+                    let _ = app.emit(
+                        "dusk-event",
+                        DuskEvent::HistoryBackfilled {
+                            community_id,
+                            channel_id,
+                        },
+                    );
+                }
+                // nobody answered, let a later read try again
+                None => {
+                    state.backfilled_channels.lock().await.remove(&page);
+                }
+            }
+        });
+
+        Ok(local)
+    })
+}
+
+// fetch a page of history older than cursor from an online member and store
+// it. returns how many messages were new, none if the fetch failed
+async fn backfill_history(
+    state: &AppState,
+    community_id: &str,
+    channel_id: &str,
+    cursor: Option<u64>,
+    limit: u32,
+) -> Option<usize> {
+    let command_tx = state.node_handle.lock().await.as_ref()?.command_tx.clone();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    command_tx
+        .send(NodeCommand::FetchHistory {
+            community_id: community_id.to_string(),
+            channel_id: channel_id.to_string(),
+            before: cursor,
+            limit,
+            reply: tx,
+        })
+        .await
+        .ok()?;
+
+    let fetched = match timeout(Duration::from_secs(15), rx).await {
+        Ok(Ok(Ok(messages))) => messages,
+        _ => return None,
+    };
+
+    // duplicates are dropped by append_message
+    let mut engine = state.crdt_engine.lock().await;
+    let mut added = 0;
+    for message in fetched.iter().filter(|m| m.channel_id == channel_id) {
+        if let Ok(true) = engine.append_message(community_id, message) {
+            added += 1;
+        }
+    }
+    Some(added)
+}
+
+// the user opened a channel. marks it as of interest so interest-based
+// subscriptions follow it, and in that mode drops channels left unopened
+// for longer than gossip::CHANNEL_INTEREST_SECS
+#[tauri::command]
+pub async fn open_channel(
+    state: State<'_, AppState>,
+    community_id: String,
+    channel_id: String,
+) -> Result<(), String> {
+    ipc_log!("open_channel", {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        state
+            .storage
+            .record_channel_opened(&community_id, &channel_id, now)
+            .map_err(|e| format!("failed to record channel interest: {}", e))?;
+
+        let interest_mode = state
+            .storage
+            .load_settings()
+            .map(|s| s.interest_subscriptions)
+            .unwrap_or(false);
+        let stale = if interest_mode {
+            state
+                .storage
+                .forget_channels_opened_before(
+                    now.saturating_sub(gossip::CHANNEL_INTEREST_SECS * 1000),
+                )
+                .map_err(|e| format!("failed to expire channel interest: {}", e))?
+        } else {
+            Vec::new()
+        };
+
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            // subscribing again is a no-op unless the channel was skipped
+            for topic in [
+                gossip::topic_for_messages(&community_id, &channel_id),
+                gossip::topic_for_typing(&community_id, &channel_id),
+            ] {
+                let _ = handle
+                    .command_tx
+                    .send(NodeCommand::Subscribe { topic })
+                    .await;
+            }
+            for (stale_community, stale_channel) in stale {
+                for topic in [
+                    gossip::topic_for_messages(&stale_community, &stale_channel),
+                    gossip::topic_for_typing(&stale_community, &stale_channel),
+                ] {
+                    let _ = handle
+                        .command_tx
+                        .send(NodeCommand::Unsubscribe { topic })
+                        .await;
+                }
+                // mentions from the dropped channel keep coming in
+                let _ = handle
+                    .command_tx
+                    .send(NodeCommand::Subscribe {
+                        topic: gossip::topic_for_mentions(&stale_community),
+                    })
+                    .await;
+            }
+        }
+
+        Ok(())
     })
 }
 
@@ -372,6 +676,33 @@ pub async fn broadcast_presence(state: State<'_, AppState>, status: String) -> R
     })
 }
 
+// a message and all replies to it
+#[tauri::command]
+pub async fn get_thread(
+    state: State<'_, AppState>,
+    channel_id: String,
+    root_id: String,
+) -> Result<MessageThread, String> {
+    ipc_log!("get_thread", {
+        let engine = state.crdt_engine.lock().await;
+        let community_id = find_community_for_channel(&engine, &channel_id)?;
+        engine.get_thread(&community_id, &channel_id, &root_id)
+    })
+}
+
+// reply counts for every message in the channel that has replies
+#[tauri::command]
+pub async fn get_thread_summaries(
+    state: State<'_, AppState>,
+    channel_id: String,
+) -> Result<Vec<ThreadSummary>, String> {
+    ipc_log!("get_thread_summaries", {
+        let engine = state.crdt_engine.lock().await;
+        let community_id = find_community_for_channel(&engine, &channel_id)?;
+        engine.get_thread_summaries(&community_id, &channel_id)
+    })
+}
+
 // find which community a channel belongs to by checking all loaded documents
 fn find_community_for_channel(
     engine: &crate::crdt::CrdtEngine,
@@ -395,24 +726,156 @@ fn find_community_for_channel(
 #[tauri::command]
 pub async fn check_internet_connectivity() -> Result<bool, String> {
     ipc_log!("check_internet_connectivity", {
-        let hosts = vec![
-            ("www.apple.com", 80),
-            ("www.google.com", 80),
-            ("www.yahoo.com", 80),
-        ];
+        Ok(watchdog::probe_internet().await)
+    })
+}
+
+// per-peer gossipsub scores, lets the ui show which peers are being pruned
+#[tauri::command]
+pub async fn get_peer_scores(state: State<'_, AppState>) -> Result<Vec<PeerScore>, String> {
+    ipc_log!("get_peer_scores", {
+        let node_handle = state.node_handle.lock().await;
+        let handle = node_handle.as_ref().ok_or("node not running")?;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        handle
+            .command_tx
+            .send(NodeCommand::GetPeerScores { reply: tx })
+            .await
+            .map_err(|e| format!("failed to query peer scores: {}", e))?;
+        drop(node_handle);
+
+        rx.await
+            .map_err(|e| format!("failed to receive peer scores: {}", e))
+    })
+}
+
+// connection counts by priority and the peers dropped to stay under the cap
+#[tauri::command]
+pub async fn get_connection_report(state: State<'_, AppState>) -> Result<ConnectionReport, String> {
+    ipc_log!("get_connection_report", {
+        let node_handle = state.node_handle.lock().await;
+        let handle = node_handle.as_ref().ok_or("node not running")?;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        handle
+            .command_tx
+            .send(NodeCommand::GetConnectionReport { reply: tx })
+            .await
+            .map_err(|e| format!("failed to query connections: {}", e))?;
+        drop(node_handle);
+
+        rx.await
+            .map_err(|e| format!("failed to receive connection report: {}", e))
+    })
+}
+
+// how long sync_on_foreground waits for catch-up replies before summarizing
+const FOREGROUND_SYNC_WAIT_MS: u64 = 3000;
+// newest messages scanned per channel when looking for missed mentions
+const FOREGROUND_MENTION_SCAN: usize = 200;
+
+// what arrived while the app was in the background
+#[derive(Debug, Clone, Serialize)]
+pub struct ForegroundSync {
+    pub background_secs: u64,
+    pub unread_dms: u32,
+    pub dm_conversations: Vec<DMConversationMeta>,
+    pub mentions: Vec<ChatMessage>,
+}
+
+// mobile only: the os suspends backgrounded apps, so drop to a relay-only
+// connection instead of letting the whole node die. push-style wake needs a
+// relay mailbox, which does not exist yet, so missed traffic is pulled from
+// peer caches when the app resumes
+#[tauri::command]
+pub async fn enter_background_mode(state: State<'_, AppState>) -> Result<(), String> {
+    ipc_log!("enter_background_mode", {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        *state.backgrounded_at.lock().await = Some(now);
+
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            handle
+                .command_tx
+                .send(NodeCommand::SetBackgroundMode { enabled: true })
+                .await
+                .map_err(|e| format!("failed to enter background mode: {}", e))?;
+        }
+        Ok(())
+    })
+}
+
+// leave background mode and report unread dms and mentions missed while away
+#[tauri::command]
+pub async fn sync_on_foreground(state: State<'_, AppState>) -> Result<ForegroundSync, String> {
+    ipc_log!("sync_on_foreground", {
+        let backgrounded_at = state.backgrounded_at.lock().await.take();
 
-        let connect_timeout = Duration::from_secs(5);
+        let node_handle = state.node_handle.lock().await;
+        let node_running = node_handle.is_some();
+        if let Some(ref handle) = *node_handle {
+            handle
+                .command_tx
+                .send(NodeCommand::SetBackgroundMode { enabled: false })
+                .await
+                .map_err(|e| format!("failed to leave background mode: {}", e))?;
+        }
+        drop(node_handle);
+
+        // give catch-up responses a moment to land before reading local state
+        if node_running {
+            tokio::time::sleep(Duration::from_millis(FOREGROUND_SYNC_WAIT_MS)).await;
+        }
 
-        let futures: Vec<_> = hosts
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let since = backgrounded_at.unwrap_or(now);
+
+        let dm_conversations: Vec<DMConversationMeta> = state
+            .storage
+            .load_all_dm_conversations(true)
+            .map_err(|e| format!("failed to load dm conversations: {}", e))?
             .into_iter()
-            .map(|(host, port)| {
-                let addr = format!("{}:{}", host, port);
-                timeout(connect_timeout, TcpStream::connect(addr))
-            })
+            .map(|(_, meta)| meta)
+            .filter(|meta| meta.unread_count > 0)
             .collect();
+        let unread_dms = dm_conversations.iter().map(|m| m.unread_count).sum();
 
-        let results = futures::future::join_all(futures).await;
-
-        Ok(results.iter().any(|r| matches!(r, Ok(Ok(_)))))
+        let local_peer_id = {
+            let identity = state.identity.lock().await;
+            identity.as_ref().map(|i| i.peer_id.to_string())
+        };
+        let mut mentions = Vec::new();
+        if let Some(local_peer_id) = local_peer_id {
+            let direct = format!("<@{}>", local_peer_id);
+            let engine = state.crdt_engine.lock().await;
+            for community_id in engine.community_ids() {
+                let channels = engine.get_channels(&community_id).unwrap_or_default();
+                for channel in channels {
+                    let messages = engine
+                        .get_messages(&community_id, &channel.id, None, FOREGROUND_MENTION_SCAN)
+                        .unwrap_or_default();
+                    mentions.extend(messages.into_iter().filter(|m| {
+                        m.timestamp > since
+                            && m.author_id != local_peer_id
+                            && (m.content.contains(&direct) || m.content.contains("<@everyone>"))
+                    }));
+                }
+            }
+        }
+        mentions.sort_by_key(|m| m.timestamp);
+
+        Ok(ForegroundSync {
+            background_secs: now.saturating_sub(since) / 1000,
+            unread_dms,
+            dm_conversations,
+            mentions,
+        })
     })
 }
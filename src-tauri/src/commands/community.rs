@@ -3,72 +3,75 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use sha2::{Digest, Sha256};
 use tauri::State;
 
-use super::ipc_log;
-use crate::crdt::sync::{DocumentSnapshot, SyncMessage};
+use super::{authz, ipc_log};
+use crate::catalog::Catalog;
+use crate::crdt::sync::SyncMessage;
+use crate::crdt::AppliedDeletion;
 use crate::node::gossip;
 use crate::node::NodeCommand;
-use crate::protocol::community::{CategoryMeta, ChannelKind, ChannelMeta, CommunityMeta, Member};
-use crate::protocol::messages::PeerStatus;
+use crate::protocol::community::{
+    CategoryMeta, ChannelFollow, ChannelKind, ChannelMeta, CommunityMeta, CommunityProfile,
+    DocConflict, Member, MembershipAction, MembershipEvent, MembershipLogEntry,
+    INVITE_PROOF_TTL_MS, MAX_COMMUNITY_AVATAR_SEED_LEN, MAX_COMMUNITY_DISPLAY_NAME_LEN,
+};
+use crate::protocol::identity::VerificationPolicy;
+use crate::protocol::messages::{
+    ChatMessage, MessageReaction, MessageRevision, PeerStatus, MAX_REACTION_LEN,
+};
 use crate::AppState;
 
-// check if the requester has one of the required roles in the community
-fn check_permission(
-    members: &[Member],
-    requester_id: &str,
-    required_roles: &[&str],
+// sign a membership event as the local identity and append it to the
+// community's membership log
+async fn record_membership(
+    state: &State<'_, AppState>,
+    community_id: &str,
+    action: MembershipAction,
+    peer_id: &str,
+    roles: &[String],
+    display_name: &str,
+    timestamp: u64,
 ) -> Result<(), String> {
-    let requester = members
-        .iter()
-        .find(|m| m.peer_id == requester_id)
-        .ok_or("requester not found in community")?;
-
-    let has_permission = requester
-        .roles
-        .iter()
-        .any(|r| required_roles.contains(&r.as_str()));
-
-    if !has_permission {
-        return Err("insufficient permissions".to_string());
-    }
+    let event = {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        crate::verification::sign_membership_event(
+            &id.keypair,
+            community_id,
+            action,
+            peer_id,
+            roles,
+            display_name,
+            timestamp,
+        )
+    };
+    let mut engine = state.crdt_engine.lock().await;
+    engine.record_membership_event(community_id, &event)
+}
 
-    Ok(())
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
 }
 
-// helper to broadcast a crdt change to peers via the sync topic
+// helper to hand a crdt change to the community's connected members. the
+// node sends the doc to each of them directly, never on the sync topic
 pub(super) async fn broadcast_sync(state: &State<'_, AppState>, community_id: &str) {
-    let doc_bytes = {
-        let mut engine = state.crdt_engine.lock().await;
-        engine.get_doc_bytes(community_id)
-    };
-
-    let Some(doc_bytes) = doc_bytes else {
-        return;
-    };
-
-    let sync_msg = SyncMessage::DocumentOffer(DocumentSnapshot {
-        community_id: community_id.to_string(),
-        doc_bytes,
-    });
-
-    let data = match serde_json::to_vec(&sync_msg) {
-        Ok(data) => data,
-        Err(_) => return,
-    };
-
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
         let _ = handle
             .command_tx
-            .send(NodeCommand::SendMessage {
-                topic: gossip::topic_for_sync(),
-                data,
+            .send(NodeCommand::PushSnapshot {
+                community_id: community_id.to_string(),
             })
             .await;
     }
 }
 
-// request a full sync from currently connected peers
-async fn request_sync(state: &State<'_, AppState>) {
+// request the community's doc from members currently connected, presenting
+// our invite if we have not merged a snapshot yet
+async fn request_sync(state: &State<'_, AppState>, community_id: &str) {
     let peer_id = {
         let identity = state.identity.lock().await;
         let Some(id) = identity.as_ref() else {
@@ -76,8 +79,13 @@ async fn request_sync(state: &State<'_, AppState>) {
         };
         id.peer_id.to_string()
     };
+    let invite = state.crdt_engine.lock().await.join_invite(community_id);
 
-    let sync_msg = SyncMessage::RequestSync { peer_id };
+    let sync_msg = SyncMessage::RequestSync {
+        peer_id,
+        community_id: community_id.to_string(),
+        invite,
+    };
     let data = match serde_json::to_vec(&sync_msg) {
         Ok(data) => data,
         Err(_) => return,
@@ -88,7 +96,7 @@ async fn request_sync(state: &State<'_, AppState>) {
         let _ = handle
             .command_tx
             .send(NodeCommand::SendMessage {
-                topic: gossip::topic_for_sync(),
+                topic: gossip::topic_for_community_sync(community_id),
                 data,
             })
             .await;
@@ -100,8 +108,13 @@ pub async fn create_community(
     state: State<'_, AppState>,
     name: String,
     description: String,
+    idempotency_key: Option<String>,
 ) -> Result<CommunityMeta, String> {
     ipc_log!("create_community", {
+        let claim = state.idempotency.claim("create_community", idempotency_key).await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
         let identity = state.identity.lock().await;
         let id = identity.as_ref().ok_or("no identity loaded")?;
 
@@ -131,6 +144,27 @@ pub async fn create_community(
         let _ = state.storage.save_community_meta(&meta);
         drop(engine);
 
+        // the founder's join opens the membership log with the owner role
+        record_membership(
+            &state,
+            &community_id,
+            MembershipAction::Join,
+            &peer_id_str,
+            &["owner".to_string()],
+            &display_name,
+            now,
+        )
+        .await?;
+        // joiners check the first snapshot they get against an owner checkpoint
+        if let Some(id) = state.identity.lock().await.as_ref() {
+            state
+                .crdt_engine
+                .lock()
+                .await
+                .create_checkpoint(&community_id, &id.keypair);
+        }
+        claim.complete(&meta);
+
         // subscribe to community topics on the p2p node
         let node_handle = state.node_handle.lock().await;
         if let Some(ref handle) = *node_handle {
@@ -142,6 +176,27 @@ pub async fn create_community(
                 })
                 .await;
 
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Subscribe {
+                    topic: gossip::topic_for_notes(&community_id),
+                })
+                .await;
+
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Subscribe {
+                    topic: gossip::topic_for_community_directory(&community_id),
+                })
+                .await;
+
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Subscribe {
+                    topic: gossip::topic_for_community_sync(&community_id),
+                })
+                .await;
+
             // subscribe to the default general channel
             let engine = state.crdt_engine.lock().await;
             if let Ok(channels) = engine.get_channels(&community_id) {
@@ -191,6 +246,8 @@ pub async fn join_community(
         // create a placeholder document that will be backfilled via crdt sync
         // once we connect to existing community members through the relay
         let mut engine = state.crdt_engine.lock().await;
+        // rejoining a community we archived picks its history back up
+        engine.unarchive_community(&invite.community_id)?;
         let had_existing_doc = engine.has_community(&invite.community_id);
         if !had_existing_doc {
             engine.create_placeholder_community(
@@ -198,6 +255,10 @@ pub async fn join_community(
                 &invite.community_name,
                 "",
             )?;
+            // only merge a first snapshot that chains back to an owner checkpoint
+            engine.expect_owner(&invite.community_id, &invite.owner_id);
+            // members only serve the doc to a non-member showing a signed invite
+            engine.hold_join_invite(&invite);
         }
 
         // add ourselves as a member so other peers see us after crdt merge
@@ -208,24 +269,6 @@ pub async fn join_community(
             &["member"],
         )?;
 
-        // joining via invite must never keep elevated local roles from stale local docs
-        if had_existing_doc {
-            if let Ok(members) = engine.get_members(&invite.community_id) {
-                let local_has_elevated_role = members.iter().any(|member| {
-                    member.peer_id == local_peer_id
-                        && member
-                            .roles
-                            .iter()
-                            .any(|role| role == "owner" || role == "admin")
-                });
-
-                if local_has_elevated_role {
-                    let roles = vec!["member".to_string()];
-                    let _ = engine.set_member_role(&invite.community_id, &local_peer_id, &roles);
-                }
-            }
-        }
-
         let meta = engine.get_community_meta(&invite.community_id)?;
         let _ = state.storage.save_community_meta(&meta);
 
@@ -235,11 +278,18 @@ pub async fn join_community(
             .unwrap_or_default();
         drop(engine);
 
-        // mark this community for one-time role hardening on first sync merge
-        {
-            let mut guard = state.pending_join_role_guard.lock().await;
-            guard.insert(invite.community_id.clone());
-        }
+        // a signed join resets us to a plain member on every peer, so roles
+        // from an earlier membership can't come back with an old doc
+        record_membership(
+            &state,
+            &invite.community_id,
+            MembershipAction::Join,
+            &local_peer_id,
+            &["member".to_string()],
+            &local_display_name,
+            now_millis(),
+        )
+        .await?;
 
         let node_handle = state.node_handle.lock().await;
         if let Some(ref handle) = *node_handle {
@@ -252,6 +302,27 @@ pub async fn join_community(
                 })
                 .await;
 
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Subscribe {
+                    topic: gossip::topic_for_notes(&invite.community_id),
+                })
+                .await;
+
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Subscribe {
+                    topic: gossip::topic_for_community_directory(&invite.community_id),
+                })
+                .await;
+
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Subscribe {
+                    topic: gossip::topic_for_community_sync(&invite.community_id),
+                })
+                .await;
+
             // subscribe to all channel topics
             for channel in &channels {
                 let msg_topic = gossip::topic_for_messages(&invite.community_id, &channel.id);
@@ -290,16 +361,20 @@ pub async fn join_community(
         drop(node_handle);
 
         // request a snapshot now so joins work even when peers were already connected
-        request_sync(&state).await;
+        request_sync(&state, &invite.community_id).await;
 
         Ok(meta)
     })
 }
 
+// leave a community. with keep_history the doc stays on disk as a read-only
+// archive, otherwise the doc, meta and all local state for it are removed.
+// either way we drop out of the member list and stop syncing it
 #[tauri::command]
 pub async fn leave_community(
     state: State<'_, AppState>,
     community_id: String,
+    keep_history: Option<bool>,
 ) -> Result<(), String> {
     ipc_log!("leave_community", {
         let local_peer_id = {
@@ -326,6 +401,20 @@ pub async fn leave_community(
         };
 
         if removed_self {
+            // the signed leave keeps us out even if a peer merges an old member entry
+            if let Err(e) = record_membership(
+                &state,
+                &community_id,
+                MembershipAction::Leave,
+                &local_peer_id,
+                &[],
+                "",
+                now_millis(),
+            )
+            .await
+            {
+                log::warn!("failed to record leave for {}: {}", community_id, e);
+            }
             broadcast_sync(&state, &community_id).await;
         }
 
@@ -356,6 +445,27 @@ pub async fn leave_community(
                 })
                 .await;
 
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Unsubscribe {
+                    topic: gossip::topic_for_notes(&community_id),
+                })
+                .await;
+
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Unsubscribe {
+                    topic: gossip::topic_for_community_directory(&community_id),
+                })
+                .await;
+
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Unsubscribe {
+                    topic: gossip::topic_for_community_sync(&community_id),
+                })
+                .await;
+
             let namespace = format!("dusk/community/{}", community_id);
             let _ = handle
                 .command_tx
@@ -363,14 +473,17 @@ pub async fn leave_community(
                 .await;
         }
 
-        // remove local cached community state so leave persists across restarts
+        drop(node_handle);
+
+        // archive or remove the local copy so leave persists across restarts
         let mut engine = state.crdt_engine.lock().await;
-        engine.remove_community(&community_id)?;
+        if keep_history.unwrap_or(false) {
+            engine.archive_community(&community_id)?;
+        } else {
+            engine.remove_community(&community_id)?;
+        }
         drop(engine);
 
-        let mut guard = state.pending_join_role_guard.lock().await;
-        guard.remove(&community_id);
-
         Ok(())
     })
 }
@@ -381,7 +494,12 @@ pub async fn get_communities(state: State<'_, AppState>) -> Result<Vec<Community
         let engine = state.crdt_engine.lock().await;
         let mut communities = Vec::new();
 
-        for id in engine.community_ids() {
+        // archives are listed too, flagged so they open read-only
+        for id in engine
+            .community_ids()
+            .into_iter()
+            .chain(engine.archived_community_ids())
+        {
             if let Ok(meta) = engine.get_community_meta(&id) {
                 communities.push(meta);
             }
@@ -399,17 +517,16 @@ pub async fn create_channel(
     topic: String,
     kind: Option<String>,
     category_id: Option<String>,
+    idempotency_key: Option<String>,
 ) -> Result<ChannelMeta, String> {
     ipc_log!("create_channel", {
-        let identity = state.identity.lock().await;
-        let id = identity.as_ref().ok_or("no identity loaded")?;
-        let requester_id = id.peer_id.to_string();
-        drop(identity);
-
-        let engine = state.crdt_engine.lock().await;
-        let members = engine.get_members(&community_id)?;
-        check_permission(&members, &requester_id, &["owner", "admin"])?;
-        drop(engine);
+        let claim = state.idempotency.claim("create_channel", idempotency_key).await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        authz::resolve(&state, &community_id)
+            .await?
+            .require_admin()?;
 
         let mut hasher = Sha256::new();
         hasher.update(community_id.as_bytes());
@@ -424,6 +541,8 @@ pub async fn create_channel(
 
         let channel_kind = match kind.as_deref() {
             Some("voice") | Some("Voice") => ChannelKind::Voice,
+            Some("announcement") | Some("Announcement") => ChannelKind::Announcement,
+            Some("stage") | Some("Stage") => ChannelKind::Stage,
             _ => ChannelKind::Text,
         };
 
@@ -435,11 +554,13 @@ pub async fn create_channel(
             kind: channel_kind,
             position: 0,
             category_id,
+            max_participants: None,
         };
 
         let mut engine = state.crdt_engine.lock().await;
         engine.create_channel(&community_id, &channel)?;
         drop(engine);
+        claim.complete(&channel);
 
         // subscribe to the new channel's topics
         let node_handle = state.node_handle.lock().await;
@@ -481,17 +602,16 @@ pub async fn create_category(
     state: State<'_, AppState>,
     community_id: String,
     name: String,
+    idempotency_key: Option<String>,
 ) -> Result<CategoryMeta, String> {
     ipc_log!("create_category", {
-        let identity = state.identity.lock().await;
-        let id = identity.as_ref().ok_or("no identity loaded")?;
-        let requester_id = id.peer_id.to_string();
-        drop(identity);
-
-        let engine = state.crdt_engine.lock().await;
-        let members = engine.get_members(&community_id)?;
-        check_permission(&members, &requester_id, &["owner", "admin"])?;
-        drop(engine);
+        let claim = state.idempotency.claim("create_category", idempotency_key).await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        authz::resolve(&state, &community_id)
+            .await?
+            .require_admin()?;
 
         let mut hasher = Sha256::new();
         hasher.update(community_id.as_bytes());
@@ -514,6 +634,7 @@ pub async fn create_category(
         let mut engine = state.crdt_engine.lock().await;
         engine.create_category(&community_id, &category)?;
         drop(engine);
+        claim.complete(&category);
 
         broadcast_sync(&state, &community_id).await;
 
@@ -543,12 +664,11 @@ pub async fn get_members(
     // their latest known name even before a ProfileAnnounce arrives this session
     let directory = state.storage.load_directory().unwrap_or_default();
     for member in &mut members {
-        if member.display_name.is_empty() {
-            if let Some(entry) = directory.get(&member.peer_id) {
-                if !entry.display_name.is_empty() {
-                    member.display_name = entry.display_name.clone();
-                }
+        if let Some(entry) = directory.get(&member.peer_id) {
+            if member.display_name.is_empty() && !entry.display_name.is_empty() {
+                member.display_name = entry.display_name.clone();
             }
+            member.activity = entry.activity.clone();
         }
     }
 
@@ -557,9 +677,11 @@ pub async fn get_members(
     if let Some(ref id) = *identity {
         let local_peer = id.peer_id.to_string();
         let found = members.iter_mut().find(|m| m.peer_id == local_peer);
+        let activity = state.activity.lock().await.clone();
         if let Some(member) = found {
             member.display_name = id.display_name.clone();
             member.status = PeerStatus::Online;
+            member.activity = activity;
         } else {
             // local user isn't in the doc yet (shouldn't happen, but be safe)
             members.push(Member {
@@ -569,13 +691,85 @@ pub async fn get_members(
                 roles: vec!["member".to_string()],
                 trust_level: 1.0,
                 joined_at: 0,
+                activity,
+                avatar_seed: None,
             });
         }
     }
+    drop(identity);
+
+    // per-community names win over everything else, including our own global name
+    let profiles = state
+        .storage
+        .load_community_member_profiles(&community_id)
+        .unwrap_or_default();
+    for member in &mut members {
+        if let Some(profile) = profiles.get(&member.peer_id) {
+            member.display_name = profile.display_name.clone();
+            member.avatar_seed = profile.avatar_seed.clone();
+        }
+    }
 
     Ok(members)
 }
 
+// the community's membership log in stored order with each event's
+// signature status, so moderators can audit joins, leaves and role changes
+#[tauri::command]
+pub async fn get_membership_log(
+    state: State<'_, AppState>,
+    community_id: String,
+) -> Result<Vec<MembershipLogEntry>, String> {
+    ipc_log!("get_membership_log", {
+        let settings = state.storage.load_settings().unwrap_or_default();
+        let text = Catalog::from_settings(&settings.locale);
+
+        let engine = state.crdt_engine.lock().await;
+        let events = engine.get_membership_log(&community_id)?;
+        let members = engine.get_members(&community_id).unwrap_or_default();
+        drop(engine);
+
+        Ok(events
+            .into_iter()
+            .map(|event| MembershipLogEntry {
+                verified: crate::verification::verify_membership_event(&community_id, &event),
+                summary: describe_membership(&text, &event, &members),
+                event,
+            })
+            .collect())
+    })
+}
+
+// who did what in words, names come from the event and the current member
+// list, falling back to the peer id for anyone not in it anymore
+fn describe_membership(text: &Catalog, event: &MembershipEvent, members: &[Member]) -> String {
+    let name_of = |peer_id: &str| {
+        if peer_id == event.peer_id && !event.display_name.is_empty() {
+            return event.display_name.clone();
+        }
+        members
+            .iter()
+            .find(|m| m.peer_id == peer_id && !m.display_name.is_empty())
+            .map_or_else(|| peer_id.to_string(), |m| m.display_name.clone())
+    };
+    let name = name_of(&event.peer_id);
+    let actor = name_of(&event.actor);
+    let roles = event.roles.join(", ");
+    let args = [
+        ("name", name.as_str()),
+        ("actor", actor.as_str()),
+        ("roles", roles.as_str()),
+    ];
+    let id = match event.action {
+        MembershipAction::Join => "membership.join",
+        MembershipAction::Leave => "membership.leave",
+        MembershipAction::Kick => "membership.kick",
+        MembershipAction::SetRoles if event.roles.is_empty() => "membership.clear_roles",
+        MembershipAction::SetRoles => "membership.set_roles",
+    };
+    text.text(id, &args)
+}
+
 #[tauri::command]
 pub async fn edit_message(
     state: State<'_, AppState>,
@@ -626,39 +820,64 @@ pub async fn edit_message(
 }
 
 #[tauri::command]
-pub async fn delete_message(
+pub async fn add_reaction(
     state: State<'_, AppState>,
     community_id: String,
     message_id: String,
-) -> Result<(), String> {
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let peer_id_str = id.peer_id.to_string();
-    drop(identity);
+    emoji: String,
+) -> Result<Vec<MessageReaction>, String> {
+    ipc_log!("add_reaction", {
+        set_reaction(&state, community_id, message_id, emoji, true).await
+    })
+}
 
-    // verify the user is the message author
-    let mut engine = state.crdt_engine.lock().await;
-    let message = engine
-        .get_message(&community_id, &message_id)?
-        .ok_or_else(|| format!("message {} not found", message_id))?;
+#[tauri::command]
+pub async fn remove_reaction(
+    state: State<'_, AppState>,
+    community_id: String,
+    message_id: String,
+    emoji: String,
+) -> Result<Vec<MessageReaction>, String> {
+    ipc_log!("remove_reaction", {
+        set_reaction(&state, community_id, message_id, emoji, false).await
+    })
+}
 
-    if message.author_id != peer_id_str {
-        return Err("not authorized to delete this message".to_string());
+// record our reaction in the doc and tell the channel, returns the message's
+// reactions afterwards
+async fn set_reaction(
+    state: &State<'_, AppState>,
+    community_id: String,
+    message_id: String,
+    emoji: String,
+    active: bool,
+) -> Result<Vec<MessageReaction>, String> {
+    let emoji_len = emoji.chars().count();
+    if emoji_len == 0 || emoji_len > MAX_REACTION_LEN {
+        return Err("invalid reaction".to_string());
     }
+    let perms = authz::resolve(state, &community_id).await?;
+    perms.require_member()?;
 
-    // capture the channel id before deleting so we broadcast to the right topic
-    let channel_id = message.channel_id.clone();
-    engine.delete_message(&community_id, &message_id)?;
+    let mut engine = state.crdt_engine.lock().await;
+    let channel_id = engine
+        .get_message(&community_id, &message_id)?
+        .ok_or_else(|| format!("message {} not found", message_id))?
+        .channel_id;
+    let reactions = engine
+        .set_reaction(&community_id, &message_id, &perms.peer_id, &emoji, active)?
+        .ok_or_else(|| format!("message {} not found", message_id))?;
     drop(engine);
 
-    // broadcast the deletion to the correct channel topic only
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
         let topic = gossip::topic_for_messages(&community_id, &channel_id);
-        let deletion = crate::protocol::messages::GossipMessage::DeleteMessage {
-            message_id: message_id.clone(),
+        let reaction = crate::protocol::messages::GossipMessage::Reaction {
+            message_id,
+            emoji,
+            active,
         };
-        if let Ok(data) = serde_json::to_vec(&deletion) {
+        if let Ok(data) = serde_json::to_vec(&reaction) {
             let _ = handle
                 .command_tx
                 .send(NodeCommand::SendMessage { topic, data })
@@ -666,57 +885,142 @@ pub async fn delete_message(
         }
     }
 
-    Ok(())
+    Ok(reactions)
 }
 
+// deleting a message hides it right away but keeps it restorable with
+// undo_delete_message for crdt::UNDO_DELETE_SECS. the deletion only reaches
+// the doc and peers once that window has passed
 #[tauri::command]
-pub async fn kick_member(
+pub async fn delete_message(
     state: State<'_, AppState>,
     community_id: String,
-    member_peer_id: String,
+    message_id: String,
 ) -> Result<(), String> {
     let identity = state.identity.lock().await;
     let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
+    let peer_id_str = id.peer_id.to_string();
     drop(identity);
 
-    // verify the requester has admin rights
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
+    let mut engine = state.crdt_engine.lock().await;
+    let message = engine
+        .get_message(&community_id, &message_id)?
+        .ok_or_else(|| format!("message {} not found", message_id))?;
+    let capability = authz::resolve_with(&engine, &community_id, &peer_id_str)?
+        .authorize_delete(&message.author_id, &message.channel_id)?;
 
-    let requester = members
-        .iter()
-        .find(|m| m.peer_id == requester_id)
-        .ok_or("requester not found in community")?;
+    engine.soft_delete_message(&community_id, &message_id, capability)?;
+    drop(engine);
 
-    let is_admin = requester.roles.iter().any(|r| r == "admin" || r == "owner");
-    if !is_admin {
-        return Err("not authorized to kick members".to_string());
-    }
+    let crdt_engine = state.crdt_engine.clone();
+    let node_handle = state.node_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(crate::crdt::UNDO_DELETE_SECS)).await;
 
-    // cannot kick the owner
-    let target = members
-        .iter()
-        .find(|m| m.peer_id == member_peer_id)
-        .ok_or("member not found")?;
+        let finished = crdt_engine.lock().await.finish_soft_delete(&message_id);
+        let Some(deletion) = finished else {
+            return;
+        };
 
-    if target.roles.iter().any(|r| r == "owner") {
-        return Err("cannot kick the community owner".to_string());
+        let node_handle = node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            broadcast_deletion(handle, deletion).await;
+        }
+    });
+
+    Ok(())
+}
+
+// send an applied deletion to the channel topic it belongs to only
+pub(crate) async fn broadcast_deletion(
+    handle: &crate::node::NodeHandle,
+    deletion: AppliedDeletion,
+) {
+    let topic = gossip::topic_for_messages(&deletion.community_id, &deletion.channel_id);
+    let message = crate::protocol::messages::GossipMessage::DeleteMessage {
+        message_id: deletion.message_id,
+        capability: deletion.capability,
+    };
+    if let Ok(data) = serde_json::to_vec(&message) {
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::SendMessage { topic, data })
+            .await;
     }
+}
 
-    drop(engine);
+// restore a message deleted within the last crdt::UNDO_DELETE_SECS
+#[tauri::command]
+pub async fn undo_delete_message(
+    state: State<'_, AppState>,
+    community_id: String,
+    message_id: String,
+) -> Result<ChatMessage, String> {
+    ipc_log!("undo_delete_message", {
+        let mut engine = state.crdt_engine.lock().await;
+        engine.undo_delete_message(&community_id, &message_id)
+    })
+}
+
+#[tauri::command]
+pub async fn kick_member(
+    state: State<'_, AppState>,
+    community_id: String,
+    member_peer_id: String,
+) -> Result<(), String> {
+    // the token goes out with the kick so peers don't have to trust their
+    // copy of our roles
+    let permissions = authz::resolve(&state, &community_id).await?;
+    let capability = permissions.authorize_kick(&member_peer_id)?;
+    let is_admin = permissions.is_admin;
+    let target_display_name = permissions
+        .member(&member_peer_id)
+        .map(|m| m.display_name.clone())
+        .unwrap_or_default();
 
     // remove the member from the community
     let mut engine = state.crdt_engine.lock().await;
     engine.remove_member(&community_id, &member_peer_id)?;
     drop(engine);
 
+    match capability {
+        Some(ref token) if !is_admin => {
+            let event = {
+                let identity = state.identity.lock().await;
+                let id = identity.as_ref().ok_or("no identity loaded")?;
+                crate::verification::sign_capability_kick(
+                    &id.keypair,
+                    &community_id,
+                    &member_peer_id,
+                    &target_display_name,
+                    now_millis(),
+                    token,
+                )
+            };
+            let mut engine = state.crdt_engine.lock().await;
+            engine.record_membership_event(&community_id, &event)?;
+        }
+        _ => {
+            record_membership(
+                &state,
+                &community_id,
+                MembershipAction::Kick,
+                &member_peer_id,
+                &[],
+                &target_display_name,
+                now_millis(),
+            )
+            .await?;
+        }
+    }
+
     // broadcast the kick to peers via gossip and crdt sync
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
         let presence_topic = gossip::topic_for_presence(&community_id);
         let kick_msg = crate::protocol::messages::GossipMessage::MemberKicked {
             peer_id: member_peer_id.clone(),
+            capability,
         };
         if let Ok(data) = serde_json::to_vec(&kick_msg) {
             let _ = handle
@@ -744,13 +1048,35 @@ pub async fn generate_invite(
     let meta = engine.get_community_meta(&community_id)?;
     drop(engine);
 
+    let identity = state.identity.lock().await;
+    let id = identity.as_ref().ok_or("no identity loaded")?;
+
     // invite contains only the community id and name
     // no IP addresses or peer addresses are included
-    // peers discover each other through the relay's rendezvous protocol
-    let invite = crate::protocol::community::InviteCode {
+    // peers discover each other through the relay's rendezvous protocol.
+    // it is signed by us so members serve its holder the community doc
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let mut invite = crate::protocol::community::InviteCode {
         community_id: meta.id.clone(),
         community_name: meta.name.clone(),
+        owner_id: meta.created_by.clone(),
+        inviter: id.peer_id.to_string(),
+        inviter_key: hex::encode(id.keypair.public().encode_protobuf()),
+        expires_at: now + INVITE_PROOF_TTL_MS,
+        signature: String::new(),
     };
+    invite.signature = crate::verification::sign_invite(&id.keypair, &invite);
+
+    // as the owner, make sure the doc the joiner will get carries a checkpoint
+    // covering it, rather than waiting for the next periodic one
+    state
+        .crdt_engine
+        .lock()
+        .await
+        .create_checkpoint(&community_id, &id.keypair);
 
     Ok(invite.encode())
 }
@@ -761,15 +1087,9 @@ pub async fn reorder_channels(
     community_id: String,
     channel_ids: Vec<String>,
 ) -> Result<Vec<ChannelMeta>, String> {
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
-
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner", "admin"])?;
-    drop(engine);
+    authz::resolve(&state, &community_id)
+        .await?
+        .require_admin()?;
 
     let mut engine = state.crdt_engine.lock().await;
     let channels = engine.reorder_channels(&community_id, &channel_ids)?;
@@ -797,15 +1117,9 @@ pub async fn update_community(
         return Err("community name cannot be empty".to_string());
     }
 
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
-
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner", "admin"])?;
-    drop(engine);
+    authz::resolve(&state, &community_id)
+        .await?
+        .require_admin()?;
 
     let mut engine = state.crdt_engine.lock().await;
     engine.update_community_meta(&community_id, &name, &description)?;
@@ -830,15 +1144,9 @@ pub async fn update_channel(
         return Err("channel name cannot be empty".to_string());
     }
 
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
-
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner", "admin"])?;
-    drop(engine);
+    authz::resolve(&state, &community_id)
+        .await?
+        .require_admin()?;
 
     let mut engine = state.crdt_engine.lock().await;
     engine.update_channel(&community_id, &channel_id, &name, &topic)?;
@@ -855,22 +1163,52 @@ pub async fn update_channel(
     Ok(channel)
 }
 
+// cap how many participants a voice channel admits, None lifts the cap
 #[tauri::command]
-pub async fn delete_channel(
+pub async fn set_voice_channel_capacity(
     state: State<'_, AppState>,
     community_id: String,
     channel_id: String,
-) -> Result<(), String> {
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
+    max_participants: Option<u32>,
+) -> Result<ChannelMeta, String> {
+    if max_participants == Some(0) {
+        return Err("capacity must be at least 1".to_string());
+    }
 
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner", "admin"])?;
+    authz::resolve(&state, &community_id)
+        .await?
+        .require_admin()?;
+
+    let mut engine = state.crdt_engine.lock().await;
+    let channel = engine
+        .get_channels(&community_id)?
+        .into_iter()
+        .find(|c| c.id == channel_id)
+        .ok_or("channel not found")?;
+    if !channel.kind.is_voice() {
+        return Err("only voice channels have a capacity".to_string());
+    }
+    engine.set_channel_capacity(&community_id, &channel_id, max_participants)?;
     drop(engine);
 
+    broadcast_sync(&state, &community_id).await;
+
+    Ok(ChannelMeta {
+        max_participants,
+        ..channel
+    })
+}
+
+#[tauri::command]
+pub async fn delete_channel(
+    state: State<'_, AppState>,
+    community_id: String,
+    channel_id: String,
+) -> Result<(), String> {
+    authz::resolve(&state, &community_id)
+        .await?
+        .require_admin()?;
+
     // unsubscribe from channel topics before deletion
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
@@ -904,15 +1242,9 @@ pub async fn delete_category(
     community_id: String,
     category_id: String,
 ) -> Result<(), String> {
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
-
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner", "admin"])?;
-    drop(engine);
+    authz::resolve(&state, &community_id)
+        .await?
+        .require_admin()?;
 
     let mut engine = state.crdt_engine.lock().await;
     engine.delete_category(&community_id, &category_id)?;
@@ -934,15 +1266,9 @@ pub async fn update_category(
         return Err("category name cannot be empty".to_string());
     }
 
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
-
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner", "admin"])?;
-    drop(engine);
+    authz::resolve(&state, &community_id)
+        .await?
+        .require_admin()?;
 
     let mut engine = state.crdt_engine.lock().await;
     engine.update_category(&community_id, &category_id, &name)?;
@@ -965,15 +1291,9 @@ pub async fn reorder_categories(
     community_id: String,
     category_ids: Vec<String>,
 ) -> Result<Vec<CategoryMeta>, String> {
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
-
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner", "admin"])?;
-    drop(engine);
+    authz::resolve(&state, &community_id)
+        .await?
+        .require_admin()?;
 
     let mut engine = state.crdt_engine.lock().await;
     let categories = engine.reorder_categories(&community_id, &category_ids)?;
@@ -995,32 +1315,37 @@ pub async fn set_member_role(
         return Err("invalid role: must be 'admin' or 'member'".to_string());
     }
 
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
-
     // only the owner can change roles
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner"])?;
+    let permissions = authz::resolve(&state, &community_id).await?;
+    permissions.require_owner()?;
 
     // cannot change the owner's own role through this command
-    let target = members
-        .iter()
-        .find(|m| m.peer_id == member_peer_id)
+    let target = permissions
+        .member(&member_peer_id)
         .ok_or("member not found")?;
 
     if target.roles.iter().any(|r| r == "owner") {
         return Err("cannot change the owner's role, use transfer_ownership instead".to_string());
     }
 
-    drop(engine);
+    let target_display_name = target.display_name.clone();
 
+    let roles = vec![role];
     let mut engine = state.crdt_engine.lock().await;
-    engine.set_member_role(&community_id, &member_peer_id, &[role])?;
+    engine.set_member_role(&community_id, &member_peer_id, &roles)?;
     drop(engine);
 
+    record_membership(
+        &state,
+        &community_id,
+        MembershipAction::SetRoles,
+        &member_peer_id,
+        &roles,
+        &target_display_name,
+        now_millis(),
+    )
+    .await?;
+
     broadcast_sync(&state, &community_id).await;
 
     Ok(())
@@ -1032,26 +1357,20 @@ pub async fn transfer_ownership(
     community_id: String,
     new_owner_peer_id: String,
 ) -> Result<(), String> {
-    let identity = state.identity.lock().await;
-    let id = identity.as_ref().ok_or("no identity loaded")?;
-    let requester_id = id.peer_id.to_string();
-    drop(identity);
-
-    let engine = state.crdt_engine.lock().await;
-    let members = engine.get_members(&community_id)?;
-    check_permission(&members, &requester_id, &["owner"])?;
+    let permissions = authz::resolve(&state, &community_id).await?;
+    permissions.require_owner()?;
+    let requester_id = permissions.peer_id.clone();
 
     if requester_id == new_owner_peer_id {
         return Err("cannot transfer ownership to yourself".to_string());
     }
 
     // verify the target is actually a member
-    members
-        .iter()
-        .find(|m| m.peer_id == new_owner_peer_id)
+    let new_owner = permissions
+        .member(&new_owner_peer_id)
         .ok_or("target member not found in community")?;
-
-    drop(engine);
+    let new_owner_display_name = new_owner.display_name.clone();
+    let own_display_name = permissions.require_member()?.display_name.clone();
 
     let mut engine = state.crdt_engine.lock().await;
     engine.transfer_ownership(&community_id, &requester_id, &new_owner_peer_id)?;
@@ -1059,7 +1378,399 @@ pub async fn transfer_ownership(
     let _ = state.storage.save_community_meta(&meta);
     drop(engine);
 
+    // promote first, the demotion a millisecond later is replayed while we
+    // still hold the owner role
+    let now = now_millis();
+    record_membership(
+        &state,
+        &community_id,
+        MembershipAction::SetRoles,
+        &new_owner_peer_id,
+        &["owner".to_string()],
+        &new_owner_display_name,
+        now,
+    )
+    .await?;
+    record_membership(
+        &state,
+        &community_id,
+        MembershipAction::SetRoles,
+        &requester_id,
+        &["admin".to_string()],
+        &own_display_name,
+        now + 1,
+    )
+    .await?;
+
     broadcast_sync(&state, &community_id).await;
 
     Ok(())
 }
+
+// list keys left with concurrent values after partitioned halves merged
+#[tauri::command]
+pub async fn get_community_conflicts(
+    state: State<'_, AppState>,
+    community_id: String,
+) -> Result<Vec<DocConflict>, String> {
+    ipc_log!("get_community_conflicts", {
+        let engine = state.crdt_engine.lock().await;
+        engine.get_conflicts(&community_id)
+    })
+}
+
+// moderator picks the surviving value for a conflicting key, the fresh write
+// supersedes the concurrent ones on every peer once it syncs
+#[tauri::command]
+pub async fn resolve_community_conflict(
+    state: State<'_, AppState>,
+    community_id: String,
+    key: String,
+    value: String,
+) -> Result<(), String> {
+    ipc_log!("resolve_community_conflict", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let requester_id = id.peer_id.to_string();
+        drop(identity);
+
+        let mut engine = state.crdt_engine.lock().await;
+        let permissions = authz::resolve_with(&engine, &community_id, &requester_id)?;
+        permissions.require_admin()?;
+
+        // ownership only moves through transfer_ownership
+        if key == "meta.created_by" || value.split(',').any(|r| r.trim() == "owner") {
+            permissions.require_owner()?;
+        }
+
+        let conflicts = engine.get_conflicts(&community_id)?;
+        let conflict = conflicts
+            .iter()
+            .find(|c| c.key == key)
+            .ok_or("no conflict recorded for that key")?;
+        if !conflict.values.contains(&value) {
+            return Err("value must be one of the conflicting values".to_string());
+        }
+
+        engine.resolve_conflict(&community_id, &key, &value)?;
+        if key.starts_with("meta.") {
+            let meta = engine.get_community_meta(&community_id)?;
+            let _ = state.storage.save_community_meta(&meta);
+        }
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+
+        Ok(())
+    })
+}
+
+// set the minimum verification members must meet, moderators remove unverified
+// peers that join afterwards
+#[tauri::command]
+pub async fn set_community_verification_policy(
+    state: State<'_, AppState>,
+    community_id: String,
+    policy: String,
+) -> Result<CommunityMeta, String> {
+    ipc_log!("set_community_verification_policy", {
+        let policy = VerificationPolicy::parse(&policy)?;
+
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let requester_id = id.peer_id.to_string();
+        drop(identity);
+
+        let mut engine = state.crdt_engine.lock().await;
+        authz::resolve_with(&engine, &community_id, &requester_id)?.require_admin()?;
+
+        engine.set_verification_policy(&community_id, policy)?;
+        let meta = engine.get_community_meta(&community_id)?;
+        let _ = state.storage.save_community_meta(&meta);
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+
+        Ok(meta)
+    })
+}
+
+// keep earlier revisions of edited messages in the doc so members can see
+// what changed. only edits made while this is on are recorded
+#[tauri::command]
+pub async fn set_community_edit_history(
+    state: State<'_, AppState>,
+    community_id: String,
+    enabled: bool,
+) -> Result<CommunityMeta, String> {
+    ipc_log!("set_community_edit_history", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let requester_id = id.peer_id.to_string();
+        drop(identity);
+
+        let mut engine = state.crdt_engine.lock().await;
+        authz::resolve_with(&engine, &community_id, &requester_id)?.require_admin()?;
+
+        engine.set_edit_history(&community_id, enabled)?;
+        let meta = engine.get_community_meta(&community_id)?;
+        let _ = state.storage.save_community_meta(&meta);
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+
+        Ok(meta)
+    })
+}
+
+// earlier revisions of an edited message, oldest first. only available in
+// communities that turned edit history on
+#[tauri::command]
+pub async fn get_message_edit_history(
+    state: State<'_, AppState>,
+    community_id: String,
+    message_id: String,
+) -> Result<Vec<MessageRevision>, String> {
+    ipc_log!("get_message_edit_history", {
+        let engine = state.crdt_engine.lock().await;
+        if !engine.get_community_meta(&community_id)?.edit_history {
+            return Err("edit history is disabled in this community".to_string());
+        }
+        if engine.get_message(&community_id, &message_id)?.is_none() {
+            return Err(format!("message {} not found", message_id));
+        }
+        engine.get_message_revisions(&community_id, &message_id)
+    })
+}
+
+// mirror an announcement channel from another community into one of ours.
+// our node re-publishes every announcement it sees there, with provenance,
+// so this only works while it is running
+#[tauri::command]
+pub async fn follow_channel(
+    state: State<'_, AppState>,
+    source_community_id: String,
+    source_channel_id: String,
+    target_community_id: String,
+    target_channel_id: String,
+) -> Result<ChannelFollow, String> {
+    ipc_log!("follow_channel", {
+        if source_community_id == target_community_id {
+            return Err("cannot follow a channel from the same community".to_string());
+        }
+
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let requester_id = id.peer_id.to_string();
+        drop(identity);
+
+        let engine = state.crdt_engine.lock().await;
+        authz::resolve_with(&engine, &target_community_id, &requester_id)?.require_admin()?;
+
+        let source = engine
+            .get_channels(&source_community_id)?
+            .into_iter()
+            .find(|ch| ch.id == source_channel_id)
+            .ok_or("source channel not found")?;
+        if !matches!(source.kind, ChannelKind::Announcement) {
+            return Err("only announcement channels can be followed".to_string());
+        }
+        let target = engine
+            .get_channels(&target_community_id)?
+            .into_iter()
+            .find(|ch| ch.id == target_channel_id)
+            .ok_or("target channel not found")?;
+        if target.kind.is_voice() {
+            return Err("announcements cannot be mirrored into a voice channel".to_string());
+        }
+        drop(engine);
+
+        let follow = ChannelFollow {
+            source_community_id,
+            source_channel_id,
+            target_community_id,
+            target_channel_id,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+        };
+        state
+            .storage
+            .save_channel_follow(&follow)
+            .map_err(|e| format!("failed to save channel follow: {}", e))?;
+
+        Ok(follow)
+    })
+}
+
+#[tauri::command]
+pub async fn unfollow_channel(
+    state: State<'_, AppState>,
+    source_channel_id: String,
+    target_channel_id: String,
+) -> Result<(), String> {
+    ipc_log!("unfollow_channel", {
+        let removed = state
+            .storage
+            .delete_channel_follow(&source_channel_id, &target_channel_id)
+            .map_err(|e| format!("failed to remove channel follow: {}", e))?;
+        if !removed {
+            return Err("channel is not followed".to_string());
+        }
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub async fn get_channel_follows(state: State<'_, AppState>) -> Result<Vec<ChannelFollow>, String> {
+    ipc_log!("get_channel_follows", {
+        state
+            .storage
+            .load_channel_follows()
+            .map_err(|e| format!("failed to load channel follows: {}", e))
+    })
+}
+
+// announce a community profile override on that community's presence topic only
+async fn publish_community_profile(state: &State<'_, AppState>, profile: CommunityProfile) {
+    let node_handle = state.node_handle.lock().await;
+    if let Some(ref handle) = *node_handle {
+        let topic = gossip::topic_for_presence(&profile.community_id);
+        let msg = crate::protocol::messages::GossipMessage::CommunityProfile(profile);
+        if let Ok(data) = serde_json::to_vec(&msg) {
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::SendMessage { topic, data })
+                .await;
+        }
+    }
+}
+
+// go by a different name and avatar in one community. the override is only
+// ever sent on that community's topics, though the peer id stays the same
+// so members who share several communities can still link the identities
+#[tauri::command]
+pub async fn set_community_profile(
+    state: State<'_, AppState>,
+    community_id: String,
+    display_name: String,
+    avatar_seed: Option<String>,
+) -> Result<CommunityProfile, String> {
+    ipc_log!("set_community_profile", {
+        let display_name = display_name.trim().to_string();
+        if display_name.is_empty() {
+            return Err("display name cannot be empty".to_string());
+        }
+        if display_name.chars().count() > MAX_COMMUNITY_DISPLAY_NAME_LEN {
+            return Err(format!(
+                "display name must be {} characters or fewer",
+                MAX_COMMUNITY_DISPLAY_NAME_LEN
+            ));
+        }
+        let avatar_seed = avatar_seed
+            .map(|seed| seed.trim().to_string())
+            .filter(|seed| !seed.is_empty());
+        if avatar_seed
+            .as_ref()
+            .is_some_and(|seed| seed.chars().count() > MAX_COMMUNITY_AVATAR_SEED_LEN)
+        {
+            return Err(format!(
+                "avatar seed must be {} characters or fewer",
+                MAX_COMMUNITY_AVATAR_SEED_LEN
+            ));
+        }
+
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let peer_id = id.peer_id.to_string();
+        drop(identity);
+
+        let profile = CommunityProfile {
+            community_id: community_id.clone(),
+            peer_id: peer_id.clone(),
+            display_name: display_name.clone(),
+            avatar_seed,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+        };
+
+        let mut engine = state.crdt_engine.lock().await;
+        engine.update_member_display_name(&community_id, &peer_id, &display_name)?;
+        drop(engine);
+
+        state
+            .storage
+            .save_community_profile(&profile)
+            .map_err(|e| format!("failed to save community profile: {}", e))?;
+
+        broadcast_sync(&state, &community_id).await;
+        publish_community_profile(&state, profile.clone()).await;
+
+        Ok(profile)
+    })
+}
+
+// drop the override and go back to the global profile in this community
+#[tauri::command]
+pub async fn clear_community_profile(
+    state: State<'_, AppState>,
+    community_id: String,
+) -> Result<(), String> {
+    ipc_log!("clear_community_profile", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let peer_id = id.peer_id.to_string();
+        let global_name = id.display_name.clone();
+        drop(identity);
+
+        state
+            .storage
+            .delete_community_profile(&community_id, &peer_id)
+            .map_err(|e| format!("failed to delete community profile: {}", e))?;
+
+        let mut engine = state.crdt_engine.lock().await;
+        engine.update_member_display_name(&community_id, &peer_id, &global_name)?;
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+
+        // an empty name tells members to forget the override
+        publish_community_profile(
+            &state,
+            CommunityProfile {
+                community_id,
+                peer_id,
+                display_name: String::new(),
+                avatar_seed: None,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as u64,
+            },
+        )
+        .await;
+
+        Ok(())
+    })
+}
+
+// our own per-community profiles
+#[tauri::command]
+pub async fn get_community_profiles(
+    state: State<'_, AppState>,
+) -> Result<Vec<CommunityProfile>, String> {
+    ipc_log!("get_community_profiles", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let peer_id = id.peer_id.to_string();
+        drop(identity);
+
+        state
+            .storage
+            .load_peer_community_profiles(&peer_id)
+            .map_err(|e| format!("failed to load community profiles: {}", e))
+    })
+}
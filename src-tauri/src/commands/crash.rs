@@ -0,0 +1,54 @@
+use tauri::State;
+
+use super::ipc_log;
+use crate::crash::{self, CrashReport};
+use crate::AppState;
+
+const SUBMIT_TIMEOUT_SECS: u64 = 30;
+
+// crash dumps on disk, newest first
+#[tauri::command]
+pub async fn list_crash_reports(state: State<'_, AppState>) -> Result<Vec<CrashReport>, String> {
+    ipc_log!("list_crash_reports", {
+        crash::list_reports(&state.storage.crash_dir())
+            .map_err(|e| format!("failed to list crash reports: {}", e))
+    })
+}
+
+// upload one crash dump to the endpoint the user configured in settings.
+// this is the only path by which a report leaves the machine
+#[tauri::command]
+pub async fn submit_crash_report(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    ipc_log!("submit_crash_report", {
+        let endpoint = state
+            .storage
+            .load_settings()
+            .ok()
+            .and_then(|s| s.crash_report_endpoint)
+            .filter(|e| !e.trim().is_empty())
+            .ok_or("no crash report endpoint configured")?;
+        if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+            return Err("crash report endpoint must be an http(s) url".to_string());
+        }
+
+        let crash_dir = state.storage.crash_dir();
+        let mut report = crash::load_report(&crash_dir, &id)
+            .map_err(|e| format!("failed to load crash report: {}", e))?;
+
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(SUBMIT_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| format!("failed to build http client: {}", e))?;
+        client
+            .post(&endpoint)
+            .json(&report)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("failed to submit crash report: {}", e))?;
+
+        report.submitted = true;
+        crash::save_report(&crash_dir, &report)
+            .map_err(|e| format!("failed to update crash report: {}", e))
+    })
+}
@@ -6,11 +6,17 @@ use super::ipc_log;
 use crate::node::gossip;
 use crate::node::NodeCommand;
 use crate::protocol::messages::{
-    DMConversationMeta, DMTypingIndicator, DirectMessage, GossipMessage,
+    validate_attachments, Attachment, DMConversationMeta, DMConversationPage, DMDeleteRequest,
+    DMEdit, DMEphemeralKind, DMEphemeralPolicy, DMEphemeralSignal, DMPolicyLog, DMReaction,
+    DMReactionSignal, DMTypingIndicator, DirectMessage, GossipMessage, DM_DELETE_WINDOW_SECS,
+    MAX_DM_EPHEMERAL_TTL_SECS, MAX_DM_REACTION_LEN, MIN_DM_EPHEMERAL_TTL_SECS,
 };
 use crate::storage::DmSearchParams;
 use crate::AppState;
 
+const DEFAULT_DM_CONVERSATION_PAGE: usize = 50;
+const MAX_DM_CONVERSATION_PAGE: usize = 200;
+
 // send a direct message to a peer
 // creates the conversation on disk if it doesn't exist,
 // publishes the message over gossipsub on the pair topic
@@ -19,8 +25,18 @@ pub async fn send_dm(
     state: State<'_, AppState>,
     peer_id: String,
     content: String,
+    idempotency_key: Option<String>,
+    reply_to: Option<String>,
+    attachments: Option<Vec<Attachment>>,
 ) -> Result<DirectMessage, String> {
     ipc_log!("send_dm", {
+        let claim = state.idempotency.claim("send_dm", idempotency_key).await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        let attachments = attachments.unwrap_or_default();
+        validate_attachments(&attachments, &[])?;
+
         let identity = state.identity.lock().await;
         let id = identity.as_ref().ok_or("no identity loaded")?;
 
@@ -33,6 +49,18 @@ pub async fn send_dm(
         let display_name = id.display_name.clone();
         drop(identity);
 
+        // derive the conversation id and persist the message
+        let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
+
+        // replies only point at messages in the same conversation
+        if let Some(ref parent_id) = reply_to {
+            state
+                .storage
+                .load_dm_message(&conversation_id, parent_id)
+                .map_err(|e| format!("failed to load message: {}", e))?
+                .ok_or("replied message not found")?;
+        }
+
         let msg = DirectMessage {
             id: format!("dm_{}_{}", local_peer_id, now),
             from_peer: local_peer_id.clone(),
@@ -40,15 +68,17 @@ pub async fn send_dm(
             from_display_name: display_name.clone(),
             content: content.clone(),
             timestamp: now,
+            reply_to,
+            attachments,
+            edited: false,
+            reactions: Vec::new(),
         };
 
-        // derive the conversation id and persist the message
-        let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
-
         state
             .storage
             .append_dm_message(&conversation_id, &msg)
             .map_err(|e| format!("failed to persist dm: {}", e))?;
+        claim.complete(&msg);
 
         // ensure conversation metadata exists on disk
         // try to load existing meta to preserve peer's display name,
@@ -61,9 +91,10 @@ pub async fn send_dm(
                 // look up in directory
                 state
                     .storage
-                    .load_directory()
+                    .load_directory_entry(&peer_id)
                     .ok()
-                    .and_then(|d| d.get(&peer_id).map(|e| e.display_name.clone()))
+                    .flatten()
+                    .map(|e| e.display_name)
                     .unwrap_or_else(|| peer_id.clone())
             });
 
@@ -72,7 +103,9 @@ pub async fn send_dm(
             display_name: peer_display_name,
             last_message: Some(content),
             last_message_time: Some(now),
-            unread_count: existing_meta.map(|m| m.unread_count).unwrap_or(0),
+            unread_count: existing_meta.as_ref().map(|m| m.unread_count).unwrap_or(0),
+            pinned: existing_meta.as_ref().map(|m| m.pinned).unwrap_or(false),
+            archived: false,
         };
 
         state
@@ -86,23 +119,12 @@ pub async fn send_dm(
             let data = serde_json::to_vec(&GossipMessage::DirectMessage(msg.clone()))
                 .map_err(|e| format!("serialize error: {}", e))?;
 
-            // publish to the pair topic (for when both peers are already subscribed)
-            let pair_topic = gossip::topic_for_dm(&local_peer_id, &peer_id);
-            let _ = handle
-                .command_tx
-                .send(NodeCommand::SendMessage {
-                    topic: pair_topic,
-                    data: data.clone(),
-                })
-                .await;
-
-            // also publish to the recipient's inbox topic to guarantee delivery
-            // on first-time dms where the peer isn't subscribed to the pair topic yet
-            let inbox_topic = gossip::topic_for_dm_inbox(&peer_id);
+            // the node falls back to the recipient's inbox topic on first-time
+            // dms where the peer isn't subscribed to the pair topic yet
             let _ = handle
                 .command_tx
-                .send(NodeCommand::SendMessage {
-                    topic: inbox_topic,
+                .send(NodeCommand::SendDirectMessage {
+                    peer_id: peer_id.clone(),
                     data,
                 })
                 .await;
@@ -188,21 +210,100 @@ pub async fn search_dm_messages(
     })
 }
 
-// load all dm conversations for the sidebar
+// load all dm conversations for the sidebar, pinned ones first.
+// archived conversations are only included when asked for
 #[tauri::command]
 pub async fn get_dm_conversations(
     state: State<'_, AppState>,
+    include_archived: Option<bool>,
 ) -> Result<Vec<DMConversationMeta>, String> {
     ipc_log!("get_dm_conversations", {
         let conversations = state
             .storage
-            .load_all_dm_conversations()
+            .load_all_dm_conversations(include_archived.unwrap_or(false))
             .map_err(|e| format!("failed to load dm conversations: {}", e))?;
 
         Ok(conversations.into_iter().map(|(_, meta)| meta).collect())
     })
 }
 
+// page through dm conversations in sidebar order instead of loading them
+// all, optionally only the unread ones. the unread totals come back with
+// every page so the badge doesn't need the full list
+#[tauri::command]
+pub async fn get_dm_conversation_page(
+    state: State<'_, AppState>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    unread_only: Option<bool>,
+    include_archived: Option<bool>,
+) -> Result<DMConversationPage, String> {
+    ipc_log!("get_dm_conversation_page", {
+        let limit = limit
+            .unwrap_or(DEFAULT_DM_CONVERSATION_PAGE)
+            .min(MAX_DM_CONVERSATION_PAGE);
+
+        state
+            .storage
+            .load_dm_conversation_page(
+                cursor.as_deref(),
+                limit,
+                unread_only.unwrap_or(false),
+                include_archived.unwrap_or(false),
+            )
+            .map_err(|e| format!("failed to load dm conversations: {}", e))
+    })
+}
+
+// pin or unpin a dm conversation
+#[tauri::command]
+pub async fn set_dm_pinned(
+    state: State<'_, AppState>,
+    peer_id: String,
+    pinned: bool,
+) -> Result<DMConversationMeta, String> {
+    ipc_log!("set_dm_pinned", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let conversation_id = gossip::dm_conversation_id(&id.peer_id.to_string(), &peer_id);
+        drop(identity);
+
+        state
+            .storage
+            .set_dm_pinned(&conversation_id, pinned)
+            .map_err(|e| format!("failed to update conversation: {}", e))?;
+        state
+            .storage
+            .load_dm_conversation(&conversation_id)
+            .map_err(|e| format!("failed to load conversation: {}", e))
+    })
+}
+
+// archive or restore a dm conversation. archived conversations stay
+// subscribed and come back on their own when a new message arrives
+#[tauri::command]
+pub async fn set_dm_archived(
+    state: State<'_, AppState>,
+    peer_id: String,
+    archived: bool,
+) -> Result<DMConversationMeta, String> {
+    ipc_log!("set_dm_archived", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let conversation_id = gossip::dm_conversation_id(&id.peer_id.to_string(), &peer_id);
+        drop(identity);
+
+        state
+            .storage
+            .set_dm_archived(&conversation_id, archived)
+            .map_err(|e| format!("failed to update conversation: {}", e))?;
+        state
+            .storage
+            .load_dm_conversation(&conversation_id)
+            .map_err(|e| format!("failed to load conversation: {}", e))
+    })
+}
+
 // mark all messages in a dm conversation as read
 #[tauri::command]
 pub async fn mark_dm_read(state: State<'_, AppState>, peer_id: String) -> Result<(), String> {
@@ -224,7 +325,386 @@ pub async fn mark_dm_read(state: State<'_, AppState>, peer_id: String) -> Result
         state
             .storage
             .save_dm_conversation(&conversation_id, &meta)
-            .map_err(|e| format!("failed to save conversation: {}", e))
+            .map_err(|e| format!("failed to save conversation: {}", e))?;
+
+        // disappearing messages: start the timer here and send a read receipt
+        // so the sender's copies start theirs
+        let ephemeral = state
+            .storage
+            .load_dm_ephemeral_policy(&conversation_id)
+            .ok()
+            .flatten()
+            .is_some_and(|p| p.ttl_secs.is_some());
+        if ephemeral {
+            let now = now_millis();
+            let newest = state
+                .storage
+                .dm_conversation_stats(&conversation_id)
+                .ok()
+                .and_then(|(_, _, newest)| newest)
+                .unwrap_or(0);
+            let up_to = now.max(newest);
+            let _ = state
+                .storage
+                .mark_dm_messages_read(&conversation_id, &peer_id, up_to, now);
+            send_ephemeral_signal(&state, &local_peer_id, &peer_id, DMEphemeralKind::Read { up_to }).await;
+        }
+
+        Ok(())
+    })
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+async fn send_ephemeral_signal(state: &AppState, local_peer_id: &str, peer_id: &str, kind: DMEphemeralKind) {
+    let signal = DMEphemeralSignal {
+        from_peer: local_peer_id.to_string(),
+        to_peer: peer_id.to_string(),
+        kind,
+        timestamp: now_millis(),
+        public_key: String::new(),
+        signature: String::new(),
+    };
+    publish_ephemeral_signal(state, signal).await;
+}
+
+async fn publish_ephemeral_signal(state: &AppState, signal: DMEphemeralSignal) {
+    let topic = gossip::topic_for_dm(&signal.from_peer, &signal.to_peer);
+    let node_handle = state.node_handle.lock().await;
+    if let Some(ref handle) = *node_handle {
+        if let Ok(data) = serde_json::to_vec(&GossipMessage::DMEphemeral(signal)) {
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::SendMessage { topic, data })
+                .await;
+        }
+    }
+}
+
+// take a negotiation step locally: update and log the policy, then tell the peer
+async fn apply_ephemeral_step(
+    state: &AppState,
+    peer_id: &str,
+    kind: DMEphemeralKind,
+) -> Result<DMEphemeralPolicy, String> {
+    let (local_peer_id, keypair) = {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        (id.peer_id.to_string(), id.keypair.clone())
+    };
+
+    let conversation_id = gossip::dm_conversation_id(&local_peer_id, peer_id);
+    state
+        .storage
+        .load_dm_conversation(&conversation_id)
+        .map_err(|e| format!("failed to load conversation: {}", e))?;
+
+    let mut policy = state
+        .storage
+        .load_dm_ephemeral_policy(&conversation_id)
+        .map_err(|e| format!("failed to load disappearing messages policy: {}", e))?
+        .unwrap_or_else(|| DMEphemeralPolicy::new(peer_id));
+
+    let now = now_millis();
+    let Some((action, ttl_secs)) = policy.apply(&local_peer_id, &kind, now) else {
+        return Ok(policy);
+    };
+    state
+        .storage
+        .save_dm_ephemeral_policy(&conversation_id, &policy)
+        .map_err(|e| format!("failed to save disappearing messages policy: {}", e))?;
+
+    // answering the peer's own proposal goes out as an accept. both logs
+    // keep the step as signed here
+    let kind = match (action, ttl_secs) {
+        ("accept", Some(ttl_secs)) => DMEphemeralKind::Accept { ttl_secs },
+        _ => kind,
+    };
+    let mut signal = DMEphemeralSignal {
+        from_peer: local_peer_id,
+        to_peer: peer_id.to_string(),
+        kind,
+        timestamp: now,
+        public_key: hex::encode(keypair.public().encode_protobuf()),
+        signature: String::new(),
+    };
+    signal.signature =
+        crate::verification::sign_dm_policy_step(&keypair, &conversation_id, &signal);
+    state
+        .storage
+        .append_dm_policy_log(&conversation_id, &signal)
+        .map_err(|e| format!("failed to log policy change: {}", e))?;
+    publish_ephemeral_signal(state, signal).await;
+
+    Ok(policy)
+}
+
+// propose disappearing messages with a timer in seconds, counted from when a
+// message was read on both ends. matching the peer's pending proposal agrees
+// to it, none turns disappearing messages off for both sides
+#[tauri::command]
+pub async fn set_dm_ephemeral(
+    state: State<'_, AppState>,
+    peer_id: String,
+    ttl_secs: Option<u64>,
+) -> Result<DMEphemeralPolicy, String> {
+    ipc_log!("set_dm_ephemeral", {
+        let kind = match ttl_secs {
+            Some(ttl_secs) => {
+                if !(MIN_DM_EPHEMERAL_TTL_SECS..=MAX_DM_EPHEMERAL_TTL_SECS).contains(&ttl_secs) {
+                    return Err(format!(
+                        "disappearing message timer must be between {} and {} seconds",
+                        MIN_DM_EPHEMERAL_TTL_SECS, MAX_DM_EPHEMERAL_TTL_SECS
+                    ));
+                }
+                DMEphemeralKind::Propose { ttl_secs }
+            }
+            None => DMEphemeralKind::Disable,
+        };
+        apply_ephemeral_step(&state, &peer_id, kind).await
+    })
+}
+
+// accept or decline the peer's pending disappearing messages proposal
+#[tauri::command]
+pub async fn respond_dm_ephemeral(
+    state: State<'_, AppState>,
+    peer_id: String,
+    accept: bool,
+) -> Result<DMEphemeralPolicy, String> {
+    ipc_log!("respond_dm_ephemeral", {
+        let kind = if accept {
+            let local_peer_id = {
+                let identity = state.identity.lock().await;
+                identity.as_ref().ok_or("no identity loaded")?.peer_id.to_string()
+            };
+            let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
+            let ttl_secs = state
+                .storage
+                .load_dm_ephemeral_policy(&conversation_id)
+                .ok()
+                .flatten()
+                .filter(|p| p.pending_from.as_deref() == Some(peer_id.as_str()))
+                .and_then(|p| p.pending_ttl_secs)
+                .ok_or("no pending proposal from this peer")?;
+            DMEphemeralKind::Accept { ttl_secs }
+        } else {
+            DMEphemeralKind::Decline
+        };
+        apply_ephemeral_step(&state, &peer_id, kind).await
+    })
+}
+
+#[tauri::command]
+pub async fn get_dm_ephemeral(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<DMEphemeralPolicy, String> {
+    ipc_log!("get_dm_ephemeral", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let conversation_id = gossip::dm_conversation_id(&id.peer_id.to_string(), &peer_id);
+        drop(identity);
+
+        Ok(state
+            .storage
+            .load_dm_ephemeral_policy(&conversation_id)
+            .map_err(|e| format!("failed to load disappearing messages policy: {}", e))?
+            .unwrap_or_else(|| DMEphemeralPolicy::new(&peer_id)))
+    })
+}
+
+// every disappearing messages change in a conversation, with a check that
+// the hash chain hasn't been edited
+#[tauri::command]
+pub async fn get_dm_policy_log(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<DMPolicyLog, String> {
+    ipc_log!("get_dm_policy_log", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let conversation_id = gossip::dm_conversation_id(&id.peer_id.to_string(), &peer_id);
+        drop(identity);
+
+        let entries = state
+            .storage
+            .load_dm_policy_log(&conversation_id)
+            .map_err(|e| format!("failed to load policy log: {}", e))?;
+        Ok(DMPolicyLog::new(&conversation_id, entries))
+    })
+}
+
+// delete one of our own dms on both ends. the peer's node checks the
+// signature and the time window before removing its copy
+#[tauri::command]
+pub async fn delete_dm_for_everyone(
+    state: State<'_, AppState>,
+    peer_id: String,
+    message_id: String,
+) -> Result<(), String> {
+    ipc_log!("delete_dm_for_everyone", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let local_peer_id = id.peer_id.to_string();
+
+        let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
+        let message = state
+            .storage
+            .load_dm_message(&conversation_id, &message_id)
+            .map_err(|e| format!("failed to load message: {}", e))?
+            .ok_or("message not found")?;
+        if message.from_peer != local_peer_id {
+            return Err("only your own messages can be deleted for everyone".to_string());
+        }
+
+        let now = now_millis();
+        if now.saturating_sub(message.timestamp) > DM_DELETE_WINDOW_SECS * 1000 {
+            return Err("message is too old to delete for everyone".to_string());
+        }
+
+        let mut request = DMDeleteRequest {
+            message_id: message_id.clone(),
+            from_peer: local_peer_id.clone(),
+            to_peer: peer_id.clone(),
+            public_key: hex::encode(id.keypair.public().encode_protobuf()),
+            timestamp: now,
+            signature: String::new(),
+        };
+        request.signature = crate::verification::sign_dm_delete(&id.keypair, &request);
+        drop(identity);
+
+        state
+            .storage
+            .delete_dm_messages(&conversation_id, &[message_id])
+            .map_err(|e| format!("failed to delete message: {}", e))?;
+
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            let data = serde_json::to_vec(&GossipMessage::DMDelete(request))
+                .map_err(|e| format!("serialize error: {}", e))?;
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::SendMessage {
+                    topic: gossip::topic_for_dm(&local_peer_id, &peer_id),
+                    data,
+                })
+                .await;
+        }
+
+        Ok(())
+    })
+}
+
+// change the text of one of our own dms on both ends
+#[tauri::command]
+pub async fn edit_dm(
+    state: State<'_, AppState>,
+    peer_id: String,
+    message_id: String,
+    content: String,
+) -> Result<DirectMessage, String> {
+    ipc_log!("edit_dm", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let local_peer_id = id.peer_id.to_string();
+        drop(identity);
+
+        let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
+        let message = state
+            .storage
+            .edit_dm_message(&conversation_id, &message_id, &local_peer_id, &content)
+            .map_err(|e| format!("failed to edit message: {}", e))?
+            .ok_or("only your own messages can be edited")?;
+
+        let edit = DMEdit {
+            message_id,
+            from_peer: local_peer_id.clone(),
+            to_peer: peer_id.clone(),
+            content,
+            timestamp: now_millis(),
+        };
+
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            let data = serde_json::to_vec(&GossipMessage::DMEdit(edit))
+                .map_err(|e| format!("serialize error: {}", e))?;
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::SendMessage {
+                    topic: gossip::topic_for_dm(&local_peer_id, &peer_id),
+                    data,
+                })
+                .await;
+        }
+
+        Ok(message)
+    })
+}
+
+// add or take back our reaction to a dm, returns the message's reactions
+#[tauri::command]
+pub async fn react_dm(
+    state: State<'_, AppState>,
+    peer_id: String,
+    message_id: String,
+    emoji: String,
+    active: bool,
+) -> Result<Vec<DMReaction>, String> {
+    ipc_log!("react_dm", {
+        let emoji_len = emoji.chars().count();
+        if emoji_len == 0 || emoji_len > MAX_DM_REACTION_LEN {
+            return Err("invalid reaction".to_string());
+        }
+
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let local_peer_id = id.peer_id.to_string();
+        drop(identity);
+
+        let now = now_millis();
+        let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
+        let reactions = state
+            .storage
+            .set_dm_reaction(
+                &conversation_id,
+                &message_id,
+                &local_peer_id,
+                &emoji,
+                active,
+                now,
+            )
+            .map_err(|e| format!("failed to save reaction: {}", e))?
+            .ok_or("message not found")?;
+
+        let signal = DMReactionSignal {
+            message_id,
+            from_peer: local_peer_id.clone(),
+            to_peer: peer_id.clone(),
+            emoji,
+            active,
+            timestamp: now,
+        };
+
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            let data = serde_json::to_vec(&GossipMessage::DMReaction(signal))
+                .map_err(|e| format!("serialize error: {}", e))?;
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::SendMessage {
+                    topic: gossip::topic_for_dm(&local_peer_id, &peer_id),
+                    data,
+                })
+                .await;
+        }
+
+        Ok(reactions)
     })
 }
 
@@ -245,11 +725,12 @@ pub async fn delete_dm_conversation(
         // unsubscribe from the dm topic
         let node_handle = state.node_handle.lock().await;
         if let Some(ref handle) = *node_handle {
-            let topic = gossip::topic_for_dm(&local_peer_id, &peer_id);
-            let _ = handle
-                .command_tx
-                .send(NodeCommand::Unsubscribe { topic })
-                .await;
+            for topic in gossip::topics_for_dm(&local_peer_id, &peer_id) {
+                let _ = handle
+                    .command_tx
+                    .send(NodeCommand::Unsubscribe { topic })
+                    .await;
+            }
         }
 
         state
@@ -316,11 +797,12 @@ pub async fn open_dm_conversation(
             // subscribe to make sure we're listening
             let node_handle = state.node_handle.lock().await;
             if let Some(ref handle) = *node_handle {
-                let topic = gossip::topic_for_dm(&local_peer_id, &peer_id);
-                let _ = handle
-                    .command_tx
-                    .send(NodeCommand::Subscribe { topic })
-                    .await;
+                for topic in gossip::topics_for_dm(&local_peer_id, &peer_id) {
+                    let _ = handle
+                        .command_tx
+                        .send(NodeCommand::Subscribe { topic })
+                        .await;
+                }
 
                 // discover the peer via rendezvous to ensure wan connectivity
                 let discover_ns = format!("dusk/peer/{}", peer_id);
@@ -340,6 +822,8 @@ pub async fn open_dm_conversation(
             last_message: None,
             last_message_time: None,
             unread_count: 0,
+            pinned: false,
+            archived: false,
         };
 
         state
@@ -350,11 +834,12 @@ pub async fn open_dm_conversation(
         // subscribe to the dm topic so we receive messages
         let node_handle = state.node_handle.lock().await;
         if let Some(ref handle) = *node_handle {
-            let topic = gossip::topic_for_dm(&local_peer_id, &peer_id);
-            let _ = handle
-                .command_tx
-                .send(NodeCommand::Subscribe { topic })
-                .await;
+            for topic in gossip::topics_for_dm(&local_peer_id, &peer_id) {
+                let _ = handle
+                    .command_tx
+                    .send(NodeCommand::Subscribe { topic })
+                    .await;
+            }
 
             // discover the peer via rendezvous to establish wan connectivity
             // through the relay circuit before any messages are sent
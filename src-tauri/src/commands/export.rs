@@ -0,0 +1,678 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tauri::{Emitter, State};
+
+use super::ipc_log;
+use crate::catalog::Catalog;
+use crate::locale::Formatter;
+use crate::node::gossip;
+use crate::node::DuskEvent;
+use crate::protocol::messages::DirectMessage;
+use crate::AppState;
+
+// emit a progress event every this many messages
+const EXPORT_PROGRESS_INTERVAL: usize = 100;
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "bmp", "avif"];
+
+// gif cdns whose urls carry no extension, the same list the ui renders inline
+const GIF_CDN_HOSTS: &[&str] = &[
+    "static.klipy.com",
+    "media.tenor.com",
+    "media1.tenor.com",
+    "c.tenor.com",
+];
+
+// images larger than this stay links in an html export
+const MAX_INLINE_IMAGE_BYTES: usize = 8 * 1024 * 1024;
+// content types embedded as they are. svg can carry script, so it stays a link
+const INLINE_IMAGE_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/avif",
+];
+const IMAGE_FETCH_TIMEOUT_SECS: u64 = 15;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ExportFormat {
+    Json,
+    Text,
+    Html,
+}
+
+impl ExportFormat {
+    fn parse(format: &str) -> Result<Self, String> {
+        match format.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "text" | "txt" => Ok(Self::Text),
+            "html" => Ok(Self::Html),
+            other => Err(format!("unsupported export format: {}", other)),
+        }
+    }
+}
+
+// export a single dm conversation to a file chosen by the user
+// messages are streamed straight from sqlite so large histories never sit in memory,
+// returns the number of messages written. linked images are only fetched into
+// an html export when inline_images is set, fetching tells every host our ip
+#[tauri::command]
+pub async fn export_dm_conversation(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    peer_id: String,
+    format: String,
+    path: String,
+    inline_images: Option<bool>,
+) -> Result<usize, String> {
+    ipc_log!("export_dm_conversation", {
+        let format = ExportFormat::parse(&format)?;
+
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let local_peer_id = id.peer_id.to_string();
+        let local_display_name = id.display_name.clone();
+        drop(identity);
+
+        let settings = state.storage.load_settings().unwrap_or_default();
+        let dates = Formatter::from_settings(&settings.locale);
+        let text = Catalog::from_settings(&settings.locale);
+
+        let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
+        let meta = state
+            .storage
+            .load_dm_conversation(&conversation_id)
+            .map_err(|e| format!("failed to load conversation: {}", e))?;
+
+        let storage = state.storage.clone();
+        let header = ExportHeader {
+            conversation_id: conversation_id.clone(),
+            local_peer_id,
+            local_display_name,
+            peer_id: meta.peer_id,
+            peer_display_name: meta.display_name,
+            exported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+        };
+
+        // sqlite and file io are blocking, keep them off the async runtime
+        tauri::async_runtime::spawn_blocking(move || -> Result<usize, String> {
+            let total = storage
+                .count_dm_messages(&conversation_id)
+                .map_err(|e| format!("failed to count messages: {}", e))?;
+
+            let file = File::create(PathBuf::from(&path))
+                .map_err(|e| format!("failed to create export file: {}", e))?;
+            let mut out = BufWriter::new(file);
+
+            write_header(&mut out, format, &header, &dates, &text)
+                .map_err(|e| format!("failed to write export: {}", e))?;
+
+            let mut images = InlineImages::new(inline_images.unwrap_or(false))?;
+            let mut written = 0;
+            storage
+                .for_each_dm_message(&conversation_id, |msg| {
+                    write_message(&mut out, format, msg, &dates, &mut images, written == 0)?;
+                    written += 1;
+
+                    if written % EXPORT_PROGRESS_INTERVAL == 0 {
+                        let _ = app.emit(
+                            "dusk-event",
+                            DuskEvent::ExportProgress {
+                                conversation_id: conversation_id.clone(),
+                                written,
+                                total,
+                            },
+                        );
+                    }
+                    Ok(())
+                })
+                .map_err(|e| format!("failed to write export: {}", e))?;
+
+            write_footer(&mut out, format)
+                .and_then(|_| out.flush())
+                .map_err(|e| format!("failed to write export: {}", e))?;
+
+            let _ = app.emit(
+                "dusk-event",
+                DuskEvent::ExportProgress {
+                    conversation_id,
+                    written,
+                    total,
+                },
+            );
+
+            Ok(written)
+        })
+        .await
+        .map_err(|e| format!("export task failed: {}", e))?
+    })
+}
+
+struct ExportHeader {
+    conversation_id: String,
+    local_peer_id: String,
+    local_display_name: String,
+    peer_id: String,
+    peer_display_name: String,
+    exported_at: u64,
+}
+
+fn write_header(
+    out: &mut impl Write,
+    format: ExportFormat,
+    header: &ExportHeader,
+    dates: &Formatter,
+    text: &Catalog,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Json => {
+            let meta = serde_json::json!({
+                "conversation_id": header.conversation_id,
+                "local_peer_id": header.local_peer_id,
+                "local_display_name": header.local_display_name,
+                "peer_id": header.peer_id,
+                "peer_display_name": header.peer_display_name,
+                "exported_at": header.exported_at,
+            });
+            // open the object by hand so messages can be appended as they stream in
+            let meta = meta.to_string();
+            write!(out, "{},\"messages\":[", &meta[..meta.len() - 1])
+        }
+        ExportFormat::Text => {
+            writeln!(
+                out,
+                "{}",
+                text.text(
+                    "export.conversation_between",
+                    &[
+                        ("local", header.local_display_name.as_str()),
+                        ("local_id", header.local_peer_id.as_str()),
+                        ("peer", header.peer_display_name.as_str()),
+                        ("peer_id", header.peer_id.as_str()),
+                    ],
+                )
+            )?;
+            let exported = dates.date_time_zone(header.exported_at);
+            writeln!(
+                out,
+                "{}",
+                text.text("export.exported", &[("date", exported.as_str())])
+            )?;
+            writeln!(out)
+        }
+        ExportFormat::Html => {
+            writeln!(out, "<!doctype html>")?;
+            writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+            writeln!(
+                out,
+                "<title>{}</title>",
+                escape_html(&text.text(
+                    "export.conversation_with",
+                    &[("peer", header.peer_display_name.as_str())],
+                ))
+            )?;
+            writeln!(
+                out,
+                "<style>body{{font-family:sans-serif;max-width:720px;margin:2em auto}}\
+                 .msg{{margin:.75em 0}}.meta{{color:#888;font-size:.85em}}\
+                 img{{max-width:100%;border-radius:4px}}</style>"
+            )?;
+            writeln!(out, "</head><body>")?;
+            writeln!(
+                out,
+                "<h1>{} &amp; {}</h1>",
+                escape_html(&header.local_display_name),
+                escape_html(&header.peer_display_name)
+            )?;
+            let exported = dates.date_time_zone(header.exported_at);
+            writeln!(
+                out,
+                "<p class=\"meta\">{}</p>",
+                escape_html(&text.text("export.exported", &[("date", exported.as_str())]))
+            )
+        }
+    }
+}
+
+fn write_message(
+    out: &mut impl Write,
+    format: ExportFormat,
+    msg: &DirectMessage,
+    dates: &Formatter,
+    images: &mut InlineImages,
+    first: bool,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Json => {
+            if !first {
+                write!(out, ",")?;
+            }
+            serde_json::to_writer(&mut *out, msg).map_err(io::Error::from)
+        }
+        ExportFormat::Text => writeln!(
+            out,
+            "[{}] {}: {}",
+            dates.date_time(msg.timestamp),
+            msg.from_display_name,
+            msg.content
+        ),
+        ExportFormat::Html => {
+            writeln!(out, "<div class=\"msg\">")?;
+            writeln!(
+                out,
+                "<div class=\"meta\"><strong>{}</strong> {}</div>",
+                escape_html(&msg.from_display_name),
+                dates.date_time(msg.timestamp)
+            )?;
+            writeln!(
+                out,
+                "<div>{}</div>",
+                render_html_content(&msg.content, images)
+            )?;
+            writeln!(out, "</div>")
+        }
+    }
+}
+
+fn write_footer(out: &mut impl Write, format: ExportFormat) -> io::Result<()> {
+    match format {
+        ExportFormat::Json => write!(out, "]}}"),
+        ExportFormat::Text => Ok(()),
+        ExportFormat::Html => writeln!(out, "</body></html>"),
+    }
+}
+
+// links become anchors and linked images are embedded, so the export opens
+// the same offline and after the hosts drop them. an image that can't be
+// fetched stays a link
+fn render_html_content(content: &str, images: &mut InlineImages) -> String {
+    content
+        .split(' ')
+        .map(|word| {
+            if word.starts_with("http://") || word.starts_with("https://") {
+                let url = escape_html(word);
+                match is_image_url(word).then(|| images.data_uri(word)).flatten() {
+                    Some(data_uri) => {
+                        format!("<br><img src=\"{}\" alt=\"\"><br>", escape_html(&data_uri))
+                    }
+                    None => format!("<a href=\"{}\">{}</a>", url, url),
+                }
+            } else {
+                escape_html(word)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('\n', "<br>")
+}
+
+fn is_image_url(url: &str) -> bool {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = without_scheme.split(['/', '?', '#']).next().unwrap_or("");
+    if GIF_CDN_HOSTS.contains(&host) {
+        return true;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+    IMAGE_EXTENSIONS
+        .iter()
+        .any(|ext| path.ends_with(&format!(".{}", ext)))
+}
+
+// images linked from exported messages, fetched once per url. runs on the
+// export's blocking thread, so each fetch is driven to completion there.
+// without a client nothing is fetched and every image stays a link
+struct InlineImages {
+    client: Option<reqwest::Client>,
+    fetched: HashMap<String, Option<String>>,
+}
+
+impl InlineImages {
+    fn new(enabled: bool) -> Result<Self, String> {
+        let client = if enabled {
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(IMAGE_FETCH_TIMEOUT_SECS))
+                .build()
+                .map_err(|e| format!("failed to build http client: {}", e))?;
+            Some(client)
+        } else {
+            None
+        };
+        Ok(Self {
+            client,
+            fetched: HashMap::new(),
+        })
+    }
+
+    // the image as a data uri, none when inlining is off or it couldn't be
+    // fetched, isn't an image we embed or is too large
+    fn data_uri(&mut self, url: &str) -> Option<String> {
+        let client = self.client.as_ref()?;
+        if let Some(cached) = self.fetched.get(url) {
+            return cached.clone();
+        }
+        let data_uri = tauri::async_runtime::block_on(fetch_data_uri(client, url));
+        if data_uri.is_none() {
+            log::debug!("export: could not inline image {}", url);
+        }
+        self.fetched.insert(url.to_string(), data_uri.clone());
+        data_uri
+    }
+}
+
+async fn fetch_data_uri(client: &reqwest::Client, url: &str) -> Option<String> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .ok()?;
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_INLINE_IMAGE_BYTES as u64)
+    {
+        return None;
+    }
+    let mime = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|mime| mime.trim().to_lowercase())
+        .filter(|mime| INLINE_IMAGE_TYPES.contains(&mime.as_str()))?;
+    let bytes = response.bytes().await.ok()?;
+    if bytes.len() > MAX_INLINE_IMAGE_BYTES {
+        return None;
+    }
+    Some(format!("data:{};base64,{}", mime, BASE64.encode(&bytes)))
+}
+
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// compile a human-readable report of everything this client stores about
+// the local user and, optionally, about one specific peer
+#[tauri::command]
+pub async fn generate_data_report(
+    state: State<'_, AppState>,
+    peer_id: Option<String>,
+) -> Result<String, String> {
+    ipc_log!("generate_data_report", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let local_peer_id = id.peer_id.to_string();
+        let has_proof = id.verification_proof.is_some();
+        drop(identity);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let profile = state
+            .storage
+            .load_profile()
+            .map_err(|e| format!("failed to load profile: {}", e))?;
+        let settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+        let directory = state
+            .storage
+            .load_directory()
+            .map_err(|e| format!("failed to load directory: {}", e))?;
+        let conversations = state
+            .storage
+            .load_all_dm_conversations(true)
+            .map_err(|e| format!("failed to load dm conversations: {}", e))?;
+        let dates = Formatter::from_settings(&settings.locale);
+        let text = Catalog::from_settings(&settings.locale);
+
+        let mut report = String::new();
+        report.push_str(&format!("{}\n", text.text("report.title", &[])));
+        let generated = dates.date_time_zone(now);
+        report.push_str(&format!(
+            "{}\n\n",
+            text.text("report.generated", &[("date", generated.as_str())])
+        ));
+
+        // -- local user --
+        report.push_str(&format!(
+            "== {} ==\n",
+            text.text("report.section.identity", &[])
+        ));
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled("report.peer_id", &local_peer_id)
+        ));
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled("report.display_name", &profile.display_name)
+        ));
+        report.push_str(&format!("{}\n", text.labelled("report.bio", &profile.bio)));
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled("report.created", dates.date_time(profile.created_at))
+        ));
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled("report.proof_stored", text.yes_no(has_proof))
+        ));
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled("report.status", &settings.status)
+        ));
+        if !settings.status_message.is_empty() {
+            report.push_str(&format!(
+                "{}\n",
+                text.labelled("report.status_message", &settings.status_message)
+            ));
+        }
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled(
+                "report.relay_discoverable",
+                text.yes_no(settings.relay_discoverable)
+            )
+        ));
+        if let Some(ref relay) = settings.custom_relay_addr {
+            report.push_str(&format!(
+                "{}\n",
+                text.labelled("report.custom_relay", relay)
+            ));
+        }
+        report.push('\n');
+
+        // -- communities --
+        report.push_str(&format!(
+            "== {} ==\n",
+            text.text("report.section.communities", &[])
+        ));
+        let engine = state.crdt_engine.lock().await;
+        let mut shared_communities = Vec::new();
+        for community_id in engine.community_ids() {
+            let name = engine
+                .get_community_meta(&community_id)
+                .map(|m| m.name)
+                .unwrap_or_else(|_| community_id.clone());
+            let members = engine.get_members(&community_id).unwrap_or_default();
+            let roles = members
+                .iter()
+                .find(|m| m.peer_id == local_peer_id)
+                .map(|m| m.roles.join(", "))
+                .unwrap_or_default();
+
+            // count what we authored, the documents hold full message history
+            let mut authored = 0;
+            for channel in engine.get_channels(&community_id).unwrap_or_default() {
+                authored += engine
+                    .get_messages(&community_id, &channel.id, None, usize::MAX)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter(|m| m.author_id == local_peer_id)
+                    .count();
+            }
+
+            let member_count = members.len().to_string();
+            let authored = authored.to_string();
+            report.push_str(&format!(
+                "{}\n",
+                text.text(
+                    "report.community",
+                    &[
+                        ("name", name.as_str()),
+                        ("id", community_id.as_str()),
+                        ("members", member_count.as_str()),
+                        ("roles", roles.as_str()),
+                        ("authored", authored.as_str()),
+                    ],
+                )
+            ));
+
+            if let Some(ref target) = peer_id {
+                if members.iter().any(|m| &m.peer_id == target) {
+                    shared_communities.push(name);
+                }
+            }
+        }
+        drop(engine);
+        report.push('\n');
+
+        // -- direct messages --
+        report.push_str(&format!("== {} ==\n", text.text("report.section.dms", &[])));
+        let mut total_dms = 0;
+        for (conversation_id, _) in &conversations {
+            let (count, _, _) = state
+                .storage
+                .dm_conversation_stats(conversation_id)
+                .unwrap_or((0, None, None));
+            total_dms += count;
+        }
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled("report.conversations", conversations.len())
+        ));
+        report.push_str(&format!(
+            "{}\n\n",
+            text.labelled("report.stored_messages", total_dms)
+        ));
+
+        // -- peer directory --
+        report.push_str(&format!(
+            "== {} ==\n",
+            text.text("report.section.directory", &[])
+        ));
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled("report.known_peers", directory.len())
+        ));
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled(
+                "report.friends",
+                directory.values().filter(|e| e.is_friend).count()
+            )
+        ));
+        report.push_str(&format!(
+            "{}\n",
+            text.labelled(
+                "report.revoked_peers",
+                state.storage.count_revoked_peers().unwrap_or(0)
+            )
+        ));
+
+        // -- a specific peer --
+        if let Some(target) = peer_id {
+            report.push_str(&format!(
+                "\n== {} ==\n",
+                text.text("report.section.peer", &[("peer", target.as_str())])
+            ));
+
+            match directory.get(&target) {
+                Some(entry) => {
+                    report.push_str(&format!(
+                        "{}\n",
+                        text.labelled("report.display_name", &entry.display_name)
+                    ));
+                    report.push_str(&format!("{}\n", text.labelled("report.bio", &entry.bio)));
+                    let public_key = if entry.public_key.is_empty() {
+                        text.text("unknown", &[])
+                    } else {
+                        entry.public_key.clone()
+                    };
+                    report.push_str(&format!(
+                        "{}\n",
+                        text.labelled("report.public_key", public_key)
+                    ));
+                    report.push_str(&format!(
+                        "{}\n",
+                        text.labelled("report.last_seen", dates.date_time(entry.last_seen))
+                    ));
+                    report.push_str(&format!(
+                        "{}\n",
+                        text.labelled("report.friend", text.yes_no(entry.is_friend))
+                    ));
+                }
+                None => report.push_str(&format!(
+                    "{}\n",
+                    text.text("report.no_directory_entry", &[])
+                )),
+            }
+
+            let shared = if shared_communities.is_empty() {
+                text.text("none", &[])
+            } else {
+                shared_communities.join(", ")
+            };
+            report.push_str(&format!(
+                "{}\n",
+                text.labelled("report.shared_communities", shared)
+            ));
+
+            let conversation_id = gossip::dm_conversation_id(&local_peer_id, &target);
+            match state.storage.load_dm_conversation(&conversation_id) {
+                Ok(meta) => {
+                    let (count, first, last) = state
+                        .storage
+                        .dm_conversation_stats(&conversation_id)
+                        .unwrap_or((0, None, None));
+                    report.push_str(&format!("{}\n", text.labelled("report.dm_stored", count)));
+                    report.push_str(&format!(
+                        "{}\n",
+                        text.labelled("report.unread", meta.unread_count)
+                    ));
+                    if let (Some(first), Some(last)) = (first, last) {
+                        let first = dates.date_time(first);
+                        let last = dates.date_time(last);
+                        report.push_str(&format!(
+                            "{}\n",
+                            text.text(
+                                "report.dm_history",
+                                &[("first", first.as_str()), ("last", last.as_str())],
+                            )
+                        ));
+                    }
+                }
+                Err(_) => report.push_str(&format!("{}\n", text.text("report.no_dm", &[]))),
+            }
+        }
+
+        Ok(report)
+    })
+}
@@ -1,49 +1,52 @@
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
 use tauri::State;
 
+use crate::boot::{self, BootPhase};
+use crate::node::announce::AnnounceTrigger;
+use crate::node::cover::CoverTrafficStatus;
 use crate::node::gossip;
+use crate::node::power::{self, NetworkProfile, NetworkProfileStatus};
 use crate::node::NodeCommand;
-use crate::protocol::identity::{DirectoryEntry, DuskIdentity, PublicIdentity};
-use crate::protocol::messages::{GossipMessage, ProfileAnnouncement, ProfileRevocation};
-use crate::storage::UserSettings;
-use crate::verification::{self, ChallengeSubmission};
+use crate::protocol::community::CommunityLayout;
+use crate::protocol::identity::{
+    ContactCard, DirectoryEntry, DuskIdentity, KeyConflict, PublicIdentity, VerificationProof,
+    MAX_CONTACT_CARD_NAME_LEN,
+};
+use crate::storage::keystore;
+use crate::protocol::messages::{clean_status_text, GossipMessage, ProfileRevocation};
+use crate::storage::{
+    DiskStorage, SettingsProfile, UserSettings, MAX_SETTINGS_PROFILES, MAX_SETTINGS_PROFILE_NAME_LEN,
+};
+use crate::verification::{self, ChallengeSubmission, SafetyNumber};
 use crate::AppState;
 
 use super::community::broadcast_sync;
 use super::ipc_log;
 
-// build a signed profile announcement and publish it on the directory topic
-// so all connected peers immediately learn about the updated profile.
-// silently no-ops if the node isn't running yet.
-async fn announce_profile(id: &DuskIdentity, state: &AppState) {
-    let mut announcement = ProfileAnnouncement {
-        peer_id: id.peer_id.to_string(),
-        display_name: id.display_name.clone(),
-        bio: id.bio.clone(),
-        public_key: hex::encode(id.keypair.public().encode_protobuf()),
-        timestamp: SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64,
-        verification_proof: id.verification_proof.clone(),
-        signature: String::new(),
-    };
-    announcement.signature = verification::sign_announcement(&id.keypair, &announcement);
+// communities where we go by a per-community name, global renames leave them alone
+pub(crate) fn overridden_communities(state: &AppState, peer_id: &str) -> Vec<String> {
+    state
+        .storage
+        .load_peer_community_profiles(peer_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| p.community_id)
+        .collect()
+}
 
+// ask the node to announce our profile, it signs the announcement from what
+// is in storage and sends it once the announce policy allows. silently
+// no-ops if the node isn't running yet.
+async fn announce_profile(state: &AppState, trigger: AnnounceTrigger) {
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
-        let msg = GossipMessage::ProfileAnnounce(announcement);
-        if let Ok(data) = serde_json::to_vec(&msg) {
-            let _ = handle
-                .command_tx
-                .send(NodeCommand::SendMessage {
-                    topic: gossip::topic_for_directory(),
-                    data,
-                })
-                .await;
-        }
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::AnnounceProfile { trigger })
+            .await;
     }
 }
 
@@ -72,21 +75,243 @@ pub async fn load_identity(state: State<'_, AppState>) -> Result<Option<PublicId
     })
 }
 
+// portable installs must seal their keypair and keep caches on the stick
+#[tauri::command]
+pub async fn is_portable_mode() -> Result<bool, String> {
+    ipc_log!("is_portable_mode", Ok(crate::storage::portable_mode()))
+}
+
+// true when a stored identity exists but needs its passphrase to load, or
+// storage is encrypted and waiting for it
+#[tauri::command]
+pub async fn is_identity_locked(state: State<'_, AppState>) -> Result<bool, String> {
+    ipc_log!("is_identity_locked", {
+        if state.storage.is_storage_locked() {
+            return Ok(true);
+        }
+        if state.identity.lock().await.is_some() {
+            return Ok(false);
+        }
+        // portable installs seal a clear keypair on unlock before using it
+        Ok(state
+            .storage
+            .load_keypair()
+            .map(|bytes| keystore::is_sealed(&bytes) || crate::storage::portable_mode())
+            .unwrap_or(false))
+    })
+}
+
+// current startup phase, boot_phase events report later changes
+#[tauri::command]
+pub async fn get_boot_phase(state: State<'_, AppState>) -> Result<BootPhase, String> {
+    ipc_log!("get_boot_phase", Ok(*state.boot_phase.lock().await))
+}
+
+#[tauri::command]
+pub async fn unlock_identity(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    passphrase: String,
+) -> Result<PublicIdentity, String> {
+    ipc_log!("unlock_identity", {
+        let mut identity = state.identity.lock().await;
+        if let Some(ref id) = *identity {
+            return Ok(id.public_identity());
+        }
+
+        // an encrypted database opens with the same passphrase, the identity
+        // and everything else in it couldn't be read at startup
+        let database_locked = state.storage.is_database_locked();
+        if database_locked {
+            let storage = state.storage.clone();
+            let key = passphrase.clone();
+            tauri::async_runtime::spawn_blocking(move || storage.unlock_database(&key))
+                .await
+                .map_err(|e| format!("failed to unlock storage: {}", e))??;
+        }
+
+        let loaded = DuskIdentity::load_with_passphrase(&state.storage, Some(&passphrase))?;
+
+        // encrypted storage opens with the same passphrase, the community
+        // documents couldn't load at startup without it
+        if database_locked || state.storage.is_storage_locked() {
+            if state.storage.is_storage_locked() {
+                state.storage.unlock_at_rest(&passphrase)?;
+            }
+            let settings = state.storage.load_settings().unwrap_or_default();
+            crate::logging::apply(&settings.logging, &state.storage);
+            crate::catalog::apply(&settings.locale);
+            let mut engine = state.crdt_engine.lock().await;
+            engine.set_track_departures(settings.community_analytics);
+            if let Err(e) = engine.load_all() {
+                log::warn!("failed to load persisted communities: {}", e);
+            }
+        }
+
+        let public = loaded.public_identity();
+        *identity = Some(loaded);
+        drop(identity);
+
+        boot::identity_ready(&app).await;
+        Ok(public)
+    })
+}
+
+// seal the stored keypair under a new passphrase, or store it in the clear
+// again when none is given (not allowed in portable mode)
+#[tauri::command]
+pub async fn set_identity_passphrase(
+    state: State<'_, AppState>,
+    passphrase: Option<String>,
+) -> Result<(), String> {
+    ipc_log!("set_identity_passphrase", {
+        require_passphrase_if_portable(&passphrase)?;
+
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+
+        // encrypted storage follows the identity's passphrase
+        if state.storage.is_database_encrypted() || state.storage.at_rest_encrypted() {
+            let passphrase = passphrase
+                .as_deref()
+                .ok_or("encrypted storage needs a passphrase")?;
+            if state.storage.is_database_encrypted() {
+                state.storage.set_database_encryption(Some(passphrase))?;
+            }
+            if state.storage.at_rest_encrypted() {
+                state.storage.encrypt_at_rest(passphrase)?;
+            }
+        }
+        id.save_keypair(&state.storage, passphrase.as_deref())
+    })
+}
+
+#[tauri::command]
+pub async fn is_storage_encrypted(state: State<'_, AppState>) -> Result<bool, String> {
+    ipc_log!(
+        "is_storage_encrypted",
+        Ok(state.storage.at_rest_encrypted())
+    )
+}
+
+// encrypt community documents, notes and dm text at rest under the identity's
+// passphrase, existing rows included. the identity must already be sealed
+// with it so both unlock together at startup. returns how many rows were
+// encrypted
+#[tauri::command]
+pub async fn encrypt_storage(
+    state: State<'_, AppState>,
+    passphrase: String,
+) -> Result<usize, String> {
+    ipc_log!("encrypt_storage", {
+        let _identity = state.identity.lock().await;
+        let stored = state
+            .storage
+            .load_keypair()
+            .map_err(|e| format!("failed to load identity: {}", e))?;
+        if !keystore::is_sealed(&stored) {
+            return Err("set an identity passphrase before encrypting storage".to_string());
+        }
+        keystore::open(&passphrase, &stored)?;
+
+        let storage = state.storage.clone();
+        let resealed =
+            tauri::async_runtime::spawn_blocking(move || storage.encrypt_at_rest(&passphrase))
+                .await
+                .map_err(|e| format!("failed to encrypt storage: {}", e))??;
+
+        // log files would keep in the clear what was just sealed
+        let settings = state.storage.load_settings().unwrap_or_default();
+        crate::logging::apply(&settings.logging, &state.storage);
+        crate::logging::clear_files(&state.storage.log_dir());
+        Ok(resealed)
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseEncryption {
+    // the build includes sqlcipher, without it the setting can't be changed
+    // and an encrypted database can't be opened
+    pub available: bool,
+    pub enabled: bool,
+    // encrypted and waiting for unlock_identity
+    pub locked: bool,
+}
+
+fn database_encryption(state: &AppState) -> DatabaseEncryption {
+    DatabaseEncryption {
+        available: DiskStorage::database_encryption_available(),
+        enabled: state.storage.is_database_encrypted(),
+        locked: state.storage.is_database_locked(),
+    }
+}
+
+#[tauri::command]
+pub async fn get_database_encryption(
+    state: State<'_, AppState>,
+) -> Result<DatabaseEncryption, String> {
+    ipc_log!("get_database_encryption", Ok(database_encryption(&state)))
+}
+
+// encrypt the whole database with sqlcipher under the identity's passphrase,
+// or turn that off again when none is given. as with encrypt_storage the
+// identity must already be sealed with the passphrase so both unlock together
+#[tauri::command]
+pub async fn set_database_encryption(
+    state: State<'_, AppState>,
+    passphrase: Option<String>,
+) -> Result<DatabaseEncryption, String> {
+    ipc_log!("set_database_encryption", {
+        let _identity = state.identity.lock().await;
+        if let Some(ref passphrase) = passphrase {
+            let stored = state
+                .storage
+                .load_keypair()
+                .map_err(|e| format!("failed to load identity: {}", e))?;
+            if !keystore::is_sealed(&stored) {
+                return Err("set an identity passphrase before encrypting the database".to_string());
+            }
+            keystore::open(passphrase, &stored)?;
+        }
+
+        let storage = state.storage.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            storage.set_database_encryption(passphrase.as_deref())
+        })
+        .await
+        .map_err(|e| format!("failed to encrypt database: {}", e))??;
+        Ok(database_encryption(&state))
+    })
+}
+
+// phrase for the keyboard-only verification challenge
+#[tauri::command]
+pub async fn get_verification_prompt() -> Result<String, String> {
+    ipc_log!("get_verification_prompt", {
+        Ok(verification::keystroke_prompt())
+    })
+}
+
 #[tauri::command]
 pub async fn create_identity(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     display_name: String,
     bio: Option<String>,
     challenge_data: Option<ChallengeSubmission>,
+    passphrase: Option<String>,
 ) -> Result<PublicIdentity, String> {
     ipc_log!("create_identity", {
+        require_passphrase_if_portable(&passphrase)?;
+
         // require challenge data and re-validate behavioral analysis in rust
         let challenge = challenge_data.ok_or("verification required")?;
         let result = verification::analyze_challenge(&challenge);
         if !result.is_human {
+            *state.verification_failures.lock().await += 1;
             Err("verification failed".to_string())
         } else {
-            let mut new_identity = DuskIdentity::generate(&display_name, &bio.unwrap_or_default());
+            let new_identity = DuskIdentity::generate(&display_name, &bio.unwrap_or_default());
 
             // generate a cryptographic proof binding the verification to this keypair
             let proof = verification::generate_proof(
@@ -95,31 +320,103 @@ pub async fn create_identity(
                 &new_identity.peer_id.to_string(),
             )?;
 
-            state
-                .storage
-                .save_verification_proof(&proof)
-                .map_err(|e| format!("failed to save verification proof: {}", e))?;
-
-            new_identity.verification_proof = Some(proof);
-            new_identity.save(&state.storage)?;
-
-            // also save initial settings with this display name so they're in sync
-            let mut settings = state.storage.load_settings().unwrap_or_default();
-            settings.display_name = display_name.clone();
-            state
-                .storage
-                .save_settings(&settings)
-                .map_err(|e| format!("failed to save initial settings: {}", e))?;
+            let public =
+                store_new_identity(&state, new_identity, proof, display_name, passphrase).await?;
+            boot::identity_ready(&app).await;
+            Ok(public)
+        }
+    })
+}
 
-            let public = new_identity.public_identity();
-            let mut identity = state.identity.lock().await;
-            *identity = Some(new_identity);
+// anti-bot fallback once the behavioral challenge has failed repeatedly,
+// the user spends cpu time on a puzzle instead
+#[tauri::command]
+pub async fn create_identity_with_pow(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    display_name: String,
+    bio: Option<String>,
+    passphrase: Option<String>,
+) -> Result<PublicIdentity, String> {
+    ipc_log!("create_identity_with_pow", {
+        require_passphrase_if_portable(&passphrase)?;
 
-            Ok(public)
+        let failures = *state.verification_failures.lock().await;
+        if failures < verification::pow::POW_FALLBACK_AFTER_FAILURES {
+            return Err("proof of work is only offered after repeated verification failures".to_string());
         }
+
+        let difficulty = state
+            .storage
+            .load_settings()
+            .map(|s| s.pow_difficulty)
+            .unwrap_or(verification::pow::DEFAULT_POW_DIFFICULTY);
+
+        let new_identity = DuskIdentity::generate(&display_name, &bio.unwrap_or_default());
+        let keypair = new_identity.keypair.clone();
+        let peer_id = new_identity.peer_id.to_string();
+        let proof = tokio::task::spawn_blocking(move || {
+            verification::generate_pow_proof(&keypair, &peer_id, difficulty)
+        })
+        .await
+        .map_err(|e| format!("failed to run proof of work: {}", e))??;
+
+        let public =
+            store_new_identity(&state, new_identity, proof, display_name, passphrase).await?;
+        boot::identity_ready(&app).await;
+        Ok(public)
     })
 }
 
+// a portable install may be left on any machine, so its key is never stored in the clear
+fn require_passphrase_if_portable(passphrase: &Option<String>) -> Result<(), String> {
+    if crate::storage::portable_mode() && passphrase.is_none() {
+        return Err("a passphrase is required in portable mode".to_string());
+    }
+    Ok(())
+}
+
+// persist a freshly verified identity and make it the active one
+async fn store_new_identity(
+    state: &AppState,
+    mut new_identity: DuskIdentity,
+    proof: VerificationProof,
+    display_name: String,
+    passphrase: Option<String>,
+) -> Result<PublicIdentity, String> {
+    // creating an identity while another is loaded switches to it, nothing
+    // of the old one may carry over
+    if state.identity.lock().await.is_some() {
+        state.reset().await?;
+    }
+
+    new_identity.save_keypair(&state.storage, passphrase.as_deref())?;
+
+    state
+        .storage
+        .save_verification_proof(&proof)
+        .map_err(|e| format!("failed to save verification proof: {}", e))?;
+
+    new_identity.verification_proof = Some(proof);
+    new_identity.save(&state.storage)?;
+
+    // also save initial settings with this display name so they're in sync
+    let mut settings = state.storage.load_settings().unwrap_or_default();
+    settings.display_name = display_name;
+    state
+        .storage
+        .save_settings(&settings)
+        .map_err(|e| format!("failed to save initial settings: {}", e))?;
+
+    *state.verification_failures.lock().await = 0;
+
+    let public = new_identity.public_identity();
+    let mut identity = state.identity.lock().await;
+    *identity = Some(new_identity);
+
+    Ok(public)
+}
+
 #[tauri::command]
 pub async fn update_display_name(state: State<'_, AppState>, name: String) -> Result<(), String> {
     ipc_log!("update_display_name", {
@@ -130,13 +427,17 @@ pub async fn update_display_name(state: State<'_, AppState>, name: String) -> Re
             id.display_name = name.clone();
             id.save(&state.storage)?;
             peer_id_str = id.peer_id.to_string();
-            announce_profile(id, &state).await;
+            announce_profile(&state, AnnounceTrigger::ProfileChange).await;
         }
 
         // propagate the name change into every community crdt
         let updated_communities = {
             let mut engine = state.crdt_engine.lock().await;
-            engine.update_member_display_name_everywhere(&peer_id_str, &name)
+            engine.update_member_display_name_everywhere(
+                &peer_id_str,
+                &name,
+                &overridden_communities(&state, &peer_id_str),
+            )
         };
         for cid in updated_communities {
             broadcast_sync(&state, &cid).await;
@@ -163,13 +464,17 @@ pub async fn update_profile(
             id.save(&state.storage)?;
             peer_id_str = id.peer_id.to_string();
             public = id.public_identity();
-            announce_profile(id, &state).await;
+            announce_profile(&state, AnnounceTrigger::ProfileChange).await;
         }
 
         // propagate the name change into every community crdt
         let updated_communities = {
             let mut engine = state.crdt_engine.lock().await;
-            engine.update_member_display_name_everywhere(&peer_id_str, &display_name)
+            engine.update_member_display_name_everywhere(
+                &peer_id_str,
+                &display_name,
+                &overridden_communities(&state, &peer_id_str),
+            )
         };
         for cid in updated_communities {
             broadcast_sync(&state, &cid).await;
@@ -196,12 +501,33 @@ pub async fn save_settings(
 ) -> Result<(), String> {
     ipc_log!("save_settings", {
         // check if status changed so we can broadcast the new presence
-        let old_status = state
-            .storage
-            .load_settings()
-            .map(|s| s.status)
-            .unwrap_or_else(|_| "online".to_string());
+        let old_settings = state.storage.load_settings().ok();
+        let old_status = old_settings
+            .as_ref()
+            .map(|s| s.status.clone())
+            .unwrap_or_else(|| "online".to_string());
         let status_changed = old_status != settings.status;
+        // custom status goes out with presence and the signed announcement
+        let status_text_changed = old_settings
+            .as_ref()
+            .map(|s| s.shared_status() != settings.shared_status())
+            .unwrap_or(true);
+        // turning the global announcement back on should reach peers now
+        let announce_enabled =
+            settings.announce_globally && !old_settings.is_none_or(|s| s.announce_globally);
+
+        // persist first, presence and announcements read the new values from storage
+        state
+            .storage
+            .save_settings(&settings)
+            .map_err(|e| format!("failed to save settings: {}", e))?;
+        if let Some(ref name) = settings.active_profile {
+            let profile = SettingsProfile::capture(name, &settings, now_millis());
+            state
+                .storage
+                .save_settings_profile(&profile)
+                .map_err(|e| format!("failed to save settings profile: {}", e))?;
+        }
 
         // also update the identity display name if it changed
         let mut identity = state.identity.lock().await;
@@ -214,11 +540,11 @@ pub async fn save_settings(
             }
         }
 
-        // re-announce if the display name was updated through settings
+        // re-announce if the display name or custom status was updated through settings
         let peer_id_str = identity.as_ref().map(|id| id.peer_id.to_string());
-        if name_changed {
-            if let Some(id) = identity.as_ref() {
-                announce_profile(id, &state).await;
+        if name_changed || status_text_changed || announce_enabled {
+            if identity.is_some() {
+                announce_profile(&state, AnnounceTrigger::ProfileChange).await;
             }
         }
         drop(identity);
@@ -228,7 +554,11 @@ pub async fn save_settings(
             if let Some(ref pid) = peer_id_str {
                 let updated_communities = {
                     let mut engine = state.crdt_engine.lock().await;
-                    engine.update_member_display_name_everywhere(pid, &settings.display_name)
+                    engine.update_member_display_name_everywhere(
+                        pid,
+                        &settings.display_name,
+                        &overridden_communities(&state, pid),
+                    )
                 };
                 for cid in updated_communities {
                     broadcast_sync(&state, &cid).await;
@@ -236,8 +566,8 @@ pub async fn save_settings(
             }
         }
 
-        // broadcast presence if status changed
-        if status_changed {
+        // broadcast presence if status or custom status changed
+        if status_changed || status_text_changed {
             use crate::node::NodeCommand;
             use crate::protocol::messages::PeerStatus;
 
@@ -259,13 +589,231 @@ pub async fn save_settings(
             }
         }
 
+        state
+            .audio_processor
+            .lock()
+            .await
+            .configure(settings.noise_suppression, settings.auto_gain_control);
+        state.cover_traffic.set_enabled(settings.cover_traffic);
+        crate::logging::apply(&settings.logging, &state.storage);
+        crate::catalog::apply(&settings.locale);
+        state
+            .crdt_engine
+            .lock()
+            .await
+            .set_track_departures(settings.community_analytics);
+
+        // no-op unless the tokenizer changed, otherwise the search index is rebuilt
+        let storage = state.storage.clone();
+        let tokenizer = settings.search_tokenizer;
+        tauri::async_runtime::spawn_blocking(move || storage.set_search_tokenizer(tokenizer))
+            .await
+            .map_err(|e| format!("failed to rebuild search index: {}", e))?
+            .map_err(|e| format!("failed to rebuild search index: {}", e))?;
+
+        Ok(())
+    })
+}
+
+const SETTINGS_EXPORT_VERSION: u32 = 1;
+
+// settings file for moving preferences to another install. identity, keys
+// and message history are not part of it
+#[derive(Debug, Serialize, Deserialize)]
+struct SettingsExport {
+    version: u32,
+    settings: UserSettings,
+    #[serde(default)]
+    community_layout: CommunityLayout,
+    #[serde(default)]
+    profiles: Vec<SettingsProfile>,
+}
+
+#[tauri::command]
+pub async fn export_settings(state: State<'_, AppState>) -> Result<String, String> {
+    ipc_log!("export_settings", {
+        let export = SettingsExport {
+            version: SETTINGS_EXPORT_VERSION,
+            settings: state
+                .storage
+                .load_settings()
+                .map_err(|e| format!("failed to load settings: {}", e))?,
+            community_layout: state
+                .storage
+                .load_community_layout()
+                .map_err(|e| format!("failed to load community layout: {}", e))?,
+            profiles: state
+                .storage
+                .load_settings_profiles()
+                .map_err(|e| format!("failed to load settings profiles: {}", e))?,
+        };
+        serde_json::to_string_pretty(&export)
+            .map_err(|e| format!("failed to serialize settings: {}", e))
+    })
+}
+
+// restores the community layout right away and hands the settings back, the
+// frontend applies them through save_settings so presence and the other side
+// effects run as usual
+#[tauri::command]
+pub async fn import_settings(
+    state: State<'_, AppState>,
+    data: String,
+) -> Result<UserSettings, String> {
+    ipc_log!("import_settings", {
+        let export: SettingsExport =
+            serde_json::from_str(&data).map_err(|e| format!("invalid settings file: {}", e))?;
+        if export.version > SETTINGS_EXPORT_VERSION {
+            return Err("settings file is from a newer version of dusk".to_string());
+        }
+        state
+            .storage
+            .save_community_layout(&export.community_layout)
+            .map_err(|e| format!("failed to save community layout: {}", e))?;
+        for profile in &export.profiles {
+            if check_profile_name(&profile.name).is_err() {
+                continue;
+            }
+            state
+                .storage
+                .save_settings_profile(profile)
+                .map_err(|e| format!("failed to save settings profile: {}", e))?;
+        }
+        Ok(export.settings)
+    })
+}
+
+// -- settings profiles --
+
+#[tauri::command]
+pub async fn list_settings_profiles(
+    state: State<'_, AppState>,
+) -> Result<Vec<SettingsProfile>, String> {
+    ipc_log!("list_settings_profiles", {
+        state
+            .storage
+            .load_settings_profiles()
+            .map_err(|e| format!("failed to load settings profiles: {}", e))
+    })
+}
+
+// store the current ui-facing settings under name, replacing a profile of
+// the same name, and make it the active one
+#[tauri::command]
+pub async fn save_settings_profile(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<SettingsProfile, String> {
+    ipc_log!("save_settings_profile", {
+        let name = check_profile_name(&name)?;
+        let profiles = state
+            .storage
+            .load_settings_profiles()
+            .map_err(|e| format!("failed to load settings profiles: {}", e))?;
+        if profiles.len() >= MAX_SETTINGS_PROFILES && !profiles.iter().any(|p| p.name == name) {
+            return Err(format!(
+                "at most {} settings profiles can be kept",
+                MAX_SETTINGS_PROFILES
+            ));
+        }
+
+        let mut settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+        let profile = SettingsProfile::capture(&name, &settings, now_millis());
+        state
+            .storage
+            .save_settings_profile(&profile)
+            .map_err(|e| format!("failed to save settings profile: {}", e))?;
+        settings.active_profile = Some(name);
+        state
+            .storage
+            .save_settings(&settings)
+            .map_err(|e| format!("failed to save settings: {}", e))?;
+        Ok(profile)
+    })
+}
+
+// swap in a profile's ui-facing settings, returns the settings as they now
+// stand. nothing outside the profile changes, so none of save_settings'
+// network side effects apply
+#[tauri::command]
+pub async fn switch_settings_profile(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<UserSettings, String> {
+    ipc_log!("switch_settings_profile", {
+        let profile = state
+            .storage
+            .load_settings_profiles()
+            .map_err(|e| format!("failed to load settings profiles: {}", e))?
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or("settings profile not found")?;
+
+        let mut settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+        profile.apply_to(&mut settings);
         state
             .storage
             .save_settings(&settings)
-            .map_err(|e| format!("failed to save settings: {}", e))
+            .map_err(|e| format!("failed to save settings: {}", e))?;
+        Ok(settings)
     })
 }
 
+// deleting the active profile keeps its settings in place, they just stop
+// being saved back to it
+#[tauri::command]
+pub async fn delete_settings_profile(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<(), String> {
+    ipc_log!("delete_settings_profile", {
+        state
+            .storage
+            .delete_settings_profile(&name)
+            .map_err(|e| format!("failed to delete settings profile: {}", e))?;
+
+        let mut settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+        if settings.active_profile.as_deref() == Some(name.as_str()) {
+            settings.active_profile = None;
+            state
+                .storage
+                .save_settings(&settings)
+                .map_err(|e| format!("failed to save settings: {}", e))?;
+        }
+        Ok(())
+    })
+}
+
+fn check_profile_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("profile name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_SETTINGS_PROFILE_NAME_LEN {
+        return Err(format!(
+            "profile names are limited to {} characters",
+            MAX_SETTINGS_PROFILE_NAME_LEN
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
 // -- user directory commands --
 
 #[tauri::command]
@@ -283,32 +831,71 @@ pub async fn get_known_peers(state: State<'_, AppState>) -> Result<Vec<Directory
     })
 }
 
+// drop strangers not seen for older_than_days from the directory, the
+// directory_ttl_days setting when not given. returns how many were removed
+#[tauri::command]
+pub async fn prune_directory(
+    state: State<'_, AppState>,
+    older_than_days: Option<u32>,
+) -> Result<usize, String> {
+    ipc_log!("prune_directory", {
+        let days = match older_than_days {
+            Some(days) => days,
+            None => {
+                let ttl = state
+                    .storage
+                    .load_settings()
+                    .map_err(|e| format!("failed to load settings: {}", e))?
+                    .directory_ttl_days;
+                if ttl == 0 {
+                    return Ok(0);
+                }
+                ttl
+            }
+        };
+        prune_stale_directory(&state, days).await
+    })
+}
+
+// friends, dm peers and members of communities we hold are never pruned
+pub(crate) async fn prune_stale_directory(state: &AppState, days: u32) -> Result<usize, String> {
+    let cutoff = now_millis().saturating_sub(days as u64 * 86_400_000);
+    let protected = state.crdt_engine.lock().await.all_member_ids();
+    state
+        .storage
+        .prune_directory(cutoff, &protected)
+        .map_err(|e| format!("failed to prune directory: {}", e))
+}
+
+const DEFAULT_DIRECTORY_PAGE: usize = 50;
+const MAX_DIRECTORY_PAGE: usize = 200;
+
+// most recently seen first, a page at a time. rendezvous placeholders are
+// left out unless asked for, they carry nothing to match on but the peer id
 #[tauri::command]
 pub async fn search_directory(
     state: State<'_, AppState>,
     query: String,
+    include_placeholders: Option<bool>,
+    limit: Option<usize>,
+    offset: Option<usize>,
 ) -> Result<Vec<DirectoryEntry>, String> {
     ipc_log!("search_directory", {
         let query_trimmed = query.trim().to_string();
+        let include_placeholders = include_placeholders.unwrap_or(false);
+        let limit = limit
+            .unwrap_or(DEFAULT_DIRECTORY_PAGE)
+            .min(MAX_DIRECTORY_PAGE);
+        let offset = offset.unwrap_or(0);
 
         // local search first
-        let entries = state
+        let results = state
             .storage
-            .load_directory()
-            .map_err(|e| format!("failed to load directory: {}", e))?;
-
-        let query_lower = query_trimmed.to_lowercase();
-        let mut results: Vec<DirectoryEntry> = entries
-            .into_values()
-            .filter(|entry| {
-                entry.display_name.to_lowercase().contains(&query_lower)
-                    || entry.peer_id.to_lowercase().contains(&query_lower)
-            })
-            .collect();
-        results.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+            .search_directory_entries(&query_trimmed, include_placeholders, limit, offset)
+            .map_err(|e| format!("failed to search directory: {}", e))?;
 
-        // relay fallback when local results are sparse
-        if results.len() < 5 && !query_trimmed.is_empty() {
+        // relay fallback when the first page of local results is sparse
+        if offset == 0 && results.len() < 5 && !query_trimmed.is_empty() {
             let node_handle = state.node_handle.lock().await;
             if let Some(ref handle) = *node_handle {
                 let (tx, rx) = tokio::sync::oneshot::channel();
@@ -334,6 +921,11 @@ pub async fn search_directory(
                     let mut dial_addrs: Vec<String> = Vec::new();
 
                     for entry in relay_entries {
+                        // the relay may still list identities that were revoked
+                        if state.storage.is_peer_revoked(&entry.peer_id) {
+                            continue;
+                        }
+
                         // upsert as stub — empty bio/public_key means never directly connected
                         let stub = DirectoryEntry {
                             peer_id: entry.peer_id.clone(),
@@ -342,6 +934,12 @@ pub async fn search_directory(
                             public_key: String::new(),
                             last_seen: entry.last_seen.saturating_mul(1000).max(now - 86_400_000),
                             is_friend: false,
+                            verified: false,
+                            activity: None,
+                            status_message: String::new(),
+                            status_emoji: String::new(),
+                            key_verified: false,
+                            placeholder: false,
                         };
                         // preserve existing local data if we already know this peer
                         let _ = state.storage.save_directory_entry_if_new(&stub);
@@ -366,16 +964,10 @@ pub async fn search_directory(
                     }
 
                     // re-run local search to get merged results
-                    let entries2 = state.storage.load_directory().unwrap_or_default();
-                    let mut results2: Vec<DirectoryEntry> = entries2
-                        .into_values()
-                        .filter(|entry| {
-                            entry.display_name.to_lowercase().contains(&query_lower)
-                                || entry.peer_id.to_lowercase().contains(&query_lower)
-                        })
-                        .collect();
-                    results2.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
-                    return Ok(results2);
+                    return state
+                        .storage
+                        .search_directory_entries(&query_trimmed, include_placeholders, limit, 0)
+                        .map_err(|e| format!("failed to search directory: {}", e));
                 }
             }
         }
@@ -387,22 +979,10 @@ pub async fn search_directory(
 #[tauri::command]
 pub async fn get_friends(state: State<'_, AppState>) -> Result<Vec<DirectoryEntry>, String> {
     ipc_log!("get_friends", {
-        let entries = state
+        state
             .storage
-            .load_directory()
-            .map_err(|e| format!("failed to load directory: {}", e))?;
-
-        let mut friends: Vec<DirectoryEntry> = entries
-            .into_values()
-            .filter(|entry| entry.is_friend)
-            .collect();
-
-        friends.sort_by(|a, b| {
-            a.display_name
-                .to_lowercase()
-                .cmp(&b.display_name.to_lowercase())
-        });
-        Ok(friends)
+            .load_friends()
+            .map_err(|e| format!("failed to load friends: {}", e))
     })
 }
 
@@ -414,8 +994,13 @@ pub async fn add_friend(state: State<'_, AppState>, peer_id: String) -> Result<(
             .set_friend_status(&peer_id, true)
             .map_err(|e| format!("failed to add friend: {}", e));
 
-        // explicitly try to discover new friend over rendezvous
+        // explicitly try to discover new friend over rendezvous, and join
+        // the dm topics so their first message skips the inbox
         if res.is_ok() {
+            let local_peer_id = {
+                let identity = state.identity.lock().await;
+                identity.as_ref().map(|id| id.peer_id.to_string())
+            };
             let node_handle = state.node_handle.lock().await;
             if let Some(ref handle) = *node_handle {
                 let _ = handle
@@ -424,6 +1009,14 @@ pub async fn add_friend(state: State<'_, AppState>, peer_id: String) -> Result<(
                         namespace: format!("dusk/peer/{}", peer_id),
                     })
                     .await;
+                if let Some(ref local_peer_id) = local_peer_id {
+                    for topic in gossip::topics_for_dm(local_peer_id, &peer_id) {
+                        let _ = handle
+                            .command_tx
+                            .send(crate::node::NodeCommand::Subscribe { topic })
+                            .await;
+                    }
+                }
             }
         }
 
@@ -453,6 +1046,274 @@ pub async fn remove_friend(state: State<'_, AppState>, peer_id: String) -> Resul
     res
 }
 
+// -- contact cards --
+
+// sign a card for a contact with our own key
+fn sign_contact_card(
+    id: &DuskIdentity,
+    peer_id: String,
+    public_key: String,
+    display_name: String,
+) -> ContactCard {
+    let mut card = ContactCard {
+        peer_id,
+        public_key,
+        display_name,
+        signer: id.peer_id.to_string(),
+        signer_key: hex::encode(id.keypair.public().encode_protobuf()),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64,
+        signature: String::new(),
+    };
+    card.signature = verification::sign_contact_card(&id.keypair, &card);
+    card
+}
+
+// our own contact card, encoded for sharing as text or a qr code
+#[tauri::command]
+pub async fn export_my_contact_card(state: State<'_, AppState>) -> Result<String, String> {
+    ipc_log!("export_my_contact_card", {
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+
+        let card = sign_contact_card(
+            id,
+            id.peer_id.to_string(),
+            hex::encode(id.keypair.public().encode_protobuf()),
+            id.display_name.clone(),
+        );
+        Ok(card.encode())
+    })
+}
+
+// pass on a contact from the directory, signed by us as the introducer
+#[tauri::command]
+pub async fn export_contact_card(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<String, String> {
+    ipc_log!("export_contact_card", {
+        let entry = state
+            .storage
+            .load_directory_entry(&peer_id)
+            .map_err(|e| format!("failed to load directory: {}", e))?
+            .ok_or("peer not found in directory")?;
+        if entry.public_key.is_empty() {
+            return Err("public key for this peer is not known yet".to_string());
+        }
+
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+
+        let card = sign_contact_card(id, entry.peer_id, entry.public_key, entry.display_name);
+        Ok(card.encode())
+    })
+}
+
+// check a contact card's signature and add its peer to the directory.
+// peers that announced themselves keep their profile, placeholders from
+// discovery take the card's key and name
+#[tauri::command]
+pub async fn import_contact_card(
+    state: State<'_, AppState>,
+    card: String,
+) -> Result<DirectoryEntry, String> {
+    ipc_log!("import_contact_card", {
+        let card = ContactCard::decode(&card)?;
+        if !verification::verify_contact_card(&card) {
+            return Err("contact card signature is invalid".to_string());
+        }
+
+        let local_peer_id = {
+            let identity = state.identity.lock().await;
+            identity.as_ref().map(|id| id.peer_id.to_string())
+        };
+        if local_peer_id.as_deref() == Some(card.peer_id.as_str()) {
+            return Err("this is your own contact card".to_string());
+        }
+
+        let existing = state
+            .storage
+            .load_directory_entry(&card.peer_id)
+            .map_err(|e| format!("failed to load directory: {}", e))?;
+
+        let mut display_name = clean_status_text(&card.display_name, MAX_CONTACT_CARD_NAME_LEN);
+        if display_name.is_empty() {
+            display_name = card.peer_id.clone();
+        }
+
+        let entry = match existing {
+            Some(entry) if !entry.public_key.is_empty() => entry,
+            Some(mut entry) => {
+                entry.public_key = card.public_key;
+                entry.display_name = display_name;
+                entry.placeholder = false;
+                entry
+            }
+            None => DirectoryEntry {
+                display_name,
+                peer_id: card.peer_id,
+                bio: String::new(),
+                public_key: card.public_key,
+                last_seen: 0,
+                is_friend: false,
+                verified: false,
+                activity: None,
+                status_message: String::new(),
+                status_emoji: String::new(),
+                key_verified: false,
+                placeholder: false,
+            },
+        };
+
+        state
+            .storage
+            .save_directory_entry(&entry)
+            .map_err(|e| format!("failed to save contact: {}", e))?;
+
+        // look for the new contact right away instead of waiting for an announcement
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::DiscoverRendezvous {
+                    namespace: format!("dusk/peer/{}", entry.peer_id),
+                })
+                .await;
+        }
+
+        Ok(entry)
+    })
+}
+
+// -- safety numbers --
+
+// the number to compare with a peer in person or over another channel
+#[tauri::command]
+pub async fn get_safety_number(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<SafetyNumber, String> {
+    ipc_log!("get_safety_number", {
+        let entry = state
+            .storage
+            .load_directory_entry(&peer_id)
+            .map_err(|e| format!("failed to load directory: {}", e))?
+            .ok_or("peer not found in directory")?;
+        if entry.public_key.is_empty() {
+            return Err("public key for this peer is not known yet".to_string());
+        }
+
+        let identity = state.identity.lock().await;
+        let id = identity.as_ref().ok_or("no identity loaded")?;
+        let number = verification::safety_number(
+            &hex::encode(id.keypair.public().encode_protobuf()),
+            &id.peer_id.to_string(),
+            &entry.public_key,
+            &entry.peer_id,
+        )
+        .ok_or("peer has an invalid public key")?;
+
+        Ok(SafetyNumber {
+            peer_id: entry.peer_id,
+            number,
+            key_verified: entry.key_verified,
+        })
+    })
+}
+
+// mark a peer's current key as verified after comparing safety numbers.
+// the mark is dropped on its own if the peer's key ever changes
+#[tauri::command]
+pub async fn mark_peer_verified(
+    state: State<'_, AppState>,
+    peer_id: String,
+    verified: bool,
+) -> Result<(), String> {
+    ipc_log!("mark_peer_verified", {
+        let entry = state
+            .storage
+            .load_directory_entry(&peer_id)
+            .map_err(|e| format!("failed to load directory: {}", e))?
+            .ok_or("peer not found in directory")?;
+        if verified && entry.public_key.is_empty() {
+            return Err("public key for this peer is not known yet".to_string());
+        }
+
+        state
+            .storage
+            .set_peer_key_verified(&peer_id, &entry.public_key, verified)
+            .map_err(|e| format!("failed to update peer: {}", e))
+    })
+}
+
+// -- pinned keys --
+
+// announcements held back because they carry a key other than the pinned one
+#[tauri::command]
+pub async fn get_key_conflicts(state: State<'_, AppState>) -> Result<Vec<KeyConflict>, String> {
+    ipc_log!("get_key_conflicts", {
+        state
+            .storage
+            .load_key_conflicts()
+            .map_err(|e| format!("failed to load key conflicts: {}", e))
+    })
+}
+
+// pin the key from a held back announcement and apply the profile it carried
+#[tauri::command]
+pub async fn accept_new_key(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<DirectoryEntry, String> {
+    ipc_log!("accept_new_key", {
+        let announcement = state
+            .storage
+            .load_key_conflict(&peer_id)
+            .map_err(|e| format!("failed to load key conflict: {}", e))?
+            .ok_or("no pending key change for this peer")?;
+
+        state
+            .storage
+            .replace_peer_key(&peer_id, &announcement.public_key)
+            .map_err(|e| format!("failed to pin new key: {}", e))?;
+
+        let verified = announcement
+            .verification_proof
+            .as_ref()
+            .is_some_and(|proof| verification::verify_proof_work(proof, &peer_id));
+        let entry = DirectoryEntry::from_announcement(&announcement, verified);
+        state
+            .storage
+            .save_announced_directory_entry(&entry, announcement.timestamp)
+            .map_err(|e| format!("failed to save profile: {}", e))?;
+
+        state
+            .storage
+            .remove_key_conflict(&peer_id)
+            .map_err(|e| format!("failed to clear key conflict: {}", e))?;
+
+        state
+            .storage
+            .load_directory_entry(&peer_id)
+            .map_err(|e| format!("failed to load directory: {}", e))?
+            .ok_or_else(|| "peer not found in directory".to_string())
+    })
+}
+
+// keep the pinned key and drop the held back announcement
+#[tauri::command]
+pub async fn reject_new_key(state: State<'_, AppState>, peer_id: String) -> Result<(), String> {
+    ipc_log!("reject_new_key", {
+        state
+            .storage
+            .remove_key_conflict(&peer_id)
+            .map_err(|e| format!("failed to clear key conflict: {}", e))
+    })
+}
+
 // discover online peers via the global relay tracker namespace
 // this allows finding peers without sharing a community or knowing their peer_id
 #[tauri::command]
@@ -462,7 +1323,7 @@ pub async fn discover_global_peers(state: State<'_, AppState>) -> Result<(), Str
         let _ = handle
             .command_tx
             .send(crate::node::NodeCommand::DiscoverRendezvous {
-                namespace: "dusk/peers".to_string(),
+                namespace: crate::node::GLOBAL_PEERS_NAMESPACE.to_string(),
             })
             .await;
     }
@@ -496,6 +1357,224 @@ pub async fn set_relay_discoverable(
     })
 }
 
+// communities our profile announcements stay out of. hiding stops further
+// announcements there, members who already heard one keep what they have
+#[tauri::command]
+pub async fn get_profile_hidden_communities(
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    ipc_log!("get_profile_hidden_communities", {
+        let mut hidden: Vec<String> = state
+            .storage
+            .load_profile_hidden_communities()
+            .map_err(|e| format!("failed to load profile visibility: {}", e))?
+            .into_iter()
+            .collect();
+        hidden.sort();
+        Ok(hidden)
+    })
+}
+
+#[tauri::command]
+pub async fn set_community_profile_visibility(
+    state: State<'_, AppState>,
+    community_id: String,
+    visible: bool,
+) -> Result<(), String> {
+    ipc_log!("set_community_profile_visibility", {
+        state
+            .storage
+            .set_profile_visible_in_community(&community_id, visible)
+            .map_err(|e| format!("failed to save profile visibility: {}", e))?;
+
+        if visible {
+            announce_profile(&state, AnnounceTrigger::ProfileChange).await;
+        }
+        Ok(())
+    })
+}
+
+// announce our profile now, also while only manual announcements are allowed
+#[tauri::command]
+pub async fn announce_profile_now(state: State<'_, AppState>) -> Result<(), String> {
+    ipc_log!("announce_profile_now", {
+        if state.node_handle.lock().await.is_none() {
+            return Err("node not running".to_string());
+        }
+        announce_profile(&state, AnnounceTrigger::Manual).await;
+        Ok(())
+    })
+}
+
+// take our profile out of the relay's directory now and stay out of it.
+// this opts out of relay discoverability too, otherwise the next refresh
+// would list us again
+#[tauri::command]
+pub async fn purge_relay_directory_entry(state: State<'_, AppState>) -> Result<(), String> {
+    ipc_log!("purge_relay_directory_entry", {
+        let mut settings = state.storage.load_settings().unwrap_or_default();
+        if settings.relay_discoverable {
+            settings.relay_discoverable = false;
+            state
+                .storage
+                .save_settings(&settings)
+                .map_err(|e| format!("failed to save settings: {}", e))?;
+        }
+
+        let node_handle = state.node_handle.lock().await;
+        let handle = node_handle.as_ref().ok_or("node not running")?;
+        let _ = handle
+            .command_tx
+            .send(crate::node::NodeCommand::SetRelayDiscoverable { enabled: false })
+            .await;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        handle
+            .command_tx
+            .send(crate::node::NodeCommand::DirectoryRemove { reply: tx })
+            .await
+            .map_err(|e| format!("failed to reach node: {}", e))?;
+        drop(node_handle);
+
+        match tokio::time::timeout(std::time::Duration::from_secs(10), rx).await {
+            Ok(Ok(Ok(_))) => Ok(()),
+            Ok(Ok(Err(e))) => Err(format!("failed to purge relay directory entry: {}", e)),
+            _ => Err("relay did not confirm the purge".to_string()),
+        }
+    })
+}
+
+// opt in or out of friend presence through the relay. while on, the relay
+// learns when we are online, show_online_status still hides us from friends
+#[tauri::command]
+pub async fn set_relay_presence(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    ipc_log!("set_relay_presence", {
+        let mut settings = state.storage.load_settings().unwrap_or_default();
+        settings.relay_presence = enabled;
+        state
+            .storage
+            .save_settings(&settings)
+            .map_err(|e| format!("failed to save settings: {}", e))?;
+
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            let _ = handle
+                .command_tx
+                .send(crate::node::NodeCommand::SetRelayPresence { enabled })
+                .await;
+        }
+        Ok(())
+    })
+}
+
+// pick auto, normal or low power networking, heartbeat and mdns changes
+// only take effect the next time the node starts
+#[tauri::command]
+pub async fn set_network_profile(
+    state: State<'_, AppState>,
+    profile: String,
+) -> Result<NetworkProfileStatus, String> {
+    ipc_log!("set_network_profile", {
+        let profile = NetworkProfile::parse(&profile)?;
+
+        let mut settings = state.storage.load_settings().unwrap_or_default();
+        settings.network_profile = profile;
+        state
+            .storage
+            .save_settings(&settings)
+            .map_err(|e| format!("failed to save settings: {}", e))?;
+
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::SetNetworkProfile { profile })
+                .await;
+        }
+        drop(node_handle);
+
+        Ok(NetworkProfileStatus::new(profile, power::detect_signals().await))
+    })
+}
+
+// current networking profile, falls back to settings when the node is stopped
+#[tauri::command]
+pub async fn get_network_profile(state: State<'_, AppState>) -> Result<NetworkProfileStatus, String> {
+    ipc_log!("get_network_profile", {
+        let node_handle = state.node_handle.lock().await;
+        if let Some(ref handle) = *node_handle {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            handle
+                .command_tx
+                .send(NodeCommand::GetNetworkProfile { reply: tx })
+                .await
+                .map_err(|e| format!("failed to query network profile: {}", e))?;
+            drop(node_handle);
+            return rx
+                .await
+                .map_err(|e| format!("failed to receive network profile: {}", e));
+        }
+        drop(node_handle);
+
+        let profile = state
+            .storage
+            .load_settings()
+            .map(|s| s.network_profile)
+            .unwrap_or_default();
+        Ok(NetworkProfileStatus::new(profile, power::detect_signals().await))
+    })
+}
+
+// turn cover traffic mode on or off, takes effect right away
+#[tauri::command]
+pub async fn set_cover_traffic(
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<CoverTrafficStatus, String> {
+    ipc_log!("set_cover_traffic", {
+        let mut settings = state.storage.load_settings().unwrap_or_default();
+        settings.cover_traffic = enabled;
+        state
+            .storage
+            .save_settings(&settings)
+            .map_err(|e| format!("failed to save settings: {}", e))?;
+        state.cover_traffic.set_enabled(enabled);
+
+        Ok(cover_traffic_status(&state))
+    })
+}
+
+// cover traffic mode and the bandwidth it has used this session
+#[tauri::command]
+pub async fn get_cover_traffic(state: State<'_, AppState>) -> Result<CoverTrafficStatus, String> {
+    ipc_log!("get_cover_traffic", { Ok(cover_traffic_status(&state)) })
+}
+
+fn cover_traffic_status(state: &AppState) -> CoverTrafficStatus {
+    state.cover_traffic.status()
+}
+
+// rich presence shown next to our name, e.g. "Playing Factorio". pass None
+// or an empty string to clear it. not persisted, it ends with the session
+#[tauri::command]
+pub async fn set_activity(
+    state: State<'_, AppState>,
+    activity: Option<String>,
+) -> Result<Option<String>, String> {
+    ipc_log!("set_activity", {
+        let activity = activity.as_deref().and_then(crate::activity::normalize);
+        crate::activity::apply(&state.activity, &state.node_handle, activity.clone()).await;
+        Ok(activity)
+    })
+}
+
+#[tauri::command]
+pub async fn get_activity(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    ipc_log!("get_activity", {
+        let activity = state.activity.lock().await.clone();
+        Ok(activity)
+    })
+}
+
 // change relay address and restart the node
 // used when default relay is unreachable or at capacity
 #[tauri::command]
@@ -541,8 +1620,11 @@ pub async fn set_relay_address(
 
 // broadcast a revocation to all peers, stop the node, and wipe all local data
 #[tauri::command]
-pub async fn reset_identity(state: State<'_, AppState>) -> Result<(), String> {
-    let mut identity = state.identity.lock().await;
+pub async fn reset_identity(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let identity = state.identity.lock().await;
     let id = identity.as_ref().ok_or("no identity loaded")?;
 
     // build the revocation message before we destroy the identity
@@ -575,44 +1657,25 @@ pub async fn reset_identity(state: State<'_, AppState>) -> Result<(), String> {
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
     drop(node_handle);
+    drop(identity);
 
-    // stop the p2p node
-    {
-        let mut node_handle = state.node_handle.lock().await;
-        if let Some(handle) = node_handle.take() {
-            let _ = handle.command_tx.send(NodeCommand::Shutdown).await;
-            let _ = handle.task.await;
-        }
-    }
-
-    // clear the crdt engine so no community data lingers in memory
-    {
-        let mut engine = state.crdt_engine.lock().await;
-        engine.clear();
-    }
-
-    {
-        let mut guard = state.pending_join_role_guard.lock().await;
-        guard.clear();
-    }
-
-    // clear in-memory identity
-    *identity = None;
-
-    // wipe all data from disk
-    state
-        .storage
-        .wipe_all_data()
-        .map_err(|e| format!("failed to wipe data: {}", e))?;
+    // stops the node, wipes storage and rebuilds the engine and the rest
+    // of the in-memory state
+    state.reset().await?;
 
+    boot::set_phase(&app, BootPhase::Onboarding).await;
     Ok(())
 }
 
 // write an svg string to a cache directory and return the absolute path
 // used for notification icons so the os can display the user's avatar
 #[tauri::command]
-pub async fn cache_avatar_icon(cache_key: String, svg_content: String) -> Result<String, String> {
-    let cache_dir = std::env::temp_dir().join("dusk-avatars");
+pub async fn cache_avatar_icon(
+    state: State<'_, AppState>,
+    cache_key: String,
+    svg_content: String,
+) -> Result<String, String> {
+    let cache_dir = state.storage.cache_dir().join("dusk-avatars");
     std::fs::create_dir_all(&cache_dir)
         .map_err(|e| format!("failed to create avatar cache dir: {}", e))?;
 
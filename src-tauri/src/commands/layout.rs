@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use super::ipc_log;
+use crate::protocol::community::{
+    CommunityFolder, CommunityLayout, CommunityPlacement, MAX_FOLDER_NAME_LEN,
+};
+use crate::AppState;
+
+fn load_layout(state: &AppState) -> Result<CommunityLayout, String> {
+    state
+        .storage
+        .load_community_layout()
+        .map_err(|e| format!("failed to load community layout: {}", e))
+}
+
+fn clean_folder_name(name: &str) -> Result<String, String> {
+    let name: String = name
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_FOLDER_NAME_LEN)
+        .collect();
+    if name.is_empty() {
+        return Err("folder name cannot be empty".to_string());
+    }
+    Ok(name)
+}
+
+// folders, community order and collapse state for the sidebar
+#[tauri::command]
+pub async fn get_community_layout(state: State<'_, AppState>) -> Result<CommunityLayout, String> {
+    ipc_log!("get_community_layout", load_layout(&state))
+}
+
+// new folders go to the end of the list
+#[tauri::command]
+pub async fn create_folder(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<CommunityFolder, String> {
+    ipc_log!("create_folder", {
+        let name = clean_folder_name(&name)?;
+        let layout = load_layout(&state)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mut hasher = Sha256::new();
+        hasher.update(name.as_bytes());
+        hasher.update(now.to_le_bytes());
+        let hash = hasher.finalize();
+
+        let folder = CommunityFolder {
+            id: format!("folder_{}", &hex::encode(hash)[..12]),
+            name,
+            position: layout
+                .folders
+                .iter()
+                .map(|f| f.position + 1)
+                .max()
+                .unwrap_or(0),
+            collapsed: false,
+        };
+        state
+            .storage
+            .save_community_folder(&folder)
+            .map_err(|e| format!("failed to save folder: {}", e))?;
+        Ok(folder)
+    })
+}
+
+// rename a folder or open/close it, fields left out stay as they are
+#[tauri::command]
+pub async fn update_folder(
+    state: State<'_, AppState>,
+    folder_id: String,
+    name: Option<String>,
+    collapsed: Option<bool>,
+) -> Result<CommunityFolder, String> {
+    ipc_log!("update_folder", {
+        let mut folder = load_layout(&state)?
+            .folders
+            .into_iter()
+            .find(|f| f.id == folder_id)
+            .ok_or("folder not found")?;
+        if let Some(name) = name {
+            folder.name = clean_folder_name(&name)?;
+        }
+        if let Some(collapsed) = collapsed {
+            folder.collapsed = collapsed;
+        }
+        state
+            .storage
+            .save_community_folder(&folder)
+            .map_err(|e| format!("failed to save folder: {}", e))?;
+        Ok(folder)
+    })
+}
+
+// the communities inside move back to the top level
+#[tauri::command]
+pub async fn delete_folder(state: State<'_, AppState>, folder_id: String) -> Result<(), String> {
+    ipc_log!("delete_folder", {
+        let removed = state
+            .storage
+            .delete_community_folder(&folder_id)
+            .map_err(|e| format!("failed to delete folder: {}", e))?;
+        if !removed {
+            return Err("folder not found".to_string());
+        }
+        Ok(())
+    })
+}
+
+// reorder folders, ids left out keep their relative order after the given ones
+#[tauri::command]
+pub async fn set_folder_order(
+    state: State<'_, AppState>,
+    folder_ids: Vec<String>,
+) -> Result<CommunityLayout, String> {
+    ipc_log!("set_folder_order", {
+        let mut layout = load_layout(&state)?;
+        layout.folders.sort_by_key(|f| {
+            folder_ids
+                .iter()
+                .position(|id| *id == f.id)
+                .unwrap_or(folder_ids.len())
+        });
+        state
+            .storage
+            .save_community_layout(&layout)
+            .map_err(|e| format!("failed to save community layout: {}", e))?;
+        load_layout(&state)
+    })
+}
+
+// place communities in the given order, each either at the top level or in a
+// folder. replaces the previous order, positions follow the list
+#[tauri::command]
+pub async fn set_community_order(
+    state: State<'_, AppState>,
+    communities: Vec<CommunityPlacement>,
+) -> Result<CommunityLayout, String> {
+    ipc_log!("set_community_order", {
+        let mut layout = load_layout(&state)?;
+        let folder_ids: HashSet<&str> = layout.folders.iter().map(|f| f.id.as_str()).collect();
+
+        let mut seen = HashSet::new();
+        for placement in &communities {
+            if !seen.insert(placement.community_id.as_str()) {
+                return Err(format!(
+                    "community {} is listed twice",
+                    placement.community_id
+                ));
+            }
+            if let Some(ref folder_id) = placement.folder_id {
+                if !folder_ids.contains(folder_id.as_str()) {
+                    return Err(format!("folder {} not found", folder_id));
+                }
+            }
+        }
+
+        layout.communities = communities;
+        state
+            .storage
+            .save_community_layout(&layout)
+            .map_err(|e| format!("failed to save community layout: {}", e))?;
+        load_layout(&state)
+    })
+}
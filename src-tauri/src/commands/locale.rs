@@ -0,0 +1,54 @@
+use tauri::State;
+
+use super::ipc_log;
+use crate::locale::{self, ResolvedLocale};
+use crate::AppState;
+
+// the ui reports the system locale and time zone it sees, on startup and
+// when they change, so text generated here matches it. returns what to
+// format with once the user's overrides are applied
+#[tauri::command]
+pub async fn set_system_locale(
+    state: State<'_, AppState>,
+    locale: String,
+    time_zone: String,
+    utc_offset_minutes: i32,
+) -> Result<ResolvedLocale, String> {
+    ipc_log!("set_system_locale", {
+        // real offsets run from utc-12:00 to utc+14:00
+        if !(-12 * 60..=14 * 60).contains(&utc_offset_minutes) {
+            return Err(format!("invalid utc offset: {}", utc_offset_minutes));
+        }
+        let mut settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+
+        let reported = settings.locale.clone();
+        settings.locale.system_locale = locale::normalize_tag(&locale);
+        settings.locale.system_time_zone =
+            Some(time_zone.trim().to_string()).filter(|z| !z.is_empty());
+        settings.locale.system_utc_offset_minutes = Some(utc_offset_minutes);
+
+        // reported on every startup, only write when something moved
+        if settings.locale != reported {
+            state
+                .storage
+                .save_settings(&settings)
+                .map_err(|e| format!("failed to save settings: {}", e))?;
+            crate::catalog::apply(&settings.locale);
+        }
+        Ok(settings.locale.resolve())
+    })
+}
+
+#[tauri::command]
+pub async fn get_locale(state: State<'_, AppState>) -> Result<ResolvedLocale, String> {
+    ipc_log!("get_locale", {
+        let settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+        Ok(settings.locale.resolve())
+    })
+}
@@ -0,0 +1,123 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tauri::State;
+
+use super::ipc_log;
+use crate::crash;
+use crate::locale::format_utc;
+use crate::logging::{self, LogConfig};
+use crate::AppState;
+
+// change how verbose logging is from the next line on and keep it in
+// settings. without a module this sets the default level, with one it sets
+// that module's, and no level drops the module's own setting
+#[tauri::command]
+pub async fn set_log_level(
+    state: State<'_, AppState>,
+    module: Option<String>,
+    level: Option<String>,
+) -> Result<LogConfig, String> {
+    ipc_log!("set_log_level", {
+        if let Some(ref level) = level {
+            logging::parse_level(level)?;
+        }
+        let mut settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+
+        let module = module
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty());
+        match (module, level) {
+            (None, Some(level)) => settings.logging.level = level.trim().to_lowercase(),
+            (None, None) => return Err("a level is needed for the default".to_string()),
+            (Some(module), Some(level)) => {
+                settings
+                    .logging
+                    .modules
+                    .insert(module, level.trim().to_lowercase());
+            }
+            (Some(module), None) => {
+                settings.logging.modules.remove(&module);
+            }
+        }
+
+        state
+            .storage
+            .save_settings(&settings)
+            .map_err(|e| format!("failed to save settings: {}", e))?;
+        logging::apply(&settings.logging, &state.storage);
+        Ok(settings.logging)
+    })
+}
+
+// write a redacted diagnostics file for a bug report: app and platform,
+// logging setup, crash dumps on disk and the current log file, or the
+// recent lines kept in memory when file logging is off
+#[tauri::command]
+pub async fn export_diagnostics(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    ipc_log!("export_diagnostics", {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let settings = state.storage.load_settings().unwrap_or_default();
+
+        let mut report = String::new();
+        report.push_str("dusk diagnostics\n");
+        report.push_str(&format!("generated {}\n\n", format_utc(now)));
+
+        report.push_str("== app ==\n");
+        report.push_str(&format!("version: {}\n", env!("CARGO_PKG_VERSION")));
+        report.push_str(&format!(
+            "platform: {} {}\n",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ));
+        report.push_str(&format!(
+            "boot phase: {:?}\n\n",
+            *state.boot_phase.lock().await
+        ));
+
+        report.push_str("== logging ==\n");
+        report.push_str(&format!("level: {}\n", settings.logging.level));
+        for (module, level) in &settings.logging.modules {
+            report.push_str(&format!("{}: {}\n", module, level));
+        }
+        report.push_str(&format!(
+            "log file: {}\n\n",
+            if settings.logging.file { "on" } else { "off" }
+        ));
+
+        report.push_str("== crash reports ==\n");
+        let crashes = crash::list_reports(&state.storage.crash_dir()).unwrap_or_default();
+        if crashes.is_empty() {
+            report.push_str("none\n");
+        }
+        for crashed in &crashes {
+            report.push_str(&format!(
+                "{} {}: {}\n",
+                crashed.id,
+                format_utc(crashed.created_at),
+                crashed.message
+            ));
+        }
+        report.push('\n');
+
+        let logs = match logging::current_file() {
+            Some(contents) => {
+                report.push_str("== current log file ==\n");
+                contents
+            }
+            None => {
+                report.push_str("== recent log lines ==\n");
+                logging::recent_lines().join("\n")
+            }
+        };
+        report.push_str(&crash::redact(&logs));
+        report.push('\n');
+
+        std::fs::write(&path, report).map_err(|e| format!("failed to write diagnostics: {}", e))
+    })
+}
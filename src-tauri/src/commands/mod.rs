@@ -1,23 +1,43 @@
-// logs every tauri ipc command invocation and its result to the terminal
+// logs every tauri ipc command invocation and its result to the terminal.
+// calls over the command's budget in crate::ratelimit fail without running.
+// errors are logged in english and handed to the ui in the user's language
 macro_rules! ipc_log {
     ($cmd:expr, $body:expr) => {{
         let start = std::time::Instant::now();
         log::info!("[ipc] -> {}", $cmd);
-        let result = $body;
+        let result = match crate::ratelimit::check($cmd) {
+            Ok(()) => $body,
+            Err(e) => Err(e),
+        };
         let elapsed = start.elapsed();
         match &result {
             Ok(_) => log::info!("[ipc] <- {} ok ({:.1?})", $cmd, elapsed),
             Err(e) => log::error!("[ipc] <- {} err ({:.1?}): {}", $cmd, elapsed, e),
         }
-        result
+        result.map_err(crate::catalog::localize_error)
     }};
 }
 
 pub(crate) use ipc_log;
 
+pub mod authz;
+pub mod call;
 pub mod chat;
 pub mod community;
+pub mod crash;
 pub mod dm;
+pub mod export;
 pub mod gif;
 pub mod identity;
+pub mod layout;
+pub mod locale;
+pub mod logging;
+pub mod moderation;
+pub mod notes;
+pub mod pages;
+pub mod qr;
+pub mod stats;
+pub mod storage;
+pub mod tasks;
+pub mod update;
 pub mod voice;
@@ -0,0 +1,124 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use super::community::broadcast_sync;
+use super::{authz, ipc_log};
+use crate::protocol::community::{CapabilityToken, ModerationCapability, MAX_CAPABILITY_DEPTH};
+use crate::AppState;
+
+// number of delegations between a token and the owner
+fn chain_depth(token: &CapabilityToken) -> usize {
+    let mut depth = 0;
+    let mut current = token;
+    while let Some(ref parent) = current.parent {
+        depth += 1;
+        current = parent;
+    }
+    depth
+}
+
+// hand a moderation capability to a member until expires_at (unix millis).
+// the owner issues tokens directly, other holders delegate from their own
+// token and can only narrow it
+#[tauri::command]
+pub async fn grant_capability(
+    state: State<'_, AppState>,
+    community_id: String,
+    holder_peer_id: String,
+    capability: ModerationCapability,
+    channel_id: Option<String>,
+    expires_at: u64,
+) -> Result<CapabilityToken, String> {
+    ipc_log!("grant_capability", {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        if expires_at <= now {
+            return Err("capability must expire in the future".to_string());
+        }
+
+        let keypair = {
+            let identity = state.identity.lock().await;
+            let id = identity.as_ref().ok_or("no identity loaded")?;
+            id.keypair.clone()
+        };
+        let local_peer_id = keypair.public().to_peer_id().to_string();
+        if holder_peer_id == local_peer_id {
+            return Err("cannot grant a capability to yourself".to_string());
+        }
+
+        let mut engine = state.crdt_engine.lock().await;
+        let permissions = authz::resolve_with(&engine, &community_id, &local_peer_id)?;
+        if permissions.member(&holder_peer_id).is_none() {
+            return Err("member not found".to_string());
+        }
+        if let Some(ref channel_id) = channel_id {
+            let channels = engine.get_channels(&community_id)?;
+            if !channels.iter().any(|c| c.id == *channel_id) {
+                return Err("channel not found".to_string());
+            }
+        }
+
+        let parent = if permissions.is_owner {
+            None
+        } else {
+            let parent = permissions
+                .capability(capability, channel_id.as_deref())
+                .cloned()
+                .ok_or("no capability to delegate")?;
+            if parent.expires_at < expires_at {
+                return Err("cannot delegate past your own capability's expiry".to_string());
+            }
+            if parent.channel_id.is_some() && parent.channel_id != channel_id {
+                return Err("cannot widen a channel-scoped capability".to_string());
+            }
+            if chain_depth(&parent) + 1 > MAX_CAPABILITY_DEPTH {
+                return Err("capability has been delegated too many times".to_string());
+            }
+            Some(Box::new(parent))
+        };
+
+        let mut hasher = Sha256::new();
+        hasher.update(local_peer_id.as_bytes());
+        hasher.update(holder_peer_id.as_bytes());
+        hasher.update(capability.as_str().as_bytes());
+        hasher.update(now.to_le_bytes());
+        let hash = hasher.finalize();
+
+        let mut token = CapabilityToken {
+            id: format!("cap_{}", &hex::encode(hash)[..16]),
+            community_id: community_id.clone(),
+            capability,
+            channel_id,
+            holder: holder_peer_id,
+            issuer: local_peer_id,
+            issuer_key: hex::encode(keypair.public().encode_protobuf()),
+            expires_at,
+            parent,
+            signature: String::new(),
+        };
+        token.signature = crate::verification::sign_capability_token(&keypair, &token);
+
+        engine.add_capability(&community_id, &token)?;
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+
+        Ok(token)
+    })
+}
+
+// unexpired capability tokens in the community that chain back to the owner
+#[tauri::command]
+pub async fn get_capabilities(
+    state: State<'_, AppState>,
+    community_id: String,
+) -> Result<Vec<CapabilityToken>, String> {
+    ipc_log!("get_capabilities", {
+        let engine = state.crdt_engine.lock().await;
+        engine.get_capabilities(&community_id)
+    })
+}
@@ -0,0 +1,132 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use super::{authz, ipc_log};
+use crate::node::gossip;
+use crate::node::NodeCommand;
+use crate::protocol::messages::GossipMessage;
+use crate::protocol::notes::{Note, NoteEdit};
+use crate::AppState;
+
+#[tauri::command]
+pub async fn create_note(
+    state: State<'_, AppState>,
+    community_id: String,
+    title: String,
+    idempotency_key: Option<String>,
+) -> Result<Note, String> {
+    ipc_log!("create_note", {
+        let claim = state
+            .idempotency
+            .claim("create_note", idempotency_key)
+            .await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_member()?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(community_id.as_bytes());
+        hasher.update(perms.peer_id.as_bytes());
+        hasher.update(title.as_bytes());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        hasher.update(now.to_le_bytes());
+        let note_id = format!("note_{}", &hex::encode(hasher.finalize())[..12]);
+
+        let (note, changes) = {
+            let mut engine = state.crdt_engine.lock().await;
+            engine.create_note(&community_id, &note_id, title.trim(), &perms.peer_id)?
+        };
+        claim.complete(&note);
+
+        publish_note_changes(&state, &community_id, &note_id, changes).await;
+        Ok(note)
+    })
+}
+
+// apply the frontend's edit steps as one change and gossip it to the
+// community, returns the note as it reads afterwards
+#[tauri::command]
+pub async fn apply_note_changes(
+    state: State<'_, AppState>,
+    community_id: String,
+    note_id: String,
+    edits: Vec<NoteEdit>,
+) -> Result<Note, String> {
+    ipc_log!("apply_note_changes", {
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_member()?;
+        if edits.is_empty() {
+            let engine = state.crdt_engine.lock().await;
+            return engine.get_note(&community_id, &note_id);
+        }
+
+        let (note, changes) = {
+            let mut engine = state.crdt_engine.lock().await;
+            if !engine.can_edit_note(&community_id, &note_id, &perms.peer_id) {
+                return Err("insufficient permissions".to_string());
+            }
+            engine.apply_note_edits(&community_id, &note_id, &edits, &perms.peer_id)?
+        };
+
+        publish_note_changes(&state, &community_id, &note_id, changes).await;
+        Ok(note)
+    })
+}
+
+#[tauri::command]
+pub async fn get_note(
+    state: State<'_, AppState>,
+    community_id: String,
+    note_id: String,
+) -> Result<Note, String> {
+    ipc_log!("get_note", {
+        let engine = state.crdt_engine.lock().await;
+        engine.get_note(&community_id, &note_id)
+    })
+}
+
+#[tauri::command]
+pub async fn get_notes(
+    state: State<'_, AppState>,
+    community_id: String,
+) -> Result<Vec<Note>, String> {
+    ipc_log!("get_notes", {
+        let engine = state.crdt_engine.lock().await;
+        Ok(engine.get_notes(&community_id))
+    })
+}
+
+// the change is already saved locally, members who miss it catch up through
+// a sync request once a later change reaches them
+pub(super) async fn publish_note_changes(
+    state: &AppState,
+    community_id: &str,
+    note_id: &str,
+    changes: Vec<u8>,
+) {
+    let msg = GossipMessage::NoteChanges {
+        community_id: community_id.to_string(),
+        note_id: note_id.to_string(),
+        changes,
+    };
+    let Ok(data) = serde_json::to_vec(&msg) else {
+        return;
+    };
+    let node_handle = state.node_handle.lock().await;
+    if let Some(ref handle) = *node_handle {
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::SendMessage {
+                topic: gossip::topic_for_notes(community_id),
+                data,
+            })
+            .await;
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use super::community::broadcast_sync;
+use super::notes::publish_note_changes;
+use super::{authz, ipc_log};
+use crate::protocol::notes::{Note, NoteEdit, NoteRevision, PageEditRole, PageMeta};
+use crate::AppState;
+
+// admins create pages and pick who edits them. a page's body is a note, so
+// edits travel like note edits and every change is kept as its history
+
+#[tauri::command]
+pub async fn create_page(
+    state: State<'_, AppState>,
+    community_id: String,
+    title: String,
+    edit_role: Option<PageEditRole>,
+    idempotency_key: Option<String>,
+) -> Result<PageMeta, String> {
+    ipc_log!("create_page", {
+        let claim = state
+            .idempotency
+            .claim("create_page", idempotency_key)
+            .await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        let title = title.trim();
+        if title.is_empty() {
+            return Err("page title cannot be empty".to_string());
+        }
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_admin()?;
+        // the page's entry in the community doc is signed, so a merge can
+        // tell it came from an admin
+        let keypair = {
+            let identity = state.identity.lock().await;
+            let id = identity.as_ref().ok_or("no identity loaded")?;
+            id.keypair.clone()
+        };
+
+        let mut hasher = Sha256::new();
+        hasher.update(community_id.as_bytes());
+        hasher.update(perms.peer_id.as_bytes());
+        hasher.update(title.as_bytes());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        hasher.update(now.to_le_bytes());
+        let page_id = format!("page_{}", &hex::encode(hasher.finalize())[..12]);
+
+        let (page, changes) = {
+            let mut engine = state.crdt_engine.lock().await;
+            engine.create_page(
+                &community_id,
+                &page_id,
+                title,
+                edit_role.unwrap_or_default(),
+                &keypair,
+            )?
+        };
+        claim.complete(&page);
+
+        publish_note_changes(&state, &community_id, &page_id, changes).await;
+        broadcast_sync(&state, &community_id).await;
+        Ok(page)
+    })
+}
+
+// apply edit steps to a page as one change, returns the page as it reads
+// afterwards
+#[tauri::command]
+pub async fn edit_page(
+    state: State<'_, AppState>,
+    community_id: String,
+    page_id: String,
+    edits: Vec<NoteEdit>,
+) -> Result<Note, String> {
+    ipc_log!("edit_page", {
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_member()?;
+
+        let (note, changes) = {
+            let mut engine = state.crdt_engine.lock().await;
+            let edit_role = engine
+                .page_edit_role(&community_id, &page_id)
+                .ok_or("page not found")?;
+            if edit_role == PageEditRole::Admin && !perms.is_admin {
+                return Err("only admins can edit this page".to_string());
+            }
+            if edits.is_empty() {
+                return engine.get_note(&community_id, &page_id);
+            }
+            engine.apply_note_edits(&community_id, &page_id, &edits, &perms.peer_id)?
+        };
+
+        publish_note_changes(&state, &community_id, &page_id, changes).await;
+        Ok(note)
+    })
+}
+
+#[tauri::command]
+pub async fn get_pages(
+    state: State<'_, AppState>,
+    community_id: String,
+) -> Result<Vec<PageMeta>, String> {
+    ipc_log!("get_pages", {
+        let engine = state.crdt_engine.lock().await;
+        engine.get_pages(&community_id)
+    })
+}
+
+// the page as it reads now, or right after the given revision
+#[tauri::command]
+pub async fn get_page(
+    state: State<'_, AppState>,
+    community_id: String,
+    page_id: String,
+    revision: Option<String>,
+) -> Result<Note, String> {
+    ipc_log!("get_page", {
+        let mut engine = state.crdt_engine.lock().await;
+        engine
+            .page_edit_role(&community_id, &page_id)
+            .ok_or("page not found")?;
+        match revision {
+            Some(revision) => engine.get_note_at(&community_id, &page_id, &revision),
+            None => engine.get_note(&community_id, &page_id),
+        }
+    })
+}
+
+// every change to the page, oldest first
+#[tauri::command]
+pub async fn get_page_history(
+    state: State<'_, AppState>,
+    community_id: String,
+    page_id: String,
+) -> Result<Vec<NoteRevision>, String> {
+    ipc_log!("get_page_history", {
+        let mut engine = state.crdt_engine.lock().await;
+        engine
+            .page_edit_role(&community_id, &page_id)
+            .ok_or("page not found")?;
+        engine.note_history(&community_id, &page_id)
+    })
+}
+
+#[tauri::command]
+pub async fn set_page_edit_role(
+    state: State<'_, AppState>,
+    community_id: String,
+    page_id: String,
+    edit_role: PageEditRole,
+) -> Result<Vec<PageMeta>, String> {
+    ipc_log!("set_page_edit_role", {
+        authz::resolve(&state, &community_id)
+            .await?
+            .require_admin()?;
+        let keypair = {
+            let identity = state.identity.lock().await;
+            let id = identity.as_ref().ok_or("no identity loaded")?;
+            id.keypair.clone()
+        };
+
+        let mut engine = state.crdt_engine.lock().await;
+        engine.set_page_edit_role(&community_id, &page_id, edit_role, &keypair)?;
+        let pages = engine.get_pages(&community_id)?;
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+        Ok(pages)
+    })
+}
@@ -0,0 +1,21 @@
+use super::ipc_log;
+
+// qr code for an invite or contact card as png bytes, rendered here so the
+// frontend doesn't need a js qr library. raw ipc keeps the image out of json
+#[tauri::command]
+pub async fn generate_qr(data: String) -> Result<tauri::ipc::Response, String> {
+    ipc_log!("generate_qr", {
+        crate::qr::render_png(&data).map(tauri::ipc::Response::new)
+    })
+}
+
+// write the qr code for some data straight to a png file chosen by the user
+#[tauri::command]
+pub async fn save_qr(data: String, path: String) -> Result<(), String> {
+    ipc_log!("save_qr", {
+        let png = crate::qr::render_png(&data)?;
+        tokio::fs::write(&path, png)
+            .await
+            .map_err(|e| format!("failed to save qr code: {}", e))
+    })
+}
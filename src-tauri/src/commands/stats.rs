@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tauri::State;
+
+use super::{authz, ipc_log};
+use crate::AppState;
+
+const DEFAULT_STATS_DAYS: u32 = 30;
+const MAX_STATS_DAYS: u32 = 365;
+const TOP_CHANNELS: usize = 10;
+const DAY_MILLIS: u64 = 86_400_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommunityUsage {
+    pub community_id: String,
+    pub name: String,
+    pub channel_count: usize,
+    pub member_count: usize,
+    pub message_count: usize,
+    // size of the stored community document, messages included
+    pub storage_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelActivity {
+    pub community_id: String,
+    pub community_name: String,
+    pub channel_id: String,
+    pub channel_name: String,
+    pub message_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyCount {
+    // start of the utc day in unix millis
+    pub day: u64,
+    pub count: u64,
+}
+
+// everything the insights view shows, computed from local data only
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageStats {
+    pub communities: Vec<CommunityUsage>,
+    pub top_channels: Vec<ChannelActivity>,
+    pub dm_message_count: usize,
+    pub dm_conversation_count: usize,
+    // days without dms are left out
+    pub dm_daily: Vec<DailyCount>,
+    pub database_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelHealth {
+    pub channel_id: String,
+    pub name: String,
+    pub message_count: usize,
+    pub messages_7d: usize,
+    pub messages_30d: usize,
+}
+
+// owner-facing health of one community. derived from the local doc, so it
+// only covers what this node has synced, and departures only count from the
+// moment community analytics was switched on
+#[derive(Debug, Clone, Serialize)]
+pub struct CommunityAnalytics {
+    pub community_id: String,
+    pub member_count: usize,
+    // distinct authors over the window
+    pub active_members_7d: usize,
+    pub active_members_30d: usize,
+    pub messages_7d: usize,
+    pub messages_30d: usize,
+    // joins count members who are still in the community
+    pub joins_7d: usize,
+    pub joins_30d: usize,
+    pub leaves_7d: usize,
+    pub leaves_30d: usize,
+    // busiest first
+    pub channels: Vec<ChannelHealth>,
+}
+
+// per-community and dm activity for the insights view. dm volume covers
+// the last `days` days, 30 by default
+#[tauri::command]
+pub async fn get_usage_stats(
+    state: State<'_, AppState>,
+    days: Option<u32>,
+) -> Result<UsageStats, String> {
+    ipc_log!("get_usage_stats", {
+        let days = days.unwrap_or(DEFAULT_STATS_DAYS).clamp(1, MAX_STATS_DAYS);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let since = now.saturating_sub(days as u64 * DAY_MILLIS);
+
+        let sizes = state
+            .storage
+            .document_sizes()
+            .map_err(|e| format!("failed to load document sizes: {}", e))?;
+
+        let mut communities = Vec::new();
+        let mut channels = Vec::new();
+        {
+            let engine = state.crdt_engine.lock().await;
+            for community_id in engine.community_ids() {
+                let name = engine
+                    .get_community_meta(&community_id)
+                    .map(|m| m.name)
+                    .unwrap_or_else(|_| community_id.clone());
+                let community_channels = engine.get_channels(&community_id).unwrap_or_default();
+
+                let mut message_count = 0;
+                for channel in &community_channels {
+                    let count = engine
+                        .count_messages(&community_id, &channel.id)
+                        .unwrap_or(0);
+                    message_count += count;
+                    channels.push(ChannelActivity {
+                        community_id: community_id.clone(),
+                        community_name: name.clone(),
+                        channel_id: channel.id.clone(),
+                        channel_name: channel.name.clone(),
+                        message_count: count,
+                    });
+                }
+
+                communities.push(CommunityUsage {
+                    storage_bytes: sizes.get(&community_id).copied().unwrap_or(0),
+                    member_count: engine.get_members(&community_id).map(|m| m.len()).unwrap_or(0),
+                    channel_count: community_channels.len(),
+                    message_count,
+                    name,
+                    community_id,
+                });
+            }
+        }
+
+        communities.sort_by(|a, b| b.message_count.cmp(&a.message_count));
+        channels.sort_by(|a, b| b.message_count.cmp(&a.message_count));
+        channels.retain(|c| c.message_count > 0);
+        channels.truncate(TOP_CHANNELS);
+
+        let dm_daily = state
+            .storage
+            .count_dm_messages_per_day(since)
+            .map_err(|e| format!("failed to count dms: {}", e))?
+            .into_iter()
+            .map(|(day, count)| DailyCount { day, count })
+            .collect();
+        let dm_message_count = state
+            .storage
+            .count_all_dm_messages()
+            .map_err(|e| format!("failed to count dms: {}", e))?;
+        let dm_conversation_count = state
+            .storage
+            .load_all_dm_conversations(true)
+            .map_err(|e| format!("failed to load dm conversations: {}", e))?
+            .len();
+
+        Ok(UsageStats {
+            communities,
+            top_channels: channels,
+            dm_message_count,
+            dm_conversation_count,
+            dm_daily,
+            database_bytes: state.storage.database_size(),
+        })
+    })
+}
+
+// health metrics for a community we own or moderate. requires the
+// community_analytics setting, nothing leaves this device
+#[tauri::command]
+pub async fn get_community_analytics(
+    state: State<'_, AppState>,
+    community_id: String,
+) -> Result<CommunityAnalytics, String> {
+    ipc_log!("get_community_analytics", {
+        let settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+        if !settings.community_analytics {
+            return Err("community analytics is disabled".to_string());
+        }
+
+        let local_peer_id = {
+            let identity = state.identity.lock().await;
+            let id = identity.as_ref().ok_or("no identity loaded")?;
+            id.peer_id.to_string()
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let week_ago = now.saturating_sub(7 * DAY_MILLIS);
+        let month_ago = now.saturating_sub(30 * DAY_MILLIS);
+
+        let mut analytics = {
+            let engine = state.crdt_engine.lock().await;
+            authz::resolve_with(&engine, &community_id, &local_peer_id)?.require_admin()?;
+            let members = engine.get_members(&community_id)?;
+
+            let mut authors_7d = HashSet::new();
+            let mut authors_30d = HashSet::new();
+            let mut channels = Vec::new();
+            for channel in engine.get_channels(&community_id)? {
+                let messages =
+                    engine.get_messages(&community_id, &channel.id, None, usize::MAX)?;
+                let mut health = ChannelHealth {
+                    channel_id: channel.id,
+                    name: channel.name,
+                    message_count: messages.len(),
+                    messages_7d: 0,
+                    messages_30d: 0,
+                };
+                for message in messages.iter().filter(|m| m.timestamp >= month_ago) {
+                    health.messages_30d += 1;
+                    authors_30d.insert(message.author_id.clone());
+                    if message.timestamp >= week_ago {
+                        health.messages_7d += 1;
+                        authors_7d.insert(message.author_id.clone());
+                    }
+                }
+                channels.push(health);
+            }
+            channels.sort_by(|a, b| {
+                b.messages_30d
+                    .cmp(&a.messages_30d)
+                    .then(b.message_count.cmp(&a.message_count))
+            });
+
+            CommunityAnalytics {
+                community_id: community_id.clone(),
+                member_count: members.len(),
+                active_members_7d: authors_7d.len(),
+                active_members_30d: authors_30d.len(),
+                messages_7d: channels.iter().map(|c| c.messages_7d).sum(),
+                messages_30d: channels.iter().map(|c| c.messages_30d).sum(),
+                joins_7d: members.iter().filter(|m| m.joined_at >= week_ago).count(),
+                joins_30d: members.iter().filter(|m| m.joined_at >= month_ago).count(),
+                leaves_7d: 0,
+                leaves_30d: 0,
+                channels,
+            }
+        };
+
+        analytics.leaves_7d = state
+            .storage
+            .count_member_departures(&community_id, week_ago)
+            .map_err(|e| format!("failed to count departures: {}", e))?;
+        analytics.leaves_30d = state
+            .storage
+            .count_member_departures(&community_id, month_ago)
+            .map_err(|e| format!("failed to count departures: {}", e))?;
+
+        Ok(analytics)
+    })
+}
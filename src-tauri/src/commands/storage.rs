@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use tauri::{Emitter, State};
+
+use super::ipc_log;
+use crate::node::{DuskEvent, NodeCommand};
+use crate::protocol::community::CommunityMeta;
+use crate::storage::doctor::{self, DoctorReport};
+use crate::storage::SearchTokenizer;
+use crate::AppState;
+
+// check database integrity, leftover legacy files, the search index and the
+// community meta cache. with repair set, fixable problems are fixed in place
+#[tauri::command]
+pub async fn run_storage_doctor(
+    state: State<'_, AppState>,
+    repair: bool,
+) -> Result<DoctorReport, String> {
+    ipc_log!("run_storage_doctor", {
+        // meta a repair may need is read from the documents up front, so the
+        // engine isn't held while the checks run against sqlite
+        let derived: HashMap<String, Result<CommunityMeta, String>> = if repair {
+            let engine = state.crdt_engine.lock().await;
+            engine
+                .community_ids()
+                .into_iter()
+                .chain(engine.archived_community_ids())
+                .map(|id| {
+                    let meta = engine.get_community_meta(&id);
+                    (id, meta)
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let storage = state.storage.clone();
+        let report = tauri::async_runtime::spawn_blocking(move || {
+            doctor::run(&storage, repair, |community_id| {
+                derived
+                    .get(community_id)
+                    .cloned()
+                    .unwrap_or_else(|| Err("community document not loaded".to_string()))
+            })
+        })
+        .await
+        .map_err(|e| format!("failed to run storage doctor: {}", e))?
+        .map_err(|e| format!("failed to run storage doctor: {}", e))?;
+
+        for finding in report.findings.iter().filter(|f| f.severity != doctor::Severity::Ok) {
+            log::warn!(
+                "storage doctor: {} {} (repaired: {})",
+                finding.check,
+                finding.message,
+                finding.repaired
+            );
+        }
+        Ok(report)
+    })
+}
+
+// switch the dm search tokenizer and rebuild the index with it, which takes
+// a while on large histories. returns false when it was already in use
+#[tauri::command]
+pub async fn set_search_tokenizer(
+    state: State<'_, AppState>,
+    tokenizer: SearchTokenizer,
+) -> Result<bool, String> {
+    ipc_log!("set_search_tokenizer", {
+        let mut settings = state
+            .storage
+            .load_settings()
+            .map_err(|e| format!("failed to load settings: {}", e))?;
+        settings.search_tokenizer = tokenizer;
+        state
+            .storage
+            .save_settings(&settings)
+            .map_err(|e| format!("failed to save settings: {}", e))?;
+
+        let storage = state.storage.clone();
+        tauri::async_runtime::spawn_blocking(move || storage.set_search_tokenizer(tokenizer))
+            .await
+            .map_err(|e| format!("failed to rebuild search index: {}", e))?
+            .map_err(|e| format!("failed to rebuild search index: {}", e))
+    })
+}
+
+// move all user data to another directory, then restart so storage reopens
+// there. the old location is left untouched if the copy fails
+#[tauri::command]
+pub async fn set_data_directory(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<(), String> {
+    ipc_log!("set_data_directory", {
+        if crate::storage::data_dir_overridden() {
+            return Err("data directory is pinned by --data-dir, DUSK_DATA_DIR or portable mode".to_string());
+        }
+
+        let dest = PathBuf::from(path.trim());
+        if !dest.is_absolute() {
+            return Err("data directory must be an absolute path".to_string());
+        }
+        let current = state.storage.data_dir().to_path_buf();
+        if dest.starts_with(&current) || current.starts_with(&dest) {
+            return Err("data directory cannot overlap the current location".to_string());
+        }
+        if dest.exists() {
+            let empty = std::fs::read_dir(&dest)
+                .map_err(|e| format!("failed to read data directory: {}", e))?
+                .next()
+                .is_none();
+            if !empty {
+                return Err("data directory must be empty".to_string());
+            }
+        }
+
+        // stop the node so nothing writes to the database mid-copy
+        {
+            let mut node_handle = state.node_handle.lock().await;
+            if let Some(handle) = node_handle.take() {
+                let _ = handle.command_tx.send(NodeCommand::Shutdown).await;
+                let _ = handle.task.await;
+            }
+        }
+
+        let storage = state.storage.clone();
+        let progress_app = app.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            storage.migrate_data_dir(&dest, |copied, total| {
+                let _ = progress_app.emit(
+                    "dusk-event",
+                    DuskEvent::DataMigrationProgress { copied, total },
+                );
+            })
+        })
+        .await
+        .map_err(|e| format!("data migration task failed: {}", e))?
+        .map_err(|e| format!("failed to migrate data directory: {}", e))?;
+
+        app.restart()
+    })
+}
@@ -0,0 +1,294 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use super::community::broadcast_sync;
+use super::{authz, ipc_log};
+use crate::protocol::community::{
+    TaskBoard, TaskCard, TaskCardPatch, TaskList, MAX_TASK_DESCRIPTION_LEN, MAX_TASK_TITLE_LEN,
+};
+use crate::AppState;
+
+// any member manages the board, deleting a list or card takes its creator
+// or an admin. changes reach the other members with the doc sync like any
+// other community doc change
+
+#[tauri::command]
+pub async fn get_task_board(
+    state: State<'_, AppState>,
+    community_id: String,
+) -> Result<TaskBoard, String> {
+    ipc_log!("get_task_board", {
+        let engine = state.crdt_engine.lock().await;
+        engine.get_task_board(&community_id)
+    })
+}
+
+#[tauri::command]
+pub async fn create_task_list(
+    state: State<'_, AppState>,
+    community_id: String,
+    name: String,
+    idempotency_key: Option<String>,
+) -> Result<TaskList, String> {
+    ipc_log!("create_task_list", {
+        let claim = state
+            .idempotency
+            .claim("create_task_list", idempotency_key)
+            .await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        let name = check_title(&name, "list name")?;
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_member()?;
+
+        let now = now_millis();
+        let mut list = TaskList {
+            id: task_id("tl", &community_id, &perms.peer_id, &name, now),
+            community_id: community_id.clone(),
+            name,
+            position: 0,
+            created_by: perms.peer_id.clone(),
+            created_at: now,
+        };
+        let mut engine = state.crdt_engine.lock().await;
+        list.position = engine.create_task_list(&community_id, &list)?;
+        drop(engine);
+        claim.complete(&list);
+
+        broadcast_sync(&state, &community_id).await;
+        Ok(list)
+    })
+}
+
+// rename a list and/or move it to another position on the board
+#[tauri::command]
+pub async fn update_task_list(
+    state: State<'_, AppState>,
+    community_id: String,
+    list_id: String,
+    name: Option<String>,
+    position: Option<u32>,
+) -> Result<TaskBoard, String> {
+    ipc_log!("update_task_list", {
+        let name = name
+            .map(|name| check_title(&name, "list name"))
+            .transpose()?;
+        authz::resolve(&state, &community_id)
+            .await?
+            .require_member()?;
+
+        let mut engine = state.crdt_engine.lock().await;
+        engine.update_task_list(&community_id, &list_id, name.as_deref(), position)?;
+        let board = engine.get_task_board(&community_id)?;
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+        Ok(board)
+    })
+}
+
+#[tauri::command]
+pub async fn delete_task_list(
+    state: State<'_, AppState>,
+    community_id: String,
+    list_id: String,
+) -> Result<TaskBoard, String> {
+    ipc_log!("delete_task_list", {
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_member()?;
+
+        let mut engine = state.crdt_engine.lock().await;
+        let list = engine
+            .get_task_board(&community_id)?
+            .lists
+            .into_iter()
+            .find(|l| l.id == list_id)
+            .ok_or("list not found")?;
+        if list.created_by != perms.peer_id && !perms.is_admin {
+            return Err("only the list's creator or an admin can delete it".to_string());
+        }
+        engine.delete_task_list(&community_id, &list_id)?;
+        let board = engine.get_task_board(&community_id)?;
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+        Ok(board)
+    })
+}
+
+// appended to the bottom of the list, assign and schedule it with update_task_card
+#[tauri::command]
+pub async fn create_task_card(
+    state: State<'_, AppState>,
+    community_id: String,
+    list_id: String,
+    title: String,
+    description: Option<String>,
+    idempotency_key: Option<String>,
+) -> Result<TaskCard, String> {
+    ipc_log!("create_task_card", {
+        let claim = state
+            .idempotency
+            .claim("create_task_card", idempotency_key)
+            .await;
+        if let Some(replayed) = claim.replayed()? {
+            return Ok(replayed);
+        }
+        let title = check_title(&title, "card title")?;
+        let description = description.unwrap_or_default();
+        check_description(&description)?;
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_member()?;
+
+        let now = now_millis();
+        let mut card = TaskCard {
+            id: task_id("tc", &community_id, &perms.peer_id, &title, now),
+            community_id: community_id.clone(),
+            list_id,
+            title,
+            description,
+            position: 0,
+            assignees: Vec::new(),
+            due_at: None,
+            done: false,
+            created_by: perms.peer_id.clone(),
+            created_at: now,
+            updated_at: now,
+        };
+        let mut engine = state.crdt_engine.lock().await;
+        let board = engine.get_task_board(&community_id)?;
+        if !board.lists.iter().any(|l| l.id == card.list_id) {
+            return Err("list not found".to_string());
+        }
+        card.position = engine.create_task_card(&community_id, &card)?;
+        drop(engine);
+        claim.complete(&card);
+
+        broadcast_sync(&state, &community_id).await;
+        Ok(card)
+    })
+}
+
+// edit, assign, schedule, complete or move a card
+#[tauri::command]
+pub async fn update_task_card(
+    state: State<'_, AppState>,
+    community_id: String,
+    card_id: String,
+    patch: TaskCardPatch,
+) -> Result<TaskBoard, String> {
+    ipc_log!("update_task_card", {
+        let mut patch = patch;
+        patch.title = patch
+            .title
+            .map(|title| check_title(&title, "card title"))
+            .transpose()?;
+        if let Some(ref description) = patch.description {
+            check_description(description)?;
+        }
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_member()?;
+        if let Some(ref assignees) = patch.assignees {
+            check_assignees(&perms, assignees)?;
+        }
+
+        let mut engine = state.crdt_engine.lock().await;
+        let board = engine.get_task_board(&community_id)?;
+        if !board.cards.iter().any(|c| c.id == card_id) {
+            return Err("card not found".to_string());
+        }
+        if let Some(ref list_id) = patch.list_id {
+            if !board.lists.iter().any(|l| l.id == *list_id) {
+                return Err("list not found".to_string());
+            }
+        }
+        engine.update_task_card(&community_id, &card_id, &patch)?;
+        let board = engine.get_task_board(&community_id)?;
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+        Ok(board)
+    })
+}
+
+#[tauri::command]
+pub async fn delete_task_card(
+    state: State<'_, AppState>,
+    community_id: String,
+    card_id: String,
+) -> Result<TaskBoard, String> {
+    ipc_log!("delete_task_card", {
+        let perms = authz::resolve(&state, &community_id).await?;
+        perms.require_member()?;
+
+        let mut engine = state.crdt_engine.lock().await;
+        let card = engine
+            .get_task_board(&community_id)?
+            .cards
+            .into_iter()
+            .find(|c| c.id == card_id)
+            .ok_or("card not found")?;
+        if card.created_by != perms.peer_id && !perms.is_admin {
+            return Err("only the card's creator or an admin can delete it".to_string());
+        }
+        engine.delete_task_card(&community_id, &card_id)?;
+        let board = engine.get_task_board(&community_id)?;
+        drop(engine);
+
+        broadcast_sync(&state, &community_id).await;
+        Ok(board)
+    })
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn task_id(prefix: &str, community_id: &str, peer_id: &str, name: &str, now: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(community_id.as_bytes());
+    hasher.update(peer_id.as_bytes());
+    hasher.update(name.as_bytes());
+    hasher.update(now.to_le_bytes());
+    format!("{}_{}", prefix, &hex::encode(hasher.finalize())[..12])
+}
+
+fn check_title(title: &str, what: &str) -> Result<String, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err(format!("{} cannot be empty", what));
+    }
+    if title.chars().count() > MAX_TASK_TITLE_LEN {
+        return Err(format!(
+            "{} is limited to {} characters",
+            what, MAX_TASK_TITLE_LEN
+        ));
+    }
+    Ok(title.to_string())
+}
+
+fn check_description(description: &str) -> Result<(), String> {
+    if description.chars().count() > MAX_TASK_DESCRIPTION_LEN {
+        return Err(format!(
+            "card description is limited to {} characters",
+            MAX_TASK_DESCRIPTION_LEN
+        ));
+    }
+    Ok(())
+}
+
+fn check_assignees(perms: &authz::Permissions, assignees: &[String]) -> Result<(), String> {
+    match assignees
+        .iter()
+        .find(|peer_id| perms.member(peer_id).is_none())
+    {
+        Some(peer_id) => Err(format!("{} is not a member of this community", peer_id)),
+        None => Ok(()),
+    }
+}
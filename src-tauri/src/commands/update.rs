@@ -0,0 +1,190 @@
+use tauri::State;
+
+use super::ipc_log;
+use crate::updater::{UpdateChannel, UpdateInfo};
+use crate::AppState;
+
+// switch between the stable and beta release channels
+#[tauri::command]
+pub async fn set_update_channel(state: State<'_, AppState>, channel: String) -> Result<(), String> {
+    ipc_log!("set_update_channel", {
+        let channel = UpdateChannel::parse(&channel)?;
+        let mut settings = state.storage.load_settings().unwrap_or_default();
+        settings.update_channel = channel;
+        state
+            .storage
+            .save_settings(&settings)
+            .map_err(|e| format!("failed to save settings: {}", e))
+    })
+}
+
+// look for a newer signed release on the user's channel, none when up to date
+// or when this install is not yet part of a staged rollout
+#[tauri::command]
+pub async fn check_for_updates(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<UpdateInfo>, String> {
+    ipc_log!("check_for_updates", {
+        let channel = state
+            .storage
+            .load_settings()
+            .map(|s| s.update_channel)
+            .unwrap_or_default();
+
+        #[cfg(desktop)]
+        {
+            let install_id = install_id(&state).await;
+            Ok(desktop::check(&app, channel, &install_id)
+                .await?
+                .map(|(_, info)| info))
+        }
+
+        #[cfg(not(desktop))]
+        {
+            let _ = (app, channel);
+            Err("updates are delivered through the app store on mobile".to_string())
+        }
+    })
+}
+
+// download, verify and install the pending release, then restart into it.
+// progress is reported through update_progress events
+#[tauri::command]
+pub async fn install_update(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    ipc_log!("install_update", {
+        let channel = state
+            .storage
+            .load_settings()
+            .map(|s| s.update_channel)
+            .unwrap_or_default();
+
+        #[cfg(desktop)]
+        {
+            let install_id = install_id(&state).await;
+            let (update, info) = desktop::check(&app, channel, &install_id)
+                .await?
+                .ok_or("no update available")?;
+
+            desktop::install(&app, update, &info.version).await?;
+
+            // leave the network cleanly before the process is replaced
+            let node_handle = state.node_handle.lock().await;
+            if let Some(ref handle) = *node_handle {
+                let _ = handle
+                    .command_tx
+                    .send(crate::node::NodeCommand::Shutdown)
+                    .await;
+            }
+            drop(node_handle);
+
+            app.restart()
+        }
+
+        #[cfg(not(desktop))]
+        {
+            let _ = (app, channel);
+            Err("updates are delivered through the app store on mobile".to_string())
+        }
+    })
+}
+
+// rollout buckets are keyed on the peer id so they survive reinstalls of the
+// same identity, installs without one all share a bucket
+#[cfg(desktop)]
+async fn install_id(state: &AppState) -> String {
+    let identity = state.identity.lock().await;
+    identity
+        .as_ref()
+        .map(|id| id.peer_id.to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(desktop)]
+mod desktop {
+    use tauri::Emitter;
+    use tauri_plugin_updater::{Update, UpdaterExt};
+
+    use crate::node::DuskEvent;
+    use crate::updater::{self, UpdateChannel, UpdateInfo};
+
+    pub async fn check(
+        app: &tauri::AppHandle,
+        channel: UpdateChannel,
+        install_id: &str,
+    ) -> Result<Option<(Update, UpdateInfo)>, String> {
+        let pubkey = updater::UPDATER_PUBKEY.ok_or("updates are not configured for this build")?;
+        let endpoint = updater::endpoint(channel)
+            .parse()
+            .map_err(|e| format!("invalid update endpoint: {}", e))?;
+
+        // signatures are checked against the baked-in key before anything is installed
+        let update = app
+            .updater_builder()
+            .pubkey(pubkey)
+            .endpoints(vec![endpoint])
+            .and_then(|builder| builder.build())
+            .map_err(|e| format!("failed to configure updater: {}", e))?
+            .check()
+            .await
+            .map_err(|e| format!("failed to check for updates: {}", e))?;
+
+        let update = match update {
+            Some(update) => update,
+            None => return Ok(None),
+        };
+
+        let rollout_percent = updater::rollout_percent(&update.raw_json);
+        if !updater::in_rollout(install_id, &update.version, rollout_percent) {
+            log::info!(
+                "update {} is at {}% rollout, not offered to this install yet",
+                update.version,
+                rollout_percent
+            );
+            return Ok(None);
+        }
+
+        let info = UpdateInfo {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            channel,
+            notes: update.body.clone(),
+            date: update.date.map(|d| d.to_string()),
+            rollout_percent,
+        };
+        Ok(Some((update, info)))
+    }
+
+    pub async fn install(app: &tauri::AppHandle, update: Update, version: &str) -> Result<(), String> {
+        let mut downloaded: u64 = 0;
+        let progress_app = app.clone();
+        let progress_version = version.to_string();
+        let finished_app = app.clone();
+        let finished_version = version.to_string();
+
+        update
+            .download_and_install(
+                move |chunk, total| {
+                    downloaded += chunk as u64;
+                    let _ = progress_app.emit(
+                        "dusk-event",
+                        DuskEvent::UpdateProgress {
+                            version: progress_version.clone(),
+                            downloaded,
+                            total,
+                        },
+                    );
+                },
+                move || {
+                    let _ = finished_app.emit(
+                        "dusk-event",
+                        DuskEvent::UpdateDownloaded {
+                            version: finished_version,
+                        },
+                    );
+                },
+            )
+            .await
+            .map_err(|e| format!("failed to install update: {}", e))
+    }
+}
@@ -1,8 +1,16 @@
+use std::time::Duration;
+
 use tauri::State;
+use tokio::time::timeout;
 
+use super::authz;
+use super::ipc_log;
 use crate::node::gossip;
 use crate::node::NodeCommand;
-use crate::protocol::messages::{GossipMessage, VoiceMediaState, VoiceParticipant};
+use crate::protocol::community::ChannelKind;
+use crate::protocol::messages::{
+    sdp_sends_audio, GossipMessage, StageRole, VoiceMediaState, VoiceParticipant,
+};
 use crate::protocol::turn::TurnCredentialResponse;
 use crate::AppState;
 
@@ -21,7 +29,9 @@ pub async fn join_voice_channel(
     let id = identity.as_ref().ok_or("no identity loaded")?;
 
     let peer_id = id.peer_id.to_string();
-    let display_name = id.display_name.clone();
+    let display_name = state
+        .storage
+        .community_display_name(&community_id, &peer_id, &id.display_name);
     drop(identity);
 
     let media_state = VoiceMediaState {
@@ -31,6 +41,36 @@ pub async fn join_voice_channel(
         screen_sharing: false,
     };
 
+    // learn who is already in the call first, announcements only cover what
+    // arrives after we subscribe. a full channel turns us away here, the
+    // participants enforce the cap again when our join arrives
+    let roster = fetch_voice_roster(&state, &community_id, &channel_id)
+        .await
+        .unwrap_or_default();
+    let (capacity, stage_role) = {
+        let engine = state.crdt_engine.lock().await;
+        let channel = engine
+            .get_channels(&community_id)?
+            .into_iter()
+            .find(|c| c.id == channel_id);
+        // on a stage only moderators start out as speakers
+        let stage_role = match channel {
+            Some(ref c)
+                if matches!(c.kind, ChannelKind::Stage)
+                    && !engine.is_stage_moderator(&community_id, &peer_id, &channel_id) =>
+            {
+                StageRole::Listener
+            }
+            _ => StageRole::Speaker,
+        };
+        (channel.and_then(|c| c.max_participants), stage_role)
+    };
+    if let Some(max) = capacity {
+        if roster.iter().filter(|p| p.peer_id != peer_id).count() >= max as usize {
+            return Err(format!("voice channel is full ({} participants)", max));
+        }
+    }
+
     // subscribe to the voice topic for this channel
     let voice_topic = gossip::topic_for_voice(&community_id, &channel_id);
     let node_handle = state.node_handle.lock().await;
@@ -90,6 +130,7 @@ pub async fn join_voice_channel(
         eprintln!("[Voice] No node handle available — cannot join voice channel");
         return Err("Node not running — cannot join voice channel".to_string());
     }
+    drop(node_handle);
 
     // add ourselves to the local voice channel tracking
     let key = format!("{}:{}", community_id, channel_id);
@@ -100,11 +141,16 @@ pub async fn join_voice_channel(
         peer_id,
         display_name,
         media_state,
+        stage_role,
+        hand_raised: false,
     });
 
     let result = participants.clone();
     drop(vc);
 
+    // start the new call with fresh denoiser and gain state
+    state.audio_processor.lock().await.reset();
+
     log::info!("joined voice channel {}:{}", community_id, channel_id);
 
     Ok(result)
@@ -276,7 +322,30 @@ pub async fn send_voice_sdp(
     let from_peer = id.peer_id.to_string();
     drop(identity);
 
-    let voice_topic = gossip::topic_for_voice(&community_id, &channel_id);
+    // the other side drops these anyway, fail early so the frontend knows
+    if sdp_type == "offer" && sdp_sends_audio(&sdp) {
+        let key = format!("{}:{}", community_id, channel_id);
+        let listening = state
+            .voice_channels
+            .lock()
+            .await
+            .get(&key)
+            .is_some_and(|participants| {
+                participants
+                    .iter()
+                    .any(|p| p.peer_id == from_peer && p.stage_role == StageRole::Listener)
+            });
+        if listening {
+            return Err("stage listeners can't send audio, raise your hand to speak".to_string());
+        }
+    }
+
+    // dm calls have no voice channel, signal over the pair topic
+    let voice_topic = if community_id == gossip::DM_CALL_SCOPE {
+        gossip::topic_for_dm(&from_peer, &to_peer)
+    } else {
+        gossip::topic_for_voice(&community_id, &channel_id)
+    };
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
         let msg = GossipMessage::VoiceSdp {
@@ -332,7 +401,12 @@ pub async fn send_voice_ice_candidate(
     let from_peer = id.peer_id.to_string();
     drop(identity);
 
-    let voice_topic = gossip::topic_for_voice(&community_id, &channel_id);
+    // dm calls have no voice channel, signal over the pair topic
+    let voice_topic = if community_id == gossip::DM_CALL_SCOPE {
+        gossip::topic_for_dm(&from_peer, &to_peer)
+    } else {
+        gossip::topic_for_voice(&community_id, &channel_id)
+    };
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
         let msg = GossipMessage::VoiceIceCandidate {
@@ -369,6 +443,78 @@ pub async fn send_voice_ice_candidate(
     Ok(())
 }
 
+// called many times a second with the local microphone level, so unlike the
+// other voice commands it stays quiet in the logs and never waits on the node
+#[tauri::command]
+pub async fn report_voice_activity(
+    state: State<'_, AppState>,
+    community_id: String,
+    channel_id: String,
+    level: f32,
+) -> Result<(), String> {
+    let node_handle = state.node_handle.lock().await;
+    let handle = node_handle.as_ref().ok_or("node not running")?;
+    // a full queue just drops this sample, the next one follows shortly
+    let _ = handle.command_tx.try_send(NodeCommand::ReportVoiceActivity {
+        community_id,
+        channel_id,
+        level: level.clamp(0.0, 1.0),
+    });
+    Ok(())
+}
+
+// microphone frames routed through rust before encoding. the body is raw
+// little-endian f32 mono samples at 48khz and the reply has the same layout,
+// raw ipc avoids json encoding a few hundred floats every 10ms
+#[tauri::command]
+pub async fn process_audio_frame(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+) -> Result<tauri::ipc::Response, String> {
+    let bytes = match request.body() {
+        tauri::ipc::InvokeBody::Raw(bytes) => bytes,
+        _ => return Err("audio frames must be sent as raw bytes".to_string()),
+    };
+    if bytes.len() % 4 != 0 {
+        return Err("audio frame length must be a multiple of 4 bytes".to_string());
+    }
+
+    let samples: Vec<f32> = bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    let processed = state.audio_processor.lock().await.process(&samples);
+
+    let out: Vec<u8> = processed.iter().flat_map(|s| s.to_le_bytes()).collect();
+    Ok(tauri::ipc::Response::new(out))
+}
+
+#[tauri::command]
+pub async fn set_audio_processing(
+    state: State<'_, AppState>,
+    noise_suppression: bool,
+    auto_gain_control: bool,
+) -> Result<(), String> {
+    eprintln!(
+        "[Voice] set_audio_processing called: noise_suppression={}, auto_gain_control={}",
+        noise_suppression, auto_gain_control
+    );
+
+    state
+        .audio_processor
+        .lock()
+        .await
+        .configure(noise_suppression, auto_gain_control);
+
+    let mut settings = state.storage.load_settings().unwrap_or_default();
+    settings.noise_suppression = noise_suppression;
+    settings.auto_gain_control = auto_gain_control;
+    state
+        .storage
+        .save_settings(&settings)
+        .map_err(|e| format!("failed to save settings: {}", e))
+}
+
 #[tauri::command]
 pub async fn get_voice_participants(
     state: State<'_, AppState>,
@@ -381,8 +527,14 @@ pub async fn get_voice_participants(
     );
 
     let key = format!("{}:{}", community_id, channel_id);
-    let vc = state.voice_channels.lock().await;
-    let participants = vc.get(&key).cloned().unwrap_or_default();
+    let known = state.voice_channels.lock().await.get(&key).cloned();
+    // nothing heard on this channel yet, ask someone in the call
+    let participants = match known {
+        Some(participants) => participants,
+        None => fetch_voice_roster(&state, &community_id, &channel_id)
+            .await
+            .unwrap_or_default(),
+    };
 
     eprintln!(
         "[Voice] Returning {} participants for {}",
@@ -393,6 +545,247 @@ pub async fn get_voice_participants(
     Ok(participants)
 }
 
+// take a participant out of a call, moderators only
+#[tauri::command]
+pub async fn disconnect_voice_participant(
+    state: State<'_, AppState>,
+    community_id: String,
+    channel_id: String,
+    peer_id: String,
+) -> Result<(), String> {
+    ipc_log!("disconnect_voice_participant", {
+        send_voice_disconnect(&state, community_id, channel_id, peer_id, None).await
+    })
+}
+
+// move a participant into another voice channel of the same community
+#[tauri::command]
+pub async fn move_voice_participant(
+    state: State<'_, AppState>,
+    community_id: String,
+    channel_id: String,
+    peer_id: String,
+    target_channel_id: String,
+) -> Result<(), String> {
+    ipc_log!("move_voice_participant", {
+        if target_channel_id == channel_id {
+            return Err("participant is already in that channel".to_string());
+        }
+        let target_is_voice = {
+            let engine = state.crdt_engine.lock().await;
+            engine
+                .get_channels(&community_id)?
+                .iter()
+                .any(|c| c.id == target_channel_id && c.kind.is_voice())
+        };
+        if !target_is_voice {
+            return Err("target is not a voice channel".to_string());
+        }
+        send_voice_disconnect(
+            &state,
+            community_id,
+            channel_id,
+            peer_id,
+            Some(target_channel_id),
+        )
+        .await
+    })
+}
+
+// ask the stage's moderators to let us speak, or take the request back
+#[tauri::command]
+pub async fn raise_stage_hand(
+    state: State<'_, AppState>,
+    community_id: String,
+    channel_id: String,
+    raised: bool,
+) -> Result<(), String> {
+    ipc_log!("raise_stage_hand", {
+        let peer_id = {
+            let identity = state.identity.lock().await;
+            identity
+                .as_ref()
+                .ok_or("no identity loaded")?
+                .peer_id
+                .to_string()
+        };
+
+        let key = format!("{}:{}", community_id, channel_id);
+        let mut vc = state.voice_channels.lock().await;
+        let me = vc
+            .get_mut(&key)
+            .and_then(|participants| participants.iter_mut().find(|p| p.peer_id == peer_id))
+            .ok_or("not in this voice channel")?;
+        if me.stage_role != StageRole::Listener {
+            return Err("only stage listeners raise their hand".to_string());
+        }
+        me.hand_raised = raised;
+        drop(vc);
+
+        let msg = GossipMessage::StageHandRaise {
+            community_id: community_id.clone(),
+            channel_id: channel_id.clone(),
+            peer_id,
+            raised,
+        };
+        publish_voice(&state, &community_id, &channel_id, &msg).await
+    })
+}
+
+// make a stage participant a speaker or a listener. moderators only, except
+// that anyone may step down to listener themselves
+#[tauri::command]
+pub async fn set_stage_role(
+    state: State<'_, AppState>,
+    community_id: String,
+    channel_id: String,
+    peer_id: String,
+    speaker: bool,
+) -> Result<(), String> {
+    ipc_log!("set_stage_role", {
+        let perms = authz::resolve(&state, &community_id).await?;
+        let is_stage = {
+            let engine = state.crdt_engine.lock().await;
+            engine
+                .get_channels(&community_id)?
+                .iter()
+                .any(|c| c.id == channel_id && matches!(c.kind, ChannelKind::Stage))
+        };
+        if !is_stage {
+            return Err("not a stage channel".to_string());
+        }
+        let role = if speaker {
+            StageRole::Speaker
+        } else {
+            StageRole::Listener
+        };
+        let capability = if peer_id == perms.peer_id && role == StageRole::Listener {
+            None
+        } else {
+            perms.authorize_voice_move(&peer_id, &channel_id)?
+        };
+
+        let key = format!("{}:{}", community_id, channel_id);
+        let mut vc = state.voice_channels.lock().await;
+        let target = vc
+            .get_mut(&key)
+            .and_then(|participants| participants.iter_mut().find(|p| p.peer_id == peer_id))
+            .ok_or("participant is not in this voice channel")?;
+        target.stage_role = role;
+        target.hand_raised = false;
+        drop(vc);
+
+        let msg = GossipMessage::StageRoleChange {
+            community_id: community_id.clone(),
+            channel_id: channel_id.clone(),
+            peer_id,
+            role,
+            capability,
+        };
+        publish_voice(&state, &community_id, &channel_id, &msg).await
+    })
+}
+
+async fn publish_voice(
+    state: &AppState,
+    community_id: &str,
+    channel_id: &str,
+    msg: &GossipMessage,
+) -> Result<(), String> {
+    let data = serde_json::to_vec(msg).map_err(|e| format!("serialize error: {}", e))?;
+    let node_handle = state.node_handle.lock().await;
+    let handle = node_handle.as_ref().ok_or("node not running")?;
+    handle
+        .command_tx
+        .send(NodeCommand::SendMessage {
+            topic: gossip::topic_for_voice(community_id, channel_id),
+            data,
+        })
+        .await
+        .map_err(|e| format!("failed to publish to voice channel: {}", e))
+}
+
+// the target's node leaves the call on receipt and, for a move, its
+// frontend joins move_to. everyone else drops it from the participants
+async fn send_voice_disconnect(
+    state: &AppState,
+    community_id: String,
+    channel_id: String,
+    peer_id: String,
+    move_to: Option<String>,
+) -> Result<(), String> {
+    let capability = authz::resolve(state, &community_id)
+        .await?
+        .authorize_voice_move(&peer_id, &channel_id)?;
+
+    let key = format!("{}:{}", community_id, channel_id);
+    let mut vc = state.voice_channels.lock().await;
+    if let Some(participants) = vc.get_mut(&key) {
+        participants.retain(|p| p.peer_id != peer_id);
+        if participants.is_empty() {
+            vc.remove(&key);
+        }
+    }
+    drop(vc);
+
+    let msg = GossipMessage::VoiceDisconnect {
+        community_id: community_id.clone(),
+        channel_id: channel_id.clone(),
+        peer_id,
+        move_to,
+        capability,
+    };
+    let data = serde_json::to_vec(&msg).map_err(|e| format!("serialize error: {}", e))?;
+    let node_handle = state.node_handle.lock().await;
+    let handle = node_handle.as_ref().ok_or("node not running")?;
+    handle
+        .command_tx
+        .send(NodeCommand::SendMessage {
+            topic: gossip::topic_for_voice(&community_id, &channel_id),
+            data,
+        })
+        .await
+        .map_err(|e| format!("failed to send voice disconnect: {}", e))
+}
+
+// ask a participant on the voice topic for the call's roster, merged into
+// the tracked participants by the node. None when nobody answered in time
+async fn fetch_voice_roster(
+    state: &AppState,
+    community_id: &str,
+    channel_id: &str,
+) -> Option<Vec<VoiceParticipant>> {
+    let rx = {
+        let node_handle = state.node_handle.lock().await;
+        let handle = node_handle.as_ref()?;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        handle
+            .command_tx
+            .send(NodeCommand::FetchVoiceRoster {
+                community_id: community_id.to_string(),
+                channel_id: channel_id.to_string(),
+                reply: tx,
+            })
+            .await
+            .ok()?;
+        rx
+    };
+
+    match timeout(Duration::from_secs(3), rx).await {
+        Ok(Ok(Ok(participants))) => Some(participants),
+        Ok(Ok(Err(e))) => {
+            log::debug!(
+                "voice roster for {}:{} unavailable: {}",
+                community_id,
+                channel_id,
+                e
+            );
+            None
+        }
+        _ => None,
+    }
+}
+
 #[tauri::command]
 pub async fn get_turn_credentials(
     state: State<'_, AppState>,
@@ -0,0 +1,179 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+// oldest dumps are pruned beyond this many
+const MAX_CRASH_REPORTS: usize = 20;
+// a peer id in its base58 text form
+const PEER_ID_PREFIX: &str = "12D3KooW";
+
+// a crash dump written by the panic hook, nothing leaves the machine
+// until the user submits it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub id: String,
+    pub created_at: u64,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub thread: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+    pub recent_logs: Vec<String>,
+    #[serde(default)]
+    pub submitted: bool,
+}
+
+// write a redacted dump for every panic, then fall through to the default hook
+pub fn install_panic_hook(crash_dir: PathBuf) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+
+        // the panic may have happened while logging on this thread, so the
+        // lines are only taken if nothing holds them
+        let recent_logs = crate::logging::recent_lines()
+            .iter()
+            .map(|line| redact(line))
+            .collect();
+
+        let created_at = now_millis();
+        let report = CrashReport {
+            id: format!("crash_{}", created_at),
+            created_at,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            thread: std::thread::current().name().unwrap_or("unnamed").to_string(),
+            message: redact(&message),
+            location,
+            backtrace: redact(&std::backtrace::Backtrace::force_capture().to_string()),
+            recent_logs,
+            submitted: false,
+        };
+
+        if let Err(e) = save_report(&crash_dir, &report) {
+            eprintln!("failed to write crash report: {}", e);
+        }
+
+        default_hook(info);
+    }));
+}
+
+// crash dumps newest first
+pub fn list_reports(crash_dir: &Path) -> Result<Vec<CrashReport>, io::Error> {
+    let entries = match fs::read_dir(crash_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut reports: Vec<CrashReport> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().map(|ext| ext == "json").unwrap_or(false))
+        .filter_map(|entry| fs::read(entry.path()).ok())
+        .filter_map(|bytes| serde_json::from_slice(&bytes).ok())
+        .collect();
+    reports.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(reports)
+}
+
+pub fn load_report(crash_dir: &Path, id: &str) -> Result<CrashReport, io::Error> {
+    // ids are generated by us, refuse anything that could escape the directory
+    if !id.starts_with("crash_") || !id[6..].chars().all(|c| c.is_ascii_digit()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid crash report id"));
+    }
+    let bytes = fs::read(crash_dir.join(format!("{}.json", id)))?;
+    serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn save_report(crash_dir: &Path, report: &CrashReport) -> Result<(), io::Error> {
+    fs::create_dir_all(crash_dir)?;
+    let json = serde_json::to_vec_pretty(report)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(crash_dir.join(format!("{}.json", report.id)), json)?;
+
+    // keep the directory bounded if the app crash-loops
+    if let Ok(reports) = list_reports(crash_dir) {
+        for old in reports.iter().skip(MAX_CRASH_REPORTS) {
+            let _ = fs::remove_file(crash_dir.join(format!("{}.json", old.id)));
+        }
+    }
+    Ok(())
+}
+
+// strip anything that identifies the user: home directory paths, peer ids,
+// keys and signatures, and ip addresses inside multiaddrs
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+        let home = home.to_string_lossy().to_string();
+        if !home.is_empty() {
+            text = text.replace(&home, "~");
+        }
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut token = String::new();
+    for c in text.chars().chain(std::iter::once('\n')) {
+        if c.is_ascii_alphanumeric() {
+            token.push(c);
+            continue;
+        }
+        out.push_str(&redact_token(&token));
+        token.clear();
+        out.push(c);
+    }
+    out.pop();
+
+    redact_multiaddr_ips(&out)
+}
+
+fn redact_token(token: &str) -> String {
+    if token.starts_with(PEER_ID_PREFIX) && token.len() >= 46 {
+        return "<peer_id>".to_string();
+    }
+    if token.len() >= 32 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+        return "<hex>".to_string();
+    }
+    token.to_string()
+}
+
+fn redact_multiaddr_ips(text: &str) -> String {
+    let mut out = text.to_string();
+    for marker in ["/ip4/", "/ip6/"] {
+        let mut result = String::with_capacity(out.len());
+        let mut rest = out.as_str();
+        while let Some(pos) = rest.find(marker) {
+            result.push_str(&rest[..pos + marker.len()]);
+            let after = &rest[pos + marker.len()..];
+            let end = after
+                .find(|c: char| c == '/' || c.is_whitespace() || c == '"' || c == ',')
+                .unwrap_or(after.len());
+            result.push_str("<ip>");
+            rest = &after[end..];
+        }
+        result.push_str(rest);
+        out = result;
+    }
+    out
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
@@ -1,8 +1,15 @@
 use automerge::{transaction::Transactable, AutoCommit, ObjType, ReadDoc, ROOT};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::protocol::community::{CategoryMeta, ChannelKind, ChannelMeta, CommunityMeta};
-use crate::protocol::messages::ChatMessage;
+use crate::protocol::community::{
+    CapabilityToken, CategoryMeta, ChannelKind, ChannelMeta, CommunityMeta, DocCheckpoint,
+    DocConflict, MembershipAction, MembershipEvent, MembershipOrder,
+};
+use crate::protocol::identity::VerificationPolicy;
+use crate::protocol::messages::{
+    ChatMessage, Hlc, MessageOrigin, MessageReaction, MessageRevision, MessageThread,
+    ThreadSummary, MAX_REACTIONS_PER_MESSAGE,
+};
 
 // initialize a new community document with metadata and a default general channel
 pub fn init_community_doc(
@@ -22,12 +29,17 @@ pub fn init_community_doc(
     doc.put(&meta, "name", name)?;
     doc.put(&meta, "description", description)?;
     doc.put(&meta, "created_by", created_by)?;
+    // created_by follows ownership transfers, the founder stays put so the
+    // membership log knows whose first join carries the owner role
+    doc.put(&meta, "founder", created_by)?;
     doc.put(&meta, "created_at", now as i64)?;
 
     let channels = doc.put_object(ROOT, "channels", ObjType::Map)?;
     let _categories = doc.put_object(ROOT, "categories", ObjType::Map)?;
     let members = doc.put_object(ROOT, "members", ObjType::Map)?;
     let _roles = doc.put_object(ROOT, "roles", ObjType::Map)?;
+    super::tasks::init_tasks(doc)?;
+    let _pages = doc.put_object(ROOT, "pages", ObjType::Map)?;
 
     // create a default general channel
     let general_id = format!(
@@ -171,6 +183,8 @@ pub fn add_channel(
         match channel.kind {
             ChannelKind::Text => "text",
             ChannelKind::Voice => "voice",
+            ChannelKind::Announcement => "announcement",
+            ChannelKind::Stage => "stage",
         },
     )?;
     doc.put(&ch, "position", position as i64)?;
@@ -333,10 +347,15 @@ pub fn get_channels(doc: &AutoCommit, community_id: &str) -> Result<Vec<ChannelM
             let kind_str = get_str(doc, &ch_id, "kind").unwrap_or_else(|| "text".to_string());
             let kind = match kind_str.as_str() {
                 "voice" => ChannelKind::Voice,
+                "announcement" => ChannelKind::Announcement,
+                "stage" => ChannelKind::Stage,
                 _ => ChannelKind::Text,
             };
             let position = get_i64(doc, &ch_id, "position").unwrap_or(0) as u32;
             let category_id = get_str(doc, &ch_id, "category_id");
+            let max_participants = get_i64(doc, &ch_id, "max_participants")
+                .filter(|max| *max > 0)
+                .map(|max| max.min(u32::MAX as i64) as u32);
 
             result.push(ChannelMeta {
                 id: key.to_string(),
@@ -346,6 +365,7 @@ pub fn get_channels(doc: &AutoCommit, community_id: &str) -> Result<Vec<ChannelM
                 kind,
                 position,
                 category_id,
+                max_participants,
             });
         }
     }
@@ -357,11 +377,12 @@ pub fn get_channels(doc: &AutoCommit, community_id: &str) -> Result<Vec<ChannelM
 }
 
 // append a message to a channel's message list
+// returns false without writing when a message with the same id already exists
 pub fn append_message(
     doc: &mut AutoCommit,
     channel_id: &str,
     message: &ChatMessage,
-) -> Result<(), automerge::AutomergeError> {
+) -> Result<bool, automerge::AutomergeError> {
     let channels = doc
         .get(ROOT, "channels")?
         .map(|(_, id)| id)
@@ -378,6 +399,26 @@ pub fn append_message(
         .ok_or_else(|| automerge::AutomergeError::InvalidObjId("messages not found".to_string()))?;
 
     let len = doc.length(&messages);
+
+    // republished or relayed-twice messages must not show up twice. a reply
+    // to a reply from an older peer joins the thread the latter is in
+    let mut thread_root = message.reply_to.clone();
+    let mut root_obj = None;
+    for i in 0..len {
+        if let Some((_, existing)) = doc.get(&messages, i)? {
+            let existing_id = get_str(doc, &existing, "id");
+            if existing_id.as_deref() == Some(message.id.as_str()) {
+                return Ok(false);
+            }
+            if existing_id.is_some() && existing_id == message.reply_to {
+                match get_str(doc, &existing, "reply_to") {
+                    Some(root_id) => thread_root = Some(root_id),
+                    None => root_obj = Some(existing),
+                }
+            }
+        }
+    }
+
     let msg_obj = doc.insert_object(&messages, len, ObjType::Map)?;
     doc.put(&msg_obj, "id", message.id.as_str())?;
     doc.put(&msg_obj, "author_id", message.author_id.as_str())?;
@@ -385,8 +426,37 @@ pub fn append_message(
     doc.put(&msg_obj, "content", message.content.as_str())?;
     doc.put(&msg_obj, "timestamp", message.timestamp as i64)?;
     doc.put(&msg_obj, "edited", message.edited)?;
+    doc.put(&msg_obj, "hlc_wall", message.hlc.wall as i64)?;
+    doc.put(&msg_obj, "hlc_counter", message.hlc.counter as i64)?;
+    if let Some(ref origin) = message.forwarded_from {
+        let origin_obj = doc.put_object(&msg_obj, "forwarded_from", ObjType::Map)?;
+        doc.put(&origin_obj, "community_id", origin.community_id.as_str())?;
+        doc.put(
+            &origin_obj,
+            "community_name",
+            origin.community_name.as_str(),
+        )?;
+        doc.put(&origin_obj, "channel_id", origin.channel_id.as_str())?;
+        doc.put(&origin_obj, "channel_name", origin.channel_name.as_str())?;
+        doc.put(&origin_obj, "message_id", origin.message_id.as_str())?;
+        doc.put(&origin_obj, "author_id", origin.author_id.as_str())?;
+        doc.put(&origin_obj, "author_name", origin.author_name.as_str())?;
+    }
+    put_json_list(doc, &msg_obj, "attachments", &message.attachments)?;
+    put_json_list(doc, &msg_obj, "embeds", &message.embeds)?;
+    if let Some(ref root_id) = thread_root {
+        doc.put(&msg_obj, "reply_to", root_id.as_str())?;
+        index_reply(
+            doc,
+            &channel,
+            root_id,
+            root_obj.as_ref(),
+            &message.id,
+            &msg_obj,
+        )?;
+    }
 
-    Ok(())
+    Ok(true)
 }
 
 // read messages from a channel, optionally filtered and limited
@@ -417,8 +487,7 @@ pub fn get_messages(
     let len = doc.length(&messages);
     let mut result = Vec::new();
 
-    // iterate backwards for most recent first, then reverse for chronological order
-    for i in (0..len).rev() {
+    for i in 0..len {
         let msg_obj = doc
             .get(&messages, i)
             .map_err(|e| e.to_string())?
@@ -433,29 +502,44 @@ pub fn get_messages(
                 }
             }
 
-            let msg = ChatMessage {
-                id: get_str(doc, &msg_id, "id").unwrap_or_default(),
-                channel_id: channel_id.to_string(),
-                author_id: get_str(doc, &msg_id, "author_id").unwrap_or_default(),
-                author_name: get_str(doc, &msg_id, "author_name").unwrap_or_default(),
-                content: get_str(doc, &msg_id, "content").unwrap_or_default(),
-                timestamp,
-                edited: get_bool(doc, &msg_id, "edited").unwrap_or(false),
-            };
-
-            result.push(msg);
-
-            if result.len() >= limit {
-                break;
-            }
+            result.push(read_message(doc, &msg_id, channel_id));
         }
     }
 
-    // reverse to get chronological order
-    result.reverse();
+    // list position depends on when each peer inserted, so order by logical clock
+    // and fall back to the message id for a stable tiebreak across peers
+    result.sort_by(|a, b| a.hlc.cmp(&b.hlc).then_with(|| a.id.cmp(&b.id)));
+
+    // keep only the most recent messages in chronological order
+    if result.len() > limit {
+        result.drain(..result.len() - limit);
+    }
     Ok(result)
 }
 
+// number of messages in a channel without reading them
+pub fn count_messages(doc: &AutoCommit, channel_id: &str) -> Result<usize, String> {
+    let channels = doc
+        .get(ROOT, "channels")
+        .map_err(|e| e.to_string())?
+        .map(|(_, id)| id)
+        .ok_or("channels not found")?;
+
+    let channel = doc
+        .get(&channels, channel_id)
+        .map_err(|e| e.to_string())?
+        .map(|(_, id)| id)
+        .ok_or("channel not found")?;
+
+    let messages = doc
+        .get(&channel, "messages")
+        .map_err(|e| e.to_string())?
+        .map(|(_, id)| id)
+        .ok_or("messages not found")?;
+
+    Ok(doc.length(&messages))
+}
+
 // read community metadata from the document
 pub fn get_community_meta(doc: &AutoCommit, community_id: &str) -> Result<CommunityMeta, String> {
     let meta = doc
@@ -470,9 +554,70 @@ pub fn get_community_meta(doc: &AutoCommit, community_id: &str) -> Result<Commun
         description: get_str(doc, &meta, "description").unwrap_or_default(),
         created_by: get_str(doc, &meta, "created_by").unwrap_or_default(),
         created_at: get_i64(doc, &meta, "created_at").unwrap_or(0) as u64,
+        verification_policy: get_str(doc, &meta, "verification_policy")
+            .and_then(|p| VerificationPolicy::parse(&p).ok())
+            .unwrap_or_else(crate::protocol::community::default_community_policy),
+        edit_history: get_bool(doc, &meta, "edit_history").unwrap_or(false),
+        archived: false,
     })
 }
 
+// creator recorded at community creation, missing in docs made before the
+// membership log existed
+pub fn get_founder(doc: &AutoCommit) -> Option<String> {
+    let (_, meta) = doc.get(ROOT, "meta").ok().flatten()?;
+    get_str(doc, &meta, "founder")
+}
+
+// put back the founder a merge changed. none removes a founder that a merge
+// brought into a doc created without one
+pub fn restore_founder(
+    doc: &mut AutoCommit,
+    founder: Option<&str>,
+) -> Result<(), automerge::AutomergeError> {
+    let meta = doc
+        .get(ROOT, "meta")?
+        .map(|(_, id)| id)
+        .ok_or_else(|| automerge::AutomergeError::InvalidObjId("meta not found".to_string()))?;
+
+    match founder {
+        Some(founder) => doc.put(&meta, "founder", founder)?,
+        None => doc.delete(&meta, "founder")?,
+    }
+
+    Ok(())
+}
+
+// set the minimum verification policy members must meet
+pub fn set_verification_policy(
+    doc: &mut AutoCommit,
+    policy: VerificationPolicy,
+) -> Result<(), automerge::AutomergeError> {
+    let meta = doc
+        .get(ROOT, "meta")?
+        .map(|(_, id)| id)
+        .ok_or_else(|| automerge::AutomergeError::InvalidObjId("meta not found".to_string()))?;
+
+    doc.put(&meta, "verification_policy", policy.as_str())?;
+
+    Ok(())
+}
+
+// turn edit history on or off, revisions already stored are kept
+pub fn set_edit_history(
+    doc: &mut AutoCommit,
+    enabled: bool,
+) -> Result<(), automerge::AutomergeError> {
+    let meta = doc
+        .get(ROOT, "meta")?
+        .map(|(_, id)| id)
+        .ok_or_else(|| automerge::AutomergeError::InvalidObjId("meta not found".to_string()))?;
+
+    doc.put(&meta, "edit_history", enabled)?;
+
+    Ok(())
+}
+
 // reorder channels by updating their positions
 pub fn reorder_channels(
     doc: &mut AutoCommit,
@@ -502,27 +647,92 @@ pub fn reorder_channels(
 
 // -- helpers for reading automerge values --
 
-fn get_str(doc: &AutoCommit, obj: &automerge::ObjId, key: &str) -> Option<String> {
+pub(super) fn get_str(doc: &AutoCommit, obj: &automerge::ObjId, key: &str) -> Option<String> {
     doc.get(obj, key)
         .ok()
         .flatten()
         .and_then(|(val, _)| val.into_string().ok())
 }
 
-fn get_i64(doc: &AutoCommit, obj: &automerge::ObjId, key: &str) -> Option<i64> {
+pub(super) fn get_i64(doc: &AutoCommit, obj: &automerge::ObjId, key: &str) -> Option<i64> {
     doc.get(obj, key)
         .ok()
         .flatten()
         .and_then(|(val, _)| val.to_i64())
 }
 
-fn get_bool(doc: &AutoCommit, obj: &automerge::ObjId, key: &str) -> Option<bool> {
+pub(super) fn get_bool(doc: &AutoCommit, obj: &automerge::ObjId, key: &str) -> Option<bool> {
     doc.get(obj, key)
         .ok()
         .flatten()
         .and_then(|(val, _)| val.to_bool())
 }
 
+// messages written before logical clocks existed fall back to their wall timestamp
+fn get_hlc(doc: &AutoCommit, obj: &automerge::ObjId, timestamp: u64) -> Hlc {
+    match get_i64(doc, obj, "hlc_wall") {
+        Some(wall) => Hlc {
+            wall: wall.max(0) as u64,
+            counter: get_i64(doc, obj, "hlc_counter").unwrap_or(0).max(0) as u32,
+        },
+        None => Hlc {
+            wall: timestamp,
+            counter: 0,
+        },
+    }
+}
+
+fn get_origin(doc: &AutoCommit, msg_obj: &automerge::ObjId) -> Option<MessageOrigin> {
+    let (_, origin) = doc.get(msg_obj, "forwarded_from").ok().flatten()?;
+    Some(MessageOrigin {
+        community_id: get_str(doc, &origin, "community_id")?,
+        community_name: get_str(doc, &origin, "community_name").unwrap_or_default(),
+        channel_id: get_str(doc, &origin, "channel_id")?,
+        channel_name: get_str(doc, &origin, "channel_name").unwrap_or_default(),
+        message_id: get_str(doc, &origin, "message_id")?,
+        author_id: get_str(doc, &origin, "author_id").unwrap_or_default(),
+        author_name: get_str(doc, &origin, "author_name").unwrap_or_default(),
+    })
+}
+
+// attachments and embeds are stored as json strings in a list, like
+// capability tokens. messages without any have no list at all
+fn put_json_list<T: serde::Serialize>(
+    doc: &mut AutoCommit,
+    obj: &automerge::ObjId,
+    key: &str,
+    items: &[T],
+) -> Result<(), automerge::AutomergeError> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    let list = doc.put_object(obj, key, ObjType::List)?;
+    for (i, item) in items.iter().enumerate() {
+        doc.insert(&list, i, serde_json::to_string(item).unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+// entries that don't parse, e.g. a kind added by a newer client, are skipped
+fn get_json_list<T: serde::de::DeserializeOwned>(
+    doc: &AutoCommit,
+    obj: &automerge::ObjId,
+    key: &str,
+) -> Vec<T> {
+    let Some((_, list)) = doc.get(obj, key).ok().flatten() else {
+        return Vec::new();
+    };
+    (0..doc.length(&list))
+        .filter_map(|i| {
+            doc.get(&list, i)
+                .ok()
+                .flatten()
+                .and_then(|(val, _)| val.into_string().ok())
+                .and_then(|json| serde_json::from_str(&json).ok())
+        })
+        .collect()
+}
+
 // simple sha256 hash for generating deterministic ids
 fn sha2_hash(data: &[u8]) -> Vec<u8> {
     use sha2::{Digest, Sha256};
@@ -531,6 +741,29 @@ fn sha2_hash(data: &[u8]) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+fn read_message(doc: &AutoCommit, msg_obj: &automerge::ObjId, channel_id: &str) -> ChatMessage {
+    let timestamp = get_i64(doc, msg_obj, "timestamp").unwrap_or(0) as u64;
+    // a merged doc can hold lists no client of ours would have written
+    let mut attachments = get_json_list(doc, msg_obj, "attachments");
+    let mut embeds = get_json_list(doc, msg_obj, "embeds");
+    crate::protocol::messages::filter_attachments(&mut attachments, &mut embeds);
+    ChatMessage {
+        id: get_str(doc, msg_obj, "id").unwrap_or_default(),
+        channel_id: channel_id.to_string(),
+        author_id: get_str(doc, msg_obj, "author_id").unwrap_or_default(),
+        author_name: get_str(doc, msg_obj, "author_name").unwrap_or_default(),
+        content: get_str(doc, msg_obj, "content").unwrap_or_default(),
+        timestamp,
+        edited: get_bool(doc, msg_obj, "edited").unwrap_or(false),
+        hlc: get_hlc(doc, msg_obj, timestamp),
+        forwarded_from: get_origin(doc, msg_obj),
+        attachments,
+        embeds,
+        reactions: get_reactions(doc, msg_obj),
+        reply_to: get_str(doc, msg_obj, "reply_to"),
+    }
+}
+
 // get a specific message by id from any channel in the community
 pub fn get_message_by_id(
     doc: &AutoCommit,
@@ -567,17 +800,7 @@ pub fn get_message_by_id(
                     if let Some(msg_id) = msg_obj {
                         let id = get_str(doc, &msg_id, "id").unwrap_or_default();
                         if id == message_id {
-                            let msg = ChatMessage {
-                                id: id.clone(),
-                                channel_id: channel_key.to_string(),
-                                author_id: get_str(doc, &msg_id, "author_id").unwrap_or_default(),
-                                author_name: get_str(doc, &msg_id, "author_name")
-                                    .unwrap_or_default(),
-                                content: get_str(doc, &msg_id, "content").unwrap_or_default(),
-                                timestamp: get_i64(doc, &msg_id, "timestamp").unwrap_or(0) as u64,
-                                edited: get_bool(doc, &msg_id, "edited").unwrap_or(false),
-                            };
-                            return Ok(Some(msg));
+                            return Ok(Some(read_message(doc, &msg_id, &channel_key)));
                         }
                     }
                 }
@@ -594,6 +817,12 @@ pub fn edit_message_by_id(
     message_id: &str,
     new_content: &str,
 ) -> Result<(), String> {
+    let keep_history = doc
+        .get(ROOT, "meta")
+        .map_err(|e| e.to_string())?
+        .and_then(|(_, meta)| get_bool(doc, &meta, "edit_history"))
+        .unwrap_or(false);
+
     let channels_obj = doc
         .get(ROOT, "channels")
         .map_err(|e| e.to_string())?
@@ -625,6 +854,9 @@ pub fn edit_message_by_id(
                     if let Some(msg_obj_id) = msg_obj {
                         let id = get_str(doc, &msg_obj_id, "id").unwrap_or_default();
                         if id == message_id {
+                            if keep_history {
+                                push_revision(doc, &msg_obj_id)?;
+                            }
                             doc.put(&msg_obj_id, "content", new_content)
                                 .map_err(|e| e.to_string())?;
                             doc.put(&msg_obj_id, "edited", true)
@@ -640,6 +872,95 @@ pub fn edit_message_by_id(
     Err(format!("message {} not found", message_id))
 }
 
+// earlier revisions kept per message, the oldest fall off first
+const MAX_EDIT_REVISIONS: usize = 10;
+
+// revisions live in a map keyed by a zero-padded sequence number. every peer
+// applies a gossiped edit to its own copy, keying by sequence rather than
+// appending to a list lets those concurrent copies merge into one entry
+fn push_revision(doc: &mut AutoCommit, msg_obj: &automerge::ObjId) -> Result<(), String> {
+    let current = get_str(doc, msg_obj, "content").unwrap_or_default();
+    let existing = doc
+        .get(msg_obj, "revisions")
+        .map_err(|e| e.to_string())?
+        .map(|(_, id)| id);
+    let revisions = match existing {
+        Some(id) => id,
+        None => doc
+            .put_object(msg_obj, "revisions", ObjType::Map)
+            .map_err(|e| e.to_string())?,
+    };
+
+    let mut keys: Vec<String> = doc.keys(&revisions).collect();
+    keys.sort();
+    let next = keys
+        .last()
+        .and_then(|k| k.parse::<u32>().ok())
+        .map(|n| n + 1)
+        .unwrap_or(0);
+    doc.put(&revisions, format!("{:06}", next), current)
+        .map_err(|e| e.to_string())?;
+
+    // drop the oldest revisions past the bound
+    let excess = (keys.len() + 1).saturating_sub(MAX_EDIT_REVISIONS);
+    for key in keys.iter().take(excess) {
+        doc.delete(&revisions, key.as_str())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// earlier revisions of a message, oldest first. none if it was never edited
+// while edit history was on
+pub fn get_message_revisions(
+    doc: &AutoCommit,
+    message_id: &str,
+) -> Result<Vec<MessageRevision>, String> {
+    let channels_obj = doc
+        .get(ROOT, "channels")
+        .map_err(|e| e.to_string())?
+        .map(|(_, id)| id)
+        .ok_or("channels key not found")?;
+
+    for channel_key in doc.keys(&channels_obj) {
+        let Some((_, ch_id)) = doc
+            .get(&channels_obj, &channel_key)
+            .map_err(|e| e.to_string())?
+        else {
+            continue;
+        };
+        let Some((_, msgs_id)) = doc.get(&ch_id, "messages").map_err(|e| e.to_string())? else {
+            continue;
+        };
+
+        for i in 0..doc.length(&msgs_id) {
+            let Some((_, msg_obj)) = doc.get(&msgs_id, i).map_err(|e| e.to_string())? else {
+                continue;
+            };
+            if get_str(doc, &msg_obj, "id").as_deref() != Some(message_id) {
+                continue;
+            }
+
+            let Some((_, revisions)) = doc.get(&msg_obj, "revisions").map_err(|e| e.to_string())?
+            else {
+                return Ok(Vec::new());
+            };
+            let mut keys: Vec<String> = doc.keys(&revisions).collect();
+            keys.sort();
+            return Ok(keys
+                .into_iter()
+                .filter_map(|key| {
+                    let revision = key.parse::<u32>().ok()?;
+                    let content = get_str(doc, &revisions, &key)?;
+                    Some(MessageRevision { revision, content })
+                })
+                .collect());
+        }
+    }
+
+    Err(format!("message {} not found", message_id))
+}
+
 // delete a message by id from any channel in the community
 pub fn delete_message_by_id(doc: &mut AutoCommit, message_id: &str) -> Result<(), String> {
     let channels_obj = doc
@@ -673,6 +994,7 @@ pub fn delete_message_by_id(doc: &mut AutoCommit, message_id: &str) -> Result<()
                     if let Some(msg_obj_id) = msg_obj {
                         let id = get_str(doc, &msg_obj_id, "id").unwrap_or_default();
                         if id == message_id {
+                            unindex_message(doc, &ch_id, &msg_obj_id, message_id)?;
                             doc.delete(&msgs_id, i).map_err(|e| e.to_string())?;
                             return Ok(());
                         }
@@ -685,6 +1007,304 @@ pub fn delete_message_by_id(doc: &mut AutoCommit, message_id: &str) -> Result<()
     Err(format!("message {} not found", message_id))
 }
 
+// -- threads --
+
+// a channel indexes its threads as threads: root id -> reply id -> the
+// reply's object id, and thread_roots: root id -> the root's object id.
+// object ids are the same on every peer, so a thread is read straight from
+// the index instead of scanning the channel. maps created concurrently each
+// win a copy, readers merge them all
+
+fn index_reply(
+    doc: &mut AutoCommit,
+    channel: &automerge::ObjId,
+    root_id: &str,
+    root_obj: Option<&automerge::ObjId>,
+    reply_id: &str,
+    reply_obj: &automerge::ObjId,
+) -> Result<(), automerge::AutomergeError> {
+    if let Some(root_obj) = root_obj {
+        let roots = match doc.get(channel, "thread_roots")? {
+            Some((_, id)) => id,
+            None => doc.put_object(channel, "thread_roots", ObjType::Map)?,
+        };
+        doc.put(&roots, root_id, root_obj.to_string())?;
+    }
+    let threads = match doc.get(channel, "threads")? {
+        Some((_, id)) => id,
+        None => doc.put_object(channel, "threads", ObjType::Map)?,
+    };
+    let replies = match doc.get(&threads, root_id)? {
+        Some((_, id)) => id,
+        None => doc.put_object(&threads, root_id, ObjType::Map)?,
+    };
+    doc.put(&replies, reply_id, reply_obj.to_string())?;
+    Ok(())
+}
+
+// a deleted reply leaves its thread, a deleted root leaves its replies in
+// a thread without a first message
+fn unindex_message(
+    doc: &mut AutoCommit,
+    channel: &automerge::ObjId,
+    msg_obj: &automerge::ObjId,
+    message_id: &str,
+) -> Result<(), String> {
+    if let Some(root_id) = get_str(doc, msg_obj, "reply_to") {
+        for threads in map_copies(doc, channel, "threads") {
+            for replies in map_copies(doc, &threads, &root_id) {
+                if get_str(doc, &replies, message_id).is_some() {
+                    doc.delete(&replies, message_id)
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+        }
+    }
+    for roots in map_copies(doc, channel, "thread_roots") {
+        if get_str(doc, &roots, message_id).is_some() {
+            doc.delete(&roots, message_id).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn channel_obj(doc: &AutoCommit, channel_id: &str) -> Result<automerge::ObjId, String> {
+    let (_, channels) = doc
+        .get(ROOT, "channels")
+        .map_err(|e| e.to_string())?
+        .ok_or("channels not found")?;
+    let (_, channel) = doc
+        .get(&channels, channel_id)
+        .map_err(|e| e.to_string())?
+        .ok_or("channel not found")?;
+    Ok(channel)
+}
+
+// the message an indexed object id points at, none once it no longer names
+// a message with that id
+fn indexed_message(doc: &AutoCommit, obj_id: &str, message_id: &str) -> Option<automerge::ObjId> {
+    let (obj, obj_type) = doc.import(obj_id).ok()?;
+    (obj_type == ObjType::Map && get_str(doc, &obj, "id").as_deref() == Some(message_id))
+        .then_some(obj)
+}
+
+// root ids with replies, each once however many copies of the index exist
+fn thread_roots(doc: &AutoCommit, channel: &automerge::ObjId) -> Vec<String> {
+    let mut roots: Vec<String> = Vec::new();
+    for threads in map_copies(doc, channel, "threads") {
+        for root_id in doc.keys(&threads) {
+            if !roots.contains(&root_id) {
+                roots.push(root_id);
+            }
+        }
+    }
+    roots
+}
+
+// a thread's replies ordered like the channel, by logical clock then id
+fn thread_replies(
+    doc: &AutoCommit,
+    channel: &automerge::ObjId,
+    channel_id: &str,
+    root_id: &str,
+) -> Vec<ChatMessage> {
+    let mut replies: Vec<ChatMessage> = Vec::new();
+    for threads in map_copies(doc, channel, "threads") {
+        for thread in map_copies(doc, &threads, root_id) {
+            for reply_id in doc.keys(&thread).collect::<Vec<_>>() {
+                if replies.iter().any(|r| r.id == reply_id) {
+                    continue;
+                }
+                let reply_obj = get_str(doc, &thread, &reply_id)
+                    .and_then(|obj_id| indexed_message(doc, &obj_id, &reply_id));
+                if let Some(reply_obj) = reply_obj {
+                    replies.push(read_message(doc, &reply_obj, channel_id));
+                }
+            }
+        }
+    }
+    replies.sort_by(|a, b| a.hlc.cmp(&b.hlc).then_with(|| a.id.cmp(&b.id)));
+    replies
+}
+
+pub fn get_thread(
+    doc: &AutoCommit,
+    channel_id: &str,
+    root_id: &str,
+) -> Result<MessageThread, String> {
+    let channel = channel_obj(doc, channel_id)?;
+    let root = map_copies(doc, &channel, "thread_roots")
+        .into_iter()
+        .find_map(|roots| {
+            get_str(doc, &roots, root_id).and_then(|obj_id| indexed_message(doc, &obj_id, root_id))
+        })
+        .map(|root_obj| read_message(doc, &root_obj, channel_id));
+    Ok(MessageThread {
+        root_id: root_id.to_string(),
+        root,
+        replies: thread_replies(doc, &channel, channel_id, root_id),
+    })
+}
+
+// every thread in a channel with at least one reply, most recently active
+// first
+pub fn get_thread_summaries(
+    doc: &AutoCommit,
+    channel_id: &str,
+) -> Result<Vec<ThreadSummary>, String> {
+    let channel = channel_obj(doc, channel_id)?;
+    let mut summaries: Vec<ThreadSummary> = thread_roots(doc, &channel)
+        .into_iter()
+        .filter_map(|root_id| {
+            let replies = thread_replies(doc, &channel, channel_id, &root_id);
+            let last_reply_at = replies.iter().map(|r| r.timestamp).max()?;
+            let mut participants: Vec<String> = Vec::new();
+            for reply in &replies {
+                if !participants.contains(&reply.author_id) {
+                    participants.push(reply.author_id.clone());
+                }
+            }
+            Some(ThreadSummary {
+                root_id,
+                reply_count: replies.len(),
+                last_reply_at,
+                participants,
+            })
+        })
+        .collect();
+    summaries.sort_by(|a, b| {
+        b.last_reply_at
+            .cmp(&a.last_reply_at)
+            .then_with(|| a.root_id.cmp(&b.root_id))
+    });
+    Ok(summaries)
+}
+
+// -- reactions --
+
+// a message keeps reactions as emoji -> peer id -> when they reacted. two
+// peers creating a map at the same time each win a copy, so readers merge
+// every copy and removals clear the peer from all of them
+
+fn find_message_obj(doc: &AutoCommit, message_id: &str) -> Option<automerge::ObjId> {
+    let (_, channels) = doc.get(ROOT, "channels").ok().flatten()?;
+    for channel_key in doc.keys(&channels).collect::<Vec<_>>() {
+        let Some((_, channel)) = doc.get(&channels, &channel_key).ok().flatten() else {
+            continue;
+        };
+        let Some((_, messages)) = doc.get(&channel, "messages").ok().flatten() else {
+            continue;
+        };
+        for i in 0..doc.length(&messages) {
+            if let Some((_, msg)) = doc.get(&messages, i).ok().flatten() {
+                if get_str(doc, &msg, "id").as_deref() == Some(message_id) {
+                    return Some(msg);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn map_copies(doc: &AutoCommit, obj: &automerge::ObjId, key: &str) -> Vec<automerge::ObjId> {
+    doc.get_all(obj, key)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_, id)| id)
+        .collect()
+}
+
+fn get_reactions(doc: &AutoCommit, msg_obj: &automerge::ObjId) -> Vec<MessageReaction> {
+    // emoji -> (peer, reacted at)
+    let mut by_emoji: Vec<(String, Vec<(String, i64)>)> = Vec::new();
+    for reactions in map_copies(doc, msg_obj, "reactions") {
+        for emoji in doc.keys(&reactions).collect::<Vec<_>>() {
+            for peers in map_copies(doc, &reactions, &emoji) {
+                for peer_id in doc.keys(&peers).collect::<Vec<_>>() {
+                    let at = get_i64(doc, &peers, &peer_id).unwrap_or(0);
+                    let index = match by_emoji.iter().position(|(e, _)| *e == emoji) {
+                        Some(index) => index,
+                        None => {
+                            by_emoji.push((emoji.clone(), Vec::new()));
+                            by_emoji.len() - 1
+                        }
+                    };
+                    let entry = &mut by_emoji[index].1;
+                    if !entry.iter().any(|(p, _)| *p == peer_id) {
+                        entry.push((peer_id, at));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut result: Vec<(i64, MessageReaction)> = by_emoji
+        .into_iter()
+        .filter(|(_, peers)| !peers.is_empty())
+        .map(|(emoji, mut peers)| {
+            peers.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+            let first = peers[0].1;
+            let peers = peers.into_iter().map(|(p, _)| p).collect();
+            (first, MessageReaction { emoji, peers })
+        })
+        .collect();
+    // emojis in the order they were first used
+    result.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.emoji.cmp(&b.1.emoji)));
+    result.into_iter().map(|(_, reaction)| reaction).collect()
+}
+
+// add or take back one peer's reaction. returns the message's reactions
+// afterwards, None when the message isn't in the doc
+pub fn set_reaction(
+    doc: &mut AutoCommit,
+    message_id: &str,
+    peer_id: &str,
+    emoji: &str,
+    active: bool,
+    now: u64,
+) -> Result<Option<Vec<MessageReaction>>, String> {
+    let Some(msg_obj) = find_message_obj(doc, message_id) else {
+        return Ok(None);
+    };
+
+    if active {
+        let current = get_reactions(doc, &msg_obj);
+        if current.len() >= MAX_REACTIONS_PER_MESSAGE && !current.iter().any(|r| r.emoji == emoji) {
+            return Err("this message has too many different reactions".to_string());
+        }
+        let reactions = match doc.get(&msg_obj, "reactions").map_err(|e| e.to_string())? {
+            Some((_, id)) => id,
+            None => doc
+                .put_object(&msg_obj, "reactions", ObjType::Map)
+                .map_err(|e| e.to_string())?,
+        };
+        let peers = match doc.get(&reactions, emoji).map_err(|e| e.to_string())? {
+            Some((_, id)) => id,
+            None => doc
+                .put_object(&reactions, emoji, ObjType::Map)
+                .map_err(|e| e.to_string())?,
+        };
+        if get_i64(doc, &peers, peer_id).is_none() {
+            doc.put(&peers, peer_id, now as i64)
+                .map_err(|e| e.to_string())?;
+        }
+    } else {
+        for reactions in map_copies(doc, &msg_obj, "reactions") {
+            for peers in map_copies(doc, &reactions, emoji) {
+                if doc
+                    .get(&peers, peer_id)
+                    .map_err(|e| e.to_string())?
+                    .is_some()
+                {
+                    doc.delete(&peers, peer_id).map_err(|e| e.to_string())?;
+                }
+            }
+        }
+    }
+
+    Ok(Some(get_reactions(doc, &msg_obj)))
+}
+
 // get all members from the community document
 pub fn get_members(doc: &AutoCommit) -> Result<Vec<crate::protocol::community::Member>, String> {
     let members_obj = doc
@@ -731,6 +1351,8 @@ pub fn get_members(doc: &AutoCommit) -> Result<Vec<crate::protocol::community::M
                 roles,
                 trust_level: 1.0,
                 joined_at,
+                activity: None,
+                avatar_seed: None,
             });
         }
     }
@@ -792,6 +1414,30 @@ pub fn update_channel(
     Ok(())
 }
 
+// cap the participants of a voice channel, None lifts the cap
+pub fn set_channel_capacity(
+    doc: &mut AutoCommit,
+    channel_id: &str,
+    max_participants: Option<u32>,
+) -> Result<(), automerge::AutomergeError> {
+    let channels = doc
+        .get(ROOT, "channels")?
+        .map(|(_, id)| id)
+        .ok_or_else(|| automerge::AutomergeError::InvalidObjId("channels not found".to_string()))?;
+
+    let channel = doc
+        .get(&channels, channel_id)?
+        .map(|(_, id)| id)
+        .ok_or_else(|| automerge::AutomergeError::InvalidObjId("channel not found".to_string()))?;
+
+    match max_participants {
+        Some(max) => doc.put(&channel, "max_participants", max as i64)?,
+        None => doc.delete(&channel, "max_participants")?,
+    }
+
+    Ok(())
+}
+
 // remove a channel and all its messages from the document
 pub fn delete_channel(
     doc: &mut AutoCommit,
@@ -883,3 +1529,300 @@ pub fn transfer_ownership(
 
     Ok(())
 }
+
+// read every string in a list object, used for role lists
+pub(super) fn read_string_list(doc: &AutoCommit, list: &automerge::ObjId) -> Vec<String> {
+    (0..doc.length(list))
+        .filter_map(|i| {
+            doc.get(list, i)
+                .ok()
+                .flatten()
+                .and_then(|(val, _)| val.into_string().ok())
+        })
+        .collect()
+}
+
+// collect meta fields and member role lists that hold more than one concurrent
+// value, automerge picks a winner silently so these are surfaced for review
+pub fn find_conflicts(doc: &AutoCommit) -> Vec<DocConflict> {
+    let mut conflicts = Vec::new();
+
+    if let Some(meta) = doc.get(ROOT, "meta").ok().flatten().map(|(_, id)| id) {
+        for field in ["name", "description", "created_by"] {
+            let values: Vec<String> = doc
+                .get_all(&meta, field)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(val, _)| val.into_string().ok())
+                .collect();
+            if values.len() > 1 {
+                conflicts.push(DocConflict {
+                    key: format!("meta.{}", field),
+                    values,
+                });
+            }
+        }
+    }
+
+    if let Some(members) = doc.get(ROOT, "members").ok().flatten().map(|(_, id)| id) {
+        for peer_id in doc.keys(&members) {
+            let member = match doc.get(&members, &peer_id).ok().flatten() {
+                Some((_, id)) => id,
+                None => continue,
+            };
+            let lists = doc.get_all(&member, "roles").unwrap_or_default();
+            if lists.len() > 1 {
+                let values = lists
+                    .iter()
+                    .map(|(_, list_id)| read_string_list(doc, list_id).join(","))
+                    .collect();
+                conflicts.push(DocConflict {
+                    key: format!("members.{}.roles", peer_id),
+                    values,
+                });
+            }
+        }
+    }
+
+    conflicts
+}
+
+// settle a conflicting key by writing the chosen value, a fresh put supersedes
+// every concurrent value seen so far. roles are given comma separated
+pub fn resolve_conflict(doc: &mut AutoCommit, key: &str, value: &str) -> Result<(), String> {
+    let parts: Vec<&str> = key.split('.').collect();
+    match parts.as_slice() {
+        ["meta", field @ ("name" | "description" | "created_by")] => {
+            let meta = doc
+                .get(ROOT, "meta")
+                .map_err(|e| e.to_string())?
+                .map(|(_, id)| id)
+                .ok_or("meta not found")?;
+            doc.put(&meta, *field, value).map_err(|e| e.to_string())?;
+            Ok(())
+        }
+        ["members", peer_id, "roles"] => {
+            let roles: Vec<String> = value
+                .split(',')
+                .map(|r| r.trim())
+                .filter(|r| !r.is_empty())
+                .map(String::from)
+                .collect();
+            if roles.is_empty() {
+                return Err("a member needs at least one role".to_string());
+            }
+            set_member_role(doc, peer_id, &roles).map_err(|e| e.to_string())
+        }
+        _ => Err(format!("unknown conflict key: {}", key)),
+    }
+}
+
+// only the most recent checkpoints are kept, older ones are implied by them
+const MAX_CHECKPOINTS: usize = 16;
+
+// record an owner-signed checkpoint, dropping the oldest beyond the cap
+pub fn append_checkpoint(
+    doc: &mut AutoCommit,
+    checkpoint: &DocCheckpoint,
+) -> Result<(), automerge::AutomergeError> {
+    let list = match doc.get(ROOT, "checkpoints")? {
+        Some((_, id)) => id,
+        None => doc.put_object(ROOT, "checkpoints", ObjType::List)?,
+    };
+
+    let idx = doc.length(&list);
+    let entry = doc.insert_object(&list, idx, ObjType::Map)?;
+    doc.put(&entry, "heads", checkpoint.heads.join(","))?;
+    doc.put(&entry, "created_at", checkpoint.created_at as i64)?;
+    doc.put(&entry, "signer", checkpoint.signer.as_str())?;
+    doc.put(&entry, "public_key", checkpoint.public_key.as_str())?;
+    doc.put(&entry, "signature", checkpoint.signature.as_str())?;
+
+    while doc.length(&list) > MAX_CHECKPOINTS {
+        doc.delete(&list, 0)?;
+    }
+
+    Ok(())
+}
+
+// read all stored checkpoints, oldest first
+pub fn get_checkpoints(doc: &AutoCommit) -> Vec<DocCheckpoint> {
+    let list = match doc.get(ROOT, "checkpoints").ok().flatten() {
+        Some((_, id)) => id,
+        None => return Vec::new(),
+    };
+
+    (0..doc.length(&list))
+        .filter_map(|i| {
+            let (_, entry) = doc.get(&list, i).ok().flatten()?;
+            Some(DocCheckpoint {
+                heads: get_str(doc, &entry, "heads")
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter(|h| !h.is_empty())
+                    .map(String::from)
+                    .collect(),
+                created_at: get_i64(doc, &entry, "created_at").unwrap_or(0) as u64,
+                signer: get_str(doc, &entry, "signer")?,
+                public_key: get_str(doc, &entry, "public_key")?,
+                signature: get_str(doc, &entry, "signature")?,
+            })
+        })
+        .collect()
+}
+
+// append a signed event to the membership log. the log is never trimmed,
+// the member list is derived from all of it
+pub fn append_membership_event(
+    doc: &mut AutoCommit,
+    event: &MembershipEvent,
+) -> Result<(), automerge::AutomergeError> {
+    let list = match doc.get(ROOT, "membership_log")? {
+        Some((_, id)) => id,
+        None => doc.put_object(ROOT, "membership_log", ObjType::List)?,
+    };
+
+    let idx = doc.length(&list);
+    let entry = doc.insert_object(&list, idx, ObjType::Map)?;
+    doc.put(&entry, "action", event.action.as_str())?;
+    doc.put(&entry, "peer_id", event.peer_id.as_str())?;
+    doc.put(&entry, "actor", event.actor.as_str())?;
+    doc.put(&entry, "actor_key", event.actor_key.as_str())?;
+    doc.put(&entry, "roles", event.roles.join(","))?;
+    doc.put(&entry, "display_name", event.display_name.as_str())?;
+    doc.put(&entry, "timestamp", event.timestamp as i64)?;
+    doc.put(&entry, "signature", event.signature.as_str())?;
+    if let Some(ref token) = event.capability {
+        doc.put(
+            &entry,
+            "capability",
+            serde_json::to_string(token).unwrap_or_default(),
+        )?;
+    }
+
+    Ok(())
+}
+
+// read the membership log in list order, entries with unknown actions are skipped
+pub fn get_membership_events(doc: &AutoCommit) -> Vec<MembershipEvent> {
+    let list = match doc.get(ROOT, "membership_log").ok().flatten() {
+        Some((_, id)) => id,
+        None => return Vec::new(),
+    };
+
+    (0..doc.length(&list))
+        .filter_map(|i| {
+            let (_, entry) = doc.get(&list, i).ok().flatten()?;
+            Some(MembershipEvent {
+                action: MembershipAction::parse(&get_str(doc, &entry, "action")?)?,
+                peer_id: get_str(doc, &entry, "peer_id")?,
+                actor: get_str(doc, &entry, "actor")?,
+                actor_key: get_str(doc, &entry, "actor_key")?,
+                roles: get_str(doc, &entry, "roles")
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter(|r| !r.is_empty())
+                    .map(String::from)
+                    .collect(),
+                display_name: get_str(doc, &entry, "display_name").unwrap_or_default(),
+                timestamp: get_i64(doc, &entry, "timestamp").unwrap_or(0) as u64,
+                signature: get_str(doc, &entry, "signature")?,
+                capability: get_str(doc, &entry, "capability")
+                    .and_then(|c| serde_json::from_str(&c).ok()),
+            })
+        })
+        .collect()
+}
+
+// record the founder's place for a membership event. never pruned, the log
+// it orders isn't either
+pub fn append_membership_order(
+    doc: &mut AutoCommit,
+    order: &MembershipOrder,
+) -> Result<(), automerge::AutomergeError> {
+    let list = match doc.get(ROOT, "membership_order")? {
+        Some((_, id)) => id,
+        None => doc.put_object(ROOT, "membership_order", ObjType::List)?,
+    };
+
+    let idx = doc.length(&list);
+    let entry = doc.insert_object(&list, idx, ObjType::Map)?;
+    doc.put(&entry, "event_key", order.event_key.as_str())?;
+    doc.put(&entry, "seq", order.seq as i64)?;
+    doc.put(&entry, "placed_at", order.placed_at as i64)?;
+    doc.put(&entry, "signer", order.signer.as_str())?;
+    doc.put(&entry, "public_key", order.public_key.as_str())?;
+    doc.put(&entry, "signature", order.signature.as_str())?;
+
+    Ok(())
+}
+
+pub fn get_membership_order(doc: &AutoCommit) -> Vec<MembershipOrder> {
+    let list = match doc.get(ROOT, "membership_order").ok().flatten() {
+        Some((_, id)) => id,
+        None => return Vec::new(),
+    };
+
+    (0..doc.length(&list))
+        .filter_map(|i| {
+            let (_, entry) = doc.get(&list, i).ok().flatten()?;
+            Some(MembershipOrder {
+                event_key: get_str(doc, &entry, "event_key")?,
+                seq: get_i64(doc, &entry, "seq")?.max(0) as u64,
+                placed_at: get_i64(doc, &entry, "placed_at")?.max(0) as u64,
+                signer: get_str(doc, &entry, "signer")?,
+                public_key: get_str(doc, &entry, "public_key")?,
+                signature: get_str(doc, &entry, "signature")?,
+            })
+        })
+        .collect()
+}
+
+// store a capability token, dropping any that expired before `now`. tokens
+// are json since delegated ones nest their whole chain
+pub fn append_capability(
+    doc: &mut AutoCommit,
+    token: &CapabilityToken,
+    now: u64,
+) -> Result<(), automerge::AutomergeError> {
+    let list = match doc.get(ROOT, "capabilities")? {
+        Some((_, id)) => id,
+        None => doc.put_object(ROOT, "capabilities", ObjType::List)?,
+    };
+
+    let mut i = doc.length(&list);
+    while i > 0 {
+        i -= 1;
+        let expired = doc
+            .get(&list, i)?
+            .and_then(|(val, _)| val.into_string().ok())
+            .and_then(|json| serde_json::from_str::<CapabilityToken>(&json).ok())
+            .is_none_or(|t| t.expires_at <= now);
+        if expired {
+            doc.delete(&list, i)?;
+        }
+    }
+
+    let idx = doc.length(&list);
+    doc.insert(&list, idx, serde_json::to_string(token).unwrap_or_default())?;
+
+    Ok(())
+}
+
+// every stored capability token, unchecked
+pub fn get_capabilities(doc: &AutoCommit) -> Vec<CapabilityToken> {
+    let list = match doc.get(ROOT, "capabilities").ok().flatten() {
+        Some((_, id)) => id,
+        None => return Vec::new(),
+    };
+
+    (0..doc.length(&list))
+        .filter_map(|i| {
+            doc.get(&list, i)
+                .ok()
+                .flatten()
+                .and_then(|(val, _)| val.into_string().ok())
+                .and_then(|json| serde_json::from_str(&json).ok())
+        })
+        .collect()
+}
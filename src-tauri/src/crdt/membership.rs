@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use crate::protocol::community::{MembershipAction, MembershipEvent, ModerationCapability};
+
+// where a peer stands once the membership log has been replayed
+#[derive(Debug, Clone)]
+pub struct MemberState {
+    pub active: bool,
+    pub roles: Vec<String>,
+    pub display_name: String,
+    pub joined_at: u64,
+}
+
+fn has_role(roles: &[String], role: &str) -> bool {
+    roles.iter().any(|r| r == role)
+}
+
+// a member that left or was kicked keeps an entry so an older map entry
+// can't stand in for it
+fn deactivate(state: &mut HashMap<String, MemberState>, event: &MembershipEvent) {
+    let entry = state
+        .entry(event.peer_id.clone())
+        .or_insert_with(|| MemberState {
+            active: false,
+            roles: Vec::new(),
+            display_name: event.display_name.clone(),
+            joined_at: 0,
+        });
+    entry.active = false;
+    entry.roles.clear();
+}
+
+// a signature-checked membership event, with the founder's place for it and
+// when it was placed, once the founder has placed it
+#[derive(Debug, Clone)]
+pub struct LoggedEvent {
+    pub event: MembershipEvent,
+    pub seq: Option<u64>,
+    pub placed_at: Option<u64>,
+}
+
+// replay signature-checked events, those the founder placed first in its
+// order, the rest after them in timestamp order. the member map roles
+// in `fallback_roles` only count for peers the log has not seen yet, so
+// communities that predate the log keep their owner and admins until those
+// show up in it. `founder` is the creator recorded at community creation,
+// the only peer whose first join may carry the owner role. a kick under a
+// capability needs the token live when the founder placed the kick, or at
+// `now` while it isn't placed, the kick's own timestamp could be backdated
+pub fn replay(
+    community_id: &str,
+    events: &[LoggedEvent],
+    fallback_roles: &HashMap<String, Vec<String>>,
+    founder: Option<&str>,
+    now: u64,
+) -> HashMap<String, MemberState> {
+    let mut ordered: Vec<&LoggedEvent> = events.iter().collect();
+    // ties between concurrent events break the same way on every peer
+    ordered.sort_by(|a, b| {
+        let seq = |logged: &LoggedEvent| logged.seq.unwrap_or(u64::MAX);
+        let (a_seq, b_seq) = (seq(a), seq(b));
+        let (a, b) = (&a.event, &b.event);
+        a_seq
+            .cmp(&b_seq)
+            .then_with(|| a.timestamp.cmp(&b.timestamp))
+            .then_with(|| a.actor.cmp(&b.actor))
+            .then_with(|| a.peer_id.cmp(&b.peer_id))
+            .then_with(|| a.action.as_str().cmp(b.action.as_str()))
+    });
+
+    let roles_of = |state: &HashMap<String, MemberState>, peer_id: &str| -> Vec<String> {
+        match state.get(peer_id) {
+            Some(s) if s.active => s.roles.clone(),
+            Some(_) => Vec::new(),
+            None => fallback_roles.get(peer_id).cloned().unwrap_or_default(),
+        }
+    };
+
+    let mut state: HashMap<String, MemberState> = HashMap::new();
+    for logged in ordered {
+        let event = &logged.event;
+        let actor_roles = roles_of(&state, &event.actor);
+        let target_roles = roles_of(&state, &event.peer_id);
+
+        match event.action {
+            MembershipAction::Join => {
+                if event.actor != event.peer_id {
+                    continue;
+                }
+                // a rejoin always starts over as a plain member, whatever
+                // roles an old doc still lists
+                let genesis = founder == Some(event.peer_id.as_str())
+                    && !state.contains_key(&event.peer_id)
+                    && has_role(&event.roles, "owner");
+                let roles = if genesis {
+                    vec!["owner".to_string()]
+                } else {
+                    vec!["member".to_string()]
+                };
+                state.insert(
+                    event.peer_id.clone(),
+                    MemberState {
+                        active: true,
+                        roles,
+                        display_name: event.display_name.clone(),
+                        joined_at: event.timestamp,
+                    },
+                );
+            }
+            MembershipAction::Leave => {
+                if event.actor != event.peer_id {
+                    continue;
+                }
+                deactivate(&mut state, event);
+            }
+            MembershipAction::Kick => {
+                let moderator = has_role(&actor_roles, "owner")
+                    || has_role(&actor_roles, "admin")
+                    || event.capability.as_ref().is_some_and(|token| {
+                        token.community_id == community_id
+                            && token.holder == event.actor
+                            && token.capability == ModerationCapability::KickMembers
+                            && crate::verification::verify_capability_token(
+                                token,
+                                logged.placed_at.unwrap_or(now),
+                                &|peer_id| has_role(&roles_of(&state, peer_id), "owner"),
+                            )
+                    });
+                if !moderator || has_role(&target_roles, "owner") {
+                    continue;
+                }
+                deactivate(&mut state, event);
+            }
+            MembershipAction::SetRoles => {
+                // only the owner hands out roles, mirroring set_member_role
+                if !has_role(&actor_roles, "owner") || event.roles.is_empty() {
+                    continue;
+                }
+                let known_legacy = fallback_roles.contains_key(&event.peer_id);
+                match state.get_mut(&event.peer_id) {
+                    Some(s) if s.active => s.roles = event.roles.clone(),
+                    Some(_) => {}
+                    None if known_legacy => {
+                        state.insert(
+                            event.peer_id.clone(),
+                            MemberState {
+                                active: true,
+                                roles: event.roles.clone(),
+                                display_name: event.display_name.clone(),
+                                joined_at: event.timestamp,
+                            },
+                        );
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    state
+}
+
+// map roles that a merge raised without a signed grant behind them, paired
+// with the roles to put back. `before` is the member map ahead of the merge
+// and `states` the log replayed against it, so a forged map entry can't vouch
+// for itself. `owner` is the owner the doc named before the merge, the only
+// peer allowed an owner role the log has not seen
+pub fn unsigned_grants(
+    before: &HashMap<String, Vec<String>>,
+    after: &HashMap<String, Vec<String>>,
+    states: &HashMap<String, MemberState>,
+    owner: &str,
+) -> Vec<(String, Vec<String>)> {
+    let elevated = |roles: &[String]| has_role(roles, "owner") || has_role(roles, "admin");
+    let same_roles = |a: &[String], b: &[String]| {
+        let mut a = a.to_vec();
+        let mut b = b.to_vec();
+        a.sort();
+        b.sort();
+        a == b
+    };
+
+    let mut corrections = Vec::new();
+    for (peer_id, roles) in after {
+        if !elevated(roles) || before.get(peer_id).is_some_and(|b| same_roles(b, roles)) {
+            continue;
+        }
+        let signed = match states.get(peer_id) {
+            Some(state) if state.active => state.roles.clone(),
+            Some(_) => vec!["member".to_string()],
+            None if peer_id == owner => vec!["owner".to_string()],
+            None => before
+                .get(peer_id)
+                .cloned()
+                .unwrap_or_else(|| vec!["member".to_string()]),
+        };
+        if !same_roles(&signed, roles) {
+            corrections.push((peer_id.clone(), signed));
+        }
+    }
+    corrections
+}
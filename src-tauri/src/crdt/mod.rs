@@ -1,19 +1,100 @@
 mod document;
+mod membership;
+mod notes;
 pub mod sync;
+mod tasks;
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use automerge::{AutoCommit, ChangeHash};
+
+use crate::protocol::community::{
+    CapabilityToken, CategoryMeta, ChannelMeta, CommunityMeta, DocCheckpoint, DocConflict,
+    InviteCode, Member, MembershipEvent, ModerationCapability, TaskBoard, TaskCard, TaskCardPatch,
+    TaskList,
+};
+use crate::protocol::identity::VerificationPolicy;
+use crate::protocol::messages::{
+    ChatMessage, MessageReaction, MessageRevision, MessageThread, ThreadSummary,
+};
+use crate::protocol::notes::{
+    Note, NoteEdit, NoteRevision, PageEditRole, PageMeta, PageRefSignature, MAX_NOTE_TITLE_CHARS,
+};
+use crate::storage::DiskStorage;
 
-use automerge::AutoCommit;
+// what a remote merge changed relative to our local copy
+pub struct MergeOutcome {
+    // both sides held changes the other had never seen
+    pub diverged: bool,
+    // seconds since the previous merge for this community, if any
+    pub partition_secs: Option<u64>,
+    // keys left with concurrent values after the merge
+    pub conflicts: Vec<DocConflict>,
+}
 
-use crate::protocol::community::{CategoryMeta, ChannelMeta, CommunityMeta};
-use crate::protocol::messages::ChatMessage;
-use crate::storage::DiskStorage;
+// what merging a note's changes from the network did
+pub struct NoteMerge {
+    // the note as it now reads, when the changes were new to us
+    pub note: Option<Note>,
+    // our heads, when some changes wait on history we never received
+    pub missing_heads: Option<Vec<String>>,
+}
+
+// a note's own automerge doc, tied to the community it was created in
+struct NoteDoc {
+    community_id: String,
+    doc: AutoCommit,
+}
+
+// how long a deleted message can be restored before the deletion goes out
+pub const UNDO_DELETE_SECS: u64 = 30;
+
+// a message hidden locally but still in the doc until its undo window ends
+struct PendingDeletion {
+    community_id: String,
+    channel_id: String,
+    // token that allowed deleting someone else's message, goes out with it
+    capability: Option<CapabilityToken>,
+    deadline: Instant,
+}
+
+// a soft deletion written to the doc and ready to broadcast
+pub struct AppliedDeletion {
+    pub message_id: String,
+    pub community_id: String,
+    pub channel_id: String,
+    pub capability: Option<CapabilityToken>,
+}
 
 // manages automerge documents for all joined communities
 pub struct CrdtEngine {
     documents: HashMap<String, AutoCommit>,
     storage: Arc<DiskStorage>,
+    // wall clock of the last merge per community, used to size partitions
+    last_merged_at: HashMap<String, u64>,
+    // communities joined via invite that have not accepted a verified snapshot
+    // yet, value is the owner peer id carried by the invite (empty if unknown)
+    expected_owners: HashMap<String, String>,
+    // signed invites of communities we joined but have no snapshot for yet,
+    // sent along with our sync requests until the first merge
+    join_invites: HashMap<String, InviteCode>,
+    // heads right after our last checkpoint, so idle docs are not re-signed
+    checkpointed_heads: HashMap<String, Vec<ChangeHash>>,
+    // log members leaving for owner analytics, off unless opted in
+    track_departures: bool,
+    // soft-deleted messages by id, see soft_delete_message
+    pending_deletions: HashMap<String, PendingDeletion>,
+    // communities left with their history kept. the docs stay readable but
+    // are neither synced nor written to
+    archived: HashSet<String>,
+    // keys of membership events and orders already checked, the log is
+    // replayed on every member lookup
+    verified_membership: RefCell<HashSet<String>>,
+    // collaborative notes by note id, see protocol::notes
+    notes: HashMap<String, NoteDoc>,
 }
 
 impl CrdtEngine {
@@ -21,6 +102,35 @@ impl CrdtEngine {
         Self {
             documents: HashMap::new(),
             storage,
+            last_merged_at: HashMap::new(),
+            expected_owners: HashMap::new(),
+            join_invites: HashMap::new(),
+            checkpointed_heads: HashMap::new(),
+            track_departures: false,
+            pending_deletions: HashMap::new(),
+            archived: HashSet::new(),
+            verified_membership: RefCell::new(HashSet::new()),
+            notes: HashMap::new(),
+        }
+    }
+
+    pub fn set_track_departures(&mut self, enabled: bool) {
+        self.track_departures = enabled;
+    }
+
+    fn record_departures(&self, community_id: &str, peer_ids: &[String]) {
+        if !self.track_departures || peer_ids.is_empty() {
+            return;
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        if let Err(e) = self
+            .storage
+            .record_member_departures(community_id, peer_ids, now)
+        {
+            log::warn!("failed to record member departures: {}", e);
         }
     }
 
@@ -30,6 +140,10 @@ impl CrdtEngine {
             .storage
             .list_communities()
             .map_err(|e| format!("failed to list communities: {}", e))?;
+        self.archived = self
+            .storage
+            .load_archived_communities()
+            .map_err(|e| format!("failed to load archived communities: {}", e))?;
 
         for id in community_ids {
             if let Ok(bytes) = self.storage.load_document(&id) {
@@ -44,6 +158,19 @@ impl CrdtEngine {
             }
         }
 
+        let notes = self
+            .storage
+            .load_note_documents()
+            .map_err(|e| format!("failed to load notes: {}", e))?;
+        for (note_id, community_id, bytes) in notes {
+            match AutoCommit::load(&bytes) {
+                Ok(doc) => {
+                    self.notes.insert(note_id, NoteDoc { community_id, doc });
+                }
+                Err(e) => log::warn!("failed to load note {}: {}", note_id, e),
+            }
+        }
+
         Ok(())
     }
 
@@ -89,10 +216,7 @@ impl CrdtEngine {
         display_name: &str,
         roles: &[&str],
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::add_member(doc, peer_id, display_name, roles)
             .map_err(|e| format!("failed to add member: {}", e))?;
@@ -108,23 +232,27 @@ impl CrdtEngine {
         peer_id: &str,
         display_name: &str,
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::update_member_display_name(doc, peer_id, display_name)?;
         self.persist(community_id)?;
         Ok(())
     }
 
-    // update a member's display name across all communities they belong to
+    // update a member's display name across all communities they belong to,
+    // except those where they use a per-community name
     pub fn update_member_display_name_everywhere(
         &mut self,
         peer_id: &str,
         display_name: &str,
+        skip: &[String],
     ) -> Vec<String> {
-        let community_ids: Vec<String> = self.documents.keys().cloned().collect();
+        let community_ids: Vec<String> = self
+            .documents
+            .keys()
+            .filter(|cid| !skip.contains(cid))
+            .cloned()
+            .collect();
         let mut updated = Vec::new();
         for cid in community_ids {
             if self.update_member_display_name(&cid, peer_id, display_name).is_ok() {
@@ -140,10 +268,7 @@ impl CrdtEngine {
         community_id: &str,
         channel: &ChannelMeta,
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::add_channel(doc, channel).map_err(|e| format!("failed to add channel: {}", e))?;
 
@@ -167,10 +292,7 @@ impl CrdtEngine {
         community_id: &str,
         channel_ids: &[String],
     ) -> Result<Vec<ChannelMeta>, String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         let channels = document::reorder_channels(doc, community_id, channel_ids)?;
         self.persist(community_id)?;
@@ -183,10 +305,7 @@ impl CrdtEngine {
         community_id: &str,
         category: &CategoryMeta,
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::add_category(doc, category)
             .map_err(|e| format!("failed to add category: {}", e))?;
@@ -206,21 +325,21 @@ impl CrdtEngine {
     }
 
     // append a message to a channel within a community
+    // returns false when the message was already present in the channel
     pub fn append_message(
         &mut self,
         community_id: &str,
         message: &ChatMessage,
-    ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+    ) -> Result<bool, String> {
+        let doc = self.writable_doc(community_id)?;
 
-        document::append_message(doc, &message.channel_id, message)
+        let inserted = document::append_message(doc, &message.channel_id, message)
             .map_err(|e| format!("failed to append message: {}", e))?;
 
-        self.persist(community_id)?;
-        Ok(())
+        if inserted {
+            self.persist(community_id)?;
+        }
+        Ok(inserted)
     }
 
     // get messages for a channel, optionally paginated
@@ -236,7 +355,54 @@ impl CrdtEngine {
             .get(community_id)
             .ok_or("community not found")?;
 
-        document::get_messages(doc, channel_id, before, limit)
+        let mut messages = document::get_messages(doc, channel_id, before, limit)?;
+        messages.retain(|m| !self.pending_deletions.contains_key(&m.id));
+        Ok(messages)
+    }
+
+    // a message and its replies, read from the channel's thread index
+    pub fn get_thread(
+        &self,
+        community_id: &str,
+        channel_id: &str,
+        root_id: &str,
+    ) -> Result<MessageThread, String> {
+        let doc = self
+            .documents
+            .get(community_id)
+            .ok_or("community not found")?;
+
+        let mut thread = document::get_thread(doc, channel_id, root_id)?;
+        if self.pending_deletions.contains_key(root_id) {
+            thread.root = None;
+        }
+        thread
+            .replies
+            .retain(|m| !self.pending_deletions.contains_key(&m.id));
+        Ok(thread)
+    }
+
+    pub fn get_thread_summaries(
+        &self,
+        community_id: &str,
+        channel_id: &str,
+    ) -> Result<Vec<ThreadSummary>, String> {
+        let doc = self
+            .documents
+            .get(community_id)
+            .ok_or("community not found")?;
+
+        document::get_thread_summaries(doc, channel_id)
+    }
+
+    // number of messages in a channel
+    pub fn count_messages(&self, community_id: &str, channel_id: &str) -> Result<usize, String> {
+        let doc = self
+            .documents
+            .get(community_id)
+            .ok_or("community not found")?;
+
+        document::count_messages(doc, channel_id)
     }
 
     // get community metadata
@@ -246,12 +412,75 @@ impl CrdtEngine {
             .get(community_id)
             .ok_or("community not found")?;
 
-        document::get_community_meta(doc, community_id)
+        let mut meta = document::get_community_meta(doc, community_id)?;
+        meta.archived = self.archived.contains(community_id);
+        Ok(meta)
     }
 
-    // get all community ids we have documents for
+    // ids of the communities we are in, archives left out
     pub fn community_ids(&self) -> Vec<String> {
-        self.documents.keys().cloned().collect()
+        self.documents
+            .keys()
+            .filter(|id| !self.archived.contains(*id))
+            .cloned()
+            .collect()
+    }
+
+    // everyone in any community we hold, archived ones included
+    pub fn all_member_ids(&self) -> HashSet<String> {
+        self.documents
+            .keys()
+            .filter_map(|id| self.get_members(id).ok())
+            .flatten()
+            .map(|m| m.peer_id)
+            .collect()
+    }
+
+    pub fn archived_community_ids(&self) -> Vec<String> {
+        self.documents
+            .keys()
+            .filter(|id| self.archived.contains(*id))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_archived(&self, community_id: &str) -> bool {
+        self.archived.contains(community_id)
+    }
+
+    // current heads of every loaded doc as hex change hashes, archives included
+    pub fn doc_heads(&mut self) -> Vec<(String, Vec<String>)> {
+        self.documents
+            .iter_mut()
+            .map(|(community_id, doc)| {
+                let heads = doc.get_heads().iter().map(|h| h.to_string()).collect();
+                (community_id.clone(), heads)
+            })
+            .collect()
+    }
+
+    // keep a community we left as a read-only local archive
+    pub fn archive_community(&mut self, community_id: &str) -> Result<(), String> {
+        if !self.documents.contains_key(community_id) {
+            return Err("community not found".to_string());
+        }
+        self.storage
+            .set_community_archived(community_id, true)
+            .map_err(|e| format!("failed to archive community: {}", e))?;
+        self.archived.insert(community_id.to_string());
+        self.pending_deletions
+            .retain(|_, pending| pending.community_id != community_id);
+        Ok(())
+    }
+
+    // rejoining an archived community picks its history back up
+    pub fn unarchive_community(&mut self, community_id: &str) -> Result<(), String> {
+        if !self.archived.remove(community_id) {
+            return Ok(());
+        }
+        self.storage
+            .set_community_archived(community_id, false)
+            .map_err(|e| format!("failed to unarchive community: {}", e))
     }
 
     // check if we have a document for a community
@@ -262,12 +491,43 @@ impl CrdtEngine {
     // fully remove a community from memory and disk
     pub fn remove_community(&mut self, community_id: &str) -> Result<(), String> {
         self.documents.remove(community_id);
+        self.notes
+            .retain(|_, note| note.community_id != community_id);
+        self.last_merged_at.remove(community_id);
+        self.expected_owners.remove(community_id);
+        self.join_invites.remove(community_id);
+        self.checkpointed_heads.remove(community_id);
+        self.pending_deletions
+            .retain(|_, pending| pending.community_id != community_id);
+        if self.archived.remove(community_id) {
+            self.storage
+                .set_community_archived(community_id, false)
+                .map_err(|e| format!("failed to delete community archive flag: {}", e))?;
+        }
         self.storage
             .delete_document(community_id)
             .map_err(|e| format!("failed to delete community document: {}", e))?;
         self.storage
             .delete_community_meta(community_id)
             .map_err(|e| format!("failed to delete community meta: {}", e))?;
+        self.storage
+            .delete_community_profiles(community_id)
+            .map_err(|e| format!("failed to delete community profiles: {}", e))?;
+        self.storage
+            .delete_member_departures(community_id)
+            .map_err(|e| format!("failed to delete member departures: {}", e))?;
+        self.storage
+            .delete_channel_follows_for_community(community_id)
+            .map_err(|e| format!("failed to delete channel follows: {}", e))?;
+        self.storage
+            .delete_channel_interest_for_community(community_id)
+            .map_err(|e| format!("failed to delete channel interest: {}", e))?;
+        self.storage
+            .delete_note_documents(community_id)
+            .map_err(|e| format!("failed to delete notes: {}", e))?;
+        self.storage
+            .delete_community_placement(community_id)
+            .map_err(|e| format!("failed to delete community placement: {}", e))?;
         Ok(())
     }
 
@@ -284,6 +544,16 @@ impl CrdtEngine {
             .map_err(|e| format!("failed to persist document: {}", e))
     }
 
+    // the doc of a community we may still change, archived ones are read only
+    fn writable_doc(&mut self, community_id: &str) -> Result<&mut AutoCommit, String> {
+        if self.archived.contains(community_id) {
+            return Err("community is archived".to_string());
+        }
+        self.documents
+            .get_mut(community_id)
+            .ok_or_else(|| "community not found".to_string())
+    }
+
     // get a mutable reference to a document for sync operations
     pub fn get_doc_mut(&mut self, community_id: &str) -> Option<&mut AutoCommit> {
         self.documents.get_mut(community_id)
@@ -310,6 +580,9 @@ impl CrdtEngine {
             .get(community_id)
             .ok_or("community not found")?;
 
+        if self.pending_deletions.contains_key(message_id) {
+            return Ok(None);
+        }
         document::get_message_by_id(doc, message_id)
     }
 
@@ -320,50 +593,353 @@ impl CrdtEngine {
         message_id: &str,
         new_content: &str,
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::edit_message_by_id(doc, message_id, new_content)?;
         self.persist(community_id)?;
         Ok(())
     }
 
+    // add or take back a peer's reaction to a message. returns the message's
+    // reactions afterwards, None when it isn't here (yet)
+    pub fn set_reaction(
+        &mut self,
+        community_id: &str,
+        message_id: &str,
+        peer_id: &str,
+        emoji: &str,
+        active: bool,
+    ) -> Result<Option<Vec<MessageReaction>>, String> {
+        if self.pending_deletions.contains_key(message_id) {
+            return Ok(None);
+        }
+        let doc = self.writable_doc(community_id)?;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let reactions = document::set_reaction(doc, message_id, peer_id, emoji, active, now)?;
+        if reactions.is_some() {
+            self.persist(community_id)?;
+        }
+        Ok(reactions)
+    }
+
     // delete a message by id
     pub fn delete_message(&mut self, community_id: &str, message_id: &str) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        document::delete_message_by_id(doc, message_id)?;
+        self.pending_deletions.remove(message_id);
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    // hide a message locally without touching the doc, so nothing reaches
+    // peers until the undo window runs out. returns the channel id
+    pub fn soft_delete_message(
+        &mut self,
+        community_id: &str,
+        message_id: &str,
+        capability: Option<CapabilityToken>,
+    ) -> Result<String, String> {
+        let message = self
+            .get_message(community_id, message_id)?
+            .ok_or_else(|| format!("message {} not found", message_id))?;
+
+        self.pending_deletions.insert(
+            message_id.to_string(),
+            PendingDeletion {
+                community_id: community_id.to_string(),
+                channel_id: message.channel_id.clone(),
+                capability,
+                deadline: Instant::now() + Duration::from_secs(UNDO_DELETE_SECS),
+            },
+        );
+        Ok(message.channel_id)
+    }
+
+    // bring back a soft-deleted message while its undo window is open
+    pub fn undo_delete_message(
+        &mut self,
+        community_id: &str,
+        message_id: &str,
+    ) -> Result<ChatMessage, String> {
+        match self.pending_deletions.get(message_id) {
+            Some(pending) if pending.community_id == community_id => {}
+            _ => return Err("message is not pending deletion".to_string()),
+        }
+        self.pending_deletions.remove(message_id);
+
+        self.get_message(community_id, message_id)?
+            .ok_or_else(|| format!("message {} not found", message_id))
+    }
+
+    // apply a soft deletion whose undo window has passed. none if it was
+    // undone or deleted again with a fresh window in the meantime
+    pub fn finish_soft_delete(&mut self, message_id: &str) -> Option<AppliedDeletion> {
+        match self.pending_deletions.get(message_id) {
+            Some(pending) if pending.deadline <= Instant::now() => {}
+            _ => return None,
+        }
+        self.apply_pending_deletion(message_id)
+    }
+
+    fn apply_pending_deletion(&mut self, message_id: &str) -> Option<AppliedDeletion> {
+        let pending = self.pending_deletions.remove(message_id)?;
+
+        // a peer may have removed it already, the deletion still goes out
+        if let Err(e) = self.delete_message(&pending.community_id, message_id) {
+            log::warn!("failed to apply deletion of {}: {}", message_id, e);
+        }
+        Some(AppliedDeletion {
+            message_id: message_id.to_string(),
+            community_id: pending.community_id,
+            channel_id: pending.channel_id,
+            capability: pending.capability,
+        })
+    }
+
+    // write everything out before exit. soft deletions still inside their
+    // undo window are applied now since the timers die with the process, and
+    // every live doc is saved again in case an earlier write failed. returns
+    // the deletions so the caller can broadcast them
+    pub fn flush(&mut self) -> Vec<AppliedDeletion> {
+        let pending: Vec<String> = self.pending_deletions.keys().cloned().collect();
+        let applied = pending
+            .iter()
+            .filter_map(|message_id| self.apply_pending_deletion(message_id))
+            .collect();
+
+        let community_ids: Vec<String> = self
+            .documents
+            .keys()
+            .filter(|id| !self.archived.contains(*id))
+            .cloned()
+            .collect();
+        for community_id in community_ids {
+            if let Err(e) = self.persist(&community_id) {
+                log::warn!("failed to flush community {}: {}", community_id, e);
+            }
+        }
+        applied
+    }
+
+    // get all members of a community. the member map is checked against the
+    // membership log: peers the log has leaving are dropped, roles come from
+    // the log, and members only the log knows about are added
+    pub fn get_members(&self, community_id: &str) -> Result<Vec<Member>, String> {
         let doc = self
             .documents
-            .get_mut(community_id)
+            .get(community_id)
             .ok_or("community not found")?;
 
-        document::delete_message_by_id(doc, message_id)?;
+        let mut members = document::get_members(doc)?;
+        let events = verified_membership_events(&self.verified_membership, community_id, doc);
+        if events.is_empty() {
+            return Ok(members);
+        }
+
+        let fallback_roles: HashMap<String, Vec<String>> = members
+            .iter()
+            .map(|m| (m.peer_id.clone(), m.roles.clone()))
+            .collect();
+        let founder = document::get_founder(doc);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let states = membership::replay(
+            community_id,
+            &events,
+            &fallback_roles,
+            founder.as_deref(),
+            now,
+        );
+
+        members.retain(|m| states.get(&m.peer_id).is_none_or(|s| s.active));
+        for member in members.iter_mut() {
+            if let Some(state) = states.get(&member.peer_id) {
+                member.roles = state.roles.clone();
+            }
+        }
+        for (peer_id, state) in &states {
+            if state.active && !fallback_roles.contains_key(peer_id) {
+                members.push(Member {
+                    peer_id: peer_id.clone(),
+                    display_name: state.display_name.clone(),
+                    status: crate::protocol::messages::PeerStatus::Online,
+                    roles: state.roles.clone(),
+                    trust_level: 1.0,
+                    joined_at: state.joined_at,
+                    activity: None,
+                    avatar_seed: None,
+                });
+            }
+        }
+
+        Ok(members)
+    }
+
+    // append a signed join, leave, kick or role change to the membership log
+    pub fn record_membership_event(
+        &mut self,
+        community_id: &str,
+        event: &MembershipEvent,
+    ) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        document::append_membership_event(doc, event)
+            .map_err(|e| format!("failed to record membership event: {}", e))?;
+
         self.persist(community_id)?;
         Ok(())
     }
 
-    // get all members of a community
-    pub fn get_members(
-        &self,
+    // store a capability token in the doc so its holder picks it up on sync
+    pub fn add_capability(
+        &mut self,
         community_id: &str,
-    ) -> Result<Vec<crate::protocol::community::Member>, String> {
+        token: &CapabilityToken,
+    ) -> Result<(), String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let doc = self.writable_doc(community_id)?;
+
+        document::append_capability(doc, token, now)
+            .map_err(|e| format!("failed to store capability: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    // capability tokens in the doc that are valid right now
+    pub fn get_capabilities(&self, community_id: &str) -> Result<Vec<CapabilityToken>, String> {
         let doc = self
             .documents
             .get(community_id)
             .ok_or("community not found")?;
 
-        document::get_members(doc)
+        Ok(document::get_capabilities(doc)
+            .into_iter()
+            .filter(|token| self.capability_valid(community_id, token))
+            .collect())
     }
 
-    // remove a member from a community
-    pub fn remove_member(&mut self, community_id: &str, peer_id: &str) -> Result<(), String> {
+    // a token chained back to the current owner, for this community and unexpired
+    pub fn capability_valid(&self, community_id: &str, token: &CapabilityToken) -> bool {
+        let Ok(members) = self.get_members(community_id) else {
+            return false;
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let is_owner = |peer_id: &str| {
+            members
+                .iter()
+                .any(|m| m.peer_id == peer_id && m.roles.iter().any(|r| r == "owner"))
+        };
+        token.community_id == community_id
+            && crate::verification::verify_capability_token(token, now, &is_owner)
+    }
+
+    // whether `actor` may moderate here. the owner always can, anyone else
+    // needs a valid token of theirs for the capability that covers the channel
+    pub fn may_moderate(
+        &self,
+        community_id: &str,
+        actor: &str,
+        capability: ModerationCapability,
+        channel_id: Option<&str>,
+        token: Option<&CapabilityToken>,
+    ) -> bool {
+        let is_owner = self
+            .get_members(community_id)
+            .map(|members| {
+                members
+                    .iter()
+                    .any(|m| m.peer_id == actor && m.roles.iter().any(|r| r == "owner"))
+            })
+            .unwrap_or(false);
+        if is_owner {
+            return true;
+        }
+        token.is_some_and(|token| {
+            token.holder == actor
+                && token.capability == capability
+                && (token.channel_id.is_none() || token.channel_id.as_deref() == channel_id)
+                && self.capability_valid(community_id, token)
+        })
+    }
+
+    // owners and admins move anyone but the owner out of a voice channel,
+    // other members need a move capability covering the channel
+    pub fn may_move_voice(
+        &self,
+        community_id: &str,
+        actor: &str,
+        target: &str,
+        channel_id: &str,
+        token: Option<&CapabilityToken>,
+    ) -> bool {
+        let members = self.get_members(community_id).unwrap_or_default();
+        let has_role = |peer_id: &str, role: &str| {
+            members
+                .iter()
+                .any(|m| m.peer_id == peer_id && m.roles.iter().any(|r| r == role))
+        };
+        if has_role(target, "owner") && !has_role(actor, "owner") {
+            return false;
+        }
+        has_role(actor, "admin")
+            || self.may_moderate(
+                community_id,
+                actor,
+                ModerationCapability::MoveMembers,
+                Some(channel_id),
+                token,
+            )
+    }
+
+    // who joins a stage as a speaker: owners, admins and holders of a
+    // move capability covering the channel
+    pub fn is_stage_moderator(&self, community_id: &str, peer_id: &str, channel_id: &str) -> bool {
+        let is_admin = self.get_members(community_id).is_ok_and(|members| {
+            members.iter().any(|m| {
+                m.peer_id == peer_id && m.roles.iter().any(|r| r == "owner" || r == "admin")
+            })
+        });
+        is_admin
+            || self.get_capabilities(community_id).is_ok_and(|tokens| {
+                tokens.iter().any(|t| {
+                    t.holder == peer_id
+                        && t.capability == ModerationCapability::MoveMembers
+                        && t.channel_id.as_deref().is_none_or(|c| c == channel_id)
+                })
+            })
+    }
+
+    // the raw membership log, including events whose signature does not check out
+    pub fn get_membership_log(&self, community_id: &str) -> Result<Vec<MembershipEvent>, String> {
         let doc = self
             .documents
-            .get_mut(community_id)
+            .get(community_id)
             .ok_or("community not found")?;
 
+        Ok(document::get_membership_events(doc))
+    }
+
+    // remove a member from a community
+    pub fn remove_member(&mut self, community_id: &str, peer_id: &str) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
         document::remove_member(doc, peer_id)?;
         self.persist(community_id)?;
+        self.record_departures(community_id, &[peer_id.to_string()]);
         Ok(())
     }
 
@@ -374,18 +950,291 @@ impl CrdtEngine {
         &mut self,
         community_id: &str,
         remote_bytes: &[u8],
-    ) -> Result<(), String> {
-        let remote_doc = AutoCommit::load(remote_bytes)
+    ) -> Result<MergeOutcome, String> {
+        if self.archived.contains(community_id) {
+            return Err("community is archived".to_string());
+        }
+        let mut remote_doc = AutoCommit::load(remote_bytes)
             .map_err(|e| format!("failed to load remote doc: {}", e))?;
 
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let partition_secs = self
+            .last_merged_at
+            .insert(community_id.to_string(), now)
+            .map(|last| now.saturating_sub(last));
+
+        let mut outcome = MergeOutcome {
+            diverged: false,
+            partition_secs,
+            conflicts: Vec::new(),
+        };
+
+        let mut departed = Vec::new();
         if let Some(local_doc) = self.documents.get_mut(community_id) {
+            let local_heads = local_doc.get_heads();
+            let remote_heads = remote_doc.get_heads();
+            let members_before = document::get_members(local_doc).unwrap_or_default();
+            let owner_before = document::get_community_meta(local_doc, community_id)
+                .map(|meta| meta.created_by)
+                .unwrap_or_default();
+            // the founder is fixed at creation. only a placeholder waiting for
+            // its first snapshot, which has no owner yet, takes it from a merge
+            let placeholder = owner_before.is_empty();
+            let founder_before = document::get_founder(local_doc);
+            let pages_before: Vec<(PageMeta, Option<PageRefSignature>)> =
+                notes::list_page_refs(local_doc, community_id)
+                    .into_iter()
+                    .map(|page| {
+                        let signature = notes::page_ref_signature(local_doc, &page.id);
+                        (page, signature)
+                    })
+                    .collect();
             local_doc
-                .merge(&mut remote_doc.clone())
+                .merge(&mut remote_doc)
                 .map_err(|e| format!("failed to merge docs: {}", e))?;
+
+            let founder = if placeholder {
+                document::get_founder(local_doc)
+            } else {
+                if document::get_founder(local_doc) != founder_before {
+                    log::warn!("sync: reverted a founder change in {}", community_id);
+                    document::restore_founder(local_doc, founder_before.as_deref())
+                        .map_err(|e| format!("failed to restore founder: {}", e))?;
+                }
+                founder_before
+            };
+
+            let stripped = strip_unsigned_grants(
+                &self.verified_membership,
+                community_id,
+                local_doc,
+                &members_before,
+                &owner_before,
+                founder.as_deref(),
+            );
+            if !stripped.is_empty() {
+                log::warn!(
+                    "sync: reverted unsigned role grants for {} in {}",
+                    stripped.join(", "),
+                    community_id
+                );
+            }
+
+            // a placeholder has no pages of its own to defend, the snapshot
+            // it takes was checked against an owner checkpoint
+            if !placeholder {
+                let reverted = strip_unsigned_pages(community_id, local_doc, &pages_before);
+                if !reverted.is_empty() {
+                    log::warn!(
+                        "sync: reverted unsigned page changes for {} in {}",
+                        reverted.join(", "),
+                        community_id
+                    );
+                }
+            }
+
+            if self.track_departures && !members_before.is_empty() {
+                let members_after = document::get_members(local_doc).unwrap_or_default();
+                departed = members_before
+                    .into_iter()
+                    .filter(|m| !members_after.iter().any(|a| a.peer_id == m.peer_id))
+                    .map(|m| m.peer_id)
+                    .collect();
+            }
+
+            // a fast-forward lands on one side's heads, anything else means
+            // both halves kept writing while apart
+            let merged_heads = local_doc.get_heads();
+            outcome.diverged = merged_heads != local_heads && merged_heads != remote_heads;
+            if outcome.diverged {
+                outcome.conflicts = document::find_conflicts(local_doc);
+            }
         } else {
             self.documents.insert(community_id.to_string(), remote_doc);
         }
 
+        self.persist(community_id)?;
+        self.record_departures(community_id, &departed);
+        // our join event travels in our own doc from here on
+        self.join_invites.remove(community_id);
+        Ok(outcome)
+    }
+
+    // require the next snapshot for this community to chain back to an
+    // owner-signed checkpoint before it is merged
+    pub fn expect_owner(&mut self, community_id: &str, owner_id: &str) {
+        self.expected_owners
+            .insert(community_id.to_string(), owner_id.to_string());
+    }
+
+    // present this invite when asking members for the community's doc, until
+    // a snapshot has merged. unsigned invites from older clients prove nothing
+    pub fn hold_join_invite(&mut self, invite: &InviteCode) {
+        if !invite.signature.is_empty() {
+            self.join_invites
+                .insert(invite.community_id.clone(), invite.clone());
+        }
+    }
+
+    // the communities to request docs for, each with the invite to present
+    pub fn sync_requests(&self) -> Vec<(String, Option<InviteCode>)> {
+        self.community_ids()
+            .into_iter()
+            .map(|id| {
+                let invite = self.join_invites.get(&id).cloned();
+                (id, invite)
+            })
+            .collect()
+    }
+
+    pub fn join_invite(&self, community_id: &str) -> Option<InviteCode> {
+        self.join_invites.get(community_id).cloned()
+    }
+
+    // check a received snapshot for a community we are joining. it must be
+    // created by the expected owner and contain the heads of a checkpoint that
+    // owner signed. communities we already hold are trusted as before
+    pub fn verify_snapshot(&mut self, community_id: &str, remote_bytes: &[u8]) -> Result<(), String> {
+        let Some(expected_owner) = self.expected_owners.get(community_id) else {
+            return Ok(());
+        };
+
+        let mut remote_doc = AutoCommit::load(remote_bytes)
+            .map_err(|e| format!("failed to load remote doc: {}", e))?;
+        let meta = document::get_community_meta(&remote_doc, community_id)?;
+
+        if !expected_owner.is_empty() && meta.created_by != *expected_owner {
+            return Err(format!(
+                "snapshot owner {} does not match invite owner {}",
+                meta.created_by, expected_owner
+            ));
+        }
+
+        let checkpoints = document::get_checkpoints(&remote_doc);
+        let latest = checkpoints.iter().rev().find(|cp| {
+            cp.signer == meta.created_by
+                && crate::verification::verify_checkpoint(community_id, cp)
+        });
+
+        match latest {
+            Some(checkpoint) => {
+                let heads = checkpoint
+                    .heads
+                    .iter()
+                    .map(|h| h.parse::<ChangeHash>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("invalid checkpoint head: {}", e))?;
+                if !remote_doc.get_missing_deps(&heads).is_empty() {
+                    return Err("snapshot does not contain the owner checkpoint".to_string());
+                }
+            }
+            // invites from older clients carry no owner and their docs carry no
+            // checkpoints, accept those rather than locking the community out
+            None if expected_owner.is_empty() && checkpoints.is_empty() => {
+                log::warn!(
+                    "sync: accepting unchecked snapshot for {}, no checkpoints present",
+                    community_id
+                );
+            }
+            None => return Err("snapshot has no valid owner checkpoint".to_string()),
+        }
+
+        self.expected_owners.remove(community_id);
+        Ok(())
+    }
+
+    // sign the current heads of every community we own whose doc changed since
+    // the last checkpoint, returns the ids that got a new checkpoint
+    pub fn create_checkpoints(&mut self, keypair: &libp2p::identity::Keypair) -> Vec<String> {
+        let community_ids: Vec<String> = self.documents.keys().cloned().collect();
+        community_ids
+            .into_iter()
+            .filter(|community_id| self.create_checkpoint(community_id, keypair))
+            .collect()
+    }
+
+    // sign the current heads of one community, when we own it and its doc
+    // changed since the last checkpoint. also called on creation and when
+    // inviting, so a joiner has a checkpoint to check our doc against before
+    // the first periodic one
+    pub fn create_checkpoint(
+        &mut self,
+        community_id: &str,
+        keypair: &libp2p::identity::Keypair,
+    ) -> bool {
+        if self.archived.contains(community_id) {
+            return false;
+        }
+        let Some(doc) = self.documents.get_mut(community_id) else {
+            return false;
+        };
+        let local_peer_id = keypair.public().to_peer_id().to_string();
+        let owned = document::get_community_meta(doc, community_id)
+            .map(|meta| meta.created_by == local_peer_id)
+            .unwrap_or(false);
+        if !owned {
+            return false;
+        }
+
+        // the founder's order rides along with the checkpoint
+        place_membership_events(&self.verified_membership, community_id, doc, keypair);
+
+        let heads = doc.get_heads();
+        if self.checkpointed_heads.get(community_id) == Some(&heads) {
+            return false;
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mut checkpoint = DocCheckpoint {
+            heads: heads.iter().map(|h| h.to_string()).collect(),
+            created_at: now,
+            signer: local_peer_id,
+            public_key: hex::encode(keypair.public().encode_protobuf()),
+            signature: String::new(),
+        };
+        checkpoint.signature =
+            crate::verification::sign_checkpoint(keypair, community_id, &checkpoint);
+
+        if let Err(e) = document::append_checkpoint(doc, &checkpoint) {
+            log::warn!("failed to checkpoint community {}: {}", community_id, e);
+            return false;
+        }
+        self.checkpointed_heads
+            .insert(community_id.to_string(), doc.get_heads());
+
+        if let Err(e) = self.persist(community_id) {
+            log::warn!("{}", e);
+        }
+        true
+    }
+
+    // list keys currently holding concurrent values
+    pub fn get_conflicts(&self, community_id: &str) -> Result<Vec<DocConflict>, String> {
+        let doc = self
+            .documents
+            .get(community_id)
+            .ok_or("community not found")?;
+
+        Ok(document::find_conflicts(doc))
+    }
+
+    // settle a conflicting key with the moderator's chosen value
+    pub fn resolve_conflict(
+        &mut self,
+        community_id: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        document::resolve_conflict(doc, key, value)?;
+
         self.persist(community_id)?;
         Ok(())
     }
@@ -395,6 +1244,45 @@ impl CrdtEngine {
         self.documents.get_mut(community_id).map(|doc| doc.save())
     }
 
+    // set the community's minimum verification policy
+    pub fn set_verification_policy(
+        &mut self,
+        community_id: &str,
+        policy: VerificationPolicy,
+    ) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        document::set_verification_policy(doc, policy)
+            .map_err(|e| format!("failed to set verification policy: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    pub fn set_edit_history(&mut self, community_id: &str, enabled: bool) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        document::set_edit_history(doc, enabled)
+            .map_err(|e| format!("failed to set edit history: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    // earlier revisions of a message, oldest first
+    pub fn get_message_revisions(
+        &self,
+        community_id: &str,
+        message_id: &str,
+    ) -> Result<Vec<MessageRevision>, String> {
+        let doc = self
+            .documents
+            .get(community_id)
+            .ok_or("community not found")?;
+
+        document::get_message_revisions(doc, message_id)
+    }
+
     // update community name and description
     pub fn update_community_meta(
         &mut self,
@@ -402,10 +1290,7 @@ impl CrdtEngine {
         name: &str,
         description: &str,
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::update_community_meta(doc, name, description)
             .map_err(|e| format!("failed to update community meta: {}", e))?;
@@ -422,10 +1307,7 @@ impl CrdtEngine {
         name: &str,
         topic: &str,
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::update_channel(doc, channel_id, name, topic)
             .map_err(|e| format!("failed to update channel: {}", e))?;
@@ -434,12 +1316,25 @@ impl CrdtEngine {
         Ok(())
     }
 
+    // cap how many participants a voice channel admits
+    pub fn set_channel_capacity(
+        &mut self,
+        community_id: &str,
+        channel_id: &str,
+        max_participants: Option<u32>,
+    ) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        document::set_channel_capacity(doc, channel_id, max_participants)
+            .map_err(|e| format!("failed to set channel capacity: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
     // remove a channel from a community
     pub fn delete_channel(&mut self, community_id: &str, channel_id: &str) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::delete_channel(doc, channel_id)
             .map_err(|e| format!("failed to delete channel: {}", e))?;
@@ -455,10 +1350,7 @@ impl CrdtEngine {
         category_id: &str,
         name: &str,
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::update_category(doc, category_id, name)
             .map_err(|e| format!("failed to update category: {}", e))?;
@@ -473,10 +1365,7 @@ impl CrdtEngine {
         community_id: &str,
         category_ids: &[String],
     ) -> Result<Vec<CategoryMeta>, String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         let categories = document::reorder_categories(doc, community_id, category_ids)?;
         self.persist(community_id)?;
@@ -485,10 +1374,7 @@ impl CrdtEngine {
 
     // remove a category and ungroup its channels
     pub fn delete_category(&mut self, community_id: &str, category_id: &str) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::delete_category(doc, category_id)
             .map_err(|e| format!("failed to delete category: {}", e))?;
@@ -504,10 +1390,7 @@ impl CrdtEngine {
         peer_id: &str,
         roles: &[String],
     ) -> Result<(), String> {
-        let doc = self
-            .documents
-            .get_mut(community_id)
-            .ok_or("community not found")?;
+        let doc = self.writable_doc(community_id)?;
 
         document::set_member_role(doc, peer_id, roles)
             .map_err(|e| format!("failed to set member role: {}", e))?;
@@ -523,13 +1406,97 @@ impl CrdtEngine {
         old_owner_id: &str,
         new_owner_id: &str,
     ) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        document::transfer_ownership(doc, old_owner_id, new_owner_id)
+            .map_err(|e| format!("failed to transfer ownership: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    // -- task board --
+
+    pub fn get_task_board(&self, community_id: &str) -> Result<TaskBoard, String> {
         let doc = self
             .documents
-            .get_mut(community_id)
+            .get(community_id)
             .ok_or("community not found")?;
+        Ok(tasks::get_task_board(doc, community_id))
+    }
 
-        document::transfer_ownership(doc, old_owner_id, new_owner_id)
-            .map_err(|e| format!("failed to transfer ownership: {}", e))?;
+    // returns the position the list landed at
+    pub fn create_task_list(&mut self, community_id: &str, list: &TaskList) -> Result<u32, String> {
+        let doc = self.writable_doc(community_id)?;
+
+        let position = tasks::add_task_list(doc, list)
+            .map_err(|e| format!("failed to create task list: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(position)
+    }
+
+    pub fn update_task_list(
+        &mut self,
+        community_id: &str,
+        list_id: &str,
+        name: Option<&str>,
+        position: Option<u32>,
+    ) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        tasks::update_task_list(doc, list_id, name, position)
+            .map_err(|e| format!("failed to update task list: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    pub fn delete_task_list(&mut self, community_id: &str, list_id: &str) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        tasks::delete_task_list(doc, list_id)
+            .map_err(|e| format!("failed to delete task list: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    // returns the position the card landed at
+    pub fn create_task_card(&mut self, community_id: &str, card: &TaskCard) -> Result<u32, String> {
+        let doc = self.writable_doc(community_id)?;
+
+        let position = tasks::add_task_card(doc, card)
+            .map_err(|e| format!("failed to create task card: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(position)
+    }
+
+    pub fn update_task_card(
+        &mut self,
+        community_id: &str,
+        card_id: &str,
+        patch: &TaskCardPatch,
+    ) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        tasks::update_task_card(doc, card_id, patch, now)
+            .map_err(|e| format!("failed to update task card: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    pub fn delete_task_card(&mut self, community_id: &str, card_id: &str) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        tasks::delete_task_card(doc, card_id)
+            .map_err(|e| format!("failed to delete task card: {}", e))?;
 
         self.persist(community_id)?;
         Ok(())
@@ -538,5 +1505,551 @@ impl CrdtEngine {
     // drop all in-memory documents (used during identity reset)
     pub fn clear(&mut self) {
         self.documents.clear();
+        self.notes.clear();
+        self.verified_membership.borrow_mut().clear();
+    }
+
+    // -- wiki pages --
+
+    // start a page: its body is a new note, the community doc lists it with
+    // the role needed to edit it. returns the note's changes to publish,
+    // the listing goes out with the doc sync
+    pub fn create_page(
+        &mut self,
+        community_id: &str,
+        page_id: &str,
+        title: &str,
+        edit_role: PageEditRole,
+        keypair: &libp2p::identity::Keypair,
+    ) -> Result<(PageMeta, Vec<u8>), String> {
+        let created_by = keypair.public().to_peer_id().to_string();
+        let (note, changes) = self.create_note(community_id, page_id, title, &created_by)?;
+        let doc = self.writable_doc(community_id)?;
+
+        let page = PageMeta {
+            id: note.id,
+            community_id: note.community_id,
+            title: note.title,
+            edit_role,
+            position: notes::next_page_position(doc),
+            created_by: note.created_by,
+            created_at: note.created_at,
+            updated_by: note.updated_by,
+            updated_at: note.updated_at,
+        };
+        let signature = crate::verification::sign_page_ref(keypair, community_id, &page);
+        notes::put_page_ref(doc, &page, Some(&signature))
+            .map_err(|e| format!("failed to create page: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok((page, changes))
+    }
+
+    // in page order. a page whose body has not reached us yet is listed
+    // untitled until it does
+    pub fn get_pages(&self, community_id: &str) -> Result<Vec<PageMeta>, String> {
+        let doc = self
+            .documents
+            .get(community_id)
+            .ok_or("community not found")?;
+
+        let mut pages = notes::list_page_refs(doc, community_id);
+        for page in &mut pages {
+            let Some(note) = self
+                .notes
+                .get(&page.id)
+                .filter(|n| n.community_id == community_id)
+                .and_then(|n| notes::read_note(&n.doc))
+            else {
+                continue;
+            };
+            page.title = note.title;
+            page.updated_by = note.updated_by;
+            page.updated_at = note.updated_at;
+        }
+        Ok(pages)
+    }
+
+    // none when the note is not a page
+    pub fn page_edit_role(&self, community_id: &str, page_id: &str) -> Option<PageEditRole> {
+        let doc = self.documents.get(community_id)?;
+        notes::list_page_refs(doc, community_id)
+            .into_iter()
+            .find(|p| p.id == page_id)
+            .map(|p| p.edit_role)
+    }
+
+    pub fn set_page_edit_role(
+        &mut self,
+        community_id: &str,
+        page_id: &str,
+        edit_role: PageEditRole,
+        keypair: &libp2p::identity::Keypair,
+    ) -> Result<(), String> {
+        let doc = self.writable_doc(community_id)?;
+
+        let mut page = notes::list_page_refs(doc, community_id)
+            .into_iter()
+            .find(|p| p.id == page_id)
+            .ok_or("page not found")?;
+        page.edit_role = edit_role;
+        let signature = crate::verification::sign_page_ref(keypair, community_id, &page);
+        notes::put_page_ref(doc, &page, Some(&signature))
+            .map_err(|e| format!("failed to update page: {}", e))?;
+
+        self.persist(community_id)?;
+        Ok(())
+    }
+
+    // -- collaborative notes --
+
+    // start a note in one of our communities, returns it with the changes
+    // to publish, which carry its whole history
+    pub fn create_note(
+        &mut self,
+        community_id: &str,
+        note_id: &str,
+        title: &str,
+        created_by: &str,
+    ) -> Result<(Note, Vec<u8>), String> {
+        if !self.documents.contains_key(community_id) {
+            return Err("community not found".to_string());
+        }
+        if self.archived.contains(community_id) {
+            return Err("community is archived".to_string());
+        }
+        if title.chars().count() > MAX_NOTE_TITLE_CHARS {
+            return Err(format!(
+                "note titles are limited to {} characters",
+                MAX_NOTE_TITLE_CHARS
+            ));
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mut doc = AutoCommit::new();
+        notes::init_note_doc(&mut doc, note_id, community_id, title, created_by, now)
+            .map_err(|e| format!("failed to create note: {}", e))?;
+        notes::commit_edit(&mut doc, created_by, now);
+        let changes = doc.save();
+        let note = notes::read_note(&doc).ok_or("failed to read note")?;
+
+        self.notes.insert(
+            note_id.to_string(),
+            NoteDoc {
+                community_id: community_id.to_string(),
+                doc,
+            },
+        );
+        self.persist_note(note_id)?;
+        Ok((note, changes))
+    }
+
+    // apply a local edit as a single change, returns the note and the change
+    // to publish. a step that fails leaves the note untouched
+    pub fn apply_note_edits(
+        &mut self,
+        community_id: &str,
+        note_id: &str,
+        edits: &[NoteEdit],
+        author: &str,
+    ) -> Result<(Note, Vec<u8>), String> {
+        if self.archived.contains(community_id) {
+            return Err("community is archived".to_string());
+        }
+        let entry = self
+            .notes
+            .get_mut(note_id)
+            .filter(|n| n.community_id == community_id)
+            .ok_or("note not found")?;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let heads = entry.doc.get_heads();
+        for edit in edits {
+            if let Err(e) = notes::apply_edit(&mut entry.doc, edit) {
+                entry.doc.rollback();
+                return Err(e);
+            }
+        }
+        notes::touch_note(&mut entry.doc, author, now)
+            .map_err(|e| format!("failed to edit note: {}", e))?;
+        notes::commit_edit(&mut entry.doc, author, now);
+        let changes = entry.doc.save_after(&heads);
+        let note = notes::read_note(&entry.doc).ok_or("failed to read note")?;
+
+        self.persist_note(note_id)?;
+        Ok((note, changes))
+    }
+
+    pub fn get_note(&self, community_id: &str, note_id: &str) -> Result<Note, String> {
+        self.notes
+            .get(note_id)
+            .filter(|n| n.community_id == community_id)
+            .and_then(|n| notes::read_note(&n.doc))
+            .ok_or_else(|| "note not found".to_string())
+    }
+
+    // most recently edited first, wiki pages are listed by get_pages instead
+    pub fn get_notes(&self, community_id: &str) -> Vec<Note> {
+        let pages: HashSet<String> = self
+            .documents
+            .get(community_id)
+            .map(|doc| notes::list_page_refs(doc, community_id))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        let mut list: Vec<Note> = self
+            .notes
+            .iter()
+            .filter(|(id, n)| n.community_id == community_id && !pages.contains(*id))
+            .filter_map(|(_, n)| notes::read_note(&n.doc))
+            .collect();
+        list.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        list
+    }
+
+    // merge changes a member published. changes for a note we have never
+    // seen start a new doc, which stays invisible until its creation arrives
+    pub fn merge_note_changes(
+        &mut self,
+        community_id: &str,
+        note_id: &str,
+        changes: &[u8],
+    ) -> Result<NoteMerge, String> {
+        if !self.documents.contains_key(community_id) {
+            return Err("community not found".to_string());
+        }
+        if self.archived.contains(community_id) {
+            return Err("community is archived".to_string());
+        }
+        // a note we don't hold yet is only kept once its changes load
+        let mut created = None;
+        let entry = match self.notes.get_mut(note_id) {
+            Some(entry) => entry,
+            None => created.insert(NoteDoc {
+                community_id: community_id.to_string(),
+                doc: AutoCommit::new(),
+            }),
+        };
+        if entry.community_id != community_id {
+            return Err("note belongs to another community".to_string());
+        }
+
+        let before = entry.doc.get_heads();
+        entry
+            .doc
+            .load_incremental(changes)
+            .map_err(|e| format!("failed to merge note changes: {}", e))?;
+        let after = entry.doc.get_heads();
+        let missing_heads = (!entry.doc.get_missing_deps(&[]).is_empty())
+            .then(|| after.iter().map(|h| h.to_string()).collect());
+        let note = notes::read_note(&entry.doc).filter(|_| before != after);
+
+        if let Some(created) = created {
+            self.notes.insert(note_id.to_string(), created);
+        }
+        if note.is_some() {
+            self.persist_note(note_id)?;
+        }
+        Ok(NoteMerge {
+            note,
+            missing_heads,
+        })
+    }
+
+    // the changes a peer at these heads lacks, none when it is up to date or
+    // we don't hold the note's creation ourselves
+    pub fn note_changes_since(
+        &mut self,
+        community_id: &str,
+        note_id: &str,
+        heads: &[String],
+    ) -> Option<Vec<u8>> {
+        let entry = self
+            .notes
+            .get_mut(note_id)
+            .filter(|n| n.community_id == community_id)?;
+        notes::read_note(&entry.doc)?;
+
+        let known: Vec<ChangeHash> = heads
+            .iter()
+            .filter_map(|h| h.parse::<ChangeHash>().ok())
+            .filter(|h| entry.doc.get_change_by_hash(h).is_some())
+            .collect();
+        let ours = entry.doc.get_heads();
+        if ours.iter().all(|h| known.contains(h)) {
+            return None;
+        }
+        Some(entry.doc.save_after(&known))
+    }
+
+    // every change to the note with who its client said made it, oldest first
+    pub fn note_history(
+        &mut self,
+        community_id: &str,
+        note_id: &str,
+    ) -> Result<Vec<NoteRevision>, String> {
+        let entry = self
+            .notes
+            .get_mut(note_id)
+            .filter(|n| n.community_id == community_id)
+            .ok_or("note not found")?;
+        Ok(notes::note_history(&mut entry.doc))
+    }
+
+    // the note as it read right after one of its changes
+    pub fn get_note_at(
+        &mut self,
+        community_id: &str,
+        note_id: &str,
+        revision: &str,
+    ) -> Result<Note, String> {
+        let entry = self
+            .notes
+            .get_mut(note_id)
+            .filter(|n| n.community_id == community_id)
+            .ok_or("note not found")?;
+        let hash = revision
+            .parse::<ChangeHash>()
+            .map_err(|_| "invalid revision".to_string())?;
+        if entry.doc.get_change_by_hash(&hash).is_none() {
+            return Err("revision not found".to_string());
+        }
+        notes::read_note_at(&entry.doc, Some(&[hash]))
+            .ok_or_else(|| "revision not found".to_string())
+    }
+
+    // whether a member may change a note. pages restricted to admins take
+    // the owner or admin role, anything else takes membership
+    pub fn can_edit_note(&self, community_id: &str, note_id: &str, peer_id: &str) -> bool {
+        let Some(member) = self
+            .get_members(community_id)
+            .ok()
+            .and_then(|members| members.into_iter().find(|m| m.peer_id == peer_id))
+        else {
+            return false;
+        };
+        match self.page_edit_role(community_id, note_id) {
+            Some(PageEditRole::Admin) => member.roles.iter().any(|r| r == "owner" || r == "admin"),
+            _ => true,
+        }
+    }
+
+    fn persist_note(&mut self, note_id: &str) -> Result<(), String> {
+        let entry = self.notes.get_mut(note_id).ok_or("note not found")?;
+        let bytes = entry.doc.save();
+        self.storage
+            .save_note_document(note_id, &entry.community_id, &bytes)
+            .map_err(|e| format!("failed to persist note: {}", e))
+    }
+}
+
+// membership events with a valid signature, each with the founder's place
+// for it. unsigned or forged events and orders are ignored as if they were
+// never written. what was already checked is cached by a key over the signed
+// content, since the log is replayed on every member lookup
+fn verified_membership_events(
+    cache: &RefCell<HashSet<String>>,
+    community_id: &str,
+    doc: &AutoCommit,
+) -> Vec<membership::LoggedEvent> {
+    let mut verified = cache.borrow_mut();
+    let founder = document::get_founder(doc);
+
+    // an event placed twice keeps its first place
+    let mut places: HashMap<String, (u64, u64)> = HashMap::new();
+    for order in document::get_membership_order(doc) {
+        if founder.as_deref() != Some(order.signer.as_str()) {
+            continue;
+        }
+        let key = crate::verification::membership_order_key(community_id, &order);
+        if !verified.contains(&key) {
+            if !crate::verification::verify_membership_order(community_id, &order) {
+                continue;
+            }
+            verified.insert(key);
+        }
+        let place = places
+            .entry(order.event_key)
+            .or_insert((order.seq, order.placed_at));
+        if order.seq < place.0 {
+            *place = (order.seq, order.placed_at);
+        }
+    }
+
+    document::get_membership_events(doc)
+        .into_iter()
+        .filter_map(|event| {
+            let key = crate::verification::membership_event_key(community_id, &event);
+            if !verified.contains(&key) {
+                if !crate::verification::verify_membership_event(community_id, &event) {
+                    return None;
+                }
+                verified.insert(key.clone());
+            }
+            let place = places.get(&key).copied();
+            Some(membership::LoggedEvent {
+                event,
+                seq: place.map(|(seq, _)| seq),
+                placed_at: place.map(|(_, placed_at)| placed_at),
+            })
+        })
+        .collect()
+}
+
+// as the founder, place the checked membership events not placed yet after
+// those that are. our own events go first, so a demotion we made is ahead of
+// anything the demoted peer signed since
+fn place_membership_events(
+    cache: &RefCell<HashSet<String>>,
+    community_id: &str,
+    doc: &mut AutoCommit,
+    keypair: &libp2p::identity::Keypair,
+) {
+    let local_peer_id = keypair.public().to_peer_id().to_string();
+    if document::get_founder(doc).as_deref() != Some(local_peer_id.as_str()) {
+        return;
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let events = verified_membership_events(cache, community_id, doc);
+    let mut next = events
+        .iter()
+        .filter_map(|logged| logged.seq)
+        .max()
+        .map_or(0, |seq| seq + 1);
+
+    let mut unplaced: Vec<&MembershipEvent> = events
+        .iter()
+        .filter(|logged| logged.seq.is_none())
+        .map(|logged| &logged.event)
+        .collect();
+    unplaced.sort_by(|a, b| {
+        (a.actor != local_peer_id)
+            .cmp(&(b.actor != local_peer_id))
+            .then_with(|| a.timestamp.cmp(&b.timestamp))
+    });
+
+    let mut seen = HashSet::new();
+    for event in unplaced {
+        let key = crate::verification::membership_event_key(community_id, event);
+        if !seen.insert(key.clone()) {
+            continue;
+        }
+        let order =
+            crate::verification::sign_membership_order(keypair, community_id, &key, next, now);
+        if let Err(e) = document::append_membership_order(doc, &order) {
+            log::warn!(
+                "failed to place membership event in {}: {}",
+                community_id,
+                e
+            );
+            break;
+        }
+        next += 1;
+    }
+}
+
+// undo changes a merge made to the pages list that no owner or admin signed,
+// after strip_unsigned_grants so the roles checked are the signed ones.
+// `before` is every page entry with its signature ahead of the merge, returns
+// the pages put back
+fn strip_unsigned_pages(
+    community_id: &str,
+    doc: &mut AutoCommit,
+    before: &[(PageMeta, Option<PageRefSignature>)],
+) -> Vec<String> {
+    let admins: HashSet<String> = document::get_members(doc)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|m| m.roles.iter().any(|r| r == "owner" || r == "admin"))
+        .map(|m| m.peer_id)
+        .collect();
+    let same_ref = |a: &PageMeta, b: &PageMeta| {
+        a.edit_role == b.edit_role
+            && a.position == b.position
+            && a.created_by == b.created_by
+            && a.created_at == b.created_at
+    };
+
+    let after = notes::list_page_refs(doc, community_id);
+    let mut reverted = Vec::new();
+    for page in &after {
+        let previous = before.iter().find(|(p, _)| p.id == page.id);
+        if previous.is_some_and(|(p, _)| same_ref(p, page)) {
+            continue;
+        }
+        let signed = notes::page_ref_signature(doc, &page.id).is_some_and(|signature| {
+            admins.contains(&signature.signer)
+                && crate::verification::verify_page_ref(community_id, page, &signature)
+        });
+        if signed {
+            continue;
+        }
+        let result = match previous {
+            Some((p, signature)) => notes::put_page_ref(doc, p, signature.as_ref()),
+            None => notes::remove_page_ref(doc, &page.id),
+        };
+        match result {
+            Ok(()) => reverted.push(page.id.clone()),
+            Err(e) => log::warn!("failed to revert page {}: {}", page.id, e),
+        }
+    }
+
+    // an entry a merge deleted comes back as it was, pages are never removed
+    for (page, signature) in before {
+        if after.iter().any(|p| p.id == page.id) {
+            continue;
+        }
+        match notes::put_page_ref(doc, page, signature.as_ref()) {
+            Ok(()) => reverted.push(page.id.clone()),
+            Err(e) => log::warn!("failed to restore page {}: {}", page.id, e),
+        }
+    }
+    reverted
+}
+
+// undo role grants a merge brought in without a signed membership event
+// behind them, returns the peers whose roles were put back
+fn strip_unsigned_grants(
+    cache: &RefCell<HashSet<String>>,
+    community_id: &str,
+    doc: &mut AutoCommit,
+    members_before: &[Member],
+    owner_before: &str,
+    founder: Option<&str>,
+) -> Vec<String> {
+    let before: HashMap<String, Vec<String>> = members_before
+        .iter()
+        .map(|m| (m.peer_id.clone(), m.roles.clone()))
+        .collect();
+    let after: HashMap<String, Vec<String>> = document::get_members(doc)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|m| (m.peer_id, m.roles))
+        .collect();
+
+    let events = verified_membership_events(cache, community_id, doc);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let states = membership::replay(community_id, &events, &before, founder, now);
+
+    let mut stripped = Vec::new();
+    for (peer_id, roles) in membership::unsigned_grants(&before, &after, &states, owner_before) {
+        match document::set_member_role(doc, &peer_id, &roles) {
+            Ok(()) => stripped.push(peer_id),
+            Err(e) => log::warn!("failed to revert roles for {}: {}", peer_id, e),
+        }
     }
+    stripped
 }
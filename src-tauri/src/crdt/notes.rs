@@ -0,0 +1,352 @@
+use automerge::marks::{ExpandMark, Mark};
+use automerge::transaction::{CommitOptions, Transactable};
+use automerge::{AutoCommit, ChangeHash, ObjId, ObjType, ReadDoc, ScalarValue, ROOT};
+
+use crate::protocol::notes::{
+    Note, NoteEdit, NoteMark, NoteRevision, PageEditRole, PageMeta, PageRefSignature,
+    MAX_NOTE_CHARS, MAX_NOTE_TITLE_CHARS,
+};
+
+// lay out a fresh note: metadata in the root map, the body as a text object
+pub fn init_note_doc(
+    doc: &mut AutoCommit,
+    note_id: &str,
+    community_id: &str,
+    title: &str,
+    created_by: &str,
+    now: u64,
+) -> Result<(), automerge::AutomergeError> {
+    doc.put(ROOT, "id", note_id)?;
+    doc.put(ROOT, "community_id", community_id)?;
+    doc.put(ROOT, "title", title)?;
+    doc.put(ROOT, "created_by", created_by)?;
+    doc.put(ROOT, "created_at", now as i64)?;
+    doc.put(ROOT, "updated_by", created_by)?;
+    doc.put(ROOT, "updated_at", now as i64)?;
+    doc.put_object(ROOT, "text", ObjType::Text)?;
+    Ok(())
+}
+
+// none until the changes creating the note have arrived
+pub fn read_note(doc: &AutoCommit) -> Option<Note> {
+    read_note_at(doc, None)
+}
+
+// the note as it read at heads, or as it reads now
+pub fn read_note_at(doc: &AutoCommit, heads: Option<&[ChangeHash]>) -> Option<Note> {
+    let get = |key: &str| {
+        match heads {
+            Some(heads) => doc.get_at(ROOT, key, heads),
+            None => doc.get(ROOT, key),
+        }
+        .ok()
+        .flatten()
+    };
+    let get_str = |key: &str| get(key).and_then(|(val, _)| val.into_string().ok());
+    let get_u64 = |key: &str| {
+        get(key)
+            .and_then(|(val, _)| val.to_i64())
+            .map_or(0, |v| v.max(0) as u64)
+    };
+
+    let (_, text_obj) = get("text")?;
+    let (text, marks) = match heads {
+        Some(heads) => (
+            doc.text_at(&text_obj, heads).ok()?,
+            doc.marks_at(&text_obj, heads).unwrap_or_default(),
+        ),
+        None => (
+            doc.text(&text_obj).ok()?,
+            doc.marks(&text_obj).unwrap_or_default(),
+        ),
+    };
+    let marks = marks
+        .iter()
+        .map(|mark| NoteMark {
+            start: mark.start,
+            end: mark.end,
+            name: mark.name().to_string(),
+            value: scalar_to_json(mark.value()),
+        })
+        .collect();
+
+    Some(Note {
+        id: get_str("id")?,
+        community_id: get_str("community_id")?,
+        title: get_str("title").unwrap_or_default(),
+        text,
+        marks,
+        created_by: get_str("created_by").unwrap_or_default(),
+        created_at: get_u64("created_at"),
+        updated_by: get_str("updated_by").unwrap_or_default(),
+        updated_at: get_u64("updated_at"),
+    })
+}
+
+// close the pending edit as one change that records who made it and when,
+// which is what the note's history shows
+pub fn commit_edit(doc: &mut AutoCommit, author: &str, now: u64) {
+    doc.commit_with(
+        CommitOptions::default()
+            .with_message(author.to_string())
+            .with_time(now as i64),
+    );
+}
+
+// every change to the note, oldest first
+pub fn note_history(doc: &mut AutoCommit) -> Vec<NoteRevision> {
+    doc.get_changes(&[])
+        .into_iter()
+        .map(|change| NoteRevision {
+            hash: change.hash().to_string(),
+            claimed_author: change.message().cloned().unwrap_or_default(),
+            timestamp: change.timestamp().max(0) as u64,
+        })
+        .collect()
+}
+
+// apply one edit step, out of range positions are refused rather than clamped
+// so a stale frontend finds out instead of garbling the note
+pub fn apply_edit(doc: &mut AutoCommit, edit: &NoteEdit) -> Result<(), String> {
+    let text_obj = text_obj(doc).ok_or("note has no text")?;
+    let len = doc.length(&text_obj);
+
+    match edit {
+        NoteEdit::Splice {
+            index,
+            delete,
+            insert,
+        } => {
+            if index.saturating_add(*delete) > len {
+                return Err("edit is outside the note".to_string());
+            }
+            if len - delete + insert.chars().count() > MAX_NOTE_CHARS {
+                return Err(format!(
+                    "notes are limited to {} characters",
+                    MAX_NOTE_CHARS
+                ));
+            }
+            doc.splice_text(&text_obj, *index, *delete as isize, insert)
+                .map_err(|e| format!("failed to edit note: {}", e))?;
+        }
+        NoteEdit::Mark {
+            start,
+            end,
+            name,
+            value,
+        } => {
+            if start >= end || *end > len {
+                return Err("mark is outside the note".to_string());
+            }
+            // typing at the end of a link shouldn't extend it
+            let expand = if name == "link" {
+                ExpandMark::None
+            } else {
+                ExpandMark::After
+            };
+            let mark = Mark::new(name.clone(), json_to_scalar(value), *start, *end);
+            doc.mark(&text_obj, mark, expand)
+                .map_err(|e| format!("failed to mark note: {}", e))?;
+        }
+        NoteEdit::Unmark { start, end, name } => {
+            if start >= end || *end > len {
+                return Err("mark is outside the note".to_string());
+            }
+            doc.unmark(&text_obj, name, *start, *end, ExpandMark::After)
+                .map_err(|e| format!("failed to unmark note: {}", e))?;
+        }
+        NoteEdit::SetTitle { title } => {
+            if title.chars().count() > MAX_NOTE_TITLE_CHARS {
+                return Err(format!(
+                    "note titles are limited to {} characters",
+                    MAX_NOTE_TITLE_CHARS
+                ));
+            }
+            doc.put(ROOT, "title", title.as_str())
+                .map_err(|e| format!("failed to rename note: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn touch_note(
+    doc: &mut AutoCommit,
+    updated_by: &str,
+    now: u64,
+) -> Result<(), automerge::AutomergeError> {
+    doc.put(ROOT, "updated_by", updated_by)?;
+    doc.put(ROOT, "updated_at", now as i64)?;
+    Ok(())
+}
+
+// -- wiki pages, listed in the community doc --
+
+// every concurrent copy of the pages map, older communities create it on
+// first use and two members doing that at once each win a copy
+fn page_maps(doc: &AutoCommit) -> Vec<ObjId> {
+    doc.get_all(ROOT, "pages")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_, id)| id)
+        .collect()
+}
+
+fn find_page_ref(doc: &AutoCommit, page_id: &str) -> Option<ObjId> {
+    page_maps(doc)
+        .into_iter()
+        .find_map(|pages| doc.get(&pages, page_id).ok().flatten().map(|(_, id)| id))
+}
+
+// where a new page goes, after the existing ones
+pub fn next_page_position(doc: &AutoCommit) -> u32 {
+    list_page_refs(doc, "").len() as u32
+}
+
+// write a page's entry, creating it when the doc has none yet. pages from
+// before entries were signed are written back without a signature
+pub fn put_page_ref(
+    doc: &mut AutoCommit,
+    page: &PageMeta,
+    signature: Option<&PageRefSignature>,
+) -> Result<(), automerge::AutomergeError> {
+    let entry = match find_page_ref(doc, &page.id) {
+        Some(entry) => entry,
+        None => {
+            let pages = match doc.get(ROOT, "pages")? {
+                Some((_, id)) => id,
+                None => doc.put_object(ROOT, "pages", ObjType::Map)?,
+            };
+            doc.put_object(&pages, page.id.as_str(), ObjType::Map)?
+        }
+    };
+    doc.put(&entry, "edit_role", edit_role_str(page.edit_role))?;
+    doc.put(&entry, "position", page.position as i64)?;
+    doc.put(&entry, "created_by", page.created_by.as_str())?;
+    doc.put(&entry, "created_at", page.created_at as i64)?;
+    match signature {
+        Some(signature) => {
+            doc.put(&entry, "signer", signature.signer.as_str())?;
+            doc.put(&entry, "public_key", signature.public_key.as_str())?;
+            doc.put(&entry, "signature", signature.signature.as_str())?;
+        }
+        None => {
+            for key in ["signer", "public_key", "signature"] {
+                if doc.get(&entry, key)?.is_some() {
+                    doc.delete(&entry, key)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// drop a page's entry from every copy of the pages map
+pub fn remove_page_ref(
+    doc: &mut AutoCommit,
+    page_id: &str,
+) -> Result<(), automerge::AutomergeError> {
+    for pages in page_maps(doc) {
+        if doc.get(&pages, page_id)?.is_some() {
+            doc.delete(&pages, page_id)?;
+        }
+    }
+    Ok(())
+}
+
+// the signature a page's entry carries, none for pages from before entries
+// were signed
+pub fn page_ref_signature(doc: &AutoCommit, page_id: &str) -> Option<PageRefSignature> {
+    let entry = find_page_ref(doc, page_id)?;
+    let get_str = |key: &str| {
+        doc.get(&entry, key)
+            .ok()
+            .flatten()
+            .and_then(|(val, _)| val.into_string().ok())
+    };
+    Some(PageRefSignature {
+        signer: get_str("signer")?,
+        public_key: get_str("public_key")?,
+        signature: get_str("signature")?,
+    })
+}
+
+// what the community doc knows of each page, title and last edit come from
+// the page's own doc
+pub fn list_page_refs(doc: &AutoCommit, community_id: &str) -> Vec<PageMeta> {
+    let mut pages: Vec<PageMeta> = Vec::new();
+    for map in page_maps(doc) {
+        for page_id in doc.keys(&map).collect::<Vec<_>>() {
+            let Some((_, page)) = doc.get(&map, &page_id).ok().flatten() else {
+                continue;
+            };
+            if pages.iter().any(|p| p.id == page_id) {
+                continue;
+            }
+            let get_str = |key: &str| {
+                doc.get(&page, key)
+                    .ok()
+                    .flatten()
+                    .and_then(|(val, _)| val.into_string().ok())
+            };
+            let get_u64 = |key: &str| {
+                doc.get(&page, key)
+                    .ok()
+                    .flatten()
+                    .and_then(|(val, _)| val.to_i64())
+                    .map_or(0, |v| v.max(0) as u64)
+            };
+            let edit_role = match get_str("edit_role").as_deref() {
+                Some("admin") => PageEditRole::Admin,
+                _ => PageEditRole::Member,
+            };
+            pages.push(PageMeta {
+                id: page_id.clone(),
+                community_id: community_id.to_string(),
+                title: String::new(),
+                edit_role,
+                position: get_u64("position") as u32,
+                created_by: get_str("created_by").unwrap_or_default(),
+                created_at: get_u64("created_at"),
+                updated_by: String::new(),
+                updated_at: 0,
+            });
+        }
+    }
+    pages.sort_by(|a, b| (a.position, a.created_at).cmp(&(b.position, b.created_at)));
+    pages
+}
+
+fn edit_role_str(edit_role: PageEditRole) -> &'static str {
+    match edit_role {
+        PageEditRole::Member => "member",
+        PageEditRole::Admin => "admin",
+    }
+}
+
+fn text_obj(doc: &AutoCommit) -> Option<ObjId> {
+    doc.get(ROOT, "text").ok().flatten().map(|(_, id)| id)
+}
+
+// marks hold scalars only, anything else is stored as null
+fn json_to_scalar(value: &serde_json::Value) -> ScalarValue {
+    match value {
+        serde_json::Value::Bool(b) => ScalarValue::Boolean(*b),
+        serde_json::Value::String(s) => ScalarValue::Str(s.as_str().into()),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => ScalarValue::Int(i),
+            None => ScalarValue::F64(n.as_f64().unwrap_or(0.0)),
+        },
+        _ => ScalarValue::Null,
+    }
+}
+
+fn scalar_to_json(value: &ScalarValue) -> serde_json::Value {
+    match value {
+        ScalarValue::Boolean(b) => serde_json::Value::Bool(*b),
+        ScalarValue::Str(s) => serde_json::Value::String(s.to_string()),
+        ScalarValue::Int(i) => serde_json::Value::from(*i),
+        ScalarValue::Uint(u) => serde_json::Value::from(*u),
+        ScalarValue::F64(f) => serde_json::Value::from(*f),
+        _ => serde_json::Value::Null,
+    }
+}
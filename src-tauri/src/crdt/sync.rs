@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-// a full document snapshot sent over gossipsub for initial sync
-// when a new peer discovers us, we broadcast our documents so they can merge
+use crate::protocol::community::InviteCode;
+
+// a full document snapshot, sent directly to a member over the snapshot
+// protocol when it asks to sync or when our copy changes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentSnapshot {
     pub community_id: String,
@@ -12,8 +14,20 @@ pub struct DocumentSnapshot {
 // envelope for sync-related gossipsub messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SyncMessage {
-    // request all documents from peers (sent when a new peer joins)
-    RequestSync { peer_id: String },
-    // response containing a full document snapshot
+    // request the topic's community doc from peers (sent when a new peer
+    // joins). members only answer members, or a joiner presenting a signed
+    // invite from one
+    RequestSync {
+        peer_id: String,
+        // the community asked for, so requests for different communities
+        // never carry the same bytes. empty from older clients
+        #[serde(default)]
+        community_id: String,
+        #[serde(default)]
+        invite: Option<InviteCode>,
+    },
+    // a full document snapshot. older clients published these on the sync
+    // topic, now they only appear in the replay log for snapshots received
+    // over the snapshot protocol
     DocumentOffer(DocumentSnapshot),
 }
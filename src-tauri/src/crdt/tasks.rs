@@ -0,0 +1,290 @@
+use std::collections::HashSet;
+
+use automerge::{transaction::Transactable, AutoCommit, ObjId, ObjType, ReadDoc, ROOT};
+
+use super::document::{get_bool, get_i64, get_str, read_string_list};
+use crate::protocol::community::{TaskBoard, TaskCard, TaskCardPatch, TaskList};
+
+// the tasks section holds a lists map and a cards map. communities created
+// before task boards get it on first use, and two members doing that at once
+// each win a copy, so reads go through every concurrent copy and writes to
+// an existing list or card go to the copy holding it
+pub fn init_tasks(doc: &mut AutoCommit) -> Result<(ObjId, ObjId), automerge::AutomergeError> {
+    let tasks = doc.put_object(ROOT, "tasks", ObjType::Map)?;
+    let lists = doc.put_object(&tasks, "lists", ObjType::Map)?;
+    let cards = doc.put_object(&tasks, "cards", ObjType::Map)?;
+    Ok((lists, cards))
+}
+
+// (lists, cards) of every concurrent copy of the section
+fn task_sections(doc: &AutoCommit) -> Vec<(ObjId, ObjId)> {
+    doc.get_all(ROOT, "tasks")
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(_, tasks)| {
+            let (_, lists) = doc.get(&tasks, "lists").ok().flatten()?;
+            let (_, cards) = doc.get(&tasks, "cards").ok().flatten()?;
+            Some((lists, cards))
+        })
+        .collect()
+}
+
+// where new lists and cards go
+fn writable_section(doc: &mut AutoCommit) -> Result<(ObjId, ObjId), automerge::AutomergeError> {
+    if let Some((_, tasks)) = doc.get(ROOT, "tasks")? {
+        if let (Some((_, lists)), Some((_, cards))) =
+            (doc.get(&tasks, "lists")?, doc.get(&tasks, "cards")?)
+        {
+            return Ok((lists, cards));
+        }
+    }
+    init_tasks(doc)
+}
+
+fn find_list(doc: &AutoCommit, list_id: &str) -> Option<ObjId> {
+    task_sections(doc)
+        .into_iter()
+        .find_map(|(lists, _)| doc.get(&lists, list_id).ok().flatten().map(|(_, id)| id))
+}
+
+fn find_card(doc: &AutoCommit, card_id: &str) -> Option<ObjId> {
+    task_sections(doc)
+        .into_iter()
+        .find_map(|(_, cards)| doc.get(&cards, card_id).ok().flatten().map(|(_, id)| id))
+}
+
+pub fn get_task_board(doc: &AutoCommit, community_id: &str) -> TaskBoard {
+    let mut lists = Vec::new();
+    let mut cards = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (lists_obj, cards_obj) in task_sections(doc) {
+        for list_id in doc.keys(&lists_obj).collect::<Vec<_>>() {
+            let Some((_, list)) = doc.get(&lists_obj, &list_id).ok().flatten() else {
+                continue;
+            };
+            if !seen.insert(list_id.clone()) {
+                continue;
+            }
+            lists.push(TaskList {
+                id: list_id,
+                community_id: community_id.to_string(),
+                name: get_str(doc, &list, "name").unwrap_or_default(),
+                position: get_i64(doc, &list, "position").unwrap_or(0).max(0) as u32,
+                created_by: get_str(doc, &list, "created_by").unwrap_or_default(),
+                created_at: get_i64(doc, &list, "created_at").unwrap_or(0).max(0) as u64,
+            });
+        }
+        for card_id in doc.keys(&cards_obj).collect::<Vec<_>>() {
+            let Some((_, card)) = doc.get(&cards_obj, &card_id).ok().flatten() else {
+                continue;
+            };
+            if !seen.insert(card_id.clone()) {
+                continue;
+            }
+            let assignees = doc
+                .get(&card, "assignees")
+                .ok()
+                .flatten()
+                .map(|(_, list)| read_string_list(doc, &list))
+                .unwrap_or_default();
+            cards.push(TaskCard {
+                id: card_id,
+                community_id: community_id.to_string(),
+                list_id: get_str(doc, &card, "list_id").unwrap_or_default(),
+                title: get_str(doc, &card, "title").unwrap_or_default(),
+                description: get_str(doc, &card, "description").unwrap_or_default(),
+                position: get_i64(doc, &card, "position").unwrap_or(0).max(0) as u32,
+                assignees,
+                due_at: get_i64(doc, &card, "due_at").map(|t| t.max(0) as u64),
+                done: get_bool(doc, &card, "done").unwrap_or(false),
+                created_by: get_str(doc, &card, "created_by").unwrap_or_default(),
+                created_at: get_i64(doc, &card, "created_at").unwrap_or(0).max(0) as u64,
+                updated_at: get_i64(doc, &card, "updated_at").unwrap_or(0).max(0) as u64,
+            });
+        }
+    }
+
+    // cards of a deleted list are dropped with it, but a concurrent move can
+    // still point one at a list that is gone
+    let list_ids: HashSet<&str> = lists.iter().map(|l| l.id.as_str()).collect();
+    cards.retain(|c| list_ids.contains(c.list_id.as_str()));
+
+    lists.sort_by(|a, b| (a.position, a.created_at).cmp(&(b.position, b.created_at)));
+    cards.sort_by(|a, b| {
+        (&a.list_id, a.position, a.created_at).cmp(&(&b.list_id, b.position, b.created_at))
+    });
+    TaskBoard { lists, cards }
+}
+
+// appended after the existing lists
+pub fn add_task_list(
+    doc: &mut AutoCommit,
+    list: &TaskList,
+) -> Result<u32, automerge::AutomergeError> {
+    let position = get_task_board(doc, "").lists.len() as u32;
+    let (lists, _) = writable_section(doc)?;
+    let obj = doc.put_object(&lists, &list.id, ObjType::Map)?;
+    doc.put(&obj, "name", list.name.as_str())?;
+    doc.put(&obj, "position", position as i64)?;
+    doc.put(&obj, "created_by", list.created_by.as_str())?;
+    doc.put(&obj, "created_at", list.created_at as i64)?;
+    Ok(position)
+}
+
+pub fn update_task_list(
+    doc: &mut AutoCommit,
+    list_id: &str,
+    name: Option<&str>,
+    position: Option<u32>,
+) -> Result<(), automerge::AutomergeError> {
+    let list = find_list(doc, list_id)
+        .ok_or_else(|| automerge::AutomergeError::InvalidObjId("list not found".to_string()))?;
+    if let Some(name) = name {
+        doc.put(&list, "name", name)?;
+    }
+    if let Some(position) = position {
+        let mut order: Vec<String> = get_task_board(doc, "")
+            .lists
+            .into_iter()
+            .map(|l| l.id)
+            .filter(|id| id != list_id)
+            .collect();
+        order.insert((position as usize).min(order.len()), list_id.to_string());
+        for (i, id) in order.iter().enumerate() {
+            if let Some(obj) = find_list(doc, id) {
+                doc.put(&obj, "position", i as i64)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// removes the list's cards along with it
+pub fn delete_task_list(
+    doc: &mut AutoCommit,
+    list_id: &str,
+) -> Result<(), automerge::AutomergeError> {
+    let card_ids: Vec<String> = get_task_board(doc, "")
+        .cards
+        .into_iter()
+        .filter(|c| c.list_id == list_id)
+        .map(|c| c.id)
+        .collect();
+    for (lists, cards) in task_sections(doc) {
+        if doc.get(&lists, list_id)?.is_some() {
+            doc.delete(&lists, list_id)?;
+        }
+        for card_id in &card_ids {
+            if doc.get(&cards, card_id.as_str())?.is_some() {
+                doc.delete(&cards, card_id.as_str())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// appended to the bottom of its list
+pub fn add_task_card(
+    doc: &mut AutoCommit,
+    card: &TaskCard,
+) -> Result<u32, automerge::AutomergeError> {
+    let position = get_task_board(doc, "")
+        .cards
+        .iter()
+        .filter(|c| c.list_id == card.list_id)
+        .count() as u32;
+    let (_, cards) = writable_section(doc)?;
+    let obj = doc.put_object(&cards, &card.id, ObjType::Map)?;
+    doc.put(&obj, "list_id", card.list_id.as_str())?;
+    doc.put(&obj, "title", card.title.as_str())?;
+    doc.put(&obj, "description", card.description.as_str())?;
+    doc.put(&obj, "position", position as i64)?;
+    put_assignees(doc, &obj, &card.assignees)?;
+    if let Some(due_at) = card.due_at {
+        doc.put(&obj, "due_at", due_at as i64)?;
+    }
+    doc.put(&obj, "done", card.done)?;
+    doc.put(&obj, "created_by", card.created_by.as_str())?;
+    doc.put(&obj, "created_at", card.created_at as i64)?;
+    doc.put(&obj, "updated_at", card.updated_at as i64)?;
+    Ok(position)
+}
+
+pub fn update_task_card(
+    doc: &mut AutoCommit,
+    card_id: &str,
+    patch: &TaskCardPatch,
+    now: u64,
+) -> Result<(), automerge::AutomergeError> {
+    let card = find_card(doc, card_id)
+        .ok_or_else(|| automerge::AutomergeError::InvalidObjId("card not found".to_string()))?;
+
+    if let Some(ref title) = patch.title {
+        doc.put(&card, "title", title.as_str())?;
+    }
+    if let Some(ref description) = patch.description {
+        doc.put(&card, "description", description.as_str())?;
+    }
+    if let Some(ref assignees) = patch.assignees {
+        put_assignees(doc, &card, assignees)?;
+    }
+    if patch.clear_due {
+        doc.delete(&card, "due_at")?;
+    } else if let Some(due_at) = patch.due_at {
+        doc.put(&card, "due_at", due_at as i64)?;
+    }
+    if let Some(done) = patch.done {
+        doc.put(&card, "done", done)?;
+    }
+
+    // a move renumbers the destination list around the card
+    if patch.list_id.is_some() || patch.position.is_some() {
+        let current_list = get_str(doc, &card, "list_id").unwrap_or_default();
+        let list_id = patch.list_id.clone().unwrap_or(current_list);
+        let mut order: Vec<String> = get_task_board(doc, "")
+            .cards
+            .into_iter()
+            .filter(|c| c.list_id == list_id && c.id != card_id)
+            .map(|c| c.id)
+            .collect();
+        let position = patch
+            .position
+            .map_or(order.len(), |p| (p as usize).min(order.len()));
+        order.insert(position, card_id.to_string());
+
+        doc.put(&card, "list_id", list_id.as_str())?;
+        for (i, id) in order.iter().enumerate() {
+            if let Some(obj) = find_card(doc, id) {
+                doc.put(&obj, "position", i as i64)?;
+            }
+        }
+    }
+
+    doc.put(&card, "updated_at", now as i64)?;
+    Ok(())
+}
+
+pub fn delete_task_card(
+    doc: &mut AutoCommit,
+    card_id: &str,
+) -> Result<(), automerge::AutomergeError> {
+    for (_, cards) in task_sections(doc) {
+        if doc.get(&cards, card_id)?.is_some() {
+            doc.delete(&cards, card_id)?;
+        }
+    }
+    Ok(())
+}
+
+fn put_assignees(
+    doc: &mut AutoCommit,
+    card: &ObjId,
+    assignees: &[String],
+) -> Result<(), automerge::AutomergeError> {
+    let list = doc.put_object(card, "assignees", ObjType::List)?;
+    for (i, peer_id) in assignees.iter().enumerate() {
+        doc.insert(&list, i, peer_id.as_str())?;
+    }
+    Ok(())
+}
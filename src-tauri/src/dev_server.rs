@@ -7,7 +7,7 @@
 //
 // NEVER enable this in production builds.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -19,15 +19,22 @@ use axum::Router;
 use serde::Deserialize;
 use tokio::sync::Mutex;
 
-use crate::crdt::sync::{DocumentSnapshot, SyncMessage};
+use crate::commands::authz;
+use crate::crdt::sync::SyncMessage;
 use crate::crdt::CrdtEngine;
 use crate::node::gossip;
+use crate::node::netem::NetemConfig;
+use crate::node::replay::ReplayReport;
 use crate::node::NodeCommand;
-use crate::protocol::community::{ChannelKind, ChannelMeta, CommunityMeta, Member};
+use crate::protocol::community::{
+    ChannelKind, ChannelMeta, CommunityMeta, Member, MembershipAction, INVITE_PROOF_TTL_MS,
+};
 use crate::protocol::identity::{DirectoryEntry, DuskIdentity};
 use crate::protocol::messages::{
-    ChatMessage, DMConversationMeta, DirectMessage, GossipMessage, PeerStatus, VoiceParticipant,
+    validate_attachments, Attachment, ChatMessage, DMConversationMeta, DirectMessage, Embed,
+    GossipMessage, Hlc, PeerStatus, VoiceParticipant,
 };
+use crate::snapshot::StateSnapshot;
 use crate::storage::{DiskStorage, UserSettings};
 
 // mirrors the fields from AppState but owned so it can be moved into axum
@@ -38,10 +45,17 @@ pub struct DevState {
     pub storage: Arc<DiskStorage>,
     pub node_handle: Arc<Mutex<Option<crate::node::NodeHandle>>>,
     pub voice_channels: Arc<Mutex<HashMap<String, Vec<VoiceParticipant>>>>,
-    pub pending_join_role_guard: Arc<Mutex<HashSet<String>>>,
+    pub hlc_clock: Arc<Mutex<crate::node::clock::HybridClock>>,
+    pub cover_traffic: Arc<crate::node::cover::CoverTraffic>,
     pub app_handle: tauri::AppHandle,
 }
 
+impl DevState {
+    async fn snapshot(&self) -> StateSnapshot {
+        crate::snapshot::capture(&self.identity, &self.crdt_engine, &self.node_handle).await
+    }
+}
+
 // unified error response so all handlers return consistent json
 struct ApiError(StatusCode, String);
 
@@ -113,6 +127,14 @@ pub async fn start(state: DevState) {
         .route("/api/node/start", post(start_node))
         .route("/api/node/stop", post(stop_node))
         .route("/api/node/status", get(get_node_status))
+        // consistent view of identity, doc heads and node status
+        .route("/api/state", get(get_state))
+        // seed deterministic communities and dms for ui and integration tests
+        .route("/api/test/fixtures", post(seed_fixtures))
+        // simulated latency and loss on gossip, an empty body turns it off
+        .route("/api/test/netem", put(set_netem))
+        // re-feed the recorded event log into a scratch engine
+        .route("/api/test/replay", post(replay_events))
         .with_state(state);
 
     let addr = format!("127.0.0.1:{}", port);
@@ -136,41 +158,21 @@ fn now_ms() -> u64 {
         .as_millis() as u64
 }
 
-// publish the latest document snapshot for a community to connected peers
+// hand the latest document snapshot for a community to its connected members
 async fn broadcast_sync(state: &DevState, community_id: &str) {
-    let doc_bytes = {
-        let mut engine = state.crdt_engine.lock().await;
-        engine.get_doc_bytes(community_id)
-    };
-
-    let Some(doc_bytes) = doc_bytes else {
-        return;
-    };
-
-    let message = SyncMessage::DocumentOffer(DocumentSnapshot {
-        community_id: community_id.to_string(),
-        doc_bytes,
-    });
-
-    let data = match serde_json::to_vec(&message) {
-        Ok(data) => data,
-        Err(_) => return,
-    };
-
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
         let _ = handle
             .command_tx
-            .send(NodeCommand::SendMessage {
-                topic: gossip::topic_for_sync(),
-                data,
+            .send(NodeCommand::PushSnapshot {
+                community_id: community_id.to_string(),
             })
             .await;
     }
 }
 
-// request snapshots from connected peers
-async fn request_sync(state: &DevState) {
+// request a community's snapshot from connected members
+async fn request_sync(state: &DevState, community_id: &str) {
     let peer_id = {
         let identity = state.identity.lock().await;
         let Some(id) = identity.as_ref() else {
@@ -179,7 +181,13 @@ async fn request_sync(state: &DevState) {
         id.peer_id.to_string()
     };
 
-    let message = SyncMessage::RequestSync { peer_id };
+    let invite = state.crdt_engine.lock().await.join_invite(community_id);
+
+    let message = SyncMessage::RequestSync {
+        peer_id,
+        community_id: community_id.to_string(),
+        invite,
+    };
     let data = match serde_json::to_vec(&message) {
         Ok(data) => data,
         Err(_) => return,
@@ -190,7 +198,7 @@ async fn request_sync(state: &DevState) {
         let _ = handle
             .command_tx
             .send(NodeCommand::SendMessage {
-                topic: gossip::topic_for_sync(),
+                topic: gossip::topic_for_community_sync(community_id),
                 data,
             })
             .await;
@@ -297,46 +305,39 @@ async fn get_directory(State(state): State<DevState>) -> ApiResult<Vec<Directory
 #[derive(Deserialize)]
 struct SearchQuery {
     q: String,
+    #[serde(default)]
+    include_placeholders: bool,
+    #[serde(default = "default_search_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+}
+
+fn default_search_limit() -> usize {
+    50
 }
 
 async fn search_directory(
     State(state): State<DevState>,
     Query(params): Query<SearchQuery>,
 ) -> ApiResult<Vec<DirectoryEntry>> {
-    let entries = state
+    let results = state
         .storage
-        .load_directory()
+        .search_directory_entries(
+            params.q.trim(),
+            params.include_placeholders,
+            params.limit.min(200),
+            params.offset,
+        )
         .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
-
-    let query_lower = params.q.to_lowercase();
-    let mut results: Vec<DirectoryEntry> = entries
-        .into_values()
-        .filter(|entry| {
-            entry.display_name.to_lowercase().contains(&query_lower)
-                || entry.peer_id.to_lowercase().contains(&query_lower)
-        })
-        .collect();
-
-    results.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
     Ok(Json(results))
 }
 
 async fn get_friends(State(state): State<DevState>) -> ApiResult<Vec<DirectoryEntry>> {
-    let entries = state
+    let friends = state
         .storage
-        .load_directory()
+        .load_friends()
         .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
-
-    let mut friends: Vec<DirectoryEntry> = entries
-        .into_values()
-        .filter(|entry| entry.is_friend)
-        .collect();
-
-    friends.sort_by(|a, b| {
-        a.display_name
-            .to_lowercase()
-            .cmp(&b.display_name.to_lowercase())
-    });
     Ok(Json(friends))
 }
 
@@ -367,7 +368,11 @@ async fn remove_friend(
 async fn get_communities(State(state): State<DevState>) -> ApiResult<Vec<CommunityMeta>> {
     let engine = state.crdt_engine.lock().await;
     let mut communities = Vec::new();
-    for id in engine.community_ids() {
+    for id in engine
+        .community_ids()
+        .into_iter()
+        .chain(engine.archived_community_ids())
+    {
         if let Ok(meta) = engine.get_community_meta(&id) {
             communities.push(meta);
         }
@@ -403,12 +408,27 @@ async fn create_community(
 
     let peer_id_str = id.peer_id.to_string();
     let display_name = id.display_name.clone();
+    let founder_join = crate::verification::sign_membership_event(
+        &id.keypair,
+        &community_id,
+        MembershipAction::Join,
+        &peer_id_str,
+        &["owner".to_string()],
+        &display_name,
+        now,
+    );
+    let keypair = id.keypair.clone();
     drop(identity);
 
     let mut engine = state.crdt_engine.lock().await;
     engine
         .create_community(&community_id, &body.name, &body.description, &peer_id_str, &display_name)
         .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    engine
+        .record_membership_event(&community_id, &founder_join)
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    // joiners check the first snapshot they get against an owner checkpoint
+    engine.create_checkpoint(&community_id, &keypair);
 
     let meta = engine
         .get_community_meta(&community_id)
@@ -426,6 +446,18 @@ async fn create_community(
                 topic: presence_topic,
             })
             .await;
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::Subscribe {
+                topic: gossip::topic_for_community_directory(&community_id),
+            })
+            .await;
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::Subscribe {
+                topic: gossip::topic_for_community_sync(&community_id),
+            })
+            .await;
 
         let engine = state.crdt_engine.lock().await;
         if let Ok(channels) = engine.get_channels(&community_id) {
@@ -468,39 +500,37 @@ async fn join_community(
     let invite = crate::protocol::community::InviteCode::decode(&body.invite_code)
         .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e))?;
 
-    let local_peer_id = {
+    // a signed join resets us to a plain member whatever an old local doc says
+    let join_event = {
         let identity = state.identity.lock().await;
         let id = identity
             .as_ref()
             .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "no identity loaded".into()))?;
-        id.peer_id.to_string()
+        crate::verification::sign_membership_event(
+            &id.keypair,
+            &invite.community_id,
+            MembershipAction::Join,
+            &id.peer_id.to_string(),
+            &["member".to_string()],
+            &id.display_name,
+            now_ms(),
+        )
     };
 
     let mut engine = state.crdt_engine.lock().await;
-    let had_existing_doc = engine.has_community(&invite.community_id);
-    if !had_existing_doc {
+    engine
+        .unarchive_community(&invite.community_id)
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if !engine.has_community(&invite.community_id) {
         engine
             .create_placeholder_community(&invite.community_id, &invite.community_name, "")
             .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        engine.hold_join_invite(&invite);
     }
 
-    // joining via invite must never keep elevated local roles from stale local docs
-    if had_existing_doc {
-        if let Ok(members) = engine.get_members(&invite.community_id) {
-            let local_has_elevated_role = members.iter().any(|member| {
-                member.peer_id == local_peer_id
-                    && member
-                        .roles
-                        .iter()
-                        .any(|role| role == "owner" || role == "admin")
-            });
-
-            if local_has_elevated_role {
-                let roles = vec!["member".to_string()];
-                let _ = engine.set_member_role(&invite.community_id, &local_peer_id, &roles);
-            }
-        }
-    }
+    engine
+        .record_membership_event(&invite.community_id, &join_event)
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
     let meta = engine
         .get_community_meta(&invite.community_id)
@@ -512,12 +542,6 @@ async fn join_community(
         .unwrap_or_default();
     drop(engine);
 
-    // mark this community for one-time role hardening on first sync merge
-    {
-        let mut guard = state.pending_join_role_guard.lock().await;
-        guard.insert(invite.community_id.clone());
-    }
-
     // subscribe and discover via rendezvous
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
@@ -528,6 +552,18 @@ async fn join_community(
                 topic: presence_topic,
             })
             .await;
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::Subscribe {
+                topic: gossip::topic_for_community_directory(&invite.community_id),
+            })
+            .await;
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::Subscribe {
+                topic: gossip::topic_for_community_sync(&invite.community_id),
+            })
+            .await;
 
         for channel in &channels {
             let msg_topic = gossip::topic_for_messages(&invite.community_id, &channel.id);
@@ -558,21 +594,39 @@ async fn join_community(
             .await;
     }
 
-    request_sync(&state).await;
+    request_sync(&state, &invite.community_id).await;
 
     Ok(Json(meta))
 }
 
+#[derive(Deserialize)]
+struct LeaveQuery {
+    #[serde(default)]
+    keep_history: bool,
+}
+
+// same semantics as the leave_community command
 async fn leave_community(
     State(state): State<DevState>,
     Path(community_id): Path<String>,
+    Query(params): Query<LeaveQuery>,
 ) -> ApiResult<serde_json::Value> {
-    let local_peer_id = {
+    let (local_peer_id, leave_event) = {
         let identity = state.identity.lock().await;
         let id = identity
             .as_ref()
             .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "no identity loaded".into()))?;
-        id.peer_id.to_string()
+        let local_peer_id = id.peer_id.to_string();
+        let leave_event = crate::verification::sign_membership_event(
+            &id.keypair,
+            &community_id,
+            MembershipAction::Leave,
+            &local_peer_id,
+            &[],
+            "",
+            now_ms(),
+        );
+        (local_peer_id, leave_event)
     };
 
     let mut removed_self = false;
@@ -587,6 +641,11 @@ async fn leave_community(
                 }
             }
         }
+        if removed_self {
+            engine
+                .record_membership_event(&community_id, &leave_event)
+                .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        }
 
         channels
     };
@@ -620,6 +679,18 @@ async fn leave_community(
                 topic: presence_topic,
             })
             .await;
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::Unsubscribe {
+                topic: gossip::topic_for_community_directory(&community_id),
+            })
+            .await;
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::Unsubscribe {
+                topic: gossip::topic_for_community_sync(&community_id),
+            })
+            .await;
 
         let namespace = format!("dusk/community/{}", community_id);
         let _ = handle
@@ -627,16 +698,17 @@ async fn leave_community(
             .send(NodeCommand::UnregisterRendezvous { namespace })
             .await;
     }
+    drop(node_handle);
 
     let mut engine = state.crdt_engine.lock().await;
-    engine
-        .remove_community(&community_id)
-        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let result = if params.keep_history {
+        engine.archive_community(&community_id)
+    } else {
+        engine.remove_community(&community_id)
+    };
+    result.map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     drop(engine);
 
-    let mut guard = state.pending_join_role_guard.lock().await;
-    guard.remove(&community_id);
-
     Ok(Json(serde_json::json!({ "ok": true })))
 }
 
@@ -650,10 +722,27 @@ async fn generate_invite(
         .map_err(|e| ApiError(StatusCode::NOT_FOUND, e))?;
     drop(engine);
 
-    let invite = crate::protocol::community::InviteCode {
+    let identity = state.identity.lock().await;
+    let id = identity
+        .as_ref()
+        .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "no identity loaded".into()))?;
+    let mut invite = crate::protocol::community::InviteCode {
         community_id: meta.id,
         community_name: meta.name,
+        owner_id: meta.created_by,
+        inviter: id.peer_id.to_string(),
+        inviter_key: hex::encode(id.keypair.public().encode_protobuf()),
+        expires_at: now_ms() + INVITE_PROOF_TTL_MS,
+        signature: String::new(),
     };
+    invite.signature = crate::verification::sign_invite(&id.keypair, &invite);
+
+    // as the owner, make sure the doc the joiner will get carries a checkpoint
+    state
+        .crdt_engine
+        .lock()
+        .await
+        .create_checkpoint(&community_id, &id.keypair);
 
     Ok(Json(serde_json::json!({ "invite_code": invite.encode() })))
 }
@@ -662,6 +751,9 @@ async fn get_members(
     State(state): State<DevState>,
     Path(community_id): Path<String>,
 ) -> ApiResult<Vec<Member>> {
+    // identity before engine, the same order as crate::snapshot, so the
+    // overlay below matches the member list it is applied to
+    let identity = state.identity.lock().await;
     let engine = state.crdt_engine.lock().await;
     let mut members = engine
         .get_members(&community_id)
@@ -669,7 +761,6 @@ async fn get_members(
     drop(engine);
 
     // overlay local user's current name
-    let identity = state.identity.lock().await;
     if let Some(ref id) = *identity {
         let local_peer = id.peer_id.to_string();
         if let Some(member) = members.iter_mut().find(|m| m.peer_id == local_peer) {
@@ -712,6 +803,19 @@ async fn create_channel(
 ) -> ApiResult<ChannelMeta> {
     use sha2::Digest;
 
+    let identity = state.identity.lock().await;
+    let id = identity
+        .as_ref()
+        .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "no identity loaded".into()))?;
+    let peer_id_str = id.peer_id.to_string();
+    drop(identity);
+
+    let engine = state.crdt_engine.lock().await;
+    authz::resolve_with(&engine, &community_id, &peer_id_str)?
+        .require_admin()
+        .map_err(|e| ApiError(StatusCode::FORBIDDEN, e))?;
+    drop(engine);
+
     let mut hasher = sha2::Sha256::new();
     hasher.update(community_id.as_bytes());
     hasher.update(body.name.as_bytes());
@@ -721,6 +825,8 @@ async fn create_channel(
 
     let channel_kind = match body.kind.as_deref() {
         Some("voice") | Some("Voice") => ChannelKind::Voice,
+        Some("announcement") | Some("Announcement") => ChannelKind::Announcement,
+        Some("stage") | Some("Stage") => ChannelKind::Stage,
         _ => ChannelKind::Text,
     };
 
@@ -732,6 +838,7 @@ async fn create_channel(
         kind: channel_kind,
         position: 0,
         category_id: body.category_id,
+        max_participants: None,
     };
 
     let mut engine = state.crdt_engine.lock().await;
@@ -792,6 +899,10 @@ async fn get_messages(
 #[derive(Deserialize)]
 struct SendMessageBody {
     content: String,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    #[serde(default)]
+    embeds: Vec<Embed>,
 }
 
 async fn send_message(
@@ -803,8 +914,11 @@ async fn send_message(
     let id = identity
         .as_ref()
         .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "no identity loaded".into()))?;
+    validate_attachments(&body.attachments, &body.embeds)
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e))?;
 
     let now = now_ms();
+    let hlc = state.hlc_clock.lock().await.now();
     let msg = ChatMessage {
         id: format!("msg_{}_{}", id.peer_id, now),
         channel_id: channel_id.clone(),
@@ -813,6 +927,12 @@ async fn send_message(
         content: body.content,
         timestamp: now,
         edited: false,
+        hlc,
+        forwarded_from: None,
+        attachments: body.attachments,
+        embeds: body.embeds,
+        reactions: Vec::new(),
+        reply_to: None,
     };
     drop(identity);
 
@@ -860,12 +980,9 @@ async fn delete_message(
             )
         })?;
 
-    if message.author_id != peer_id_str {
-        return Err(ApiError(
-            StatusCode::FORBIDDEN,
-            "not authorized to delete this message".into(),
-        ));
-    }
+    let capability = authz::resolve_with(&engine, &community_id, &peer_id_str)?
+        .authorize_delete(&message.author_id, &message.channel_id)
+        .map_err(|e| ApiError(StatusCode::FORBIDDEN, e))?;
 
     engine
         .delete_message(&community_id, &message_id)
@@ -881,6 +998,7 @@ async fn delete_message(
                 let topic = gossip::topic_for_messages(&community_id, &channel.id);
                 let deletion = GossipMessage::DeleteMessage {
                     message_id: message_id.clone(),
+                    capability: capability.clone(),
                 };
                 if let Ok(data) = serde_json::to_vec(&deletion) {
                     let _ = handle
@@ -900,7 +1018,7 @@ async fn delete_message(
 async fn get_dm_conversations(State(state): State<DevState>) -> ApiResult<Vec<DMConversationMeta>> {
     let conversations = state
         .storage
-        .load_all_dm_conversations()
+        .load_all_dm_conversations(false)
         .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
 
     Ok(Json(
@@ -956,6 +1074,10 @@ async fn send_dm(
         from_display_name: display_name,
         content: body.content.clone(),
         timestamp: now,
+        reply_to: None,
+        attachments: Vec::new(),
+        edited: false,
+        reactions: Vec::new(),
     };
 
     let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
@@ -966,17 +1088,17 @@ async fn send_dm(
         .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
 
     // update conversation metadata
-    let peer_display_name = state
-        .storage
-        .load_dm_conversation(&conversation_id)
-        .ok()
-        .map(|m| m.display_name)
+    let existing_meta = state.storage.load_dm_conversation(&conversation_id).ok();
+    let peer_display_name = existing_meta
+        .as_ref()
+        .map(|m| m.display_name.clone())
         .unwrap_or_else(|| {
             state
                 .storage
-                .load_directory()
+                .load_directory_entry(&peer_id)
                 .ok()
-                .and_then(|d| d.get(&peer_id).map(|e| e.display_name.clone()))
+                .flatten()
+                .map(|e| e.display_name)
                 .unwrap_or_else(|| peer_id.clone())
         });
 
@@ -986,6 +1108,8 @@ async fn send_dm(
         last_message: Some(body.content),
         last_message_time: Some(now),
         unread_count: 0,
+        pinned: existing_meta.map(|m| m.pinned).unwrap_or(false),
+        archived: false,
     };
 
     let _ = state.storage.save_dm_conversation(&conversation_id, &meta);
@@ -1033,11 +1157,12 @@ async fn delete_dm_conversation(
     // unsubscribe from topic
     let node_handle = state.node_handle.lock().await;
     if let Some(ref handle) = *node_handle {
-        let topic = gossip::topic_for_dm(&local_peer_id, &peer_id);
-        let _ = handle
-            .command_tx
-            .send(NodeCommand::Unsubscribe { topic })
-            .await;
+        for topic in gossip::topics_for_dm(&local_peer_id, &peer_id) {
+            let _ = handle
+                .command_tx
+                .send(NodeCommand::Unsubscribe { topic })
+                .await;
+        }
     }
 
     state
@@ -1076,56 +1201,41 @@ async fn start_node(State(state): State<DevState>) -> ApiResult<serde_json::Valu
         .ok()
         .and_then(|s| s.custom_relay_addr);
 
+    let shared = crate::node::SharedState {
+        crdt_engine: state.crdt_engine.clone(),
+        storage: state.storage.clone(),
+        voice_channels: state.voice_channels.clone(),
+        hlc_clock: state.hlc_clock.clone(),
+        cover_traffic: state.cover_traffic.clone(),
+        latency: state.latency.clone(),
+    };
     let handle = crate::node::start(
         id.keypair.clone(),
-        state.crdt_engine.clone(),
-        state.storage.clone(),
+        shared,
         state.app_handle.clone(),
-        state.voice_channels.clone(),
-        state.pending_join_role_guard.clone(),
         custom_relay,
     )
     .await
     .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
-    // build and broadcast profile announcement
-    let mut announcement = crate::protocol::messages::ProfileAnnouncement {
-        peer_id: id.peer_id.to_string(),
-        display_name: id.display_name.clone(),
-        bio: id.bio.clone(),
-        public_key: hex::encode(id.keypair.public().encode_protobuf()),
-        timestamp: now_ms() as u64,
-        verification_proof: id.verification_proof.clone(),
-        signature: String::new(),
-    };
-    announcement.signature = crate::verification::sign_announcement(&id.keypair, &announcement);
     drop(identity);
 
     // subscribe to global topics
-    let sync_topic = gossip::topic_for_sync();
     let directory_topic = gossip::topic_for_directory();
-    let _ = handle
-        .command_tx
-        .send(NodeCommand::Subscribe { topic: sync_topic })
-        .await;
     let _ = handle
         .command_tx
         .send(NodeCommand::Subscribe {
-            topic: directory_topic.clone(),
+            topic: directory_topic,
         })
         .await;
 
     // announce profile
-    let announce_msg = GossipMessage::ProfileAnnounce(announcement);
-    if let Ok(data) = serde_json::to_vec(&announce_msg) {
-        let _ = handle
-            .command_tx
-            .send(NodeCommand::SendMessage {
-                topic: directory_topic,
-                data,
-            })
-            .await;
-    }
+    let _ = handle
+        .command_tx
+        .send(NodeCommand::AnnounceProfile {
+            trigger: crate::node::announce::AnnounceTrigger::Startup,
+        })
+        .await;
 
     // subscribe to all known community topics
     let engine = state.crdt_engine.lock().await;
@@ -1161,6 +1271,18 @@ async fn start_node(State(state): State<DevState>) -> ApiResult<serde_json::Valu
                 topic: presence_topic,
             })
             .await;
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::Subscribe {
+                topic: gossip::topic_for_community_directory(community_id),
+            })
+            .await;
+        let _ = handle
+            .command_tx
+            .send(NodeCommand::Subscribe {
+                topic: gossip::topic_for_community_sync(community_id),
+            })
+            .await;
 
         let namespace = format!("dusk/community/{}", community_id);
         let _ = handle
@@ -1196,3 +1318,314 @@ async fn get_node_status(State(state): State<DevState>) -> ApiResult<serde_json:
     let running = node_handle.is_some();
     Ok(Json(serde_json::json!({ "running": running })))
 }
+
+async fn get_state(State(state): State<DevState>) -> ApiResult<StateSnapshot> {
+    Ok(Json(state.snapshot().await))
+}
+
+// -- test fixtures --
+
+// fixture timestamps count up from here so repeated seeds give identical state
+const FIXTURE_EPOCH_MS: u64 = 1_704_067_200_000;
+// keeps a typo in a test from filling the disk
+const MAX_FIXTURE_ITEMS: usize = 1000;
+
+#[derive(Deserialize)]
+struct FixturesBody {
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    communities: Vec<CommunityFixture>,
+    #[serde(default)]
+    dm_conversations: Vec<DmFixture>,
+}
+
+#[derive(Deserialize)]
+struct CommunityFixture {
+    name: String,
+    #[serde(default)]
+    description: String,
+    // text channels created next to the default general channel
+    #[serde(default)]
+    channels: Vec<String>,
+    // generated members besides the local identity, who owns the community
+    #[serde(default)]
+    members: usize,
+    #[serde(default)]
+    messages_per_channel: usize,
+}
+
+#[derive(Deserialize)]
+struct DmFixture {
+    display_name: String,
+    #[serde(default)]
+    messages: usize,
+}
+
+// hex digest of the seed and a label, used for every fixture id and key
+fn fixture_hash(seed: u64, label: &str) -> String {
+    use sha2::Digest;
+
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(seed.to_le_bytes());
+    hasher.update(label.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn fixture_keypair(seed: u64, label: &str) -> Result<libp2p::identity::Keypair, ApiError> {
+    let mut secret = [0u8; 32];
+    hex::decode_to_slice(fixture_hash(seed, label), &mut secret)
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+    libp2p::identity::Keypair::ed25519_from_bytes(secret)
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))
+}
+
+// seed communities, members, message history and dm conversations in one
+// call. the same seed always produces the same ids, keys and timestamps and
+// seeding again replaces the earlier fixtures. nothing is published to the
+// network, the data only exists locally until a peer syncs it
+async fn seed_fixtures(
+    State(state): State<DevState>,
+    Json(body): Json<FixturesBody>,
+) -> ApiResult<serde_json::Value> {
+    let identity = state.identity.lock().await;
+    let id = identity
+        .as_ref()
+        .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "no identity loaded".into()))?;
+    let local_keypair = id.keypair.clone();
+    let local_peer_id = id.peer_id.to_string();
+    let local_display_name = id.display_name.clone();
+    drop(identity);
+
+    let seed = body.seed;
+    let mut clock = FIXTURE_EPOCH_MS;
+    let mut tick = || {
+        clock += 60_000;
+        clock
+    };
+
+    let mut communities = Vec::new();
+    let mut engine = state.crdt_engine.lock().await;
+    for (ci, fixture) in body.communities.iter().enumerate() {
+        let community_id = format!(
+            "com_{}",
+            &fixture_hash(seed, &format!("community/{}", ci))[..16]
+        );
+        engine
+            .create_community(
+                &community_id,
+                &fixture.name,
+                &fixture.description,
+                &local_peer_id,
+                &local_display_name,
+            )
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        let founder_join = crate::verification::sign_membership_event(
+            &local_keypair,
+            &community_id,
+            MembershipAction::Join,
+            &local_peer_id,
+            &["owner".to_string()],
+            &local_display_name,
+            tick(),
+        );
+        engine
+            .record_membership_event(&community_id, &founder_join)
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+        // authors cycle through the owner and every generated member
+        let mut authors = vec![(local_peer_id.clone(), local_display_name.clone())];
+        for mi in 0..fixture.members.min(MAX_FIXTURE_ITEMS) {
+            let keypair = fixture_keypair(seed, &format!("community/{}/member/{}", ci, mi))?;
+            let peer_id = keypair.public().to_peer_id().to_string();
+            let display_name = format!("member {}", mi + 1);
+            let join = crate::verification::sign_membership_event(
+                &keypair,
+                &community_id,
+                MembershipAction::Join,
+                &peer_id,
+                &["member".to_string()],
+                &display_name,
+                tick(),
+            );
+            engine
+                .add_member(&community_id, &peer_id, &display_name, &["member"])
+                .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            engine
+                .record_membership_event(&community_id, &join)
+                .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            authors.push((peer_id, display_name));
+        }
+
+        for (chi, name) in fixture.channels.iter().enumerate() {
+            let channel = ChannelMeta {
+                id: format!(
+                    "ch_{}",
+                    &fixture_hash(seed, &format!("community/{}/channel/{}", ci, chi))[..12]
+                ),
+                community_id: community_id.clone(),
+                name: name.clone(),
+                topic: String::new(),
+                kind: ChannelKind::Text,
+                position: chi as u32 + 1,
+                category_id: None,
+                max_participants: None,
+            };
+            engine
+                .create_channel(&community_id, &channel)
+                .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        }
+
+        let channels = engine
+            .get_channels(&community_id)
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        for channel in channels
+            .iter()
+            .filter(|ch| matches!(ch.kind, ChannelKind::Text))
+        {
+            for n in 0..fixture.messages_per_channel.min(MAX_FIXTURE_ITEMS) {
+                let (author_id, author_name) = &authors[n % authors.len()];
+                let timestamp = tick();
+                let msg = ChatMessage {
+                    id: format!("msg_{}_{}", author_id, timestamp),
+                    channel_id: channel.id.clone(),
+                    author_id: author_id.clone(),
+                    author_name: author_name.clone(),
+                    content: format!("{} message {}", channel.name, n + 1),
+                    timestamp,
+                    edited: false,
+                    hlc: Hlc {
+                        wall: timestamp,
+                        counter: 0,
+                    },
+                    forwarded_from: None,
+                    attachments: Vec::new(),
+                    embeds: Vec::new(),
+                    reactions: Vec::new(),
+                    reply_to: None,
+                };
+                engine
+                    .append_message(&community_id, &msg)
+                    .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            }
+        }
+
+        let meta = engine
+            .get_community_meta(&community_id)
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        let _ = state.storage.save_community_meta(&meta);
+        communities.push(meta);
+    }
+    drop(engine);
+
+    let mut dm_peers = Vec::new();
+    for (di, fixture) in body.dm_conversations.iter().enumerate() {
+        let keypair = fixture_keypair(seed, &format!("dm/{}", di))?;
+        let peer_id = keypair.public().to_peer_id().to_string();
+        let conversation_id = gossip::dm_conversation_id(&local_peer_id, &peer_id);
+        state
+            .storage
+            .remove_dm_conversation(&conversation_id)
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+
+        // alternate between the peer and us, the peer speaks first
+        let mut last = None;
+        for n in 0..fixture.messages.min(MAX_FIXTURE_ITEMS) {
+            let (from_peer, to_peer, from_display_name) = if n % 2 == 0 {
+                (&peer_id, &local_peer_id, &fixture.display_name)
+            } else {
+                (&local_peer_id, &peer_id, &local_display_name)
+            };
+            let timestamp = tick();
+            let msg = DirectMessage {
+                id: format!("dm_{}_{}", from_peer, timestamp),
+                from_peer: from_peer.clone(),
+                to_peer: to_peer.clone(),
+                from_display_name: from_display_name.clone(),
+                content: format!("dm message {}", n + 1),
+                timestamp,
+                reply_to: None,
+                attachments: Vec::new(),
+                edited: false,
+                reactions: Vec::new(),
+            };
+            state
+                .storage
+                .append_dm_message(&conversation_id, &msg)
+                .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+            last = Some(msg);
+        }
+
+        let meta = DMConversationMeta {
+            peer_id: peer_id.clone(),
+            display_name: fixture.display_name.clone(),
+            last_message: last.as_ref().map(|m| m.content.clone()),
+            last_message_time: last.as_ref().map(|m| m.timestamp),
+            unread_count: 0,
+            pinned: false,
+            archived: false,
+        };
+        state
+            .storage
+            .save_dm_conversation(&conversation_id, &meta)
+            .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+        dm_peers.push(peer_id);
+    }
+
+    Ok(Json(serde_json::json!({
+        "communities": communities,
+        "dm_peers": dm_peers,
+    })))
+}
+
+// -- simulated network --
+
+async fn set_netem(
+    State(state): State<DevState>,
+    Json(config): Json<NetemConfig>,
+) -> ApiResult<NetemConfig> {
+    let node_handle = state.node_handle.lock().await;
+    let handle = node_handle
+        .as_ref()
+        .ok_or_else(|| ApiError(StatusCode::CONFLICT, "node not running".into()))?;
+    handle
+        .command_tx
+        .send(NodeCommand::SetNetem { config })
+        .await
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+    Ok(Json(config))
+}
+
+// -- event replay --
+
+#[derive(Deserialize)]
+struct ReplayBody {
+    // unix millis, events recorded before this are left out
+    #[serde(default)]
+    from_ts: u64,
+}
+
+// replays against empty docs in a throwaway data dir, the real engine and
+// storage are only read from
+async fn replay_events(
+    State(state): State<DevState>,
+    Json(body): Json<ReplayBody>,
+) -> ApiResult<ReplayReport> {
+    let storage = state.storage.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || -> Result<ReplayReport, String> {
+        let events = storage
+            .load_events_since(body.from_ts)
+            .map_err(|e| format!("failed to load events: {}", e))?;
+        let scratch_dir = std::env::temp_dir().join(format!("dusk-replay-{}", now_ms()));
+        let scratch = DiskStorage::open_at(scratch_dir.clone())
+            .map_err(|e| format!("failed to open scratch storage: {}", e))?;
+        let mut engine = CrdtEngine::new(Arc::new(scratch));
+        let report = crate::node::replay::replay(&events, &mut engine);
+        drop(engine);
+        let _ = std::fs::remove_dir_all(&scratch_dir);
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("replay task failed: {}", e))??;
+    Ok(Json(report))
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+// how long a result is replayed for its key. covers a frontend reload and
+// retry, not a user repeating the action on purpose
+const IDEMPOTENCY_TTL_SECS: u64 = 5 * 60;
+
+// results of mutating commands by idempotency key, so a command retried
+// after a frontend reload mid-flight returns the original result instead
+// of sending the message or creating the channel twice
+pub struct IdempotencyCache {
+    slots: std::sync::Mutex<HashMap<String, Slot>>,
+}
+
+struct Slot {
+    created_at: Instant,
+    result: Arc<Mutex<Option<serde_json::Value>>>,
+}
+
+// exclusive hold on one key while its command runs, a retry with the same
+// key waits for it. dropping the claim without completing it, e.g. when
+// the command fails, lets the next retry run the command again
+pub struct Claim {
+    guard: Option<OwnedMutexGuard<Option<serde_json::Value>>>,
+}
+
+impl IdempotencyCache {
+    pub fn new() -> Self {
+        Self {
+            slots: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    // keys are scoped per command. without a key nothing is deduplicated
+    pub async fn claim(&self, command: &str, key: Option<String>) -> Claim {
+        let Some(key) = key else {
+            return Claim { guard: None };
+        };
+        let result = {
+            let mut slots = self.slots.lock().unwrap();
+            let ttl = Duration::from_secs(IDEMPOTENCY_TTL_SECS);
+            slots.retain(|_, slot| slot.created_at.elapsed() < ttl);
+            slots
+                .entry(format!("{}:{}", command, key))
+                .or_insert_with(|| Slot {
+                    created_at: Instant::now(),
+                    result: Arc::new(Mutex::new(None)),
+                })
+                .result
+                .clone()
+        };
+        Claim {
+            guard: Some(result.lock_owned().await),
+        }
+    }
+
+    // results from before an identity reset must not replay for the next one
+    pub fn clear(&self) {
+        self.slots.lock().unwrap().clear();
+    }
+}
+
+impl Claim {
+    // the result an earlier run with this key completed with
+    pub fn replayed<T: DeserializeOwned>(&self) -> Result<Option<T>, String> {
+        match self.guard.as_deref() {
+            Some(Some(value)) => serde_json::from_value(value.clone())
+                .map(Some)
+                .map_err(|e| format!("failed to replay result: {}", e)),
+            _ => Ok(None),
+        }
+    }
+
+    // remember the result once the command's change is durable
+    pub fn complete<T: Serialize>(mut self, result: &T) {
+        if let Some(guard) = self.guard.as_mut() {
+            match serde_json::to_value(result) {
+                Ok(value) => **guard = Some(value),
+                Err(e) => log::warn!("failed to store idempotent result: {}", e),
+            }
+        }
+    }
+}
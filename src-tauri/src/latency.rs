@@ -0,0 +1,139 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+// samples kept per measurement, the oldest fall off first
+const MAX_SAMPLES: usize = 1000;
+
+// a send nobody confirmed within this long counts as unacknowledged
+const RECEIPT_TIMEOUT_SECS: u64 = 60;
+
+// members confirming each channel message on average, enough for a first
+// receipt without every member answering every message
+pub const RECEIPTS_PER_MESSAGE: usize = 3;
+
+// typing-to-send latency of our own channel messages, kept in memory for
+// the session so regressions in the crdt and gossip pipeline show up as
+// numbers
+pub struct LatencyTracker {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    // sends waiting for their first receipt, by message id
+    pending: HashMap<String, Instant>,
+    commit_ms: VecDeque<u64>,
+    receipt_ms: VecDeque<u64>,
+    unacknowledged: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Percentiles {
+    pub count: usize,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyStats {
+    // send_message called until the message is stored and handed to the node
+    pub commit: Percentiles,
+    // send_message called until the first member's receipt is back, a round
+    // trip so clock skew between peers doesn't enter into it
+    pub receipt: Percentiles,
+    // sends still waiting for a receipt
+    pub pending: usize,
+    // sends no receipt came back for within RECEIPT_TIMEOUT_SECS
+    pub unacknowledged: u64,
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    // a send was stored and published, started is when send_message was called
+    pub fn record_commit(&self, message_id: &str, started: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        push_sample(&mut inner.commit_ms, started.elapsed());
+
+        let timeout = Duration::from_secs(RECEIPT_TIMEOUT_SECS);
+        let before = inner.pending.len();
+        inner.pending.retain(|_, sent| sent.elapsed() < timeout);
+        inner.unacknowledged += (before - inner.pending.len()) as u64;
+        inner.pending.insert(message_id.to_string(), started);
+    }
+
+    // a member confirmed one of our messages, later receipts for the same
+    // message are ignored
+    pub fn record_receipt(&self, message_id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(started) = inner.pending.remove(message_id) {
+            push_sample(&mut inner.receipt_ms, started.elapsed());
+        }
+    }
+
+    pub fn stats(&self) -> LatencyStats {
+        let inner = self.inner.lock().unwrap();
+        LatencyStats {
+            commit: percentiles(&inner.commit_ms),
+            receipt: percentiles(&inner.receipt_ms),
+            pending: inner.pending.len(),
+            unacknowledged: inner.unacknowledged,
+        }
+    }
+
+    // measurements from before an identity reset describe another node
+    pub fn clear(&self) {
+        *self.inner.lock().unwrap() = Inner::default();
+    }
+}
+
+// whether we confirm receipt of a channel message. each member decides on
+// its own from the message and its peer id, so about RECEIPTS_PER_MESSAGE
+// members answer whatever the size of the community
+pub fn should_send_receipt(message_id: &str, local_peer_id: &str, member_count: usize) -> bool {
+    let others = member_count.saturating_sub(1);
+    if others <= RECEIPTS_PER_MESSAGE {
+        return true;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(message_id.as_bytes());
+    hasher.update(local_peer_id.as_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    (u64::from_le_bytes(bytes) % others as u64) < RECEIPTS_PER_MESSAGE as u64
+}
+
+fn push_sample(samples: &mut VecDeque<u64>, elapsed: Duration) {
+    if samples.len() >= MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(elapsed.as_millis() as u64);
+}
+
+// nearest-rank percentiles
+fn percentiles(samples: &VecDeque<u64>) -> Percentiles {
+    if samples.is_empty() {
+        return Percentiles::default();
+    }
+    let mut sorted: Vec<u64> = samples.iter().copied().collect();
+    sorted.sort_unstable();
+    let rank = |p: usize| sorted[((sorted.len() * p).div_ceil(100)).max(1) - 1];
+    Percentiles {
+        count: sorted.len(),
+        p50_ms: rank(50),
+        p90_ms: rank(90),
+        p99_ms: rank(99),
+        max_ms: sorted[sorted.len() - 1],
+    }
+}
@@ -1,10 +1,25 @@
+mod activity;
+mod audio;
+#[cfg(feature = "bench")]
+pub mod bench;
+mod boot;
+mod catalog;
 mod commands;
+mod crash;
 mod crdt;
 #[cfg(feature = "dev-server")]
 mod dev_server;
+mod idempotency;
+mod latency;
+mod locale;
+mod logging;
 mod node;
 mod protocol;
+mod qr;
+mod ratelimit;
+mod snapshot;
 mod storage;
+mod updater;
 mod verification;
 
 use std::collections::{HashMap, HashSet};
@@ -13,7 +28,7 @@ use tokio::sync::Mutex;
 
 use crate::crdt::CrdtEngine;
 use crate::protocol::identity::DuskIdentity;
-use crate::protocol::messages::VoiceParticipant;
+use crate::protocol::messages::{PeerStatus, VoiceParticipant};
 use crate::storage::DiskStorage;
 
 // shared application state accessible from all tauri commands
@@ -24,31 +39,110 @@ pub struct AppState {
     pub node_handle: Arc<Mutex<Option<node::NodeHandle>>>,
     // tracks which peers are in which voice channels, keyed by "community_id:channel_id"
     pub voice_channels: Arc<Mutex<HashMap<String, Vec<VoiceParticipant>>>>,
-    // communities joined via invite that require initial role hardening
-    pub pending_join_role_guard: Arc<Mutex<HashSet<String>>>,
+    // hybrid logical clock used to order chat messages across skewed peers
+    pub hlc_clock: Arc<Mutex<node::clock::HybridClock>>,
+    // failed behavioral challenges this session, unlocks the proof-of-work fallback
+    pub verification_failures: Arc<Mutex<u32>>,
+    // unix millis when the mobile app was last sent to the background
+    pub backgrounded_at: Arc<Mutex<Option<u64>>>,
+    // noise suppression and gain for microphone frames routed through rust
+    pub audio_processor: Arc<Mutex<audio::AudioProcessor>>,
+    // rich presence text, set from the app or the activity pipe
+    pub activity: Arc<Mutex<Option<String>>>,
+    // payload padding and dummy dm traffic, toggled from settings
+    pub cover_traffic: Arc<node::cover::CoverTraffic>,
+    // how far startup has got, for frontends that attach after an event went out
+    pub boot_phase: Arc<Mutex<boot::BootPhase>>,
+    // recent results of mutating commands by idempotency key
+    pub idempotency: Arc<idempotency::IdempotencyCache>,
+    // send and receipt timings of our channel messages this session
+    pub latency: Arc<latency::LatencyTracker>,
+    // channel pages, by the cursor they start before, already backfilled
+    // from a peer this session, so reading one again doesn't ask again
+    pub backfilled_channels: Arc<Mutex<HashSet<(String, Option<u64>)>>>,
 }
 
 impl AppState {
+    // storage opens here since every command depends on it. community docs
+    // load in the background once the window is up, see boot::run
     pub fn new() -> Self {
         let storage = Arc::new(DiskStorage::new().expect("failed to initialize storage"));
+        let settings = storage.load_settings().unwrap_or_default();
         let mut engine = CrdtEngine::new(storage.clone());
-
-        // restore persisted communities from disk so data survives restarts
-        if let Err(e) = engine.load_all() {
-            log::warn!("failed to load persisted communities: {}", e);
-        }
+        engine.set_track_departures(settings.community_analytics);
 
         let crdt_engine = Arc::new(Mutex::new(engine));
 
+        let audio_processor =
+            audio::AudioProcessor::new(settings.noise_suppression, settings.auto_gain_control);
+
         Self {
             identity: Arc::new(Mutex::new(None)),
             crdt_engine,
             storage,
             node_handle: Arc::new(Mutex::new(None)),
             voice_channels: Arc::new(Mutex::new(HashMap::new())),
-            pending_join_role_guard: Arc::new(Mutex::new(HashSet::new())),
+            hlc_clock: Arc::new(Mutex::new(node::clock::HybridClock::new())),
+            verification_failures: Arc::new(Mutex::new(0)),
+            backgrounded_at: Arc::new(Mutex::new(None)),
+            audio_processor: Arc::new(Mutex::new(audio_processor)),
+            activity: Arc::new(Mutex::new(None)),
+            cover_traffic: Arc::new(node::cover::CoverTraffic::new(settings.cover_traffic)),
+            boot_phase: Arc::new(Mutex::new(boot::BootPhase::Documents)),
+            idempotency: Arc::new(idempotency::IdempotencyCache::new()),
+            latency: Arc::new(latency::LatencyTracker::new()),
+            backfilled_channels: Arc::new(Mutex::new(HashSet::new())),
         }
     }
+
+    // tear down everything tied to the loaded identity and start over the way
+    // a fresh install would, for a reset or before switching to a new
+    // identity. the node is stopped first since it holds clones of most of
+    // this state, then every guard is held in snapshot order while storage
+    // is wiped and the rest is rebuilt, so no command sees a half reset state
+    pub async fn reset(&self) -> Result<(), String> {
+        let mut identity = self.identity.lock().await;
+        if let Some(handle) = self.node_handle.lock().await.take() {
+            let _ = handle.command_tx.send(node::NodeCommand::Shutdown).await;
+            let _ = handle.task.await;
+        }
+        let mut engine = self.crdt_engine.lock().await;
+        let _node_handle = self.node_handle.lock().await;
+
+        self.storage
+            .wipe_all_data()
+            .map_err(|e| format!("failed to wipe data: {}", e))?;
+        let settings = self.storage.load_settings().unwrap_or_default();
+        logging::apply(&settings.logging, &self.storage);
+        catalog::apply(&settings.locale);
+
+        *engine = CrdtEngine::new(self.storage.clone());
+        engine.set_track_departures(settings.community_analytics);
+        *identity = None;
+
+        self.voice_channels.lock().await.clear();
+        *self.hlc_clock.lock().await = node::clock::HybridClock::new();
+        *self.verification_failures.lock().await = 0;
+        *self.backgrounded_at.lock().await = None;
+        self.audio_processor
+            .lock()
+            .await
+            .configure(settings.noise_suppression, settings.auto_gain_control);
+        *self.activity.lock().await = None;
+        self.cover_traffic.set_enabled(settings.cover_traffic);
+        *self.boot_phase.lock().await = boot::BootPhase::Onboarding;
+        self.idempotency.clear();
+        self.latency.clear();
+        self.backfilled_channels.lock().await.clear();
+
+        Ok(())
+    }
+
+    // identity, community doc heads and node status read under one lock order
+    #[allow(dead_code)]
+    pub async fn snapshot(&self) -> snapshot::StateSnapshot {
+        snapshot::capture(&self.identity, &self.crdt_engine, &self.node_handle).await
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -56,14 +150,85 @@ pub fn run() {
     // load .env from the project root so config like DUSK_RELAY_ADDR is available
     dotenvy::dotenv().ok();
 
-    // initialize the logger so RUST_LOG=info actually produces output
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    // stdout only until the settings are loaded, recent lines are also kept
+    // in memory for crash dumps
+    logging::init();
+
+    let builder = tauri::Builder::default();
 
-    tauri::Builder::default()
+    // the single-instance check has to be the first plugin registered so a
+    // second launch exits before anything else starts
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+        forward_launch(app, args);
+    }));
+
+    let builder = builder
         .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_notification::init())
-        .manage(AppState::new())
+        .plugin(tauri_plugin_notification::init());
+
+    // mobile builds are updated through the app stores
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_updater::Builder::new().build());
+
+    builder
         .setup(|app| {
+            // storage opens only once the single-instance check has passed,
+            // a second launch exits before touching it
+            {
+                use tauri::Manager;
+                let state = AppState::new();
+                crash::install_panic_hook(state.storage.crash_dir());
+                let settings = state.storage.load_settings().unwrap_or_default();
+                logging::apply(&settings.logging, &state.storage);
+                catalog::apply(&settings.locale);
+                app.manage(state);
+            }
+
+            // the main window is built here rather than from the config so a
+            // portable install keeps its webview data on the stick too
+            {
+                use tauri::Manager;
+                let config = app
+                    .config()
+                    .app
+                    .windows
+                    .first()
+                    .cloned()
+                    .ok_or("no main window configured")?;
+                let window = tauri::WebviewWindowBuilder::from_config(app.handle(), &config)?;
+                #[cfg(desktop)]
+                let window = if storage::portable_mode() {
+                    let state = app.state::<AppState>();
+                    window.data_directory(state.storage.data_dir().join("webview"))
+                } else {
+                    window
+                };
+                window.build()?;
+            }
+
+            // external tools can report what the user is doing, desktop only.
+            // started here so a second instance never competes for the pipe
+            #[cfg(desktop)]
+            {
+                use tauri::Manager;
+                activity::spawn_pipe_listener(&app.state::<AppState>());
+            }
+
+            // restore persisted communities and the identity without holding
+            // up the window. the engine lock is taken before any command can
+            // run so nothing reads the engine before the docs are in
+            {
+                use tauri::Manager;
+                let state = app.state::<AppState>();
+                let engine = state
+                    .crdt_engine
+                    .clone()
+                    .try_lock_owned()
+                    .expect("engine locked before startup");
+                tauri::async_runtime::spawn(boot::run(app.handle().clone(), engine));
+            }
+
             // grant microphone/camera permissions on linux webkitgtk
             // without this, getUserMedia is denied by default
             // only allow UserMediaPermissionRequest (mic/camera), deny everything else
@@ -103,7 +268,8 @@ pub fn run() {
                     storage: std::sync::Arc::clone(&state.storage),
                     node_handle: std::sync::Arc::clone(&state.node_handle),
                     voice_channels: std::sync::Arc::clone(&state.voice_channels),
-                    pending_join_role_guard: std::sync::Arc::clone(&state.pending_join_role_guard),
+                    hlc_clock: std::sync::Arc::clone(&state.hlc_clock),
+                    cover_traffic: std::sync::Arc::clone(&state.cover_traffic),
                     app_handle: app.handle().clone(),
                 };
                 tauri::async_runtime::spawn(dev_server::start(dev_state));
@@ -115,36 +281,115 @@ pub fn run() {
             commands::identity::has_identity,
             commands::identity::load_identity,
             commands::identity::create_identity,
+            commands::identity::create_identity_with_pow,
+            commands::identity::is_portable_mode,
+            commands::identity::is_identity_locked,
+            commands::identity::unlock_identity,
+            commands::identity::get_boot_phase,
+            commands::identity::set_identity_passphrase,
+            commands::identity::is_storage_encrypted,
+            commands::identity::encrypt_storage,
+            commands::identity::get_database_encryption,
+            commands::identity::set_database_encryption,
+            commands::identity::get_verification_prompt,
             commands::identity::update_display_name,
             commands::identity::update_profile,
             commands::identity::load_settings,
             commands::identity::save_settings,
+            commands::identity::export_settings,
+            commands::identity::import_settings,
+            commands::identity::list_settings_profiles,
+            commands::identity::save_settings_profile,
+            commands::identity::switch_settings_profile,
+            commands::identity::delete_settings_profile,
             commands::identity::get_known_peers,
+            commands::identity::prune_directory,
             commands::identity::search_directory,
             commands::identity::get_friends,
             commands::identity::add_friend,
             commands::identity::remove_friend,
+            commands::identity::export_my_contact_card,
+            commands::identity::export_contact_card,
+            commands::identity::import_contact_card,
+            commands::identity::get_safety_number,
+            commands::identity::mark_peer_verified,
+            commands::identity::get_key_conflicts,
+            commands::identity::accept_new_key,
+            commands::identity::reject_new_key,
             commands::identity::discover_global_peers,
             commands::identity::set_relay_discoverable,
+            commands::identity::purge_relay_directory_entry,
+            commands::identity::get_profile_hidden_communities,
+            commands::identity::set_community_profile_visibility,
+            commands::identity::announce_profile_now,
+            commands::identity::set_relay_presence,
+            commands::identity::set_network_profile,
+            commands::identity::get_network_profile,
+            commands::identity::set_cover_traffic,
+            commands::identity::get_cover_traffic,
+            commands::identity::set_activity,
+            commands::identity::get_activity,
             commands::identity::set_relay_address,
             commands::identity::reset_identity,
             commands::identity::cache_avatar_icon,
             commands::chat::send_message,
+            commands::chat::send_message_optimistic,
+            commands::chat::get_latency_stats,
             commands::chat::get_messages,
+            commands::chat::get_thread,
+            commands::chat::get_thread_summaries,
             commands::chat::send_typing,
+            commands::chat::open_channel,
             commands::chat::start_node,
             commands::chat::stop_node,
             commands::chat::check_internet_connectivity,
+            commands::chat::get_peer_scores,
+            commands::chat::get_connection_report,
+            commands::chat::enter_background_mode,
+            commands::chat::sync_on_foreground,
             commands::chat::broadcast_presence,
+            commands::update::check_for_updates,
+            commands::update::install_update,
+            commands::update::set_update_channel,
+            commands::crash::list_crash_reports,
+            commands::crash::submit_crash_report,
+            commands::logging::set_log_level,
+            commands::logging::export_diagnostics,
+            commands::locale::set_system_locale,
+            commands::locale::get_locale,
+            commands::storage::run_storage_doctor,
+            commands::storage::set_search_tokenizer,
+            commands::storage::set_data_directory,
             commands::community::create_community,
             commands::community::join_community,
             commands::community::leave_community,
             commands::community::get_communities,
             commands::community::create_channel,
             commands::community::get_channels,
+            commands::notes::create_note,
+            commands::notes::apply_note_changes,
+            commands::notes::get_note,
+            commands::notes::get_notes,
+            commands::pages::create_page,
+            commands::pages::edit_page,
+            commands::pages::get_pages,
+            commands::pages::get_page,
+            commands::pages::get_page_history,
+            commands::pages::set_page_edit_role,
+            commands::tasks::get_task_board,
+            commands::tasks::create_task_list,
+            commands::tasks::update_task_list,
+            commands::tasks::delete_task_list,
+            commands::tasks::create_task_card,
+            commands::tasks::update_task_card,
+            commands::tasks::delete_task_card,
             commands::community::get_members,
+            commands::community::get_membership_log,
             commands::community::edit_message,
+            commands::community::add_reaction,
+            commands::community::remove_reaction,
             commands::community::delete_message,
+            commands::community::undo_delete_message,
             commands::community::kick_member,
             commands::community::generate_invite,
             commands::community::reorder_channels,
@@ -152,30 +397,135 @@ pub fn run() {
             commands::community::get_categories,
             commands::community::update_community,
             commands::community::update_channel,
+            commands::community::set_voice_channel_capacity,
             commands::community::delete_channel,
             commands::community::delete_category,
             commands::community::update_category,
             commands::community::reorder_categories,
             commands::community::set_member_role,
             commands::community::transfer_ownership,
+            commands::community::get_community_conflicts,
+            commands::community::resolve_community_conflict,
+            commands::community::set_community_verification_policy,
+            commands::community::set_community_edit_history,
+            commands::community::get_message_edit_history,
+            commands::community::follow_channel,
+            commands::community::unfollow_channel,
+            commands::community::get_channel_follows,
+            commands::community::set_community_profile,
+            commands::community::clear_community_profile,
+            commands::community::get_community_profiles,
             commands::voice::join_voice_channel,
             commands::voice::leave_voice_channel,
             commands::voice::update_voice_media_state,
+            commands::voice::report_voice_activity,
+            commands::voice::process_audio_frame,
+            commands::voice::set_audio_processing,
             commands::voice::send_voice_sdp,
             commands::voice::send_voice_ice_candidate,
             commands::voice::get_voice_participants,
+            commands::voice::disconnect_voice_participant,
+            commands::voice::move_voice_participant,
+            commands::voice::raise_stage_hand,
+            commands::voice::set_stage_role,
             commands::voice::get_turn_credentials,
             commands::dm::send_dm,
             commands::dm::get_dm_messages,
             commands::dm::search_dm_messages,
             commands::dm::get_dm_conversations,
+            commands::dm::get_dm_conversation_page,
+            commands::dm::set_dm_pinned,
+            commands::dm::set_dm_archived,
             commands::dm::mark_dm_read,
+            commands::dm::set_dm_ephemeral,
+            commands::dm::respond_dm_ephemeral,
+            commands::dm::get_dm_ephemeral,
+            commands::dm::get_dm_policy_log,
+            commands::dm::delete_dm_for_everyone,
+            commands::dm::edit_dm,
+            commands::dm::react_dm,
             commands::dm::delete_dm_conversation,
             commands::dm::send_dm_typing,
             commands::dm::open_dm_conversation,
+            commands::call::start_dm_call,
+            commands::call::accept_dm_call,
+            commands::call::end_dm_call,
+            commands::call::get_call_history,
+            commands::export::export_dm_conversation,
+            commands::export::generate_data_report,
+            commands::qr::generate_qr,
+            commands::qr::save_qr,
+            commands::stats::get_usage_stats,
+            commands::stats::get_community_analytics,
+            commands::layout::get_community_layout,
+            commands::layout::create_folder,
+            commands::layout::update_folder,
+            commands::layout::delete_folder,
+            commands::layout::set_folder_order,
+            commands::layout::set_community_order,
+            commands::moderation::grant_capability,
+            commands::moderation::get_capabilities,
             commands::gif::search_gifs,
             commands::gif::get_trending_gifs,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running dusk");
+        .build(tauri::generate_context!())
+        .expect("error while building dusk")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                use tauri::Manager;
+                // setup never ran when the app failed to start
+                let Some(state) = app.try_state::<AppState>() else {
+                    return;
+                };
+                tauri::async_runtime::block_on(async {
+                    let limit = std::time::Duration::from_secs(SHUTDOWN_TIMEOUT_SECS);
+                    if tokio::time::timeout(limit, shutdown(&state)).await.is_err() {
+                        log::warn!("shutdown did not finish within {}s", SHUTDOWN_TIMEOUT_SECS);
+                    }
+                });
+            }
+        });
+}
+
+// another launch was started while we run: bring our window forward and
+// pass its arguments (links opened with the app, for one) to the frontend
+#[cfg(desktop)]
+fn forward_launch(app: &tauri::AppHandle, args: Vec<String>) {
+    use tauri::{Emitter, Manager};
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+
+    // the first argument is the executable path
+    let args: Vec<String> = args.into_iter().skip(1).collect();
+    log::info!("second launch forwarded with {} argument(s)", args.len());
+    let _ = app.emit("dusk-event", node::DuskEvent::LaunchForwarded { args });
+}
+
+// upper bound on how long exit waits for the node and pending writes
+const SHUTDOWN_TIMEOUT_SECS: u64 = 3;
+
+// runs once on exit, the process ends right after. pending deletions and
+// documents are written out, then peers are told we went offline and the
+// node gets to send that before it stops
+async fn shutdown(state: &AppState) {
+    let deletions = state.crdt_engine.lock().await.flush();
+
+    let Some(handle) = state.node_handle.lock().await.take() else {
+        return;
+    };
+    for deletion in deletions {
+        commands::community::broadcast_deletion(&handle, deletion).await;
+    }
+    let _ = handle
+        .command_tx
+        .send(node::NodeCommand::BroadcastPresence {
+            status: PeerStatus::Offline,
+        })
+        .await;
+    let _ = handle.command_tx.send(node::NodeCommand::Shutdown).await;
+    let _ = handle.task.await;
 }
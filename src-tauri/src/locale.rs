@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+
+// how text the backend generates writes dates and times. exports, reports
+// and anything else a person reads should match what the ui shows
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocaleSettings {
+    // bcp 47 tag such as "en-US" or "de", none follows the system
+    pub locale: Option<String>,
+    // minutes east of utc, none follows the system
+    pub utc_offset_minutes: Option<i32>,
+    // none follows the locale
+    pub hour_cycle: Option<HourCycle>,
+    // what the ui last reported for the system, kept so text generated
+    // before the ui is up still matches it
+    pub system_locale: Option<String>,
+    pub system_time_zone: Option<String>,
+    pub system_utc_offset_minutes: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HourCycle {
+    H12,
+    H24,
+}
+
+// the settings with overrides and system values folded together, what the
+// ui should format with too
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolvedLocale {
+    pub locale: String,
+    // iana name when it comes from the system, "utc+02:00" style for an
+    // override or when the system zone isn't known
+    pub time_zone: String,
+    pub utc_offset_minutes: i32,
+    pub hour_cycle: HourCycle,
+}
+
+impl LocaleSettings {
+    pub fn resolve(&self) -> ResolvedLocale {
+        let locale = self
+            .locale
+            .as_deref()
+            .or(self.system_locale.as_deref())
+            .and_then(normalize_tag)
+            .or_else(env_locale)
+            .unwrap_or_else(|| "en-US".to_string());
+
+        let (time_zone, utc_offset_minutes) = match self.utc_offset_minutes {
+            Some(offset) => (offset_label(offset), offset),
+            None => {
+                let offset = self.system_utc_offset_minutes.unwrap_or(0);
+                let zone = self
+                    .system_time_zone
+                    .clone()
+                    .filter(|z| !z.trim().is_empty())
+                    .unwrap_or_else(|| offset_label(offset));
+                (zone, offset)
+            }
+        };
+
+        let hour_cycle = self.hour_cycle.unwrap_or_else(|| {
+            if uses_12_hour(&locale) {
+                HourCycle::H12
+            } else {
+                HourCycle::H24
+            }
+        });
+
+        ResolvedLocale {
+            locale,
+            time_zone,
+            utc_offset_minutes,
+            hour_cycle,
+        }
+    }
+}
+
+// the order and separator a locale writes numeric dates in
+#[derive(Debug, Clone, Copy)]
+enum DatePattern {
+    // 2026-03-14, also the fallback for locales we know nothing about
+    Iso,
+    // 2026/03/14
+    YmdSlash,
+    // 2026. 03. 14.
+    YmdDot,
+    // 03/14/2026
+    MdySlash,
+    // 14/03/2026
+    DmySlash,
+    // 14.03.2026
+    DmyDot,
+    // 14-03-2026
+    DmyDash,
+}
+
+// formats unix ms timestamps for people to read. the current offset applies
+// to every timestamp, so one from the other side of a daylight saving change
+// reads an hour off, as there is no zone database to look the old one up in
+pub struct Formatter {
+    pattern: DatePattern,
+    hour_cycle: HourCycle,
+    offset_minutes: i32,
+    zone: String,
+}
+
+impl Formatter {
+    pub fn new(resolved: &ResolvedLocale) -> Self {
+        Self {
+            pattern: date_pattern(&resolved.locale),
+            hour_cycle: resolved.hour_cycle,
+            offset_minutes: resolved.utc_offset_minutes,
+            zone: resolved.time_zone.clone(),
+        }
+    }
+
+    pub fn from_settings(settings: &LocaleSettings) -> Self {
+        Self::new(&settings.resolve())
+    }
+
+    pub fn date(&self, ms: u64) -> String {
+        let (year, month, day, _, _) = self.local(ms);
+        match self.pattern {
+            DatePattern::Iso => format!("{:04}-{:02}-{:02}", year, month, day),
+            DatePattern::YmdSlash => format!("{:04}/{:02}/{:02}", year, month, day),
+            DatePattern::YmdDot => format!("{:04}. {:02}. {:02}.", year, month, day),
+            DatePattern::MdySlash => format!("{:02}/{:02}/{:04}", month, day, year),
+            DatePattern::DmySlash => format!("{:02}/{:02}/{:04}", day, month, year),
+            DatePattern::DmyDot => format!("{:02}.{:02}.{:04}", day, month, year),
+            DatePattern::DmyDash => format!("{:02}-{:02}-{:04}", day, month, year),
+        }
+    }
+
+    pub fn time(&self, ms: u64) -> String {
+        let (_, _, _, hour, minute) = self.local(ms);
+        match self.hour_cycle {
+            HourCycle::H24 => format!("{:02}:{:02}", hour, minute),
+            HourCycle::H12 => {
+                let suffix = if hour < 12 { "AM" } else { "PM" };
+                let hour = match hour % 12 {
+                    0 => 12,
+                    h => h,
+                };
+                format!("{}:{:02} {}", hour, minute, suffix)
+            }
+        }
+    }
+
+    pub fn date_time(&self, ms: u64) -> String {
+        format!("{} {}", self.date(ms), self.time(ms))
+    }
+
+    // date and time with the zone, for a header that the rest of a document's
+    // timestamps are read against
+    pub fn date_time_zone(&self, ms: u64) -> String {
+        format!("{} ({})", self.date_time(ms), self.zone)
+    }
+
+    fn local(&self, ms: u64) -> (i64, i64, i64, i64, i64) {
+        let secs = (ms / 1000) as i64 + self.offset_minutes as i64 * 60;
+        civil(secs)
+    }
+}
+
+// render a unix ms timestamp as "yyyy-mm-dd hh:mm utc", for diagnostics that
+// get compared across machines
+pub fn format_utc(ms: u64) -> String {
+    let (year, month, day, hour, minute) = civil((ms / 1000) as i64);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} utc",
+        year, month, day, hour, minute
+    )
+}
+
+// year, month, day, hour and minute of unix seconds, without pulling in a
+// date crate
+fn civil(secs: i64) -> (i64, i64, i64, i64, i64) {
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+
+    // civil-from-days, proleptic gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day, rem / 3600, (rem % 3600) / 60)
+}
+
+fn offset_label(offset_minutes: i32) -> String {
+    let sign = if offset_minutes < 0 { '-' } else { '+' };
+    let abs = offset_minutes.unsigned_abs();
+    format!("utc{}{:02}:{:02}", sign, abs / 60, abs % 60)
+}
+
+// "en_US.UTF-8" and "en-us" both become "en-US". none for an empty tag or
+// the posix "C" locale
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().split(['.', '@']).next().unwrap_or("");
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return None;
+    }
+    let mut parts = tag.split(['-', '_']).filter(|p| !p.is_empty());
+    let language = parts.next()?.to_lowercase();
+    if !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut normalized = language;
+    for part in parts {
+        if !part.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        normalized.push('-');
+        match part.len() {
+            2 => normalized.push_str(&part.to_uppercase()),
+            4 => {
+                normalized.push_str(&part[..1].to_uppercase());
+                normalized.push_str(&part[1..].to_lowercase());
+            }
+            _ => normalized.push_str(&part.to_lowercase()),
+        }
+    }
+    Some(normalized)
+}
+
+// the language and region of a normalized tag, a script in between is skipped
+pub fn split_tag(tag: &str) -> (&str, Option<&str>) {
+    let mut parts = tag.split('-');
+    let language = parts.next().unwrap_or("");
+    let region = parts.find(|p| p.len() == 2 || p.chars().all(|c| c.is_ascii_digit()));
+    (language, region)
+}
+
+// the posix locale of the process, for before the ui has reported one
+fn env_locale() -> Option<String> {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find_map(|value| normalize_tag(&value))
+}
+
+fn date_pattern(locale: &str) -> DatePattern {
+    match split_tag(locale) {
+        ("en", None | Some("US" | "PH")) | ("fil", _) => DatePattern::MdySlash,
+        ("en", Some("CA")) | ("fr", Some("CA")) | ("sv" | "lt", _) => DatePattern::Iso,
+        ("ja" | "zh", _) => DatePattern::YmdSlash,
+        ("ko" | "hu", _) => DatePattern::YmdDot,
+        ("nl", _) => DatePattern::DmyDash,
+        (
+            "de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "nn" | "no" | "da" | "tr" | "uk"
+            | "ro" | "bg" | "hr" | "sr" | "sl" | "et" | "lv" | "is",
+            _,
+        ) => DatePattern::DmyDot,
+        (
+            "en" | "fr" | "es" | "it" | "pt" | "el" | "vi" | "id" | "ms" | "ca" | "ga" | "cy"
+            | "he" | "ar" | "hi" | "th",
+            _,
+        ) => DatePattern::DmySlash,
+        _ => DatePattern::Iso,
+    }
+}
+
+fn uses_12_hour(locale: &str) -> bool {
+    matches!(
+        split_tag(locale),
+        ("en", None | Some("US" | "CA" | "AU" | "NZ" | "IN" | "PH"))
+            | ("fil" | "hi" | "ar" | "ko" | "bn" | "ur", _)
+    )
+}
@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::DiskStorage;
+
+// log lines kept in memory for inclusion in crash dumps
+const LOG_RING_CAPACITY: usize = 500;
+// the current file is rotated once it grows past this
+const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+// rotated files kept beside the current one, dusk.log.1 is the newest
+const MAX_ROTATED_FILES: usize = 3;
+const LOG_FILE: &str = "dusk.log";
+
+static LOGGER: OnceLock<DuskLogger> = OnceLock::new();
+
+// where logs go and how verbose they are, persisted in settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    // off, error, warn, info, debug or trace, for modules without their own
+    pub level: String,
+    // levels by module path such as "dusk_chat_lib::node" or
+    // "libp2p_gossipsub", the longest matching path wins
+    pub modules: BTreeMap<String, String>,
+    // write to rotating files in the data dir as well as stdout. off by
+    // default, and held off while storage is encrypted at rest since lines
+    // name peers, communities and messages the database keeps sealed
+    pub file: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            modules: BTreeMap::new(),
+            file: false,
+        }
+    }
+}
+
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(level.trim()).map_err(|_| format!("unknown log level: {}", level))
+}
+
+// levels by target, modules sorted longest path first so the first match
+// is the most specific one
+struct Filter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    fn from_config(config: &LogConfig) -> Self {
+        let modules = config
+            .modules
+            .iter()
+            .filter_map(|(module, level)| Some((module.clone(), parse_level(level).ok()?)))
+            .collect();
+        Self::new(
+            parse_level(&config.level).unwrap_or(LevelFilter::Info),
+            modules,
+        )
+    }
+
+    // RUST_LOG style directives, "info" or "dusk_chat_lib::node=debug,warn".
+    // a module without a level gets everything, as with env_logger
+    fn from_directives(spec: &str) -> Self {
+        let mut default = LevelFilter::Error;
+        let mut modules = Vec::new();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    if let Ok(level) = parse_level(level) {
+                        modules.push((module.trim().to_string(), level));
+                    }
+                }
+                None => match parse_level(directive) {
+                    Ok(level) => default = level,
+                    Err(_) => modules.push((directive.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+        Self::new(default, modules)
+    }
+
+    fn new(default: LevelFilter, mut modules: Vec<(String, LevelFilter)>) -> Self {
+        modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        Self { default, modules }
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    // the most verbose level anything is logged at, lets the log macros skip
+    // formatting everything else
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+// the current file and how much has gone into it
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    written: u64,
+}
+
+impl LogFile {
+    fn open(dir: &Path) -> Result<Self, io::Error> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_FILE))?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            written,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), io::Error> {
+        if self.written + line.len() as u64 + 1 > MAX_LOG_FILE_BYTES {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    // dusk.log becomes dusk.log.1, which becomes dusk.log.2 and so on, the
+    // oldest falls off
+    fn rotate(&mut self) -> Result<(), io::Error> {
+        self.file.flush()?;
+        let _ = fs::remove_file(rotated_path(&self.dir, MAX_ROTATED_FILES));
+        for n in (1..MAX_ROTATED_FILES).rev() {
+            let _ = fs::rename(rotated_path(&self.dir, n), rotated_path(&self.dir, n + 1));
+        }
+        fs::rename(self.dir.join(LOG_FILE), rotated_path(&self.dir, 1))?;
+        *self = Self::open(&self.dir)?;
+        Ok(())
+    }
+}
+
+fn rotated_path(dir: &Path, n: usize) -> PathBuf {
+    dir.join(format!("{}.{}", LOG_FILE, n))
+}
+
+// writes to stdout through env_logger and optionally to a file, and keeps
+// the most recent lines for crash dumps
+struct DuskLogger {
+    // formats for stdout, built to let everything through since the
+    // filter below decides
+    stdout: env_logger::Logger,
+    filter: RwLock<Filter>,
+    file: Mutex<Option<LogFile>>,
+    ring: Mutex<VecDeque<String>>,
+    // RUST_LOG was set, it takes precedence over the settings
+    env_override: bool,
+}
+
+impl log::Log for DuskLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.filter.read().unwrap().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.stdout.log(record);
+
+        let line = format!(
+            "{} {} {}: {}",
+            now_millis(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                // stop writing rather than log about failing to log
+                if let Err(e) = log_file.write_line(&line) {
+                    eprintln!("failed to write log file, file logging stopped: {}", e);
+                    *file = None;
+                }
+            }
+        }
+        if let Ok(mut ring) = self.ring.lock() {
+            if ring.len() >= LOG_RING_CAPACITY {
+                ring.pop_front();
+            }
+            ring.push_back(line);
+        }
+    }
+
+    fn flush(&self) {
+        self.stdout.flush();
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                let _ = log_file.file.flush();
+            }
+        }
+    }
+}
+
+// install the logger, stdout only until apply knows the settings. RUST_LOG
+// still works and overrides the configured levels when set
+pub fn init() {
+    let env_spec = std::env::var("RUST_LOG")
+        .ok()
+        .filter(|s| !s.trim().is_empty());
+    let filter = match env_spec {
+        Some(ref spec) => Filter::from_directives(spec),
+        None => Filter::from_config(&LogConfig::default()),
+    };
+    let max_level = filter.max_level();
+    let stdout = env_logger::Builder::new()
+        .filter_level(LevelFilter::Trace)
+        .build();
+    let logger = LOGGER.get_or_init(|| DuskLogger {
+        stdout,
+        filter: RwLock::new(filter),
+        file: Mutex::new(None),
+        ring: Mutex::new(VecDeque::with_capacity(LOG_RING_CAPACITY)),
+        env_override: env_spec.is_some(),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+// switch to the configured levels and file destination, takes effect for
+// the next line logged
+pub fn apply(config: &LogConfig, storage: &DiskStorage) {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+    if !logger.env_override {
+        let filter = Filter::from_config(config);
+        log::set_max_level(filter.max_level());
+        *logger.filter.write().unwrap() = filter;
+    }
+
+    let mut file = logger.file.lock().unwrap();
+    let to_file = config.file && !storage.at_rest_encrypted();
+    match (to_file, file.is_some()) {
+        (true, false) => match LogFile::open(&storage.log_dir()) {
+            Ok(opened) => *file = Some(opened),
+            Err(e) => eprintln!("failed to open log file: {}", e),
+        },
+        (false, true) => *file = None,
+        _ => {}
+    }
+}
+
+// the most recent lines, oldest first
+pub fn recent_lines() -> Vec<String> {
+    LOGGER
+        .get()
+        .and_then(|logger| {
+            logger
+                .ring
+                .try_lock()
+                .ok()
+                .map(|ring| ring.iter().cloned().collect())
+        })
+        .unwrap_or_default()
+}
+
+// contents of the current log file, none when file logging is off
+pub fn current_file() -> Option<String> {
+    let logger = LOGGER.get()?;
+    let mut file = logger.file.lock().ok()?;
+    let log_file = file.as_mut()?;
+    let _ = log_file.file.flush();
+    fs::read_to_string(log_file.dir.join(LOG_FILE)).ok()
+}
+
+// empty the current file and drop the rotated ones, for a reset. the
+// current file stays open so logging carries on into it
+pub fn clear_files(log_dir: &Path) {
+    for n in 1..=MAX_ROTATED_FILES {
+        let _ = fs::remove_file(rotated_path(log_dir, n));
+    }
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+    let mut file = logger.file.lock().unwrap();
+    match file.as_mut() {
+        Some(log_file) => {
+            if log_file.file.set_len(0).is_ok() {
+                log_file.written = 0;
+            }
+        }
+        None => {
+            let _ = fs::remove_file(log_dir.join(LOG_FILE));
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
@@ -0,0 +1,129 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+
+// what asked for a profile announcement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnounceTrigger {
+    // the node came up
+    Startup,
+    // name, bio or custom status changed, or a community became visible
+    ProfileChange,
+    // a peer showed up on the local network
+    LanPeer,
+    // the relay reservation was accepted
+    RelayConnect,
+    // a connection to another peer was established
+    PeerConnect,
+    // the user asked for it, goes out even in manual mode
+    Manual,
+}
+
+// which events announce our profile and how often. every announcement tells
+// whoever is listening that we are online right now, so frequent ones make
+// us easy to follow across connections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnnouncePolicy {
+    // only announce when the user asks for it
+    pub manual_only: bool,
+    pub on_startup: bool,
+    pub on_profile_change: bool,
+    pub on_lan_peer: bool,
+    pub on_relay_connect: bool,
+    pub on_peer_connect: bool,
+    // announcements are at least this far apart, triggers in between fold
+    // into the next one
+    pub min_interval_secs: u64,
+    // random delay added to each scheduled announcement so it doesn't line
+    // up with the connection that triggered it
+    pub jitter_secs: u64,
+}
+
+impl Default for AnnouncePolicy {
+    fn default() -> Self {
+        Self {
+            manual_only: false,
+            on_startup: true,
+            on_profile_change: true,
+            on_lan_peer: true,
+            on_relay_connect: true,
+            on_peer_connect: true,
+            min_interval_secs: 60,
+            jitter_secs: 10,
+        }
+    }
+}
+
+impl AnnouncePolicy {
+    pub fn allows(&self, trigger: AnnounceTrigger) -> bool {
+        if trigger == AnnounceTrigger::Manual {
+            return true;
+        }
+        if self.manual_only {
+            return false;
+        }
+        match trigger {
+            AnnounceTrigger::Startup => self.on_startup,
+            AnnounceTrigger::ProfileChange => self.on_profile_change,
+            AnnounceTrigger::LanPeer => self.on_lan_peer,
+            AnnounceTrigger::RelayConnect => self.on_relay_connect,
+            AnnounceTrigger::PeerConnect => self.on_peer_connect,
+            AnnounceTrigger::Manual => true,
+        }
+    }
+}
+
+// folds announcement triggers into at most one pending announcement
+pub struct AnnounceScheduler {
+    due: Option<Instant>,
+    last: Option<Instant>,
+    manual: bool,
+}
+
+impl AnnounceScheduler {
+    pub fn new() -> Self {
+        Self {
+            due: None,
+            last: None,
+            manual: false,
+        }
+    }
+
+    pub fn request(&mut self, trigger: AnnounceTrigger, policy: &AnnouncePolicy) {
+        if trigger == AnnounceTrigger::Manual {
+            self.manual = true;
+            self.due = Some(Instant::now());
+            return;
+        }
+        if !policy.allows(trigger) || self.due.is_some() {
+            return;
+        }
+        let now = Instant::now();
+        let earliest = self
+            .last
+            .map_or(now, |last| {
+                last + Duration::from_secs(policy.min_interval_secs)
+            })
+            .max(now);
+        let jitter = rand::thread_rng().gen_range(0..=policy.jitter_secs * 1000);
+        self.due = Some(earliest + Duration::from_millis(jitter));
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.due
+    }
+
+    // the pending announcement is due, returns whether it should still go
+    // out. the user may have switched to manual mode since it was scheduled
+    pub fn take(&mut self, policy: &AnnouncePolicy) -> bool {
+        let manual = std::mem::take(&mut self.manual);
+        self.due = None;
+        if !manual && policy.manual_only {
+            return false;
+        }
+        self.last = Some(Instant::now());
+        true
+    }
+}
@@ -1,19 +1,32 @@
+use super::cover::PaddingTransform;
+use crate::crdt::sync::DocumentSnapshot;
+use crate::protocol::catchup::{CatchupRequest, CatchupResponse};
 use crate::protocol::directory::{DirectoryRequest, DirectoryResponse};
 use crate::protocol::gif::{GifRequest, GifResponse};
+use crate::protocol::history::{HistoryRequest, HistoryResponse};
+use crate::protocol::presence::{PresenceRequest, PresenceResponse};
+use crate::protocol::snapshot::SnapshotResponse;
 use crate::protocol::turn::{TurnCredentialRequest, TurnCredentialResponse};
+use crate::protocol::voice::{VoiceRosterRequest, VoiceRosterResponse};
 use libp2p::{
-    gossipsub, identify, kad, mdns, ping, relay, rendezvous, request_response::cbor,
-    swarm::NetworkBehaviour,
+    autonat, gossipsub, identify, kad, mdns, ping, relay, rendezvous,
+    request_response::cbor,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour},
 };
 
 #[derive(NetworkBehaviour)]
 pub struct DuskBehaviour {
     pub relay_client: relay::client::Behaviour,
     pub rendezvous: rendezvous::client::Behaviour,
-    pub gossipsub: gossipsub::Behaviour,
+    // payloads are padded to size buckets while cover traffic mode is on
+    pub gossipsub: gossipsub::Behaviour<PaddingTransform>,
     pub kademlia: kad::Behaviour<kad::store::MemoryStore>,
-    pub mdns: mdns::tokio::Behaviour,
+    // disabled when the node starts in the low power network profile
+    pub mdns: Toggle<mdns::tokio::Behaviour>,
     pub identify: identify::Behaviour,
+    // confirms identify-observed addresses are reachable from outside,
+    // only enabled when the user opted into direct connections
+    pub autonat: Toggle<autonat::Behaviour>,
     pub ping: ping::Behaviour,
     // gif search: sends requests to the relay, receives responses
     pub gif_service: cbor::Behaviour<GifRequest, GifResponse>,
@@ -21,4 +34,16 @@ pub struct DuskBehaviour {
     pub directory_service: cbor::Behaviour<DirectoryRequest, DirectoryResponse>,
     // turn credentials: request time-limited TURN server credentials from the relay
     pub turn_credentials: cbor::Behaviour<TurnCredentialRequest, TurnCredentialResponse>,
+    // catch-up: peers serve recently cached channel messages to late joiners
+    pub catchup: cbor::Behaviour<CatchupRequest, CatchupResponse>,
+    // channel history: members serve pages of older messages to each other
+    pub history: cbor::Behaviour<HistoryRequest, HistoryResponse>,
+    // friend presence: opt-in heartbeats to the relay, answered with which
+    // friends are online
+    pub presence_service: cbor::Behaviour<PresenceRequest, PresenceResponse>,
+    // voice roster: participants tell joining members who is in the call
+    pub voice_roster: cbor::Behaviour<VoiceRosterRequest, VoiceRosterResponse>,
+    // document snapshots: members hand their doc to a peer that proved
+    // membership, and get the merged doc back
+    pub snapshot: cbor::Behaviour<DocumentSnapshot, SnapshotResponse>,
 }
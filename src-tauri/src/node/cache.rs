@@ -0,0 +1,95 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::community_id_from_topic;
+use super::gossip;
+use crate::protocol::catchup::CachedGossip;
+
+// how long payloads stay available for late joiners
+const CACHE_TTL_MS: u64 = 15 * 60 * 1000;
+// upper bound of cached payloads per topic
+const CACHE_MAX_PER_TOPIC: usize = 200;
+
+// short-term cache of recent gossip payloads per topic, served to peers that
+// subscribe after a message was published and would otherwise never see it
+pub struct MessageCache {
+    topics: HashMap<String, VecDeque<CachedGossip>>,
+}
+
+impl MessageCache {
+    pub fn new() -> Self {
+        Self {
+            topics: HashMap::new(),
+        }
+    }
+
+    // only channel message and dm pair topics are worth replaying, typing and
+    // presence are ephemeral
+    pub fn is_cacheable(topic: &str) -> bool {
+        (topic.starts_with("dusk/community/")
+            && (topic.ends_with("/messages") || topic.ends_with("/mentions")))
+            || gossip::is_dm_pair_topic(topic)
+    }
+
+    // dm history is only replayed to the other participant. pair topics are
+    // hashed, so check whether the requester's pair with us names this topic.
+    // community history only goes to members of the community in the topic
+    pub fn may_serve(
+        topic: &str,
+        requester: &str,
+        local_peer_id: &str,
+        is_member: impl FnOnce(&str) -> bool,
+    ) -> bool {
+        if gossip::is_dm_pair_topic(topic) {
+            gossip::topics_for_dm(local_peer_id, requester)
+                .iter()
+                .any(|t| t == topic)
+        } else {
+            community_id_from_topic(topic).is_some_and(is_member)
+        }
+    }
+
+    pub fn insert(&mut self, topic: &str, data: Vec<u8>, now: u64) {
+        let entry = CachedGossip {
+            received_at: now,
+            data,
+        };
+        // only a topic seen for the first time needs its own copy of the name
+        match self.topics.get_mut(topic) {
+            Some(entries) => {
+                entries.push_back(entry);
+                while entries.len() > CACHE_MAX_PER_TOPIC {
+                    entries.pop_front();
+                }
+            }
+            None => {
+                self.topics
+                    .insert(topic.to_string(), VecDeque::from([entry]));
+            }
+        }
+        self.prune(now);
+    }
+
+    // payloads received after the given timestamp, oldest first
+    pub fn since(&self, topic: &str, since: u64) -> Vec<CachedGossip> {
+        self.topics
+            .get(topic)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|e| e.received_at > since)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn prune(&mut self, now: u64) {
+        let cutoff = now.saturating_sub(CACHE_TTL_MS);
+        for entries in self.topics.values_mut() {
+            while entries.front().map(|e| e.received_at < cutoff).unwrap_or(false) {
+                entries.pop_front();
+            }
+        }
+        self.topics.retain(|_, entries| !entries.is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use tokio::time::{Duration, Instant};
+
+use crate::protocol::messages::CallDirection;
+
+// an unanswered call stops ringing after this long on both ends
+pub const RING_TIMEOUT_SECS: u64 = 45;
+
+// live dm call state for the node loop: which calls are still ringing and
+// which one, if any, is connected. the call log in storage is the record,
+// this only drives ring timeouts and busy replies
+pub struct CallTracker {
+    ringing: HashMap<String, (CallDirection, Instant)>,
+    active: Option<String>,
+}
+
+impl CallTracker {
+    pub fn new() -> Self {
+        Self {
+            ringing: HashMap::new(),
+            active: None,
+        }
+    }
+
+    pub fn ring(&mut self, call_id: &str, direction: CallDirection) {
+        let deadline = Instant::now() + Duration::from_secs(RING_TIMEOUT_SECS);
+        self.ringing.insert(call_id.to_string(), (direction, deadline));
+    }
+
+    pub fn answer(&mut self, call_id: &str) {
+        self.ringing.remove(call_id);
+        self.active = Some(call_id.to_string());
+    }
+
+    pub fn end(&mut self, call_id: &str) {
+        self.ringing.remove(call_id);
+        if self.active.as_deref() == Some(call_id) {
+            self.active = None;
+        }
+    }
+
+    // connected, or placing a call of our own. several incoming calls
+    // may ring at once, the user picks one
+    pub fn is_busy(&self) -> bool {
+        self.active.is_some()
+            || self
+                .ringing
+                .values()
+                .any(|(direction, _)| *direction == CallDirection::Outgoing)
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.ringing.values().map(|(_, deadline)| *deadline).min()
+    }
+
+    // calls whose ring time ran out, removed from tracking
+    pub fn expire(&mut self) -> Vec<(String, CallDirection)> {
+        let now = Instant::now();
+        let expired: Vec<(String, CallDirection)> = self
+            .ringing
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(call_id, (direction, _))| (call_id.clone(), *direction))
+            .collect();
+        for (call_id, _) in &expired {
+            self.ringing.remove(call_id);
+        }
+        expired
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::protocol::messages::Hlc;
+
+// remote clocks further ahead than this are treated as skewed rather than trusted
+pub const MAX_CLOCK_SKEW_MS: u64 = 60_000;
+
+// hybrid logical clock shared by the node loop and message-producing commands
+// wall time keeps ordering close to real time, the counter breaks ties and
+// keeps the clock monotonic when the system clock stalls or steps backwards
+pub struct HybridClock {
+    last: Hlc,
+}
+
+impl HybridClock {
+    pub fn new() -> Self {
+        Self {
+            last: Hlc::default(),
+        }
+    }
+
+    // produce a timestamp for a locally created event
+    pub fn now(&mut self) -> Hlc {
+        let wall = wall_ms();
+        self.last = if wall > self.last.wall {
+            Hlc { wall, counter: 0 }
+        } else {
+            Hlc {
+                wall: self.last.wall,
+                counter: self.last.counter.saturating_add(1),
+            }
+        };
+        self.last
+    }
+
+    // merge a timestamp received from a remote peer
+    // returns the skew in ms when the remote clock is too far ahead to be trusted,
+    // in which case the local clock is not advanced
+    pub fn observe(&mut self, remote: Hlc) -> Result<Hlc, u64> {
+        let wall = wall_ms();
+        if remote.wall > wall + MAX_CLOCK_SKEW_MS {
+            return Err(remote.wall - wall);
+        }
+
+        let max_wall = wall.max(self.last.wall).max(remote.wall);
+        let counter = if max_wall == self.last.wall && max_wall == remote.wall {
+            self.last.counter.max(remote.counter).saturating_add(1)
+        } else if max_wall == self.last.wall {
+            self.last.counter.saturating_add(1)
+        } else if max_wall == remote.wall {
+            remote.counter.saturating_add(1)
+        } else {
+            0
+        };
+
+        self.last = Hlc {
+            wall: max_wall,
+            counter,
+        };
+        Ok(self.last)
+    }
+}
+
+fn wall_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
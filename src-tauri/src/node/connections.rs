@@ -0,0 +1,139 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Instant;
+
+use libp2p::PeerId;
+use serde::Serialize;
+
+// peers we keep connections to unless the user picks another cap. the relay
+// is never counted
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+// evictions kept for the diagnostics report
+const EVICTION_HISTORY: usize = 50;
+
+// who a connected peer is to us, later variants are kept longer under pressure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerPriority {
+    Stranger,
+    Member,
+    // never evicted, friends may push the count past the cap
+    Friend,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Eviction {
+    pub peer_id: String,
+    pub priority: PeerPriority,
+    // unix millis
+    pub evicted_at: u64,
+}
+
+// connection counts and recent evictions for the diagnostics view
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionReport {
+    pub max_connections: usize,
+    pub friends: usize,
+    pub members: usize,
+    pub strangers: usize,
+    // newest first
+    pub evictions: Vec<Eviction>,
+}
+
+// keeps the number of peer connections under a cap, dropping strangers
+// before community members. within a tier the newest connection goes first
+// so long-lived links survive a burst of new ones
+pub struct ConnectionManager {
+    max_connections: usize,
+    connected_at: HashMap<PeerId, Instant>,
+    evictions: VecDeque<Eviction>,
+}
+
+impl ConnectionManager {
+    pub fn new(max_connections: usize) -> Self {
+        Self {
+            max_connections,
+            connected_at: HashMap::new(),
+            evictions: VecDeque::new(),
+        }
+    }
+
+    pub fn on_connected(&mut self, peer: PeerId) {
+        self.connected_at.entry(peer).or_insert_with(Instant::now);
+    }
+
+    pub fn on_disconnected(&mut self, peer: &PeerId) {
+        self.connected_at.remove(peer);
+    }
+
+    pub fn over_limit(&self) -> bool {
+        self.connected_at.len() > self.max_connections
+    }
+
+    // peers to disconnect so the count gets back under the cap. `priority`
+    // classifies each tracked peer, evictions are recorded for the report
+    pub fn select_evictions(&mut self, priority: impl Fn(&PeerId) -> PeerPriority) -> Vec<PeerId> {
+        let excess = self.connected_at.len().saturating_sub(self.max_connections);
+        if excess == 0 {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<(PeerPriority, Instant, PeerId)> = self
+            .connected_at
+            .iter()
+            .map(|(peer, at)| (priority(peer), *at, *peer))
+            .filter(|(p, _, _)| *p != PeerPriority::Friend)
+            .collect();
+        // lowest priority first, newest connection first within a tier
+        candidates.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(&a.1)));
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mut evicted = Vec::new();
+        for (priority, _, peer) in candidates.into_iter().take(excess) {
+            self.connected_at.remove(&peer);
+            self.evictions.push_front(Eviction {
+                peer_id: peer.to_string(),
+                priority,
+                evicted_at: now,
+            });
+            evicted.push(peer);
+        }
+        self.evictions.truncate(EVICTION_HISTORY);
+        evicted
+    }
+
+    pub fn report(&self, priority: impl Fn(&PeerId) -> PeerPriority) -> ConnectionReport {
+        let mut report = ConnectionReport {
+            max_connections: self.max_connections,
+            friends: 0,
+            members: 0,
+            strangers: 0,
+            evictions: self.evictions.iter().cloned().collect(),
+        };
+        for peer in self.connected_at.keys() {
+            match priority(peer) {
+                PeerPriority::Friend => report.friends += 1,
+                PeerPriority::Member => report.members += 1,
+                PeerPriority::Stranger => report.strangers += 1,
+            }
+        }
+        report
+    }
+}
+
+pub fn classify(
+    peer: &PeerId,
+    friends: &HashSet<String>,
+    members: &HashSet<String>,
+) -> PeerPriority {
+    let peer = peer.to_string();
+    if friends.contains(&peer) {
+        PeerPriority::Friend
+    } else if members.contains(&peer) {
+        PeerPriority::Member
+    } else {
+        PeerPriority::Stranger
+    }
+}
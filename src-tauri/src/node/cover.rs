@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use libp2p::gossipsub;
+use rand::Rng;
+use serde::Serialize;
+use tokio::time::Duration;
+
+// payloads are padded with trailing spaces up to the next bucket. json parsers
+// skip the whitespace, so peers without the mode read padded payloads as usual.
+// anything past the largest bucket goes out unpadded to stay under the
+// gossipsub transmit limit
+const PAD_BUCKETS: &[usize] = &[256, 1024, 4096, 16384, 32768];
+
+// mean gap between dummy messages on each dm topic, jittered so the
+// schedule itself doesn't stand out
+pub const COVER_INTERVAL_SECS: u64 = 30;
+const COVER_JITTER_SECS: u64 = 20;
+
+// cover traffic mode: padding for every outgoing gossip payload plus dummy
+// messages on dm topics so passive observers can't tell a live conversation
+// from an idle one. shared between the settings commands and the swarm
+pub struct CoverTraffic {
+    enabled: AtomicBool,
+    padding_bytes: AtomicU64,
+    cover_bytes: AtomicU64,
+    cover_messages: AtomicU64,
+    // dm topics the last round of dummy messages actually went out on
+    cover_topics: AtomicU64,
+}
+
+// what the mode has cost this session, for the settings page
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverTrafficStatus {
+    pub enabled: bool,
+    pub padding_bytes: u64,
+    pub cover_bytes: u64,
+    pub cover_messages: u64,
+    // dummy traffic alone at the number of dm topics the last round reached,
+    // before gossip framing and mesh fan-out
+    pub estimated_bytes_per_hour: u64,
+}
+
+impl CoverTraffic {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            padding_bytes: AtomicU64::new(0),
+            cover_bytes: AtomicU64::new(0),
+            cover_messages: AtomicU64::new(0),
+            cover_topics: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn record_cover(&self, bytes: usize) {
+        self.cover_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.cover_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // publishes that failed, e.g. with no peers on the topic, cost nothing
+    // and don't count toward the estimate
+    pub fn record_round(&self, published: usize) {
+        self.cover_topics.store(published as u64, Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> CoverTrafficStatus {
+        let per_message = padded_len(cover_payload().len()) as u64;
+        let dm_topics = self.cover_topics.load(Ordering::Relaxed);
+        CoverTrafficStatus {
+            enabled: self.is_enabled(),
+            padding_bytes: self.padding_bytes.load(Ordering::Relaxed),
+            cover_bytes: self.cover_bytes.load(Ordering::Relaxed),
+            cover_messages: self.cover_messages.load(Ordering::Relaxed),
+            estimated_bytes_per_hour: dm_topics * per_message * 3600 / COVER_INTERVAL_SECS,
+        }
+    }
+}
+
+// size a payload of this length goes out with once padded
+pub fn padded_len(len: usize) -> usize {
+    PAD_BUCKETS
+        .iter()
+        .copied()
+        .find(|bucket| *bucket >= len)
+        .unwrap_or(len)
+}
+
+// a dummy message, receivers drop it without a trace
+pub fn cover_payload() -> Vec<u8> {
+    let cover = crate::protocol::messages::GossipMessage::Cover {
+        nonce: rand::thread_rng().gen(),
+    };
+    serde_json::to_vec(&cover).unwrap_or_default()
+}
+
+pub fn next_cover_delay() -> Duration {
+    let jitter = rand::thread_rng().gen_range(0..=COVER_JITTER_SECS * 1000);
+    Duration::from_millis((COVER_INTERVAL_SECS - COVER_JITTER_SECS / 2) * 1000 + jitter)
+}
+
+// gossipsub hook that pads outgoing payloads while the mode is on. inbound
+// payloads are left untouched so message ids match peers on older versions
+pub struct PaddingTransform {
+    cover: Arc<CoverTraffic>,
+}
+
+impl PaddingTransform {
+    pub fn new(cover: Arc<CoverTraffic>) -> Self {
+        Self { cover }
+    }
+}
+
+impl gossipsub::DataTransform for PaddingTransform {
+    fn inbound_transform(
+        &self,
+        raw_message: gossipsub::RawMessage,
+    ) -> Result<gossipsub::Message, std::io::Error> {
+        Ok(gossipsub::Message {
+            source: raw_message.source,
+            data: raw_message.data,
+            sequence_number: raw_message.sequence_number,
+            topic: raw_message.topic,
+        })
+    }
+
+    fn outbound_transform(
+        &self,
+        _topic: &gossipsub::TopicHash,
+        mut data: Vec<u8>,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        if self.cover.is_enabled() {
+            let target = padded_len(data.len());
+            self.cover
+                .padding_bytes
+                .fetch_add((target - data.len()) as u64, Ordering::Relaxed);
+            data.resize(target, b' ');
+        }
+        Ok(data)
+    }
+}
@@ -0,0 +1,31 @@
+use serde::Serialize;
+
+// a buffer this size holds most chat and signaling payloads without growing
+const INITIAL_CAPACITY: usize = 4096;
+
+// serializes gossip payloads through one buffer kept for the life of the
+// node loop. serde_json::to_vec starts small and doubles for every larger
+// message, this grows once and hands out exactly sized copies
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::with_capacity(INITIAL_CAPACITY),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&mut self, value: &T) -> serde_json::Result<Vec<u8>> {
+        self.buf.clear();
+        serde_json::to_writer(&mut self.buf, value)?;
+        Ok(self.buf.as_slice().to_vec())
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,6 +1,10 @@
 // gossipsub topic naming conventions for the dusk protocol
 // topics encode the routing path for different message types
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
 pub fn topic_for_messages(community_id: &str, channel_id: &str) -> String {
     format!(
         "dusk/community/{}/channel/{}/messages",
@@ -15,6 +19,30 @@ pub fn topic_for_typing(community_id: &str, channel_id: &str) -> String {
     )
 }
 
+// every mention in a community is published here as well as on its channel,
+// so members following only the channels they use still get pinged
+pub fn topic_for_mentions(community_id: &str) -> String {
+    format!("dusk/community/{}/mentions", community_id)
+}
+
+// with interest-based subscriptions on, channels not opened within this
+// window are left unsubscribed
+pub const CHANNEL_INTEREST_SECS: u64 = 7 * 24 * 60 * 60;
+
+// community and channel ids of a channel message or typing topic
+pub fn channel_of_topic(topic: &str) -> Option<(&str, &str)> {
+    let mut parts = topic.strip_prefix("dusk/community/")?.split('/');
+    let community_id = parts.next()?;
+    if parts.next()? != "channel" {
+        return None;
+    }
+    let channel_id = parts.next()?;
+    match parts.next()? {
+        "messages" | "typing" => Some((community_id, channel_id)),
+        _ => None,
+    }
+}
+
 pub fn topic_for_presence(community_id: &str) -> String {
     format!("dusk/community/{}/presence", community_id)
 }
@@ -23,14 +51,33 @@ pub fn topic_for_meta(community_id: &str) -> String {
     format!("dusk/community/{}/meta", community_id)
 }
 
+// incremental changes to the community's collaborative notes
+pub fn topic_for_notes(community_id: &str) -> String {
+    format!("dusk/community/{}/notes", community_id)
+}
+
 // global topic for user profile announcements and directory discovery
 pub fn topic_for_directory() -> String {
     "dusk/directory".to_string()
 }
 
-// global sync topic used to exchange full document snapshots between peers
-pub fn topic_for_sync() -> String {
-    "dusk/sync".to_string()
+// profile announcements meant for one community's members only
+pub fn topic_for_community_directory(community_id: &str) -> String {
+    format!("dusk/community/{}/directory", community_id)
+}
+
+// document snapshots and sync requests for one community. only peers who
+// know the community id subscribe, so nobody else learns it exists
+pub fn topic_for_community_sync(community_id: &str) -> String {
+    format!("dusk/community/{}/sync", community_id)
+}
+
+// community id of a community sync topic
+pub fn community_of_sync_topic(topic: &str) -> Option<&str> {
+    topic
+        .strip_prefix("dusk/community/")?
+        .strip_suffix("/sync")
+        .filter(|community_id| !community_id.contains('/'))
 }
 
 // voice signaling topic for webrtc sdp/ice exchange and presence
@@ -41,20 +88,74 @@ pub fn topic_for_voice(community_id: &str, channel_id: &str) -> String {
     )
 }
 
+// community id used in voice sdp/ice messages that belong to a 1:1 dm call,
+// those are published on the dm pair topic instead of a voice channel topic
+pub const DM_CALL_SCOPE: &str = "dm";
+
 // personal inbox topic for receiving first-time dms from peers we haven't
 // subscribed to yet. every peer subscribes to their own inbox on startup.
 pub fn topic_for_dm_inbox(peer_id: &str) -> String {
     format!("dusk/dm/inbox/{}", peer_id)
 }
 
-// dm topic between two peers, sorted alphabetically so both peers derive the same topic
-pub fn topic_for_dm(peer_a: &str, peer_b: &str) -> String {
+// dm pair topics are named after a hash of both peer ids and an epoch, so
+// subscriptions seen on the mesh don't say who talks to whom and the name
+// changes every epoch. anyone who already suspects a pair can still compute it
+pub const DM_TOPIC_EPOCH_SECS: u64 = 24 * 60 * 60;
+
+pub fn dm_topic_epoch() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / DM_TOPIC_EPOCH_SECS
+}
+
+// time left until the next dm topic epoch starts
+pub fn until_next_dm_epoch() -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    Duration::from_secs(DM_TOPIC_EPOCH_SECS - now % DM_TOPIC_EPOCH_SECS)
+}
+
+// dm topic between two peers for an epoch, sorted so both peers derive the same topic
+pub fn topic_for_dm_epoch(peer_a: &str, peer_b: &str, epoch: u64) -> String {
     let (first, second) = if peer_a < peer_b {
         (peer_a, peer_b)
     } else {
         (peer_b, peer_a)
     };
-    format!("dusk/dm/{}/{}", first, second)
+    let mut hasher = Sha256::new();
+    hasher.update(b"dusk-dm-topic");
+    hasher.update(first.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(second.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(epoch.to_be_bytes());
+    format!("dusk/dm/{}", hex::encode(&hasher.finalize()[..16]))
+}
+
+// the topic dms are published on right now
+pub fn topic_for_dm(peer_a: &str, peer_b: &str) -> String {
+    topic_for_dm_epoch(peer_a, peer_b, dm_topic_epoch())
+}
+
+// topics to listen on for a pair: the current epoch and both neighbours, so
+// messages sent around an epoch change or by a peer with a skewed clock arrive
+pub fn topics_for_dm(peer_a: &str, peer_b: &str) -> Vec<String> {
+    let epoch = dm_topic_epoch();
+    (epoch.saturating_sub(1)..=epoch + 1)
+        .map(|e| topic_for_dm_epoch(peer_a, peer_b, e))
+        .collect()
+}
+
+pub fn is_dm_pair_topic(topic: &str) -> bool {
+    topic
+        .strip_prefix("dusk/dm/")
+        .map(|rest| !rest.is_empty() && !rest.contains('/'))
+        .unwrap_or(false)
 }
 
 // derive a stable conversation id from two peer ids
@@ -1,7 +1,21 @@
+pub mod announce;
 pub mod behaviour;
+pub mod cache;
+pub mod calls;
+pub mod clock;
+pub mod connections;
+pub mod cover;
 pub mod discovery;
+pub mod encode;
 pub mod gossip;
+pub mod netem;
+pub mod power;
+pub mod prevalidate;
+pub mod replay;
+pub mod scoring;
+pub mod speaking;
 pub mod swarm;
+pub mod watchdog;
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -10,9 +24,15 @@ use tauri::Emitter;
 use tokio::sync::Mutex;
 
 use crate::crdt::CrdtEngine;
-use crate::protocol::identity::DirectoryEntry;
+use crate::protocol::community::{MembershipAction, ModerationCapability};
+use crate::protocol::identity::{DirectoryEntry, KeyConflict, VerificationPolicy};
 use crate::verification;
 
+// rendezvous namespace every discoverable peer registers under, what
+// global peer discovery browses. peers who turned relay_discoverable off
+// stay out of it
+pub const GLOBAL_PEERS_NAMESPACE: &str = "dusk/peers";
+
 // default public relay - override with DUSK_RELAY_ADDR env var
 const DEFAULT_RELAY_ADDR: &str =
     "/dns4/relay.duskchat.app/tcp/4001/p2p/12D3KooWGQkCkACcibJPKzus7Q6U1aYngfTuS4gwYwmJkJJtrSaw";
@@ -29,6 +49,28 @@ const PENDING_QUEUE_TTL_SECS: u64 = 600;
 // grace period before warning the frontend about relay being down,
 // prevents banner flashing on transient disconnections
 const RELAY_WARN_GRACE_SECS: u64 = 8;
+// wall clock is sampled this often to notice the machine waking from sleep
+const WAKE_CHECK_TICK_SECS: u64 = 5;
+// a wall clock gap this much larger than the tick means we were suspended
+const WAKE_GAP_THRESHOLD_SECS: u64 = 20;
+// catch-up cursors are widened by this much to absorb clock skew, dedup drops repeats
+const CATCHUP_SLACK_MS: u64 = 60_000;
+// peers asked for catch-up right after subscribing to a topic
+const CATCHUP_MAX_PEERS: usize = 3;
+// diverged merges without concrete conflicts are only reported after a partition this long
+const DIVERGENCE_WARN_SECS: u64 = 3600;
+// how long the swarm keeps running after shutdown to send what is queued
+const SHUTDOWN_DRAIN_MS: u64 = 300;
+// address book entries unused for this long are dropped instead of redialed
+const ADDRESS_BOOK_TTL_SECS: u64 = 14 * 24 * 3600;
+// peers redialed from the address book on start, most recently seen first
+const ADDRESS_BOOK_DIAL_LIMIT: usize = 32;
+// how often owned community docs are checked for new heads to sign
+const CHECKPOINT_TICK_SECS: u64 = 600;
+// epochs before the listening window whose dm topics are left on rotation
+const DM_TOPIC_STALE_EPOCHS: u64 = 2;
+// how often read disappearing messages are checked for an expired timer
+const DM_EXPIRY_TICK_SECS: u64 = 30;
 
 #[derive(Clone)]
 struct RelayConfig {
@@ -147,6 +189,16 @@ fn queue_namespace_unique(queue: &mut Vec<String>, namespace: String) {
     }
 }
 
+// app state the node shares with commands, cloned handles to the same data
+pub struct SharedState {
+    pub crdt_engine: Arc<Mutex<CrdtEngine>>,
+    pub storage: Arc<crate::storage::DiskStorage>,
+    pub voice_channels: VoiceChannelMap,
+    pub hlc_clock: Arc<Mutex<clock::HybridClock>>,
+    pub cover_traffic: Arc<cover::CoverTraffic>,
+    pub latency: Arc<crate::latency::LatencyTracker>,
+}
+
 // handle to the running p2p node, used to stop it
 pub struct NodeHandle {
     pub task: JoinHandle<()>,
@@ -161,6 +213,16 @@ pub enum NodeCommand {
         topic: String,
         data: Vec<u8>,
     },
+    // hand our copy of a community's doc to its connected members
+    PushSnapshot {
+        community_id: String,
+    },
+    // publish a dm on the pair topic, and on the peer's inbox topic too
+    // unless the peer is already known to be on the pair topic
+    SendDirectMessage {
+        peer_id: String,
+        data: Vec<u8>,
+    },
     Subscribe {
         topic: String,
     },
@@ -171,6 +233,10 @@ pub enum NodeCommand {
     GetListenAddrs {
         reply: tokio::sync::oneshot::Sender<Vec<String>>,
     },
+    // rich presence text, None clears it. republishes presence right away
+    SetActivity {
+        activity: Option<String>,
+    },
     // broadcast our presence status to all community presence topics
     BroadcastPresence {
         status: crate::protocol::messages::PeerStatus,
@@ -198,8 +264,13 @@ pub enum NodeCommand {
     },
     // register this peer's profile in the relay's persistent directory
     DirectoryRegister,
-    // remove this peer's profile from the relay's directory
-    DirectoryRemove,
+    // remove this peer's profile from the relay's directory, the reply
+    // comes once the relay answered
+    DirectoryRemove {
+        reply: tokio::sync::oneshot::Sender<
+            Result<Vec<crate::protocol::directory::DirectoryProfileEntry>, String>,
+        >,
+    },
     // search the relay's directory by display_name or peer_id
     DirectorySearch {
         query: String,
@@ -215,12 +286,74 @@ pub enum NodeCommand {
     SetRelayDiscoverable {
         enabled: bool,
     },
+    // start or stop friend presence heartbeats to the relay
+    SetRelayPresence {
+        enabled: bool,
+    },
+    // schedule a profile announcement, subject to the announce policy
+    AnnounceProfile {
+        trigger: announce::AnnounceTrigger,
+    },
+    // fetch a page of channel history from an online member of the community
+    FetchHistory {
+        community_id: String,
+        channel_id: String,
+        before: Option<u64>,
+        limit: u32,
+        reply: tokio::sync::oneshot::Sender<
+            Result<Vec<crate::protocol::messages::ChatMessage>, String>,
+        >,
+    },
+    // ask a participant already on the voice topic who is in the call, the
+    // answer is merged into the tracked participants and returned
+    FetchVoiceRoster {
+        community_id: String,
+        channel_id: String,
+        reply: tokio::sync::oneshot::Sender<
+            Result<Vec<crate::protocol::messages::VoiceParticipant>, String>,
+        >,
+    },
+    // switch between auto, normal and low power networking
+    SetNetworkProfile {
+        profile: power::NetworkProfile,
+    },
+    // effective networking profile and the os signals behind it
+    GetNetworkProfile {
+        reply: tokio::sync::oneshot::Sender<power::NetworkProfileStatus>,
+    },
+    // local microphone level from the frontend, fed through speaking hysteresis
+    ReportVoiceActivity {
+        community_id: String,
+        channel_id: String,
+        level: f32,
+    },
+    // publish a dm call signal from the local user and track the call's ring state
+    SendCallSignal {
+        signal: crate::protocol::messages::DMCallSignal,
+    },
+    // mobile background sync, keeps only the relay link alive while suspended
+    SetBackgroundMode {
+        enabled: bool,
+    },
+    // snapshot gossipsub and application scores for every connected peer
+    GetPeerScores {
+        reply: tokio::sync::oneshot::Sender<Vec<scoring::PeerScore>>,
+    },
+    // connection counts by priority and recent evictions
+    GetConnectionReport {
+        reply: tokio::sync::oneshot::Sender<connections::ConnectionReport>,
+    },
     // request time-limited TURN server credentials from the relay
     GetTurnCredentials {
         reply: tokio::sync::oneshot::Sender<
             Result<crate::protocol::turn::TurnCredentialResponse, String>,
         >,
     },
+    // simulated latency and loss on gossip, set from the dev server
+    #[cfg(feature = "dev-server")]
+    SetNetem {
+        config: netem::NetemConfig,
+    },
 }
 
 // events emitted from the node to the tauri frontend
@@ -229,6 +362,16 @@ pub enum NodeCommand {
 pub enum DuskEvent {
     #[serde(rename = "message_received")]
     MessageReceived(crate::protocol::messages::ChatMessage),
+    // an optimistic send was stored and published, as stored
+    #[serde(rename = "message_committed")]
+    MessageCommitted(crate::protocol::messages::ChatMessage),
+    // an optimistic send was refused, nothing was stored or published
+    #[serde(rename = "message_failed")]
+    MessageFailed {
+        message_id: String,
+        channel_id: String,
+        error: String,
+    },
     #[serde(rename = "message_edited")]
     MessageEdited {
         message_id: String,
@@ -236,6 +379,13 @@ pub enum DuskEvent {
     },
     #[serde(rename = "message_deleted")]
     MessageDeleted { message_id: String },
+    // a member reacted to a channel message or took a reaction back
+    #[serde(rename = "reaction_updated")]
+    ReactionUpdated {
+        community_id: String,
+        message_id: String,
+        reactions: Vec<crate::protocol::messages::MessageReaction>,
+    },
     #[serde(rename = "member_kicked")]
     MemberKicked { peer_id: String },
     #[serde(rename = "peer_connected")]
@@ -243,7 +393,19 @@ pub enum DuskEvent {
     #[serde(rename = "peer_disconnected")]
     PeerDisconnected { peer_id: String },
     #[serde(rename = "presence_updated")]
-    PresenceUpdated { peer_id: String, status: String },
+    PresenceUpdated {
+        peer_id: String,
+        status: String,
+        activity: Option<String>,
+        status_message: String,
+        status_emoji: String,
+    },
+    // a friend came online or went offline according to the relay
+    #[serde(rename = "friend_presence")]
+    FriendPresence { peer_id: String, online: bool },
+    // our own activity changed, possibly from the activity pipe
+    #[serde(rename = "local_activity_changed")]
+    LocalActivityChanged { activity: Option<String> },
     #[serde(rename = "typing")]
     Typing { peer_id: String, channel_id: String },
     #[serde(rename = "node_status")]
@@ -253,17 +415,64 @@ pub enum DuskEvent {
     },
     #[serde(rename = "sync_complete")]
     SyncComplete { community_id: String },
+    #[serde(rename = "history_backfilled")]
+    HistoryBackfilled {
+        community_id: String,
+        channel_id: String,
+    },
     #[serde(rename = "profile_received")]
     ProfileReceived {
         peer_id: String,
         display_name: String,
         bio: String,
         public_key: String,
+        verified: bool,
     },
     #[serde(rename = "profile_revoked")]
     ProfileRevoked { peer_id: String },
+    #[serde(rename = "community_profile_updated")]
+    CommunityProfileUpdated(crate::protocol::community::CommunityProfile),
+    // a member's edit reached a note, carries the note as it now reads
+    #[serde(rename = "note_updated")]
+    NoteUpdated(crate::protocol::notes::Note),
     #[serde(rename = "relay_status")]
     RelayStatus { connected: bool },
+    #[serde(rename = "network_changed")]
+    NetworkChanged {
+        mode: String,
+        // the relay's host answered the watchdog's probe
+        internet_reachable: bool,
+        relay_connected: bool,
+    },
+    #[serde(rename = "node_resumed")]
+    NodeResumed { slept_secs: u64 },
+    #[serde(rename = "network_profile_changed")]
+    NetworkProfileChanged(power::NetworkProfileStatus),
+    #[serde(rename = "clock_skew_detected")]
+    ClockSkewDetected { peer_id: String, skew_ms: u64 },
+    #[serde(rename = "community_diverged")]
+    CommunityDiverged {
+        community_id: String,
+        partition_secs: Option<u64>,
+        conflicts: Vec<crate::protocol::community::DocConflict>,
+    },
+    #[serde(rename = "update_progress")]
+    UpdateProgress {
+        version: String,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    // verified and handed to the installer, the app restarts next
+    #[serde(rename = "update_downloaded")]
+    UpdateDownloaded { version: String },
+    #[serde(rename = "data_migration_progress")]
+    DataMigrationProgress { copied: usize, total: usize },
+    #[serde(rename = "export_progress")]
+    ExportProgress {
+        conversation_id: String,
+        written: usize,
+        total: usize,
+    },
     #[serde(rename = "voice_participant_joined")]
     VoiceParticipantJoined {
         community_id: String,
@@ -271,6 +480,7 @@ pub enum DuskEvent {
         peer_id: String,
         display_name: String,
         media_state: crate::protocol::messages::VoiceMediaState,
+        stage_role: crate::protocol::messages::StageRole,
     },
     #[serde(rename = "voice_participant_left")]
     VoiceParticipantLeft {
@@ -278,6 +488,37 @@ pub enum DuskEvent {
         channel_id: String,
         peer_id: String,
     },
+    // our join hit the channel's capacity, the node already left the call
+    #[serde(rename = "voice_join_rejected")]
+    VoiceJoinRejected {
+        community_id: String,
+        channel_id: String,
+        max_participants: u32,
+    },
+    // a moderator took us out of the call, join move_to when it is set
+    #[serde(rename = "voice_disconnected")]
+    VoiceDisconnected {
+        community_id: String,
+        channel_id: String,
+        by: String,
+        move_to: Option<String>,
+    },
+    #[serde(rename = "stage_hand_raised")]
+    StageHandRaised {
+        community_id: String,
+        channel_id: String,
+        peer_id: String,
+        raised: bool,
+    },
+    // when peer_id is us the frontend adds or drops its microphone track
+    #[serde(rename = "stage_role_changed")]
+    StageRoleChanged {
+        community_id: String,
+        channel_id: String,
+        peer_id: String,
+        role: crate::protocol::messages::StageRole,
+        by: String,
+    },
     #[serde(rename = "voice_media_state_changed")]
     VoiceMediaStateChanged {
         community_id: String,
@@ -285,6 +526,13 @@ pub enum DuskEvent {
         peer_id: String,
         media_state: crate::protocol::messages::VoiceMediaState,
     },
+    #[serde(rename = "voice_speaking_changed")]
+    VoiceSpeakingChanged {
+        community_id: String,
+        channel_id: String,
+        peer_id: String,
+        speaking: bool,
+    },
     #[serde(rename = "voice_sdp_received")]
     VoiceSdpReceived {
         community_id: String,
@@ -306,1288 +554,4023 @@ pub enum DuskEvent {
     DMReceived(crate::protocol::messages::DirectMessage),
     #[serde(rename = "dm_typing")]
     DMTyping { peer_id: String },
+    #[serde(rename = "incoming_call")]
+    IncomingCall {
+        call_id: String,
+        peer_id: String,
+        display_name: String,
+    },
+    #[serde(rename = "call_accepted")]
+    CallAccepted { call_id: String, peer_id: String },
+    #[serde(rename = "call_ended")]
+    CallEnded(crate::protocol::messages::CallRecord),
+    // the caller hung up while we were still ringing
+    #[serde(rename = "call_cancelled")]
+    CallCancelled(crate::protocol::messages::CallRecord),
+    #[serde(rename = "call_busy")]
+    CallBusy(crate::protocol::messages::CallRecord),
+    #[serde(rename = "call_timed_out")]
+    CallTimedOut(crate::protocol::messages::CallRecord),
+    // disappearing messages were proposed, agreed, declined or turned off
+    #[serde(rename = "dm_ephemeral_policy_changed")]
+    DMEphemeralPolicyChanged(crate::protocol::messages::DMEphemeralPolicy),
+    // our copies of these messages ran out their timer and were deleted
+    #[serde(rename = "dm_messages_expired")]
+    DMMessagesExpired {
+        peer_id: String,
+        message_ids: Vec<String>,
+    },
+    // the author of a dm deleted it for everyone
+    #[serde(rename = "dm_deleted")]
+    DMDeleted {
+        peer_id: String,
+        message_id: String,
+    },
+    // the peer confirmed deleting its copies
+    #[serde(rename = "dm_deletion_confirmed")]
+    DMDeletionConfirmed {
+        peer_id: String,
+        message_ids: Vec<String>,
+    },
+    // the author of a dm changed its text
+    #[serde(rename = "dm_edited")]
+    DMEdited(crate::protocol::messages::DirectMessage),
+    // the peer reacted to a dm or took a reaction back
+    #[serde(rename = "dm_reactions_changed")]
+    DMReactionsChanged {
+        peer_id: String,
+        message_id: String,
+        reactions: Vec<crate::protocol::messages::DMReaction>,
+    },
+    // a peer whose safety number we verified now presents a different key
+    #[serde(rename = "verified_key_changed")]
+    VerifiedKeyChanged {
+        peer_id: String,
+        display_name: String,
+    },
+    // a peer announced a key other than the one pinned for it, the
+    // announcement is held back until accept_new_key
+    #[serde(rename = "key_conflict")]
+    KeyConflict(crate::protocol::identity::KeyConflict),
+    // startup moved on to another phase
+    #[serde(rename = "boot_phase")]
+    BootPhase { phase: crate::boot::BootPhase },
+    // the app was launched again while running, args exclude the executable
+    #[serde(rename = "launch_forwarded")]
+    LaunchForwarded { args: Vec<String> },
 }
 
-// extract the community id from a gossipsub topic string
-fn community_id_from_topic(topic: &str) -> Option<&str> {
-    topic
-        .strip_prefix("dusk/community/")
-        .and_then(|rest| rest.split('/').next())
+// ask the other members of each community for their snapshot, on that
+// community's own sync topic, with the invite for communities we are still
+// joining. returns how many requests went out
+fn publish_sync_requests(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    requests: &[(String, Option<crate::protocol::community::InviteCode>)],
+) -> usize {
+    let peer_id = swarm.local_peer_id().to_string();
+    let mut published = 0;
+    for (community_id, invite) in requests {
+        let request = crate::crdt::sync::SyncMessage::RequestSync {
+            peer_id: peer_id.clone(),
+            community_id: community_id.clone(),
+            invite: invite.clone(),
+        };
+        let Ok(data) = serde_json::to_vec(&request) else {
+            continue;
+        };
+        let topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_community_sync(community_id));
+        match swarm.behaviour_mut().gossipsub.publish(topic, data) {
+            Ok(_) => published += 1,
+            Err(e) => log::debug!("sync: RequestSync publish failed for {}: {:?}", community_id, e),
+        }
+    }
+    published
 }
 
-// voice channel participant tracking type alias for readability
-pub type VoiceChannelMap =
-    Arc<Mutex<HashMap<String, Vec<crate::protocol::messages::VoiceParticipant>>>>;
-
-// build a signed profile announcement from the keypair and storage
-// used by the event loop to re-announce after relay connection or new peer joins
-fn build_profile_announcement(
-    keypair: &libp2p::identity::Keypair,
-    storage: &crate::storage::DiskStorage,
-) -> Option<crate::protocol::messages::ProfileAnnouncement> {
-    let profile = storage.load_profile().ok()?;
-    let proof = storage.load_verification_proof().ok().flatten();
-    let peer_id = libp2p::PeerId::from(keypair.public());
+// pending snapshot pushes keyed by request_response request id, with the
+// community the pushed doc belongs to
+type PendingSnapshots = HashMap<libp2p::request_response::OutboundRequestId, String>;
 
-    let mut announcement = crate::protocol::messages::ProfileAnnouncement {
-        peer_id: peer_id.to_string(),
-        display_name: profile.display_name,
-        bio: profile.bio,
-        public_key: hex::encode(keypair.public().encode_protobuf()),
-        timestamp: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64,
-        verification_proof: proof,
-        signature: String::new(),
+// hand our doc to one peer over the snapshot protocol. a snapshot carries the
+// community's whole history, so it never goes on the public sync topic
+fn send_snapshot(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    pending_snapshots: &mut PendingSnapshots,
+    peer: &libp2p::PeerId,
+    community_id: String,
+    doc_bytes: Vec<u8>,
+) {
+    let snapshot = crate::crdt::sync::DocumentSnapshot {
+        community_id: community_id.clone(),
+        doc_bytes,
     };
-    announcement.signature = verification::sign_announcement(keypair, &announcement);
-    Some(announcement)
+    let request_id = swarm.behaviour_mut().snapshot.send_request(peer, snapshot);
+    pending_snapshots.insert(request_id, community_id);
 }
 
-// publish our profile on the directory gossipsub topic so connected peers
-// learn about us and add us to their local directory
-fn publish_profile(
+// hand our doc to every connected peer our copy lists as a member, after a
+// local change or a checkpoint
+fn push_snapshot(
     swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
-    keypair: &libp2p::identity::Keypair,
-    storage: &crate::storage::DiskStorage,
+    pending_snapshots: &mut PendingSnapshots,
+    engine: &mut CrdtEngine,
+    community_id: &str,
 ) {
-    if let Some(announcement) = build_profile_announcement(keypair, storage) {
-        let msg = crate::protocol::messages::GossipMessage::ProfileAnnounce(announcement);
-        if let Ok(data) = serde_json::to_vec(&msg) {
-            let topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_directory());
-            let _ = swarm.behaviour_mut().gossipsub.publish(topic, data);
-        }
+    let Some(doc_bytes) = engine.get_doc_bytes(community_id) else {
+        return;
+    };
+    let members: HashSet<String> = engine
+        .get_members(community_id)
+        .map(|members| members.into_iter().map(|m| m.peer_id).collect())
+        .unwrap_or_default();
+    let peers: Vec<libp2p::PeerId> = swarm
+        .connected_peers()
+        .filter(|peer| members.contains(&peer.to_string()))
+        .copied()
+        .collect();
+    for peer in &peers {
+        send_snapshot(
+            swarm,
+            pending_snapshots,
+            peer,
+            community_id.to_string(),
+            doc_bytes.clone(),
+        );
     }
 }
 
-// build the full relay circuit multiaddr string for this peer
-// e.g. /dns4/relay.duskchat.app/tcp/4001/p2p/<relay_id>/p2p-circuit/p2p/<local_id>
-fn build_circuit_addr(relay_multiaddr: &libp2p::Multiaddr, local_peer_id: libp2p::PeerId) -> String {
-    relay_multiaddr
-        .clone()
-        .with(libp2p::multiaddr::Protocol::P2pCircuit)
-        .with(libp2p::multiaddr::Protocol::P2p(local_peer_id))
-        .to_string()
+// how a snapshot from a peer went
+enum SnapshotMerge {
+    // not a community we hold, or it failed verification
+    Ignored,
+    // the bytes did not merge, worth a penalty
+    Malformed,
+    // merged. corrected when unverified joiners were dropped afterwards and
+    // the other members need our copy
+    Merged { corrected: bool },
 }
 
-// send a directory registration to the relay so other peers can discover
-// and connect to us via the circuit address
-fn register_directory(
+// verify and merge a snapshot a peer sent us, then subscribe to the topics of
+// the channels it brought in
+async fn merge_snapshot(
+    snapshot: crate::crdt::sync::DocumentSnapshot,
     swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
-    relay_peer: &libp2p::PeerId,
+    crdt_engine: &Arc<Mutex<CrdtEngine>>,
     storage: &crate::storage::DiskStorage,
-    relay_multiaddr: &libp2p::Multiaddr,
-    local_peer_id: libp2p::PeerId,
-) {
-    let profile = storage.load_profile().unwrap_or_default();
-    let circuit_addr = build_circuit_addr(relay_multiaddr, local_peer_id);
+    unverified_peers: &HashSet<String>,
+    node_keypair: &libp2p::identity::Keypair,
+    app_handle: &tauri::AppHandle,
+) -> SnapshotMerge {
     log::info!(
-        "directory: sending Register to relay {} (name='{}', circuit='{}')",
-        relay_peer,
-        profile.display_name,
-        circuit_addr,
-    );
-    swarm.behaviour_mut().directory_service.send_request(
-        relay_peer,
-        crate::protocol::directory::DirectoryRequest::Register {
-            display_name: profile.display_name,
-            relay_addr: circuit_addr,
-        },
+        "sync: received snapshot for community {} ({} bytes)",
+        snapshot.community_id,
+        snapshot.doc_bytes.len()
     );
-}
-
-// start the p2p node on a background task
-pub async fn start(
-    keypair: libp2p::identity::Keypair,
-    crdt_engine: Arc<Mutex<CrdtEngine>>,
-    storage: Arc<crate::storage::DiskStorage>,
-    app_handle: tauri::AppHandle,
-    voice_channels: VoiceChannelMap,
-    pending_join_role_guard: Arc<Mutex<HashSet<String>>>,
-    custom_relay_addr: Option<String>,
-) -> Result<NodeHandle, String> {
-    let mut swarm_instance =
-        swarm::build_swarm(&keypair).map_err(|e| format!("failed to build swarm: {}", e))?;
+    let community_id = snapshot.community_id;
+    let mut engine = crdt_engine.lock().await;
 
-    // listen on all interfaces for LAN peer discovery via mDNS
-    swarm_instance
-        .listen_on("/ip4/0.0.0.0/tcp/0".parse().unwrap())
-        .map_err(|e| format!("failed to listen: {}", e))?;
+    // only merge docs for communities we've explicitly joined or created,
+    // otherwise any peer could push all their communities to us
+    if !engine.has_community(&community_id) || engine.is_archived(&community_id) {
+        log::info!(
+            "sync: ignoring snapshot for unknown community {}",
+            community_id
+        );
+        return SnapshotMerge::Ignored;
+    }
 
-    let (command_tx, mut command_rx) = tokio::sync::mpsc::channel::<NodeCommand>(256);
+    // a first snapshot for a community we are joining must chain back
+    // to an owner-signed checkpoint, not just any member's doc bytes
+    if let Err(e) = engine.verify_snapshot(&community_id, &snapshot.doc_bytes) {
+        log::warn!("sync: rejecting snapshot for {}: {}", community_id, e);
+        return SnapshotMerge::Ignored;
+    }
 
-    // emit initial node status
-    let _ = app_handle.emit(
-        "dusk-event",
-        DuskEvent::NodeStatus {
-            is_connected: false,
-            peer_count: 0,
-        },
+    let members_before: HashSet<String> = engine
+        .get_members(&community_id)
+        .map(|members| members.into_iter().map(|m| m.peer_id).collect())
+        .unwrap_or_default();
+    let outcome = match engine.merge_remote_doc(&community_id, &snapshot.doc_bytes) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            log::warn!("sync: merge failed for community {}: {}", community_id, e);
+            return SnapshotMerge::Malformed;
+        }
+    };
+    let member_count = engine
+        .get_members(&community_id)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    log::info!(
+        "sync: merge success for community {}, now have {} members",
+        community_id,
+        member_count
     );
 
-    // resolve validated relay and bootstrap peer configuration for WAN connectivity
-    let relay_config = resolve_relay_config(custom_relay_addr.as_deref());
-    if let Some(cfg) = relay_config.as_ref() {
-        log::info!(
-            "using relay {} (peer {}) from {}",
-            cfg.addr,
-            cfg.peer_id,
-            cfg.source
+    // both halves wrote while apart, warn so a moderator
+    // can review what the merge interleaved
+    let long_partition = outcome
+        .partition_secs
+        .is_none_or(|secs| secs >= DIVERGENCE_WARN_SECS);
+    if outcome.diverged && (!outcome.conflicts.is_empty() || long_partition) {
+        log::warn!(
+            "sync: community {} diverged, {} conflicting keys",
+            community_id,
+            outcome.conflicts.len()
         );
-    }
-    let relay_multiaddr = relay_config.as_ref().map(|cfg| cfg.addr.clone());
-    let relay_peer_id = relay_config.as_ref().map(|cfg| cfg.peer_id);
-
-    let bootstrap_nodes = bootstrap_peers(relay_config.as_ref());
-    if !bootstrap_nodes.is_empty() {
-        log::info!(
-            "configured {} WAN bootstrap peer(s) (relay + optional {})",
-            bootstrap_nodes.len(),
-            DUSK_BOOTSTRAP_PEERS_ENV
+        let _ = app_handle.emit(
+            "dusk-event",
+            DuskEvent::CommunityDiverged {
+                community_id: community_id.clone(),
+                partition_secs: outcome.partition_secs,
+                conflicts: outcome.conflicts.clone(),
+            },
         );
     }
-    for (addr, peer) in &bootstrap_nodes {
-        swarm_instance
-            .behaviour_mut()
-            .kademlia
-            .add_address(peer, addr.clone());
-    }
 
-    // if a relay is configured, dial it immediately
-    // don't emit RelayStatus here -- the store defaults to connected=true so
-    // no warning shows during the initial handshake. the warning only appears
-    // if the dial actually fails (OutgoingConnectionError) or the connection drops.
-    if let Some(ref addr) = relay_multiaddr {
-        log::info!("relay dial start (startup): {}", addr);
-        if let Err(e) = swarm_instance.dial(addr.clone()) {
-            log::warn!("relay dial failed (startup): {}", e);
-            // emit disconnected status immediately if dial fails
-            let _ = app_handle.emit("dusk-event", DuskEvent::RelayStatus { connected: false });
-        } else {
-            log::info!("relay dial initiated (startup)");
-        }
-    } else {
-        // if relay address is invalid or not configured, emit disconnected status
+    let channels_after_merge = engine.get_channels(&community_id).unwrap_or_default();
+    let removed = remove_unverified_joiners(
+        &mut engine,
+        storage,
+        unverified_peers,
+        &community_id,
+        &members_before,
+        node_keypair,
+    );
+    if !removed.is_empty() {
         log::warn!(
-            "no valid relay address configured from DUSK_RELAY_ADDR/custom/default, running in LAN-only mode"
+            "removed {} unverified joiners from {} per community policy",
+            removed.len(),
+            community_id
         );
-        let _ = app_handle.emit("dusk-event", DuskEvent::RelayStatus { connected: false });
+    }
+    drop(engine);
+
+    // keep topic subscriptions aligned with merged channels
+    let presence_topic =
+        libp2p::gossipsub::IdentTopic::new(gossip::topic_for_presence(&community_id));
+    let _ = swarm.behaviour_mut().gossipsub.subscribe(&presence_topic);
+
+    for channel in &channels_after_merge {
+        let messages_topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_messages(
+            &community_id,
+            &channel.id,
+        ));
+        let _ = swarm.behaviour_mut().gossipsub.subscribe(&messages_topic);
+
+        let typing_topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_typing(
+            &community_id,
+            &channel.id,
+        ));
+        let _ = swarm.behaviour_mut().gossipsub.subscribe(&typing_topic);
     }
 
-    // clone the keypair into the event loop so it can re-announce our profile
-    // when new peers connect or the relay comes online
-    let node_keypair = keypair;
+    let _ = app_handle.emit("dusk-event", DuskEvent::SyncComplete { community_id });
 
-    let task = tauri::async_runtime::spawn(async move {
-        use futures::StreamExt;
+    SnapshotMerge::Merged {
+        corrected: !removed.is_empty(),
+    }
+}
 
-        // track connected peers for accurate count
-        let mut connected_peers: HashSet<String> = HashSet::new();
+// extract the community id from a gossipsub topic string
+fn community_id_from_topic(topic: &str) -> Option<&str> {
+    topic
+        .strip_prefix("dusk/community/")
+        .and_then(|rest| rest.split('/').next())
+}
 
-        // dedup set for dm message ids -- messages arrive on both the pair topic
-        // and inbox topic so we need to skip duplicates
-        let mut seen_dm_ids: HashSet<String> = HashSet::new();
+// apply a chat message from the mesh or a catch-up reply: dedup, merge the
+// sender's logical clock, append to the community doc and notify the frontend
+async fn ingest_chat_message(
+    mut chat_msg: crate::protocol::messages::ChatMessage,
+    community_id: Option<&str>,
+    seen_chat_ids: &mut HashSet<String>,
+    hlc_clock: &Arc<Mutex<clock::HybridClock>>,
+    crdt_engine: &Arc<Mutex<CrdtEngine>>,
+    app_handle: &tauri::AppHandle,
+) -> bool {
+    if !seen_chat_ids.insert(chat_msg.id.clone()) {
+        return false;
+    }
+    // cap the dedup set to prevent unbounded memory growth
+    if seen_chat_ids.len() > 10000 {
+        seen_chat_ids.clear();
+    }
 
-        // track whether we have a relay reservation
-        let mut relay_reservation_active = false;
+    // only our own sends went through validate_attachments
+    crate::protocol::messages::filter_attachments(&mut chat_msg.attachments, &mut chat_msg.embeds);
 
-        // track the relay peer id for rendezvous operations
-        let relay_peer = relay_peer_id;
+    // peers without a logical clock only send wall time
+    if chat_msg.hlc == crate::protocol::messages::Hlc::default() {
+        chat_msg.hlc = crate::protocol::messages::Hlc {
+            wall: chat_msg.timestamp,
+            counter: 0,
+        };
+    }
 
-        // community namespaces we need to register on rendezvous
-        // queued until the relay connection is ready
-        let mut pending_registrations: Vec<String> = Vec::new();
-        let mut pending_discoveries: Vec<String> = Vec::new();
-        // timestamp when pending items were first queued (for TTL cleanup)
-        let mut pending_queued_at: Option<std::time::Instant> = None;
+    // merge the sender's clock, a sender far in the future is
+    // re-stamped locally so it cannot pin messages to the bottom
+    let mut clock = hlc_clock.lock().await;
+    if let Err(skew_ms) = clock.observe(chat_msg.hlc) {
+        log::warn!(
+            "clock of {} is {}ms ahead, re-stamping message {}",
+            chat_msg.author_id,
+            skew_ms,
+            chat_msg.id
+        );
+        chat_msg.hlc = clock.now();
+        let _ = app_handle.emit(
+            "dusk-event",
+            DuskEvent::ClockSkewDetected {
+                peer_id: chat_msg.author_id.clone(),
+                skew_ms,
+            },
+        );
+    }
+    drop(clock);
+
+    let appended = match community_id {
+        Some(community_id) => {
+            let mut engine = crdt_engine.lock().await;
+            match engine.append_message(community_id, &chat_msg) {
+                Ok(true) => true,
+                // already in the document, e.g. merged earlier via sync
+                Ok(false) => return false,
+                Err(_) => false,
+            }
+        }
+        None => false,
+    };
+    let _ = app_handle.emit("dusk-event", DuskEvent::MessageReceived(chat_msg));
+    appended
+}
 
-        // rendezvous registration/rediscovery refresh interval
-        let mut rendezvous_tick =
-            tokio::time::interval(std::time::Duration::from_secs(RENDEZVOUS_TICK_SECS));
-        // periodic Kademlia bootstrap fallback for WAN resilience
-        let mut kad_bootstrap_tick =
-            tokio::time::interval(std::time::Duration::from_secs(KAD_BOOTSTRAP_TICK_SECS));
+// copy an announcement into every channel following the one it was posted in,
+// returning the copies to publish. only original posts by an owner or admin of
+// an announcement channel are mirrored, already mirrored posts are not passed
+// on again so two communities following each other can't loop
+async fn mirror_followed_announcement(
+    chat_msg: &crate::protocol::messages::ChatMessage,
+    topic: &str,
+    local_peer_id: &str,
+    storage: &crate::storage::DiskStorage,
+    hlc_clock: &Arc<Mutex<clock::HybridClock>>,
+    crdt_engine: &Arc<Mutex<CrdtEngine>>,
+    app_handle: &tauri::AppHandle,
+) -> Vec<(String, Vec<u8>)> {
+    let mut outgoing = Vec::new();
+    if chat_msg.forwarded_from.is_some() {
+        return outgoing;
+    }
+    let (Some(community_id), Some(channel_id)) =
+        (community_id_from_topic(topic), channel_id_from_topic(topic))
+    else {
+        return outgoing;
+    };
+    if chat_msg.channel_id != channel_id {
+        return outgoing;
+    }
+    let follows = storage
+        .load_channel_followers(community_id, channel_id)
+        .unwrap_or_default();
+    if follows.is_empty() {
+        return outgoing;
+    }
 
-        // namespaces we actively register under
-        let mut register_namespaces: HashSet<String> = HashSet::new();
+    let mut engine = crdt_engine.lock().await;
+    let Some(channel) = engine
+        .get_channels(community_id)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|ch| ch.id == channel_id)
+    else {
+        return outgoing;
+    };
+    let author_can_post = engine
+        .get_members(community_id)
+        .unwrap_or_default()
+        .iter()
+        .any(|m| {
+            m.peer_id == chat_msg.author_id && m.roles.iter().any(|r| r == "owner" || r == "admin")
+        });
+    if !matches!(channel.kind, crate::protocol::community::ChannelKind::Announcement)
+        || !author_can_post
+    {
+        return outgoing;
+    }
 
-        // namespaces we actively discover
-        let mut discover_namespaces: HashSet<String> = HashSet::new();
+    let origin = crate::protocol::messages::MessageOrigin {
+        community_id: community_id.to_string(),
+        community_name: engine
+            .get_community_meta(community_id)
+            .map(|m| m.name)
+            .unwrap_or_default(),
+        channel_id: channel_id.to_string(),
+        channel_name: channel.name,
+        message_id: chat_msg.id.clone(),
+        author_id: chat_msg.author_id.clone(),
+        author_name: chat_msg.author_name.clone(),
+    };
+    let display_name = storage.load_settings().unwrap_or_default().display_name;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    for follow in follows {
+        if !engine.has_community(&follow.target_community_id) {
+            continue;
+        }
+        let mirrored = crate::protocol::messages::ChatMessage {
+            // stable per source message and target, so a re-delivered
+            // announcement or a second admin following it doesn't duplicate
+            id: format!("fwd_{}_{}", follow.target_channel_id, chat_msg.id),
+            channel_id: follow.target_channel_id.clone(),
+            author_id: local_peer_id.to_string(),
+            author_name: storage.community_display_name(
+                &follow.target_community_id,
+                local_peer_id,
+                &display_name,
+            ),
+            content: chat_msg.content.clone(),
+            timestamp: now,
+            edited: false,
+            hlc: hlc_clock.lock().await.now(),
+            forwarded_from: Some(origin.clone()),
+            attachments: chat_msg.attachments.clone(),
+            embeds: chat_msg.embeds.clone(),
+            reactions: Vec::new(),
+            reply_to: None,
+        };
+        if !matches!(engine.append_message(&follow.target_community_id, &mirrored), Ok(true)) {
+            continue;
+        }
+        let Ok(data) = serde_json::to_vec(&crate::protocol::messages::GossipMessage::Chat(
+            mirrored.clone(),
+        )) else {
+            continue;
+        };
+        outgoing.push((
+            gossip::topic_for_messages(&follow.target_community_id, &follow.target_channel_id),
+            data,
+        ));
+        let _ = app_handle.emit("dusk-event", DuskEvent::MessageReceived(mirrored));
+    }
+    outgoing
+}
 
-        // pending gif search replies keyed by request_response request id
-        let mut pending_gif_replies: HashMap<
-            libp2p::request_response::OutboundRequestId,
-            tokio::sync::oneshot::Sender<Result<crate::protocol::gif::GifResponse, String>>,
-        > = HashMap::new();
+// publish mirrored announcements, caching them like our own messages since
+// we may be the only peer holding them
+fn publish_mirrored(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    message_cache: &mut cache::MessageCache,
+    outgoing: Vec<(String, Vec<u8>)>,
+) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    for (topic, data) in outgoing {
+        if cache::MessageCache::is_cacheable(&topic) {
+            message_cache.insert(&topic, data.clone(), now);
+        }
+        let ident_topic = libp2p::gossipsub::IdentTopic::new(topic);
+        let _ = swarm.behaviour_mut().gossipsub.publish(ident_topic, data);
+    }
+}
 
-        // pending directory search replies keyed by request_response request id
-        let mut pending_directory_replies: HashMap<
-            libp2p::request_response::OutboundRequestId,
-            tokio::sync::oneshot::Sender<
-                Result<Vec<crate::protocol::directory::DirectoryProfileEntry>, String>,
-            >,
-        > = HashMap::new();
+// extract the channel id from a channel-scoped topic
+fn channel_id_from_topic(topic: &str) -> Option<&str> {
+    topic
+        .strip_prefix("dusk/community/")
+        .and_then(|rest| rest.split('/').nth(2))
+}
 
-        // pending turn credential replies keyed by request_response request id
-        let mut pending_turn_credential_replies: HashMap<
-            libp2p::request_response::OutboundRequestId,
-            tokio::sync::oneshot::Sender<
-                Result<crate::protocol::turn::TurnCredentialResponse, String>,
-            >,
-        > = HashMap::new();
+// newest message timestamp we hold for a channel topic, used as the catch-up cursor
+fn catchup_cursor(
+    engine: &CrdtEngine,
+    storage: &crate::storage::DiskStorage,
+    local_peer_id: &str,
+    topic: &str,
+) -> u64 {
+    // dm pair topics resume from the newest stored message of the conversation
+    if gossip::is_dm_pair_topic(topic) {
+        return dm_conversation_for_topic(storage, local_peer_id, topic)
+            .and_then(|conversation_id| storage.dm_conversation_stats(&conversation_id).ok())
+            .and_then(|(_, _, newest)| newest)
+            .unwrap_or(0);
+    }
 
-        // relay_discoverable flag -- read from storage once at startup
-        let mut relay_discoverable = storage
-            .load_settings()
-            .map(|s| s.relay_discoverable)
-            .unwrap_or(true);
-        log::info!("directory: relay_discoverable={}, relay_multiaddr={:?}", relay_discoverable, relay_multiaddr);
-        // relay reconnection state with exponential backoff
-        let mut relay_backoff_secs = RELAY_INITIAL_BACKOFF_SECS;
-        // deferred warning timer -- only notify the frontend after the grace
-        // period expires so transient disconnections don't flash the banner
-        let mut relay_warn_at: Option<tokio::time::Instant> = None;
-        // deferred sync+presence re-broadcast after a new peer connects,
-        // giving gossipsub mesh time to form before publishing
-        let mut deferred_sync_at: Option<tokio::time::Instant> = None;
-        // next instant at which we should attempt a relay reconnect
-        let mut relay_retry_at: Option<tokio::time::Instant> = if relay_multiaddr.is_some() {
-            // schedule initial retry in case the first dial failed synchronously
-            Some(
-                tokio::time::Instant::now()
-                    + std::time::Duration::from_secs(RELAY_INITIAL_BACKOFF_SECS),
-            )
-        } else {
-            None
+    match (community_id_from_topic(topic), channel_id_from_topic(topic)) {
+        (Some(community_id), Some(channel_id)) => engine
+            .get_messages(community_id, channel_id, None, 1)
+            .ok()
+            .and_then(|msgs| msgs.last().map(|m| m.timestamp))
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+// pair topics are hashed, so find the conversation by deriving each one's topics
+fn dm_conversation_for_topic(
+    storage: &crate::storage::DiskStorage,
+    local_peer_id: &str,
+    topic: &str,
+) -> Option<String> {
+    storage
+        .load_all_dm_conversations(true)
+        .ok()?
+        .into_iter()
+        .find(|(_, meta)| gossip::topics_for_dm(local_peer_id, &meta.peer_id).iter().any(|t| t == topic))
+        .map(|(conversation_id, _)| conversation_id)
+}
+
+// publish one of our own messages, cached for late joiners where the topic
+// allows it and passed through the network simulator
+fn publish_outbound(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    message_cache: &mut cache::MessageCache,
+    network_sim: &mut netem::Netem,
+    topic: String,
+    data: Vec<u8>,
+) {
+    // we are often the only holder of our own message, so cache it for late joiners
+    if cache::MessageCache::is_cacheable(&topic) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        message_cache.insert(&topic, data.clone(), now);
+    }
+    let Some((topic, data)) = network_sim.outbound(topic, data) else {
+        return;
+    };
+    let ident_topic = libp2p::gossipsub::IdentTopic::new(topic.clone());
+    match swarm.behaviour_mut().gossipsub.publish(ident_topic, data) {
+        Ok(msg_id) => log::debug!("gossipsub publish ok on '{}' (msg_id={:?})", topic, msg_id),
+        Err(e) => log::warn!("gossipsub publish failed on '{}': {:?}", topic, e),
+    }
+}
+
+// whether gossipsub has seen the peer subscribe to the topic
+fn peer_on_topic(
+    swarm: &libp2p::Swarm<behaviour::DuskBehaviour>,
+    peer_id: &str,
+    topic: &str,
+) -> bool {
+    let Ok(peer) = peer_id.parse::<libp2p::PeerId>() else {
+        return false;
+    };
+    let hash = libp2p::gossipsub::IdentTopic::new(topic).hash();
+    swarm
+        .behaviour()
+        .gossipsub
+        .all_peers()
+        .any(|(p, topics)| *p == peer && topics.contains(&&hash))
+}
+
+// send a dummy message to each dm conversation so real messages don't stand
+// out, only counted when it actually left for a peer
+fn publish_cover_traffic(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    cover_traffic: &cover::CoverTraffic,
+) {
+    let local_id = swarm.local_peer_id().to_string();
+    let conversations = storage.load_all_dm_conversations(true).unwrap_or_default();
+    let mut published = 0;
+    for (_, meta) in &conversations {
+        let topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_dm(&local_id, &meta.peer_id));
+        // a fresh payload per topic, message ids don't include the topic
+        let data = cover::cover_payload();
+        let len = data.len();
+        if swarm.behaviour_mut().gossipsub.publish(topic, data).is_ok() {
+            cover_traffic.record_cover(cover::padded_len(len));
+            published += 1;
+        }
+    }
+    cover_traffic.record_round(published);
+}
+
+// a new dm topic epoch started: join the upcoming epoch's topic of every
+// conversation and friend and leave the ones that fell out of the window
+fn rotate_dm_topics(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+) {
+    let local_id = swarm.local_peer_id().to_string();
+    let epoch = gossip::dm_topic_epoch();
+    let peer_ids = storage.load_dm_peer_ids().unwrap_or_default();
+    for peer_id in &peer_ids {
+        for topic in gossip::topics_for_dm(&local_id, peer_id) {
+            let ident_topic = libp2p::gossipsub::IdentTopic::new(topic);
+            let _ = swarm.behaviour_mut().gossipsub.subscribe(&ident_topic);
+        }
+        // a few epochs back in case the machine slept through a rotation
+        for old in epoch.saturating_sub(DM_TOPIC_STALE_EPOCHS + 1)..epoch.saturating_sub(1) {
+            let topic = gossip::topic_for_dm_epoch(&local_id, peer_id, old);
+            let ident_topic = libp2p::gossipsub::IdentTopic::new(topic);
+            let _ = swarm.behaviour_mut().gossipsub.unsubscribe(&ident_topic);
+        }
+    }
+}
+
+// a voice channel's capacity and the role a joiner gets in it, from the community doc
+fn voice_admission(
+    engine: &CrdtEngine,
+    community_id: &str,
+    channel_id: &str,
+    peer_id: &str,
+) -> (Option<u32>, crate::protocol::messages::StageRole) {
+    let channel = engine
+        .get_channels(community_id)
+        .ok()
+        .and_then(|channels| channels.into_iter().find(|c| c.id == channel_id));
+    let role = match channel {
+        Some(ref c)
+            if matches!(c.kind, crate::protocol::community::ChannelKind::Stage)
+                && !engine.is_stage_moderator(community_id, peer_id, channel_id) =>
+        {
+            crate::protocol::messages::StageRole::Listener
+        }
+        _ => crate::protocol::messages::StageRole::Speaker,
+    };
+    (channel.and_then(|c| c.max_participants), role)
+}
+
+// drop out of a voice channel without the frontend asking, after a full
+// channel turned us away or a moderator disconnected us
+async fn leave_voice_locally(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    voice_channels: &VoiceChannelMap,
+    community_id: &str,
+    channel_id: &str,
+    announce: bool,
+) {
+    let local_id = swarm.local_peer_id().to_string();
+    let key = format!("{}:{}", community_id, channel_id);
+    let mut vc = voice_channels.lock().await;
+    if let Some(participants) = vc.get_mut(&key) {
+        participants.retain(|p| p.peer_id != local_id);
+        if participants.is_empty() {
+            vc.remove(&key);
+        }
+    }
+    drop(vc);
+
+    let topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_voice(community_id, channel_id));
+    if announce {
+        let leave = crate::protocol::messages::GossipMessage::VoiceLeave {
+            community_id: community_id.to_string(),
+            channel_id: channel_id.to_string(),
+            peer_id: local_id,
         };
+        let payload = serde_json::to_vec(&leave).unwrap_or_default();
+        let _ = swarm.behaviour_mut().gossipsub.publish(topic.clone(), payload);
+    }
+    let _ = swarm.behaviour_mut().gossipsub.unsubscribe(&topic);
+}
 
-        loop {
-            tokio::select! {
-                event = swarm_instance.select_next_some() => {
-                    match event {
-                        // --- kademlia fallback discovery lifecycle ---
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Kademlia(
-                            libp2p::kad::Event::OutboundQueryProgressed {
-                                id,
-                                result: libp2p::kad::QueryResult::Bootstrap(Ok(result)),
+// broadcast a speaking transition and show it locally, we do not receive our own gossip
+fn publish_speaking(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    app_handle: &tauri::AppHandle,
+    change: speaking::SpeakingChange,
+) {
+    let peer_id = swarm.local_peer_id().to_string();
+    let msg = crate::protocol::messages::GossipMessage::VoiceSpeaking {
+        community_id: change.community_id.clone(),
+        channel_id: change.channel_id.clone(),
+        peer_id: peer_id.clone(),
+        speaking: change.speaking,
+    };
+    if let Ok(data) = serde_json::to_vec(&msg) {
+        let topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_voice(
+            &change.community_id,
+            &change.channel_id,
+        ));
+        let _ = swarm.behaviour_mut().gossipsub.publish(topic, data);
+    }
+
+    let _ = app_handle.emit("dusk-event", DuskEvent::VoiceSpeakingChanged {
+        community_id: change.community_id,
+        channel_id: change.channel_id,
+        peer_id,
+        speaking: change.speaking,
+    });
+}
+
+// the presence status the user picked in settings
+fn settings_presence_status(storage: &crate::storage::DiskStorage) -> crate::protocol::messages::PeerStatus {
+    storage
+        .load_settings()
+        .map(|s| match s.status.as_str() {
+            "idle" => crate::protocol::messages::PeerStatus::Idle,
+            "dnd" => crate::protocol::messages::PeerStatus::Dnd,
+            "invisible" => crate::protocol::messages::PeerStatus::Offline,
+            _ => crate::protocol::messages::PeerStatus::Online,
+        })
+        .unwrap_or(crate::protocol::messages::PeerStatus::Online)
+}
+
+// whether the user opened this channel within gossip::CHANNEL_INTEREST_SECS
+fn channel_of_interest(
+    storage: &crate::storage::DiskStorage,
+    community_id: &str,
+    channel_id: &str,
+) -> bool {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    storage
+        .channel_opened_at(community_id, channel_id)
+        .ok()
+        .flatten()
+        .is_some_and(|at| at + gossip::CHANNEL_INTEREST_SECS * 1000 >= now)
+}
+
+// friends and members of our communities, by peer id
+async fn known_peer_sets(
+    storage: &crate::storage::DiskStorage,
+    crdt_engine: &Arc<Mutex<CrdtEngine>>,
+) -> (HashSet<String>, HashSet<String>) {
+    let friends: HashSet<String> = storage
+        .load_friends()
+        .map(|friends| friends.into_iter().map(|e| e.peer_id).collect())
+        .unwrap_or_default();
+    let mut members = HashSet::new();
+    let engine = crdt_engine.lock().await;
+    for community_id in engine.community_ids() {
+        for member in engine.get_members(&community_id).unwrap_or_default() {
+            members.insert(member.peer_id);
+        }
+    }
+    (friends, members)
+}
+
+// dial community members and friends on addresses that worked before, so
+// sync starts without waiting for mdns or rendezvous. with direct
+// connections off only relay circuits are tried, a remembered public
+// address would show our ip to whoever holds it now
+async fn dial_address_book(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    crdt_engine: &Arc<Mutex<CrdtEngine>>,
+    direct_connections: bool,
+) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let since = now.saturating_sub(ADDRESS_BOOK_TTL_SECS * 1000);
+    let entries = match storage.load_peer_addresses(since) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("failed to load address book: {}", e);
+            return;
+        }
+    };
+    if entries.is_empty() {
+        return;
+    }
+
+    let (mut known, members) = known_peer_sets(storage, crdt_engine).await;
+    known.extend(members);
+    let local_id = swarm.local_peer_id().to_string();
+    known.remove(&local_id);
+
+    // entries come most recent first, so peers keep that order
+    let mut peers: Vec<(libp2p::PeerId, Vec<libp2p::Multiaddr>)> = Vec::new();
+    for (peer_id, addr) in entries {
+        if !known.contains(&peer_id) || storage.is_peer_revoked(&peer_id) {
+            continue;
+        }
+        let (Ok(peer), Ok(addr)) = (
+            peer_id.parse::<libp2p::PeerId>(),
+            addr.parse::<libp2p::Multiaddr>(),
+        ) else {
+            continue;
+        };
+        let circuit = addr.iter().any(|p| matches!(p, libp2p::multiaddr::Protocol::P2pCircuit));
+        if !direct_connections && !circuit {
+            continue;
+        }
+        match peers.iter_mut().find(|(p, _)| *p == peer) {
+            Some((_, addrs)) => addrs.push(addr),
+            None if peers.len() < ADDRESS_BOOK_DIAL_LIMIT => peers.push((peer, vec![addr])),
+            None => {}
+        }
+    }
+
+    log::info!("redialing {} known peer(s) from the address book", peers.len());
+    for (peer, addrs) in peers {
+        let opts = libp2p::swarm::dial_opts::DialOpts::peer_id(peer)
+            .addresses(addrs)
+            .build();
+        if let Err(e) = swarm.dial(opts) {
+            log::debug!("address book dial failed for {}: {}", peer, e);
+        }
+    }
+}
+
+// publish a presence update on every community presence topic
+async fn publish_presence(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    crdt_engine: &Arc<Mutex<CrdtEngine>>,
+    status: crate::protocol::messages::PeerStatus,
+    activity: Option<String>,
+) {
+    let local_id = swarm.local_peer_id().to_string();
+    let display_name = storage
+        .load_profile()
+        .map(|p| p.display_name)
+        .unwrap_or_else(|_| "unknown".to_string());
+    let (status_message, status_emoji) = storage.load_settings().unwrap_or_default().shared_status();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    // broadcast to every community presence topic we're subscribed to, using
+    // the per-community name where we set one so global and community
+    // identities never travel together on the same topic
+    let community_ids = crdt_engine.lock().await.community_ids();
+    for cid in community_ids {
+        let topic_str = gossip::topic_for_presence(&cid);
+        let override_profile = storage.load_community_profile(&cid, &local_id).ok().flatten();
+        let update = crate::protocol::messages::PresenceUpdate {
+            peer_id: local_id.clone(),
+            display_name: override_profile
+                .as_ref()
+                .map(|p| p.display_name.clone())
+                .unwrap_or_else(|| display_name.clone()),
+            status: status.clone(),
+            timestamp: now,
+            activity: activity.clone(),
+            status_message: status_message.clone(),
+            status_emoji: status_emoji.clone(),
+        };
+        let msg = crate::protocol::messages::GossipMessage::Presence(update);
+        if let Ok(data) = serde_json::to_vec(&msg) {
+            let ident_topic = libp2p::gossipsub::IdentTopic::new(topic_str.clone());
+            let _ = swarm.behaviour_mut().gossipsub.publish(ident_topic, data);
+        }
+
+        // repeat the override alongside presence so members who joined later learn it
+        if let Some(profile) = override_profile {
+            let msg = crate::protocol::messages::GossipMessage::CommunityProfile(profile);
+            if let Ok(data) = serde_json::to_vec(&msg) {
+                let ident_topic = libp2p::gossipsub::IdentTopic::new(topic_str);
+                let _ = swarm.behaviour_mut().gossipsub.publish(ident_topic, data);
+            }
+        }
+    }
+}
+
+// persist and surface a dm addressed to us, shared by live gossip and catch-up replies
+fn ingest_direct_message(
+    mut dm_msg: crate::protocol::messages::DirectMessage,
+    topic_str: &str,
+    seen_dm_ids: &mut HashSet<String>,
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    app_handle: &tauri::AppHandle,
+) {
+    // only process dms addressed to us (ignore our own echoes)
+    let local_id = swarm.local_peer_id().to_string();
+    if dm_msg.to_peer != local_id {
+        return;
+    }
+
+    // dedup: messages arrive on both the pair topic and inbox
+    // topic so skip if we've already processed this one
+    if !seen_dm_ids.insert(dm_msg.id.clone()) {
+        return;
+    }
+    // cap the dedup set to prevent unbounded memory growth
+    if seen_dm_ids.len() > 10000 {
+        seen_dm_ids.clear();
+    }
+
+    crate::protocol::messages::filter_attachments(&mut dm_msg.attachments, &mut Vec::new());
+
+    // if this arrived on the inbox topic, the sender might be
+    // someone we've never dm'd before -- auto-subscribe to the
+    // pair topic so subsequent messages use the direct channel
+    if topic_str.starts_with("dusk/dm/inbox/") {
+        for pair_topic in gossip::topics_for_dm(&dm_msg.from_peer, &dm_msg.to_peer) {
+            let ident_topic = libp2p::gossipsub::IdentTopic::new(pair_topic);
+            let _ = swarm.behaviour_mut().gossipsub.subscribe(&ident_topic);
+        }
+    }
+
+    // persist the incoming message, one we deleted before stays gone
+    let conversation_id = gossip::dm_conversation_id(&dm_msg.from_peer, &dm_msg.to_peer);
+    if !storage.append_dm_message(&conversation_id, &dm_msg).unwrap_or(false) {
+        return;
+    }
+
+    // update or create conversation metadata
+    let existing = storage.load_dm_conversation(&conversation_id).ok();
+    let meta = crate::protocol::messages::DMConversationMeta {
+        peer_id: dm_msg.from_peer.clone(),
+        display_name: dm_msg.from_display_name.clone(),
+        last_message: Some(dm_msg.content.clone()),
+        last_message_time: Some(dm_msg.timestamp),
+        unread_count: existing.as_ref().map(|m| m.unread_count + 1).unwrap_or(1),
+        pinned: existing.as_ref().map(|m| m.pinned).unwrap_or(false),
+        // a new message brings an archived conversation back
+        archived: false,
+    };
+    let _ = storage.save_dm_conversation(&conversation_id, &meta);
+
+    let _ = app_handle.emit("dusk-event", DuskEvent::DMReceived(dm_msg));
+}
+
+// publish a call signal on the pair topic and on the callee's inbox topic,
+// the inbox reaches peers that are not yet subscribed to the pair topic
+fn publish_call_signal(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    signal: &crate::protocol::messages::DMCallSignal,
+) {
+    let msg = crate::protocol::messages::GossipMessage::DMCall(signal.clone());
+    if let Ok(data) = serde_json::to_vec(&msg) {
+        for topic in [
+            gossip::topic_for_dm(&signal.from_peer, &signal.to_peer),
+            gossip::topic_for_dm_inbox(&signal.to_peer),
+        ] {
+            let ident_topic = libp2p::gossipsub::IdentTopic::new(topic);
+            let _ = swarm.behaviour_mut().gossipsub.publish(ident_topic, data.clone());
+        }
+    }
+}
+
+// reply to a call signal on behalf of the local user
+fn reply_call_signal(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    to: &crate::protocol::messages::DMCallSignal,
+    kind: crate::protocol::messages::DMCallSignalKind,
+) {
+    let display_name = storage
+        .load_profile()
+        .map(|p| p.display_name)
+        .unwrap_or_else(|_| "unknown".to_string());
+    let reply = crate::protocol::messages::DMCallSignal {
+        call_id: to.call_id.clone(),
+        from_peer: to.to_peer.clone(),
+        to_peer: to.from_peer.clone(),
+        from_display_name: display_name,
+        kind,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64,
+    };
+    publish_call_signal(swarm, &reply);
+}
+
+// apply call signaling addressed to us and keep the call log in step with it
+fn handle_dm_call_signal(
+    signal: crate::protocol::messages::DMCallSignal,
+    source: Option<libp2p::PeerId>,
+    topic_str: &str,
+    calls: &mut calls::CallTracker,
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    app_handle: &tauri::AppHandle,
+) {
+    use crate::protocol::messages::{CallDirection, CallRecord, CallStatus, DMCallSignalKind};
+
+    // only the named caller may ring us or end, decline or cancel its call
+    let local_id = swarm.local_peer_id().to_string();
+    if signal.to_peer != local_id || source.map(|p| p.to_string()) != Some(signal.from_peer.clone()) {
+        return;
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    if signal.kind == DMCallSignalKind::Invite {
+        let record = CallRecord {
+            call_id: signal.call_id.clone(),
+            peer_id: signal.from_peer.clone(),
+            display_name: signal.from_display_name.clone(),
+            direction: CallDirection::Incoming,
+            status: CallStatus::Ringing,
+            started_at: now,
+            answered_at: None,
+            ended_at: None,
+            duration_secs: 0,
+        };
+        // the invite arrives on both topics, only handle it once
+        if !storage.insert_call(&record).unwrap_or(false) {
+            return;
+        }
+
+        // already talking to someone, log it as missed and tell the caller
+        if calls.is_busy() {
+            reply_call_signal(swarm, storage, &signal, DMCallSignalKind::Busy);
+            if let Ok(Some(record)) = storage.finish_call(&signal.call_id, now, CallStatus::Missed) {
+                let _ = app_handle.emit("dusk-event", DuskEvent::CallEnded(record));
+            }
+            return;
+        }
+
+        // sdp and ice for the call travel on the pair topic
+        if topic_str.starts_with("dusk/dm/inbox/") {
+            for pair_topic in gossip::topics_for_dm(&signal.from_peer, &signal.to_peer) {
+                let ident_topic = libp2p::gossipsub::IdentTopic::new(pair_topic);
+                let _ = swarm.behaviour_mut().gossipsub.subscribe(&ident_topic);
+            }
+        }
+
+        calls.ring(&signal.call_id, CallDirection::Incoming);
+        let _ = app_handle.emit("dusk-event", DuskEvent::IncomingCall {
+            call_id: signal.call_id,
+            peer_id: signal.from_peer,
+            display_name: signal.from_display_name,
+        });
+        return;
+    }
+
+    // everything else refers to a call we already know about with this peer
+    let call = match storage.load_call(&signal.call_id) {
+        Ok(Some(call)) if call.peer_id == signal.from_peer => call,
+        _ => return,
+    };
+
+    match signal.kind {
+        DMCallSignalKind::Invite => {}
+        DMCallSignalKind::Accept => {
+            if call.direction == CallDirection::Outgoing
+                && storage.mark_call_answered(&signal.call_id, now).unwrap_or(false)
+            {
+                calls.answer(&signal.call_id);
+                let _ = app_handle.emit("dusk-event", DuskEvent::CallAccepted {
+                    call_id: signal.call_id,
+                    peer_id: signal.from_peer,
+                });
+            }
+        }
+        DMCallSignalKind::Decline => {
+            calls.end(&signal.call_id);
+            if let Ok(Some(record)) = storage.finish_call(&signal.call_id, now, CallStatus::Declined) {
+                let _ = app_handle.emit("dusk-event", DuskEvent::CallEnded(record));
+            }
+        }
+        DMCallSignalKind::Busy => {
+            calls.end(&signal.call_id);
+            if let Ok(Some(record)) = storage.finish_call(&signal.call_id, now, CallStatus::Busy) {
+                let _ = app_handle.emit("dusk-event", DuskEvent::CallBusy(record));
+            }
+        }
+        DMCallSignalKind::Cancel => {
+            calls.end(&signal.call_id);
+            if let Ok(Some(record)) = storage.finish_call(&signal.call_id, now, CallStatus::Missed) {
+                let _ = app_handle.emit("dusk-event", DuskEvent::CallCancelled(record));
+            }
+        }
+        DMCallSignalKind::End => {
+            calls.end(&signal.call_id);
+            // an end before any answer reads as the peer walking away
+            let unanswered = match call.direction {
+                CallDirection::Incoming => CallStatus::Missed,
+                CallDirection::Outgoing => CallStatus::Declined,
+            };
+            if let Ok(Some(record)) = storage.finish_call(&signal.call_id, now, unanswered) {
+                let _ = app_handle.emit("dusk-event", DuskEvent::CallEnded(record));
+            }
+        }
+    }
+}
+
+// disappearing messages signaling from the other side of a conversation
+fn handle_dm_ephemeral_signal(
+    signal: crate::protocol::messages::DMEphemeralSignal,
+    source: Option<libp2p::PeerId>,
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    app_handle: &tauri::AppHandle,
+) {
+    use crate::protocol::messages::{
+        DMEphemeralKind, DMEphemeralPolicy, MAX_DM_EPHEMERAL_TTL_SECS, MIN_DM_EPHEMERAL_TTL_SECS,
+    };
+
+    let local_id = swarm.local_peer_id().to_string();
+    if signal.to_peer != local_id || source.map(|p| p.to_string()) != Some(signal.from_peer.clone()) {
+        return;
+    }
+    let conversation_id = gossip::dm_conversation_id(&signal.from_peer, &signal.to_peer);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    match signal.kind.clone() {
+        DMEphemeralKind::Read { up_to } => {
+            // the peer read what we sent, our copies start their timer too
+            let _ = storage.mark_dm_messages_read(&conversation_id, &local_id, up_to, now);
+        }
+        DMEphemeralKind::Deleted { message_ids } => {
+            let _ = app_handle.emit("dusk-event", DuskEvent::DMDeletionConfirmed {
+                peer_id: signal.from_peer,
+                message_ids,
+            });
+        }
+        kind => {
+            if let DMEphemeralKind::Propose { ttl_secs } | DMEphemeralKind::Accept { ttl_secs } = kind {
+                if !(MIN_DM_EPHEMERAL_TTL_SECS..=MAX_DM_EPHEMERAL_TTL_SECS).contains(&ttl_secs) {
+                    return;
+                }
+            }
+            // the step goes into our log under the peer's signature
+            if !crate::verification::verify_dm_policy_signal(&conversation_id, &signal) {
+                log::debug!("ignoring unsigned disappearing messages step from {}", signal.from_peer);
+                return;
+            }
+            let mut policy = storage
+                .load_dm_ephemeral_policy(&conversation_id)
+                .ok()
+                .flatten()
+                .unwrap_or_else(|| DMEphemeralPolicy::new(&signal.from_peer));
+            if policy.apply(&signal.from_peer, &signal.kind, now).is_none() {
+                return;
+            }
+            if storage.save_dm_ephemeral_policy(&conversation_id, &policy).is_err() {
+                return;
+            }
+            let _ = storage.append_dm_policy_log(&conversation_id, &signal);
+            let _ = app_handle.emit("dusk-event", DuskEvent::DMEphemeralPolicyChanged(policy));
+        }
+    }
+}
+
+// honor a verified "delete for everyone" from the author of a dm we received
+fn handle_dm_delete_request(
+    request: crate::protocol::messages::DMDeleteRequest,
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    app_handle: &tauri::AppHandle,
+) {
+    if request.to_peer != swarm.local_peer_id().to_string() {
+        return;
+    }
+    let conversation_id = gossip::dm_conversation_id(&request.from_peer, &request.to_peer);
+    let message = match storage.load_dm_message(&conversation_id, &request.message_id) {
+        Ok(Some(message)) => message,
+        _ => return,
+    };
+
+    // only the author may delete, and only for a while after we got the
+    // message. both times are ours, the author picks the ones it signs
+    if message.from_peer != request.from_peer {
+        return;
+    }
+    let Ok(Some(received_at)) = storage.dm_received_at(&conversation_id, &request.message_id) else {
+        return;
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let window_ms = crate::protocol::messages::DM_DELETE_WINDOW_SECS * 1000;
+    if now.saturating_sub(received_at) > window_ms {
+        return;
+    }
+
+    if let Ok(deleted) = storage.delete_dm_messages(&conversation_id, &[request.message_id]) {
+        for message_id in deleted {
+            let _ = app_handle.emit("dusk-event", DuskEvent::DMDeleted {
+                peer_id: request.from_peer.clone(),
+                message_id,
+            });
+        }
+    }
+}
+
+// an edit from the author of a dm we received, the stored author has to
+// match the peer that published it
+fn handle_dm_edit(
+    edit: crate::protocol::messages::DMEdit,
+    source: Option<libp2p::PeerId>,
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    app_handle: &tauri::AppHandle,
+) {
+    let local_id = swarm.local_peer_id().to_string();
+    if edit.to_peer != local_id || source.map(|p| p.to_string()) != Some(edit.from_peer.clone()) {
+        return;
+    }
+    let conversation_id = gossip::dm_conversation_id(&edit.from_peer, &edit.to_peer);
+    if let Ok(Some(message)) =
+        storage.edit_dm_message(&conversation_id, &edit.message_id, &edit.from_peer, &edit.content)
+    {
+        let _ = app_handle.emit("dusk-event", DuskEvent::DMEdited(message));
+    }
+}
+
+// a reaction the peer added to or took off a dm in our conversation
+fn handle_dm_reaction(
+    signal: crate::protocol::messages::DMReactionSignal,
+    source: Option<libp2p::PeerId>,
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    app_handle: &tauri::AppHandle,
+) {
+    let local_id = swarm.local_peer_id().to_string();
+    if signal.to_peer != local_id || source.map(|p| p.to_string()) != Some(signal.from_peer.clone()) {
+        return;
+    }
+    let emoji_len = signal.emoji.chars().count();
+    if emoji_len == 0 || emoji_len > crate::protocol::messages::MAX_DM_REACTION_LEN {
+        return;
+    }
+    let conversation_id = gossip::dm_conversation_id(&signal.from_peer, &signal.to_peer);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    if let Ok(Some(reactions)) = storage.set_dm_reaction(
+        &conversation_id,
+        &signal.message_id,
+        &signal.from_peer,
+        &signal.emoji,
+        signal.active,
+        now,
+    ) {
+        let _ = app_handle.emit("dusk-event", DuskEvent::DMReactionsChanged {
+            peer_id: signal.from_peer,
+            message_id: signal.message_id,
+            reactions,
+        });
+    }
+}
+
+// delete read disappearing messages whose timer ran out and tell each peer
+fn expire_dm_messages(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    app_handle: &tauri::AppHandle,
+) {
+    use crate::protocol::messages::{DMEphemeralKind, DMEphemeralSignal, GossipMessage};
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let expired = match storage.expire_dm_messages(now) {
+        Ok(expired) => expired,
+        Err(e) => {
+            log::warn!("failed to expire disappearing dm messages: {}", e);
+            return;
+        }
+    };
+
+    let local_id = swarm.local_peer_id().to_string();
+    for (_, peer_id, message_ids) in expired {
+        let confirmation = GossipMessage::DMEphemeral(DMEphemeralSignal {
+            from_peer: local_id.clone(),
+            to_peer: peer_id.clone(),
+            kind: DMEphemeralKind::Deleted {
+                message_ids: message_ids.clone(),
+            },
+            timestamp: now,
+            public_key: String::new(),
+            signature: String::new(),
+        });
+        if let Ok(data) = serde_json::to_vec(&confirmation) {
+            let topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_dm(&local_id, &peer_id));
+            let _ = swarm.behaviour_mut().gossipsub.publish(topic, data);
+        }
+        let _ = app_handle.emit("dusk-event", DuskEvent::DMMessagesExpired { peer_id, message_ids });
+    }
+}
+
+// close out calls whose ring time ran out. outgoing calls also tell the
+// callee to stop ringing in case their own timer is behind ours
+fn expire_ringing_calls(
+    calls: &mut calls::CallTracker,
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    storage: &crate::storage::DiskStorage,
+    app_handle: &tauri::AppHandle,
+) {
+    use crate::protocol::messages::{CallDirection, CallStatus, DMCallSignal, DMCallSignalKind};
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    for (call_id, direction) in calls.expire() {
+        let unanswered = match direction {
+            CallDirection::Incoming => CallStatus::Missed,
+            CallDirection::Outgoing => CallStatus::NoAnswer,
+        };
+        let record = match storage.finish_call(&call_id, now, unanswered) {
+            Ok(Some(record)) => record,
+            _ => continue,
+        };
+
+        if direction == CallDirection::Outgoing {
+            let cancel = DMCallSignal {
+                call_id: call_id.clone(),
+                from_peer: swarm.local_peer_id().to_string(),
+                to_peer: record.peer_id.clone(),
+                from_display_name: storage
+                    .load_profile()
+                    .map(|p| p.display_name)
+                    .unwrap_or_else(|_| "unknown".to_string()),
+                kind: DMCallSignalKind::Cancel,
+                timestamp: now,
+            };
+            publish_call_signal(swarm, &cancel);
+        }
+
+        let _ = app_handle.emit("dusk-event", DuskEvent::CallTimedOut(record));
+    }
+}
+
+// voice channel participant tracking type alias for readability
+pub type VoiceChannelMap =
+    Arc<Mutex<HashMap<String, Vec<crate::protocol::messages::VoiceParticipant>>>>;
+
+// drop members that arrived with a merge without meeting a "require" community
+// policy, only moderators act on it so the removal carries their authority
+fn remove_unverified_joiners(
+    engine: &mut CrdtEngine,
+    storage: &crate::storage::DiskStorage,
+    unverified_peers: &HashSet<String>,
+    community_id: &str,
+    members_before: &HashSet<String>,
+    keypair: &libp2p::identity::Keypair,
+) -> Vec<String> {
+    let local_peer_id = keypair.public().to_peer_id().to_string();
+    let requires_verification = engine
+        .get_community_meta(community_id)
+        .map(|meta| meta.verification_policy == VerificationPolicy::Require)
+        .unwrap_or(false);
+    if !requires_verification {
+        return Vec::new();
+    }
+
+    let members = match engine.get_members(community_id) {
+        Ok(members) => members,
+        Err(_) => return Vec::new(),
+    };
+    let is_moderator = members.iter().any(|m| {
+        m.peer_id == local_peer_id && m.roles.iter().any(|r| r == "owner" || r == "admin")
+    });
+    if !is_moderator {
+        return Vec::new();
+    }
+
+    // placeholders have no public key yet, so only judge peers we heard announce
+    let directory = storage.load_directory().unwrap_or_default();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let mut removed = Vec::new();
+    for member in members
+        .iter()
+        .filter(|m| !members_before.contains(&m.peer_id) && m.peer_id != local_peer_id)
+    {
+        let announced_unverified = directory
+            .get(&member.peer_id)
+            .is_some_and(|e| !e.verified && !e.public_key.is_empty());
+        if unverified_peers.contains(&member.peer_id) || announced_unverified {
+            // the map entry may be missing for joiners only the log knows about,
+            // the signed kick removes them either way
+            let _ = engine.remove_member(community_id, &member.peer_id);
+            let kick = verification::sign_membership_event(
+                keypair,
+                community_id,
+                MembershipAction::Kick,
+                &member.peer_id,
+                &[],
+                &member.display_name,
+                now,
+            );
+            if let Err(e) = engine.record_membership_event(community_id, &kick) {
+                log::warn!("{}", e);
+            }
+            removed.push(member.peer_id.clone());
+        }
+    }
+
+    removed
+}
+
+// record application misbehaviour and feed the new score into gossipsub
+fn penalize_peer(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    peer_scores: &mut scoring::PeerScores,
+    peer: &libp2p::PeerId,
+    kind: scoring::Misbehaviour,
+) {
+    let score = peer_scores.penalize(peer, kind);
+    log::warn!("scoring: penalized {} for {} (app score {:.1})", peer, kind.as_str(), score);
+    swarm
+        .behaviour_mut()
+        .gossipsub
+        .set_application_score(peer, score);
+}
+
+// tell gossipsub whether to relay an inbound message. a rejection also counts
+// against the peer that handed it to us, which had no business forwarding it
+fn report_gossip(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    message_id: &libp2p::gossipsub::MessageId,
+    propagation_source: &libp2p::PeerId,
+    acceptance: libp2p::gossipsub::MessageAcceptance,
+) {
+    let _ = swarm
+        .behaviour_mut()
+        .gossipsub
+        .report_message_validation_result(message_id, propagation_source, acceptance);
+}
+
+// rate limit and cache an inbound gossip message, then queue it for parsing
+// and signature checks on the prevalidation workers
+fn receive_gossip(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    peer_scores: &mut scoring::PeerScores,
+    message_cache: &mut cache::MessageCache,
+    prevalidator: &mut prevalidate::Prevalidator,
+    event_recorder: &replay::EventRecorder,
+    relay_peer: Option<libp2p::PeerId>,
+    (propagation_source, message_id, message): (
+        libp2p::PeerId,
+        libp2p::gossipsub::MessageId,
+        libp2p::gossipsub::Message,
+    ),
+) {
+    event_recorder.gossip(&propagation_source, &message);
+    let topic_str = message.topic.as_str();
+
+    // limits apply to the signed author, not the neighbour relaying for the
+    // whole mesh. the relay is exempt, it carries everyone's traffic
+    let author = message.source.unwrap_or(propagation_source);
+    if Some(author) != relay_peer && peer_scores.note_message(&author) {
+        penalize_peer(swarm, peer_scores, &author, scoring::Misbehaviour::RateLimited);
+        report_gossip(
+            swarm,
+            &message_id,
+            &propagation_source,
+            libp2p::gossipsub::MessageAcceptance::Ignore,
+        );
+        return;
+    }
+
+    if cache::MessageCache::is_cacheable(topic_str) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        message_cache.insert(topic_str, message.data.clone(), now);
+    }
+
+    // the result comes back through the prevalidated branch of the event loop
+    if !prevalidator.submit(propagation_source, message_id.clone(), message) {
+        report_gossip(
+            swarm,
+            &message_id,
+            &propagation_source,
+            libp2p::gossipsub::MessageAcceptance::Ignore,
+        );
+    }
+}
+
+// build a signed profile announcement from the keypair and storage
+// used by the event loop to re-announce after relay connection or new peer joins
+fn build_profile_announcement(
+    keypair: &libp2p::identity::Keypair,
+    storage: &crate::storage::DiskStorage,
+) -> Option<crate::protocol::messages::ProfileAnnouncement> {
+    let profile = storage.load_profile().ok()?;
+    let proof = storage.load_verification_proof().ok().flatten();
+    let peer_id = libp2p::PeerId::from(keypair.public());
+    let (status_message, status_emoji) = storage.load_settings().unwrap_or_default().shared_status();
+
+    let mut announcement = crate::protocol::messages::ProfileAnnouncement {
+        peer_id: peer_id.to_string(),
+        display_name: profile.display_name,
+        bio: profile.bio,
+        public_key: hex::encode(keypair.public().encode_protobuf()),
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64,
+        verification_proof: proof,
+        signature: String::new(),
+        status_message,
+        status_emoji,
+    };
+    announcement.signature = verification::sign_announcement(keypair, &announcement);
+    Some(announcement)
+}
+
+fn announce_policy(storage: &crate::storage::DiskStorage) -> announce::AnnouncePolicy {
+    storage.load_settings().map(|s| s.announce_policy).unwrap_or_default()
+}
+
+// topics our profile announcement goes out on: the global directory unless
+// announce_globally is off, and the directory topic of every community we
+// stay visible to. communities where we go by another name are skipped, the
+// announcement would tie that name to our global one
+fn profile_announce_topics(
+    storage: &crate::storage::DiskStorage,
+    local_peer_id: &str,
+    community_ids: Vec<String>,
+) -> Vec<String> {
+    let mut topics = Vec::new();
+    if storage.load_settings().ok().is_none_or(|s| s.announce_globally) {
+        topics.push(gossip::topic_for_directory());
+    }
+    let hidden = storage.load_profile_hidden_communities().unwrap_or_default();
+    let renamed: HashSet<String> = storage
+        .load_peer_community_profiles(local_peer_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| p.community_id)
+        .collect();
+    for community_id in community_ids {
+        if !hidden.contains(&community_id) && !renamed.contains(&community_id) {
+            topics.push(gossip::topic_for_community_directory(&community_id));
+        }
+    }
+    topics
+}
+
+// publish our profile on the directory gossipsub topics so connected peers
+// learn about us and add us to their local directory
+async fn publish_profile(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    keypair: &libp2p::identity::Keypair,
+    storage: &crate::storage::DiskStorage,
+    crdt_engine: &Arc<Mutex<CrdtEngine>>,
+) {
+    let Some(announcement) = build_profile_announcement(keypair, storage) else {
+        return;
+    };
+    let msg = crate::protocol::messages::GossipMessage::ProfileAnnounce(announcement);
+    let Ok(data) = serde_json::to_vec(&msg) else {
+        return;
+    };
+    let community_ids = crdt_engine.lock().await.community_ids();
+    let local_peer_id = swarm.local_peer_id().to_string();
+    for topic in profile_announce_topics(storage, &local_peer_id, community_ids) {
+        let topic = libp2p::gossipsub::IdentTopic::new(topic);
+        let _ = swarm.behaviour_mut().gossipsub.publish(topic, data.clone());
+    }
+}
+
+// build the full relay circuit multiaddr string for this peer
+// e.g. /dns4/relay.duskchat.app/tcp/4001/p2p/<relay_id>/p2p-circuit/p2p/<local_id>
+fn build_circuit_addr(relay_multiaddr: &libp2p::Multiaddr, local_peer_id: libp2p::PeerId) -> String {
+    relay_multiaddr
+        .clone()
+        .with(libp2p::multiaddr::Protocol::P2pCircuit)
+        .with(libp2p::multiaddr::Protocol::P2p(local_peer_id))
+        .to_string()
+}
+
+// send a directory registration to the relay so other peers can discover
+// and connect to us via the circuit address
+fn register_directory(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    relay_peer: &libp2p::PeerId,
+    storage: &crate::storage::DiskStorage,
+    relay_multiaddr: &libp2p::Multiaddr,
+    local_peer_id: libp2p::PeerId,
+) {
+    let profile = storage.load_profile().unwrap_or_default();
+    let circuit_addr = build_circuit_addr(relay_multiaddr, local_peer_id);
+    log::info!(
+        "directory: sending Register to relay {} (name='{}', circuit='{}')",
+        relay_peer,
+        profile.display_name,
+        circuit_addr,
+    );
+    swarm.behaviour_mut().directory_service.send_request(
+        relay_peer,
+        crate::protocol::directory::DirectoryRequest::Register {
+            display_name: profile.display_name,
+            relay_addr: circuit_addr,
+        },
+    );
+}
+
+// refresh our relay presence entry and ask which friends are online.
+// returns the request id with the tokens we asked about, mapped back to
+// peer ids for the response
+fn send_presence_heartbeat(
+    swarm: &mut libp2p::Swarm<behaviour::DuskBehaviour>,
+    relay_peer: &libp2p::PeerId,
+    keypair: &libp2p::identity::Keypair,
+    storage: &crate::storage::DiskStorage,
+) -> (libp2p::request_response::OutboundRequestId, HashMap<String, String>) {
+    let visible = storage
+        .load_settings()
+        .map(|s| s.show_online_status)
+        .unwrap_or(true);
+    let friends: HashMap<String, String> = storage
+        .load_friends()
+        .unwrap_or_default()
+        .into_iter()
+        .take(crate::protocol::presence::MAX_PRESENCE_FRIENDS)
+        .filter_map(|entry| {
+            let token = crate::protocol::presence::presence_token(keypair, &entry.peer_id)?;
+            Some((token, entry.peer_id))
+        })
+        .collect();
+    let request_id = swarm.behaviour_mut().presence_service.send_request(
+        relay_peer,
+        crate::protocol::presence::PresenceRequest::Heartbeat {
+            friend_tokens: friends.keys().cloned().collect(),
+            visible,
+        },
+    );
+    (request_id, friends)
+}
+
+// emit the friends whose relay presence changed since the last heartbeat
+fn apply_friend_presence(
+    online: HashSet<String>,
+    online_friends: &mut HashSet<String>,
+    app_handle: &tauri::AppHandle,
+) {
+    for peer_id in online.difference(online_friends) {
+        let _ = app_handle.emit("dusk-event", DuskEvent::FriendPresence {
+            peer_id: peer_id.clone(),
+            online: true,
+        });
+    }
+    for peer_id in online_friends.difference(&online) {
+        let _ = app_handle.emit("dusk-event", DuskEvent::FriendPresence {
+            peer_id: peer_id.clone(),
+            online: false,
+        });
+    }
+    *online_friends = online;
+}
+
+// start the p2p node on a background task
+pub async fn start(
+    keypair: libp2p::identity::Keypair,
+    shared: SharedState,
+    app_handle: tauri::AppHandle,
+    custom_relay_addr: Option<String>,
+) -> Result<NodeHandle, String> {
+    let SharedState {
+        crdt_engine,
+        storage,
+        voice_channels,
+        hlc_clock,
+        cover_traffic,
+        latency,
+    } = shared;
+
+    // heartbeat and mdns are fixed at build time, so resolve the profile first
+    let settings = storage.load_settings().unwrap_or_default();
+    let network_profile = settings.network_profile;
+    let power_signals = power::detect_signals().await;
+    let low_power = power::is_low_power(network_profile, power_signals);
+    if low_power {
+        log::info!(
+            "starting in low power networking (profile {}, battery {}, metered {})",
+            network_profile.as_str(),
+            power_signals.on_battery,
+            power_signals.metered
+        );
+    }
+
+    let direct_connections = settings.direct_connections;
+    let max_connections = settings.max_connections;
+    let interest_subscriptions = settings.interest_subscriptions;
+    let record_events = settings.record_events;
+    let mut swarm_instance = swarm::build_swarm(
+        &keypair,
+        low_power,
+        settings.mdns_enabled,
+        direct_connections,
+        cover_traffic.clone(),
+    )
+        .map_err(|e| format!("failed to build swarm: {}", e))?;
+
+    // listen on all interfaces for LAN peer discovery via mDNS
+    swarm_instance
+        .listen_on("/ip4/0.0.0.0/tcp/0".parse().unwrap())
+        .map_err(|e| format!("failed to listen: {}", e))?;
+
+    dial_address_book(&mut swarm_instance, &storage, &crdt_engine, direct_connections).await;
+
+    let (command_tx, mut command_rx) = tokio::sync::mpsc::channel::<NodeCommand>(256);
+
+    // emit initial node status
+    let _ = app_handle.emit(
+        "dusk-event",
+        DuskEvent::NodeStatus {
+            is_connected: false,
+            peer_count: 0,
+        },
+    );
+
+    // resolve validated relay and bootstrap peer configuration for WAN connectivity
+    let relay_config = resolve_relay_config(custom_relay_addr.as_deref());
+    if let Some(cfg) = relay_config.as_ref() {
+        log::info!(
+            "using relay {} (peer {}) from {}",
+            cfg.addr,
+            cfg.peer_id,
+            cfg.source
+        );
+    }
+    let relay_multiaddr = relay_config.as_ref().map(|cfg| cfg.addr.clone());
+    let relay_peer_id = relay_config.as_ref().map(|cfg| cfg.peer_id);
+
+    let bootstrap_nodes = bootstrap_peers(relay_config.as_ref());
+    if !bootstrap_nodes.is_empty() {
+        log::info!(
+            "configured {} WAN bootstrap peer(s) (relay + optional {})",
+            bootstrap_nodes.len(),
+            DUSK_BOOTSTRAP_PEERS_ENV
+        );
+    }
+    for (addr, peer) in &bootstrap_nodes {
+        swarm_instance
+            .behaviour_mut()
+            .kademlia
+            .add_address(peer, addr.clone());
+    }
+
+    // if a relay is configured, dial it immediately
+    // don't emit RelayStatus here -- the store defaults to connected=true so
+    // no warning shows during the initial handshake. the warning only appears
+    // if the dial actually fails (OutgoingConnectionError) or the connection drops.
+    if let Some(ref addr) = relay_multiaddr {
+        log::info!("relay dial start (startup): {}", addr);
+        if let Err(e) = swarm_instance.dial(addr.clone()) {
+            log::warn!("relay dial failed (startup): {}", e);
+            // emit disconnected status immediately if dial fails
+            let _ = app_handle.emit("dusk-event", DuskEvent::RelayStatus { connected: false });
+        } else {
+            log::info!("relay dial initiated (startup)");
+        }
+    } else {
+        // if relay address is invalid or not configured, emit disconnected status
+        log::warn!(
+            "no valid relay address configured from DUSK_RELAY_ADDR/custom/default, running in LAN-only mode"
+        );
+        let _ = app_handle.emit("dusk-event", DuskEvent::RelayStatus { connected: false });
+    }
+
+    // clone the keypair into the event loop so it can re-announce our profile
+    // when new peers connect or the relay comes online
+    let node_keypair = keypair;
+
+    let task = tauri::async_runtime::spawn(async move {
+        use futures::StreamExt;
+
+        // track connected peers for accurate count
+        let mut connected_peers: HashSet<String> = HashSet::new();
+
+        // dedup set for dm message ids -- messages arrive on both the pair topic
+        // and inbox topic so we need to skip duplicates
+        let mut seen_dm_ids: HashSet<String> = HashSet::new();
+
+        // dedup set for channel message ids -- the same message can be republished
+        // or reach us over more than one mesh path
+        let mut seen_chat_ids: HashSet<String> = HashSet::new();
+
+        // our own peer id as text, formatted once instead of for every inbound
+        // message that checks whether it is addressed to us
+        let local_peer_str = swarm_instance.local_peer_id().to_string();
+
+        // peers whose latest announcement carried no verification proof, kept even
+        // when our own policy rejected it so community policies can be enforced
+        let mut unverified_peers: HashSet<String> = HashSet::new();
+
+        // recent channel payloads served to late joiners over the catch-up protocol
+        let mut message_cache = cache::MessageCache::new();
+        // serialization buffer for the replies and receipts inbound gossip triggers
+        let mut encoder = encode::Encoder::new();
+        // inbound gossip waiting on or running through the prevalidation workers
+        let mut prevalidator = prevalidate::Prevalidator::new();
+        // gossip held back or dropped by simulated network conditions
+        let mut network_sim = netem::Netem::new();
+        // opt-in log of inbound gossip and state changes for replay debugging
+        let event_recorder = replay::EventRecorder::new(storage.clone(), record_events);
+        event_recorder.state("node_started", &swarm_instance.local_peer_id().to_string());
+        // topics we subscribed to but have not asked anyone to catch us up on yet
+        let mut pending_catchup_topics: HashSet<String> = HashSet::new();
+        // in-flight catch-up requests keyed by request id, value is the topic
+        let mut pending_catchup_requests: HashMap<
+            libp2p::request_response::OutboundRequestId,
+            String,
+        > = HashMap::new();
+
+        // track whether we have a relay reservation
+        let mut relay_reservation_active = false;
+        // peers dialed on their direct addresses, retried over the relay if that fails
+        let mut direct_dial_pending: HashSet<libp2p::PeerId> = HashSet::new();
+
+        // track the relay peer id for rendezvous operations
+        let relay_peer = relay_peer_id;
+
+        // community namespaces we need to register on rendezvous
+        // queued until the relay connection is ready
+        let mut pending_registrations: Vec<String> = Vec::new();
+        let mut pending_discoveries: Vec<String> = Vec::new();
+        // timestamp when pending items were first queued (for TTL cleanup)
+        let mut pending_queued_at: Option<std::time::Instant> = None;
+
+        // rendezvous registration/rediscovery refresh interval
+        let mut rendezvous_tick =
+            tokio::time::interval(std::time::Duration::from_secs(RENDEZVOUS_TICK_SECS));
+        // periodic Kademlia bootstrap fallback for WAN resilience
+        let mut kad_bootstrap_tick =
+            tokio::time::interval(std::time::Duration::from_secs(KAD_BOOTSTRAP_TICK_SECS));
+
+        // background connectivity watchdog, probes run on their own task
+        // and report back so the event loop never blocks on tcp timeouts
+        let mut watchdog_tick =
+            tokio::time::interval(std::time::Duration::from_secs(watchdog::WATCHDOG_TICK_SECS));
+        let (probe_tx, mut probe_rx) = tokio::sync::mpsc::channel::<bool>(1);
+        let mut probe_in_flight = false;
+        // assume the relay is reachable until the first probe says otherwise
+        let mut internet_reachable = true;
+        let mut network_mode = watchdog::NetworkMode::Full;
+
+        // sleep/wake detection -- monotonic timers pause while suspended on most
+        // platforms but the wall clock keeps going, so a large jump means we resumed
+        let mut wake_check_tick =
+            tokio::time::interval(std::time::Duration::from_secs(WAKE_CHECK_TICK_SECS));
+        let mut last_wake_check = std::time::SystemTime::now();
+
+        // application-specific penalties fed into gossipsub peer scoring
+        let mut peer_scores = scoring::PeerScores::new();
+        // caps peer connections, the relay link is left out
+        let mut connection_manager = connections::ConnectionManager::new(max_connections);
+        let mut score_decay_tick =
+            tokio::time::interval(std::time::Duration::from_secs(scoring::SCORE_DECAY_TICK_SECS));
+
+        // battery and metered-connection aware networking, dht walks stop and
+        // presence is batched while low power is in effect
+        let mut network_profile = network_profile;
+        let mut power_signals = power_signals;
+        let mut low_power = low_power;
+        let mut power_tick =
+            tokio::time::interval(std::time::Duration::from_secs(power::POWER_TICK_SECS));
+        let mut presence_batch_tick =
+            tokio::time::interval(std::time::Duration::from_secs(power::PRESENCE_BATCH_SECS));
+        let mut pending_presence: Option<crate::protocol::messages::PeerStatus> = None;
+
+        // rich presence set by the user or an external tool, sent with every presence update
+        let mut local_activity: Option<String> = None;
+
+        // local speaking indicator, the deadline fires once speech has gone quiet
+        let mut speaking_state = speaking::SpeakingState::new();
+
+        // pending profile announcement, triggers fold into it per the announce policy
+        let mut announce_scheduler = announce::AnnounceScheduler::new();
+
+        // ringing and connected dm calls, calls open from a previous run can't be live
+        let mut call_tracker = calls::CallTracker::new();
+        let _ = storage.close_open_calls();
+
+        // disappearing dm messages are deleted once their timer runs out
+        let mut dm_expiry_tick =
+            tokio::time::interval(std::time::Duration::from_secs(DM_EXPIRY_TICK_SECS));
+
+        // opt-in friend presence through the relay
+        let mut relay_presence = storage
+            .load_settings()
+            .map(|s| s.relay_presence)
+            .unwrap_or(false);
+        let mut presence_heartbeat_tick = tokio::time::interval(std::time::Duration::from_secs(
+            crate::protocol::presence::PRESENCE_HEARTBEAT_SECS,
+        ));
+        // friends the relay last reported online
+        let mut relay_online_friends: HashSet<String> = HashSet::new();
+        // the heartbeat in flight, with its hashes mapped back to peer ids
+        let mut pending_presence_heartbeat: Option<(
+            libp2p::request_response::OutboundRequestId,
+            HashMap<String, String>,
+        )> = None;
+
+        // dummy dm traffic while cover traffic mode is on
+        let mut next_cover_at = tokio::time::Instant::now() + cover::next_cover_delay();
+
+        // dm pair topics are renamed every epoch
+        let mut next_dm_rotation = tokio::time::Instant::now() + gossip::until_next_dm_epoch();
+
+        // owner checkpoints of community doc heads, first tick fires immediately
+        let mut checkpoint_tick =
+            tokio::time::interval(std::time::Duration::from_secs(CHECKPOINT_TICK_SECS));
+
+        // namespaces we actively register under
+        let mut register_namespaces: HashSet<String> = HashSet::new();
+
+        // namespaces we actively discover
+        let mut discover_namespaces: HashSet<String> = HashSet::new();
+
+        // pending gif search replies keyed by request_response request id
+        let mut pending_gif_replies: HashMap<
+            libp2p::request_response::OutboundRequestId,
+            tokio::sync::oneshot::Sender<Result<crate::protocol::gif::GifResponse, String>>,
+        > = HashMap::new();
+
+        // pending directory search replies keyed by request_response request id
+        let mut pending_directory_replies: HashMap<
+            libp2p::request_response::OutboundRequestId,
+            tokio::sync::oneshot::Sender<
+                Result<Vec<crate::protocol::directory::DirectoryProfileEntry>, String>,
+            >,
+        > = HashMap::new();
+
+        // pending turn credential replies keyed by request_response request id
+        let mut pending_turn_credential_replies: HashMap<
+            libp2p::request_response::OutboundRequestId,
+            tokio::sync::oneshot::Sender<
+                Result<crate::protocol::turn::TurnCredentialResponse, String>,
+            >,
+        > = HashMap::new();
+
+        // pending channel history replies keyed by request_response request id
+        let mut pending_history_replies: HashMap<
+            libp2p::request_response::OutboundRequestId,
+            tokio::sync::oneshot::Sender<
+                Result<Vec<crate::protocol::messages::ChatMessage>, String>,
+            >,
+        > = HashMap::new();
+
+        // pending voice roster replies keyed by request_response request id,
+        // with the voice channel key the answer is merged into
+        let mut pending_voice_roster_replies: HashMap<
+            libp2p::request_response::OutboundRequestId,
+            (
+                String,
+                tokio::sync::oneshot::Sender<
+                    Result<Vec<crate::protocol::messages::VoiceParticipant>, String>,
+                >,
+            ),
+        > = HashMap::new();
+
+        // snapshots we pushed to members, their merged doc comes back in the reply
+        let mut pending_snapshots: PendingSnapshots = HashMap::new();
+        // joiners that presented a live invite in a RequestSync, by community
+        // and peer id, with the invite's expiry. their first push carries their
+        // join and has to be taken before our copy lists them
+        let mut invited_joiners: HashMap<(String, String), u64> = HashMap::new();
+
+        // relay_discoverable flag -- read from storage once at startup
+        let mut relay_discoverable = storage
+            .load_settings()
+            .map(|s| s.relay_discoverable)
+            .unwrap_or(true);
+        log::info!("directory: relay_discoverable={}, relay_multiaddr={:?}", relay_discoverable, relay_multiaddr);
+        // relay reconnection state with exponential backoff
+        let mut relay_backoff_secs = RELAY_INITIAL_BACKOFF_SECS;
+        // deferred warning timer -- only notify the frontend after the grace
+        // period expires so transient disconnections don't flash the banner
+        let mut relay_warn_at: Option<tokio::time::Instant> = None;
+        // deferred sync+presence re-broadcast after a new peer connects,
+        // giving gossipsub mesh time to form before publishing
+        let mut deferred_sync_at: Option<tokio::time::Instant> = None;
+        // set while the mobile app is backgrounded, periodic discovery is paused
+        // and only the relay connection is kept so the os does not kill us
+        let mut background_mode = false;
+        // next instant at which we should attempt a relay reconnect
+        let mut relay_retry_at: Option<tokio::time::Instant> = if relay_multiaddr.is_some() {
+            // schedule initial retry in case the first dial failed synchronously
+            Some(
+                tokio::time::Instant::now()
+                    + std::time::Duration::from_secs(RELAY_INITIAL_BACKOFF_SECS),
+            )
+        } else {
+            None
+        };
+
+        loop {
+            tokio::select! {
+                event = swarm_instance.select_next_some() => {
+                    match event {
+                        // --- kademlia fallback discovery lifecycle ---
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Kademlia(
+                            libp2p::kad::Event::OutboundQueryProgressed {
+                                id,
+                                result: libp2p::kad::QueryResult::Bootstrap(Ok(result)),
+                                ..
+                            }
+                        )) => {
+                            log::debug!(
+                                "kademlia bootstrap progress (query {:?}): remaining={}",
+                                id,
+                                result.num_remaining
+                            );
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Kademlia(
+                            libp2p::kad::Event::OutboundQueryProgressed {
+                                id,
+                                result: libp2p::kad::QueryResult::Bootstrap(Err(e)),
+                                ..
+                            }
+                        )) => {
+                            log::warn!("kademlia bootstrap query {:?} failed: {:?}", id, e);
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Kademlia(
+                            libp2p::kad::Event::OutboundQueryProgressed {
+                                id,
+                                result: libp2p::kad::QueryResult::GetClosestPeers(result),
+                                ..
+                            }
+                        )) => {
+                            match result {
+                                Ok(ok) => {
+                                    log::debug!(
+                                        "kademlia closest-peers query {:?} returned {} peer(s)",
+                                        id,
+                                        ok.peers.len()
+                                    );
+                                }
+                                Err(e) => {
+                                    log::warn!(
+                                        "kademlia closest-peers query {:?} failed: {:?}",
+                                        id,
+                                        e
+                                    );
+                                }
+                            }
+                        }
+
+                        // --- gossipsub messages ---
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Gossipsub(
+                            libp2p::gossipsub::Event::Message { propagation_source, message_id, message }
+                        )) => {
+                            // simulated network conditions hold or drop it first, a no-op
+                            // unless the dev server configured them
+                            if let Some(received) = network_sim.inbound(propagation_source, message_id, message) {
+                                receive_gossip(
+                                    &mut swarm_instance,
+                                    &mut peer_scores,
+                                    &mut message_cache,
+                                    &mut prevalidator,
+                                    &event_recorder,
+                                    relay_peer,
+                                    received,
+                                );
+                            }
+                        }
+
+                        // --- mDNS discovery (LAN) ---
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Mdns(
+                            libp2p::mdns::Event::Discovered(peers)
+                        )) => {
+                            for (peer_id, addr) in &peers {
+                                swarm_instance.behaviour_mut().gossipsub.add_explicit_peer(peer_id);
+                                swarm_instance.behaviour_mut().kademlia.add_address(peer_id, addr.clone());
+                                connected_peers.insert(peer_id.to_string());
+                                let _ = app_handle.emit("dusk-event", DuskEvent::PeerConnected {
+                                    peer_id: peer_id.to_string(),
+                                });
+                            }
+                            let _ = app_handle.emit("dusk-event", DuskEvent::NodeStatus {
+                                is_connected: !connected_peers.is_empty(),
+                                peer_count: connected_peers.len(),
+                            });
+
+                            // sync documents and announce profile to newly discovered LAN peers
+                            if !peers.is_empty() {
+                                let community_ids = crdt_engine.lock().await.community_ids();
+                                publish_sync_requests(&mut swarm_instance, &community_ids);
+
+                                announce_scheduler.request(announce::AnnounceTrigger::LanPeer, &announce_policy(&storage));
+                            }
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Mdns(
+                            libp2p::mdns::Event::Expired(peers)
+                        )) => {
+                            for (peer_id, _) in peers {
+                                swarm_instance.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
+                                connected_peers.remove(&peer_id.to_string());
+                                let _ = app_handle.emit("dusk-event", DuskEvent::PeerDisconnected {
+                                    peer_id: peer_id.to_string(),
+                                });
+                            }
+                            let _ = app_handle.emit("dusk-event", DuskEvent::NodeStatus {
+                                is_connected: !connected_peers.is_empty(),
+                                peer_count: connected_peers.len(),
+                            });
+                        }
+
+                        // --- relay client events ---
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::RelayClient(
+                            libp2p::relay::client::Event::ReservationReqAccepted { relay_peer_id, .. }
+                        )) => {
+                            log::info!("relay reservation accepted by {}", relay_peer_id);
+                            relay_reservation_active = true;
+                            event_recorder.state("relay_reserved", &relay_peer_id.to_string());
+                            relay_warn_at = None;
+                            let _ = app_handle.emit("dusk-event", DuskEvent::RelayStatus { connected: true });
+
+                            // now that we have a relay reservation, process any pending
+                            // rendezvous registrations that were queued before the relay was ready
+                            let queued = std::mem::take(&mut pending_registrations);
+                            for ns in queued {
+                                if let Some(rp) = relay_peer {
+                                    match libp2p::rendezvous::Namespace::new(ns.clone()) {
+                                        Ok(namespace) => {
+                                            log::info!(
+                                                "rendezvous register start (queued replay) for namespace '{}'",
+                                                ns
+                                            );
+                                            if let Err(e) = swarm_instance.behaviour_mut().rendezvous.register(
+                                                namespace,
+                                                rp,
+                                                None,
+                                            ) {
+                                                log::warn!(
+                                                    "rendezvous register failed (queued replay) for '{}': {:?}",
+                                                    ns,
+                                                    e
+                                                );
+                                            }
+                                        }
+                                        Err(e) => {
+                                            log::warn!("invalid rendezvous namespace '{}': {:?}", ns, e);
+                                        }
+                                    }
+                                }
+                            }
+
+                            let queued = std::mem::take(&mut pending_discoveries);
+                            for ns in queued {
+                                if let Some(rp) = relay_peer {
+                                    match libp2p::rendezvous::Namespace::new(ns.clone()) {
+                                        Ok(namespace) => {
+                                            log::info!(
+                                                "rendezvous discover start (queued replay) for namespace '{}'",
+                                                ns
+                                            );
+                                            swarm_instance.behaviour_mut().rendezvous.discover(
+                                                Some(namespace),
+                                                None,
+                                                None,
+                                                rp,
+                                            );
+                                        }
+                                        Err(e) => {
+                                            log::warn!(
+                                                "invalid queued rendezvous namespace '{}': {:?}",
+                                                ns,
+                                                e
+                                            );
+                                        }
+                                    }
+                                }
+                            }
+
+                            // queues drained, reset the TTL tracker
+                            pending_queued_at = None;
+
+                            // re-announce our profile now that the relay is up
+                            // the initial announcement in start_node fires before
+                            // any WAN peers are reachable, so this ensures remote
+                            // peers learn about us once the relay mesh is live
+                            announce_scheduler.request(announce::AnnounceTrigger::RelayConnect, &announce_policy(&storage));
+
+                            // register profile in relay's persistent directory if discoverable
+                            if relay_discoverable {
+                                if let Some(ref addr) = relay_multiaddr {
+                                    let local_id = *swarm_instance.local_peer_id();
+                                    register_directory(&mut swarm_instance, &relay_peer_id, &storage, addr, local_id);
+                                    log::info!("directory: sent Register to relay");
+                                } else {
+                                    log::warn!("directory: skipped Register -- relay_multiaddr is None");
+                                }
+                            } else {
+                                // an entry may be left from before we opted out, or from an
+                                // opt-out made while the relay was unreachable
+                                swarm_instance.behaviour_mut().directory_service.send_request(
+                                    &relay_peer_id,
+                                    crate::protocol::directory::DirectoryRequest::Remove,
+                                );
+                                log::info!("directory: skipped Register, sent Remove -- relay_discoverable is false");
+                            }
+
+                            if relay_presence {
+                                pending_presence_heartbeat = Some(send_presence_heartbeat(&mut swarm_instance, &relay_peer_id, &node_keypair, &storage));
+                            }
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::RelayClient(event)) => {
+                            log::debug!("relay client event: {:?}", event);
+                        }
+
+                        // --- rendezvous client events ---
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
+                            libp2p::rendezvous::client::Event::Registered { namespace, .. }
+                        )) => {
+                            log::info!(
+                                "rendezvous register success for namespace '{}'",
+                                namespace
+                            );
+                            register_namespaces.insert(namespace.to_string());
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
+                            libp2p::rendezvous::client::Event::Discovered { registrations, cookie, .. }
+                        )) => {
+                            let namespace_desc = cookie
+                                .namespace()
+                                .map(|ns| ns.to_string())
+                                .unwrap_or_else(|| "<all>".to_string());
+                            log::info!(
+                                "rendezvous discover success: namespace '{}' returned {} peer record(s)",
+                                namespace_desc,
+                                registrations.len()
+                            );
+
+                            // discovered peers on rendezvous, connect to them through the relay
+                            for registration in registrations {
+                                let discovered_peer = registration.record.peer_id();
+                                let local_id = *swarm_instance.local_peer_id();
+
+                                // don't connect to ourselves
+                                if discovered_peer == local_id {
+                                    continue;
+                                }
+
+                                // never expose relay infrastructure in the user directory
+                                if Some(discovered_peer) == relay_peer {
+                                    continue;
+                                }
+
+                                log::info!("discovered peer {} via rendezvous", discovered_peer);
+
+                                // cache a placeholder entry so global discovery is visible
+                                // before we receive the peer's signed profile announcement
+                                let now = std::time::SystemTime::now()
+                                    .duration_since(std::time::UNIX_EPOCH)
+                                    .unwrap()
+                                    .as_millis() as u64;
+                                let discovered_peer_str = discovered_peer.to_string();
+                                let already_known = storage
+                                    .load_directory_entry(&discovered_peer_str)
+                                    .ok()
+                                    .flatten()
+                                    .is_some();
+
+                                // add a lightweight placeholder if we have not learned this peer's profile yet
+                                if !already_known && !storage.is_peer_revoked(&discovered_peer_str) {
+                                    let placeholder = DirectoryEntry {
+                                        peer_id: discovered_peer_str.clone(),
+                                        display_name: "discovered peer".to_string(),
+                                        bio: String::new(),
+                                        public_key: String::new(),
+                                        last_seen: now,
+                                        is_friend: false,
+                                        verified: false,
+                                        activity: None,
+                                        status_message: String::new(),
+                                        status_emoji: String::new(),
+                                        key_verified: false,
+                                        placeholder: true,
+                                    };
+                                    let _ = storage.save_directory_entry(&placeholder);
+
+                                    let _ = app_handle.emit("dusk-event", DuskEvent::ProfileReceived {
+                                        peer_id: placeholder.peer_id,
+                                        display_name: placeholder.display_name,
+                                        bio: placeholder.bio,
+                                        public_key: placeholder.public_key,
+                                        verified: false,
+                                    });
+                                }
+
+                                // with direct connections on, try the public addresses the
+                                // peer registered first and keep the circuit as a fallback
+                                let direct_addrs: Vec<libp2p::Multiaddr> = registration
+                                    .record
+                                    .addresses()
+                                    .iter()
+                                    .filter(|addr| !addr.iter().any(|p| matches!(p, libp2p::multiaddr::Protocol::P2pCircuit)))
+                                    .cloned()
+                                    .collect();
+                                if direct_connections && !direct_addrs.is_empty() {
+                                    let opts = libp2p::swarm::dial_opts::DialOpts::peer_id(discovered_peer)
+                                        .addresses(direct_addrs)
+                                        .build();
+                                    match swarm_instance.dial(opts) {
+                                        Ok(()) => {
+                                            log::info!("direct dial start to discovered peer {}", discovered_peer);
+                                            direct_dial_pending.insert(discovered_peer);
+                                            continue;
+                                        }
+                                        Err(e) => log::debug!("direct dial failed for peer {}: {}", discovered_peer, e),
+                                    }
+                                }
+
+                                // connect through the relay circuit so neither peer reveals their IP
+                                if let Some(ref relay_addr) = relay_multiaddr {
+                                    let circuit_addr = relay_addr.clone()
+                                        .with(libp2p::multiaddr::Protocol::P2pCircuit)
+                                        .with(libp2p::multiaddr::Protocol::P2p(discovered_peer));
+
+                                    log::info!(
+                                        "relay-circuit dial start to discovered peer {} via {}",
+                                        discovered_peer,
+                                        relay_addr
+                                    );
+                                    if let Err(e) = swarm_instance.dial(circuit_addr) {
+                                        log::warn!(
+                                            "relay-circuit dial failed for peer {}: {}",
+                                            discovered_peer,
+                                            e
+                                        );
+                                    } else {
+                                        log::info!(
+                                            "relay-circuit dial initiated for peer {}",
+                                            discovered_peer
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
+                            libp2p::rendezvous::client::Event::RegisterFailed { namespace, error, .. }
+                        )) => {
+                            log::warn!("rendezvous registration failed for '{}': {:?}", namespace, error);
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
+                            libp2p::rendezvous::client::Event::DiscoverFailed { namespace, error, .. }
+                        )) => {
+                            let ns = namespace
+                                .map(|ns| ns.to_string())
+                                .unwrap_or_else(|| "<all>".to_string());
+                            log::warn!(
+                                "rendezvous discover failed for namespace '{}': {:?}",
+                                ns,
+                                error
+                            );
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
+                            libp2p::rendezvous::client::Event::Expired { peer }
+                        )) => {
+                            log::debug!("rendezvous registration expired for peer {}", peer);
+                        }
+
+                        // --- identify events ---
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Identify(
+                            libp2p::identify::Event::Received { peer_id, info, .. }
+                        )) => {
+                            // add observed addresses to kademlia so peers can find each other
+                            for addr in &info.listen_addrs {
+                                swarm_instance.behaviour_mut().kademlia.add_address(&peer_id, addr.clone());
+                            }
+                            log::debug!("identified peer {}: {} addresses", peer_id, info.listen_addrs.len());
+                        }
+
+                        // --- outgoing dial failures ---
+                        libp2p::swarm::SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+                            // a direct dial that didn't get through falls back to the relay circuit
+                            if let Some(failed_peer) = peer_id.filter(|p| direct_dial_pending.remove(p)) {
+                                log::info!("direct dial to {} failed, falling back to relay: {}", failed_peer, error);
+                                if let Some(ref relay_addr) = relay_multiaddr {
+                                    let circuit_addr = relay_addr.clone()
+                                        .with(libp2p::multiaddr::Protocol::P2pCircuit)
+                                        .with(libp2p::multiaddr::Protocol::P2p(failed_peer));
+                                    if let Err(e) = swarm_instance.dial(circuit_addr) {
+                                        log::warn!("relay-circuit dial failed for peer {}: {}", failed_peer, e);
+                                    }
+                                }
+                            }
+
+                            // if this was a failed dial to the relay, schedule a retry
+                            if let Some(failed_peer) = peer_id {
+                                if Some(failed_peer) == relay_peer {
+                                    log::warn!("failed to connect to relay: {}", error);
+                                    log::info!("scheduling relay reconnect in {}s", relay_backoff_secs);
+                                    // defer the warning so transient failures don't flash the banner
+                                    if relay_warn_at.is_none() {
+                                        relay_warn_at = Some(
+                                            tokio::time::Instant::now()
+                                                + std::time::Duration::from_secs(RELAY_WARN_GRACE_SECS),
+                                        );
+                                    }
+                                    relay_retry_at = Some(
+                                        tokio::time::Instant::now() + std::time::Duration::from_secs(relay_backoff_secs),
+                                    );
+                                    // exponential backoff capped at max
+                                    relay_backoff_secs = (relay_backoff_secs * RELAY_BACKOFF_MULTIPLIER)
+                                        .min(RELAY_MAX_BACKOFF_SECS);
+                                }
+                            }
+                        }
+
+                        // --- connection lifecycle ---
+                        libp2p::swarm::SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
+                            // add to gossipsub mesh for WAN peers (mDNS handles LAN peers)
+                            swarm_instance.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                            connected_peers.insert(peer_id.to_string());
+                            direct_dial_pending.remove(&peer_id);
+
+                            // over the cap, strangers go before community members
+                            if Some(peer_id) != relay_peer {
+                                connection_manager.on_connected(peer_id);
+                                if connection_manager.over_limit() {
+                                    let (friends, members) = known_peer_sets(&storage, &crdt_engine).await;
+                                    let evicted = connection_manager
+                                        .select_evictions(|p| connections::classify(p, &friends, &members));
+                                    for peer in evicted {
+                                        log::info!("connection limit of {} reached, disconnecting {}", max_connections, peer);
+                                        // gossipsub redials explicit peers, which would undo the eviction
+                                        swarm_instance.behaviour_mut().gossipsub.remove_explicit_peer(&peer);
+                                        let _ = swarm_instance.disconnect_peer_id(peer);
+                                    }
+                                }
+                            }
+
+                            // remember addresses we dialed successfully for the next start
+                            if let libp2p::core::ConnectedPoint::Dialer { ref address, .. } = endpoint {
+                                if Some(peer_id) != relay_peer {
+                                    let now = std::time::SystemTime::now()
+                                        .duration_since(std::time::UNIX_EPOCH)
+                                        .unwrap()
+                                        .as_millis() as u64;
+                                    if let Err(e) = storage.record_peer_address(&peer_id.to_string(), &address.to_string(), now) {
+                                        log::warn!("failed to remember address for {}: {}", peer_id, e);
+                                    }
+                                }
+                            }
+
+                            if Some(peer_id) == relay_peer {
+                                log::info!("relay dial success: connected to relay peer {}", peer_id);
+                                // the relay always sees our public address, so it is
+                                // the first peer asked to confirm it
+                                if let Some(autonat) = swarm_instance.behaviour_mut().autonat.as_mut() {
+                                    autonat.add_server(peer_id, relay_multiaddr.clone());
+                                }
+                            }
+
+                            let _ = app_handle.emit("dusk-event", DuskEvent::PeerConnected {
+                                peer_id: peer_id.to_string(),
+                            });
+                            let _ = app_handle.emit("dusk-event", DuskEvent::NodeStatus {
+                                is_connected: true,
+                                peer_count: connected_peers.len(),
+                            });
+
+                            // if we just connected to the relay, make a reservation
+                            // so other peers can reach us through it
+                            if Some(peer_id) == relay_peer && !relay_reservation_active {
+                                // reset backoff on successful connection
+                                relay_backoff_secs = RELAY_INITIAL_BACKOFF_SECS;
+                                // cancel any pending retry and deferred warning
+                                relay_retry_at = None;
+                                relay_warn_at = None;
+                                // clear the banner if it was already showing
+                                let _ = app_handle.emit("dusk-event", DuskEvent::RelayStatus { connected: true });
+
+                                if let Some(ref addr) = relay_multiaddr {
+                                    let relay_circuit_addr = addr.clone()
+                                        .with(libp2p::multiaddr::Protocol::P2pCircuit);
+
+                                    log::info!(
+                                        "relay reservation request start via listen_on {}",
+                                        relay_circuit_addr
+                                    );
+                                    if let Err(e) = swarm_instance.listen_on(relay_circuit_addr) {
+                                        log::warn!(
+                                            "relay reservation request failed (listen_on error): {}",
+                                            e
+                                        );
+                                    }
+                                }
+                            }
+
+                            // publish sync requests immediately, a peer already in one of
+                            // our communities' sync meshes answers before the deferred retry
+                            let requests = crdt_engine.lock().await.sync_requests();
+                            let published = publish_sync_requests(&mut swarm_instance, &requests);
+                            log::info!("sync: published RequestSync on connect for {}/{} communities", published, requests.len());
+
+                            // also schedule a deferred retry in case the mesh
+                            // wasn't ready for the immediate publish
+                            if Some(peer_id) != relay_peer {
+                                deferred_sync_at = Some(
+                                    tokio::time::Instant::now() + std::time::Duration::from_secs(3),
+                                );
+                            }
+
+                            // re-announce our profile so the new peer adds us to
+                            // their directory. skip the relay itself since it does
+                            // not participate in the gossipsub directory mesh.
+                            if Some(peer_id) != relay_peer {
+                                announce_scheduler.request(announce::AnnounceTrigger::PeerConnect, &announce_policy(&storage));
+                            }
+                        }
+                        libp2p::swarm::SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
+                            if num_established == 0 {
+                                connected_peers.remove(&peer_id.to_string());
+                                connection_manager.on_disconnected(&peer_id);
+
+                                // remove disconnected peer from all voice channels and notify frontend
+                                let peer_id_str = peer_id.to_string();
+                                let mut vc = voice_channels.lock().await;
+                                let mut empty_keys = Vec::new();
+                                for (key, participants) in vc.iter_mut() {
+                                    let before_len = participants.len();
+                                    participants.retain(|p| p.peer_id != peer_id_str);
+                                    if participants.len() < before_len {
+                                        // parse the key back into community_id and channel_id
+                                        if let Some((cid, chid)) = key.split_once(':') {
+                                            let _ = app_handle.emit("dusk-event", DuskEvent::VoiceParticipantLeft {
+                                                community_id: cid.to_string(),
+                                                channel_id: chid.to_string(),
+                                                peer_id: peer_id_str.clone(),
+                                            });
+                                        }
+                                    }
+                                    if participants.is_empty() {
+                                        empty_keys.push(key.clone());
+                                    }
+                                }
+                                for key in empty_keys {
+                                    vc.remove(&key);
+                                }
+                                drop(vc);
+
+                                let _ = app_handle.emit("dusk-event", DuskEvent::PeerDisconnected {
+                                    peer_id: peer_id.to_string(),
+                                });
+                                let _ = app_handle.emit("dusk-event", DuskEvent::NodeStatus {
+                                    is_connected: !connected_peers.is_empty(),
+                                    peer_count: connected_peers.len(),
+                                });
+
+                                // if we lost the relay connection, mark reservation as inactive
+                                // and schedule a retry with backoff
+                                if Some(peer_id) == relay_peer {
+                                    relay_reservation_active = false;
+                                    event_recorder.state("relay_lost", &peer_id.to_string());
+                                    log::warn!(
+                                        "relay reservation closed (relay connection dropped), scheduling reconnect in {}s",
+                                        relay_backoff_secs
+                                    );
+                                    // defer the warning so quick reconnections don't flash the banner
+                                    if relay_warn_at.is_none() {
+                                        relay_warn_at = Some(
+                                            tokio::time::Instant::now()
+                                                + std::time::Duration::from_secs(RELAY_WARN_GRACE_SECS),
+                                        );
+                                    }
+
+                                    relay_retry_at = Some(
+                                        tokio::time::Instant::now() + std::time::Duration::from_secs(relay_backoff_secs),
+                                    );
+                                    relay_backoff_secs = (relay_backoff_secs * RELAY_BACKOFF_MULTIPLIER)
+                                        .min(RELAY_MAX_BACKOFF_SECS);
+                                }
+                            }
+                        }
+
+                        // gif service response from relay
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::GifService(
+                            libp2p::request_response::Event::Message {
+                                message: libp2p::request_response::Message::Response { request_id, response },
+                                ..
+                            }
+                        )) => {
+                            if let Some(reply) = pending_gif_replies.remove(&request_id) {
+                                let _ = reply.send(Ok(response));
+                            }
+                        }
+                        // gif service outbound failure
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::GifService(
+                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
+                        )) => {
+                            if let Some(reply) = pending_gif_replies.remove(&request_id) {
+                                let _ = reply.send(Err(format!("gif request failed: {:?}", error)));
+                            }
+                        }
+                        // ignore inbound requests (we only send outbound) and other events
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::GifService(_)) => {}
+
+                        // directory service response from relay
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::DirectoryService(
+                            libp2p::request_response::Event::Message {
+                                message: libp2p::request_response::Message::Response { request_id, response },
+                                ..
+                            }
+                        )) => {
+                            if let Some(reply) = pending_directory_replies.remove(&request_id) {
+                                match response {
+                                    crate::protocol::directory::DirectoryResponse::Results(entries) => {
+                                        let _ = reply.send(Ok(entries));
+                                    }
+                                    crate::protocol::directory::DirectoryResponse::Ok => {
+                                        let _ = reply.send(Ok(vec![]));
+                                    }
+                                    crate::protocol::directory::DirectoryResponse::Error(msg) => {
+                                        log::warn!("directory service error from relay: {}", msg);
+                                        let _ = reply.send(Err(msg));
+                                    }
+                                }
+                            }
+                        }
+                        // directory service outbound failure
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::DirectoryService(
+                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
+                        )) => {
+                            log::warn!("directory: outbound failure: {:?}", error);
+                            if let Some(reply) = pending_directory_replies.remove(&request_id) {
+                                let _ = reply.send(Err(format!("directory request failed: {:?}", error)));
+                            }
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::DirectoryService(_)) => {}
+
+                        // turn credentials response from relay
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::TurnCredentials(
+                            libp2p::request_response::Event::Message {
+                                message: libp2p::request_response::Message::Response { request_id, response },
+                                ..
+                            }
+                        )) => {
+                            if let Some(reply) = pending_turn_credential_replies.remove(&request_id) {
+                                let _ = reply.send(Ok(response));
+                            }
+                        }
+                        // turn credentials outbound failure
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::TurnCredentials(
+                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
+                        )) => {
+                            log::warn!("turn credentials: outbound failure: {:?}", error);
+                            if let Some(reply) = pending_turn_credential_replies.remove(&request_id) {
+                                let _ = reply.send(Err(format!("turn credentials request failed: {:?}", error)));
+                            }
+                        }
+                        // ignore inbound requests and other events for turn credentials
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::TurnCredentials(_)) => {}
+
+                        // a peer joined a topic we are still waiting to catch up on
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Gossipsub(
+                            libp2p::gossipsub::Event::Subscribed { peer_id, topic }
+                        )) => {
+                            if Some(peer_id) != relay_peer && pending_catchup_topics.remove(topic.as_str()) {
+                                let topic_str = topic.as_str().to_string();
+                                let since = {
+                                    let engine = crdt_engine.lock().await;
+                                    catchup_cursor(&engine, &storage, &local_peer_str, &topic_str)
+                                };
+                                let request_id = swarm_instance.behaviour_mut().catchup.send_request(
+                                    &peer_id,
+                                    crate::protocol::catchup::CatchupRequest {
+                                        topic: topic_str.clone(),
+                                        since,
+                                    },
+                                );
+                                pending_catchup_requests.insert(request_id, topic_str);
+                            }
+                        }
+
+                        // serve cached payloads to a peer catching up on a topic
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Catchup(
+                            libp2p::request_response::Event::Message {
+                                peer,
+                                message: libp2p::request_response::Message::Request { request, channel, .. },
                                 ..
                             }
                         )) => {
+                            let requester = peer.to_string();
+                            let allowed = {
+                                let engine = crdt_engine.lock().await;
+                                cache::MessageCache::may_serve(
+                                    &request.topic,
+                                    &requester,
+                                    &local_peer_str,
+                                    |community_id| {
+                                        engine
+                                            .get_members(community_id)
+                                            .map(|members| members.iter().any(|m| m.peer_id == requester))
+                                            .unwrap_or(false)
+                                    },
+                                )
+                            };
+                            let messages = if allowed {
+                                message_cache.since(
+                                    &request.topic,
+                                    request.since.saturating_sub(CATCHUP_SLACK_MS),
+                                )
+                            } else {
+                                Vec::new()
+                            };
                             log::debug!(
-                                "kademlia bootstrap progress (query {:?}): remaining={}",
-                                id,
-                                result.num_remaining
+                                "catch-up: serving {} message(s) on '{}' to {}",
+                                messages.len(),
+                                request.topic,
+                                peer
+                            );
+                            let _ = swarm_instance.behaviour_mut().catchup.send_response(
+                                channel,
+                                crate::protocol::catchup::CatchupResponse { messages },
                             );
                         }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Kademlia(
-                            libp2p::kad::Event::OutboundQueryProgressed {
-                                id,
-                                result: libp2p::kad::QueryResult::Bootstrap(Err(e)),
+                        // cached payloads from a peer, replay them through the normal chat path
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Catchup(
+                            libp2p::request_response::Event::Message {
+                                message: libp2p::request_response::Message::Response { request_id, response },
                                 ..
                             }
                         )) => {
-                            log::warn!("kademlia bootstrap query {:?} failed: {:?}", id, e);
-                        }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Kademlia(
-                            libp2p::kad::Event::OutboundQueryProgressed {
-                                id,
-                                result: libp2p::kad::QueryResult::GetClosestPeers(result),
-                                ..
+                            if let Some(topic_str) = pending_catchup_requests.remove(&request_id) {
+                                log::debug!(
+                                    "catch-up: received {} message(s) on '{}'",
+                                    response.messages.len(),
+                                    topic_str
+                                );
+                                for cached in response.messages {
+                                    match serde_json::from_slice::<crate::protocol::messages::GossipMessage>(&cached.data) {
+                                        Ok(crate::protocol::messages::GossipMessage::Chat(chat_msg)) => {
+                                            // not mirrored, a catch-up reply can't show who published it
+                                            ingest_chat_message(
+                                                chat_msg,
+                                                community_id_from_topic(&topic_str),
+                                                &mut seen_chat_ids,
+                                                &hlc_clock,
+                                                &crdt_engine,
+                                                &app_handle,
+                                            )
+                                            .await;
+                                        }
+                                        Ok(crate::protocol::messages::GossipMessage::DirectMessage(dm_msg)) => {
+                                            ingest_direct_message(
+                                                dm_msg,
+                                                &topic_str,
+                                                &mut seen_dm_ids,
+                                                &mut swarm_instance,
+                                                &storage,
+                                                &app_handle,
+                                            );
+                                        }
+                                        // deletions made while we were away
+                                        Ok(crate::protocol::messages::GossipMessage::DMDelete(request))
+                                            if verification::verify_dm_delete(&request) =>
+                                        {
+                                            handle_dm_delete_request(request, &mut swarm_instance, &storage, &app_handle);
+                                        }
+                                        _ => {}
+                                    }
+                                }
                             }
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Catchup(
+                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
                         )) => {
-                            match result {
-                                Ok(ok) => {
-                                    log::debug!(
-                                        "kademlia closest-peers query {:?} returned {} peer(s)",
-                                        id,
-                                        ok.peers.len()
-                                    );
-                                }
-                                Err(e) => {
-                                    log::warn!(
-                                        "kademlia closest-peers query {:?} failed: {:?}",
-                                        id,
-                                        e
-                                    );
-                                }
+                            if let Some(topic_str) = pending_catchup_requests.remove(&request_id) {
+                                log::debug!("catch-up: request for '{}' failed: {:?}", topic_str, error);
                             }
                         }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Catchup(_)) => {}
 
-                        // --- gossipsub messages ---
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Gossipsub(
-                            libp2p::gossipsub::Event::Message { message, .. }
+                        // serve a page of channel history, members only
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::History(
+                            libp2p::request_response::Event::Message {
+                                peer,
+                                message: libp2p::request_response::Message::Request { request, channel, .. },
+                                ..
+                            }
                         )) => {
-                            let topic_str = message.topic.as_str().to_string();
-
-                            // handle sync messages on the dedicated sync topic
-                            if topic_str == gossip::topic_for_sync() {
-                                if let Ok(sync_msg) = serde_json::from_slice::<crate::crdt::sync::SyncMessage>(&message.data) {
-                                    match sync_msg {
-                                        crate::crdt::sync::SyncMessage::RequestSync { peer_id: requesting_peer } => {
-                                            log::info!("sync: received RequestSync from {}", requesting_peer);
-                                            let mut engine = crdt_engine.lock().await;
-                                            let ids = engine.community_ids();
-                                            log::info!("sync: responding with DocumentOffer for {} communities", ids.len());
-                                            for cid in ids {
-                                                if let Some(doc_bytes) = engine.get_doc_bytes(&cid) {
-                                                    let snapshot = crate::crdt::sync::DocumentSnapshot {
-                                                        community_id: cid.clone(),
-                                                        doc_bytes,
-                                                    };
-                                                    let offer = crate::crdt::sync::SyncMessage::DocumentOffer(snapshot);
-                                                    if let Ok(data) = serde_json::to_vec(&offer) {
-                                                        let sync_topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_sync());
-                                                        let _ = swarm_instance.behaviour_mut().gossipsub.publish(sync_topic, data);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        crate::crdt::sync::SyncMessage::DocumentOffer(snapshot) => {
-                                            log::info!(
-                                                "sync: received DocumentOffer for community {} ({} bytes)",
-                                                snapshot.community_id,
-                                                snapshot.doc_bytes.len()
-                                            );
-                                            let mut engine = crdt_engine.lock().await;
-
-                                            // only merge docs for communities we've explicitly joined or created,
-                                            // otherwise any LAN peer would push all their communities to us
-                                            if !engine.has_community(&snapshot.community_id) {
-                                                log::info!("sync: ignoring document offer for unknown community {}", snapshot.community_id);
-                                                continue;
-                                            }
+                            let response = {
+                                let engine = crdt_engine.lock().await;
+                                let is_member = engine
+                                    .get_members(&request.community_id)
+                                    .map(|members| members.iter().any(|m| m.peer_id == peer.to_string()))
+                                    .unwrap_or(false);
 
-                                            let community_id = snapshot.community_id.clone();
-                                            let merge_result = engine.merge_remote_doc(&community_id, &snapshot.doc_bytes);
-                                            match &merge_result {
-                                                Ok(()) => {
-                                                    let member_count = engine.get_members(&community_id)
-                                                        .map(|m| m.len()).unwrap_or(0);
-                                                    log::info!(
-                                                        "sync: merge success for community {}, now have {} members",
-                                                        community_id, member_count
-                                                    );
-                                                }
-                                                Err(e) => {
-                                                    log::warn!("sync: merge failed for community {}: {}", community_id, e);
-                                                }
-                                            }
-                                            let channels_after_merge = if merge_result.is_ok() {
-                                                engine.get_channels(&community_id).unwrap_or_default()
-                                            } else {
-                                                Vec::new()
-                                            };
-                                            let mut corrected_local_role = false;
-                                            let mut corrected_doc_bytes: Option<Vec<u8>> = None;
-                                            if merge_result.is_ok() {
-                                                let should_harden_join_role = {
-                                                    let guard = pending_join_role_guard.lock().await;
-                                                    guard.contains(&community_id)
-                                                };
-
-                                                if should_harden_join_role {
-                                                    let local_peer_id = swarm_instance.local_peer_id().to_string();
-                                                    let local_has_elevated_role = engine
-                                                        .get_members(&community_id)
-                                                        .map(|members| {
-                                                            members.iter().any(|member| {
-                                                                member.peer_id == local_peer_id
-                                                                    && member.roles.iter().any(|role| role == "owner" || role == "admin")
-                                                            })
-                                                        })
-                                                        .unwrap_or(false);
-
-                                                    if local_has_elevated_role {
-                                                        let roles = vec!["member".to_string()];
-                                                        if engine.set_member_role(&community_id, &local_peer_id, &roles).is_ok() {
-                                                            corrected_local_role = true;
-                                                            corrected_doc_bytes = engine.get_doc_bytes(&community_id);
-                                                        }
-                                                    }
-
-                                                    let mut guard = pending_join_role_guard.lock().await;
-                                                    guard.remove(&community_id);
-                                                }
-                                            }
-                                            drop(engine);
-
-                                            match merge_result {
-                                                Ok(()) => {
-                                                    // broadcast our merged doc back so the other side
-                                                    // converges (e.g. they learn about our member entry)
-                                                    {
-                                                        let mut engine = crdt_engine.lock().await;
-                                                        let broadcast_bytes = if let Some(bytes) = corrected_doc_bytes {
-                                                            Some(bytes)
-                                                        } else {
-                                                            engine.get_doc_bytes(&community_id)
-                                                        };
-                                                        if let Some(doc_bytes) = broadcast_bytes {
-                                                            let reply_snapshot = crate::crdt::sync::DocumentSnapshot {
-                                                                community_id: community_id.clone(),
-                                                                doc_bytes,
-                                                            };
-                                                            let reply_offer = crate::crdt::sync::SyncMessage::DocumentOffer(reply_snapshot);
-                                                            if let Ok(data) = serde_json::to_vec(&reply_offer) {
-                                                                let sync_topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_sync());
-                                                                let _ = swarm_instance.behaviour_mut().gossipsub.publish(sync_topic, data);
-                                                            }
-                                                        }
-                                                    }
-
-                                                    if corrected_local_role {
-                                                        log::warn!(
-                                                            "downgraded local elevated role to member during invite join sync for {}",
-                                                            community_id
-                                                        );
-                                                    }
-
-                                                    // keep topic subscriptions aligned with merged channels
-                                                    let presence_topic = libp2p::gossipsub::IdentTopic::new(
-                                                        gossip::topic_for_presence(&community_id),
-                                                    );
-                                                    let _ = swarm_instance
-                                                        .behaviour_mut()
-                                                        .gossipsub
-                                                        .subscribe(&presence_topic);
-
-                                                    for channel in &channels_after_merge {
-                                                        let messages_topic = libp2p::gossipsub::IdentTopic::new(
-                                                            gossip::topic_for_messages(&community_id, &channel.id),
-                                                        );
-                                                        let _ = swarm_instance
-                                                            .behaviour_mut()
-                                                            .gossipsub
-                                                            .subscribe(&messages_topic);
-
-                                                        let typing_topic = libp2p::gossipsub::IdentTopic::new(
-                                                            gossip::topic_for_typing(&community_id, &channel.id),
-                                                        );
-                                                        let _ = swarm_instance
-                                                            .behaviour_mut()
-                                                            .gossipsub
-                                                            .subscribe(&typing_topic);
-                                                    }
-
-                                                    let _ = app_handle.emit("dusk-event", DuskEvent::SyncComplete {
-                                                        community_id,
-                                                    });
-                                                }
-                                                Err(e) => {
-                                                    log::warn!("failed to merge remote doc for {}: {}", community_id, e);
-                                                }
-                                            }
-                                        }
+                                if !is_member {
+                                    crate::protocol::history::HistoryResponse::Error(
+                                        "not a member of this community".to_string(),
+                                    )
+                                } else {
+                                    let limit = request
+                                        .limit
+                                        .min(crate::protocol::history::HISTORY_MAX_LIMIT) as usize;
+                                    match engine.get_messages(
+                                        &request.community_id,
+                                        &request.channel_id,
+                                        request.before,
+                                        limit,
+                                    ) {
+                                        Ok(messages) => crate::protocol::history::HistoryResponse::Messages(messages),
+                                        Err(e) => crate::protocol::history::HistoryResponse::Error(e),
                                     }
                                 }
-                                continue;
+                            };
+                            let _ = swarm_instance.behaviour_mut().history.send_response(channel, response);
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::History(
+                            libp2p::request_response::Event::Message {
+                                message: libp2p::request_response::Message::Response { request_id, response },
+                                ..
                             }
+                        )) => {
+                            if let Some(reply) = pending_history_replies.remove(&request_id) {
+                                let _ = reply.send(match response {
+                                    crate::protocol::history::HistoryResponse::Messages(messages) => Ok(messages),
+                                    crate::protocol::history::HistoryResponse::Error(e) => Err(e),
+                                });
+                            }
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::History(
+                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
+                        )) => {
+                            if let Some(reply) = pending_history_replies.remove(&request_id) {
+                                let _ = reply.send(Err(format!("history request failed: {:?}", error)));
+                            }
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::History(_)) => {}
 
-                            // handle regular gossip messages on community topics
-                            if let Ok(gossip_msg) = serde_json::from_slice::<crate::protocol::messages::GossipMessage>(&message.data) {
-                                match gossip_msg {
-                                    crate::protocol::messages::GossipMessage::Chat(chat_msg) => {
-                                        if let Some(community_id) = community_id_from_topic(&topic_str) {
-                                            let mut engine = crdt_engine.lock().await;
-                                            let _ = engine.append_message(community_id, &chat_msg);
-                                        }
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::MessageReceived(chat_msg));
-                                    }
-                                    crate::protocol::messages::GossipMessage::Typing(indicator) => {
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::Typing {
-                                            peer_id: indicator.peer_id,
-                                            channel_id: indicator.channel_id,
-                                        });
-                                    }
-                                    crate::protocol::messages::GossipMessage::EditMessage { message_id, new_content } => {
-                                        if let Some(community_id) = community_id_from_topic(&topic_str) {
-                                            let mut engine = crdt_engine.lock().await;
-                                            let _ = engine.edit_message(community_id, &message_id, &new_content);
-                                        }
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::MessageEdited { message_id, new_content });
-                                    }
-                                    crate::protocol::messages::GossipMessage::DeleteMessage { message_id } => {
-                                        if let Some(community_id) = community_id_from_topic(&topic_str) {
-                                            let mut engine = crdt_engine.lock().await;
-                                            let _ = engine.delete_message(community_id, &message_id);
-                                        }
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::MessageDeleted { message_id });
-                                    }
-                                    crate::protocol::messages::GossipMessage::MemberKicked { peer_id } => {
-                                        if let Some(community_id) = community_id_from_topic(&topic_str) {
-                                            let mut engine = crdt_engine.lock().await;
-                                            let _ = engine.remove_member(community_id, &peer_id);
-                                        }
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::MemberKicked { peer_id });
-                                    }
-                                    crate::protocol::messages::GossipMessage::Presence(update) => {
-                                        // map PeerStatus to a string the frontend understands
-                                        let status_str = match &update.status {
-                                            crate::protocol::messages::PeerStatus::Online => "Online",
-                                            crate::protocol::messages::PeerStatus::Idle => "Idle",
-                                            crate::protocol::messages::PeerStatus::Dnd => "Dnd",
-                                            crate::protocol::messages::PeerStatus::Offline => "Offline",
-                                        };
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::PresenceUpdated {
-                                            peer_id: update.peer_id.clone(),
-                                            status: status_str.to_string(),
-                                        });
-
-                                        // also update online/offline tracking based on status
-                                        match update.status {
-                                            crate::protocol::messages::PeerStatus::Offline => {
-                                                let _ = app_handle.emit("dusk-event", DuskEvent::PeerDisconnected {
-                                                    peer_id: update.peer_id,
-                                                });
-                                            }
-                                            _ => {
-                                                let _ = app_handle.emit("dusk-event", DuskEvent::PeerConnected {
-                                                    peer_id: update.peer_id,
-                                                });
-                                            }
-                                        }
-                                    }
-                                    crate::protocol::messages::GossipMessage::MetaUpdate(meta) => {
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::SyncComplete {
-                                            community_id: meta.id,
-                                        });
-                                    }
-                                    crate::protocol::messages::GossipMessage::ProfileAnnounce(profile) => {
-                                        // reject announcements with invalid signatures
-                                        if !verification::verify_announcement(&profile.public_key, &profile) {
-                                            log::warn!("rejected unsigned/invalid profile from {}", profile.peer_id);
-                                            continue;
-                                        }
-
-                                        // reject unverified identities
-                                        if profile.verification_proof.is_none() {
-                                            log::warn!("rejected unverified profile from {}", profile.peer_id);
-                                            continue;
-                                        }
-
-                                        // cache the peer profile in our local directory
-                                        let entry = DirectoryEntry {
-                                            peer_id: profile.peer_id.clone(),
-                                            display_name: profile.display_name.clone(),
-                                            bio: profile.bio.clone(),
-                                            public_key: profile.public_key.clone(),
-                                            last_seen: profile.timestamp,
-                                            is_friend: storage
-                                                .load_directory()
-                                                .ok()
-                                                .and_then(|d| d.get(&profile.peer_id).map(|e| e.is_friend))
-                                                .unwrap_or(false),
-                                        };
-                                        let _ = storage.save_directory_entry(&entry);
-
-                                        // update the member's display name in all community crdts
-                                        {
-                                            let mut engine = crdt_engine.lock().await;
-                                            engine.update_member_display_name_everywhere(
-                                                &profile.peer_id,
-                                                &profile.display_name,
-                                            );
-                                        }
-
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::ProfileReceived {
-                                            peer_id: profile.peer_id,
-                                            display_name: profile.display_name,
-                                            bio: profile.bio,
-                                            public_key: profile.public_key,
-                                        });
-                                    }
-                                    crate::protocol::messages::GossipMessage::ProfileRevoke(revocation) => {
-                                        // reject revocations with invalid signatures
-                                        if !verification::verify_revocation(&revocation.public_key, &revocation) {
-                                            log::warn!("rejected unsigned revocation for {}", revocation.peer_id);
-                                            continue;
-                                        }
-
-                                        // peer is revoking their identity, remove them from our directory
-                                        let _ = storage.remove_directory_entry(&revocation.peer_id);
+                        // tell a joining member who is in a call we are part of
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::VoiceRoster(
+                            libp2p::request_response::Event::Message {
+                                peer,
+                                message: libp2p::request_response::Message::Request { request, channel, .. },
+                                ..
+                            }
+                        )) => {
+                            let is_member = {
+                                let engine = crdt_engine.lock().await;
+                                engine
+                                    .get_members(&request.community_id)
+                                    .map(|members| members.iter().any(|m| m.peer_id == peer.to_string()))
+                                    .unwrap_or(false)
+                            };
+                            let local_id = swarm_instance.local_peer_id().to_string();
+                            let key = format!("{}:{}", request.community_id, request.channel_id);
+                            let participants = voice_channels.lock().await.get(&key).cloned().unwrap_or_default();
 
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::ProfileRevoked {
-                                            peer_id: revocation.peer_id,
-                                        });
-                                    }
-                                    crate::protocol::messages::GossipMessage::VoiceJoin {
-                                        community_id, channel_id, peer_id, display_name, media_state,
-                                    } => {
-                                        let participant = crate::protocol::messages::VoiceParticipant {
-                                            peer_id: peer_id.clone(),
-                                            display_name: display_name.clone(),
-                                            media_state: media_state.clone(),
+                            let response = if !is_member {
+                                crate::protocol::voice::VoiceRosterResponse::Error("not a member of this community".to_string())
+                            } else if !participants.iter().any(|p| p.peer_id == local_id) {
+                                crate::protocol::voice::VoiceRosterResponse::Error("not in this voice channel".to_string())
+                            } else {
+                                crate::protocol::voice::VoiceRosterResponse::Participants(participants)
+                            };
+                            let _ = swarm_instance.behaviour_mut().voice_roster.send_response(channel, response);
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::VoiceRoster(
+                            libp2p::request_response::Event::Message {
+                                message: libp2p::request_response::Message::Response { request_id, response },
+                                ..
+                            }
+                        )) => {
+                            if let Some((key, reply)) = pending_voice_roster_replies.remove(&request_id) {
+                                match response {
+                                    crate::protocol::voice::VoiceRosterResponse::Participants(roster) => {
+                                        // join announcements already seen take precedence. the
+                                        // responder only tells us who is there, stage roles come
+                                        // from the doc and role changes we checked ourselves, so a
+                                        // speaker promoted before we arrived shows as a listener
+                                        // until its next role change
+                                        let (community_id, channel_id) = key.split_once(':').unwrap_or_default();
+                                        let admitted: Vec<_> = {
+                                            let engine = crdt_engine.lock().await;
+                                            roster
+                                                .into_iter()
+                                                .map(|mut participant| {
+                                                    participant.stage_role =
+                                                        voice_admission(&engine, community_id, channel_id, &participant.peer_id).1;
+                                                    participant.hand_raised = false;
+                                                    participant
+                                                })
+                                                .collect()
                                         };
-
-                                        // track the participant in shared voice state
-                                        let key = format!("{}:{}", community_id, channel_id);
                                         let mut vc = voice_channels.lock().await;
                                         let participants = vc.entry(key).or_insert_with(Vec::new);
-                                        // avoid duplicates if we receive a repeated join
-                                        participants.retain(|p| p.peer_id != peer_id);
-                                        participants.push(participant);
-                                        drop(vc);
-
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::VoiceParticipantJoined {
-                                            community_id, channel_id, peer_id, display_name, media_state,
-                                        });
-                                    }
-                                    crate::protocol::messages::GossipMessage::VoiceLeave {
-                                        community_id, channel_id, peer_id,
-                                    } => {
-                                        let key = format!("{}:{}", community_id, channel_id);
-                                        let mut vc = voice_channels.lock().await;
-                                        if let Some(participants) = vc.get_mut(&key) {
-                                            participants.retain(|p| p.peer_id != peer_id);
-                                            if participants.is_empty() {
-                                                vc.remove(&key);
-                                            }
-                                        }
-                                        drop(vc);
-
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::VoiceParticipantLeft {
-                                            community_id, channel_id, peer_id,
-                                        });
-                                    }
-                                    crate::protocol::messages::GossipMessage::VoiceParticipantsRequest {
-                                        community_id, channel_id,
-                                    } => {
-                                        // A peer has joined and is requesting the current participants.
-                                        // If we are currently in this channel, we should rebroadcast our VoiceJoin.
-                                        let key = format!("{}:{}", community_id, channel_id);
-                                        let local_id = swarm_instance.local_peer_id().to_string();
-
-                                        let vc = voice_channels.lock().await;
-                                        if let Some(participants) = vc.get(&key) {
-                                            if let Some(me) = participants.iter().find(|p| p.peer_id == local_id) {
-                                                let join_msg = crate::protocol::messages::GossipMessage::VoiceJoin {
-                                                    community_id: community_id.clone(),
-                                                    channel_id: channel_id.clone(),
-                                                    peer_id: me.peer_id.clone(),
-                                                    display_name: me.display_name.clone(),
-                                                    media_state: me.media_state.clone(),
-                                                };
-
-                                                let payload = serde_json::to_vec(&join_msg).unwrap_or_default();
-                                                let topic = libp2p::gossipsub::IdentTopic::new(crate::node::gossip::topic_for_voice(&community_id, &channel_id));
-                                                let _ = swarm_instance.behaviour_mut().gossipsub.publish(topic, payload);
-                                            }
-                                        }
-                                    }
-                                    crate::protocol::messages::GossipMessage::VoiceMediaStateUpdate {
-                                        community_id, channel_id, peer_id, media_state,
-                                    } => {
-                                        // update tracked media state for this participant
-                                        let key = format!("{}:{}", community_id, channel_id);
-                                        let mut vc = voice_channels.lock().await;
-                                        if let Some(participants) = vc.get_mut(&key) {
-                                            if let Some(p) = participants.iter_mut().find(|p| p.peer_id == peer_id) {
-                                                p.media_state = media_state.clone();
-                                            }
-                                        }
-                                        drop(vc);
-
-                                        let _ = app_handle.emit("dusk-event", DuskEvent::VoiceMediaStateChanged {
-                                            community_id, channel_id, peer_id, media_state,
-                                        });
-                                    }
-                                    crate::protocol::messages::GossipMessage::VoiceSdp {
-                                        community_id, channel_id, from_peer, to_peer, sdp_type, sdp,
-                                    } => {
-                                        // only forward sdp messages addressed to us
-                                        let local_id = swarm_instance.local_peer_id().to_string();
-                                        if to_peer == local_id {
-                                            let _ = app_handle.emit("dusk-event", DuskEvent::VoiceSdpReceived {
-                                                community_id, channel_id, from_peer, sdp_type, sdp,
-                                            });
-                                        }
-                                    }
-                                    crate::protocol::messages::GossipMessage::VoiceIceCandidate {
-                                        community_id, channel_id, from_peer, to_peer, candidate, sdp_mid, sdp_mline_index,
-                                    } => {
-                                        // only forward ice candidates addressed to us
-                                        let local_id = swarm_instance.local_peer_id().to_string();
-                                        if to_peer == local_id {
-                                            let _ = app_handle.emit("dusk-event", DuskEvent::VoiceIceCandidateReceived {
-                                                community_id, channel_id, from_peer, candidate, sdp_mid, sdp_mline_index,
-                                            });
-                                        }
-                                    }
-                                    crate::protocol::messages::GossipMessage::DirectMessage(dm_msg) => {
-                                        // only process dms addressed to us (ignore our own echoes)
-                                        let local_id = swarm_instance.local_peer_id().to_string();
-                                        if dm_msg.to_peer == local_id {
-                                            // dedup: messages arrive on both the pair topic and inbox
-                                            // topic so skip if we've already processed this one
-                                            if !seen_dm_ids.insert(dm_msg.id.clone()) {
-                                                continue;
+                                        for participant in admitted {
+                                            if !participants.iter().any(|p| p.peer_id == participant.peer_id) {
+                                                participants.push(participant);
                                             }
-                                            // cap the dedup set to prevent unbounded memory growth
-                                            if seen_dm_ids.len() > 10000 {
-                                                seen_dm_ids.clear();
-                                            }
-
-                                            // if this arrived on the inbox topic, the sender might be
-                                            // someone we've never dm'd before -- auto-subscribe to the
-                                            // pair topic so subsequent messages use the direct channel
-                                            if topic_str.starts_with("dusk/dm/inbox/") {
-                                                let pair_topic = gossip::topic_for_dm(&dm_msg.from_peer, &dm_msg.to_peer);
-                                                let ident_topic = libp2p::gossipsub::IdentTopic::new(pair_topic);
-                                                let _ = swarm_instance.behaviour_mut().gossipsub.subscribe(&ident_topic);
-                                            }
-
-                                            // persist the incoming message
-                                            let conversation_id = gossip::dm_conversation_id(&dm_msg.from_peer, &dm_msg.to_peer);
-                                            let _ = storage.append_dm_message(&conversation_id, &dm_msg);
-
-                                            // update or create conversation metadata
-                                            let existing = storage.load_dm_conversation(&conversation_id).ok();
-                                            let meta = crate::protocol::messages::DMConversationMeta {
-                                                peer_id: dm_msg.from_peer.clone(),
-                                                display_name: dm_msg.from_display_name.clone(),
-                                                last_message: Some(dm_msg.content.clone()),
-                                                last_message_time: Some(dm_msg.timestamp),
-                                                unread_count: existing.map(|m| m.unread_count + 1).unwrap_or(1),
-                                            };
-                                            let _ = storage.save_dm_conversation(&conversation_id, &meta);
-
-                                            let _ = app_handle.emit("dusk-event", DuskEvent::DMReceived(dm_msg));
                                         }
+                                        let _ = reply.send(Ok(participants.clone()));
                                     }
-                                    crate::protocol::messages::GossipMessage::DMTyping(indicator) => {
-                                        let local_id = swarm_instance.local_peer_id().to_string();
-                                        if indicator.to_peer == local_id {
-                                            let _ = app_handle.emit("dusk-event", DuskEvent::DMTyping {
-                                                peer_id: indicator.from_peer,
-                                            });
-                                        }
+                                    crate::protocol::voice::VoiceRosterResponse::Error(e) => {
+                                        let _ = reply.send(Err(e));
                                     }
                                 }
                             }
                         }
-
-                        // --- mDNS discovery (LAN) ---
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Mdns(
-                            libp2p::mdns::Event::Discovered(peers)
-                        )) => {
-                            for (peer_id, addr) in &peers {
-                                swarm_instance.behaviour_mut().gossipsub.add_explicit_peer(peer_id);
-                                swarm_instance.behaviour_mut().kademlia.add_address(peer_id, addr.clone());
-                                connected_peers.insert(peer_id.to_string());
-                                let _ = app_handle.emit("dusk-event", DuskEvent::PeerConnected {
-                                    peer_id: peer_id.to_string(),
-                                });
-                            }
-                            let _ = app_handle.emit("dusk-event", DuskEvent::NodeStatus {
-                                is_connected: !connected_peers.is_empty(),
-                                peer_count: connected_peers.len(),
-                            });
-
-                            // sync documents and announce profile to newly discovered LAN peers
-                            if !peers.is_empty() {
-                                let local_peer_id = *swarm_instance.local_peer_id();
-                                let request = crate::crdt::sync::SyncMessage::RequestSync {
-                                    peer_id: local_peer_id.to_string(),
-                                };
-                                if let Ok(data) = serde_json::to_vec(&request) {
-                                    let sync_topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_sync());
-                                    let _ = swarm_instance.behaviour_mut().gossipsub.publish(sync_topic, data);
-                                }
-
-                                publish_profile(&mut swarm_instance, &node_keypair, &storage);
-                            }
-                        }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Mdns(
-                            libp2p::mdns::Event::Expired(peers)
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::VoiceRoster(
+                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
                         )) => {
-                            for (peer_id, _) in peers {
-                                swarm_instance.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
-                                connected_peers.remove(&peer_id.to_string());
-                                let _ = app_handle.emit("dusk-event", DuskEvent::PeerDisconnected {
-                                    peer_id: peer_id.to_string(),
-                                });
+                            if let Some((_, reply)) = pending_voice_roster_replies.remove(&request_id) {
+                                let _ = reply.send(Err(format!("voice roster request failed: {:?}", error)));
                             }
-                            let _ = app_handle.emit("dusk-event", DuskEvent::NodeStatus {
-                                is_connected: !connected_peers.is_empty(),
-                                peer_count: connected_peers.len(),
-                            });
                         }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::VoiceRoster(_)) => {}
 
-                        // --- relay client events ---
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::RelayClient(
-                            libp2p::relay::client::Event::ReservationReqAccepted { relay_peer_id, .. }
+                        // a member handed us its doc, merge it and hand ours back if the
+                        // sender was in our member list before the merge. anyone else
+                        // could list themselves in the doc they push
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Snapshot(
+                            libp2p::request_response::Event::Message {
+                                peer,
+                                message: libp2p::request_response::Message::Request { request, channel, .. },
+                                ..
+                            }
                         )) => {
-                            log::info!("relay reservation accepted by {}", relay_peer_id);
-                            relay_reservation_active = true;
-                            relay_warn_at = None;
-                            let _ = app_handle.emit("dusk-event", DuskEvent::RelayStatus { connected: true });
-
-                            // now that we have a relay reservation, process any pending
-                            // rendezvous registrations that were queued before the relay was ready
-                            let queued = std::mem::take(&mut pending_registrations);
-                            for ns in queued {
-                                if let Some(rp) = relay_peer {
-                                    match libp2p::rendezvous::Namespace::new(ns.clone()) {
-                                        Ok(namespace) => {
-                                            log::info!(
-                                                "rendezvous register start (queued replay) for namespace '{}'",
-                                                ns
-                                            );
-                                            if let Err(e) = swarm_instance.behaviour_mut().rendezvous.register(
-                                                namespace,
-                                                rp,
-                                                None,
-                                            ) {
-                                                log::warn!(
-                                                    "rendezvous register failed (queued replay) for '{}': {:?}",
-                                                    ns,
-                                                    e
-                                                );
-                                            }
-                                        }
-                                        Err(e) => {
-                                            log::warn!("invalid rendezvous namespace '{}': {:?}", ns, e);
-                                        }
+                            event_recorder.snapshot(&peer, &request);
+                            let community_id = request.community_id.clone();
+                            let peer_str = peer.to_string();
+                            let members_before = crdt_engine
+                                .lock()
+                                .await
+                                .get_members(&community_id)
+                                .unwrap_or_default();
+                            let was_member = members_before.iter().any(|m| m.peer_id == peer_str);
+                            let now = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap()
+                                .as_millis() as u64;
+                            let invited = invited_joiners
+                                .get(&(community_id.clone(), peer_str))
+                                .is_some_and(|expires_at| *expires_at > now);
+                            // a placeholder we are still joining lists nobody yet, its first
+                            // snapshot is vetted against the owner checkpoint instead
+                            if !was_member && !invited && !members_before.is_empty() {
+                                log::info!("sync: ignoring snapshot for {} from non-member {}", community_id, peer);
+                                let response = crate::protocol::snapshot::SnapshotResponse::Error(
+                                    "snapshot not accepted".to_string(),
+                                );
+                                let _ = swarm_instance.behaviour_mut().snapshot.send_response(channel, response);
+                                continue;
+                            }
+                            let merged = merge_snapshot(
+                                request,
+                                &mut swarm_instance,
+                                &crdt_engine,
+                                &storage,
+                                &unverified_peers,
+                                &node_keypair,
+                                &app_handle,
+                            )
+                            .await;
+                            let response = match merged {
+                                SnapshotMerge::Ignored => {
+                                    crate::protocol::snapshot::SnapshotResponse::Error("snapshot not accepted".to_string())
+                                }
+                                SnapshotMerge::Malformed => {
+                                    penalize_peer(
+                                        &mut swarm_instance,
+                                        &mut peer_scores,
+                                        &peer,
+                                        scoring::Misbehaviour::MalformedDoc,
+                                    );
+                                    crate::protocol::snapshot::SnapshotResponse::Error("snapshot failed to merge".to_string())
+                                }
+                                SnapshotMerge::Merged { corrected } => {
+                                    let mut engine = crdt_engine.lock().await;
+                                    if corrected {
+                                        // every member needs the removals, the sender included
+                                        push_snapshot(&mut swarm_instance, &mut pending_snapshots, &mut engine, &community_id);
+                                        crate::protocol::snapshot::SnapshotResponse::Merged(None)
+                                    } else {
+                                        let reply = was_member
+                                            .then(|| engine.get_doc_bytes(&community_id))
+                                            .flatten()
+                                            .map(|doc_bytes| crate::crdt::sync::DocumentSnapshot {
+                                                community_id: community_id.clone(),
+                                                doc_bytes,
+                                            });
+                                        crate::protocol::snapshot::SnapshotResponse::Merged(reply)
                                     }
                                 }
+                            };
+                            let _ = swarm_instance.behaviour_mut().snapshot.send_response(channel, response);
+                        }
+                        // the member we pushed to merged and sent its doc back
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Snapshot(
+                            libp2p::request_response::Event::Message {
+                                peer,
+                                message: libp2p::request_response::Message::Response { request_id, response },
+                                ..
                             }
-
-                            let queued = std::mem::take(&mut pending_discoveries);
-                            for ns in queued {
-                                if let Some(rp) = relay_peer {
-                                    match libp2p::rendezvous::Namespace::new(ns.clone()) {
-                                        Ok(namespace) => {
-                                            log::info!(
-                                                "rendezvous discover start (queued replay) for namespace '{}'",
-                                                ns
-                                            );
-                                            swarm_instance.behaviour_mut().rendezvous.discover(
-                                                Some(namespace),
-                                                None,
-                                                None,
-                                                rp,
+                        )) => {
+                            let Some(community_id) = pending_snapshots.remove(&request_id) else {
+                                continue;
+                            };
+                            match response {
+                                crate::protocol::snapshot::SnapshotResponse::Merged(Some(snapshot))
+                                    if snapshot.community_id == community_id =>
+                                {
+                                    event_recorder.snapshot(&peer, &snapshot);
+                                    // no reply to a reply, so two members never bounce docs back and forth
+                                    let merged = merge_snapshot(
+                                        snapshot,
+                                        &mut swarm_instance,
+                                        &crdt_engine,
+                                        &storage,
+                                        &unverified_peers,
+                                        &node_keypair,
+                                        &app_handle,
+                                    )
+                                    .await;
+                                    match merged {
+                                        SnapshotMerge::Malformed => {
+                                            penalize_peer(
+                                                &mut swarm_instance,
+                                                &mut peer_scores,
+                                                &peer,
+                                                scoring::Misbehaviour::MalformedDoc,
                                             );
                                         }
-                                        Err(e) => {
-                                            log::warn!(
-                                                "invalid queued rendezvous namespace '{}': {:?}",
-                                                ns,
-                                                e
-                                            );
+                                        SnapshotMerge::Merged { corrected: true } => {
+                                            let mut engine = crdt_engine.lock().await;
+                                            push_snapshot(&mut swarm_instance, &mut pending_snapshots, &mut engine, &community_id);
                                         }
+                                        _ => {}
                                     }
                                 }
-                            }
-
-                            // queues drained, reset the TTL tracker
-                            pending_queued_at = None;
-
-                            // re-announce our profile now that the relay is up
-                            // the initial announcement in start_node fires before
-                            // any WAN peers are reachable, so this ensures remote
-                            // peers learn about us once the relay mesh is live
-                            publish_profile(&mut swarm_instance, &node_keypair, &storage);
-
-                            // register profile in relay's persistent directory if discoverable
-                            if relay_discoverable {
-                                if let Some(ref addr) = relay_multiaddr {
-                                    let local_id = *swarm_instance.local_peer_id();
-                                    register_directory(&mut swarm_instance, &relay_peer_id, &storage, addr, local_id);
-                                    log::info!("directory: sent Register to relay");
-                                } else {
-                                    log::warn!("directory: skipped Register -- relay_multiaddr is None");
+                                crate::protocol::snapshot::SnapshotResponse::Merged(_) => {}
+                                crate::protocol::snapshot::SnapshotResponse::Error(e) => {
+                                    log::debug!("sync: {} did not take our snapshot of {}: {}", peer, community_id, e);
                                 }
-                            } else {
-                                log::info!("directory: skipped Register -- relay_discoverable is false");
                             }
                         }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::RelayClient(event)) => {
-                            log::debug!("relay client event: {:?}", event);
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Snapshot(
+                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
+                        )) => {
+                            if let Some(community_id) = pending_snapshots.remove(&request_id) {
+                                log::debug!("sync: snapshot push for {} failed: {:?}", community_id, error);
+                            }
                         }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Snapshot(_)) => {}
 
-                        // --- rendezvous client events ---
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
-                            libp2p::rendezvous::client::Event::Registered { namespace, .. }
+                        // friend presence response from relay
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::PresenceService(
+                            libp2p::request_response::Event::Message {
+                                message: libp2p::request_response::Message::Response { request_id, response },
+                                ..
+                            }
                         )) => {
-                            log::info!(
-                                "rendezvous register success for namespace '{}'",
-                                namespace
-                            );
-                            register_namespaces.insert(namespace.to_string());
+                            if pending_presence_heartbeat.as_ref().map(|(id, _)| *id) == Some(request_id) {
+                                let (_, friends) = pending_presence_heartbeat.take().unwrap();
+                                match response {
+                                    crate::protocol::presence::PresenceResponse::Online(tokens) => {
+                                        // tokens we didn't ask about are ignored
+                                        let online = tokens
+                                            .iter()
+                                            .filter_map(|token| friends.get(token).cloned())
+                                            .collect();
+                                        apply_friend_presence(online, &mut relay_online_friends, &app_handle);
+                                    }
+                                    crate::protocol::presence::PresenceResponse::Ok => {}
+                                    crate::protocol::presence::PresenceResponse::Error(msg) => {
+                                        log::warn!("presence service error from relay: {}", msg);
+                                    }
+                                }
+                            }
                         }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
-                            libp2p::rendezvous::client::Event::Discovered { registrations, cookie, .. }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::PresenceService(
+                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
                         )) => {
-                            let namespace_desc = cookie
-                                .namespace()
-                                .map(|ns| ns.to_string())
-                                .unwrap_or_else(|| "<all>".to_string());
-                            log::info!(
-                                "rendezvous discover success: namespace '{}' returned {} peer record(s)",
-                                namespace_desc,
-                                registrations.len()
-                            );
-
-                            // discovered peers on rendezvous, connect to them through the relay
-                            for registration in registrations {
-                                let discovered_peer = registration.record.peer_id();
-                                let local_id = *swarm_instance.local_peer_id();
-
-                                // don't connect to ourselves
-                                if discovered_peer == local_id {
-                                    continue;
-                                }
+                            log::debug!("presence: outbound failure: {:?}", error);
+                            if pending_presence_heartbeat.as_ref().map(|(id, _)| *id) == Some(request_id) {
+                                pending_presence_heartbeat = None;
+                            }
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::PresenceService(_)) => {}
 
-                                // never expose relay infrastructure in the user directory
-                                if Some(discovered_peer) == relay_peer {
-                                    continue;
-                                }
+                        // --- external address confirmation ---
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Autonat(
+                            libp2p::autonat::Event::StatusChanged { old, new }
+                        )) => {
+                            log::info!("nat status changed from {:?} to {:?}", old, new);
+                        }
+                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Autonat(_)) => {}
+                        libp2p::swarm::SwarmEvent::ExternalAddrConfirmed { address } => {
+                            log::info!("external address confirmed: {}", address);
+                        }
+                        libp2p::swarm::SwarmEvent::ExternalAddrExpired { address } => {
+                            log::info!("external address expired: {}", address);
+                        }
 
-                                log::info!("discovered peer {} via rendezvous", discovered_peer);
+                        other => {
+                            log::info!("unhandled swarm event: {:?}", other);
+                        }
+                    }
+                }
 
-                                // cache a placeholder entry so global discovery is visible
-                                // before we receive the peer's signed profile announcement
-                                let now = std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_millis() as u64;
-                                let discovered_peer_str = discovered_peer.to_string();
-                                let already_known = storage
-                                    .load_directory()
-                                    .ok()
-                                    .map(|d| d.contains_key(&discovered_peer_str))
-                                    .unwrap_or(false);
+                // inbound gossip decoded and signature checked by the prevalidation workers, in arrival order
+                Some(validated) = prevalidator.next(), if !prevalidator.is_idle() => {
+                    let prevalidate::Prevalidated {
+                        propagation_source,
+                        message_id,
+                        message,
+                        payload,
+                        signature_valid,
+                        proof_verified,
+                    } = validated;
+                    // borrowed from the message, it outlives every handler below
+                    let topic_str = message.topic.as_str();
+
+                    // a payload that doesn't decode or carries a bad signature is
+                    // rejected, so gossipsub drops it instead of relaying it. the app
+                    // penalty goes to its signed author, not the peer that forwarded it
+                    let invalid = if matches!(payload, prevalidate::Payload::Malformed) {
+                        Some(scoring::Misbehaviour::MalformedMessage)
+                    } else if !signature_valid {
+                        Some(scoring::Misbehaviour::InvalidSignature)
+                    } else {
+                        None
+                    };
+                    let acceptance = match invalid {
+                        Some(kind) => {
+                            let author = message.source.unwrap_or(propagation_source);
+                            log::warn!("rejected gossip from {} on {}: {}", author, topic_str, kind.as_str());
+                            penalize_peer(&mut swarm_instance, &mut peer_scores, &author, kind);
+                            libp2p::gossipsub::MessageAcceptance::Reject
+                        }
+                        // a message type from a newer release is dropped here without
+                        // counting against its author, so mixed versions keep meshing
+                        None if matches!(payload, prevalidate::Payload::Unknown) => {
+                            log::debug!("ignoring unknown gossip message on {}", topic_str);
+                            libp2p::gossipsub::MessageAcceptance::Ignore
+                        }
+                        None => libp2p::gossipsub::MessageAcceptance::Accept,
+                    };
+                    report_gossip(&mut swarm_instance, &message_id, &propagation_source, acceptance);
+                    if invalid.is_some() || matches!(payload, prevalidate::Payload::Unknown) {
+                        continue;
+                    }
 
-                                // add a lightweight placeholder if we have not learned this peer's profile yet
-                                if !already_known {
-                                    let placeholder = DirectoryEntry {
-                                        peer_id: discovered_peer_str.clone(),
-                                        display_name: "discovered peer".to_string(),
-                                        bio: String::new(),
-                                        public_key: String::new(),
-                                        last_seen: now,
-                                        is_friend: false,
+                    // handle sync messages on the community sync topics
+                    if let Some(sync_community) = gossip::community_of_sync_topic(topic_str).map(str::to_string) {
+                        if let prevalidate::Payload::Sync(sync_msg) = payload {
+                            match sync_msg {
+                                crate::crdt::sync::SyncMessage::RequestSync { peer_id: requesting_peer, community_id, invite } => {
+                                    log::info!("sync: received RequestSync for {} from {}", sync_community, requesting_peer);
+                                    if !community_id.is_empty() && community_id != sync_community {
+                                        continue;
+                                    }
+                                    // only the peer named in the request may send it, and only peers in
+                                    // our copy of the community or holding a live invite get a snapshot
+                                    let Some(requester) = message.source.filter(|p| p.to_string() == requesting_peer) else {
+                                        continue;
                                     };
-                                    let _ = storage.save_directory_entry(&placeholder);
-
-                                    let _ = app_handle.emit("dusk-event", DuskEvent::ProfileReceived {
-                                        peer_id: placeholder.peer_id,
-                                        display_name: placeholder.display_name,
-                                        bio: placeholder.bio,
-                                        public_key: placeholder.public_key,
+                                    let mut engine = crdt_engine.lock().await;
+                                    if !engine.has_community(&sync_community) || engine.is_archived(&sync_community) {
+                                        continue;
+                                    }
+                                    let members = engine.get_members(&sync_community).unwrap_or_default();
+                                    let is_member = members.iter().any(|m| m.peer_id == requesting_peer);
+                                    // the invite must be for this community, unexpired, and come from
+                                    // someone still in it. its signature was checked on arrival
+                                    let now = std::time::SystemTime::now()
+                                        .duration_since(std::time::UNIX_EPOCH)
+                                        .unwrap()
+                                        .as_millis() as u64;
+                                    let invited = invite.as_ref().is_some_and(|invite| {
+                                        invite.community_id == sync_community
+                                            && invite.expires_at > now
+                                            && members.iter().any(|m| m.peer_id == invite.inviter)
                                     });
-                                }
-
-                                // connect through the relay circuit so neither peer reveals their IP
-                                if let Some(ref relay_addr) = relay_multiaddr {
-                                    let circuit_addr = relay_addr.clone()
-                                        .with(libp2p::multiaddr::Protocol::P2pCircuit)
-                                        .with(libp2p::multiaddr::Protocol::P2p(discovered_peer));
-
-                                    log::info!(
-                                        "relay-circuit dial start to discovered peer {} via {}",
-                                        discovered_peer,
-                                        relay_addr
-                                    );
-                                    if let Err(e) = swarm_instance.dial(circuit_addr) {
-                                        log::warn!(
-                                            "relay-circuit dial failed for peer {}: {}",
-                                            discovered_peer,
-                                            e
-                                        );
-                                    } else {
-                                        log::info!(
-                                            "relay-circuit dial initiated for peer {}",
-                                            discovered_peer
+                                    if !is_member && !invited {
+                                        log::info!("sync: ignoring RequestSync for {} from {} without membership proof", sync_community, requesting_peer);
+                                        continue;
+                                    }
+                                    if let Some(invite) = invite.filter(|_| !is_member) {
+                                        invited_joiners.retain(|_, expires_at| *expires_at > now);
+                                        invited_joiners.insert((sync_community.clone(), requesting_peer), invite.expires_at);
+                                    }
+                                    if let Some(doc_bytes) = engine.get_doc_bytes(&sync_community) {
+                                        send_snapshot(
+                                            &mut swarm_instance,
+                                            &mut pending_snapshots,
+                                            &requester,
+                                            sync_community,
+                                            doc_bytes,
                                         );
                                     }
                                 }
+                                crate::crdt::sync::SyncMessage::DocumentOffer(snapshot) => {
+                                    // snapshots only arrive over the snapshot protocol now, one on
+                                    // the public topic comes from an older client
+                                    log::debug!(
+                                        "sync: ignoring document offer for {} on the sync topic",
+                                        snapshot.community_id
+                                    );
+                                }
                             }
                         }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
-                            libp2p::rendezvous::client::Event::RegisterFailed { namespace, error, .. }
-                        )) => {
-                            log::warn!("rendezvous registration failed for '{}': {:?}", namespace, error);
-                        }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
-                            libp2p::rendezvous::client::Event::DiscoverFailed { namespace, error, .. }
-                        )) => {
-                            let ns = namespace
-                                .map(|ns| ns.to_string())
-                                .unwrap_or_else(|| "<all>".to_string());
-                            log::warn!(
-                                "rendezvous discover failed for namespace '{}': {:?}",
-                                ns,
-                                error
-                            );
-                        }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Rendezvous(
-                            libp2p::rendezvous::client::Event::Expired { peer }
-                        )) => {
-                            log::debug!("rendezvous registration expired for peer {}", peer);
-                        }
+                        continue;
+                    }
 
-                        // --- identify events ---
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::Identify(
-                            libp2p::identify::Event::Received { peer_id, info, .. }
-                        )) => {
-                            // add observed addresses to kademlia so peers can find each other
-                            for addr in &info.listen_addrs {
-                                swarm_instance.behaviour_mut().kademlia.add_address(&peer_id, addr.clone());
+                    // handle regular gossip messages on community topics
+                    if let prevalidate::Payload::Gossip(gossip_msg) = payload {
+                        match *gossip_msg {
+                            crate::protocol::messages::GossipMessage::Chat(chat_msg) => {
+                                // chat messages aren't signed, only the author's own publish is
+                                // mirrored so nobody can post into followers under their name
+                                if message.source.is_some_and(|p| p.to_string() == chat_msg.author_id) {
+                                    let mirrored = mirror_followed_announcement(
+                                        &chat_msg,
+                                        topic_str,
+                                        &local_peer_str,
+                                        &storage,
+                                        &hlc_clock,
+                                        &crdt_engine,
+                                        &app_handle,
+                                    )
+                                    .await;
+                                    publish_mirrored(&mut swarm_instance, &mut message_cache, mirrored);
+                                }
+                                let receipt = (chat_msg.author_id != local_peer_id)
+                                    .then(|| (chat_msg.id.clone(), chat_msg.author_id.clone()));
+                                let appended = ingest_chat_message(
+                                    chat_msg,
+                                    community_id_from_topic(topic_str),
+                                    &mut seen_chat_ids,
+                                    &hlc_clock,
+                                    &crdt_engine,
+                                    &app_handle,
+                                )
+                                .await;
+
+                                // a few members confirm receipt so the author can measure delivery,
+                                // only for messages that just landed in the doc
+                                if let (true, Some((message_id, author_id)), Some(community_id)) = (appended, receipt, community_id_from_topic(topic_str)) {
+                                    let member_count = crdt_engine.lock().await.get_members(community_id).map_or(0, |m| m.len());
+                                    if crate::latency::should_send_receipt(&message_id, &local_peer_id, member_count) {
+                                        let receipt = crate::protocol::messages::GossipMessage::ChatReceipt { message_id, author_id };
+                                        let data = encoder.encode(&receipt).unwrap_or_default();
+                                        publish_outbound(&mut swarm_instance, &mut message_cache, &mut network_sim, topic_str.to_string(), data);
+                                    }
+                                }
                             }
-                            log::debug!("identified peer {}: {} addresses", peer_id, info.listen_addrs.len());
-                        }
-
-                        // --- outgoing dial failures ---
-                        libp2p::swarm::SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
-                            // if this was a failed dial to the relay, schedule a retry
-                            if let Some(failed_peer) = peer_id {
-                                if Some(failed_peer) == relay_peer {
-                                    log::warn!("failed to connect to relay: {}", error);
-                                    log::info!("scheduling relay reconnect in {}s", relay_backoff_secs);
-                                    // defer the warning so transient failures don't flash the banner
-                                    if relay_warn_at.is_none() {
-                                        relay_warn_at = Some(
-                                            tokio::time::Instant::now()
-                                                + std::time::Duration::from_secs(RELAY_WARN_GRACE_SECS),
-                                        );
+                            crate::protocol::messages::GossipMessage::ChatReceipt { message_id, author_id } => {
+                                // only the author tracks receipts for its messages
+                                if author_id != swarm_instance.local_peer_id().to_string() {
+                                    continue;
+                                }
+                                latency.record_receipt(&message_id);
+                            }
+                            crate::protocol::messages::GossipMessage::Typing(indicator) => {
+                                let _ = app_handle.emit("dusk-event", DuskEvent::Typing {
+                                    peer_id: indicator.peer_id,
+                                    channel_id: indicator.channel_id,
+                                });
+                            }
+                            crate::protocol::messages::GossipMessage::EditMessage { message_id, new_content } => {
+                                if let Some(community_id) = community_id_from_topic(topic_str) {
+                                    let mut engine = crdt_engine.lock().await;
+                                    let _ = engine.edit_message(community_id, &message_id, &new_content);
+                                }
+                                let _ = app_handle.emit("dusk-event", DuskEvent::MessageEdited { message_id, new_content });
+                            }
+                            crate::protocol::messages::GossipMessage::DeleteMessage { message_id, capability } => {
+                                // the author, the owner or a holder of a delete
+                                // capability for the channel, checked against the
+                                // signed gossip source
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                let Some(community_id) = community_id_from_topic(topic_str) else {
+                                    continue;
+                                };
+                                let mut engine = crdt_engine.lock().await;
+                                let allowed = match engine.get_message(community_id, &message_id) {
+                                    Ok(Some(msg)) => {
+                                        msg.author_id == sender
+                                            || engine.may_moderate(
+                                                community_id,
+                                                &sender,
+                                                ModerationCapability::DeleteMessages,
+                                                Some(&msg.channel_id),
+                                                capability.as_ref(),
+                                            )
                                     }
-                                    relay_retry_at = Some(
-                                        tokio::time::Instant::now() + std::time::Duration::from_secs(relay_backoff_secs),
+                                    _ => false,
+                                };
+                                if !allowed {
+                                    log::warn!("ignoring unauthorized deletion of {} from {}", message_id, sender);
+                                    continue;
+                                }
+                                let _ = engine.delete_message(community_id, &message_id);
+                                drop(engine);
+                                let _ = app_handle.emit("dusk-event", DuskEvent::MessageDeleted { message_id });
+                            }
+                            crate::protocol::messages::GossipMessage::Reaction { message_id, emoji, active } => {
+                                // the reaction is the signed gossip source's own, and only members react
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                let Some(community_id) = community_id_from_topic(topic_str) else {
+                                    continue;
+                                };
+                                let emoji_len = emoji.chars().count();
+                                if emoji_len == 0 || emoji_len > crate::protocol::messages::MAX_REACTION_LEN {
+                                    continue;
+                                }
+                                let mut engine = crdt_engine.lock().await;
+                                let is_member = engine
+                                    .get_members(community_id)
+                                    .is_ok_and(|members| members.iter().any(|m| m.peer_id == sender));
+                                if !is_member {
+                                    continue;
+                                }
+                                let Ok(Some(reactions)) = engine.set_reaction(community_id, &message_id, &sender, &emoji, active) else {
+                                    continue;
+                                };
+                                drop(engine);
+                                let _ = app_handle.emit("dusk-event", DuskEvent::ReactionUpdated {
+                                    community_id: community_id.to_string(),
+                                    message_id,
+                                    reactions,
+                                });
+                            }
+                            crate::protocol::messages::GossipMessage::MemberKicked { peer_id, capability } => {
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                let Some(community_id) = community_id_from_topic(topic_str) else {
+                                    continue;
+                                };
+                                let mut engine = crdt_engine.lock().await;
+                                let target_is_owner = engine
+                                    .get_members(community_id)
+                                    .map(|members| {
+                                        members.iter().any(|m| {
+                                            m.peer_id == peer_id && m.roles.iter().any(|r| r == "owner")
+                                        })
+                                    })
+                                    .unwrap_or(false);
+                                let allowed = !target_is_owner
+                                    && engine.may_moderate(
+                                        community_id,
+                                        &sender,
+                                        ModerationCapability::KickMembers,
+                                        None,
+                                        capability.as_ref(),
                                     );
-                                    // exponential backoff capped at max
-                                    relay_backoff_secs = (relay_backoff_secs * RELAY_BACKOFF_MULTIPLIER)
-                                        .min(RELAY_MAX_BACKOFF_SECS);
+                                if !allowed {
+                                    // admins without a token still kick through the
+                                    // signed membership log once the doc syncs
+                                    log::debug!("ignoring kick of {} from {} without a capability", peer_id, sender);
+                                    continue;
                                 }
+                                let _ = engine.remove_member(community_id, &peer_id);
+                                drop(engine);
+                                let _ = app_handle.emit("dusk-event", DuskEvent::MemberKicked { peer_id });
                             }
-                        }
-
-                        // --- connection lifecycle ---
-                        libp2p::swarm::SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                            // add to gossipsub mesh for WAN peers (mDNS handles LAN peers)
-                            swarm_instance.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
-                            connected_peers.insert(peer_id.to_string());
+                            crate::protocol::messages::GossipMessage::Presence(update) => {
+                                // a peer only speaks for itself, otherwise anyone could set
+                                // another peer's activity and status in our directory
+                                if message.source.map(|p| p.to_string()).as_deref() != Some(update.peer_id.as_str()) {
+                                    log::debug!("dropped presence for {} sent by another peer", update.peer_id);
+                                    continue;
+                                }
+                                // map PeerStatus to a string the frontend understands
+                                let status_str = match &update.status {
+                                    crate::protocol::messages::PeerStatus::Online => "Online",
+                                    crate::protocol::messages::PeerStatus::Idle => "Idle",
+                                    crate::protocol::messages::PeerStatus::Dnd => "Dnd",
+                                    crate::protocol::messages::PeerStatus::Offline => "Offline",
+                                };
+                                // going offline clears whatever the peer was doing
+                                let activity = match update.status {
+                                    crate::protocol::messages::PeerStatus::Offline => None,
+                                    _ => update.activity.as_deref().and_then(crate::activity::normalize),
+                                };
+                                let _ = storage.set_directory_activity(&update.peer_id, activity.as_deref());
+                                let status_message = crate::protocol::messages::clean_status_text(
+                                    &update.status_message,
+                                    crate::protocol::messages::MAX_STATUS_MESSAGE_LEN,
+                                );
+                                let status_emoji = crate::protocol::messages::clean_status_text(
+                                    &update.status_emoji,
+                                    crate::protocol::messages::MAX_STATUS_EMOJI_LEN,
+                                );
+                                let _ = storage.set_directory_status(&update.peer_id, &status_message, &status_emoji);
+                                let _ = app_handle.emit("dusk-event", DuskEvent::PresenceUpdated {
+                                    peer_id: update.peer_id.clone(),
+                                    status: status_str.to_string(),
+                                    activity,
+                                    status_message,
+                                    status_emoji,
+                                });
 
-                            if Some(peer_id) == relay_peer {
-                                log::info!("relay dial success: connected to relay peer {}", peer_id);
+                                // also update online/offline tracking based on status
+                                match update.status {
+                                    crate::protocol::messages::PeerStatus::Offline => {
+                                        let _ = app_handle.emit("dusk-event", DuskEvent::PeerDisconnected {
+                                            peer_id: update.peer_id,
+                                        });
+                                    }
+                                    _ => {
+                                        let _ = app_handle.emit("dusk-event", DuskEvent::PeerConnected {
+                                            peer_id: update.peer_id,
+                                        });
+                                    }
+                                }
+                            }
+                            crate::protocol::messages::GossipMessage::MetaUpdate(meta) => {
+                                let _ = app_handle.emit("dusk-event", DuskEvent::SyncComplete {
+                                    community_id: meta.id,
+                                });
                             }
+                            crate::protocol::messages::GossipMessage::ProfileAnnounce(profile) => {
+                                // a community's directory topic only carries its own members
+                                if let Some(community_id) = community_id_from_topic(topic_str) {
+                                    let is_member = crdt_engine
+                                        .lock()
+                                        .await
+                                        .get_members(community_id)
+                                        .is_ok_and(|members| members.iter().any(|m| m.peer_id == profile.peer_id));
+                                    if !is_member {
+                                        log::warn!("dropped profile from non-member {} on {}", profile.peer_id, topic_str);
+                                        continue;
+                                    }
+                                }
 
-                            let _ = app_handle.emit("dusk-event", DuskEvent::PeerConnected {
-                                peer_id: peer_id.to_string(),
-                            });
-                            let _ = app_handle.emit("dusk-event", DuskEvent::NodeStatus {
-                                is_connected: true,
-                                peer_count: connected_peers.len(),
-                            });
+                                // unverified identities are handled per the user's policy, peers
+                                // who could not complete the challenge are not always bots
+                                // a proof claiming proof-of-work must carry a valid solution,
+                                // checked by the prevalidation worker
+                                let verified = proof_verified;
+                                if verified {
+                                    unverified_peers.remove(&profile.peer_id);
+                                } else {
+                                    unverified_peers.insert(profile.peer_id.clone());
+                                    let policy = storage
+                                        .load_settings()
+                                        .map(|s| s.verification_policy)
+                                        .unwrap_or_default();
+                                    match policy {
+                                        VerificationPolicy::Require => {
+                                            log::warn!("rejected unverified profile from {}", profile.peer_id);
+                                            continue;
+                                        }
+                                        VerificationPolicy::Warn => {
+                                            log::warn!("accepting unverified profile from {}", profile.peer_id);
+                                        }
+                                        VerificationPolicy::Allow => {}
+                                    }
+                                }
 
-                            // if we just connected to the relay, make a reservation
-                            // so other peers can reach us through it
-                            if Some(peer_id) == relay_peer && !relay_reservation_active {
-                                // reset backoff on successful connection
-                                relay_backoff_secs = RELAY_INITIAL_BACKOFF_SECS;
-                                // cancel any pending retry and deferred warning
-                                relay_retry_at = None;
-                                relay_warn_at = None;
-                                // clear the banner if it was already showing
-                                let _ = app_handle.emit("dusk-event", DuskEvent::RelayStatus { connected: true });
+                                // revoked identities stay dead even if an old announcement is replayed
+                                if storage.is_peer_revoked(&profile.peer_id) {
+                                    log::warn!("rejected announcement from revoked peer {}", profile.peer_id);
+                                    continue;
+                                }
 
-                                if let Some(ref addr) = relay_multiaddr {
-                                    let relay_circuit_addr = addr.clone()
-                                        .with(libp2p::multiaddr::Protocol::P2pCircuit);
+                                // the key must be the one the peer id is derived from, so nobody
+                                // can announce under another peer's id with their own key
+                                if !verification::key_matches_peer(&profile.public_key, &profile.peer_id) {
+                                    log::warn!("rejected announcement with a key not bound to {}", profile.peer_id);
+                                    continue;
+                                }
 
-                                    log::info!(
-                                        "relay reservation request start via listen_on {}",
-                                        relay_circuit_addr
-                                    );
-                                    if let Err(e) = swarm_instance.listen_on(relay_circuit_addr) {
-                                        log::warn!(
-                                            "relay reservation request failed (listen_on error): {}",
-                                            e
-                                        );
+                                // trust on first use, kept as a consistency check on top of the
+                                // binding: the first key seen for a peer id stays pinned
+                                if let Some(pinned) = storage.load_directory_entry(&profile.peer_id).ok().flatten() {
+                                    let key_changed = !pinned.public_key.is_empty() && pinned.public_key != profile.public_key;
+                                    if key_changed && !verification::key_matches_peer(&pinned.public_key, &profile.peer_id) {
+                                        // a pinned key that doesn't derive the peer id was never this
+                                        // peer's, left over from a forged announcement. the bound key
+                                        // replaces it without asking
+                                        log::warn!("replacing pinned key for {} not bound to its peer id", profile.peer_id);
+                                        let _ = storage.replace_peer_key(&profile.peer_id, &profile.public_key);
+                                        let _ = storage.remove_key_conflict(&profile.peer_id);
+                                    } else if key_changed {
+                                        // both keys derive the peer id, the stored entry disagrees
+                                        // with itself. hold the announcement back for the user
+                                        let already_pending = storage
+                                            .load_key_conflict(&profile.peer_id)
+                                            .ok()
+                                            .flatten()
+                                            .is_some_and(|pending| pending.public_key == profile.public_key);
+                                        let received_at = std::time::SystemTime::now()
+                                            .duration_since(std::time::UNIX_EPOCH)
+                                            .unwrap()
+                                            .as_millis() as u64;
+                                        let _ = storage.save_key_conflict(&profile, received_at);
+
+                                        // re-announcements of the same key don't raise the alarm again
+                                        if !already_pending {
+                                            log::warn!("held back announcement with a new key from {}", profile.peer_id);
+                                            if pinned.key_verified {
+                                                let _ = app_handle.emit("dusk-event", DuskEvent::VerifiedKeyChanged {
+                                                    peer_id: profile.peer_id.clone(),
+                                                    display_name: pinned.display_name.clone(),
+                                                });
+                                            }
+                                            let _ = app_handle.emit(
+                                                "dusk-event",
+                                                DuskEvent::KeyConflict(KeyConflict::new(&pinned, &profile, received_at)),
+                                            );
+                                        }
+                                        continue;
+                                    }
+                                }
+
+                                // cache the peer profile in our local directory, the upsert
+                                // only applies if this announcement is newer than the last one seen
+                                let entry = DirectoryEntry::from_announcement(&profile, verified);
+                                match storage.save_announced_directory_entry(&entry, profile.timestamp) {
+                                    Ok(true) => {}
+                                    Ok(false) => {
+                                        log::debug!("ignored stale announcement from {}", profile.peer_id);
+                                        continue;
+                                    }
+                                    Err(e) => {
+                                        log::warn!("failed to save announcement from {}: {}", profile.peer_id, e);
+                                        continue;
                                     }
                                 }
+
+                                // update the member's display name in all community crdts,
+                                // except where they go by a per-community name
+                                {
+                                    let overridden: Vec<String> = storage
+                                        .load_peer_community_profiles(&profile.peer_id)
+                                        .unwrap_or_default()
+                                        .into_iter()
+                                        .map(|p| p.community_id)
+                                        .collect();
+                                    let mut engine = crdt_engine.lock().await;
+                                    engine.update_member_display_name_everywhere(
+                                        &profile.peer_id,
+                                        &profile.display_name,
+                                        &overridden,
+                                    );
+                                }
+
+                                let _ = app_handle.emit("dusk-event", DuskEvent::ProfileReceived {
+                                    peer_id: profile.peer_id,
+                                    display_name: profile.display_name,
+                                    bio: profile.bio,
+                                    public_key: profile.public_key,
+                                    verified,
+                                });
                             }
+                            crate::protocol::messages::GossipMessage::ProfileRevoke(revocation) => {
+                                // peer is revoking their identity, remove them from our directory
+                                // and remember the revocation so the peer id can never come back
+                                let _ = storage.save_revocation(
+                                    &revocation.peer_id,
+                                    &revocation.public_key,
+                                    revocation.timestamp,
+                                );
+                                let _ = storage.remove_directory_entry(&revocation.peer_id);
 
-                            // publish a sync request immediately -- now that the
-                            // relay subscribes to dusk/sync it can forward this
-                            // even before the direct peer mesh forms
-                            let local_peer_id = *swarm_instance.local_peer_id();
-                            let request = crate::crdt::sync::SyncMessage::RequestSync {
-                                peer_id: local_peer_id.to_string(),
-                            };
-                            if let Ok(data) = serde_json::to_vec(&request) {
-                                let sync_topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_sync());
-                                match swarm_instance.behaviour_mut().gossipsub.publish(sync_topic, data) {
-                                    Ok(id) => log::info!("sync: published RequestSync on connect (msg_id={:?})", id),
-                                    Err(e) => log::warn!("sync: RequestSync publish failed on connect: {:?}", e),
+                                let _ = app_handle.emit("dusk-event", DuskEvent::ProfileRevoked {
+                                    peer_id: revocation.peer_id,
+                                });
+                            }
+                            crate::protocol::messages::GossipMessage::NoteChanges {
+                                community_id, note_id, changes,
+                            } => {
+                                // only members edit notes, only admins edit pages restricted to
+                                // them, and only on the community's own topic. sync replies count
+                                // as edits too, so a restricted page catches up through its admins
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                if community_id_from_topic(topic_str) != Some(community_id.as_str()) {
+                                    continue;
+                                }
+                                let merged = {
+                                    let mut engine = crdt_engine.lock().await;
+                                    if !engine.can_edit_note(&community_id, &note_id, &sender) {
+                                        log::debug!("ignoring changes to note {} from {}", note_id, sender);
+                                        continue;
+                                    }
+                                    engine.merge_note_changes(&community_id, &note_id, &changes)
+                                };
+                                let merged = match merged {
+                                    Ok(merged) => merged,
+                                    Err(e) => {
+                                        log::debug!("failed to merge changes to note {}: {}", note_id, e);
+                                        continue;
+                                    }
+                                };
+
+                                // ask the other members for the history these changes build on
+                                if let Some(heads) = merged.missing_heads {
+                                    let request = crate::protocol::messages::GossipMessage::NoteSyncRequest {
+                                        community_id: community_id.clone(),
+                                        note_id: note_id.clone(),
+                                        heads,
+                                    };
+                                    let data = encoder.encode(&request).unwrap_or_default();
+                                    publish_outbound(&mut swarm_instance, &mut message_cache, &mut network_sim, topic_str.to_string(), data);
+                                }
+                                if let Some(note) = merged.note {
+                                    let _ = app_handle.emit("dusk-event", DuskEvent::NoteUpdated(note));
+                                }
+                            }
+                            crate::protocol::messages::GossipMessage::NoteSyncRequest {
+                                community_id, note_id, heads,
+                            } => {
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                if community_id_from_topic(topic_str) != Some(community_id.as_str()) {
+                                    continue;
+                                }
+                                // members on the topic whose changes the asker would accept
+                                let online: Vec<String> = swarm_instance
+                                    .behaviour()
+                                    .gossipsub
+                                    .all_peers()
+                                    .filter(|(_, topics)| topics.contains(&&message.topic))
+                                    .map(|(p, _)| p.to_string())
+                                    .collect();
+                                let changes = {
+                                    let mut engine = crdt_engine.lock().await;
+                                    let is_member = engine
+                                        .get_members(&community_id)
+                                        .is_ok_and(|members| members.iter().any(|m| m.peer_id == sender));
+                                    if !is_member {
+                                        continue;
+                                    }
+                                    // everyone holding the note could answer, one of us does
+                                    let responder = online
+                                        .iter()
+                                        .chain(std::iter::once(&local_peer_str))
+                                        .filter(|p| **p != sender && engine.can_edit_note(&community_id, &note_id, p))
+                                        .min()
+                                        == Some(&local_peer_str);
+                                    if !responder {
+                                        continue;
+                                    }
+                                    engine.note_changes_since(&community_id, &note_id, &heads)
+                                };
+                                if let Some(changes) = changes {
+                                    let reply = crate::protocol::messages::GossipMessage::NoteChanges {
+                                        community_id, note_id, changes,
+                                    };
+                                    let data = encoder.encode(&reply).unwrap_or_default();
+                                    publish_outbound(&mut swarm_instance, &mut message_cache, &mut network_sim, topic_str.to_string(), data);
                                 }
                             }
+                            crate::protocol::messages::GossipMessage::CommunityProfile(mut profile) => {
+                                // overrides only count on the community's own topic and only
+                                // from the peer they belong to
+                                if community_id_from_topic(topic_str) != Some(profile.community_id.as_str()) {
+                                    continue;
+                                }
+                                if message.source.map(|p| p.to_string()).as_deref() != Some(profile.peer_id.as_str()) {
+                                    continue;
+                                }
+                                if profile.peer_id == local_peer_str {
+                                    continue;
+                                }
 
-                            // also schedule a deferred retry in case the mesh
-                            // wasn't ready for the immediate publish
-                            if Some(peer_id) != relay_peer {
-                                deferred_sync_at = Some(
-                                    tokio::time::Instant::now() + std::time::Duration::from_secs(3),
+                                profile.display_name = crate::protocol::messages::clean_status_text(
+                                    &profile.display_name,
+                                    crate::protocol::community::MAX_COMMUNITY_DISPLAY_NAME_LEN,
                                 );
-                            }
+                                profile.avatar_seed = profile
+                                    .avatar_seed
+                                    .map(|seed| {
+                                        crate::protocol::messages::clean_status_text(
+                                            &seed,
+                                            crate::protocol::community::MAX_COMMUNITY_AVATAR_SEED_LEN,
+                                        )
+                                    })
+                                    .filter(|seed| !seed.is_empty());
+
+                                // an empty name withdraws the override, the peer is back to
+                                // their global name in this community
+                                if profile.display_name.is_empty() {
+                                    let _ = storage.delete_community_profile(&profile.community_id, &profile.peer_id);
+                                } else if !storage.save_community_profile(&profile).unwrap_or(false) {
+                                    // stale or repeated announcement
+                                    continue;
+                                }
 
-                            // re-announce our profile so the new peer adds us to
-                            // their directory. skip the relay itself since it does
-                            // not participate in the gossipsub directory mesh.
-                            if Some(peer_id) != relay_peer {
-                                publish_profile(&mut swarm_instance, &node_keypair, &storage);
+                                let _ = app_handle.emit("dusk-event", DuskEvent::CommunityProfileUpdated(profile));
                             }
-                        }
-                        libp2p::swarm::SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
-                            if num_established == 0 {
-                                connected_peers.remove(&peer_id.to_string());
+                            crate::protocol::messages::GossipMessage::VoiceJoin {
+                                community_id, channel_id, peer_id, display_name, media_state,
+                            } => {
+                                let (capacity, admitted_role) = {
+                                    let engine = crdt_engine.lock().await;
+                                    voice_admission(&engine, &community_id, &channel_id, &peer_id)
+                                };
 
-                                // remove disconnected peer from all voice channels and notify frontend
-                                let peer_id_str = peer_id.to_string();
+                                // track the participant in shared voice state
+                                let key = format!("{}:{}", community_id, channel_id);
                                 let mut vc = voice_channels.lock().await;
-                                let mut empty_keys = Vec::new();
-                                for (key, participants) in vc.iter_mut() {
-                                    let before_len = participants.len();
-                                    participants.retain(|p| p.peer_id != peer_id_str);
-                                    if participants.len() < before_len {
-                                        // parse the key back into community_id and channel_id
-                                        if let Some((cid, chid)) = key.split_once(':') {
-                                            let _ = app_handle.emit("dusk-event", DuskEvent::VoiceParticipantLeft {
-                                                community_id: cid.to_string(),
-                                                channel_id: chid.to_string(),
-                                                peer_id: peer_id_str.clone(),
-                                            });
-                                        }
+                                let participants = vc.entry(key).or_insert_with(Vec::new);
+                                let rejoin = participants.iter().any(|p| p.peer_id == peer_id);
+                                if let Some(max) = capacity.filter(|max| !rejoin && participants.len() >= *max as usize) {
+                                    // everyone leaves the joiner out, one of us tells it
+                                    let local_id = swarm_instance.local_peer_id().to_string();
+                                    let responder = participants.iter().map(|p| p.peer_id.as_str()).min() == Some(local_id.as_str());
+                                    drop(vc);
+                                    if responder {
+                                        let rejection = crate::protocol::messages::GossipMessage::VoiceJoinRejected {
+                                            community_id: community_id.clone(),
+                                            channel_id: channel_id.clone(),
+                                            peer_id,
+                                            max_participants: max,
+                                        };
+                                        let payload = encoder.encode(&rejection).unwrap_or_default();
+                                        let topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_voice(&community_id, &channel_id));
+                                        let _ = swarm_instance.behaviour_mut().gossipsub.publish(topic, payload);
                                     }
+                                    continue;
+                                }
+                                // a repeated join keeps the stage role a moderator gave since
+                                let (stage_role, hand_raised) = participants
+                                    .iter()
+                                    .find(|p| p.peer_id == peer_id)
+                                    .map_or((admitted_role, false), |p| (p.stage_role, p.hand_raised));
+                                // avoid duplicates if we receive a repeated join
+                                participants.retain(|p| p.peer_id != peer_id);
+                                participants.push(crate::protocol::messages::VoiceParticipant {
+                                    peer_id: peer_id.clone(),
+                                    display_name: display_name.clone(),
+                                    media_state: media_state.clone(),
+                                    stage_role,
+                                    hand_raised,
+                                });
+                                drop(vc);
+
+                                let _ = app_handle.emit("dusk-event", DuskEvent::VoiceParticipantJoined {
+                                    community_id, channel_id, peer_id, display_name, media_state, stage_role,
+                                });
+                            }
+                            crate::protocol::messages::GossipMessage::VoiceLeave {
+                                community_id, channel_id, peer_id,
+                            } => {
+                                let key = format!("{}:{}", community_id, channel_id);
+                                let mut vc = voice_channels.lock().await;
+                                if let Some(participants) = vc.get_mut(&key) {
+                                    participants.retain(|p| p.peer_id != peer_id);
                                     if participants.is_empty() {
-                                        empty_keys.push(key.clone());
+                                        vc.remove(&key);
                                     }
                                 }
-                                for key in empty_keys {
-                                    vc.remove(&key);
-                                }
                                 drop(vc);
 
-                                let _ = app_handle.emit("dusk-event", DuskEvent::PeerDisconnected {
-                                    peer_id: peer_id.to_string(),
+                                let _ = app_handle.emit("dusk-event", DuskEvent::VoiceParticipantLeft {
+                                    community_id, channel_id, peer_id,
                                 });
-                                let _ = app_handle.emit("dusk-event", DuskEvent::NodeStatus {
-                                    is_connected: !connected_peers.is_empty(),
-                                    peer_count: connected_peers.len(),
+                            }
+                            crate::protocol::messages::GossipMessage::VoiceJoinRejected {
+                                community_id, channel_id, peer_id, max_participants,
+                            } => {
+                                // only a participant of the call may turn us away
+                                let local_id = swarm_instance.local_peer_id().to_string();
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                let key = format!("{}:{}", community_id, channel_id);
+                                let from_participant = voice_channels
+                                    .lock()
+                                    .await
+                                    .get(&key)
+                                    .is_some_and(|participants| participants.iter().any(|p| p.peer_id == sender));
+                                if peer_id != local_id || sender == local_id || !from_participant {
+                                    continue;
+                                }
+                                leave_voice_locally(&mut swarm_instance, &voice_channels, &community_id, &channel_id, false).await;
+                                let _ = app_handle.emit("dusk-event", DuskEvent::VoiceJoinRejected {
+                                    community_id, channel_id, max_participants,
                                 });
+                            }
+                            crate::protocol::messages::GossipMessage::VoiceDisconnect {
+                                community_id, channel_id, peer_id, move_to, capability,
+                            } => {
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                let allowed = {
+                                    let engine = crdt_engine.lock().await;
+                                    engine.may_move_voice(&community_id, &sender, &peer_id, &channel_id, capability.as_ref())
+                                };
+                                if !allowed {
+                                    log::debug!("ignoring voice disconnect of {} from {}", peer_id, sender);
+                                    continue;
+                                }
 
-                                // if we lost the relay connection, mark reservation as inactive
-                                // and schedule a retry with backoff
-                                if Some(peer_id) == relay_peer {
-                                    relay_reservation_active = false;
-                                    log::warn!(
-                                        "relay reservation closed (relay connection dropped), scheduling reconnect in {}s",
-                                        relay_backoff_secs
-                                    );
-                                    // defer the warning so quick reconnections don't flash the banner
-                                    if relay_warn_at.is_none() {
-                                        relay_warn_at = Some(
-                                            tokio::time::Instant::now()
-                                                + std::time::Duration::from_secs(RELAY_WARN_GRACE_SECS),
-                                        );
-                                    }
+                                let local_id = swarm_instance.local_peer_id().to_string();
+                                if peer_id == local_id {
+                                    leave_voice_locally(&mut swarm_instance, &voice_channels, &community_id, &channel_id, true).await;
+                                    let _ = app_handle.emit("dusk-event", DuskEvent::VoiceDisconnected {
+                                        community_id, channel_id, by: sender, move_to,
+                                    });
+                                    continue;
+                                }
 
-                                    relay_retry_at = Some(
-                                        tokio::time::Instant::now() + std::time::Duration::from_secs(relay_backoff_secs),
-                                    );
-                                    relay_backoff_secs = (relay_backoff_secs * RELAY_BACKOFF_MULTIPLIER)
-                                        .min(RELAY_MAX_BACKOFF_SECS);
+                                let key = format!("{}:{}", community_id, channel_id);
+                                let mut vc = voice_channels.lock().await;
+                                if let Some(participants) = vc.get_mut(&key) {
+                                    participants.retain(|p| p.peer_id != peer_id);
+                                    if participants.is_empty() {
+                                        vc.remove(&key);
+                                    }
                                 }
-                            }
-                        }
+                                drop(vc);
 
-                        // gif service response from relay
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::GifService(
-                            libp2p::request_response::Event::Message {
-                                message: libp2p::request_response::Message::Response { request_id, response },
-                                ..
-                            }
-                        )) => {
-                            if let Some(reply) = pending_gif_replies.remove(&request_id) {
-                                let _ = reply.send(Ok(response));
+                                let _ = app_handle.emit("dusk-event", DuskEvent::VoiceParticipantLeft {
+                                    community_id, channel_id, peer_id,
+                                });
                             }
-                        }
-                        // gif service outbound failure
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::GifService(
-                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
-                        )) => {
-                            if let Some(reply) = pending_gif_replies.remove(&request_id) {
-                                let _ = reply.send(Err(format!("gif request failed: {:?}", error)));
+                            crate::protocol::messages::GossipMessage::StageHandRaise {
+                                community_id, channel_id, peer_id, raised,
+                            } => {
+                                // only listeners raise hands, and only their own
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                if sender != peer_id {
+                                    continue;
+                                }
+                                let key = format!("{}:{}", community_id, channel_id);
+                                let mut vc = voice_channels.lock().await;
+                                let Some(p) = vc
+                                    .get_mut(&key)
+                                    .and_then(|participants| participants.iter_mut().find(|p| p.peer_id == peer_id))
+                                    .filter(|p| p.stage_role == crate::protocol::messages::StageRole::Listener)
+                                else {
+                                    continue;
+                                };
+                                p.hand_raised = raised;
+                                drop(vc);
+
+                                let _ = app_handle.emit("dusk-event", DuskEvent::StageHandRaised {
+                                    community_id, channel_id, peer_id, raised,
+                                });
                             }
-                        }
-                        // ignore inbound requests (we only send outbound) and other events
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::GifService(_)) => {}
+                            crate::protocol::messages::GossipMessage::StageRoleChange {
+                                community_id, channel_id, peer_id, role, capability,
+                            } => {
+                                let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                let stepping_down = sender == peer_id && role == crate::protocol::messages::StageRole::Listener;
+                                let allowed = stepping_down || {
+                                    let engine = crdt_engine.lock().await;
+                                    engine.may_move_voice(&community_id, &sender, &peer_id, &channel_id, capability.as_ref())
+                                };
+                                if !allowed {
+                                    log::debug!("ignoring stage role change of {} from {}", peer_id, sender);
+                                    continue;
+                                }
 
-                        // directory service response from relay
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::DirectoryService(
-                            libp2p::request_response::Event::Message {
-                                message: libp2p::request_response::Message::Response { request_id, response },
-                                ..
+                                let key = format!("{}:{}", community_id, channel_id);
+                                let mut vc = voice_channels.lock().await;
+                                let Some(p) = vc
+                                    .get_mut(&key)
+                                    .and_then(|participants| participants.iter_mut().find(|p| p.peer_id == peer_id))
+                                else {
+                                    continue;
+                                };
+                                p.stage_role = role;
+                                p.hand_raised = false;
+                                drop(vc);
+
+                                let _ = app_handle.emit("dusk-event", DuskEvent::StageRoleChanged {
+                                    community_id, channel_id, peer_id, role, by: sender,
+                                });
                             }
-                        )) => {
-                            if let Some(reply) = pending_directory_replies.remove(&request_id) {
-                                match response {
-                                    crate::protocol::directory::DirectoryResponse::Results(entries) => {
-                                        let _ = reply.send(Ok(entries));
+                            crate::protocol::messages::GossipMessage::VoiceParticipantsRequest {
+                                community_id, channel_id,
+                            } => {
+                                // A peer has joined and is requesting the current participants.
+                                // If we are currently in this channel, we should rebroadcast our VoiceJoin.
+                                let key = format!("{}:{}", community_id, channel_id);
+
+                                let vc = voice_channels.lock().await;
+                                if let Some(participants) = vc.get(&key) {
+                                    if let Some(me) = participants.iter().find(|p| p.peer_id == local_peer_str) {
+                                        let join_msg = crate::protocol::messages::GossipMessage::VoiceJoin {
+                                            community_id: community_id.clone(),
+                                            channel_id: channel_id.clone(),
+                                            peer_id: me.peer_id.clone(),
+                                            display_name: me.display_name.clone(),
+                                            media_state: me.media_state.clone(),
+                                        };
+
+                                        let payload = encoder.encode(&join_msg).unwrap_or_default();
+                                        let topic = libp2p::gossipsub::IdentTopic::new(crate::node::gossip::topic_for_voice(&community_id, &channel_id));
+                                        let _ = swarm_instance.behaviour_mut().gossipsub.publish(topic, payload);
                                     }
-                                    crate::protocol::directory::DirectoryResponse::Ok => {
-                                        let _ = reply.send(Ok(vec![]));
+                                }
+                            }
+                            crate::protocol::messages::GossipMessage::VoiceMediaStateUpdate {
+                                community_id, channel_id, peer_id, media_state,
+                            } => {
+                                // update tracked media state for this participant
+                                let key = format!("{}:{}", community_id, channel_id);
+                                let mut vc = voice_channels.lock().await;
+                                if let Some(participants) = vc.get_mut(&key) {
+                                    if let Some(p) = participants.iter_mut().find(|p| p.peer_id == peer_id) {
+                                        p.media_state = media_state.clone();
                                     }
-                                    crate::protocol::directory::DirectoryResponse::Error(msg) => {
-                                        log::warn!("directory service error from relay: {}", msg);
-                                        let _ = reply.send(Ok(vec![]));
+                                }
+                                drop(vc);
+
+                                let _ = app_handle.emit("dusk-event", DuskEvent::VoiceMediaStateChanged {
+                                    community_id, channel_id, peer_id, media_state,
+                                });
+                            }
+                            crate::protocol::messages::GossipMessage::VoiceSpeaking {
+                                community_id, channel_id, peer_id, speaking,
+                            } => {
+                                // only the speaker itself may say it is speaking
+                                if message.source.map(|p| p.to_string()).as_deref() != Some(peer_id.as_str()) {
+                                    continue;
+                                }
+                                let _ = app_handle.emit("dusk-event", DuskEvent::VoiceSpeakingChanged {
+                                    community_id, channel_id, peer_id, speaking,
+                                });
+                            }
+                            crate::protocol::messages::GossipMessage::VoiceSdp {
+                                community_id, channel_id, from_peer, to_peer, sdp_type, sdp,
+                            } => {
+                                // only forward sdp messages addressed to us
+                                if to_peer == local_peer_str {
+                                    // stage listeners only receive, refuse offers and answers
+                                    // that would have us take audio from one
+                                    if (sdp_type == "offer" || sdp_type == "answer")
+                                        && crate::protocol::messages::sdp_sends_audio(&sdp)
+                                    {
+                                        let sender = message.source.map(|p| p.to_string()).unwrap_or_default();
+                                        let key = format!("{}:{}", community_id, channel_id);
+                                        let listener = voice_channels.lock().await.get(&key).is_some_and(|participants| {
+                                            participants.iter().any(|p| {
+                                                (p.peer_id == from_peer || p.peer_id == sender)
+                                                    && p.stage_role == crate::protocol::messages::StageRole::Listener
+                                            })
+                                        });
+                                        if listener {
+                                            log::debug!("dropping audio offer from stage listener {}", from_peer);
+                                            continue;
+                                        }
                                     }
+                                    let _ = app_handle.emit("dusk-event", DuskEvent::VoiceSdpReceived {
+                                        community_id, channel_id, from_peer, sdp_type, sdp,
+                                    });
                                 }
                             }
-                        }
-                        // directory service outbound failure
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::DirectoryService(
-                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
-                        )) => {
-                            log::warn!("directory: outbound failure: {:?}", error);
-                            if let Some(reply) = pending_directory_replies.remove(&request_id) {
-                                let _ = reply.send(Err(format!("directory request failed: {:?}", error)));
+                            crate::protocol::messages::GossipMessage::VoiceIceCandidate {
+                                community_id, channel_id, from_peer, to_peer, candidate, sdp_mid, sdp_mline_index,
+                            } => {
+                                // only forward ice candidates addressed to us
+                                if to_peer == local_peer_str {
+                                    let _ = app_handle.emit("dusk-event", DuskEvent::VoiceIceCandidateReceived {
+                                        community_id, channel_id, from_peer, candidate, sdp_mid, sdp_mline_index,
+                                    });
+                                }
                             }
-                        }
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::DirectoryService(_)) => {}
-
-                        // turn credentials response from relay
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::TurnCredentials(
-                            libp2p::request_response::Event::Message {
-                                message: libp2p::request_response::Message::Response { request_id, response },
-                                ..
+                            crate::protocol::messages::GossipMessage::DirectMessage(dm_msg) => {
+                                ingest_direct_message(
+                                    dm_msg,
+                                    topic_str,
+                                    &mut seen_dm_ids,
+                                    &mut swarm_instance,
+                                    &storage,
+                                    &app_handle,
+                                );
                             }
-                        )) => {
-                            if let Some(reply) = pending_turn_credential_replies.remove(&request_id) {
-                                let _ = reply.send(Ok(response));
+                            crate::protocol::messages::GossipMessage::DMTyping(indicator) => {
+                                if indicator.to_peer == local_peer_str {
+                                    let _ = app_handle.emit("dusk-event", DuskEvent::DMTyping {
+                                        peer_id: indicator.from_peer,
+                                    });
+                                }
                             }
-                        }
-                        // turn credentials outbound failure
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::TurnCredentials(
-                            libp2p::request_response::Event::OutboundFailure { request_id, error, .. }
-                        )) => {
-                            log::warn!("turn credentials: outbound failure: {:?}", error);
-                            if let Some(reply) = pending_turn_credential_replies.remove(&request_id) {
-                                let _ = reply.send(Err(format!("turn credentials request failed: {:?}", error)));
+                            crate::protocol::messages::GossipMessage::DMCall(signal) => {
+                                handle_dm_call_signal(
+                                    signal,
+                                    message.source,
+                                    topic_str,
+                                    &mut call_tracker,
+                                    &mut swarm_instance,
+                                    &storage,
+                                    &app_handle,
+                                );
                             }
-                        }
-                        // ignore inbound requests and other events for turn credentials
-                        libp2p::swarm::SwarmEvent::Behaviour(behaviour::DuskBehaviourEvent::TurnCredentials(_)) => {}
-
-                        other => {
-                            log::info!("unhandled swarm event: {:?}", other);
+                            crate::protocol::messages::GossipMessage::DMEphemeral(signal) => {
+                                handle_dm_ephemeral_signal(
+                                    signal,
+                                    message.source,
+                                    &mut swarm_instance,
+                                    &storage,
+                                    &app_handle,
+                                );
+                            }
+                            crate::protocol::messages::GossipMessage::DMDelete(request) => {
+                                handle_dm_delete_request(request, &mut swarm_instance, &storage, &app_handle);
+                            }
+                            crate::protocol::messages::GossipMessage::DMEdit(edit) => {
+                                handle_dm_edit(edit, message.source, &mut swarm_instance, &storage, &app_handle);
+                            }
+                            crate::protocol::messages::GossipMessage::DMReaction(signal) => {
+                                handle_dm_reaction(signal, message.source, &mut swarm_instance, &storage, &app_handle);
+                            }
+                            crate::protocol::messages::GossipMessage::Cover { .. } => {}
                         }
                     }
                 }
 
                 // periodic rendezvous re-registration/rediscovery (expires on the server)
                 _ = rendezvous_tick.tick() => {
+                    if background_mode {
+                        continue;
+                    }
                     if relay_reservation_active {
                         if let Some(rp) = relay_peer {
                             for ns in register_namespaces.clone() {
@@ -1676,6 +4659,9 @@ pub async fn start(
 
                 // periodic kademlia bootstrap/query as WAN fallback when relay+rendezvous are degraded
                 _ = kad_bootstrap_tick.tick() => {
+                    if background_mode || low_power {
+                        continue;
+                    }
                     for (addr, peer) in &bootstrap_nodes {
                         swarm_instance
                             .behaviour_mut()
@@ -1710,12 +4696,85 @@ pub async fn start(
                     );
                 }
 
+                // a dm call rang for RING_TIMEOUT_SECS without an answer
+                _ = tokio::time::sleep_until(
+                    call_tracker.deadline().unwrap_or_else(|| tokio::time::Instant::now() + std::time::Duration::from_secs(86400))
+                ), if call_tracker.deadline().is_some() => {
+                    expire_ringing_calls(&mut call_tracker, &mut swarm_instance, &storage, &app_handle);
+                }
+
+                _ = dm_expiry_tick.tick() => {
+                    expire_dm_messages(&mut swarm_instance, &storage, &app_handle);
+                }
+
+                // friend presence heartbeat, only while opted in and reachable through the relay
+                _ = presence_heartbeat_tick.tick(), if relay_presence && relay_reservation_active => {
+                    if let Some(rp) = relay_peer {
+                        pending_presence_heartbeat = Some(send_presence_heartbeat(&mut swarm_instance, &rp, &node_keypair, &storage));
+                    }
+                }
+
+                // cover traffic: one padded dummy message on every dm topic
+                _ = tokio::time::sleep_until(next_cover_at), if cover_traffic.is_enabled() => {
+                    publish_cover_traffic(&mut swarm_instance, &storage, &cover_traffic);
+                    next_cover_at = tokio::time::Instant::now() + cover::next_cover_delay();
+                }
+
+                // move dm conversations over to the new epoch's topics
+                _ = tokio::time::sleep_until(next_dm_rotation) => {
+                    rotate_dm_topics(&mut swarm_instance, &storage);
+                    next_dm_rotation = tokio::time::Instant::now() + gossip::until_next_dm_epoch();
+                }
+
+                // our own speaking period ran out without louder frames
+                _ = tokio::time::sleep_until(
+                    speaking_state.deadline().unwrap_or_else(|| tokio::time::Instant::now() + std::time::Duration::from_secs(86400))
+                ), if speaking_state.deadline().is_some() => {
+                    if let Some(change) = speaking_state.expire() {
+                        publish_speaking(&mut swarm_instance, &app_handle, change);
+                    }
+                }
+
+                // a scheduled profile announcement is due
+                _ = tokio::time::sleep_until(
+                    announce_scheduler.deadline().unwrap_or_else(|| tokio::time::Instant::now() + std::time::Duration::from_secs(86400))
+                ), if announce_scheduler.deadline().is_some() => {
+                    if announce_scheduler.take(&announce_policy(&storage)) {
+                        publish_profile(&mut swarm_instance, &node_keypair, &storage, &crdt_engine).await;
+                    }
+                }
+
+                // simulated latency ran out for held gossip, dev builds only
+                _ = tokio::time::sleep_until(
+                    network_sim.next_release().unwrap_or_else(|| tokio::time::Instant::now() + std::time::Duration::from_secs(86400))
+                ), if network_sim.next_release().is_some() => {
+                    let released = network_sim.take_released();
+                    for (topic, data) in released.outbound {
+                        let ident_topic = libp2p::gossipsub::IdentTopic::new(topic.clone());
+                        if let Err(e) = swarm_instance.behaviour_mut().gossipsub.publish(ident_topic, data) {
+                            log::warn!("gossipsub publish failed on '{}': {:?}", topic, e);
+                        }
+                    }
+                    for received in released.inbound {
+                        receive_gossip(
+                            &mut swarm_instance,
+                            &mut peer_scores,
+                            &mut message_cache,
+                            &mut prevalidator,
+                            &event_recorder,
+                            relay_peer,
+                            received,
+                        );
+                    }
+                }
+
                 // relay reconnection with exponential backoff
                 _ = tokio::time::sleep_until(
                     relay_retry_at.unwrap_or_else(|| tokio::time::Instant::now() + std::time::Duration::from_secs(86400))
                 ), if relay_retry_at.is_some() => {
                     relay_retry_at = None;
-                    if !relay_reservation_active {
+                    // no point dialing while offline, the watchdog re-dials when the network returns
+                    if !relay_reservation_active && internet_reachable {
                         if let Some(ref addr) = relay_multiaddr {
                             log::info!("relay dial start (reconnect): {}", addr);
                             if let Err(e) = swarm_instance.dial(addr.clone()) {
@@ -1765,80 +4824,338 @@ pub async fn start(
                         peers_in_mesh
                     );
 
-                    // publish sync request
-                    let local_peer_id = *swarm_instance.local_peer_id();
-                    let request = crate::crdt::sync::SyncMessage::RequestSync {
-                        peer_id: local_peer_id.to_string(),
-                    };
-                    if let Ok(data) = serde_json::to_vec(&request) {
-                        let sync_topic = libp2p::gossipsub::IdentTopic::new(gossip::topic_for_sync());
-                        match swarm_instance.behaviour_mut().gossipsub.publish(sync_topic, data) {
-                            Ok(msg_id) => log::info!("deferred sync: RequestSync published (msg_id={:?})", msg_id),
-                            Err(e) => log::warn!("deferred sync: RequestSync publish failed: {:?}", e),
+                    // publish sync requests
+                    let requests = crdt_engine.lock().await.sync_requests();
+                    let published = publish_sync_requests(&mut swarm_instance, &requests);
+                    log::info!("deferred sync: RequestSync published for {}/{} communities", published, requests.len());
+
+                    // re-broadcast presence so the new peer knows we're online
+                    let presence_status = settings_presence_status(&storage);
+                    publish_presence(&mut swarm_instance, &storage, &crdt_engine, presence_status, local_activity.clone()).await;
+                }
+
+                // resume after sleep: drop connections that died while suspended and
+                // rebuild relay, rendezvous and sync state instead of waiting for timeouts
+                _ = wake_check_tick.tick() => {
+                    let now_wall = std::time::SystemTime::now();
+                    let gap = now_wall
+                        .duration_since(last_wake_check)
+                        .unwrap_or_default();
+                    last_wake_check = now_wall;
+
+                    if gap > std::time::Duration::from_secs(WAKE_CHECK_TICK_SECS + WAKE_GAP_THRESHOLD_SECS) {
+                        log::info!("resume: wall clock jumped {}s, assuming wake from sleep", gap.as_secs());
+
+                        let stale: Vec<libp2p::PeerId> = swarm_instance.connected_peers().cloned().collect();
+                        for peer in stale {
+                            let _ = swarm_instance.disconnect_peer_id(peer);
+                        }
+                        relay_reservation_active = false;
+                        event_recorder.state("resumed", &format!("{}s", gap.as_secs()));
+
+                        // the monotonic clock may not have counted the sleep, so the
+                        // dm topic epoch could have changed without the rotation firing
+                        rotate_dm_topics(&mut swarm_instance, &storage);
+                        next_dm_rotation = tokio::time::Instant::now() + gossip::until_next_dm_epoch();
+
+                        // replay every registration and discovery once the reservation is back
+                        for ns in &register_namespaces {
+                            if !pending_registrations.contains(ns) {
+                                pending_registrations.push(ns.clone());
+                            }
+                        }
+                        for ns in &discover_namespaces {
+                            if !pending_discoveries.contains(ns) {
+                                pending_discoveries.push(ns.clone());
+                            }
                         }
+                        if pending_queued_at.is_none() {
+                            pending_queued_at = Some(std::time::Instant::now());
+                        }
+
+                        // re-dial the relay immediately rather than after the backoff
+                        relay_backoff_secs = RELAY_INITIAL_BACKOFF_SECS;
+                        if relay_multiaddr.is_some() {
+                            relay_retry_at = Some(tokio::time::Instant::now());
+                        }
+
+                        // the network may have changed while asleep, probe right away
+                        watchdog_tick.reset_immediately();
+
+                        // request a sync once the mesh has had a moment to re-form
+                        deferred_sync_at = Some(
+                            tokio::time::Instant::now() + std::time::Duration::from_secs(5),
+                        );
+
+                        let _ = app_handle.emit("dusk-event", DuskEvent::NodeResumed {
+                            slept_secs: gap.as_secs(),
+                        });
                     }
+                }
 
-                    // re-broadcast presence so the new peer knows we're online
-                    let local_id = local_peer_id.to_string();
-                    let presence_status = storage
-                        .load_settings()
-                        .map(|s| match s.status.as_str() {
-                            "idle" => crate::protocol::messages::PeerStatus::Idle,
-                            "dnd" => crate::protocol::messages::PeerStatus::Dnd,
-                            "invisible" => crate::protocol::messages::PeerStatus::Offline,
-                            _ => crate::protocol::messages::PeerStatus::Online,
-                        })
-                        .unwrap_or(crate::protocol::messages::PeerStatus::Online);
-                    let display_name = storage
-                        .load_profile()
-                        .map(|p| p.display_name)
-                        .unwrap_or_else(|_| "unknown".to_string());
-                    let now = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis() as u64;
-                    let update = crate::protocol::messages::PresenceUpdate {
-                        peer_id: local_id,
-                        display_name,
-                        status: presence_status,
-                        timestamp: now,
-                    };
-                    let msg = crate::protocol::messages::GossipMessage::Presence(update);
-                    if let Ok(data) = serde_json::to_vec(&msg) {
-                        let engine = crdt_engine.lock().await;
-                        let community_ids = engine.community_ids();
-                        drop(engine);
-                        for cid in community_ids {
-                            let topic_str = gossip::topic_for_presence(&cid);
-                            let ident_topic = libp2p::gossipsub::IdentTopic::new(topic_str);
-                            let _ = swarm_instance.behaviour_mut().gossipsub.publish(ident_topic, data.clone());
+                // sign the heads of communities we own so joiners can verify snapshots
+                _ = checkpoint_tick.tick() => {
+                    let mut engine = crdt_engine.lock().await;
+                    for community_id in engine.create_checkpoints(&node_keypair) {
+                        log::info!("sync: checkpointed community {}", community_id);
+                        push_snapshot(&mut swarm_instance, &mut pending_snapshots, &mut engine, &community_id);
+                    }
+                }
+
+                // re-read os power signals, only the auto profile reacts to them
+                _ = power_tick.tick() => {
+                    if network_profile != power::NetworkProfile::Auto {
+                        continue;
+                    }
+                    let signals = power::detect_signals().await;
+                    if signals == power_signals {
+                        continue;
+                    }
+                    power_signals = signals;
+                    let was_low_power = low_power;
+                    low_power = power::is_low_power(network_profile, power_signals);
+                    if low_power != was_low_power {
+                        log::info!(
+                            "power: low power networking {} (battery {}, metered {})",
+                            if low_power { "enabled" } else { "disabled" },
+                            signals.on_battery,
+                            signals.metered
+                        );
+                        if !low_power {
+                            if let Some(status) = pending_presence.take() {
+                                publish_presence(&mut swarm_instance, &storage, &crdt_engine, status, local_activity.clone()).await;
+                            }
+                        }
+                    }
+                    let _ = app_handle.emit(
+                        "dusk-event",
+                        DuskEvent::NetworkProfileChanged(power::NetworkProfileStatus::new(
+                            network_profile,
+                            power_signals,
+                        )),
+                    );
+                }
+
+                // flush the coalesced presence update while in low power
+                _ = presence_batch_tick.tick() => {
+                    if let Some(status) = pending_presence.take() {
+                        publish_presence(&mut swarm_instance, &storage, &crdt_engine, status, local_activity.clone()).await;
+                    }
+                }
+
+                // let application penalties fade so peers can recover from old mistakes
+                _ = score_decay_tick.tick() => {
+                    for (peer, score) in peer_scores.decay() {
+                        swarm_instance
+                            .behaviour_mut()
+                            .gossipsub
+                            .set_application_score(&peer, score);
+                    }
+                }
+
+                // periodic connectivity probe
+                _ = watchdog_tick.tick() => {
+                    if background_mode {
+                        continue;
+                    }
+                    if !probe_in_flight {
+                        probe_in_flight = true;
+                        let tx = probe_tx.clone();
+                        let target = relay_multiaddr.as_ref().and_then(watchdog::probe_target);
+                        let relay_connected = relay_peer.is_some_and(|rp| connected_peers.contains(&rp.to_string()));
+                        tauri::async_runtime::spawn(async move {
+                            // a relay without a tcp address can't be probed, an open
+                            // connection to it is all we go by
+                            let reachable = match target {
+                                Some(target) => watchdog::probe_relay(&target).await,
+                                None => relay_connected,
+                            };
+                            let _ = tx.send(reachable).await;
+                        });
+                    }
+                }
+
+                // connectivity probe finished, switch modes on network changes
+                Some(reachable) = probe_rx.recv() => {
+                    probe_in_flight = false;
+                    let was_reachable = internet_reachable;
+                    internet_reachable = reachable;
+
+                    let relay_connected = relay_peer
+                        .map(|rp| connected_peers.contains(&rp.to_string()))
+                        .unwrap_or(false);
+
+                    // network came back (wake from sleep, wi-fi switch), re-dial the
+                    // relay right away instead of waiting out the backoff
+                    if reachable && !relay_connected && relay_multiaddr.is_some() {
+                        if !was_reachable || relay_retry_at.is_none() {
+                            log::info!("watchdog: network reachable without relay, re-dialing now");
+                            relay_backoff_secs = RELAY_INITIAL_BACKOFF_SECS;
+                            relay_retry_at = Some(tokio::time::Instant::now());
                         }
                     }
+
+                    let mode = if reachable && relay_multiaddr.is_some() {
+                        watchdog::NetworkMode::Full
+                    } else {
+                        watchdog::NetworkMode::LanOnly
+                    };
+
+                    if mode != network_mode {
+                        log::info!(
+                            "watchdog: network mode {} -> {}",
+                            network_mode.as_str(),
+                            mode.as_str()
+                        );
+                        network_mode = mode;
+                        event_recorder.state("network_mode", mode.as_str());
+                        let _ = app_handle.emit("dusk-event", DuskEvent::NetworkChanged {
+                            mode: mode.as_str().to_string(),
+                            internet_reachable: reachable,
+                            relay_connected,
+                        });
+                    }
                 }
 
                 cmd = command_rx.recv() => {
                     match cmd {
-                        Some(NodeCommand::Shutdown) | None => break,
+                        None => break,
+                        Some(NodeCommand::Shutdown) => {
+                            event_recorder.state("node_stopped", "");
+                            // a status held back by low power still goes out
+                            if let Some(status) = pending_presence.take() {
+                                publish_presence(&mut swarm_instance, &storage, &crdt_engine, status, local_activity.clone()).await;
+                            }
+                            // keep driving the swarm briefly so publishes queued
+                            // just before shutdown reach the wire
+                            let drain = tokio::time::sleep(std::time::Duration::from_millis(SHUTDOWN_DRAIN_MS));
+                            tokio::pin!(drain);
+                            loop {
+                                tokio::select! {
+                                    _ = &mut drain => break,
+                                    _ = swarm_instance.select_next_some() => {}
+                                }
+                            }
+                            break;
+                        }
                         Some(NodeCommand::SendMessage { topic, data }) => {
-                            let ident_topic = libp2p::gossipsub::IdentTopic::new(topic.clone());
-                            match swarm_instance.behaviour_mut().gossipsub.publish(ident_topic, data) {
-                                Ok(msg_id) => log::debug!("gossipsub publish ok on '{}' (msg_id={:?})", topic, msg_id),
-                                Err(e) => log::warn!("gossipsub publish failed on '{}': {:?}", topic, e),
+                            // gossipsub never hands our own publish back, so announcements
+                            // we post are mirrored to followers here instead of on receipt
+                            let own_chat = match gossip::channel_of_topic(&topic) {
+                                Some(_) => match serde_json::from_slice(&data) {
+                                    Ok(crate::protocol::messages::GossipMessage::Chat(chat_msg))
+                                        if chat_msg.author_id == local_peer_str =>
+                                    {
+                                        Some(chat_msg)
+                                    }
+                                    _ => None,
+                                },
+                                None => None,
+                            };
+                            publish_outbound(&mut swarm_instance, &mut message_cache, &mut network_sim, topic.clone(), data);
+                            if let Some(chat_msg) = own_chat {
+                                let mirrored = mirror_followed_announcement(
+                                    &chat_msg,
+                                    &topic,
+                                    &local_peer_str,
+                                    &storage,
+                                    &hlc_clock,
+                                    &crdt_engine,
+                                    &app_handle,
+                                )
+                                .await;
+                                publish_mirrored(&mut swarm_instance, &mut message_cache, mirrored);
+                            }
+                        }
+                        Some(NodeCommand::PushSnapshot { community_id }) => {
+                            let mut engine = crdt_engine.lock().await;
+                            push_snapshot(&mut swarm_instance, &mut pending_snapshots, &mut engine, &community_id);
+                        }
+                        Some(NodeCommand::SendDirectMessage { peer_id, data }) => {
+                            // friends and known conversations join the pair topic at
+                            // startup, the inbox copy is only for peers who haven't yet
+                            let local_id = swarm_instance.local_peer_id().to_string();
+                            let pair_topic = gossip::topic_for_dm(&local_id, &peer_id);
+                            if !peer_on_topic(&swarm_instance, &peer_id, &pair_topic) {
+                                let inbox_topic = gossip::topic_for_dm_inbox(&peer_id);
+                                publish_outbound(&mut swarm_instance, &mut message_cache, &mut network_sim, inbox_topic, data.clone());
                             }
+                            publish_outbound(&mut swarm_instance, &mut message_cache, &mut network_sim, pair_topic, data);
                         }
                         Some(NodeCommand::Subscribe { topic }) => {
-                            let ident_topic = libp2p::gossipsub::IdentTopic::new(topic);
-                            let _ = swarm_instance.behaviour_mut().gossipsub.subscribe(&ident_topic);
+                            // in interest mode a channel the user hasn't opened lately
+                            // is swapped for its community's mentions topic, opening
+                            // the channel subscribes it and catches up from there
+                            let topic = match gossip::channel_of_topic(&topic) {
+                                Some((community_id, channel_id))
+                                    if interest_subscriptions
+                                        && !channel_of_interest(&storage, community_id, channel_id) =>
+                                {
+                                    gossip::topic_for_mentions(community_id)
+                                }
+                                _ => topic,
+                            };
+                            let ident_topic = libp2p::gossipsub::IdentTopic::new(topic.clone());
+                            let newly_subscribed = matches!(
+                                swarm_instance.behaviour_mut().gossipsub.subscribe(&ident_topic),
+                                Ok(true)
+                            );
+                            if newly_subscribed {
+                                event_recorder.state("subscribed", &topic);
+                            }
+
+                            // ask members already on the topic for anything we missed,
+                            // otherwise wait for the first one to show up
+                            if newly_subscribed && cache::MessageCache::is_cacheable(&topic) {
+                                let topic_hash = ident_topic.hash();
+                                let peers: Vec<libp2p::PeerId> = swarm_instance
+                                    .behaviour()
+                                    .gossipsub
+                                    .all_peers()
+                                    .filter(|(p, topics)| Some(**p) != relay_peer && topics.contains(&&topic_hash))
+                                    .map(|(p, _)| *p)
+                                    .take(CATCHUP_MAX_PEERS)
+                                    .collect();
+
+                                if peers.is_empty() {
+                                    pending_catchup_topics.insert(topic);
+                                } else {
+                                    let since = {
+                                        let engine = crdt_engine.lock().await;
+                                        catchup_cursor(&engine, &storage, &swarm_instance.local_peer_id().to_string(), &topic)
+                                    };
+                                    for peer in peers {
+                                        let request_id = swarm_instance.behaviour_mut().catchup.send_request(
+                                            &peer,
+                                            crate::protocol::catchup::CatchupRequest {
+                                                topic: topic.clone(),
+                                                since,
+                                            },
+                                        );
+                                        pending_catchup_requests.insert(request_id, topic.clone());
+                                    }
+                                }
+                            }
                         }
                         Some(NodeCommand::Unsubscribe { topic }) => {
+                            pending_catchup_topics.remove(&topic);
+                            event_recorder.state("unsubscribed", &topic);
                             let ident_topic = libp2p::gossipsub::IdentTopic::new(topic);
                             let _ = swarm_instance.behaviour_mut().gossipsub.unsubscribe(&ident_topic);
                         }
                         Some(NodeCommand::GetListenAddrs { reply }) => {
-                            let addrs: Vec<String> = swarm_instance
-                                .listeners()
+                            // confirmed external addresses go first, they are the
+                            // ones reachable from outside the local network
+                            let mut addrs: Vec<String> = swarm_instance
+                                .external_addresses()
                                 .map(|a| a.to_string())
                                 .collect();
+                            for addr in swarm_instance.listeners() {
+                                let addr = addr.to_string();
+                                if !addrs.contains(&addr) {
+                                    addrs.push(addr);
+                                }
+                            }
                             let _ = reply.send(addrs);
                         }
                         Some(NodeCommand::Dial { addr }) => {
@@ -1849,37 +5166,35 @@ pub async fn start(
                                 log::info!("manual dial initiated: {}", addr);
                             }
                         }
+                        Some(NodeCommand::SetActivity { activity }) => {
+                            if activity == local_activity {
+                                continue;
+                            }
+                            local_activity = activity;
+                            let _ = app_handle.emit("dusk-event", DuskEvent::LocalActivityChanged {
+                                activity: local_activity.clone(),
+                            });
+
+                            let status = settings_presence_status(&storage);
+                            if low_power {
+                                pending_presence = Some(status);
+                                continue;
+                            }
+                            publish_presence(&mut swarm_instance, &storage, &crdt_engine, status, local_activity.clone()).await;
+                        }
                         Some(NodeCommand::BroadcastPresence { status }) => {
-                            // publish presence update on every subscribed community presence topic
-                            let local_id = swarm_instance.local_peer_id().to_string();
-                            let display_name = storage
-                                .load_profile()
-                                .map(|p| p.display_name)
-                                .unwrap_or_else(|_| "unknown".to_string());
-                            let now = std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap()
-                                .as_millis() as u64;
-                            let update = crate::protocol::messages::PresenceUpdate {
-                                peer_id: local_id,
-                                display_name,
-                                status,
-                                timestamp: now,
-                            };
-                            let msg = crate::protocol::messages::GossipMessage::Presence(update);
-                            if let Ok(data) = serde_json::to_vec(&msg) {
-                                // broadcast to every community presence topic we're subscribed to
-                                let engine = crdt_engine.lock().await;
-                                let community_ids = engine.community_ids();
-                                drop(engine);
-                                for cid in community_ids {
-                                    let topic_str = gossip::topic_for_presence(&cid);
-                                    let ident_topic = libp2p::gossipsub::IdentTopic::new(topic_str);
-                                    let _ = swarm_instance.behaviour_mut().gossipsub.publish(ident_topic, data.clone());
-                                }
+                            // in low power only the latest status goes out on the next batch
+                            if low_power {
+                                pending_presence = Some(status);
+                                continue;
                             }
+                            publish_presence(&mut swarm_instance, &storage, &crdt_engine, status, local_activity.clone()).await;
                         }
                         Some(NodeCommand::RegisterRendezvous { namespace }) => {
+                            if namespace == GLOBAL_PEERS_NAMESPACE && !relay_discoverable {
+                                log::info!("rendezvous: skipped global register -- relay_discoverable is false");
+                                continue;
+                            }
                             register_namespaces.insert(namespace.clone());
                             if relay_reservation_active {
                                 if let Some(rp) = relay_peer {
@@ -1968,7 +5283,7 @@ pub async fn start(
                             }
                         }
                         Some(NodeCommand::DirectoryRegister) => {
-                            if relay_reservation_active {
+                            if relay_reservation_active && relay_discoverable {
                                 if let (Some(rp), Some(ref addr)) = (relay_peer, &relay_multiaddr) {
                                     let local_id = *swarm_instance.local_peer_id();
                                     register_directory(&mut swarm_instance, &rp, &storage, addr, local_id);
@@ -1976,15 +5291,16 @@ pub async fn start(
                                 }
                             }
                         }
-                        Some(NodeCommand::DirectoryRemove) => {
-                            if relay_reservation_active {
-                                if let Some(rp) = relay_peer {
-                                    swarm_instance.behaviour_mut().directory_service.send_request(
-                                        &rp,
-                                        crate::protocol::directory::DirectoryRequest::Remove,
-                                    );
-                                    log::info!("directory: sent Remove (command)");
-                                }
+                        Some(NodeCommand::DirectoryRemove { reply }) => {
+                            if let (true, Some(rp)) = (relay_reservation_active, relay_peer) {
+                                let request_id = swarm_instance.behaviour_mut().directory_service.send_request(
+                                    &rp,
+                                    crate::protocol::directory::DirectoryRequest::Remove,
+                                );
+                                pending_directory_replies.insert(request_id, reply);
+                                log::info!("directory: sent Remove (command)");
+                            } else {
+                                let _ = reply.send(Err("relay not connected".to_string()));
                             }
                         }
                         Some(NodeCommand::DirectorySearch { query, reply }) => {
@@ -2016,6 +5332,30 @@ pub async fn start(
                         }
                         Some(NodeCommand::SetRelayDiscoverable { enabled }) => {
                             relay_discoverable = enabled;
+                            let global_ns = GLOBAL_PEERS_NAMESPACE.to_string();
+                            if enabled {
+                                register_namespaces.insert(global_ns.clone());
+                                if !relay_reservation_active {
+                                    if pending_queued_at.is_none() {
+                                        pending_queued_at = Some(std::time::Instant::now());
+                                    }
+                                    queue_namespace_unique(&mut pending_registrations, global_ns.clone());
+                                }
+                            } else {
+                                register_namespaces.remove(&global_ns);
+                                pending_registrations.retain(|ns| ns != &global_ns);
+                            }
+                            if let (true, Some(rp)) = (relay_reservation_active, relay_peer) {
+                                if let Ok(ns) = libp2p::rendezvous::Namespace::new(global_ns) {
+                                    if enabled {
+                                        if let Err(e) = swarm_instance.behaviour_mut().rendezvous.register(ns, rp, None) {
+                                            log::warn!("failed to register on rendezvous: {:?}", e);
+                                        }
+                                    } else {
+                                        swarm_instance.behaviour_mut().rendezvous.unregister(ns, rp);
+                                    }
+                                }
+                            }
                             if relay_reservation_active {
                                 if enabled {
                                     if let (Some(rp), Some(ref addr)) = (relay_peer, &relay_multiaddr) {
@@ -2034,6 +5374,227 @@ pub async fn start(
                                 }
                             }
                         }
+                        Some(NodeCommand::AnnounceProfile { trigger }) => {
+                            announce_scheduler.request(trigger, &announce_policy(&storage));
+                        }
+                        Some(NodeCommand::SetRelayPresence { enabled }) => {
+                            relay_presence = enabled;
+                            if let (true, Some(rp)) = (relay_reservation_active, relay_peer) {
+                                if enabled {
+                                    pending_presence_heartbeat = Some(send_presence_heartbeat(&mut swarm_instance, &rp, &node_keypair, &storage));
+                                } else {
+                                    swarm_instance.behaviour_mut().presence_service.send_request(
+                                        &rp,
+                                        crate::protocol::presence::PresenceRequest::Leave,
+                                    );
+                                }
+                            }
+                            if !enabled {
+                                // without heartbeats the relay's view goes stale
+                                pending_presence_heartbeat = None;
+                                apply_friend_presence(HashSet::new(), &mut relay_online_friends, &app_handle);
+                            }
+                        }
+                        Some(NodeCommand::FetchHistory { community_id, channel_id, before, limit, reply }) => {
+                            // ask any connected member of the community, the relay never holds docs
+                            let members: HashSet<String> = {
+                                let engine = crdt_engine.lock().await;
+                                engine
+                                    .get_members(&community_id)
+                                    .map(|m| m.into_iter().map(|m| m.peer_id).collect())
+                                    .unwrap_or_default()
+                            };
+                            let target = swarm_instance
+                                .connected_peers()
+                                .find(|p| Some(**p) != relay_peer && members.contains(&p.to_string()))
+                                .cloned();
+
+                            match target {
+                                Some(peer) => {
+                                    let request_id = swarm_instance.behaviour_mut().history.send_request(
+                                        &peer,
+                                        crate::protocol::history::HistoryRequest {
+                                            community_id,
+                                            channel_id,
+                                            before,
+                                            limit,
+                                        },
+                                    );
+                                    pending_history_replies.insert(request_id, reply);
+                                }
+                                None => {
+                                    let _ = reply.send(Err("no online members to fetch history from".to_string()));
+                                }
+                            }
+                        }
+                        Some(NodeCommand::FetchVoiceRoster { community_id, channel_id, reply }) => {
+                            // only participants subscribe to the voice topic, so any member
+                            // gossipsub has seen on it can answer
+                            let members: HashSet<String> = {
+                                let engine = crdt_engine.lock().await;
+                                engine
+                                    .get_members(&community_id)
+                                    .map(|m| m.into_iter().map(|m| m.peer_id).collect())
+                                    .unwrap_or_default()
+                            };
+                            let voice_topic = gossip::topic_for_voice(&community_id, &channel_id);
+                            let target = swarm_instance
+                                .connected_peers()
+                                .filter(|p| Some(**p) != relay_peer && members.contains(&p.to_string()))
+                                .find(|p| peer_on_topic(&swarm_instance, &p.to_string(), &voice_topic))
+                                .cloned();
+                            let key = format!("{}:{}", community_id, channel_id);
+
+                            match target {
+                                Some(peer) => {
+                                    let request_id = swarm_instance.behaviour_mut().voice_roster.send_request(
+                                        &peer,
+                                        crate::protocol::voice::VoiceRosterRequest { community_id, channel_id },
+                                    );
+                                    pending_voice_roster_replies.insert(request_id, (key, reply));
+                                }
+                                // nobody we can reach is in the call
+                                None => {
+                                    let participants = voice_channels.lock().await.get(&key).cloned().unwrap_or_default();
+                                    let _ = reply.send(Ok(participants));
+                                }
+                            }
+                        }
+                        Some(NodeCommand::SetNetworkProfile { profile }) => {
+                            network_profile = profile;
+                            if profile == power::NetworkProfile::Auto {
+                                power_signals = power::detect_signals().await;
+                            }
+                            low_power = power::is_low_power(network_profile, power_signals);
+                            log::info!(
+                                "power: network profile set to {} (low power {})",
+                                profile.as_str(),
+                                low_power
+                            );
+                            if !low_power {
+                                if let Some(status) = pending_presence.take() {
+                                    publish_presence(&mut swarm_instance, &storage, &crdt_engine, status, local_activity.clone()).await;
+                                }
+                            }
+                            let _ = app_handle.emit(
+                                "dusk-event",
+                                DuskEvent::NetworkProfileChanged(power::NetworkProfileStatus::new(
+                                    network_profile,
+                                    power_signals,
+                                )),
+                            );
+                        }
+                        Some(NodeCommand::GetNetworkProfile { reply }) => {
+                            let _ = reply.send(power::NetworkProfileStatus::new(network_profile, power_signals));
+                        }
+                        Some(NodeCommand::ReportVoiceActivity { community_id, channel_id, level }) => {
+                            for change in speaking_state.report(&community_id, &channel_id, level) {
+                                publish_speaking(&mut swarm_instance, &app_handle, change);
+                            }
+                        }
+                        Some(NodeCommand::SendCallSignal { signal }) => {
+                            use crate::protocol::messages::{CallDirection, DMCallSignalKind};
+                            match signal.kind {
+                                DMCallSignalKind::Invite => call_tracker.ring(&signal.call_id, CallDirection::Outgoing),
+                                DMCallSignalKind::Accept => call_tracker.answer(&signal.call_id),
+                                _ => call_tracker.end(&signal.call_id),
+                            }
+                            publish_call_signal(&mut swarm_instance, &signal);
+                        }
+                        Some(NodeCommand::SetBackgroundMode { enabled }) => {
+                            if enabled == background_mode {
+                                continue;
+                            }
+                            background_mode = enabled;
+
+                            if enabled {
+                                // drop everything but the relay, the relay holds our
+                                // reservation so peers can still reach us on resume
+                                let peers: Vec<libp2p::PeerId> = swarm_instance
+                                    .connected_peers()
+                                    .filter(|p| Some(**p) != relay_peer)
+                                    .cloned()
+                                    .collect();
+                                log::info!("background: suspending, closing {} peer connections", peers.len());
+                                for peer in peers {
+                                    let _ = swarm_instance.disconnect_peer_id(peer);
+                                }
+                                continue;
+                            }
+
+                            log::info!("background: resuming foreground sync");
+                            let relay_connected = relay_peer
+                                .map(|rp| connected_peers.contains(&rp.to_string()))
+                                .unwrap_or(false);
+                            if !relay_connected && relay_multiaddr.is_some() {
+                                relay_backoff_secs = RELAY_INITIAL_BACKOFF_SECS;
+                                relay_retry_at = Some(tokio::time::Instant::now());
+                            }
+                            watchdog_tick.reset_immediately();
+                            deferred_sync_at = Some(
+                                tokio::time::Instant::now() + std::time::Duration::from_secs(2),
+                            );
+
+                            // catch up dms and channels from whoever is still on each topic,
+                            // topics without peers wait for the first subscriber as usual
+                            let topics: Vec<libp2p::gossipsub::TopicHash> = swarm_instance
+                                .behaviour()
+                                .gossipsub
+                                .topics()
+                                .filter(|t| cache::MessageCache::is_cacheable(t.as_str()))
+                                .cloned()
+                                .collect();
+                            for topic_hash in topics {
+                                let topic = topic_hash.to_string();
+                                let peers: Vec<libp2p::PeerId> = swarm_instance
+                                    .behaviour()
+                                    .gossipsub
+                                    .all_peers()
+                                    .filter(|(p, topics)| Some(**p) != relay_peer && topics.contains(&&topic_hash))
+                                    .map(|(p, _)| *p)
+                                    .take(CATCHUP_MAX_PEERS)
+                                    .collect();
+
+                                if peers.is_empty() {
+                                    pending_catchup_topics.insert(topic);
+                                    continue;
+                                }
+                                let since = {
+                                    let engine = crdt_engine.lock().await;
+                                    catchup_cursor(&engine, &storage, &swarm_instance.local_peer_id().to_string(), &topic)
+                                };
+                                for peer in peers {
+                                    let request_id = swarm_instance.behaviour_mut().catchup.send_request(
+                                        &peer,
+                                        crate::protocol::catchup::CatchupRequest {
+                                            topic: topic.clone(),
+                                            since,
+                                        },
+                                    );
+                                    pending_catchup_requests.insert(request_id, topic.clone());
+                                }
+                            }
+                        }
+                        #[cfg(feature = "dev-server")]
+                        Some(NodeCommand::SetNetem { config }) => {
+                            network_sim.configure(config);
+                        }
+                        Some(NodeCommand::GetConnectionReport { reply }) => {
+                            let (friends, members) = known_peer_sets(&storage, &crdt_engine).await;
+                            let _ = reply.send(connection_manager.report(|p| connections::classify(p, &friends, &members)));
+                        }
+                        Some(NodeCommand::GetPeerScores { reply }) => {
+                            let peers: Vec<libp2p::PeerId> = swarm_instance.connected_peers().cloned().collect();
+                            let scores = peers
+                                .iter()
+                                .map(|peer| scoring::PeerScore {
+                                    peer_id: peer.to_string(),
+                                    score: swarm_instance.behaviour().gossipsub.peer_score(peer),
+                                    app_score: peer_scores.app_score(peer),
+                                })
+                                .collect();
+                            let _ = reply.send(scores);
+                        }
                         Some(NodeCommand::GetTurnCredentials { reply }) => {
                             if let Some(rp) = relay_peer {
                                 let local_peer_id = swarm_instance.local_peer_id().to_string();
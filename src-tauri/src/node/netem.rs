@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use libp2p::gossipsub;
+use libp2p::PeerId;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+
+// simulated network conditions for reproducing sync bugs without a real bad
+// network. only the dev server can change the config, release builds always
+// run with the default, which passes everything straight through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct NetemConfig {
+    // added to every outbound publish and inbound gossip message
+    #[serde(default)]
+    pub latency_ms: u64,
+    // extra random delay on top of the latency, up to this many millis.
+    // delays are drawn per message so jitter can reorder them
+    #[serde(default)]
+    pub jitter_ms: u64,
+    // share of messages dropped in each direction, 0.0 to 1.0
+    #[serde(default)]
+    pub loss: f64,
+}
+
+impl NetemConfig {
+    fn is_active(&self) -> bool {
+        self.latency_ms > 0 || self.jitter_ms > 0 || self.loss > 0.0
+    }
+}
+
+// messages whose delay has run out, in release order
+#[derive(Default)]
+pub struct Released {
+    pub outbound: Vec<(String, Vec<u8>)>,
+    pub inbound: Vec<(PeerId, gossipsub::MessageId, gossipsub::Message)>,
+}
+
+enum Delayed {
+    Outbound(String, Vec<u8>),
+    Inbound(PeerId, gossipsub::MessageId, gossipsub::Message),
+}
+
+// drops and delays gossip in both directions per the current config
+pub struct Netem {
+    config: NetemConfig,
+    // keyed by release time, the sequence number keeps equal times in order
+    queue: BTreeMap<(Instant, u64), Delayed>,
+    seq: u64,
+}
+
+impl Netem {
+    pub fn new() -> Self {
+        Self {
+            config: NetemConfig::default(),
+            queue: BTreeMap::new(),
+            seq: 0,
+        }
+    }
+
+    // messages already held keep the delay they were given
+    #[cfg_attr(not(feature = "dev-server"), allow(dead_code))]
+    pub fn configure(&mut self, config: NetemConfig) {
+        self.config = NetemConfig {
+            loss: config.loss.clamp(0.0, 1.0),
+            ..config
+        };
+        log::info!("netem: {:?}", self.config);
+    }
+
+    // a publish to send right away, or None when it was dropped or held back
+    pub fn outbound(&mut self, topic: String, data: Vec<u8>) -> Option<(String, Vec<u8>)> {
+        if !self.config.is_active() {
+            return Some((topic, data));
+        }
+        self.hold(Delayed::Outbound(topic, data));
+        None
+    }
+
+    // an inbound message to handle right away, or None when it was dropped
+    // or held back. a dropped message is never validated, gossipsub forgets
+    // it as if it had not arrived
+    pub fn inbound(
+        &mut self,
+        source: PeerId,
+        message_id: gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) -> Option<(PeerId, gossipsub::MessageId, gossipsub::Message)> {
+        if !self.config.is_active() {
+            return Some((source, message_id, message));
+        }
+        self.hold(Delayed::Inbound(source, message_id, message));
+        None
+    }
+
+    pub fn next_release(&self) -> Option<Instant> {
+        self.queue.keys().next().map(|(at, _)| *at)
+    }
+
+    pub fn take_released(&mut self) -> Released {
+        let now = Instant::now();
+        let mut released = Released::default();
+        while let Some(entry) = self.queue.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            match entry.remove() {
+                Delayed::Outbound(topic, data) => released.outbound.push((topic, data)),
+                Delayed::Inbound(source, message_id, message) => {
+                    released.inbound.push((source, message_id, message))
+                }
+            }
+        }
+        released
+    }
+
+    fn hold(&mut self, delayed: Delayed) {
+        let mut rng = rand::thread_rng();
+        if self.config.loss > 0.0 && rng.gen_bool(self.config.loss) {
+            return;
+        }
+        let jitter = if self.config.jitter_ms > 0 {
+            rng.gen_range(0..=self.config.jitter_ms)
+        } else {
+            0
+        };
+        let at = Instant::now() + Duration::from_millis(self.config.latency_ms + jitter);
+        self.seq += 1;
+        self.queue.insert((at, self.seq), delayed);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+// how often os power and metered signals are re-read in auto mode
+pub const POWER_TICK_SECS: u64 = 60;
+// presence changes are coalesced and published at most this often in low power
+pub const PRESENCE_BATCH_SECS: u64 = 60;
+
+// gossipsub heartbeat per profile, the low power one trades mesh repair latency for wakeups
+pub const NORMAL_HEARTBEAT_SECS: u64 = 1;
+pub const LOW_POWER_HEARTBEAT_SECS: u64 = 5;
+
+// networking profile chosen by the user
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkProfile {
+    // follow the os battery and metered-connection signals
+    #[default]
+    Auto,
+    // always run full networking
+    Normal,
+    // always run the reduced profile
+    LowPower,
+}
+
+impl NetworkProfile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkProfile::Auto => "auto",
+            NetworkProfile::Normal => "normal",
+            NetworkProfile::LowPower => "low_power",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "auto" => Ok(NetworkProfile::Auto),
+            "normal" => Ok(NetworkProfile::Normal),
+            "low_power" => Ok(NetworkProfile::LowPower),
+            other => Err(format!("unknown network profile: {}", other)),
+        }
+    }
+}
+
+// os signals that drive the auto profile
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct PowerSignals {
+    pub on_battery: bool,
+    pub metered: bool,
+}
+
+// effective networking state reported to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkProfileStatus {
+    pub profile: NetworkProfile,
+    pub low_power: bool,
+    pub on_battery: bool,
+    pub metered: bool,
+    // the ui should hold off on fetching attachments and media until asked
+    pub defer_attachments: bool,
+}
+
+impl NetworkProfileStatus {
+    pub fn new(profile: NetworkProfile, signals: PowerSignals) -> Self {
+        let low_power = is_low_power(profile, signals);
+        Self {
+            profile,
+            low_power,
+            on_battery: signals.on_battery,
+            metered: signals.metered,
+            defer_attachments: low_power && (signals.metered || profile == NetworkProfile::LowPower),
+        }
+    }
+}
+
+pub fn is_low_power(profile: NetworkProfile, signals: PowerSignals) -> bool {
+    match profile {
+        NetworkProfile::Auto => signals.on_battery || signals.metered,
+        NetworkProfile::Normal => false,
+        NetworkProfile::LowPower => true,
+    }
+}
+
+// read os signals off the async runtime, the metered check shells out
+pub async fn detect_signals() -> PowerSignals {
+    tokio::task::spawn_blocking(|| PowerSignals {
+        on_battery: on_battery(),
+        metered: metered(),
+    })
+    .await
+    .unwrap_or_default()
+}
+
+// discharging when a battery is present and no mains supply reports online
+#[cfg(target_os = "linux")]
+fn on_battery() -> bool {
+    let entries = match std::fs::read_dir("/sys/class/power_supply") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    let mut has_battery = false;
+    let mut mains_online = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+        match kind.trim() {
+            "Battery" => has_battery = true,
+            "Mains" | "USB" => {
+                let online = std::fs::read_to_string(path.join("online")).unwrap_or_default();
+                if online.trim() == "1" {
+                    mains_online = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    has_battery && !mains_online
+}
+
+// networkmanager knows whether the active connection is metered (tethering, mobile data)
+#[cfg(target_os = "linux")]
+fn metered() -> bool {
+    let output = std::process::Command::new("nmcli")
+        .args(["-t", "-f", "GENERAL.METERED", "dev", "show"])
+        .output();
+    match output {
+        Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .filter_map(|line| line.split(':').nth(1))
+            .any(|value| value.trim().starts_with("yes")),
+        _ => false,
+    }
+}
+
+// no portable signal source elsewhere yet, auto behaves like normal there
+#[cfg(not(target_os = "linux"))]
+fn on_battery() -> bool {
+    false
+}
+
+#[cfg(not(target_os = "linux"))]
+fn metered() -> bool {
+    false
+}
@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+
+use futures::stream::{FuturesOrdered, StreamExt};
+use libp2p::gossipsub;
+use libp2p::PeerId;
+use tauri::async_runtime::JoinHandle;
+
+use super::gossip;
+use crate::crdt::sync::SyncMessage;
+use crate::protocol::messages::GossipMessage;
+use crate::verification;
+
+// messages waiting for a worker before new arrivals are dropped, gossipsub
+// will redeliver anything important through the mesh or catch-up
+const MAX_BACKLOG: usize = 1024;
+
+// decoded payload of an inbound gossip message
+pub enum Payload {
+    Sync(SyncMessage),
+    Gossip(Box<GossipMessage>),
+    // well formed json that isn't a message we know, most likely one a newer
+    // release added. its author did nothing wrong
+    Unknown,
+    // not valid json for the topic it arrived on
+    Malformed,
+}
+
+// an inbound gossip message decoded and signature checked off the event loop
+pub struct Prevalidated {
+    pub propagation_source: PeerId,
+    // gossipsub holds the message back from the mesh until it is reported
+    // as accepted, ignored or rejected under this id
+    pub message_id: gossipsub::MessageId,
+    pub message: gossipsub::Message,
+    pub payload: Payload,
+    // false when a signed payload (profile announcement, revocation, dm
+    // deletion) carries a bad signature, true for everything else
+    pub signature_valid: bool,
+    // a profile announcement carried a valid proof of work
+    pub proof_verified: bool,
+}
+
+// decodes and verifies inbound gossip on the blocking thread pool so a burst
+// of messages does not stall voice signaling and relay handling. results
+// come back in arrival order so edits never overtake the message they touch
+pub struct Prevalidator {
+    workers: usize,
+    backlog: VecDeque<(PeerId, gossipsub::MessageId, gossipsub::Message)>,
+    in_flight: FuturesOrdered<JoinHandle<Prevalidated>>,
+}
+
+impl Prevalidator {
+    pub fn new() -> Self {
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(2)
+            .clamp(2, 8);
+        Self {
+            workers,
+            backlog: VecDeque::new(),
+            in_flight: FuturesOrdered::new(),
+        }
+    }
+
+    // false when the backlog is full and the message was dropped
+    pub fn submit(
+        &mut self,
+        propagation_source: PeerId,
+        message_id: gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) -> bool {
+        if self.backlog.len() >= MAX_BACKLOG {
+            log::warn!(
+                "prevalidation backlog full, dropping message on {}",
+                message.topic
+            );
+            return false;
+        }
+        self.backlog
+            .push_back((propagation_source, message_id, message));
+        self.refill();
+        true
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+
+    // the next message in arrival order, waits for its worker to finish.
+    // must not be polled while idle
+    pub async fn next(&mut self) -> Option<Prevalidated> {
+        let result = self.in_flight.next().await?;
+        self.refill();
+        match result {
+            Ok(validated) => Some(validated),
+            Err(e) => {
+                log::warn!("prevalidation task failed: {}", e);
+                None
+            }
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.in_flight.len() < self.workers {
+            let Some((source, message_id, message)) = self.backlog.pop_front() else {
+                break;
+            };
+            self.in_flight
+                .push_back(tauri::async_runtime::spawn_blocking(move || {
+                    prevalidate(source, message_id, message)
+                }));
+        }
+    }
+}
+
+pub fn prevalidate(
+    propagation_source: PeerId,
+    message_id: gossipsub::MessageId,
+    message: gossipsub::Message,
+) -> Prevalidated {
+    let mut signature_valid = true;
+    let mut proof_verified = false;
+
+    let payload = if gossip::community_of_sync_topic(message.topic.as_str()).is_some() {
+        match serde_json::from_slice::<SyncMessage>(&message.data) {
+            Ok(sync_msg) => {
+                // an invite presented as proof is checked here, whether the
+                // inviter is a member is up to the event loop
+                if let SyncMessage::RequestSync {
+                    invite: Some(invite),
+                    ..
+                } = &sync_msg
+                {
+                    signature_valid = verification::verify_invite(invite);
+                }
+                Payload::Sync(sync_msg)
+            }
+            Err(_) => undecoded(&message.data),
+        }
+    } else {
+        match serde_json::from_slice::<GossipMessage>(&message.data) {
+            Ok(gossip_msg) => {
+                match &gossip_msg {
+                    GossipMessage::ProfileAnnounce(profile) => {
+                        signature_valid =
+                            verification::verify_announcement(&profile.public_key, profile);
+                        proof_verified = signature_valid
+                            && profile.verification_proof.as_ref().is_some_and(|proof| {
+                                verification::verify_proof_work(proof, &profile.peer_id)
+                            });
+                    }
+                    GossipMessage::ProfileRevoke(revocation) => {
+                        signature_valid =
+                            verification::verify_revocation(&revocation.public_key, revocation);
+                    }
+                    GossipMessage::DMDelete(request) => {
+                        signature_valid = verification::verify_dm_delete(request);
+                    }
+                    _ => {}
+                }
+                Payload::Gossip(Box::new(gossip_msg))
+            }
+            Err(_) => undecoded(&message.data),
+        }
+    };
+
+    Prevalidated {
+        propagation_source,
+        message_id,
+        message,
+        payload,
+        signature_valid,
+        proof_verified,
+    }
+}
+
+// a payload none of our message types decode, told apart by whether it is
+// json at all
+fn undecoded(data: &[u8]) -> Payload {
+    if serde_json::from_slice::<serde_json::Value>(data).is_ok() {
+        Payload::Unknown
+    } else {
+        Payload::Malformed
+    }
+}
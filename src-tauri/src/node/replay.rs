@@ -0,0 +1,292 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use automerge::AutoCommit;
+use libp2p::gossipsub;
+use libp2p::PeerId;
+use serde::Serialize;
+
+use super::community_id_from_topic;
+use super::gossip;
+use crate::crdt::sync::{DocumentSnapshot, SyncMessage};
+use crate::crdt::CrdtEngine;
+use crate::protocol::community::ModerationCapability;
+use crate::protocol::messages::{GossipMessage, Hlc};
+use crate::storage::{DiskStorage, RecordedEvent, RecordedEventKind};
+
+// writes the opt-in replay log. a no-op unless record_events was on when the
+// node started
+pub struct EventRecorder {
+    storage: Arc<DiskStorage>,
+    enabled: bool,
+}
+
+impl EventRecorder {
+    pub fn new(storage: Arc<DiskStorage>, enabled: bool) -> Self {
+        Self { storage, enabled }
+    }
+
+    // an inbound message as it reaches the handlers. the signed author is
+    // kept since the handlers authorize against it, not the forwarding peer
+    pub fn gossip(&self, propagation_source: &PeerId, message: &gossipsub::Message) {
+        if !self.enabled {
+            return;
+        }
+        let source = message.source.unwrap_or(*propagation_source).to_string();
+        self.record(
+            RecordedEventKind::Gossip,
+            message.topic.as_str(),
+            &source,
+            &message.data,
+        );
+    }
+
+    // a snapshot received directly from a member, logged as the offer older
+    // clients gossiped so replay merges it the same way
+    pub fn snapshot(&self, peer: &PeerId, snapshot: &DocumentSnapshot) {
+        if !self.enabled {
+            return;
+        }
+        let offer = SyncMessage::DocumentOffer(snapshot.clone());
+        if let Ok(data) = serde_json::to_vec(&offer) {
+            self.record(
+                RecordedEventKind::Gossip,
+                &gossip::topic_for_community_sync(&snapshot.community_id),
+                &peer.to_string(),
+                &data,
+            );
+        }
+    }
+
+    pub fn state(&self, name: &str, detail: &str) {
+        if self.enabled {
+            self.record(RecordedEventKind::State, name, detail, &[]);
+        }
+    }
+
+    fn record(&self, kind: RecordedEventKind, topic: &str, source: &str, data: &[u8]) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        if let Err(e) = self.storage.record_event(kind, topic, source, data, now) {
+            log::warn!("failed to record event: {}", e);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayTransition {
+    pub recorded_at: u64,
+    pub name: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayError {
+    pub event_id: i64,
+    pub recorded_at: u64,
+    pub topic: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayCommunity {
+    pub community_id: String,
+    // hex change hashes, sorted
+    pub heads: Vec<String>,
+    pub members: usize,
+    // channel id and message count
+    pub channels: Vec<(String, usize)>,
+}
+
+// what a replay did and the docs it ended up with
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    pub events: usize,
+    // gossip that changed or was merged into a scratch doc
+    pub applied: usize,
+    // gossip with nothing to apply, e.g. typing or presence
+    pub skipped: usize,
+    pub transitions: Vec<ReplayTransition>,
+    pub errors: Vec<ReplayError>,
+    pub communities: Vec<ReplayCommunity>,
+}
+
+// feed recorded events through the document side of the gossip handlers
+// against `engine`, which should start empty and be backed by scratch
+// storage. docs start from the first recorded snapshot of each community,
+// later snapshots are merged and messages, edits, deletions and kicks are
+// applied with the same authorization checks the live handlers use
+pub fn replay(events: &[RecordedEvent], engine: &mut CrdtEngine) -> ReplayReport {
+    let mut report = ReplayReport {
+        events: events.len(),
+        applied: 0,
+        skipped: 0,
+        transitions: Vec::new(),
+        errors: Vec::new(),
+        communities: Vec::new(),
+    };
+    let mut seen_chat_ids: HashSet<String> = HashSet::new();
+
+    for event in events {
+        match event.kind {
+            RecordedEventKind::State => report.transitions.push(ReplayTransition {
+                recorded_at: event.recorded_at,
+                name: event.topic.clone(),
+                detail: event.source.clone(),
+            }),
+            RecordedEventKind::Gossip => match apply(event, engine, &mut seen_chat_ids) {
+                Ok(true) => report.applied += 1,
+                Ok(false) => report.skipped += 1,
+                Err(error) => report.errors.push(ReplayError {
+                    event_id: event.id,
+                    recorded_at: event.recorded_at,
+                    topic: event.topic.clone(),
+                    error,
+                }),
+            },
+        }
+    }
+
+    let mut heads = engine.doc_heads();
+    heads.sort_by(|a, b| a.0.cmp(&b.0));
+    for (community_id, mut community_heads) in heads {
+        community_heads.sort();
+        let members = engine
+            .get_members(&community_id)
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let channels = engine
+            .get_channels(&community_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|ch| {
+                let count = engine.count_messages(&community_id, &ch.id).unwrap_or(0);
+                (ch.id, count)
+            })
+            .collect();
+        report.communities.push(ReplayCommunity {
+            community_id,
+            heads: community_heads,
+            members,
+            channels,
+        });
+    }
+    report
+}
+
+// apply one gossip event, Ok(false) when it had nothing to change
+fn apply(
+    event: &RecordedEvent,
+    engine: &mut CrdtEngine,
+    seen_chat_ids: &mut HashSet<String>,
+) -> Result<bool, String> {
+    if gossip::community_of_sync_topic(&event.topic).is_some() {
+        let sync_msg = serde_json::from_slice::<SyncMessage>(&event.data)
+            .map_err(|e| format!("failed to parse sync message: {}", e))?;
+        let SyncMessage::DocumentOffer(snapshot) = sync_msg else {
+            return Ok(false);
+        };
+        if engine.has_community(&snapshot.community_id) {
+            engine.merge_remote_doc(&snapshot.community_id, &snapshot.doc_bytes)?;
+        } else {
+            let doc = AutoCommit::load(&snapshot.doc_bytes)
+                .map_err(|e| format!("failed to load snapshot: {}", e))?;
+            engine.insert_doc(&snapshot.community_id, doc);
+        }
+        return Ok(true);
+    }
+
+    let gossip_msg = serde_json::from_slice::<GossipMessage>(&event.data)
+        .map_err(|e| format!("failed to parse gossip message: {}", e))?;
+    let Some(community_id) = community_id_from_topic(&event.topic) else {
+        return Ok(false);
+    };
+    if !engine.has_community(community_id) {
+        return Ok(false);
+    }
+    let sender = event.source.as_str();
+
+    match gossip_msg {
+        GossipMessage::Chat(mut chat_msg) => {
+            if !seen_chat_ids.insert(chat_msg.id.clone()) {
+                return Ok(false);
+            }
+            if chat_msg.hlc == Hlc::default() {
+                chat_msg.hlc = Hlc {
+                    wall: chat_msg.timestamp,
+                    counter: 0,
+                };
+            }
+            engine.append_message(community_id, &chat_msg)
+        }
+        GossipMessage::EditMessage {
+            message_id,
+            new_content,
+        } => {
+            engine.edit_message(community_id, &message_id, &new_content)?;
+            Ok(true)
+        }
+        GossipMessage::DeleteMessage {
+            message_id,
+            capability,
+        } => {
+            let allowed = match engine.get_message(community_id, &message_id)? {
+                Some(msg) => {
+                    msg.author_id == sender
+                        || engine.may_moderate(
+                            community_id,
+                            sender,
+                            ModerationCapability::DeleteMessages,
+                            Some(&msg.channel_id),
+                            capability.as_ref(),
+                        )
+                }
+                None => false,
+            };
+            if !allowed {
+                return Err(format!(
+                    "unauthorized deletion of {} from {}",
+                    message_id, sender
+                ));
+            }
+            engine.delete_message(community_id, &message_id)?;
+            Ok(true)
+        }
+        GossipMessage::Reaction {
+            message_id,
+            emoji,
+            active,
+        } => Ok(engine
+            .set_reaction(community_id, &message_id, sender, &emoji, active)?
+            .is_some()),
+        GossipMessage::MemberKicked {
+            peer_id,
+            capability,
+        } => {
+            let target_is_owner = engine
+                .get_members(community_id)
+                .map(|members| {
+                    members
+                        .iter()
+                        .any(|m| m.peer_id == peer_id && m.roles.iter().any(|r| r == "owner"))
+                })
+                .unwrap_or(false);
+            let allowed = !target_is_owner
+                && engine.may_moderate(
+                    community_id,
+                    sender,
+                    ModerationCapability::KickMembers,
+                    None,
+                    capability.as_ref(),
+                );
+            if !allowed {
+                return Ok(false);
+            }
+            engine.remove_member(community_id, &peer_id)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
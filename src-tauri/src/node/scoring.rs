@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use libp2p::gossipsub::{PeerScoreParams, PeerScoreThresholds};
+use libp2p::PeerId;
+use serde::Serialize;
+
+// how often application penalties decay back toward zero
+pub const SCORE_DECAY_TICK_SECS: u64 = 60;
+// fraction of an application penalty kept per decay tick
+const SCORE_DECAY_FACTOR: f64 = 0.8;
+// penalties smaller than this are dropped entirely
+const SCORE_DECAY_TO_ZERO: f64 = 0.1;
+// floor so one flood cannot leave a peer unrecoverable forever
+const MIN_APP_SCORE: f64 = -100.0;
+
+// more gossip messages than this from one author inside the window is a violation
+const RATE_LIMIT_WINDOW_SECS: u64 = 10;
+const RATE_LIMIT_MAX_MESSAGES: u32 = 100;
+
+// application-level misbehaviour observed in the node event loop
+#[derive(Debug, Clone, Copy)]
+pub enum Misbehaviour {
+    // profile announcement or revocation whose signature does not verify
+    InvalidSignature,
+    // published more gossip than the rate limit allows
+    RateLimited,
+    // community document bytes that failed to load, verify or merge
+    MalformedDoc,
+    // payload that does not decode as any known gossip message
+    MalformedMessage,
+}
+
+impl Misbehaviour {
+    fn penalty(&self) -> f64 {
+        match self {
+            Misbehaviour::InvalidSignature => 10.0,
+            Misbehaviour::RateLimited => 2.0,
+            Misbehaviour::MalformedDoc => 5.0,
+            Misbehaviour::MalformedMessage => 1.0,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Misbehaviour::InvalidSignature => "invalid_signature",
+            Misbehaviour::RateLimited => "rate_limited",
+            Misbehaviour::MalformedDoc => "malformed_doc",
+            Misbehaviour::MalformedMessage => "malformed_message",
+        }
+    }
+}
+
+// per-peer score snapshot returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerScore {
+    pub peer_id: String,
+    // combined gossipsub score, none until gossipsub has seen the peer
+    pub score: Option<f64>,
+    // our application-specific component before gossipsub weighting
+    pub app_score: f64,
+}
+
+// tracks application penalties and per-peer message rates
+pub struct PeerScores {
+    app_scores: HashMap<PeerId, f64>,
+    // message count within the current window, keyed by signed author so
+    // peers relaying for the mesh aren't blamed for what they pass along
+    rates: HashMap<PeerId, (Instant, u32)>,
+}
+
+impl PeerScores {
+    pub fn new() -> Self {
+        Self {
+            app_scores: HashMap::new(),
+            rates: HashMap::new(),
+        }
+    }
+
+    // apply a penalty and return the new application score
+    pub fn penalize(&mut self, peer: &PeerId, kind: Misbehaviour) -> f64 {
+        let score = self.app_scores.entry(*peer).or_insert(0.0);
+        *score = (*score - kind.penalty()).max(MIN_APP_SCORE);
+        *score
+    }
+
+    // count a message by this author, returns true once it exceeds the rate limit
+    pub fn note_message(&mut self, peer: &PeerId) -> bool {
+        let now = Instant::now();
+        let entry = self.rates.entry(*peer).or_insert((now, 0));
+        if now.duration_since(entry.0).as_secs() >= RATE_LIMIT_WINDOW_SECS {
+            *entry = (now, 0);
+        }
+        entry.1 += 1;
+        entry.1 > RATE_LIMIT_MAX_MESSAGES
+    }
+
+    // decay penalties toward zero, returns every peer whose score changed
+    pub fn decay(&mut self) -> Vec<(PeerId, f64)> {
+        let mut changed = Vec::new();
+        self.app_scores.retain(|peer, score| {
+            *score *= SCORE_DECAY_FACTOR;
+            if score.abs() < SCORE_DECAY_TO_ZERO {
+                changed.push((*peer, 0.0));
+                false
+            } else {
+                changed.push((*peer, *score));
+                true
+            }
+        });
+
+        let now = Instant::now();
+        self.rates
+            .retain(|_, (start, _)| now.duration_since(*start).as_secs() < RATE_LIMIT_WINDOW_SECS);
+
+        changed
+    }
+
+    pub fn app_score(&self, peer: &PeerId) -> f64 {
+        self.app_scores.get(peer).copied().unwrap_or(0.0)
+    }
+}
+
+// gossipsub scoring parameters, the application component dominates so our
+// penalties push misbehaving peers below the graylist threshold quickly
+pub fn score_params() -> (PeerScoreParams, PeerScoreThresholds) {
+    let params = PeerScoreParams {
+        app_specific_weight: 1.0,
+        // peers reached through relay circuits all share the relay's ip, so
+        // colocation penalties would punish every relayed peer
+        ip_colocation_factor_weight: 0.0,
+        ..PeerScoreParams::default()
+    };
+
+    let thresholds = PeerScoreThresholds {
+        gossip_threshold: -10.0,
+        publish_threshold: -20.0,
+        graylist_threshold: -40.0,
+        ..PeerScoreThresholds::default()
+    };
+
+    (params, thresholds)
+}
@@ -0,0 +1,96 @@
+use tokio::time::{Duration, Instant};
+
+// audio level (0.0 - 1.0 rms) that starts a speaking period
+pub const SPEAKING_ON_LEVEL: f32 = 0.08;
+// quieter frames above this keep an active speaking period alive
+pub const SPEAKING_OFF_LEVEL: f32 = 0.04;
+// how long speech may dip below the off level before we call it silence
+const SPEAKING_HANG_MS: u64 = 400;
+
+// a transition worth broadcasting
+pub struct SpeakingChange {
+    pub community_id: String,
+    pub channel_id: String,
+    pub speaking: bool,
+}
+
+// hysteresis over the local microphone level so the speaking indicator does
+// not flicker between words, only transitions go out over gossip
+pub struct SpeakingState {
+    channel: Option<(String, String)>,
+    speaking: bool,
+    expires_at: Option<Instant>,
+}
+
+impl SpeakingState {
+    pub fn new() -> Self {
+        Self {
+            channel: None,
+            speaking: false,
+            expires_at: None,
+        }
+    }
+
+    pub fn report(&mut self, community_id: &str, channel_id: &str, level: f32) -> Vec<SpeakingChange> {
+        let mut changes = Vec::new();
+        let now = Instant::now();
+
+        // moved to another channel while talking, close out the old one
+        let same_channel = self
+            .channel
+            .as_ref()
+            .map(|(c, ch)| c == community_id && ch == channel_id)
+            .unwrap_or(false);
+        if !same_channel {
+            if let Some(change) = self.stop() {
+                changes.push(change);
+            }
+            self.channel = Some((community_id.to_string(), channel_id.to_string()));
+        }
+
+        if level >= SPEAKING_ON_LEVEL {
+            if !self.speaking {
+                self.speaking = true;
+                changes.push(SpeakingChange {
+                    community_id: community_id.to_string(),
+                    channel_id: channel_id.to_string(),
+                    speaking: true,
+                });
+            }
+            self.expires_at = Some(now + Duration::from_millis(SPEAKING_HANG_MS));
+        } else if self.speaking && level >= SPEAKING_OFF_LEVEL {
+            self.expires_at = Some(now + Duration::from_millis(SPEAKING_HANG_MS));
+        }
+
+        changes
+    }
+
+    // when the current speaking period runs out, if one is active
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.speaking {
+            self.expires_at
+        } else {
+            None
+        }
+    }
+
+    pub fn expire(&mut self) -> Option<SpeakingChange> {
+        match self.expires_at {
+            Some(at) if Instant::now() >= at => self.stop(),
+            _ => None,
+        }
+    }
+
+    fn stop(&mut self) -> Option<SpeakingChange> {
+        self.expires_at = None;
+        if !self.speaking {
+            return None;
+        }
+        self.speaking = false;
+        self.channel.as_ref().map(|(community_id, channel_id)| SpeakingChange {
+            community_id: community_id.clone(),
+            channel_id: channel_id.clone(),
+            speaking: false,
+        })
+    }
+}
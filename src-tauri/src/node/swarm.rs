@@ -1,26 +1,43 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use std::time::Duration;
 
 use libp2p::{
-    gossipsub, identify, identity, kad, mdns, noise, ping, rendezvous,
+    autonat, gossipsub, identify, identity, kad, mdns, noise, ping, rendezvous,
     request_response::{self, cbor, ProtocolSupport},
     tcp, yamux, Swarm, SwarmBuilder,
 };
 
 use super::behaviour::DuskBehaviour;
+use super::cover::{CoverTraffic, PaddingTransform};
+use super::power;
+use super::scoring;
+use crate::crdt::sync::DocumentSnapshot;
+use crate::protocol::catchup::{CatchupRequest, CatchupResponse, CATCHUP_PROTOCOL};
 use crate::protocol::directory::{DirectoryRequest, DirectoryResponse, DIRECTORY_PROTOCOL};
 use crate::protocol::gif::{GifRequest, GifResponse, GIF_PROTOCOL};
+use crate::protocol::history::{HistoryRequest, HistoryResponse, HISTORY_PROTOCOL};
+use crate::protocol::presence::{PresenceRequest, PresenceResponse, PRESENCE_PROTOCOL};
+use crate::protocol::snapshot::{SnapshotResponse, SNAPSHOT_PROTOCOL};
 use crate::protocol::turn::{
     TurnCredentialRequest, TurnCredentialResponse, TURN_CREDENTIALS_PROTOCOL,
 };
+use crate::protocol::voice::{VoiceRosterRequest, VoiceRosterResponse, VOICE_ROSTER_PROTOCOL};
 
 pub fn build_swarm(
     keypair: &identity::Keypair,
+    low_power: bool,
+    mdns_enabled: bool,
+    direct_connections: bool,
+    cover_traffic: Arc<CoverTraffic>,
 ) -> Result<Swarm<DuskBehaviour>, Box<dyn std::error::Error>> {
-    // gossipsub config: content-addressed message deduplication
+    // gossipsub config: content-addressed message deduplication. the topic is
+    // part of the id, the same payload published on several topics (a profile
+    // announcement on every directory topic) is not a duplicate
     let message_id_fn = |message: &gossipsub::Message| {
         let mut hasher = DefaultHasher::new();
+        message.topic.hash(&mut hasher);
         message.data.hash(&mut hasher);
         if let Some(ref source) = message.source {
             source.hash(&mut hasher);
@@ -28,9 +45,19 @@ pub fn build_swarm(
         gossipsub::MessageId::from(hasher.finish().to_string())
     };
 
+    // a slower heartbeat means fewer radio wakeups on battery or metered links
+    let heartbeat_secs = if low_power {
+        power::LOW_POWER_HEARTBEAT_SECS
+    } else {
+        power::NORMAL_HEARTBEAT_SECS
+    };
+
     let gossipsub_config = gossipsub::ConfigBuilder::default()
-        .heartbeat_interval(Duration::from_secs(1))
+        .heartbeat_interval(Duration::from_secs(heartbeat_secs))
         .validation_mode(gossipsub::ValidationMode::Strict)
+        // nothing is relayed until the event loop has checked it, so a bad
+        // message stops at the first peer that sees it
+        .validate_messages()
         .message_id_fn(message_id_fn)
         .mesh_n(6)
         .mesh_n_low(4)
@@ -54,16 +81,33 @@ pub fn build_swarm(
         .with_behaviour(|key, relay_client| {
             let peer_id = key.public().to_peer_id();
 
-            let gossipsub = gossipsub::Behaviour::new(
+            let mut gossipsub = gossipsub::Behaviour::new_with_transform(
                 gossipsub::MessageAuthenticity::Signed(key.clone()),
                 gossipsub_config,
+                None,
+                PaddingTransform::new(cover_traffic),
             )
             .expect("valid gossipsub behaviour");
 
+            // peer scoring so application penalties fed from the event loop
+            // prune misbehaving peers from the mesh
+            let (score_params, score_thresholds) = scoring::score_params();
+            gossipsub
+                .with_peer_score(score_params, score_thresholds)
+                .expect("valid gossipsub peer score params");
+
             let kademlia = kad::Behaviour::new(peer_id, kad::store::MemoryStore::new(peer_id));
 
-            let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), peer_id)
-                .expect("valid mdns behaviour");
+            // lan multicast queries keep the radio busy, skip them in low power
+            let mdns = if low_power || !mdns_enabled {
+                None
+            } else {
+                Some(
+                    mdns::tokio::Behaviour::new(mdns::Config::default(), peer_id)
+                        .expect("valid mdns behaviour"),
+                )
+            }
+            .into();
 
             let identify = identify::Behaviour::new(identify::Config::new(
                 "/dusk/1.0.0".to_string(),
@@ -72,6 +116,13 @@ pub fn build_swarm(
 
             let rendezvous = rendezvous::client::Behaviour::new(key.clone());
 
+            // probes ask other peers to dial our observed addresses back, the
+            // ones that answer become confirmed external addresses that identify
+            // and rendezvous hand out and that switch kademlia to server mode
+            let autonat = direct_connections
+                .then(|| autonat::Behaviour::new(peer_id, autonat::Config::default()))
+                .into();
+
             DuskBehaviour {
                 relay_client,
                 rendezvous,
@@ -79,6 +130,7 @@ pub fn build_swarm(
                 kademlia,
                 mdns,
                 identify,
+                autonat,
                 // ping every 30s to keep the relay connection alive
                 ping: ping::Behaviour::new(
                     ping::Config::new().with_interval(Duration::from_secs(30)),
@@ -102,6 +154,36 @@ pub fn build_swarm(
                         request_response::Config::default()
                             .with_request_timeout(Duration::from_secs(10)),
                     ),
+                // catch-up is served and requested peer to peer, so both directions
+                catchup: cbor::Behaviour::<CatchupRequest, CatchupResponse>::new(
+                    [(CATCHUP_PROTOCOL, ProtocolSupport::Full)],
+                    request_response::Config::default()
+                        .with_request_timeout(Duration::from_secs(10)),
+                ),
+                // history pages are served and requested between members
+                history: cbor::Behaviour::<HistoryRequest, HistoryResponse>::new(
+                    [(HISTORY_PROTOCOL, ProtocolSupport::Full)],
+                    request_response::Config::default()
+                        .with_request_timeout(Duration::from_secs(10)),
+                ),
+                // friend presence heartbeats to the relay (outbound only)
+                presence_service: cbor::Behaviour::<PresenceRequest, PresenceResponse>::new(
+                    [(PRESENCE_PROTOCOL, ProtocolSupport::Outbound)],
+                    request_response::Config::default()
+                        .with_request_timeout(Duration::from_secs(15)),
+                ),
+                // voice rosters are served and requested between members
+                voice_roster: cbor::Behaviour::<VoiceRosterRequest, VoiceRosterResponse>::new(
+                    [(VOICE_ROSTER_PROTOCOL, ProtocolSupport::Full)],
+                    request_response::Config::default()
+                        .with_request_timeout(Duration::from_secs(5)),
+                ),
+                // snapshots are pushed to and merged by members in both directions
+                snapshot: cbor::Behaviour::<DocumentSnapshot, SnapshotResponse>::new(
+                    [(SNAPSHOT_PROTOCOL, ProtocolSupport::Full)],
+                    request_response::Config::default()
+                        .with_request_timeout(Duration::from_secs(30)),
+                ),
             }
         })?
         .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(Duration::from_secs(300)))
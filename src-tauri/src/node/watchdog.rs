@@ -0,0 +1,78 @@
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+
+// how often the background watchdog probes the relay
+pub const WATCHDOG_TICK_SECS: u64 = 30;
+
+// well-known hosts used to tell a general internet outage apart from the
+// relay being unreachable, only asked when the user checks connectivity
+const PROBE_HOSTS: &[(&str, u16)] = &[
+    ("www.apple.com", 80),
+    ("www.google.com", 80),
+    ("www.yahoo.com", 80),
+];
+
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+// networking mode the node is currently operating in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkMode {
+    // no internet, only mdns peers on the local network are reachable
+    LanOnly,
+    // internet is up, relay and wan discovery are in use
+    Full,
+}
+
+impl NetworkMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkMode::LanOnly => "lan_only",
+            NetworkMode::Full => "full",
+        }
+    }
+}
+
+// attempt tcp connections to well-known hosts, true if any succeeds
+pub async fn probe_internet() -> bool {
+    let connect_timeout = Duration::from_secs(PROBE_TIMEOUT_SECS);
+
+    let futures: Vec<_> = PROBE_HOSTS
+        .iter()
+        .map(|(host, port)| {
+            let addr = format!("{}:{}", host, port);
+            timeout(connect_timeout, TcpStream::connect(addr))
+        })
+        .collect();
+
+    let results = futures::future::join_all(futures).await;
+
+    results.iter().any(|r| matches!(r, Ok(Ok(_))))
+}
+
+// host and port of a relay reachable over tcp, none for other transports
+pub fn probe_target(addr: &Multiaddr) -> Option<String> {
+    let mut host = None;
+    let mut port = None;
+    for protocol in addr.iter() {
+        match protocol {
+            Protocol::Dns(h) | Protocol::Dns4(h) | Protocol::Dns6(h) => host = Some(h.to_string()),
+            Protocol::Ip4(ip) => host = Some(ip.to_string()),
+            Protocol::Ip6(ip) => host = Some(format!("[{}]", ip)),
+            Protocol::Tcp(p) => port = Some(p),
+            _ => {}
+        }
+    }
+    Some(format!("{}:{}", host?, port?))
+}
+
+// the periodic probe only opens a tcp connection to our own relay, nothing
+// else on the internet hears from us every tick
+pub async fn probe_relay(target: &str) -> bool {
+    let connect_timeout = Duration::from_secs(PROBE_TIMEOUT_SECS);
+    matches!(
+        timeout(connect_timeout, TcpStream::connect(target)).await,
+        Ok(Ok(_))
+    )
+}
@@ -0,0 +1,26 @@
+// catch-up protocol types exchanged directly between peers.
+// a peer that just subscribed to a channel topic asks connected members for
+// messages it missed, and they answer from their short-term gossip cache.
+
+use libp2p::StreamProtocol;
+
+pub const CATCHUP_PROTOCOL: StreamProtocol = StreamProtocol::new("/dusk/catchup/1.0.0");
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CatchupRequest {
+    pub topic: String,
+    // unix ms of the newest message the requester already has
+    pub since: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CatchupResponse {
+    pub messages: Vec<CachedGossip>,
+}
+
+// a raw gossip payload as it was received on the topic
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CachedGossip {
+    pub received_at: u64,
+    pub data: Vec<u8>,
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use super::identity::VerificationPolicy;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommunityMeta {
     pub id: String,
@@ -7,6 +9,20 @@ pub struct CommunityMeta {
     pub description: String,
     pub created_by: String,
     pub created_at: u64,
+    // minimum verification required of members, enforced by moderators on join
+    #[serde(default = "default_community_policy")]
+    pub verification_policy: VerificationPolicy,
+    // keep earlier revisions of edited messages and let members read them
+    #[serde(default)]
+    pub edit_history: bool,
+    // left with keep_history, readable locally but no longer synced
+    #[serde(default)]
+    pub archived: bool,
+}
+
+// communities without an explicit policy defer to each member's own setting
+pub fn default_community_policy() -> VerificationPolicy {
+    VerificationPolicy::Allow
 }
 
 // user-defined grouping for channels within a community
@@ -18,6 +34,68 @@ pub struct CategoryMeta {
     pub position: u32,
 }
 
+// a column of the community's task board
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskList {
+    pub id: String,
+    pub community_id: String,
+    pub name: String,
+    pub position: u32,
+    pub created_by: String,
+    pub created_at: u64,
+}
+
+// cards live in one flat map pointing at their list, so a card moved by two
+// members at once ends up in one list instead of two
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskCard {
+    pub id: String,
+    pub community_id: String,
+    pub list_id: String,
+    pub title: String,
+    pub description: String,
+    pub position: u32,
+    pub assignees: Vec<String>,
+    // unix millis
+    pub due_at: Option<u64>,
+    pub done: bool,
+    pub created_by: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskBoard {
+    pub lists: Vec<TaskList>,
+    pub cards: Vec<TaskCard>,
+}
+
+// changes to a card, fields left unset stay as they are
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TaskCardPatch {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    // move to another list, or within the current one with position
+    #[serde(default)]
+    pub list_id: Option<String>,
+    #[serde(default)]
+    pub position: Option<u32>,
+    #[serde(default)]
+    pub assignees: Option<Vec<String>>,
+    #[serde(default)]
+    pub due_at: Option<u64>,
+    // drop the due date, wins over due_at
+    #[serde(default)]
+    pub clear_due: bool,
+    #[serde(default)]
+    pub done: Option<bool>,
+}
+
+pub const MAX_TASK_TITLE_LEN: usize = 200;
+pub const MAX_TASK_DESCRIPTION_LEN: usize = 4000;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelMeta {
     pub id: String,
@@ -28,14 +106,68 @@ pub struct ChannelMeta {
     pub position: u32,
     // channels without a category sit at the top level
     pub category_id: Option<String>,
+    // voice channels admit anyone while unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_participants: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChannelKind {
     Text,
     Voice,
+    // text channel only owners and admins post in, other communities can follow it
+    Announcement,
+    // voice channel where listeners need a moderator's approval to speak
+    Stage,
+}
+
+impl ChannelKind {
+    pub fn is_voice(&self) -> bool {
+        matches!(self, ChannelKind::Voice | ChannelKind::Stage)
+    }
+}
+
+// an announcement channel mirrored into one of our channels, local to the
+// admin who set it up since their node does the re-publishing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelFollow {
+    pub source_community_id: String,
+    pub source_channel_id: String,
+    pub target_community_id: String,
+    pub target_channel_id: String,
+    pub created_at: u64,
+}
+
+// how the user arranged their community list. purely local, it never goes
+// into a community document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommunityLayout {
+    pub folders: Vec<CommunityFolder>,
+    // communities missing here sit at the end of the top level
+    pub communities: Vec<CommunityPlacement>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunityFolder {
+    pub id: String,
+    pub name: String,
+    pub position: u32,
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunityPlacement {
+    pub community_id: String,
+    // none for the top level
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    #[serde(default)]
+    pub position: u32,
 }
 
+pub const MAX_FOLDER_NAME_LEN: usize = 64;
+
 // invite codes encode the minimum information needed to join a community
 // deliberately excludes IP addresses to protect peer privacy
 // peers discover each other via the rendezvous protocol on the relay server
@@ -43,8 +175,28 @@ pub enum ChannelKind {
 pub struct InviteCode {
     pub community_id: String,
     pub community_name: String,
+    // owner peer id, lets the joiner check the snapshot it receives chains back
+    // to a checkpoint signed by this owner. empty in invites from older clients
+    #[serde(default)]
+    pub owner_id: String,
+    // member who generated the invite and their public key, hex protobuf
+    #[serde(default)]
+    pub inviter: String,
+    #[serde(default)]
+    pub inviter_key: String,
+    // unix ms after which members stop serving snapshots against the invite
+    #[serde(default)]
+    pub expires_at: u64,
+    // inviter's signature over the fields above, see verification::sign_invite.
+    // a joiner presents the invite in its RequestSync as proof it was let in
+    #[serde(default)]
+    pub signature: String,
 }
 
+// how long an invite works as proof for a first sync, joining itself
+// needs no proof and still works afterwards
+pub const INVITE_PROOF_TTL_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
 impl InviteCode {
     // encode the invite as a base58 string for easy sharing
     pub fn encode(&self) -> String {
@@ -71,4 +223,174 @@ pub struct Member {
     pub roles: Vec<String>,
     pub trust_level: f64,
     pub joined_at: u64,
+    #[serde(default)]
+    pub activity: Option<String>,
+    // seed for the generated avatar when the member set one for this community
+    #[serde(default)]
+    pub avatar_seed: Option<String>,
+}
+
+// a per-community identity override. ours are kept locally and announced only
+// on that community's presence topic, other members' are cached as received.
+// an empty display name withdraws the override
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunityProfile {
+    pub community_id: String,
+    pub peer_id: String,
+    pub display_name: String,
+    // replaces the display name as the avatar generator input
+    pub avatar_seed: Option<String>,
+    pub timestamp: u64,
+}
+
+pub const MAX_COMMUNITY_DISPLAY_NAME_LEN: usize = 64;
+pub const MAX_COMMUNITY_AVATAR_SEED_LEN: usize = 64;
+
+// a document key left holding several concurrent values after two partitioned
+// halves of a community changed it independently
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocConflict {
+    // dotted path such as "meta.name" or "members.<peer_id>.roles"
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+// owner-signed record of the document heads at a point in time, stored in the doc
+// so joining peers can check a received snapshot contains an owner-approved state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocCheckpoint {
+    // hex encoded automerge change hashes
+    pub heads: Vec<String>,
+    pub created_at: u64,
+    pub signer: String,
+    // hex encoded protobuf public key of the signer
+    pub public_key: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MembershipAction {
+    Join,
+    Leave,
+    // removed by an owner or admin
+    Kick,
+    // roles replaced by an owner or admin
+    SetRoles,
+}
+
+impl MembershipAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MembershipAction::Join => "join",
+            MembershipAction::Leave => "leave",
+            MembershipAction::Kick => "kick",
+            MembershipAction::SetRoles => "set_roles",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "join" => Some(MembershipAction::Join),
+            "leave" => Some(MembershipAction::Leave),
+            "kick" => Some(MembershipAction::Kick),
+            "set_roles" => Some(MembershipAction::SetRoles),
+            _ => None,
+        }
+    }
+}
+
+// signed entry in a community's membership log. the member list is replayed
+// from these, so a stale member map entry merged in from an old doc can't
+// bring back someone who left or roles they no longer hold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MembershipEvent {
+    pub action: MembershipAction,
+    // the member the event is about
+    pub peer_id: String,
+    // who signed it, the member itself for join and leave
+    pub actor: String,
+    // hex encoded protobuf public key of the actor
+    pub actor_key: String,
+    // roles after a join or set_roles, empty otherwise
+    pub roles: Vec<String>,
+    pub display_name: String,
+    pub timestamp: u64,
+    pub signature: String,
+    // token a kick was made under when the actor is not an owner or admin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capability: Option<CapabilityToken>,
+}
+
+// the founder's place for a membership event. replay follows these ahead of
+// event timestamps, which whoever signs an event picks, so an event can't be
+// backdated in front of one the founder already placed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MembershipOrder {
+    // verification::membership_event_key of the event placed
+    pub event_key: String,
+    pub seq: u64,
+    // the founder's clock when it placed the event, which the event is known
+    // to be older than
+    pub placed_at: u64,
+    pub signer: String,
+    // hex encoded protobuf public key of the signer
+    pub public_key: String,
+    pub signature: String,
+}
+
+// a membership event as shown in the audit view
+#[derive(Debug, Clone, Serialize)]
+pub struct MembershipLogEntry {
+    #[serde(flatten)]
+    pub event: MembershipEvent,
+    pub verified: bool,
+    // one line describing the event in the user's locale
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationCapability {
+    DeleteMessages,
+    KickMembers,
+    // disconnect voice participants or move them to another voice channel
+    MoveMembers,
+}
+
+impl ModerationCapability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationCapability::DeleteMessages => "delete_messages",
+            ModerationCapability::KickMembers => "kick_members",
+            ModerationCapability::MoveMembers => "move_members",
+        }
+    }
+}
+
+// longest chain of delegations accepted below an owner-issued token
+pub const MAX_CAPABILITY_DEPTH: usize = 4;
+
+// signed grant of one moderation power until a deadline. the owner issues
+// them and holders may delegate narrower ones onward, so moderators can act
+// while the owner is offline. receivers check the chain back to the owner
+// instead of trusting whatever roles their copy of the doc shows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityToken {
+    pub id: String,
+    pub community_id: String,
+    pub capability: ModerationCapability,
+    // limited to one channel when set
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    pub holder: String,
+    pub issuer: String,
+    // hex encoded protobuf public key of the issuer
+    pub issuer_key: String,
+    // unix millis
+    pub expires_at: u64,
+    // the token the issuer delegates from, none when the owner issued it
+    #[serde(default)]
+    pub parent: Option<Box<CapabilityToken>>,
+    pub signature: String,
 }
@@ -0,0 +1,27 @@
+// channel history protocol types exchanged directly between community members.
+// a member with a sparse local copy of a channel asks an online member for a
+// page of older messages instead of waiting for a full document merge.
+
+use libp2p::StreamProtocol;
+
+use super::messages::ChatMessage;
+
+pub const HISTORY_PROTOCOL: StreamProtocol = StreamProtocol::new("/dusk/history/1.0.0");
+
+// largest page a peer will serve in one response
+pub const HISTORY_MAX_LIMIT: u32 = 100;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HistoryRequest {
+    pub community_id: String,
+    pub channel_id: String,
+    // only messages strictly older than this unix ms timestamp
+    pub before: Option<u64>,
+    pub limit: u32,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum HistoryResponse {
+    Messages(Vec<ChatMessage>),
+    Error(String),
+}
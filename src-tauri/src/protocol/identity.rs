@@ -3,7 +3,13 @@ use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::storage::DiskStorage;
+use crate::protocol::messages::{
+    clean_status_text, ProfileAnnouncement, MAX_STATUS_EMOJI_LEN, MAX_STATUS_MESSAGE_LEN,
+};
+use crate::storage::{keystore, DiskStorage};
+
+// returned by load when the stored keypair needs a passphrase first
+pub const IDENTITY_LOCKED: &str = "identity is locked";
 
 pub struct DuskIdentity {
     pub keypair: identity::Keypair,
@@ -34,12 +40,32 @@ impl DuskIdentity {
         }
     }
 
-    // load an existing identity from disk
+    // load an existing identity from disk, fails with IDENTITY_LOCKED when
+    // the keypair is sealed under a passphrase
     pub fn load(storage: &DiskStorage) -> Result<Self, String> {
-        let keypair_bytes = storage
+        Self::load_with_passphrase(storage, None)
+    }
+
+    pub fn load_with_passphrase(storage: &DiskStorage, passphrase: Option<&str>) -> Result<Self, String> {
+        let stored = storage
             .load_keypair()
             .map_err(|e| format!("failed to load keypair: {}", e))?;
 
+        let keypair_bytes = if keystore::is_sealed(&stored) {
+            let passphrase = passphrase.ok_or(IDENTITY_LOCKED)?;
+            keystore::open(passphrase, &stored)?
+        } else if crate::storage::portable_mode() {
+            // a key left in the clear is never used from a portable install,
+            // the unlock passphrase seals it first
+            let passphrase = passphrase.ok_or(IDENTITY_LOCKED)?;
+            storage
+                .save_keypair(&keystore::seal(passphrase, &stored)?)
+                .map_err(|e| format!("failed to seal keypair: {}", e))?;
+            stored
+        } else {
+            stored
+        };
+
         let keypair = identity::Keypair::from_protobuf_encoding(&keypair_bytes)
             .map_err(|e| format!("invalid keypair data: {}", e))?;
 
@@ -58,16 +84,12 @@ impl DuskIdentity {
         })
     }
 
-    // persist identity to disk
+    // persist identity to disk. the keypair never changes once stored, so an
+    // existing (possibly sealed) copy is left alone
     pub fn save(&self, storage: &DiskStorage) -> Result<(), String> {
-        let keypair_bytes = self
-            .keypair
-            .to_protobuf_encoding()
-            .map_err(|e| format!("failed to encode keypair: {}", e))?;
-
-        storage
-            .save_keypair(&keypair_bytes)
-            .map_err(|e| format!("failed to save keypair: {}", e))?;
+        if !storage.has_identity() {
+            self.save_keypair(storage, None)?;
+        }
 
         let profile = ProfileData {
             display_name: self.display_name.clone(),
@@ -81,6 +103,23 @@ impl DuskIdentity {
         Ok(())
     }
 
+    // write the keypair, sealed under the passphrase when one is given
+    pub fn save_keypair(&self, storage: &DiskStorage, passphrase: Option<&str>) -> Result<(), String> {
+        let keypair_bytes = self
+            .keypair
+            .to_protobuf_encoding()
+            .map_err(|e| format!("failed to encode keypair: {}", e))?;
+
+        let stored = match passphrase {
+            Some(passphrase) => keystore::seal(passphrase, &keypair_bytes)?,
+            None => keypair_bytes,
+        };
+
+        storage
+            .save_keypair(&stored)
+            .map_err(|e| format!("failed to save keypair: {}", e))
+    }
+
     // public-facing identity info safe to share
     pub fn public_identity(&self) -> PublicIdentity {
         let public_key_bytes = self.keypair.public().encode_protobuf();
@@ -113,6 +152,12 @@ pub struct VerificationProof {
     pub signature: String,
     pub timestamp: u64,
     pub score: f64,
+    // set when the proof came from the proof-of-work fallback instead of the challenge
+    #[serde(default)]
+    pub pow: Option<crate::verification::pow::PowSolution>,
+    // input method of the passed challenge, none for proof-of-work and older proofs
+    #[serde(default)]
+    pub kind: Option<crate::verification::ChallengeKind>,
 }
 
 // profile data stored on disk alongside the keypair
@@ -143,4 +188,137 @@ pub struct DirectoryEntry {
     pub public_key: String,
     pub last_seen: u64,
     pub is_friend: bool,
+    // false when the peer announced without a verification proof, or when we
+    // only know a placeholder for them
+    #[serde(default)]
+    pub verified: bool,
+    // latest activity from the peer's presence, not part of signed announcements
+    #[serde(default)]
+    pub activity: Option<String>,
+    // custom status from the peer's latest announcement or presence update
+    #[serde(default)]
+    pub status_message: String,
+    #[serde(default)]
+    pub status_emoji: String,
+    // the user compared safety numbers with this peer for its current key
+    #[serde(default)]
+    pub key_verified: bool,
+    // only the peer id is known, from rendezvous discovery. replaced in place
+    // by the peer's first signed announcement
+    #[serde(default)]
+    pub placeholder: bool,
+}
+
+impl DirectoryEntry {
+    // directory entry for a signed profile announcement
+    pub fn from_announcement(profile: &ProfileAnnouncement, verified: bool) -> Self {
+        Self {
+            peer_id: profile.peer_id.clone(),
+            display_name: profile.display_name.clone(),
+            bio: profile.bio.clone(),
+            public_key: profile.public_key.clone(),
+            last_seen: profile.timestamp,
+            is_friend: false,
+            verified,
+            activity: None,
+            status_message: clean_status_text(&profile.status_message, MAX_STATUS_MESSAGE_LEN),
+            status_emoji: clean_status_text(&profile.status_emoji, MAX_STATUS_EMOJI_LEN),
+            key_verified: false,
+            placeholder: false,
+        }
+    }
+}
+
+// an announcement carrying a different key than the one pinned for the peer
+// id the first time we saw it. held back until the user accepts or dismisses it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyConflict {
+    pub peer_id: String,
+    pub display_name: String,
+    pub pinned_key: String,
+    pub offered_key: String,
+    pub offered_display_name: String,
+    // the pinned key was confirmed with safety numbers
+    pub key_verified: bool,
+    pub received_at: u64,
+}
+
+impl KeyConflict {
+    pub fn new(pinned: &DirectoryEntry, offered: &ProfileAnnouncement, received_at: u64) -> Self {
+        Self {
+            peer_id: pinned.peer_id.clone(),
+            display_name: pinned.display_name.clone(),
+            pinned_key: pinned.public_key.clone(),
+            offered_key: offered.public_key.clone(),
+            offered_display_name: offered.display_name.clone(),
+            key_verified: pinned.key_verified,
+            received_at,
+        }
+    }
+}
+
+// out-of-band identity exchange, shown as text or a qr code. the signer is
+// the card's subject for our own card, or whoever vouches for a contact they
+// pass on. the peer id is derived from the public key so the pair can't be forged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContactCard {
+    pub peer_id: String,
+    pub public_key: String,
+    pub display_name: String,
+    pub signer: String,
+    pub signer_key: String,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+impl ContactCard {
+    // encode the card as a base58 string, short enough for a qr code
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("failed to serialize contact card");
+        bs58::encode(json).into_string()
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, String> {
+        let bytes = bs58::decode(encoded.trim())
+            .into_vec()
+            .map_err(|e| format!("invalid contact card encoding: {}", e))?;
+
+        serde_json::from_slice(&bytes).map_err(|e| format!("invalid contact card format: {}", e))
+    }
+}
+
+// longest display name taken from an imported contact card
+pub const MAX_CONTACT_CARD_NAME_LEN: usize = 64;
+
+// how strictly unverified identities are treated, both as a personal setting
+// for incoming announcements and as a community's minimum for its members
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationPolicy {
+    // drop unverified peers entirely
+    #[default]
+    Require,
+    // accept unverified peers but flag them
+    Warn,
+    // accept unverified peers silently
+    Allow,
+}
+
+impl VerificationPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerificationPolicy::Require => "require",
+            VerificationPolicy::Warn => "warn",
+            VerificationPolicy::Allow => "allow",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "require" => Ok(VerificationPolicy::Require),
+            "warn" => Ok(VerificationPolicy::Warn),
+            "allow" => Ok(VerificationPolicy::Allow),
+            other => Err(format!("unknown verification policy: {}", other)),
+        }
+    }
 }
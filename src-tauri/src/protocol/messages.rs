@@ -1,7 +1,22 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use super::identity::VerificationProof;
 
+// bounds on custom status text shown to other peers
+pub const MAX_STATUS_MESSAGE_LEN: usize = 128;
+// an emoji with skin tone and zwj sequences spans several code points
+pub const MAX_STATUS_EMOJI_LEN: usize = 16;
+
+// trim, drop control characters and cap free text that peers show each other
+pub fn clean_status_text(text: &str, max_chars: usize) -> String {
+    text.trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(max_chars)
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub id: String,
@@ -11,6 +26,185 @@ pub struct ChatMessage {
     pub content: String,
     pub timestamp: u64,
     pub edited: bool,
+    // logical clock used for ordering, older peers omit it
+    #[serde(default)]
+    pub hlc: Hlc,
+    // set on announcements re-published from a followed channel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwarded_from: Option<MessageOrigin>,
+    // files and link previews carried alongside the text, older peers omit them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embeds: Vec<Embed>,
+    // read from the community doc, reactions travel as their own gossip
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<MessageReaction>,
+    // the message whose thread this one is a reply in, always the thread's
+    // first message so threads stay one level deep
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+}
+
+// a message and its replies, oldest reply first. root is none when the
+// message was deleted or hasn't arrived yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageThread {
+    pub root_id: String,
+    pub root: Option<ChatMessage>,
+    pub replies: Vec<ChatMessage>,
+}
+
+// what a channel shows under a message that has replies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadSummary {
+    pub root_id: String,
+    pub reply_count: usize,
+    pub last_reply_at: u64,
+    // everyone who replied, in the order they first did
+    pub participants: Vec<String>,
+}
+
+// everyone who reacted to a channel message with one emoji, in the order
+// they reacted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageReaction {
+    pub emoji: String,
+    pub peers: Vec<String>,
+}
+
+pub const MAX_REACTION_LEN: usize = 32;
+// distinct emojis on one message, further ones are refused
+pub const MAX_REACTIONS_PER_MESSAGE: usize = 20;
+
+// where a mirrored announcement was originally posted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageOrigin {
+    pub community_id: String,
+    pub community_name: String,
+    pub channel_id: String,
+    pub channel_name: String,
+    pub message_id: String,
+    pub author_id: String,
+    pub author_name: String,
+}
+
+pub const MAX_MESSAGE_ATTACHMENTS: usize = 10;
+pub const MAX_MESSAGE_EMBEDS: usize = 5;
+pub const MAX_ATTACHMENT_FILENAME_LEN: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentKind {
+    Image,
+    Video,
+    Audio,
+    File,
+}
+
+// metadata for a file shared in a message. the bytes travel separately and
+// are looked up by hash, so a message stays small enough to gossip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub kind: AttachmentKind,
+    // hex sha256 of the file contents
+    pub hash: String,
+    // bytes
+    pub size: u64,
+    pub filename: String,
+    // pixels, images and video only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+// link preview shown under a message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Embed {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // hash of a preview image shared as an attachment would be
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_hash: Option<String>,
+}
+
+fn is_sha256_hex(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// reject attachment and embed lists a well-behaved client wouldn't send
+pub fn validate_attachments(attachments: &[Attachment], embeds: &[Embed]) -> Result<(), String> {
+    if attachments.len() > MAX_MESSAGE_ATTACHMENTS {
+        return Err(format!(
+            "a message can have at most {} attachments",
+            MAX_MESSAGE_ATTACHMENTS
+        ));
+    }
+    if embeds.len() > MAX_MESSAGE_EMBEDS {
+        return Err(format!(
+            "a message can have at most {} embeds",
+            MAX_MESSAGE_EMBEDS
+        ));
+    }
+    attachments.iter().try_for_each(check_attachment)?;
+    embeds.iter().try_for_each(check_embed)
+}
+
+// drop what validate_attachments would refuse from a message a peer sent or
+// merged into a doc, keeping the rest of the message
+pub fn filter_attachments(attachments: &mut Vec<Attachment>, embeds: &mut Vec<Embed>) {
+    attachments.retain(|attachment| check_attachment(attachment).is_ok());
+    attachments.truncate(MAX_MESSAGE_ATTACHMENTS);
+    embeds.retain(|embed| check_embed(embed).is_ok());
+    embeds.truncate(MAX_MESSAGE_EMBEDS);
+}
+
+fn check_attachment(attachment: &Attachment) -> Result<(), String> {
+    if !is_sha256_hex(&attachment.hash) {
+        return Err("attachment hash must be a hex sha256".to_string());
+    }
+    if attachment.filename.trim().is_empty()
+        || attachment.filename.chars().count() > MAX_ATTACHMENT_FILENAME_LEN
+    {
+        return Err(format!(
+            "attachment filename must be 1 to {} characters",
+            MAX_ATTACHMENT_FILENAME_LEN
+        ));
+    }
+    Ok(())
+}
+
+fn check_embed(embed: &Embed) -> Result<(), String> {
+    if !embed.url.starts_with("https://") && !embed.url.starts_with("http://") {
+        return Err("embed url must be http or https".to_string());
+    }
+    if embed
+        .thumbnail_hash
+        .as_deref()
+        .is_some_and(|hash| !is_sha256_hex(hash))
+    {
+        return Err("embed thumbnail hash must be a hex sha256".to_string());
+    }
+    Ok(())
+}
+
+// content a message had before one of its edits, oldest revision first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageRevision {
+    pub revision: u32,
+    pub content: String,
+}
+
+// hybrid logical clock timestamp, compared by wall time then counter
+// kept as two fields so it survives json number precision on the frontend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Hlc {
+    pub wall: u64,
+    pub counter: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +220,14 @@ pub struct PresenceUpdate {
     pub display_name: String,
     pub status: PeerStatus,
     pub timestamp: u64,
+    // what the peer is doing right now, e.g. "Playing Factorio"
+    #[serde(default)]
+    pub activity: Option<String>,
+    // custom status, empty when unset
+    #[serde(default)]
+    pub status_message: String,
+    #[serde(default)]
+    pub status_emoji: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -48,6 +250,11 @@ pub struct ProfileAnnouncement {
     pub timestamp: u64,
     pub verification_proof: Option<VerificationProof>,
     pub signature: String,
+    // custom status, signed along with the rest when set
+    #[serde(default)]
+    pub status_message: String,
+    #[serde(default)]
+    pub status_emoji: String,
 }
 
 // broadcast when a user resets their identity, tells peers to purge their data
@@ -75,6 +282,57 @@ pub struct VoiceParticipant {
     pub peer_id: String,
     pub display_name: String,
     pub media_state: VoiceMediaState,
+    // always speaker outside stage channels
+    #[serde(default)]
+    pub stage_role: StageRole,
+    #[serde(default)]
+    pub hand_raised: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageRole {
+    #[default]
+    Speaker,
+    // receives the call's audio but may not send any
+    Listener,
+}
+
+// whether an sdp would send audio. an audio section without its own
+// direction follows the session one, which defaults to sendrecv, and a
+// section with port 0 is rejected so it carries nothing
+pub fn sdp_sends_audio(sdp: &str) -> bool {
+    let mut session_direction = "sendrecv";
+    // (is an accepted audio section, its own direction)
+    let mut section: Option<(bool, Option<&str>)> = None;
+    let mut sends = false;
+
+    for line in sdp.lines().map(str::trim) {
+        if let Some(media) = line.strip_prefix("m=") {
+            if let Some((true, direction)) = section {
+                sends |= sends_on(direction.unwrap_or(session_direction));
+            }
+            let mut fields = media.split_whitespace();
+            let audio = fields.next() == Some("audio") && fields.next() != Some("0");
+            section = Some((audio, None));
+        } else if let Some(attribute) = line.strip_prefix("a=") {
+            if !matches!(attribute, "sendrecv" | "sendonly" | "recvonly" | "inactive") {
+                continue;
+            }
+            match section.as_mut() {
+                Some((_, direction)) => *direction = Some(attribute),
+                None => session_direction = attribute,
+            }
+        }
+    }
+    if let Some((true, direction)) = section {
+        sends |= sends_on(direction.unwrap_or(session_direction));
+    }
+    sends
+}
+
+fn sends_on(direction: &str) -> bool {
+    matches!(direction, "sendrecv" | "sendonly")
 }
 
 // a direct message between two peers
@@ -86,6 +344,62 @@ pub struct DirectMessage {
     pub from_display_name: String,
     pub content: String,
     pub timestamp: u64,
+    // id of the message in the same conversation this one answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub edited: bool,
+    // kept per side from reaction signals, never sent with the message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<DMReaction>,
+}
+
+// everyone who reacted to a dm with one emoji, in the order they reacted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DMReaction {
+    pub emoji: String,
+    pub peers: Vec<String>,
+}
+
+pub const MAX_DM_REACTION_LEN: usize = 32;
+
+// new content for one of the sender's own dms, the receiving node checks
+// the gossip source against the stored author
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DMEdit {
+    pub message_id: String,
+    pub from_peer: String,
+    pub to_peer: String,
+    pub content: String,
+    pub timestamp: u64,
+}
+
+// a reaction added to or taken off a dm by the sender
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DMReactionSignal {
+    pub message_id: String,
+    pub from_peer: String,
+    pub to_peer: String,
+    pub emoji: String,
+    pub active: bool,
+    pub timestamp: u64,
+}
+
+// how long after sending a dm its author can still delete it for everyone
+pub const DM_DELETE_WINDOW_SECS: u64 = 48 * 60 * 60;
+
+// "delete for everyone" on a dm, signed so the recipient only removes
+// messages the requester actually wrote
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DMDeleteRequest {
+    pub message_id: String,
+    pub from_peer: String,
+    pub to_peer: String,
+    pub public_key: String,
+    pub timestamp: u64,
+    pub signature: String,
 }
 
 // typing indicator scoped to a dm conversation
@@ -96,6 +410,308 @@ pub struct DMTypingIndicator {
     pub timestamp: u64,
 }
 
+// signaling for a 1:1 call, media negotiation reuses the voice sdp/ice messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DMCallSignal {
+    pub call_id: String,
+    pub from_peer: String,
+    pub to_peer: String,
+    pub from_display_name: String,
+    pub kind: DMCallSignalKind,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DMCallSignalKind {
+    Invite,
+    Accept,
+    // callee turned down a ringing call
+    Decline,
+    // caller gave up before an answer, or the ring timed out
+    Cancel,
+    // callee is already in a call
+    Busy,
+    // either side hung up a connected call
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallDirection {
+    Incoming,
+    Outgoing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallStatus {
+    Ringing,
+    Answered,
+    Completed,
+    // incoming call that ended before we picked up
+    Missed,
+    // the callee turned it down
+    Declined,
+    // the caller hung up before it was answered
+    Cancelled,
+    // the callee was already in another call
+    Busy,
+    // our outgoing call rang out
+    NoAnswer,
+}
+
+impl CallDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallDirection::Incoming => "incoming",
+            CallDirection::Outgoing => "outgoing",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "outgoing" => CallDirection::Outgoing,
+            _ => CallDirection::Incoming,
+        }
+    }
+}
+
+impl CallStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallStatus::Ringing => "ringing",
+            CallStatus::Answered => "answered",
+            CallStatus::Completed => "completed",
+            CallStatus::Missed => "missed",
+            CallStatus::Declined => "declined",
+            CallStatus::Cancelled => "cancelled",
+            CallStatus::Busy => "busy",
+            CallStatus::NoAnswer => "no_answer",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "answered" => CallStatus::Answered,
+            "completed" => CallStatus::Completed,
+            "missed" => CallStatus::Missed,
+            "declined" => CallStatus::Declined,
+            "cancelled" => CallStatus::Cancelled,
+            "busy" => CallStatus::Busy,
+            "no_answer" => CallStatus::NoAnswer,
+            _ => CallStatus::Ringing,
+        }
+    }
+}
+
+// one entry in the local call history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallRecord {
+    pub call_id: String,
+    pub peer_id: String,
+    pub display_name: String,
+    pub direction: CallDirection,
+    pub status: CallStatus,
+    pub started_at: u64,
+    pub answered_at: Option<u64>,
+    pub ended_at: Option<u64>,
+    // seconds between answer and hang up, zero for unanswered calls
+    pub duration_secs: u64,
+}
+
+// bounds for the disappearing messages timer, counted from when a message was read
+pub const MIN_DM_EPHEMERAL_TTL_SECS: u64 = 60;
+pub const MAX_DM_EPHEMERAL_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+// disappearing messages for a dm conversation. the timer only runs once both
+// peers agreed on it, read receipts and deletion confirmations ride along
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DMEphemeralSignal {
+    pub from_peer: String,
+    pub to_peer: String,
+    pub kind: DMEphemeralKind,
+    pub timestamp: u64,
+    // policy steps are signed by from_peer and kept in both policy logs,
+    // read receipts and deletion confirmations go unsigned
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum DMEphemeralKind {
+    Propose { ttl_secs: u64 },
+    Accept { ttl_secs: u64 },
+    Decline,
+    // either side may turn disappearing messages off on its own
+    Disable,
+    // the sender has read everything we sent up to this timestamp
+    Read { up_to: u64 },
+    // the sender deleted its copies of these messages after they expired
+    Deleted { message_ids: Vec<String> },
+}
+
+impl DMEphemeralKind {
+    // the log action and timer for a policy step, none for receipts
+    pub fn policy_step(&self) -> Option<(&'static str, Option<u64>)> {
+        match self {
+            DMEphemeralKind::Propose { ttl_secs } => Some(("propose", Some(*ttl_secs))),
+            DMEphemeralKind::Accept { ttl_secs } => Some(("accept", Some(*ttl_secs))),
+            DMEphemeralKind::Decline => Some(("decline", None)),
+            DMEphemeralKind::Disable => Some(("disable", None)),
+            DMEphemeralKind::Read { .. } | DMEphemeralKind::Deleted { .. } => None,
+        }
+    }
+}
+
+// agreed and proposed disappearing message timers for a conversation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DMEphemeralPolicy {
+    pub peer_id: String,
+    // active timer in seconds, none while disappearing messages are off
+    pub ttl_secs: Option<u64>,
+    // proposal waiting for an answer and who made it
+    pub pending_ttl_secs: Option<u64>,
+    pub pending_from: Option<String>,
+    pub updated_at: u64,
+}
+
+impl DMEphemeralPolicy {
+    pub fn new(peer_id: &str) -> Self {
+        Self {
+            peer_id: peer_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // apply a negotiation step taken by `actor`, local or remote. returns the
+    // log action and timer when the policy changed, none for no-ops and steps
+    // that don't fit the current state. a proposal matching the other side's
+    // pending one counts as accepting it
+    pub fn apply(&mut self, actor: &str, kind: &DMEphemeralKind, now: u64) -> Option<(&'static str, Option<u64>)> {
+        let proposed_by_other = self.pending_from.as_deref().is_some_and(|from| from != actor);
+        let change = match kind {
+            DMEphemeralKind::Propose { ttl_secs } | DMEphemeralKind::Accept { ttl_secs }
+                if proposed_by_other && self.pending_ttl_secs == Some(*ttl_secs) =>
+            {
+                self.ttl_secs = Some(*ttl_secs);
+                self.pending_ttl_secs = None;
+                self.pending_from = None;
+                ("accept", Some(*ttl_secs))
+            }
+            DMEphemeralKind::Propose { ttl_secs } => {
+                if self.ttl_secs == Some(*ttl_secs) || self.pending_ttl_secs == Some(*ttl_secs) {
+                    return None;
+                }
+                self.pending_ttl_secs = Some(*ttl_secs);
+                self.pending_from = Some(actor.to_string());
+                ("propose", Some(*ttl_secs))
+            }
+            DMEphemeralKind::Decline if proposed_by_other => {
+                self.pending_ttl_secs = None;
+                self.pending_from = None;
+                ("decline", None)
+            }
+            DMEphemeralKind::Disable if self.ttl_secs.is_some() || self.pending_ttl_secs.is_some() => {
+                self.ttl_secs = None;
+                self.pending_ttl_secs = None;
+                self.pending_from = None;
+                ("disable", None)
+            }
+            _ => return None,
+        };
+        self.updated_at = now;
+        Some(change)
+    }
+}
+
+// one policy change in a conversation's hash-chained log. every entry commits
+// to the one before it, so an edited or dropped entry breaks the chain. the
+// actor's signature over the step shows who took it, entries from before
+// steps were signed have none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DMPolicyLogEntry {
+    pub seq: u64,
+    pub actor: String,
+    pub action: String,
+    pub ttl_secs: Option<u64>,
+    pub timestamp: u64,
+    pub prev_hash: String,
+    pub hash: String,
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl DMPolicyLogEntry {
+    pub fn compute_hash(
+        prev_hash: &str,
+        seq: u64,
+        actor: &str,
+        action: &str,
+        ttl_secs: Option<u64>,
+        timestamp: u64,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(prev_hash.as_bytes());
+        hasher.update(
+            format!(
+                "|{}|{}|{}|{}|{}",
+                seq,
+                actor,
+                action,
+                ttl_secs.map(|t| t.to_string()).unwrap_or_default(),
+                timestamp
+            )
+            .as_bytes(),
+        );
+        hex::encode(hasher.finalize())
+    }
+}
+
+// a conversation's policy log, whether its hash chain still checks out and
+// whether every entry carries its actor's signature
+#[derive(Debug, Clone, Serialize)]
+pub struct DMPolicyLog {
+    pub entries: Vec<DMPolicyLogEntry>,
+    pub intact: bool,
+    pub signed: bool,
+}
+
+impl DMPolicyLog {
+    pub fn new(conversation_id: &str, entries: Vec<DMPolicyLogEntry>) -> Self {
+        let mut prev_hash = String::new();
+        let mut intact = true;
+        for (i, entry) in entries.iter().enumerate() {
+            let expected = DMPolicyLogEntry::compute_hash(
+                &prev_hash,
+                entry.seq,
+                &entry.actor,
+                &entry.action,
+                entry.ttl_secs,
+                entry.timestamp,
+            );
+            if entry.seq != i as u64 || entry.prev_hash != prev_hash || entry.hash != expected {
+                intact = false;
+                break;
+            }
+            prev_hash = entry.hash.clone();
+        }
+        let signed = entries
+            .iter()
+            .all(|entry| crate::verification::verify_dm_policy_entry(conversation_id, entry));
+        Self {
+            entries,
+            intact,
+            signed,
+        }
+    }
+}
+
 // metadata for a persisted dm conversation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DMConversationMeta {
@@ -104,6 +720,22 @@ pub struct DMConversationMeta {
     pub last_message: Option<String>,
     pub last_message_time: Option<u64>,
     pub unread_count: u32,
+    // pinned conversations sort above the rest of the sidebar
+    #[serde(default)]
+    pub pinned: bool,
+    // archived conversations are hidden from the sidebar until a new message arrives
+    #[serde(default)]
+    pub archived: bool,
+}
+
+// one page of the dm sidebar with unread totals for the badge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DMConversationPage {
+    pub conversations: Vec<DMConversationMeta>,
+    // pass back to get the next page, none on the last one
+    pub next_cursor: Option<String>,
+    pub total_unread: u32,
+    pub unread_conversations: u32,
 }
 
 // envelope for all gossipsub-published messages
@@ -117,16 +749,34 @@ pub enum GossipMessage {
         message_id: String,
         new_content: String,
     },
+    // moderators other than the owner send the token they act under,
+    // receivers drop the action if neither it nor authorship backs it up
     DeleteMessage {
         message_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        capability: Option<super::community::CapabilityToken>,
+    },
+    // the gossip source added or took back a reaction to a channel message
+    Reaction {
+        message_id: String,
+        emoji: String,
+        active: bool,
     },
     MemberKicked {
         peer_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        capability: Option<super::community::CapabilityToken>,
     },
     ProfileAnnounce(ProfileAnnouncement),
     ProfileRevoke(ProfileRevocation),
     DirectMessage(DirectMessage),
     DMTyping(DMTypingIndicator),
+    DMCall(DMCallSignal),
+    DMEphemeral(DMEphemeralSignal),
+    DMDelete(DMDeleteRequest),
+    DMEdit(DMEdit),
+    DMReaction(DMReactionSignal),
+    CommunityProfile(super::community::CommunityProfile),
     VoiceJoin {
         community_id: String,
         channel_id: String,
@@ -139,6 +789,42 @@ pub enum GossipMessage {
         channel_id: String,
         peer_id: String,
     },
+    // a stage listener asking to speak, or taking the request back
+    StageHandRaise {
+        community_id: String,
+        channel_id: String,
+        peer_id: String,
+        raised: bool,
+    },
+    // a moderator made peer_id a speaker or a listener. speakers may also
+    // step down on their own, without a capability
+    StageRoleChange {
+        community_id: String,
+        channel_id: String,
+        peer_id: String,
+        role: StageRole,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        capability: Option<super::community::CapabilityToken>,
+    },
+    // the channel was at capacity when peer_id joined. only the participant
+    // with the lowest peer id sends it, the joiner leaves on receipt
+    VoiceJoinRejected {
+        community_id: String,
+        channel_id: String,
+        peer_id: String,
+        max_participants: u32,
+    },
+    // a moderator took peer_id out of the call, into another voice channel
+    // when move_to is set. non-admins send their move capability along
+    VoiceDisconnect {
+        community_id: String,
+        channel_id: String,
+        peer_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        move_to: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        capability: Option<super::community::CapabilityToken>,
+    },
     VoiceParticipantsRequest {
         community_id: String,
         channel_id: String,
@@ -149,6 +835,13 @@ pub enum GossipMessage {
         peer_id: String,
         media_state: VoiceMediaState,
     },
+    // sent only when a participant starts or stops talking
+    VoiceSpeaking {
+        community_id: String,
+        channel_id: String,
+        peer_id: String,
+        speaking: bool,
+    },
     VoiceSdp {
         community_id: String,
         channel_id: String,
@@ -166,4 +859,30 @@ pub enum GossipMessage {
         sdp_mid: Option<String>,
         sdp_mline_index: Option<u32>,
     },
+    // automerge changes to a note, as produced by save_after. the first one
+    // for a note carries its whole history
+    NoteChanges {
+        community_id: String,
+        note_id: String,
+        changes: Vec<u8>,
+    },
+    // we hold changes to a note whose history we lack, members holding the
+    // note answer with what follows our heads
+    NoteSyncRequest {
+        community_id: String,
+        note_id: String,
+        heads: Vec<String>,
+    },
+    // a member got one of author_id's channel messages, only some members
+    // send these, see latency::should_send_receipt
+    ChatReceipt {
+        message_id: String,
+        author_id: String,
+    },
+    // dummy traffic from peers in cover traffic mode, dropped on receipt.
+    // message ids hash the payload, the random nonce keeps each one from
+    // being dropped as a duplicate of the last
+    Cover {
+        nonce: u64,
+    },
 }
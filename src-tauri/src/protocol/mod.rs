@@ -1,6 +1,12 @@
+pub mod catchup;
 pub mod community;
 pub mod directory;
 pub mod gif;
+pub mod history;
 pub mod identity;
 pub mod messages;
+pub mod notes;
+pub mod presence;
+pub mod snapshot;
 pub mod turn;
+pub mod voice;
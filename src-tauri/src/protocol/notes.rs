@@ -0,0 +1,101 @@
+// collaborative notes, one automerge doc per note kept beside the community
+// doc. edits travel as incremental automerge changes on the community's notes
+// topic, so a keystroke costs a few bytes instead of a full snapshot.
+// text positions count unicode code points, automerge's default encoding
+
+use serde::{Deserialize, Serialize};
+
+pub const MAX_NOTE_TITLE_CHARS: usize = 200;
+pub const MAX_NOTE_CHARS: usize = 100_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub id: String,
+    pub community_id: String,
+    pub title: String,
+    pub text: String,
+    pub marks: Vec<NoteMark>,
+    pub created_by: String,
+    pub created_at: u64,
+    pub updated_by: String,
+    pub updated_at: u64,
+}
+
+// formatting over [start, end) of the text, e.g. bold = true or link = url
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteMark {
+    pub start: usize,
+    pub end: usize,
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+// one step of a local edit, applied in order and sent as a single change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum NoteEdit {
+    // remove delete code points at index, then insert there
+    Splice {
+        index: usize,
+        delete: usize,
+        insert: String,
+    },
+    Mark {
+        start: usize,
+        end: usize,
+        name: String,
+        value: serde_json::Value,
+    },
+    Unmark {
+        start: usize,
+        end: usize,
+        name: String,
+    },
+    SetTitle {
+        title: String,
+    },
+}
+
+// one change in a note's history. author and time are what the editing
+// client claimed, nothing signs them, so a peer can put any name there.
+// changes from before they were recorded carry neither
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteRevision {
+    pub hash: String,
+    pub claimed_author: String,
+    pub timestamp: u64,
+}
+
+// a wiki page is a note pinned to the community: its body is a note doc,
+// the community doc lists it along with the role needed to edit it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageMeta {
+    pub id: String,
+    pub community_id: String,
+    pub title: String,
+    pub edit_role: PageEditRole,
+    pub position: u32,
+    pub created_by: String,
+    pub created_at: u64,
+    pub updated_by: String,
+    pub updated_at: u64,
+}
+
+// an owner or admin's signature over a page's entry in the community doc,
+// see verification::sign_page_ref. a merge keeps a changed entry only when
+// it carries one over the entry as it now reads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageRefSignature {
+    pub signer: String,
+    // hex encoded protobuf public key of the signer
+    pub public_key: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageEditRole {
+    #[default]
+    Member,
+    Admin,
+}
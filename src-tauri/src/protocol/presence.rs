@@ -0,0 +1,76 @@
+// relay presence protocol types, opt-in online status for friends who don't
+// share a community topic. each heartbeat refreshes our own entry on the
+// relay and asks which of our friends are online. friends are named by a
+// token only the two of them can compute, so the relay can't look up anyone
+// from a peer id. it does see which connected peers send the same token,
+// so it learns which of the online users are friends
+
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use hmac::{Hmac, Mac};
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::{PeerId, StreamProtocol};
+use sha2::{Digest, Sha256, Sha512};
+
+// 2.0.0 replaced hashes of peer ids with pairwise tokens
+pub const PRESENCE_PROTOCOL: StreamProtocol = StreamProtocol::new("/dusk/presence/2.0.0");
+
+// seconds between heartbeats, the relay drops entries it hasn't heard from
+// in a few intervals
+pub const PRESENCE_HEARTBEAT_SECS: u64 = 60;
+
+// friends asked about per heartbeat, the rest are left out
+pub const MAX_PRESENCE_FRIENDS: usize = 512;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum PresenceRequest {
+    Heartbeat {
+        // presence_token shared with every friend we want the status of.
+        // while visible the relay reports us online to whoever sends one too
+        friend_tokens: Vec<String>,
+        // false while show_online_status is off, we still learn about our
+        // friends but are reported offline to theirs
+        visible: bool,
+    },
+    // drop our entry right away instead of waiting for it to expire
+    Leave,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum PresenceResponse {
+    // the requested tokens another online and visible peer also sent
+    Online(Vec<String>),
+    Ok,
+    Error(String),
+}
+
+// what a friendship is known by on the relay: an hmac over both peer ids,
+// keyed with the x25519 secret the two ed25519 identity keys agree on. none
+// when either key isn't ed25519 or the friend's can't be read from its id
+pub fn presence_token(keypair: &Keypair, friend_peer_id: &str) -> Option<String> {
+    let local = keypair.clone().try_into_ed25519().ok()?;
+    let friend_id: PeerId = friend_peer_id.parse().ok()?;
+    // ed25519 peer ids inline the public key
+    let friend_key = PublicKey::try_decode_protobuf(friend_id.as_ref().digest())
+        .ok()?
+        .try_into_ed25519()
+        .ok()?;
+
+    // the x25519 forms of the two keys: the scalar ed25519 derives from the
+    // seed, and the friend's point mapped to montgomery form
+    let mut scalar = [0u8; 32];
+    scalar.copy_from_slice(&Sha512::digest(local.secret().as_ref())[..32]);
+    let shared = CompressedEdwardsY(friend_key.to_bytes())
+        .decompress()?
+        .to_montgomery()
+        .mul_clamped(scalar);
+
+    let local_id = keypair.public().to_peer_id().to_string();
+    let (first, second) = if local_id.as_str() < friend_peer_id {
+        (local_id.as_str(), friend_peer_id)
+    } else {
+        (friend_peer_id, local_id.as_str())
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(shared.as_bytes()).ok()?;
+    mac.update(format!("dusk-presence||{}||{}", first, second).as_bytes());
+    Some(hex::encode(mac.finalize().into_bytes()))
+}
@@ -0,0 +1,17 @@
+// document snapshot protocol, sent directly from one community member to
+// another. a snapshot carries the community's whole history, so it only
+// goes to a peer that proved membership, never over the public sync topic.
+
+use libp2p::StreamProtocol;
+
+use crate::crdt::sync::DocumentSnapshot;
+
+pub const SNAPSHOT_PROTOCOL: StreamProtocol = StreamProtocol::new("/dusk/snapshot/1.0.0");
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum SnapshotResponse {
+    // the receiver's doc after merging ours, so both sides converge. none
+    // when the sender isn't in the receiver's member list
+    Merged(Option<DocumentSnapshot>),
+    Error(String),
+}
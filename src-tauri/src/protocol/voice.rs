@@ -0,0 +1,22 @@
+// voice roster protocol types exchanged directly between community members.
+// a peer joining or looking at a voice channel asks someone already in the
+// call who is there, instead of waiting for join announcements to trickle in.
+
+use libp2p::StreamProtocol;
+
+use super::messages::VoiceParticipant;
+
+pub const VOICE_ROSTER_PROTOCOL: StreamProtocol = StreamProtocol::new("/dusk/voice-roster/1.0.0");
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct VoiceRosterRequest {
+    pub community_id: String,
+    pub channel_id: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum VoiceRosterResponse {
+    // everyone the answering participant sees in the call, itself included
+    Participants(Vec<VoiceParticipant>),
+    Error(String),
+}
@@ -0,0 +1,58 @@
+use qrcode::{Color, EcLevel, QrCode};
+
+// pixels per qr module, large enough to scan from a screen or a printout
+const MODULE_PX: usize = 8;
+// blank modules around the code, scanners need at least four
+const QUIET_ZONE: usize = 4;
+// invites and contact cards are a few hundred characters, anything much
+// longer would make a code too dense to scan reliably
+pub const MAX_QR_DATA_LEN: usize = 2048;
+
+// encode text as a black on white grayscale png
+pub fn render_png(data: &str) -> Result<Vec<u8>, String> {
+    if data.is_empty() {
+        return Err("nothing to encode".to_string());
+    }
+    if data.len() > MAX_QR_DATA_LEN {
+        return Err(format!(
+            "qr data is too long ({} bytes, max {})",
+            data.len(),
+            MAX_QR_DATA_LEN
+        ));
+    }
+
+    let code = QrCode::with_error_correction_level(data.as_bytes(), EcLevel::M)
+        .map_err(|e| format!("failed to encode qr code: {}", e))?;
+    let modules = code.width();
+    let colors = code.to_colors();
+
+    let side = (modules + QUIET_ZONE * 2) * MODULE_PX;
+    let mut pixels = vec![0xffu8; side * side];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let x0 = (i % modules + QUIET_ZONE) * MODULE_PX;
+        let y0 = (i / modules + QUIET_ZONE) * MODULE_PX;
+        for y in y0..y0 + MODULE_PX {
+            let row = y * side + x0;
+            pixels[row..row + MODULE_PX].fill(0);
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, side as u32, side as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| format!("failed to write png: {}", e))?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|e| format!("failed to write png: {}", e))?;
+    writer
+        .finish()
+        .map_err(|e| format!("failed to write png: {}", e))?;
+
+    Ok(out)
+}
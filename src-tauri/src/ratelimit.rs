@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// overrides the default budgets, e.g. "send_message=60/10,send_dm=0/1".
+// a budget of 0 disables limiting for that command. read once at first use
+// so a webview can't raise its own limits
+const COMMAND_RATE_LIMITS_ENV: &str = "DUSK_COMMAND_RATE_LIMITS";
+
+// commands that publish to the network or write to disk on every call.
+// (name, calls, per seconds), generous enough for fast typing and pasting
+const DEFAULT_BUDGETS: &[(&str, u32, u64)] = &[
+    ("send_message", 30, 10),
+    ("send_message_optimistic", 30, 10),
+    ("send_dm", 30, 10),
+    ("send_typing", 20, 10),
+    ("send_dm_typing", 20, 10),
+    ("delete_dm_for_everyone", 30, 10),
+    ("edit_dm", 30, 10),
+    ("react_dm", 30, 10),
+    ("add_reaction", 30, 10),
+    ("remove_reaction", 30, 10),
+    ("create_community", 5, 60),
+    ("create_channel", 20, 60),
+    ("create_category", 20, 60),
+    ("create_note", 20, 60),
+    ("apply_note_changes", 60, 10),
+    ("create_page", 20, 60),
+    ("edit_page", 60, 10),
+    ("create_task_list", 20, 60),
+    ("create_task_card", 30, 60),
+    ("announce_profile_now", 5, 60),
+];
+
+#[derive(Debug, Clone, Copy)]
+struct Budget {
+    calls: u32,
+    per: Duration,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+// token buckets per command name, shared by every ipc call. a command
+// without a budget is never limited
+struct CommandRateLimiter {
+    budgets: HashMap<String, Budget>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+static LIMITER: OnceLock<CommandRateLimiter> = OnceLock::new();
+
+// called by ipc_log! before a command body runs
+pub fn check(command: &str) -> Result<(), String> {
+    LIMITER
+        .get_or_init(CommandRateLimiter::from_env)
+        .check(command)
+}
+
+impl CommandRateLimiter {
+    fn from_env() -> Self {
+        let mut budgets: HashMap<String, Budget> = DEFAULT_BUDGETS
+            .iter()
+            .map(|(name, calls, secs)| {
+                (
+                    name.to_string(),
+                    Budget {
+                        calls: *calls,
+                        per: Duration::from_secs(*secs),
+                    },
+                )
+            })
+            .collect();
+
+        if let Ok(raw) = std::env::var(COMMAND_RATE_LIMITS_ENV) {
+            for entry in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                match parse_budget(entry) {
+                    Some((name, budget)) => {
+                        budgets.insert(name, budget);
+                    }
+                    None => log::warn!(
+                        "ignoring invalid rate limit in {}: {}",
+                        COMMAND_RATE_LIMITS_ENV,
+                        entry
+                    ),
+                }
+            }
+        }
+        budgets.retain(|_, budget| budget.calls > 0);
+
+        Self {
+            budgets,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn check(&self, command: &str) -> Result<(), String> {
+        let Some(budget) = self.budgets.get(command) else {
+            return Ok(());
+        };
+        let capacity = budget.calls as f64;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(command.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens =
+            (bucket.tokens + elapsed * capacity / budget.per.as_secs_f64()).min(capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens < 1.0 {
+            return Err(format!(
+                "rate limited: {} allows {} calls per {}s",
+                command,
+                budget.calls,
+                budget.per.as_secs()
+            ));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+// "name=calls/secs"
+fn parse_budget(entry: &str) -> Option<(String, Budget)> {
+    let (name, limit) = entry.split_once('=')?;
+    let (calls, secs) = limit.split_once('/')?;
+    let calls = calls.trim().parse().ok()?;
+    let secs: u64 = secs.trim().parse().ok()?;
+    if name.trim().is_empty() || secs == 0 {
+        return None;
+    }
+    Some((
+        name.trim().to_string(),
+        Budget {
+            calls,
+            per: Duration::from_secs(secs),
+        },
+    ))
+}
@@ -0,0 +1,62 @@
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::crdt::CrdtEngine;
+use crate::node::NodeHandle;
+use crate::protocol::identity::{DuskIdentity, PublicIdentity};
+
+// a consistent read of the shared state. handlers that lock identity, engine
+// and node one after another can see a community created by an identity
+// that was reset in between, a snapshot holds all three locks at once
+#[derive(Debug, Clone, Serialize)]
+pub struct StateSnapshot {
+    // unix millis
+    pub captured_at: u64,
+    pub identity: Option<PublicIdentity>,
+    pub documents: Vec<DocumentHeads>,
+    pub node_running: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentHeads {
+    pub community_id: String,
+    // hex change hashes, sorted
+    pub heads: Vec<String>,
+    pub archived: bool,
+}
+
+// locks are always taken identity, then crdt engine, then node handle. code
+// that needs more than one of them at a time should keep this order
+pub async fn capture(
+    identity: &Mutex<Option<DuskIdentity>>,
+    crdt_engine: &Mutex<CrdtEngine>,
+    node_handle: &Mutex<Option<NodeHandle>>,
+) -> StateSnapshot {
+    let identity = identity.lock().await;
+    let mut engine = crdt_engine.lock().await;
+    let node_handle = node_handle.lock().await;
+
+    let mut documents: Vec<DocumentHeads> = engine
+        .doc_heads()
+        .into_iter()
+        .map(|(community_id, mut heads)| {
+            heads.sort();
+            DocumentHeads {
+                archived: engine.is_archived(&community_id),
+                community_id,
+                heads,
+            }
+        })
+        .collect();
+    documents.sort_by(|a, b| a.community_id.cmp(&b.community_id));
+
+    StateSnapshot {
+        captured_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64,
+        identity: identity.as_ref().map(|id| id.public_identity()),
+        documents,
+        node_running: node_handle.is_some(),
+    }
+}
@@ -1,16 +1,35 @@
 use directories::ProjectDirs;
+use rusqlite::functions::FunctionFlags;
 use rusqlite::types::Value as SqlValue;
 use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
 use std::time::Duration;
 
-use crate::protocol::community::CommunityMeta;
-use crate::protocol::identity::{DirectoryEntry, ProfileData, VerificationProof};
-use crate::protocol::messages::{DMConversationMeta, DirectMessage};
+use crate::locale::LocaleSettings;
+use crate::logging::LogConfig;
+use crate::node::announce::AnnouncePolicy;
+use crate::node::power::NetworkProfile;
+use crate::protocol::community::{
+    ChannelFollow, CommunityFolder, CommunityLayout, CommunityMeta, CommunityPlacement,
+    CommunityProfile,
+};
+use crate::protocol::identity::{
+    DirectoryEntry, KeyConflict, ProfileData, VerificationPolicy, VerificationProof,
+};
+use crate::protocol::messages::{
+    clean_status_text, Attachment, AttachmentKind, CallDirection, CallRecord, CallStatus,
+    DMConversationMeta, DMConversationPage, DMEphemeralPolicy, DMEphemeralSignal, DMPolicyLogEntry,
+    DMReaction, DirectMessage, ProfileAnnouncement, MAX_STATUS_EMOJI_LEN, MAX_STATUS_MESSAGE_LEN,
+};
+use crate::updater::UpdateChannel;
+
+use super::keystore::{self, Key};
 
 // user settings that persist across sessions
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,12 +48,213 @@ pub struct UserSettings {
     pub custom_relay_addr: Option<String>,
     #[serde(default = "default_true")]
     pub relay_discoverable: bool,
+    // how announcements from peers without a verification proof are handled
+    #[serde(default)]
+    pub verification_policy: VerificationPolicy,
+    // leading zero bits required by the proof-of-work verification fallback
+    #[serde(default = "default_pow_difficulty")]
+    pub pow_difficulty: u32,
+    // battery and metered-connection aware networking
+    #[serde(default)]
+    pub network_profile: NetworkProfile,
+    // release channel followed by the auto-updater
+    #[serde(default)]
+    pub update_channel: UpdateChannel,
+    // where crash reports are sent, nothing is uploaded while unset
+    #[serde(default)]
+    pub crash_report_endpoint: Option<String>,
+    // lan discovery, portable users on shared networks may want it off
+    #[serde(default = "default_true")]
+    pub mdns_enabled: bool,
+    // advertise confirmed public addresses and dial peers directly before
+    // going through the relay. peers we connect to learn our ip
+    #[serde(default)]
+    pub direct_connections: bool,
+    // most peer connections kept at once. strangers are dropped first, then
+    // community members, friends and the relay never are
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    // follow only channels opened in the last week plus each community's
+    // mentions topic, for large communities. applies from the next node start
+    #[serde(default)]
+    pub interest_subscriptions: bool,
+    // rust-side microphone processing before webrtc encoding
+    #[serde(default)]
+    pub noise_suppression: bool,
+    #[serde(default)]
+    pub auto_gain_control: bool,
+    // shown next to the custom status message
+    #[serde(default)]
+    pub status_emoji: String,
+    // pad gossip payloads and send dummy dm traffic, costs bandwidth
+    #[serde(default)]
+    pub cover_traffic: bool,
+    // how dm search splits text into terms, changing it rebuilds the index
+    #[serde(default)]
+    pub search_tokenizer: SearchTokenizer,
+    // keep a local log of members leaving so owners get churn numbers
+    #[serde(default)]
+    pub community_analytics: bool,
+    // keep a bounded local log of inbound gossip and node state changes so
+    // a dev build can replay them, applies from the next node start
+    #[serde(default)]
+    pub record_events: bool,
+    // heartbeat to the relay so friends outside our communities see us
+    // online and we see them. the relay learns when we are online
+    #[serde(default)]
+    pub relay_presence: bool,
+    // "system", "dark" or "light"
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    // settings profile the ui-facing settings were last switched to, saving
+    // settings keeps that profile up to date
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    // directory entries for strangers not seen in this many days are pruned
+    // at startup, 0 keeps them forever
+    #[serde(default = "default_directory_ttl_days")]
+    pub directory_ttl_days: u32,
+    // announce our profile on the global directory topic. off, only the
+    // communities we stay visible to learn it from their own directory topic
+    #[serde(default = "default_true")]
+    pub announce_globally: bool,
+    // which events announce our profile, how often, or only on request
+    #[serde(default)]
+    pub announce_policy: AnnouncePolicy,
+    // log levels per module and whether logs are also written to files
+    #[serde(default)]
+    pub logging: LogConfig,
+    // locale and time zone for dates in exports and reports, overrides and
+    // what the ui last reported for the system
+    #[serde(default)]
+    pub locale: LocaleSettings,
+}
+
+pub const MAX_SETTINGS_PROFILES: usize = 20;
+pub const MAX_SETTINGS_PROFILE_NAME_LEN: usize = 64;
+
+// a named set of the ui-facing settings, e.g. "work" and "gaming". switching
+// profiles swaps these and leaves identity, network and privacy settings alone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsProfile {
+    pub name: String,
+    pub theme: String,
+    pub font_size: String,
+    pub message_display: String,
+    pub enable_sounds: bool,
+    pub enable_desktop_notifications: bool,
+    pub enable_message_preview: bool,
+    pub updated_at: u64,
+}
+
+impl SettingsProfile {
+    pub fn capture(name: &str, settings: &UserSettings, now: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            theme: settings.theme.clone(),
+            font_size: settings.font_size.clone(),
+            message_display: settings.message_display.clone(),
+            enable_sounds: settings.enable_sounds,
+            enable_desktop_notifications: settings.enable_desktop_notifications,
+            enable_message_preview: settings.enable_message_preview,
+            updated_at: now,
+        }
+    }
+
+    pub fn apply_to(&self, settings: &mut UserSettings) {
+        settings.theme = self.theme.clone();
+        settings.font_size = self.font_size.clone();
+        settings.message_display = self.message_display.clone();
+        settings.enable_sounds = self.enable_sounds;
+        settings.enable_desktop_notifications = self.enable_desktop_notifications;
+        settings.enable_message_preview = self.enable_message_preview;
+        settings.active_profile = Some(self.name.clone());
+    }
+}
+
+// rows kept in the replay event log
+const MAX_RECORDED_EVENTS: i64 = 20_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordedEventKind {
+    // an inbound gossip message as it reached the handlers
+    Gossip,
+    // a node state change such as a subscription or relay reservation
+    State,
+}
+
+impl RecordedEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RecordedEventKind::Gossip => "gossip",
+            RecordedEventKind::State => "state",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "gossip" => Some(RecordedEventKind::Gossip),
+            "state" => Some(RecordedEventKind::State),
+            _ => None,
+        }
+    }
+}
+
+// one row of the replay event log
+#[derive(Debug, Clone)]
+pub struct RecordedEvent {
+    pub id: i64,
+    pub recorded_at: u64,
+    pub kind: RecordedEventKind,
+    // gossip topic, or the name of the state change
+    pub topic: String,
+    // signed author of a gossip message, or details of the state change
+    pub source: String,
+    // raw gossip payload, empty for state changes
+    pub data: Vec<u8>,
+}
+
+// tokenizer behind the dm full-text search index
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchTokenizer {
+    // whole words and word prefixes, case and diacritics folded
+    #[default]
+    Unicode61,
+    // any substring of three or more characters, for scripts written without
+    // spaces such as chinese or japanese. the index is several times larger
+    Trigram,
+}
+
+impl SearchTokenizer {
+    fn fts_spec(&self) -> &'static str {
+        match self {
+            SearchTokenizer::Unicode61 => "unicode61 remove_diacritics 2",
+            SearchTokenizer::Trigram => "trigram remove_diacritics 1",
+        }
+    }
+}
+
+fn default_pow_difficulty() -> u32 {
+    crate::verification::pow::DEFAULT_POW_DIFFICULTY
+}
+
+fn default_max_connections() -> usize {
+    crate::node::connections::DEFAULT_MAX_CONNECTIONS
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn default_theme() -> String {
+    "system".to_string()
+}
+
+fn default_directory_ttl_days() -> u32 {
+    30
+}
+
 impl Default for UserSettings {
     fn default() -> Self {
         Self {
@@ -50,7 +270,44 @@ impl Default for UserSettings {
             custom_relay_addr: None,
             font_size: "default".to_string(),
             relay_discoverable: true,
+            verification_policy: VerificationPolicy::default(),
+            pow_difficulty: default_pow_difficulty(),
+            network_profile: NetworkProfile::default(),
+            update_channel: UpdateChannel::default(),
+            crash_report_endpoint: None,
+            mdns_enabled: true,
+            direct_connections: false,
+            max_connections: default_max_connections(),
+            interest_subscriptions: false,
+            noise_suppression: false,
+            auto_gain_control: false,
+            status_emoji: String::new(),
+            cover_traffic: false,
+            search_tokenizer: SearchTokenizer::default(),
+            community_analytics: false,
+            record_events: false,
+            relay_presence: false,
+            theme: default_theme(),
+            active_profile: None,
+            directory_ttl_days: default_directory_ttl_days(),
+            announce_globally: true,
+            announce_policy: AnnouncePolicy::default(),
+            logging: LogConfig::default(),
+            locale: LocaleSettings::default(),
+        }
+    }
+}
+
+impl UserSettings {
+    // custom status text as shared with peers, invisible users share nothing
+    pub fn shared_status(&self) -> (String, String) {
+        if self.status == "invisible" {
+            return (String::new(), String::new());
         }
+        (
+            clean_status_text(&self.status_message, MAX_STATUS_MESSAGE_LEN),
+            clean_status_text(&self.status_emoji, MAX_STATUS_EMOJI_LEN),
+        )
     }
 }
 
@@ -79,28 +336,171 @@ impl Default for DmSearchParams {
     }
 }
 
+// overrides for where user data lives, the flag wins over the env var and
+// both win over a location picked in the app
+pub const DATA_DIR_ENV: &str = "DUSK_DATA_DIR";
+const DATA_DIR_ARG: &str = "--data-dir";
+// portable installs keep everything in this folder next to the executable
+pub const PORTABLE_ENV: &str = "DUSK_PORTABLE";
+const PORTABLE_ARG: &str = "--portable";
+const PORTABLE_MARKER: &str = "dusk.portable";
+const PORTABLE_DATA_DIR: &str = "dusk-data";
+// file in the os config dir holding the path chosen with set_data_directory
+const DATA_DIR_POINTER: &str = "data_dir";
+const DB_FILE: &str = "storage.sqlite3";
+// addresses remembered per peer in the address book
+const MAX_ADDRESSES_PER_PEER: usize = 4;
+
+// explicit data directory from `--data-dir <path>` or `--data-dir=<path>`
+fn data_dir_from_args() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_ARG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(value) = arg.strip_prefix(DATA_DIR_ARG).and_then(|v| v.strip_prefix('=')) {
+            return Some(PathBuf::from(value));
+        }
+    }
+    None
+}
+
+fn data_dir_from_env() -> Option<PathBuf> {
+    std::env::var(DATA_DIR_ENV)
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+fn executable_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+}
+
+// run from removable media: enabled by --portable, DUSK_PORTABLE=1, or a
+// dusk.portable file placed next to the executable
+pub fn portable_mode() -> bool {
+    if std::env::args().skip(1).any(|arg| arg == PORTABLE_ARG) {
+        return true;
+    }
+    if let Ok(value) = std::env::var(PORTABLE_ENV) {
+        return value == "1" || value.eq_ignore_ascii_case("true");
+    }
+    executable_dir()
+        .map(|dir| dir.join(PORTABLE_MARKER).exists())
+        .unwrap_or(false)
+}
+
+// true when the location is pinned by the command line, environment or portable mode
+pub fn data_dir_overridden() -> bool {
+    data_dir_from_args().is_some() || data_dir_from_env().is_some() || portable_mode()
+}
+
+fn project_dirs() -> Result<ProjectDirs, io::Error> {
+    ProjectDirs::from("app", "duskchat", "dusk")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no valid home directory"))
+}
+
+fn resolve_base_dir() -> Result<PathBuf, io::Error> {
+    if let Some(dir) = data_dir_from_args().or_else(data_dir_from_env) {
+        return Ok(dir);
+    }
+
+    // never fall back to the host's profile directories when portable
+    if portable_mode() {
+        return executable_dir()
+            .map(|dir| dir.join(PORTABLE_DATA_DIR))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no executable directory"));
+    }
+
+    let project_dirs = project_dirs()?;
+    let pointer = project_dirs.config_dir().join(DATA_DIR_POINTER);
+    if let Ok(chosen) = fs::read_to_string(pointer) {
+        let chosen = chosen.trim();
+        if !chosen.is_empty() {
+            return Ok(PathBuf::from(chosen));
+        }
+    }
+
+    Ok(project_dirs.data_dir().to_path_buf())
+}
+
 // sqlite-based persistence for identity, documents, and direct messages
 pub struct DiskStorage {
     base_dir: PathBuf,
     db_path: PathBuf,
-    fts_enabled: bool,
+    fts_enabled: AtomicBool,
+    at_rest: RwLock<AtRest>,
+    db_cipher: RwLock<DbCipher>,
+}
+
+// at-rest encryption of community documents, notes and dm text
+enum AtRest {
+    Off,
+    // encrypted, waiting for the passphrase
+    Locked,
+    Unlocked(Key),
 }
 
+// the whole database encrypted with sqlcipher, only builds with the
+// sqlcipher feature can open it
+enum DbCipher {
+    Plain,
+    // encrypted, waiting for the passphrase
+    Locked,
+    // the raw key sqlcipher opens the database with
+    Open(String),
+}
+
+const STORAGE_LOCKED: &str = "storage is locked";
+
+// a plaintext database starts with this, sqlcipher puts its salt there instead
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+// sealed dm text is stored as this prefix and the hex of the sealed record
+const SEALED_TEXT_PREFIX: &str = "\u{1}dsr:";
+
 impl DiskStorage {
     pub fn new() -> Result<Self, io::Error> {
-        let project_dirs = ProjectDirs::from("app", "duskchat", "dusk")
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no valid home directory"))?;
-
-        let base_dir = project_dirs.data_dir().to_path_buf();
+        Self::open_at(resolve_base_dir()?)
+    }
 
+    // storage rooted somewhere other than the data dir, e.g. a scratch copy
+    // for event replay
+    pub fn open_at(base_dir: PathBuf) -> Result<Self, io::Error> {
         // keep legacy directories so we can migrate existing installs safely
         fs::create_dir_all(base_dir.join("identity"))?;
         fs::create_dir_all(base_dir.join("communities"))?;
         fs::create_dir_all(base_dir.join("directory"))?;
         fs::create_dir_all(base_dir.join("dms"))?;
 
-        let db_path = base_dir.join("storage.sqlite3");
-        let conn = Self::open_conn_at(&db_path)?;
+        let db_path = base_dir.join(DB_FILE);
+        // an encrypted database can't be read before unlock_database has the
+        // key, it is set up then
+        let db_cipher = if database_salt(&db_path).is_some() {
+            DbCipher::Locked
+        } else {
+            DbCipher::Plain
+        };
+        let locked = matches!(db_cipher, DbCipher::Locked);
+        let storage = Self {
+            base_dir,
+            db_path,
+            fts_enabled: AtomicBool::new(false),
+            at_rest: RwLock::new(AtRest::Off),
+            db_cipher: RwLock::new(db_cipher),
+        };
+        if !locked {
+            storage.prepare()?;
+        }
+        Ok(storage)
+    }
+
+    // create or upgrade the schema and run one-off migrations, once the
+    // database can be read
+    fn prepare(&self) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
         conn.execute_batch(
             r#"
             PRAGMA journal_mode = WAL;
@@ -129,6 +529,11 @@ impl DiskStorage {
                 json TEXT NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS settings_profiles (
+                name TEXT PRIMARY KEY,
+                json TEXT NOT NULL
+            );
+
             CREATE TABLE IF NOT EXISTS verification_proof (
                 id INTEGER PRIMARY KEY CHECK (id = 1),
                 json TEXT NOT NULL
@@ -139,6 +544,18 @@ impl DiskStorage {
                 document BLOB NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS storage_key (
+                id INTEGER PRIMARY KEY CHECK (id = 0),
+                salt BLOB NOT NULL,
+                key_check BLOB NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS note_documents (
+                note_id TEXT PRIMARY KEY,
+                community_id TEXT NOT NULL,
+                document BLOB NOT NULL
+            );
+
             CREATE TABLE IF NOT EXISTS community_meta (
                 community_id TEXT PRIMARY KEY,
                 meta_json TEXT NOT NULL
@@ -153,6 +570,80 @@ impl DiskStorage {
                 is_friend INTEGER NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS revoked_peers (
+                peer_id TEXT PRIMARY KEY,
+                public_key TEXT NOT NULL,
+                revoked_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS key_conflicts (
+                peer_id TEXT PRIMARY KEY,
+                announcement TEXT NOT NULL,
+                received_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS archived_communities (
+                community_id TEXT PRIMARY KEY,
+                archived_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS profile_hidden_communities (
+                community_id TEXT PRIMARY KEY
+            );
+
+            CREATE TABLE IF NOT EXISTS community_folders (
+                folder_id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                collapsed INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE TABLE IF NOT EXISTS community_placements (
+                community_id TEXT PRIMARY KEY,
+                folder_id TEXT,
+                position INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS channel_follows (
+                source_community_id TEXT NOT NULL,
+                source_channel_id TEXT NOT NULL,
+                target_community_id TEXT NOT NULL,
+                target_channel_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (source_channel_id, target_channel_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS community_departures (
+                community_id TEXT NOT NULL,
+                peer_id TEXT NOT NULL,
+                departed_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS channel_interest (
+                community_id TEXT NOT NULL,
+                channel_id TEXT NOT NULL,
+                opened_at INTEGER NOT NULL,
+                PRIMARY KEY (community_id, channel_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS event_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recorded_at INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                topic TEXT NOT NULL,
+                source TEXT NOT NULL,
+                data BLOB NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_event_log_recorded_at
+                ON event_log(recorded_at);
+
+            CREATE TABLE IF NOT EXISTS peer_addresses (
+                peer_id TEXT NOT NULL,
+                addr TEXT NOT NULL,
+                last_connected INTEGER NOT NULL,
+                PRIMARY KEY (peer_id, addr)
+            );
+
             CREATE TABLE IF NOT EXISTS dm_conversations (
                 conversation_id TEXT PRIMARY KEY,
                 peer_id TEXT NOT NULL,
@@ -172,6 +663,66 @@ impl DiskStorage {
                 timestamp INTEGER NOT NULL
             );
 
+            -- ids of deleted dms, so a copy arriving again isn't stored anew
+            CREATE TABLE IF NOT EXISTS dm_tombstones (
+                conversation_id TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                deleted_at INTEGER NOT NULL,
+                PRIMARY KEY (conversation_id, message_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS dm_reactions (
+                conversation_id TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                peer_id TEXT NOT NULL,
+                emoji TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (message_id, peer_id, emoji)
+            );
+
+            CREATE TABLE IF NOT EXISTS dm_ephemeral_policies (
+                conversation_id TEXT PRIMARY KEY,
+                peer_id TEXT NOT NULL,
+                ttl_secs INTEGER,
+                pending_ttl_secs INTEGER,
+                pending_from TEXT,
+                updated_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS dm_policy_log (
+                conversation_id TEXT NOT NULL,
+                seq INTEGER NOT NULL,
+                actor TEXT NOT NULL,
+                action TEXT NOT NULL,
+                ttl_secs INTEGER,
+                timestamp INTEGER NOT NULL,
+                prev_hash TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                public_key TEXT NOT NULL DEFAULT '',
+                signature TEXT NOT NULL DEFAULT '',
+                PRIMARY KEY (conversation_id, seq)
+            );
+
+            CREATE TABLE IF NOT EXISTS community_profiles (
+                community_id TEXT NOT NULL,
+                peer_id TEXT NOT NULL,
+                display_name TEXT NOT NULL,
+                avatar_seed TEXT,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (community_id, peer_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS call_history (
+                call_id TEXT PRIMARY KEY,
+                peer_id TEXT NOT NULL,
+                display_name TEXT NOT NULL,
+                direction TEXT NOT NULL,
+                status TEXT NOT NULL,
+                started_at INTEGER NOT NULL,
+                answered_at INTEGER,
+                ended_at INTEGER
+            );
+
             CREATE INDEX IF NOT EXISTS idx_community_documents_id
                 ON community_documents (community_id);
 
@@ -186,10 +737,114 @@ impl DiskStorage {
 
             CREATE INDEX IF NOT EXISTS idx_dm_messages_conversation_sender
                 ON dm_messages (conversation_id, from_peer, timestamp DESC);
+
+            CREATE INDEX IF NOT EXISTS idx_dm_reactions_conversation
+                ON dm_reactions (conversation_id, message_id);
+
+            CREATE INDEX IF NOT EXISTS idx_call_history_started_at
+                ON call_history (started_at DESC);
+
+            CREATE INDEX IF NOT EXISTS idx_community_departures_community
+                ON community_departures (community_id, departed_at DESC);
             "#,
         )
         .map_err(sqlite_to_io_error)?;
 
+        // highest signed announcement timestamp seen per peer, used to drop replays
+        ensure_column(
+            &conn,
+            "directory_entries",
+            "announced_at",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+
+        // entries stored before this column existed were all verified, the node
+        // used to drop unverified announcements outright
+        ensure_column(
+            &conn,
+            "directory_entries",
+            "verified",
+            "INTEGER NOT NULL DEFAULT 1",
+        )?;
+
+        // rich presence from the last presence update, not signed by the peer
+        ensure_column(&conn, "directory_entries", "activity", "TEXT")?;
+
+        // custom status from announcements and presence updates
+        ensure_column(
+            &conn,
+            "directory_entries",
+            "status_message",
+            "TEXT NOT NULL DEFAULT ''",
+        )?;
+        ensure_column(
+            &conn,
+            "directory_entries",
+            "status_emoji",
+            "TEXT NOT NULL DEFAULT ''",
+        )?;
+
+        // set once the user compared safety numbers with the peer, cleared
+        // whenever the peer's key changes
+        ensure_column(
+            &conn,
+            "directory_entries",
+            "key_verified",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+
+        // rendezvous placeholders used to be told apart by their name alone
+        ensure_column(
+            &conn,
+            "directory_entries",
+            "placeholder",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        conn.execute(
+            "UPDATE directory_entries SET placeholder = 1
+             WHERE public_key = '' AND announced_at = 0 AND display_name = 'discovered peer'",
+            [],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        // when a message was read on this end, starts the disappearing timer
+        ensure_column(&conn, "dm_messages", "read_at", "INTEGER")?;
+
+        // replies, attachments (json) and edits, older rows read as plain text
+        ensure_column(&conn, "dm_messages", "reply_to", "TEXT")?;
+        ensure_column(&conn, "dm_messages", "attachments", "TEXT")?;
+        ensure_column(&conn, "dm_messages", "edited", "INTEGER NOT NULL DEFAULT 0")?;
+        // when the message reached this end, older rows go by its own timestamp
+        ensure_column(&conn, "dm_messages", "received_at", "INTEGER")?;
+
+        // the actor's signature on each policy step, older entries have none
+        ensure_column(
+            &conn,
+            "dm_policy_log",
+            "public_key",
+            "TEXT NOT NULL DEFAULT ''",
+        )?;
+        ensure_column(
+            &conn,
+            "dm_policy_log",
+            "signature",
+            "TEXT NOT NULL DEFAULT ''",
+        )?;
+
+        // sidebar organisation for dm conversations
+        ensure_column(
+            &conn,
+            "dm_conversations",
+            "pinned",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        ensure_column(
+            &conn,
+            "dm_conversations",
+            "archived",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+
         let fts_enabled = conn
             .execute_batch(
                 r#"
@@ -202,27 +857,62 @@ impl DiskStorage {
             )
             .is_ok();
 
+        let encrypted = conn
+            .query_row("SELECT 1 FROM storage_key WHERE id = 0", [], |_| Ok(()))
+            .optional()
+            .map_err(sqlite_to_io_error)?
+            .is_some();
+        let at_rest = if encrypted {
+            AtRest::Locked
+        } else {
+            AtRest::Off
+        };
+
+        self.fts_enabled.store(fts_enabled, Ordering::Relaxed);
+        *self.at_rest.write().unwrap() = at_rest;
+
         drop(conn);
 
-        let storage = Self {
-            base_dir,
-            db_path,
-            fts_enabled,
-        };
+        self.migrate_legacy_if_needed()?;
 
-        storage.migrate_legacy_if_needed()?;
+        // indexes built before the tokenizer was configurable are rebuilt once
+        let tokenizer = self
+            .load_settings()
+            .map(|s| s.search_tokenizer)
+            .unwrap_or_default();
+        if let Err(e) = self.set_search_tokenizer(tokenizer) {
+            log::warn!("failed to apply search tokenizer: {}", e);
+        }
 
-        Ok(storage)
+        Ok(())
     }
 
-    fn open_conn(&self) -> Result<Connection, io::Error> {
-        Self::open_conn_at(&self.db_path)
+    pub(super) fn open_conn(&self) -> Result<Connection, io::Error> {
+        let key = match &*self.db_cipher.read().unwrap() {
+            DbCipher::Plain => None,
+            DbCipher::Locked => return Err(locked_error()),
+            DbCipher::Open(key) => Some(key.clone()),
+        };
+        Self::open_conn_at(&self.db_path, key.as_deref())
     }
 
-    fn open_conn_at(db_path: &PathBuf) -> Result<Connection, io::Error> {
+    fn open_conn_at(db_path: &Path, key: Option<&str>) -> Result<Connection, io::Error> {
         let conn = Connection::open(db_path).map_err(sqlite_to_io_error)?;
+        // sqlcipher needs the key before anything else touches the file
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key)
+                .map_err(sqlite_to_io_error)?;
+        }
         let _ = conn.busy_timeout(Duration::from_secs(5));
         let _ = conn.pragma_update(None, "foreign_keys", "ON");
+        // sqlite's lower() only folds ascii, names in other scripts need this
+        conn.create_scalar_function(
+            "fold",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| Ok(ctx.get::<String>(0)?.to_lowercase()),
+        )
+        .map_err(sqlite_to_io_error)?;
         Ok(conn)
     }
 
@@ -258,13 +948,13 @@ impl DiskStorage {
         Ok(())
     }
 
-    fn migrate_legacy_files(&self) -> Result<(), io::Error> {
+    pub(super) fn migrate_legacy_files(&self) -> Result<(), io::Error> {
         self.migrate_legacy_identity()?;
         self.migrate_legacy_communities()?;
         self.migrate_legacy_directory()?;
         self.migrate_legacy_dms()?;
 
-        if self.fts_enabled {
+        if self.fts_enabled() {
             self.rebuild_dm_fts_index()?;
         }
 
@@ -415,8 +1105,8 @@ impl DiskStorage {
         Ok(())
     }
 
-    fn rebuild_dm_fts_index(&self) -> Result<(), io::Error> {
-        if !self.fts_enabled {
+    pub(super) fn rebuild_dm_fts_index(&self) -> Result<(), io::Error> {
+        if !self.fts_active() {
             return Ok(());
         }
 
@@ -426,7 +1116,7 @@ impl DiskStorage {
 
         let mut stmt = conn
             .prepare(
-                "SELECT id, conversation_id, content
+                "SELECT id, conversation_id, content, attachments
                  FROM dm_messages",
             )
             .map_err(sqlite_to_io_error)?;
@@ -437,16 +1127,18 @@ impl DiskStorage {
                     row.get::<_, String>(0)?,
                     row.get::<_, String>(1)?,
                     row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
                 ))
             })
             .map_err(sqlite_to_io_error)?;
 
         for row in rows {
-            let (id, conversation_id, content) = row.map_err(sqlite_to_io_error)?;
+            let (id, conversation_id, content, attachments) = row.map_err(sqlite_to_io_error)?;
+            let attachments = parse_attachments(attachments);
             conn.execute(
                 "INSERT INTO dm_message_fts (message_id, conversation_id, content)
                  VALUES (?1, ?2, ?3)",
-                params![id, conversation_id, content],
+                params![id, conversation_id, dm_search_text(&content, &attachments)],
             )
             .map_err(sqlite_to_io_error)?;
         }
@@ -454,7 +1146,86 @@ impl DiskStorage {
         Ok(())
     }
 
-    fn cleanup_legacy_files(&self) -> Result<(), io::Error> {
+    // recreate the search index with another tokenizer and refill it from
+    // dm_messages, returns false when the index already uses it
+    pub fn set_search_tokenizer(&self, tokenizer: SearchTokenizer) -> Result<bool, io::Error> {
+        if !self.fts_enabled() {
+            return Ok(false);
+        }
+
+        let conn = self.open_conn()?;
+        if self.search_tokenizer_spec(&conn)?.as_deref() == Some(tokenizer.fts_spec()) {
+            return Ok(false);
+        }
+
+        conn.execute_batch(&format!(
+            "DROP TABLE IF EXISTS dm_message_fts;
+             CREATE VIRTUAL TABLE dm_message_fts USING fts5(
+                message_id UNINDEXED,
+                conversation_id UNINDEXED,
+                content,
+                tokenize = '{}'
+             );",
+            tokenizer.fts_spec()
+        ))
+        .map_err(sqlite_to_io_error)?;
+        conn.execute(
+            "INSERT INTO app_meta (key, value) VALUES ('fts_tokenizer', ?1)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![tokenizer.fts_spec()],
+        )
+        .map_err(sqlite_to_io_error)?;
+        drop(conn);
+
+        self.rebuild_dm_fts_index()?;
+        Ok(true)
+    }
+
+    fn search_tokenizer_spec(&self, conn: &Connection) -> Result<Option<String>, io::Error> {
+        conn.query_row(
+            "SELECT value FROM app_meta WHERE key = 'fts_tokenizer'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(sqlite_to_io_error)
+    }
+
+    // tokenizer the search index was last built with
+    fn active_search_tokenizer(&self, conn: &Connection) -> SearchTokenizer {
+        match self.search_tokenizer_spec(conn).ok().flatten() {
+            Some(spec) if spec == SearchTokenizer::Trigram.fts_spec() => SearchTokenizer::Trigram,
+            _ => SearchTokenizer::Unicode61,
+        }
+    }
+
+    pub(super) fn fts_enabled(&self) -> bool {
+        self.fts_enabled.load(Ordering::Relaxed)
+    }
+
+    // pre-sqlite json files still on disk, empty once migration cleaned up
+    pub(super) fn legacy_files_remaining(&self) -> Vec<PathBuf> {
+        let mut remaining: Vec<PathBuf> = [
+            "identity/keypair.bin",
+            "identity/profile.json",
+            "identity/settings.json",
+            "identity/verification.json",
+            "directory/peers.json",
+        ]
+        .iter()
+        .map(|path| self.base_dir.join(path))
+        .filter(|path| path.exists())
+        .collect();
+
+        for dir in ["communities", "dms"] {
+            if let Ok(entries) = fs::read_dir(self.base_dir.join(dir)) {
+                remaining.extend(entries.flatten().map(|entry| entry.path()));
+            }
+        }
+        remaining
+    }
+
+    pub(super) fn cleanup_legacy_files(&self) -> Result<(), io::Error> {
         remove_if_exists(self.base_dir.join("identity/keypair.bin"))?;
         remove_if_exists(self.base_dir.join("identity/profile.json"))?;
         remove_if_exists(self.base_dir.join("identity/settings.json"))?;
@@ -585,40 +1356,348 @@ impl DiskStorage {
         }
     }
 
-    // -- automerge documents --
+    // -- database encryption --
 
-    pub fn save_document(&self, community_id: &str, doc_bytes: &[u8]) -> Result<(), io::Error> {
-        let conn = self.open_conn()?;
-        conn.execute(
-            "INSERT INTO community_documents (community_id, document)
-             VALUES (?1, ?2)
-             ON CONFLICT(community_id) DO UPDATE SET document = excluded.document",
-            params![community_id, doc_bytes],
-        )
-        .map_err(sqlite_to_io_error)?;
-        Ok(())
+    // whether this build can open and write sqlcipher databases
+    pub fn database_encryption_available() -> bool {
+        cfg!(feature = "sqlcipher")
     }
 
-    pub fn load_document(&self, community_id: &str) -> Result<Vec<u8>, io::Error> {
-        let conn = self.open_conn()?;
-        let bytes = conn
-            .query_row(
-                "SELECT document FROM community_documents WHERE community_id = ?1",
-                params![community_id],
-                |row| row.get::<_, Vec<u8>>(0),
-            )
-            .optional()
-            .map_err(sqlite_to_io_error)?;
+    pub fn is_database_encrypted(&self) -> bool {
+        !matches!(*self.db_cipher.read().unwrap(), DbCipher::Plain)
+    }
 
-        bytes.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "community document not found"))
+    // the database is encrypted and the passphrase hasn't been given yet,
+    // nothing in it can be read until it is
+    pub fn is_database_locked(&self) -> bool {
+        matches!(*self.db_cipher.read().unwrap(), DbCipher::Locked)
     }
 
-    pub fn delete_document(&self, community_id: &str) -> Result<(), io::Error> {
-        let conn = self.open_conn()?;
-        conn.execute(
-            "DELETE FROM community_documents WHERE community_id = ?1",
-            params![community_id],
-        )
+    // open the encrypted database and finish the setup skipped at startup
+    pub fn unlock_database(&self, passphrase: &str) -> Result<(), String> {
+        if !self.is_database_locked() {
+            return Ok(());
+        }
+        if !Self::database_encryption_available() {
+            return Err("the database is encrypted and this build can't open it".to_string());
+        }
+        let salt = database_salt(&self.db_path).ok_or("the database is not encrypted")?;
+        let key = database_key(passphrase, &salt)?;
+
+        let conn = Self::open_conn_at(&self.db_path, Some(&key))
+            .map_err(|e| format!("failed to open storage: {}", e))?;
+        // a wrong key only shows once the first page is read
+        let readable = conn
+            .query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .is_ok();
+        drop(conn);
+        if !readable {
+            return Err("wrong passphrase".to_string());
+        }
+
+        *self.db_cipher.write().unwrap() = DbCipher::Open(key);
+        if let Err(e) = self.prepare() {
+            *self.db_cipher.write().unwrap() = DbCipher::Locked;
+            return Err(format!("failed to open storage: {}", e));
+        }
+        Ok(())
+    }
+
+    // encrypt the whole database under a key derived from the passphrase,
+    // move it to a new passphrase, or back to plaintext when none is given.
+    // the database is exported to a new file that then replaces the old one,
+    // so a failure or crash part way leaves the old database as it was
+    pub fn set_database_encryption(&self, passphrase: Option<&str>) -> Result<(), String> {
+        if !Self::database_encryption_available() {
+            return Err("this build doesn't include database encryption".to_string());
+        }
+        // held throughout so nothing opens the file while it is replaced
+        let mut db_cipher = self.db_cipher.write().unwrap();
+        let old_key = match &*db_cipher {
+            DbCipher::Plain if passphrase.is_none() => return Ok(()),
+            DbCipher::Plain => None,
+            DbCipher::Locked => return Err(STORAGE_LOCKED.to_string()),
+            DbCipher::Open(key) => Some(key.clone()),
+        };
+        let new_key = match passphrase {
+            Some(passphrase) => Some(database_key(passphrase, &keystore::new_salt())?),
+            None => None,
+        };
+
+        let export_path = self.base_dir.join(format!("{}.export", DB_FILE));
+        let _ = fs::remove_file(&export_path);
+        let exported = Self::open_conn_at(&self.db_path, old_key.as_deref())
+            .and_then(|conn| {
+                // fold the wal into the file first, it is dropped below
+                conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+                    .and_then(|_| export_database(&conn, &export_path, new_key.as_deref()))
+                    .map_err(sqlite_to_io_error)
+            })
+            .and_then(|_| fs::rename(&export_path, &self.db_path));
+        if let Err(e) = exported {
+            let _ = fs::remove_file(&export_path);
+            return Err(format!("failed to encrypt database: {}", e));
+        }
+        for suffix in ["-wal", "-shm"] {
+            let _ = fs::remove_file(self.base_dir.join(format!("{}{}", DB_FILE, suffix)));
+        }
+        // the export doesn't carry the journal mode over
+        if let Err(e) = Self::open_conn_at(&self.db_path, new_key.as_deref()).and_then(|conn| {
+            conn.execute_batch("PRAGMA journal_mode = WAL;")
+                .map_err(sqlite_to_io_error)
+        }) {
+            log::warn!("failed to enable wal on the new database: {}", e);
+        }
+
+        *db_cipher = match new_key {
+            Some(key) => DbCipher::Open(key),
+            None => DbCipher::Plain,
+        };
+        drop(db_cipher);
+
+        // rebuilt rather than trusting the export with the index's own tables
+        if self.fts_active() {
+            if let Err(e) = self.rebuild_dm_fts_index() {
+                log::warn!("failed to rebuild dm search index: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    // -- at-rest encryption --
+
+    pub fn at_rest_encrypted(&self) -> bool {
+        !matches!(*self.at_rest.read().unwrap(), AtRest::Off)
+    }
+
+    // encrypted at rest and the passphrase hasn't been given yet, community
+    // documents and dm text can't be read or written until it is
+    pub fn is_storage_locked(&self) -> bool {
+        self.is_database_locked() || matches!(*self.at_rest.read().unwrap(), AtRest::Locked)
+    }
+
+    pub fn unlock_at_rest(&self, passphrase: &str) -> Result<(), String> {
+        let conn = self
+            .open_conn()
+            .map_err(|e| format!("failed to open storage: {}", e))?;
+        let stored: Option<(Vec<u8>, Vec<u8>)> = conn
+            .query_row(
+                "SELECT salt, key_check FROM storage_key WHERE id = 0",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("failed to read storage key: {}", e))?;
+        let Some((salt, check)) = stored else {
+            return Ok(());
+        };
+
+        let (key, _) = keystore::storage_key(passphrase, &salt)?;
+        if !keystore::key_matches(&key, &check) {
+            return Err("wrong passphrase".to_string());
+        }
+        *self.at_rest.write().unwrap() = AtRest::Unlocked(key);
+        Ok(())
+    }
+
+    // encrypt community documents, notes, dm text and the replay log under a
+    // key derived from the passphrase, or move them to a new passphrase if
+    // they already are. everything is resealed in one transaction so a
+    // failure leaves the old state. the dm search index holds plaintext, so it
+    // is emptied and stays empty while encrypted. afterwards the database is
+    // rebuilt so no plaintext lingers in freed pages or the wal. returns how
+    // many rows were written
+    pub fn encrypt_at_rest(&self, passphrase: &str) -> Result<usize, String> {
+        // held throughout so nothing is sealed under the old key meanwhile
+        let mut at_rest = self.at_rest.write().unwrap();
+        let old_key = match &*at_rest {
+            AtRest::Off => None,
+            AtRest::Locked => return Err(STORAGE_LOCKED.to_string()),
+            AtRest::Unlocked(key) => Some(*key),
+        };
+        let salt = keystore::new_salt();
+        let (key, check) = keystore::storage_key(passphrase, &salt)?;
+
+        let conn = self
+            .open_conn()
+            .map_err(|e| format!("failed to open storage: {}", e))?;
+        // pages the plaintext rows are freed from get zeroed, not just unlinked
+        conn.pragma_update(None, "secure_delete", "ON")
+            .map_err(|e| format!("failed to encrypt storage: {}", e))?;
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| format!("failed to encrypt storage: {}", e))?;
+        let resealed = reseal_rows(&tx, old_key.as_ref(), &key)?;
+        tx.execute(
+            "INSERT INTO storage_key (id, salt, key_check) VALUES (0, ?1, ?2)
+             ON CONFLICT(id) DO UPDATE SET salt = excluded.salt, key_check = excluded.key_check",
+            params![salt, check],
+        )
+        .map_err(|e| format!("failed to store storage key: {}", e))?;
+        if self.fts_enabled() {
+            tx.execute("DELETE FROM dm_message_fts", [])
+                .map_err(|e| format!("failed to clear search index: {}", e))?;
+        }
+        tx.commit()
+            .map_err(|e| format!("failed to encrypt storage: {}", e))?;
+        *at_rest = AtRest::Unlocked(key);
+        drop(at_rest);
+
+        scrub_plaintext(&conn)
+            .map_err(|e| format!("storage is encrypted but old plaintext remains: {}", e))?;
+        Ok(resealed)
+    }
+
+    // the full text index stores message text as is, so it is only kept
+    // while storage isn't encrypted
+    fn fts_active(&self) -> bool {
+        self.fts_enabled() && !self.at_rest_encrypted()
+    }
+
+    fn seal_bytes(&self, plaintext: &[u8]) -> Result<Vec<u8>, io::Error> {
+        match &*self.at_rest.read().unwrap() {
+            AtRest::Off => Ok(plaintext.to_vec()),
+            AtRest::Locked => Err(locked_error()),
+            AtRest::Unlocked(key) => keystore::seal_record(key, plaintext)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+
+    // rows written before encryption was turned on read as they are
+    fn open_bytes(&self, stored: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+        if !keystore::is_sealed_record(&stored) {
+            return Ok(stored);
+        }
+        match &*self.at_rest.read().unwrap() {
+            AtRest::Unlocked(key) => keystore::open_record(key, &stored)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            _ => Err(locked_error()),
+        }
+    }
+
+    fn seal_text(&self, plaintext: &str) -> Result<String, io::Error> {
+        match &*self.at_rest.read().unwrap() {
+            AtRest::Off => Ok(plaintext.to_string()),
+            AtRest::Locked => Err(locked_error()),
+            AtRest::Unlocked(key) => keystore::seal_record(key, plaintext.as_bytes())
+                .map(|record| format!("{}{}", SEALED_TEXT_PREFIX, hex::encode(record)))
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+
+    // text that only looks sealed, e.g. a dm crafted to, reads as it is
+    fn open_text(&self, stored: String) -> Result<String, io::Error> {
+        let Some(record) = sealed_text_record(&stored) else {
+            return Ok(stored);
+        };
+        match &*self.at_rest.read().unwrap() {
+            AtRest::Off => Ok(stored),
+            AtRest::Locked => Err(locked_error()),
+            AtRest::Unlocked(key) => Ok(open_sealed_text(key, &record).unwrap_or(stored)),
+        }
+    }
+
+    fn open_dm_messages(&self, messages: &mut [DirectMessage]) -> Result<(), io::Error> {
+        for message in messages {
+            message.content = self.open_text(std::mem::take(&mut message.content))?;
+        }
+        Ok(())
+    }
+
+    fn open_dm_conversation(&self, meta: &mut DMConversationMeta) -> Result<(), io::Error> {
+        if let Some(last_message) = meta.last_message.take() {
+            meta.last_message = Some(self.open_text(last_message)?);
+        }
+        Ok(())
+    }
+
+    // -- automerge documents --
+
+    pub fn save_document(&self, community_id: &str, doc_bytes: &[u8]) -> Result<(), io::Error> {
+        let stored = self.seal_bytes(doc_bytes)?;
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO community_documents (community_id, document)
+             VALUES (?1, ?2)
+             ON CONFLICT(community_id) DO UPDATE SET document = excluded.document",
+            params![community_id, stored],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    pub fn load_document(&self, community_id: &str) -> Result<Vec<u8>, io::Error> {
+        let conn = self.open_conn()?;
+        let bytes = conn
+            .query_row(
+                "SELECT document FROM community_documents WHERE community_id = ?1",
+                params![community_id],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()
+            .map_err(sqlite_to_io_error)?;
+
+        let bytes = bytes.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "community document not found")
+        })?;
+        self.open_bytes(bytes)
+    }
+
+    pub fn delete_document(&self, community_id: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM community_documents WHERE community_id = ?1",
+            params![community_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    pub fn save_note_document(
+        &self,
+        note_id: &str,
+        community_id: &str,
+        doc_bytes: &[u8],
+    ) -> Result<(), io::Error> {
+        let stored = self.seal_bytes(doc_bytes)?;
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO note_documents (note_id, community_id, document)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(note_id) DO UPDATE SET document = excluded.document",
+            params![note_id, community_id, stored],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // (note id, community id, automerge bytes) of every stored note
+    pub fn load_note_documents(&self) -> Result<Vec<(String, String, Vec<u8>)>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare("SELECT note_id, community_id, document FROM note_documents")
+            .map_err(sqlite_to_io_error)?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(sqlite_to_io_error)?;
+
+        let mut notes = Vec::new();
+        for row in rows {
+            let (note_id, community_id, stored) = row.map_err(sqlite_to_io_error)?;
+            notes.push((note_id, community_id, self.open_bytes(stored)?));
+        }
+        Ok(notes)
+    }
+
+    pub fn delete_note_documents(&self, community_id: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM note_documents WHERE community_id = ?1",
+            params![community_id],
+        )
         .map_err(sqlite_to_io_error)?;
         Ok(())
     }
@@ -638,6 +1717,33 @@ impl DiskStorage {
         Ok(ids)
     }
 
+    // stored size of each community document in bytes
+    pub fn document_sizes(&self) -> Result<HashMap<String, u64>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare("SELECT community_id, length(document) FROM community_documents")
+            .map_err(sqlite_to_io_error)?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let size: i64 = row.get(1)?;
+                Ok((row.get::<_, String>(0)?, size.max(0) as u64))
+            })
+            .map_err(sqlite_to_io_error)?;
+
+        let mut sizes = HashMap::new();
+        for row in rows {
+            let (community_id, size) = row.map_err(sqlite_to_io_error)?;
+            sizes.insert(community_id, size);
+        }
+        Ok(sizes)
+    }
+
+    // size of the database file on disk
+    pub fn database_size(&self) -> u64 {
+        fs::metadata(&self.db_path).map(|m| m.len()).unwrap_or(0)
+    }
+
     // -- community metadata cache --
 
     pub fn save_community_meta(&self, meta: &CommunityMeta) -> Result<(), io::Error> {
@@ -682,502 +1788,2667 @@ impl DiskStorage {
         Ok(())
     }
 
-    // -- user settings --
+    // -- per-community profiles --
 
-    pub fn save_settings(&self, settings: &UserSettings) -> Result<(), io::Error> {
-        let json = serde_json::to_string(settings)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    // upsert a per-community profile, older timestamps never replace newer ones
+    pub fn save_community_profile(&self, profile: &CommunityProfile) -> Result<bool, io::Error> {
+        let conn = self.open_conn()?;
+        let changed = conn
+            .execute(
+                "INSERT INTO community_profiles (
+                    community_id, peer_id, display_name, avatar_seed, updated_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT(community_id, peer_id) DO UPDATE SET
+                    display_name = excluded.display_name,
+                    avatar_seed = excluded.avatar_seed,
+                    updated_at = excluded.updated_at
+                WHERE excluded.updated_at > community_profiles.updated_at",
+                params![
+                    profile.community_id,
+                    profile.peer_id,
+                    profile.display_name,
+                    profile.avatar_seed,
+                    profile.timestamp as i64
+                ],
+            )
+            .map_err(sqlite_to_io_error)?;
+        Ok(changed > 0)
+    }
 
+    pub fn delete_community_profile(&self, community_id: &str, peer_id: &str) -> Result<(), io::Error> {
         let conn = self.open_conn()?;
         conn.execute(
-            "INSERT INTO settings (id, json)
-             VALUES (1, ?1)
-             ON CONFLICT(id) DO UPDATE SET json = excluded.json",
-            params![json],
+            "DELETE FROM community_profiles WHERE community_id = ?1 AND peer_id = ?2",
+            params![community_id, peer_id],
         )
         .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
 
+    pub fn delete_community_profiles(&self, community_id: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM community_profiles WHERE community_id = ?1",
+            params![community_id],
+        )
+        .map_err(sqlite_to_io_error)?;
         Ok(())
     }
 
-    pub fn load_settings(&self) -> Result<UserSettings, io::Error> {
+    // members seen leaving a community, only recorded while community
+    // analytics is switched on
+    pub fn record_member_departures(
+        &self,
+        community_id: &str,
+        peer_ids: &[String],
+        departed_at: u64,
+    ) -> Result<(), io::Error> {
         let conn = self.open_conn()?;
-        let json = conn
-            .query_row("SELECT json FROM settings WHERE id = 1", [], |row| {
-                row.get::<_, String>(0)
-            })
-            .optional()
+        for peer_id in peer_ids {
+            conn.execute(
+                "INSERT INTO community_departures (community_id, peer_id, departed_at)
+                 VALUES (?1, ?2, ?3)",
+                params![community_id, peer_id, departed_at as i64],
+            )
             .map_err(sqlite_to_io_error)?;
-
-        match json {
-            Some(data) => serde_json::from_str(&data)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
-            None => Ok(UserSettings::default()),
         }
+        Ok(())
     }
 
-    // -- peer directory --
+    pub fn count_member_departures(
+        &self,
+        community_id: &str,
+        since: u64,
+    ) -> Result<usize, io::Error> {
+        let conn = self.open_conn()?;
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM community_departures
+                 WHERE community_id = ?1 AND departed_at >= ?2",
+                params![community_id, since as i64],
+                |row| row.get(0),
+            )
+            .map_err(sqlite_to_io_error)?;
+        Ok(count as usize)
+    }
 
-    // save a discovered peer to the local directory
-    pub fn save_directory_entry(&self, entry: &DirectoryEntry) -> Result<(), io::Error> {
+    pub fn delete_member_departures(&self, community_id: &str) -> Result<(), io::Error> {
         let conn = self.open_conn()?;
         conn.execute(
-            "INSERT INTO directory_entries (
-                peer_id, display_name, bio, public_key, last_seen, is_friend
-            )
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-             ON CONFLICT(peer_id) DO UPDATE SET
-                display_name = excluded.display_name,
-                bio = excluded.bio,
-                public_key = excluded.public_key,
-                last_seen = excluded.last_seen,
-                is_friend = excluded.is_friend",
-            params![
-                entry.peer_id,
-                entry.display_name,
-                entry.bio,
-                entry.public_key,
-                entry.last_seen as i64,
-                if entry.is_friend { 1_i64 } else { 0_i64 }
-            ],
+            "DELETE FROM community_departures WHERE community_id = ?1",
+            params![community_id],
         )
         .map_err(sqlite_to_io_error)?;
-
         Ok(())
     }
 
-    // upsert a directory entry from the relay — updates display_name and last_seen but preserves bio, public_key, and is_friend
-    pub fn save_directory_entry_if_new(&self, entry: &DirectoryEntry) -> Result<(), io::Error> {
+    // an address we reached a peer on, refreshed on every connection. only
+    // the most recent few are kept per peer
+    pub fn record_peer_address(
+        &self,
+        peer_id: &str,
+        addr: &str,
+        connected_at: u64,
+    ) -> Result<(), io::Error> {
         let conn = self.open_conn()?;
         conn.execute(
-            "INSERT INTO directory_entries (
-                peer_id, display_name, bio, public_key, last_seen, is_friend
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-            ON CONFLICT(peer_id) DO UPDATE SET
-                display_name = excluded.display_name,
-                last_seen    = CASE WHEN excluded.last_seen > last_seen THEN excluded.last_seen ELSE last_seen END",
-            params![
-                entry.peer_id,
-                entry.display_name,
-                entry.bio,
-                entry.public_key,
-                entry.last_seen as i64,
-                if entry.is_friend { 1_i64 } else { 0_i64 }
-            ],
+            "INSERT INTO peer_addresses (peer_id, addr, last_connected)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(peer_id, addr) DO UPDATE SET last_connected = excluded.last_connected",
+            params![peer_id, addr, connected_at as i64],
+        )
+        .map_err(sqlite_to_io_error)?;
+        conn.execute(
+            "DELETE FROM peer_addresses WHERE peer_id = ?1 AND addr NOT IN (
+                SELECT addr FROM peer_addresses WHERE peer_id = ?1
+                ORDER BY last_connected DESC LIMIT ?2
+             )",
+            params![peer_id, MAX_ADDRESSES_PER_PEER as i64],
         )
         .map_err(sqlite_to_io_error)?;
         Ok(())
     }
 
-    // load the entire peer directory
-    pub fn load_directory(&self) -> Result<HashMap<String, DirectoryEntry>, io::Error> {
+    // (peer_id, addr) pairs used since `since`, most recent first. older
+    // entries are dropped on the way
+    pub fn load_peer_addresses(&self, since: u64) -> Result<Vec<(String, String)>, io::Error> {
         let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM peer_addresses WHERE last_connected < ?1",
+            params![since as i64],
+        )
+        .map_err(sqlite_to_io_error)?;
+
         let mut stmt = conn
             .prepare(
-                "SELECT peer_id, display_name, bio, public_key, last_seen, is_friend
-                 FROM directory_entries",
+                "SELECT peer_id, addr FROM peer_addresses
+                 ORDER BY last_connected DESC",
             )
             .map_err(sqlite_to_io_error)?;
-
-        let rows = stmt
+        let entries = stmt
             .query_map([], |row| {
-                let peer_id: String = row.get(0)?;
-                let last_seen: i64 = row.get(4)?;
-                let is_friend: i64 = row.get(5)?;
-
-                Ok((
-                    peer_id.clone(),
-                    DirectoryEntry {
-                        peer_id,
-                        display_name: row.get(1)?,
-                        bio: row.get(2)?,
-                        public_key: row.get(3)?,
-                        last_seen: last_seen.max(0) as u64,
-                        is_friend: is_friend != 0,
-                    },
-                ))
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
             })
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<Vec<_>, _>>()
             .map_err(sqlite_to_io_error)?;
 
-        let mut entries = HashMap::new();
-        for row in rows {
-            let (peer_id, entry) = row.map_err(sqlite_to_io_error)?;
-            entries.insert(peer_id, entry);
-        }
-
         Ok(entries)
     }
 
-    // remove a peer from the directory
-    pub fn remove_directory_entry(&self, peer_id: &str) -> Result<(), io::Error> {
+    pub fn save_channel_follow(&self, follow: &ChannelFollow) -> Result<(), io::Error> {
         let conn = self.open_conn()?;
         conn.execute(
-            "DELETE FROM directory_entries WHERE peer_id = ?1",
-            params![peer_id],
+            "INSERT INTO channel_follows (
+                source_community_id, source_channel_id, target_community_id,
+                target_channel_id, created_at
+             ) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(source_channel_id, target_channel_id) DO NOTHING",
+            params![
+                follow.source_community_id,
+                follow.source_channel_id,
+                follow.target_community_id,
+                follow.target_channel_id,
+                follow.created_at as i64,
+            ],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // returns whether a follow was removed
+    pub fn delete_channel_follow(
+        &self,
+        source_channel_id: &str,
+        target_channel_id: &str,
+    ) -> Result<bool, io::Error> {
+        let conn = self.open_conn()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM channel_follows
+                 WHERE source_channel_id = ?1 AND target_channel_id = ?2",
+                params![source_channel_id, target_channel_id],
+            )
+            .map_err(sqlite_to_io_error)?;
+        Ok(removed > 0)
+    }
+
+    // opt-in replay log, the oldest rows go once MAX_RECORDED_EVENTS is reached.
+    // payloads are raw gossip, dm text included, so they are sealed like dms
+    pub fn record_event(
+        &self,
+        kind: RecordedEventKind,
+        topic: &str,
+        source: &str,
+        data: &[u8],
+        recorded_at: u64,
+    ) -> Result<(), io::Error> {
+        let data = self.seal_bytes(data)?;
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO event_log (recorded_at, kind, topic, source, data)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![recorded_at as i64, kind.as_str(), topic, source, data],
+        )
+        .map_err(sqlite_to_io_error)?;
+        conn.execute(
+            "DELETE FROM event_log WHERE id <= ?1",
+            params![conn.last_insert_rowid() - MAX_RECORDED_EVENTS],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // recorded events at or after `since`, oldest first
+    pub fn load_events_since(&self, since: u64) -> Result<Vec<RecordedEvent>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, recorded_at, kind, topic, source, data FROM event_log
+                 WHERE recorded_at >= ?1 ORDER BY id ASC",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map(params![since as i64], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, Vec<u8>>(5)?,
+                ))
+            })
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_to_io_error)?;
+        let mut events = Vec::with_capacity(rows.len());
+        for (id, recorded_at, kind, topic, source, data) in rows {
+            let Some(kind) = RecordedEventKind::parse(&kind) else {
+                continue;
+            };
+            events.push(RecordedEvent {
+                id,
+                recorded_at: recorded_at.max(0) as u64,
+                kind,
+                topic,
+                source,
+                data: self.open_bytes(data)?,
+            });
+        }
+        Ok(events)
+    }
+
+    // when a channel was last opened, drives interest-based subscriptions
+    pub fn record_channel_opened(
+        &self,
+        community_id: &str,
+        channel_id: &str,
+        opened_at: u64,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO channel_interest (community_id, channel_id, opened_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(community_id, channel_id) DO UPDATE SET opened_at = excluded.opened_at",
+            params![community_id, channel_id, opened_at as i64],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    pub fn channel_opened_at(
+        &self,
+        community_id: &str,
+        channel_id: &str,
+    ) -> Result<Option<u64>, io::Error> {
+        let conn = self.open_conn()?;
+        let opened_at: Option<i64> = conn
+            .query_row(
+                "SELECT opened_at FROM channel_interest
+                 WHERE community_id = ?1 AND channel_id = ?2",
+                params![community_id, channel_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_to_io_error)?;
+        Ok(opened_at.map(|at| at.max(0) as u64))
+    }
+
+    // drop channels last opened before `before`, returning them
+    pub fn forget_channels_opened_before(
+        &self,
+        before: u64,
+    ) -> Result<Vec<(String, String)>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT community_id, channel_id FROM channel_interest
+                 WHERE opened_at < ?1",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let stale = stmt
+            .query_map(params![before as i64], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_to_io_error)?;
+        conn.execute(
+            "DELETE FROM channel_interest WHERE opened_at < ?1",
+            params![before as i64],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(stale)
+    }
+
+    pub fn delete_channel_interest_for_community(
+        &self,
+        community_id: &str,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM channel_interest WHERE community_id = ?1",
+            params![community_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // follows on either side of a community, dropped when we leave it
+    pub fn delete_channel_follows_for_community(
+        &self,
+        community_id: &str,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM channel_follows
+             WHERE source_community_id = ?1 OR target_community_id = ?1",
+            params![community_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    pub fn load_channel_follows(&self) -> Result<Vec<ChannelFollow>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT source_community_id, source_channel_id, target_community_id,
+                        target_channel_id, created_at
+                 FROM channel_follows ORDER BY created_at ASC",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map([], channel_follow_from_row)
+            .map_err(sqlite_to_io_error)?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_to_io_error)
+    }
+
+    // where announcements posted in this channel get mirrored to
+    pub fn load_channel_followers(
+        &self,
+        source_community_id: &str,
+        source_channel_id: &str,
+    ) -> Result<Vec<ChannelFollow>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT source_community_id, source_channel_id, target_community_id,
+                        target_channel_id, created_at
+                 FROM channel_follows
+                 WHERE source_community_id = ?1 AND source_channel_id = ?2",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map(
+                params![source_community_id, source_channel_id],
+                channel_follow_from_row,
+            )
+            .map_err(sqlite_to_io_error)?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_to_io_error)
+    }
+
+    pub fn load_community_profile(
+        &self,
+        community_id: &str,
+        peer_id: &str,
+    ) -> Result<Option<CommunityProfile>, io::Error> {
+        let conn = self.open_conn()?;
+        conn.query_row(
+            "SELECT community_id, peer_id, display_name, avatar_seed, updated_at
+             FROM community_profiles
+             WHERE community_id = ?1 AND peer_id = ?2",
+            params![community_id, peer_id],
+            community_profile_from_row,
+        )
+        .optional()
+        .map_err(sqlite_to_io_error)
+    }
+
+    // the name a peer goes by in a community, their override if they set one
+    pub fn community_display_name(&self, community_id: &str, peer_id: &str, fallback: &str) -> String {
+        self.load_community_profile(community_id, peer_id)
+            .ok()
+            .flatten()
+            .map(|p| p.display_name)
+            .unwrap_or_else(|| fallback.to_string())
+    }
+
+    // every community where a peer uses a per-community profile
+    pub fn load_peer_community_profiles(&self, peer_id: &str) -> Result<Vec<CommunityProfile>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT community_id, peer_id, display_name, avatar_seed, updated_at
+                 FROM community_profiles
+                 WHERE peer_id = ?1
+                 ORDER BY community_id",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map(params![peer_id], community_profile_from_row)
+            .map_err(sqlite_to_io_error)?;
+
+        let mut profiles = Vec::new();
+        for row in rows {
+            profiles.push(row.map_err(sqlite_to_io_error)?);
+        }
+        Ok(profiles)
+    }
+
+    // per-community profiles of every member of a community, keyed by peer id
+    pub fn load_community_member_profiles(
+        &self,
+        community_id: &str,
+    ) -> Result<HashMap<String, CommunityProfile>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT community_id, peer_id, display_name, avatar_seed, updated_at
+                 FROM community_profiles
+                 WHERE community_id = ?1",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map(params![community_id], community_profile_from_row)
+            .map_err(sqlite_to_io_error)?;
+
+        let mut profiles = HashMap::new();
+        for row in rows {
+            let profile = row.map_err(sqlite_to_io_error)?;
+            profiles.insert(profile.peer_id.clone(), profile);
+        }
+        Ok(profiles)
+    }
+
+    // -- user settings --
+
+    pub fn save_settings(&self, settings: &UserSettings) -> Result<(), io::Error> {
+        let json = serde_json::to_string(settings)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO settings (id, json)
+             VALUES (1, ?1)
+             ON CONFLICT(id) DO UPDATE SET json = excluded.json",
+            params![json],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        Ok(())
+    }
+
+    pub fn load_settings(&self) -> Result<UserSettings, io::Error> {
+        let conn = self.open_conn()?;
+        let json = conn
+            .query_row("SELECT json FROM settings WHERE id = 1", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()
+            .map_err(sqlite_to_io_error)?;
+
+        match json {
+            Some(data) => serde_json::from_str(&data)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            None => Ok(UserSettings::default()),
+        }
+    }
+
+    pub fn save_settings_profile(&self, profile: &SettingsProfile) -> Result<(), io::Error> {
+        let json = serde_json::to_string(profile)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO settings_profiles (name, json)
+             VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET json = excluded.json",
+            params![profile.name, json],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        Ok(())
+    }
+
+    // sorted by name, rows that no longer parse are skipped
+    pub fn load_settings_profiles(&self) -> Result<Vec<SettingsProfile>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare("SELECT json FROM settings_profiles ORDER BY name")
+            .map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(sqlite_to_io_error)?;
+
+        let mut profiles = Vec::new();
+        for row in rows {
+            let json = row.map_err(sqlite_to_io_error)?;
+            if let Ok(profile) = serde_json::from_str(&json) {
+                profiles.push(profile);
+            }
+        }
+        Ok(profiles)
+    }
+
+    pub fn delete_settings_profile(&self, name: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM settings_profiles WHERE name = ?1",
+            params![name],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // communities left with their history kept as a read-only archive
+    pub fn set_community_archived(
+        &self,
+        community_id: &str,
+        archived: bool,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        if archived {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64;
+            conn.execute(
+                "INSERT INTO archived_communities (community_id, archived_at) VALUES (?1, ?2)
+                 ON CONFLICT(community_id) DO NOTHING",
+                params![community_id, now],
+            )
+            .map_err(sqlite_to_io_error)?;
+        } else {
+            conn.execute(
+                "DELETE FROM archived_communities WHERE community_id = ?1",
+                params![community_id],
+            )
+            .map_err(sqlite_to_io_error)?;
+        }
+        Ok(())
+    }
+
+    pub fn load_archived_communities(&self) -> Result<HashSet<String>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare("SELECT community_id FROM archived_communities")
+            .map_err(sqlite_to_io_error)?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<HashSet<_>, _>>()
+            .map_err(sqlite_to_io_error)?;
+        Ok(ids)
+    }
+
+    // communities our profile announcements stay out of, every other
+    // community we are in gets them on its directory topic
+    pub fn set_profile_visible_in_community(
+        &self,
+        community_id: &str,
+        visible: bool,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        if visible {
+            conn.execute(
+                "DELETE FROM profile_hidden_communities WHERE community_id = ?1",
+                params![community_id],
+            )
+        } else {
+            conn.execute(
+                "INSERT INTO profile_hidden_communities (community_id) VALUES (?1)
+                 ON CONFLICT(community_id) DO NOTHING",
+                params![community_id],
+            )
+        }
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    pub fn load_profile_hidden_communities(&self) -> Result<HashSet<String>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare("SELECT community_id FROM profile_hidden_communities")
+            .map_err(sqlite_to_io_error)?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<HashSet<_>, _>>()
+            .map_err(sqlite_to_io_error)?;
+        Ok(ids)
+    }
+
+    // -- community list layout --
+
+    pub fn load_community_layout(&self) -> Result<CommunityLayout, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT folder_id, name, position, collapsed FROM community_folders
+                 ORDER BY position ASC, folder_id ASC",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let folders = stmt
+            .query_map([], |row| {
+                Ok(CommunityFolder {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    position: row.get::<_, i64>(2)? as u32,
+                    collapsed: row.get::<_, i64>(3)? != 0,
+                })
+            })
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_to_io_error)?;
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT community_id, folder_id, position FROM community_placements
+                 ORDER BY position ASC, community_id ASC",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let communities = stmt
+            .query_map([], |row| {
+                Ok(CommunityPlacement {
+                    community_id: row.get(0)?,
+                    folder_id: row.get(1)?,
+                    position: row.get::<_, i64>(2)? as u32,
+                })
+            })
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_to_io_error)?;
+
+        Ok(CommunityLayout {
+            folders,
+            communities,
+        })
+    }
+
+    pub fn save_community_folder(&self, folder: &CommunityFolder) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO community_folders (folder_id, name, position, collapsed)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(folder_id) DO UPDATE SET
+                name = excluded.name,
+                position = excluded.position,
+                collapsed = excluded.collapsed",
+            params![
+                folder.id,
+                folder.name,
+                folder.position as i64,
+                folder.collapsed as i64,
+            ],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // communities inside the folder move back to the top level
+    pub fn delete_community_folder(&self, folder_id: &str) -> Result<bool, io::Error> {
+        let conn = self.open_conn()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
+        let removed = tx
+            .execute(
+                "DELETE FROM community_folders WHERE folder_id = ?1",
+                params![folder_id],
+            )
+            .map_err(sqlite_to_io_error)?;
+        tx.execute(
+            "UPDATE community_placements SET folder_id = NULL WHERE folder_id = ?1",
+            params![folder_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+        tx.commit().map_err(sqlite_to_io_error)?;
+        Ok(removed > 0)
+    }
+
+    // replace the whole layout, positions follow the order given
+    pub fn save_community_layout(&self, layout: &CommunityLayout) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
+        tx.execute("DELETE FROM community_folders", [])
+            .map_err(sqlite_to_io_error)?;
+        tx.execute("DELETE FROM community_placements", [])
+            .map_err(sqlite_to_io_error)?;
+        for (position, folder) in layout.folders.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO community_folders (folder_id, name, position, collapsed)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    folder.id,
+                    folder.name,
+                    position as i64,
+                    folder.collapsed as i64,
+                ],
+            )
+            .map_err(sqlite_to_io_error)?;
+        }
+        for (position, placement) in layout.communities.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO community_placements (community_id, folder_id, position)
+                 VALUES (?1, ?2, ?3)",
+                params![placement.community_id, placement.folder_id, position as i64],
+            )
+            .map_err(sqlite_to_io_error)?;
+        }
+        tx.commit().map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    pub fn delete_community_placement(&self, community_id: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM community_placements WHERE community_id = ?1",
+            params![community_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // -- peer directory --
+
+    // save a discovered peer to the local directory
+    pub fn save_directory_entry(&self, entry: &DirectoryEntry) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO directory_entries (
+                peer_id, display_name, bio, public_key, last_seen, is_friend, verified, placeholder
+            )
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(peer_id) DO UPDATE SET
+                display_name = excluded.display_name,
+                bio = excluded.bio,
+                key_verified = CASE WHEN excluded.public_key = directory_entries.public_key
+                    THEN directory_entries.key_verified ELSE 0 END,
+                public_key = excluded.public_key,
+                last_seen = excluded.last_seen,
+                is_friend = excluded.is_friend,
+                verified = excluded.verified,
+                placeholder = excluded.placeholder",
+            params![
+                entry.peer_id,
+                entry.display_name,
+                entry.bio,
+                entry.public_key,
+                entry.last_seen as i64,
+                if entry.is_friend { 1_i64 } else { 0_i64 },
+                if entry.verified { 1_i64 } else { 0_i64 },
+                entry.placeholder as i64
+            ],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        Ok(())
+    }
+
+    // upsert a directory entry from the relay — updates last_seen but preserves bio, public_key, and is_friend.
+    // the relay's display name only replaces names we have no signed profile for, and lifts a placeholder
+    pub fn save_directory_entry_if_new(&self, entry: &DirectoryEntry) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO directory_entries (
+                peer_id, display_name, bio, public_key, last_seen, is_friend, verified, placeholder
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT(peer_id) DO UPDATE SET
+                display_name = CASE WHEN directory_entries.public_key = ''
+                    THEN excluded.display_name ELSE directory_entries.display_name END,
+                placeholder  = CASE WHEN directory_entries.public_key = ''
+                    THEN excluded.placeholder ELSE directory_entries.placeholder END,
+                last_seen    = CASE WHEN excluded.last_seen > last_seen THEN excluded.last_seen ELSE last_seen END",
+            params![
+                entry.peer_id,
+                entry.display_name,
+                entry.bio,
+                entry.public_key,
+                entry.last_seen as i64,
+                if entry.is_friend { 1_i64 } else { 0_i64 },
+                if entry.verified { 1_i64 } else { 0_i64 },
+                entry.placeholder as i64
+            ],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // save a directory entry from a signed profile announcement
+    // only applies when the announcement is newer than anything seen for this peer,
+    // returns false when the announcement was stale or replayed
+    pub fn save_announced_directory_entry(
+        &self,
+        entry: &DirectoryEntry,
+        announced_at: u64,
+    ) -> Result<bool, io::Error> {
+        let conn = self.open_conn()?;
+        let changed = conn
+            .execute(
+                "INSERT INTO directory_entries (
+                    peer_id, display_name, bio, public_key, last_seen, is_friend, announced_at, verified,
+                    status_message, status_emoji
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ON CONFLICT(peer_id) DO UPDATE SET
+                    display_name = excluded.display_name,
+                    bio = excluded.bio,
+                    key_verified = CASE WHEN excluded.public_key = directory_entries.public_key
+                        THEN directory_entries.key_verified ELSE 0 END,
+                    public_key = excluded.public_key,
+                    last_seen = excluded.last_seen,
+                    announced_at = excluded.announced_at,
+                    verified = excluded.verified,
+                    status_message = excluded.status_message,
+                    status_emoji = excluded.status_emoji,
+                    placeholder = 0
+                WHERE excluded.announced_at > directory_entries.announced_at",
+                params![
+                    entry.peer_id,
+                    entry.display_name,
+                    entry.bio,
+                    entry.public_key,
+                    entry.last_seen as i64,
+                    if entry.is_friend { 1_i64 } else { 0_i64 },
+                    announced_at as i64,
+                    if entry.verified { 1_i64 } else { 0_i64 },
+                    entry.status_message,
+                    entry.status_emoji
+                ],
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        Ok(changed > 0)
+    }
+
+    // load the entire peer directory
+    pub fn load_directory(&self) -> Result<HashMap<String, DirectoryEntry>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT peer_id, display_name, bio, public_key, last_seen, is_friend, verified, activity,
+                        status_message, status_emoji, key_verified, placeholder
+                 FROM directory_entries",
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        let rows = stmt
+            .query_map([], directory_entry_from_row)
+            .map_err(sqlite_to_io_error)?;
+
+        let mut entries = HashMap::new();
+        for row in rows {
+            let entry = row.map_err(sqlite_to_io_error)?;
+            entries.insert(entry.peer_id.clone(), entry);
+        }
+
+        Ok(entries)
+    }
+
+    // one page of the entries whose name or peer id contains query, most
+    // recently seen first. case folding is sqlite's, ascii only
+    pub fn search_directory_entries(
+        &self,
+        query: &str,
+        include_placeholders: bool,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<DirectoryEntry>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT peer_id, display_name, bio, public_key, last_seen, is_friend, verified, activity,
+                        status_message, status_emoji, key_verified, placeholder
+                 FROM directory_entries
+                 WHERE (?2 OR placeholder = 0)
+                   AND (instr(fold(display_name), fold(?1)) > 0
+                        OR instr(fold(peer_id), fold(?1)) > 0)
+                 ORDER BY last_seen DESC
+                 LIMIT ?3 OFFSET ?4",
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        let entries = stmt
+            .query_map(
+                params![query, include_placeholders, limit as i64, offset as i64],
+                directory_entry_from_row,
+            )
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_to_io_error)?;
+        Ok(entries)
+    }
+
+    // friends only, by name
+    pub fn load_friends(&self) -> Result<Vec<DirectoryEntry>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT peer_id, display_name, bio, public_key, last_seen, is_friend, verified, activity,
+                        status_message, status_emoji, key_verified, placeholder
+                 FROM directory_entries
+                 WHERE is_friend = 1
+                 ORDER BY fold(display_name)",
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        let friends = stmt
+            .query_map([], directory_entry_from_row)
+            .map_err(sqlite_to_io_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_to_io_error)?;
+        Ok(friends)
+    }
+
+    // load a single directory entry
+    pub fn load_directory_entry(&self, peer_id: &str) -> Result<Option<DirectoryEntry>, io::Error> {
+        let conn = self.open_conn()?;
+        conn.query_row(
+            "SELECT peer_id, display_name, bio, public_key, last_seen, is_friend, verified, activity,
+                    status_message, status_emoji, key_verified, placeholder
+             FROM directory_entries
+             WHERE peer_id = ?1",
+            params![peer_id],
+            directory_entry_from_row,
+        )
+        .optional()
+        .map_err(sqlite_to_io_error)
+    }
+
+    // record that the user compared safety numbers with a peer. only applies
+    // while the peer still has the key the numbers were computed from
+    pub fn set_peer_key_verified(
+        &self,
+        peer_id: &str,
+        public_key: &str,
+        verified: bool,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        let changed = conn
+            .execute(
+                "UPDATE directory_entries
+                 SET key_verified = ?3
+                 WHERE peer_id = ?1 AND public_key = ?2",
+                params![peer_id, public_key, verified as i64],
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        if changed == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "peer not found in directory or its key changed",
+            ));
+        }
+
+        Ok(())
+    }
+
+    // remember what a known peer is doing, unknown peers are ignored since
+    // presence alone is not enough to create a directory entry
+    pub fn set_directory_activity(
+        &self,
+        peer_id: &str,
+        activity: Option<&str>,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "UPDATE directory_entries SET activity = ?2 WHERE peer_id = ?1",
+            params![peer_id, activity],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // custom status from a presence update, unknown peers are ignored
+    pub fn set_directory_status(
+        &self,
+        peer_id: &str,
+        status_message: &str,
+        status_emoji: &str,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "UPDATE directory_entries SET status_message = ?2, status_emoji = ?3 WHERE peer_id = ?1",
+            params![peer_id, status_message, status_emoji],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // remove a peer from the directory
+    pub fn remove_directory_entry(&self, peer_id: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM directory_entries WHERE peer_id = ?1",
+            params![peer_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+        conn.execute(
+            "DELETE FROM key_conflicts WHERE peer_id = ?1",
+            params![peer_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // drop entries not seen since cutoff (unix millis). friends, peers with a
+    // dm conversation and the protected peers, e.g. members of communities we
+    // share, are kept however stale. returns how many were removed
+    pub fn prune_directory(
+        &self,
+        cutoff: u64,
+        protected: &HashSet<String>,
+    ) -> Result<usize, io::Error> {
+        let mut conn = self.open_conn()?;
+        let tx = conn.transaction().map_err(sqlite_to_io_error)?;
+
+        let stale: Vec<String> = {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT peer_id FROM directory_entries
+                     WHERE is_friend = 0 AND last_seen < ?1
+                       AND peer_id NOT IN (SELECT peer_id FROM dm_conversations)",
+                )
+                .map_err(sqlite_to_io_error)?;
+            let rows = stmt
+                .query_map(params![cutoff as i64], |row| row.get::<_, String>(0))
+                .map_err(sqlite_to_io_error)?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(sqlite_to_io_error)?
+                .into_iter()
+                .filter(|peer_id| !protected.contains(peer_id))
+                .collect()
+        };
+
+        for peer_id in &stale {
+            tx.execute(
+                "DELETE FROM directory_entries WHERE peer_id = ?1",
+                params![peer_id],
+            )
+            .map_err(sqlite_to_io_error)?;
+            tx.execute(
+                "DELETE FROM key_conflicts WHERE peer_id = ?1",
+                params![peer_id],
+            )
+            .map_err(sqlite_to_io_error)?;
+        }
+        tx.commit().map_err(sqlite_to_io_error)?;
+
+        Ok(stale.len())
+    }
+
+    // -- pinned keys --
+
+    // hold back an announcement whose key differs from the pinned one, only
+    // the latest offer per peer is kept
+    pub fn save_key_conflict(
+        &self,
+        announcement: &ProfileAnnouncement,
+        received_at: u64,
+    ) -> Result<(), io::Error> {
+        let json = serde_json::to_string(announcement)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO key_conflicts (peer_id, announcement, received_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(peer_id) DO UPDATE SET
+                announcement = excluded.announcement,
+                received_at = excluded.received_at",
+            params![announcement.peer_id, json, received_at as i64],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // the held back announcement for a peer, if any
+    pub fn load_key_conflict(
+        &self,
+        peer_id: &str,
+    ) -> Result<Option<ProfileAnnouncement>, io::Error> {
+        let conn = self.open_conn()?;
+        let json: Option<String> = conn
+            .query_row(
+                "SELECT announcement FROM key_conflicts WHERE peer_id = ?1",
+                params![peer_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_to_io_error)?;
+
+        json.map(|json| {
+            serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .transpose()
+    }
+
+    // every pending key change, newest first
+    pub fn load_key_conflicts(&self) -> Result<Vec<KeyConflict>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT announcement, received_at FROM key_conflicts ORDER BY received_at DESC",
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let received_at: i64 = row.get(1)?;
+                Ok((row.get::<_, String>(0)?, received_at.max(0) as u64))
+            })
+            .map_err(sqlite_to_io_error)?;
+
+        let mut conflicts = Vec::new();
+        for row in rows {
+            let (json, received_at) = row.map_err(sqlite_to_io_error)?;
+            let Ok(announcement) = serde_json::from_str::<ProfileAnnouncement>(&json) else {
+                continue;
+            };
+            if let Some(pinned) = self.load_directory_entry(&announcement.peer_id)? {
+                conflicts.push(KeyConflict::new(&pinned, &announcement, received_at));
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    pub fn remove_key_conflict(&self, peer_id: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "DELETE FROM key_conflicts WHERE peer_id = ?1",
+            params![peer_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // pin a new key for a peer after the user approved it, any safety
+    // number verification belonged to the old key
+    pub fn replace_peer_key(&self, peer_id: &str, public_key: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "UPDATE directory_entries SET public_key = ?2, key_verified = 0 WHERE peer_id = ?1",
+            params![peer_id, public_key],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // -- revoked peers --
+
+    // permanently record a revoked identity so replayed announcements cannot revive it
+    pub fn save_revocation(
+        &self,
+        peer_id: &str,
+        public_key: &str,
+        revoked_at: u64,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO revoked_peers (peer_id, public_key, revoked_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(peer_id) DO NOTHING",
+            params![peer_id, public_key, revoked_at as i64],
+        )
+        .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    pub fn count_revoked_peers(&self) -> Result<usize, io::Error> {
+        let conn = self.open_conn()?;
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM revoked_peers", [], |row| row.get(0))
+            .map_err(sqlite_to_io_error)?;
+        Ok(count.max(0) as usize)
+    }
+
+    pub fn is_peer_revoked(&self, peer_id: &str) -> bool {
+        let conn = match self.open_conn() {
+            Ok(conn) => conn,
+            Err(_) => return false,
+        };
+
+        conn.query_row(
+            "SELECT 1 FROM revoked_peers WHERE peer_id = ?1",
+            params![peer_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|row| row.is_some())
+        .unwrap_or(false)
+    }
+
+    // toggle friend status for a peer
+    pub fn set_friend_status(&self, peer_id: &str, is_friend: bool) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        let changed = conn
+            .execute(
+                "UPDATE directory_entries
+                 SET is_friend = ?2
+                 WHERE peer_id = ?1",
+                params![peer_id, if is_friend { 1_i64 } else { 0_i64 }],
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        if changed == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "peer not found in directory",
+            ));
+        }
+
+        Ok(())
+    }
+
+    // -- direct messages --
+
+    // save a dm conversation's metadata
+    pub fn save_dm_conversation(
+        &self,
+        conversation_id: &str,
+        meta: &DMConversationMeta,
+    ) -> Result<(), io::Error> {
+        let last_message = meta
+            .last_message
+            .as_deref()
+            .map(|text| self.seal_text(text))
+            .transpose()?;
+        let conn = self.open_conn()?;
+        conn.execute(
+            "INSERT INTO dm_conversations (
+                conversation_id, peer_id, display_name, last_message, last_message_time,
+                unread_count, pinned, archived
+            )
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(conversation_id) DO UPDATE SET
+                peer_id = excluded.peer_id,
+                display_name = excluded.display_name,
+                last_message = excluded.last_message,
+                last_message_time = excluded.last_message_time,
+                unread_count = excluded.unread_count,
+                pinned = excluded.pinned,
+                archived = excluded.archived",
+            params![
+                conversation_id,
+                meta.peer_id,
+                meta.display_name,
+                last_message,
+                meta.last_message_time.map(|ts| ts as i64),
+                meta.unread_count as i64,
+                meta.pinned as i64,
+                meta.archived as i64
+            ],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        Ok(())
+    }
+
+    // load a single dm conversation's metadata
+    pub fn load_dm_conversation(
+        &self,
+        conversation_id: &str,
+    ) -> Result<DMConversationMeta, io::Error> {
+        let conn = self.open_conn()?;
+
+        let meta = conn
+            .query_row(
+                "SELECT peer_id, display_name, last_message, last_message_time, unread_count,
+                        pinned, archived
+                 FROM dm_conversations
+                 WHERE conversation_id = ?1",
+                params![conversation_id],
+                |row| {
+                    let last_message_time: Option<i64> = row.get(3)?;
+                    let unread_count: i64 = row.get(4)?;
+
+                    Ok(DMConversationMeta {
+                        peer_id: row.get(0)?,
+                        display_name: row.get(1)?,
+                        last_message: row.get(2)?,
+                        last_message_time: last_message_time.map(|ts| ts.max(0) as u64),
+                        unread_count: unread_count.max(0) as u32,
+                        pinned: row.get::<_, i64>(5)? != 0,
+                        archived: row.get::<_, i64>(6)? != 0,
+                    })
+                },
+            )
+            .optional()
+            .map_err(sqlite_to_io_error)?;
+
+        let mut meta = meta
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "dm conversation not found"))?;
+        self.open_dm_conversation(&mut meta)?;
+        Ok(meta)
+    }
+
+    // load all dm conversations, pinned first then most recent. archived
+    // conversations are left out unless asked for
+    pub fn load_all_dm_conversations(
+        &self,
+        include_archived: bool,
+    ) -> Result<Vec<(String, DMConversationMeta)>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT
+                    conversation_id,
+                    peer_id,
+                    display_name,
+                    last_message,
+                    last_message_time,
+                    unread_count,
+                    pinned,
+                    archived
+                 FROM dm_conversations
+                 WHERE ?1 OR archived = 0
+                 ORDER BY pinned DESC, COALESCE(last_message_time, 0) DESC, display_name ASC,
+                    conversation_id ASC",
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        let rows = stmt
+            .query_map(params![include_archived], dm_conversation_from_row)
+            .map_err(sqlite_to_io_error)?;
+
+        let mut conversations = Vec::new();
+        for row in rows {
+            let (conversation_id, mut meta) = row.map_err(sqlite_to_io_error)?;
+            self.open_dm_conversation(&mut meta)?;
+            conversations.push((conversation_id, meta));
+        }
+
+        Ok(conversations)
+    }
+
+    // peers whose dm topics we keep joined: every conversation, archived or
+    // not, plus friends we haven't messaged yet
+    pub fn load_dm_peer_ids(&self) -> Result<Vec<String>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT peer_id FROM dm_conversations
+                 UNION
+                 SELECT peer_id FROM directory_entries WHERE is_friend = 1",
+            )
+            .map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(sqlite_to_io_error)?;
+
+        let mut peer_ids = Vec::new();
+        for row in rows {
+            peer_ids.push(row.map_err(sqlite_to_io_error)?);
+        }
+        Ok(peer_ids)
+    }
+
+    // one page of dm conversations in sidebar order. the cursor is the one
+    // returned with the previous page, none starts from the top. the unread
+    // totals cover every conversation in scope, not just this page
+    pub fn load_dm_conversation_page(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+        unread_only: bool,
+        include_archived: bool,
+    ) -> Result<DMConversationPage, io::Error> {
+        let conn = self.open_conn()?;
+
+        let (total_unread, unread_conversations): (i64, i64) = conn
+            .query_row(
+                "SELECT COALESCE(SUM(unread_count), 0), COUNT(CASE WHEN unread_count > 0 THEN 1 END)
+                 FROM dm_conversations
+                 WHERE ?1 OR archived = 0",
+                params![include_archived],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        let mut page = DMConversationPage {
+            conversations: Vec::new(),
+            next_cursor: None,
+            total_unread: total_unread.max(0) as u32,
+            unread_conversations: unread_conversations.max(0) as u32,
+        };
+        if limit == 0 {
+            return Ok(page);
+        }
+
+        let mut sql = String::from(
+            "SELECT
+                conversation_id,
+                peer_id,
+                display_name,
+                last_message,
+                last_message_time,
+                unread_count,
+                pinned,
+                archived
+             FROM dm_conversations
+             WHERE (? OR archived = 0)",
+        );
+        let mut values: Vec<SqlValue> = vec![SqlValue::Integer(include_archived as i64)];
+
+        if unread_only {
+            sql.push_str(" AND unread_count > 0");
+        }
+
+        // keyset on the sort columns so pages stay stable while new
+        // messages reorder the conversations above the cursor
+        if let Some(cursor) = cursor {
+            let (pinned, time, conversation_id, display_name) =
+                parse_dm_conversation_cursor(cursor).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "invalid conversation cursor")
+                })?;
+            sql.push_str(
+                " AND (pinned < ?
+                    OR (pinned = ? AND COALESCE(last_message_time, 0) < ?)
+                    OR (pinned = ? AND COALESCE(last_message_time, 0) = ? AND display_name > ?)
+                    OR (pinned = ? AND COALESCE(last_message_time, 0) = ? AND display_name = ?
+                        AND conversation_id > ?))",
+            );
+            values.extend([
+                SqlValue::Integer(pinned),
+                SqlValue::Integer(pinned),
+                SqlValue::Integer(time),
+                SqlValue::Integer(pinned),
+                SqlValue::Integer(time),
+                SqlValue::Text(display_name.to_string()),
+                SqlValue::Integer(pinned),
+                SqlValue::Integer(time),
+                SqlValue::Text(display_name.to_string()),
+                SqlValue::Text(conversation_id.to_string()),
+            ]);
+        }
+
+        // one extra row tells us whether there is another page
+        sql.push_str(
+            " ORDER BY pinned DESC, COALESCE(last_message_time, 0) DESC, display_name ASC,
+                conversation_id ASC
+              LIMIT ?",
+        );
+        values.push(SqlValue::Integer(limit as i64 + 1));
+
+        let mut stmt = conn.prepare(&sql).map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map(params_from_iter(values.iter()), dm_conversation_from_row)
+            .map_err(sqlite_to_io_error)?;
+
+        let mut conversations = Vec::new();
+        for row in rows {
+            let (conversation_id, mut meta) = row.map_err(sqlite_to_io_error)?;
+            self.open_dm_conversation(&mut meta)?;
+            conversations.push((conversation_id, meta));
+        }
+
+        if conversations.len() > limit {
+            conversations.truncate(limit);
+            page.next_cursor = conversations.last().map(|(conversation_id, meta)| {
+                format!(
+                    "{}:{}:{}:{}",
+                    meta.pinned as i64,
+                    meta.last_message_time.unwrap_or(0),
+                    conversation_id,
+                    meta.display_name
+                )
+            });
+        }
+        page.conversations = conversations.into_iter().map(|(_, meta)| meta).collect();
+
+        Ok(page)
+    }
+
+    // pin or unpin a dm conversation in the sidebar
+    pub fn set_dm_pinned(&self, conversation_id: &str, pinned: bool) -> Result<(), io::Error> {
+        self.set_dm_conversation_flag(conversation_id, "pinned", pinned)
+    }
+
+    // archive or restore a dm conversation, messages are kept either way
+    pub fn set_dm_archived(&self, conversation_id: &str, archived: bool) -> Result<(), io::Error> {
+        self.set_dm_conversation_flag(conversation_id, "archived", archived)
+    }
+
+    fn set_dm_conversation_flag(
+        &self,
+        conversation_id: &str,
+        column: &str,
+        value: bool,
+    ) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        let updated = conn
+            .execute(
+                &format!(
+                    "UPDATE dm_conversations SET {} = ?2 WHERE conversation_id = ?1",
+                    column
+                ),
+                params![conversation_id, value as i64],
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        if updated == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "dm conversation not found",
+            ));
+        }
+        Ok(())
+    }
+
+    // remove a dm conversation and all its messages
+    pub fn remove_dm_conversation(&self, conversation_id: &str) -> Result<(), io::Error> {
+        let conn = self.open_conn()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
+
+        tx.execute(
+            "DELETE FROM dm_messages WHERE conversation_id = ?1",
+            params![conversation_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        tx.execute(
+            "DELETE FROM dm_reactions WHERE conversation_id = ?1",
+            params![conversation_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        tx.execute(
+            "DELETE FROM dm_conversations WHERE conversation_id = ?1",
+            params![conversation_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        tx.execute(
+            "DELETE FROM dm_ephemeral_policies WHERE conversation_id = ?1",
+            params![conversation_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        tx.execute(
+            "DELETE FROM dm_policy_log WHERE conversation_id = ?1",
+            params![conversation_id],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        if self.fts_enabled() {
+            tx.execute(
+                "DELETE FROM dm_message_fts WHERE conversation_id = ?1",
+                params![conversation_id],
+            )
+            .map_err(sqlite_to_io_error)?;
+        }
+
+        tx.commit().map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
+
+    // append a message to a dm conversation's message log
+    pub fn append_dm_message(
+        &self,
+        conversation_id: &str,
+        message: &DirectMessage,
+    ) -> Result<bool, io::Error> {
+        let content = self.seal_text(&message.content)?;
+        let received_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let conn = self.open_conn()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
+
+        // a deleted message stays deleted when catch-up brings it back
+        let deleted = tx
+            .query_row(
+                "SELECT 1 FROM dm_tombstones WHERE conversation_id = ?1 AND message_id = ?2",
+                params![conversation_id, message.id],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(sqlite_to_io_error)?
+            .is_some();
+        if deleted {
+            return Ok(false);
+        }
+
+        // ensure a placeholder conversation exists so writes never fail on first contact
+        tx.execute(
+            "INSERT INTO dm_conversations (
+                conversation_id, peer_id, display_name, last_message, last_message_time, unread_count
+            ) VALUES (?1, ?2, ?3, NULL, NULL, 0)
+            ON CONFLICT(conversation_id) DO NOTHING",
+            params![conversation_id, message.from_peer, message.from_display_name],
+        )
+        .map_err(sqlite_to_io_error)?;
+
+        let inserted = tx
+            .execute(
+                "INSERT OR IGNORE INTO dm_messages (
+                    id, conversation_id, from_peer, to_peer, from_display_name, content, timestamp,
+                    reply_to, attachments, edited, received_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    message.id,
+                    conversation_id,
+                    message.from_peer,
+                    message.to_peer,
+                    message.from_display_name,
+                    content,
+                    message.timestamp as i64,
+                    message.reply_to,
+                    attachments_json(&message.attachments),
+                    message.edited,
+                    received_at
+                ],
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        if inserted > 0 && self.fts_active() {
+            tx.execute(
+                "INSERT INTO dm_message_fts (message_id, conversation_id, content)
+                 VALUES (?1, ?2, ?3)",
+                params![
+                    message.id,
+                    conversation_id,
+                    dm_search_text(&message.content, &message.attachments)
+                ],
+            )
+            .map_err(sqlite_to_io_error)?;
+        }
+
+        tx.commit().map_err(sqlite_to_io_error)?;
+        Ok(inserted > 0)
+    }
+
+    // when a stored dm reached this end, by its own timestamp for rows from
+    // before that was kept. none when the message isn't stored
+    pub fn dm_received_at(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+    ) -> Result<Option<u64>, io::Error> {
+        let conn = self.open_conn()?;
+        conn.query_row(
+            "SELECT COALESCE(received_at, timestamp) FROM dm_messages
+             WHERE conversation_id = ?1 AND id = ?2",
+            params![conversation_id, message_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map(|received_at| received_at.map(|t| t.max(0) as u64))
+        .map_err(sqlite_to_io_error)
+    }
+
+    // load dm messages with optional pagination
+    pub fn load_dm_messages(
+        &self,
+        conversation_id: &str,
+        before: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<DirectMessage>, io::Error> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let conn = self.open_conn()?;
+        let mut sql = String::from(
+            "SELECT id, from_peer, to_peer, from_display_name, content, timestamp,
+                    reply_to, attachments, edited
+             FROM dm_messages
+             WHERE conversation_id = ?1",
+        );
+
+        let mut values: Vec<SqlValue> = vec![SqlValue::Text(conversation_id.to_string())];
+
+        if let Some(before_ts) = before {
+            sql.push_str(" AND timestamp < ?2");
+            values.push(SqlValue::Integer(before_ts as i64));
+        }
+
+        sql.push_str(" ORDER BY timestamp DESC LIMIT ?");
+        values.push(SqlValue::Integer(limit as i64));
+
+        let mut stmt = conn.prepare(&sql).map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map(params_from_iter(values.iter()), direct_message_from_row)
+            .map_err(sqlite_to_io_error)?;
+
+        let mut messages = Vec::new();
+        for row in rows {
+            messages.push(row.map_err(sqlite_to_io_error)?);
+        }
+        self.open_dm_messages(&mut messages)?;
+        attach_dm_reactions(&conn, conversation_id, &mut messages)?;
+
+        // keep frontend contract stable with ascending timestamps
+        messages.reverse();
+        Ok(messages)
+    }
+
+    // dms sent and received per utc day since the given time, oldest day first.
+    // days are keyed by their start in unix millis, empty days are left out
+    pub fn count_dm_messages_per_day(&self, since: u64) -> Result<Vec<(u64, u64)>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT timestamp / 86400000 AS day, COUNT(*)
+                 FROM dm_messages
+                 WHERE timestamp >= ?1
+                 GROUP BY day
+                 ORDER BY day ASC",
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        let rows = stmt
+            .query_map(params![since as i64], |row| {
+                let day: i64 = row.get(0)?;
+                let count: i64 = row.get(1)?;
+                Ok((day.max(0) as u64 * 86_400_000, count.max(0) as u64))
+            })
+            .map_err(sqlite_to_io_error)?;
+
+        let mut days = Vec::new();
+        for row in rows {
+            days.push(row.map_err(sqlite_to_io_error)?);
+        }
+        Ok(days)
+    }
+
+    pub fn count_all_dm_messages(&self) -> Result<usize, io::Error> {
+        let conn = self.open_conn()?;
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM dm_messages", [], |row| row.get(0))
+            .map_err(sqlite_to_io_error)?;
+        Ok(count.max(0) as usize)
+    }
+
+    pub fn count_dm_messages(&self, conversation_id: &str) -> Result<usize, io::Error> {
+        let conn = self.open_conn()?;
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM dm_messages WHERE conversation_id = ?1",
+                params![conversation_id],
+                |row| row.get(0),
+            )
+            .map_err(sqlite_to_io_error)?;
+        Ok(count.max(0) as usize)
+    }
+
+    // message count plus first and last timestamps for a conversation
+    pub fn dm_conversation_stats(
+        &self,
+        conversation_id: &str,
+    ) -> Result<(usize, Option<u64>, Option<u64>), io::Error> {
+        let conn = self.open_conn()?;
+        conn.query_row(
+            "SELECT COUNT(*), MIN(timestamp), MAX(timestamp)
+             FROM dm_messages
+             WHERE conversation_id = ?1",
+            params![conversation_id],
+            |row| {
+                let count: i64 = row.get(0)?;
+                let first: Option<i64> = row.get(1)?;
+                let last: Option<i64> = row.get(2)?;
+                Ok((
+                    count.max(0) as usize,
+                    first.map(|ts| ts.max(0) as u64),
+                    last.map(|ts| ts.max(0) as u64),
+                ))
+            },
+        )
+        .map_err(sqlite_to_io_error)
+    }
+
+    // walk every message in a conversation oldest first without loading them all at once
+    pub fn for_each_dm_message<F>(&self, conversation_id: &str, mut f: F) -> Result<usize, io::Error>
+    where
+        F: FnMut(&DirectMessage) -> Result<(), io::Error>,
+    {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, from_peer, to_peer, from_display_name, content, timestamp,
+                        reply_to, attachments, edited
+                 FROM dm_messages
+                 WHERE conversation_id = ?1
+                 ORDER BY timestamp ASC, id ASC",
+            )
+            .map_err(sqlite_to_io_error)?;
+
+        let rows = stmt
+            .query_map(params![conversation_id], direct_message_from_row)
+            .map_err(sqlite_to_io_error)?;
+
+        let mut visited = 0;
+        for row in rows {
+            let mut message = row.map_err(sqlite_to_io_error)?;
+            message.content = self.open_text(message.content)?;
+            f(&message)?;
+            visited += 1;
+        }
+
+        Ok(visited)
+    }
+
+    // search dm messages with filters and indexed query execution
+    pub fn search_dm_messages(
+        &self,
+        conversation_id: &str,
+        params: &DmSearchParams,
+    ) -> Result<Vec<DirectMessage>, io::Error> {
+        let limit = params.limit.clamp(1, 1000);
+        let query = params
+            .query
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty());
+
+        if self.at_rest_encrypted() {
+            return self.search_sealed_dm_messages(conversation_id, params, query, limit);
+        }
+
+        let conn = self.open_conn()?;
+        let tokenizer = self.active_search_tokenizer(&conn);
+        let fts_query = query.and_then(|q| build_fts_query(q, tokenizer));
+
+        let mut sql;
+        let mut values: Vec<SqlValue> = Vec::new();
+
+        if self.fts_active() && fts_query.is_some() {
+            sql = String::from(
+                "SELECT
+                    m.id,
+                    m.from_peer,
+                    m.to_peer,
+                    m.from_display_name,
+                    m.content,
+                    m.timestamp,
+                    m.reply_to,
+                    m.attachments,
+                    m.edited
+                 FROM dm_messages m
+                 JOIN dm_message_fts f ON f.message_id = m.id
+                 WHERE m.conversation_id = ?1
+                   AND f.conversation_id = ?2
+                   AND f.content MATCH ?3",
+            );
+            values.push(SqlValue::Text(conversation_id.to_string()));
+            values.push(SqlValue::Text(conversation_id.to_string()));
+            values.push(SqlValue::Text(fts_query.unwrap_or_default()));
+        } else {
+            sql = String::from(
+                "SELECT
+                    m.id,
+                    m.from_peer,
+                    m.to_peer,
+                    m.from_display_name,
+                    m.content,
+                    m.timestamp,
+                    m.reply_to,
+                    m.attachments,
+                    m.edited
+                 FROM dm_messages m
+                 WHERE m.conversation_id = ?1",
+            );
+            values.push(SqlValue::Text(conversation_id.to_string()));
+
+            if let Some(text_query) = query {
+                sql.push_str(" AND lower(m.content) LIKE lower(?)");
+                values.push(SqlValue::Text(format!("%{}%", text_query)));
+            }
+        }
+
+        if let Some(from_peer) = params
+            .from_peer
+            .as_deref()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+        {
+            sql.push_str(" AND m.from_peer = ?");
+            values.push(SqlValue::Text(from_peer.to_string()));
+        }
+
+        if params.mentions_only {
+            sql.push_str(" AND m.content LIKE '%<@%'");
+        }
+
+        if let Some(after) = params.date_after {
+            sql.push_str(" AND m.timestamp >= ?");
+            values.push(SqlValue::Integer(after as i64));
+        }
+
+        if let Some(before) = params.date_before {
+            sql.push_str(" AND m.timestamp <= ?");
+            values.push(SqlValue::Integer(before as i64));
+        }
+
+        if let Some(media_filter) = params.media_filter.as_deref() {
+            append_media_filter(&mut sql, &mut values, media_filter);
+        }
+
+        sql.push_str(" ORDER BY m.timestamp DESC LIMIT ?");
+        values.push(SqlValue::Integer(limit as i64));
+
+        let mut stmt = conn.prepare(&sql).map_err(sqlite_to_io_error)?;
+        let rows = stmt
+            .query_map(params_from_iter(values.iter()), direct_message_from_row)
+            .map_err(sqlite_to_io_error)?;
+
+        let mut messages = Vec::new();
+        for row in rows {
+            messages.push(row.map_err(sqlite_to_io_error)?);
+        }
+        attach_dm_reactions(&conn, conversation_id, &mut messages)?;
+
+        messages.reverse();
+        Ok(messages)
+    }
+
+    // search_dm_messages for encrypted storage, where sql can't see the
+    // text. the conversation is opened and filtered here instead
+    fn search_sealed_dm_messages(
+        &self,
+        conversation_id: &str,
+        params: &DmSearchParams,
+        query: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<DirectMessage>, io::Error> {
+        let query = query.map(str::to_lowercase);
+        let from_peer = params
+            .from_peer
+            .as_deref()
+            .map(str::trim)
+            .filter(|id| !id.is_empty());
+
+        let mut matches = Vec::new();
+        self.for_each_dm_message(conversation_id, |message| {
+            let content = message.content.to_lowercase();
+            let keep = query.as_deref().is_none_or(|q| content.contains(q))
+                && from_peer.is_none_or(|peer| message.from_peer == peer)
+                && (!params.mentions_only || message.content.contains("<@"))
+                && params
+                    .date_after
+                    .is_none_or(|after| message.timestamp >= after)
+                && params
+                    .date_before
+                    .is_none_or(|before| message.timestamp <= before)
+                && params
+                    .media_filter
+                    .as_deref()
+                    .is_none_or(|filter| media_filter_matches(message, &content, filter));
+            if keep {
+                matches.push(message.clone());
+            }
+            Ok(())
+        })?;
+
+        // oldest first already, keep the newest `limit`
+        let mut messages = matches.split_off(matches.len().saturating_sub(limit));
+        let conn = self.open_conn()?;
+        attach_dm_reactions(&conn, conversation_id, &mut messages)?;
+        Ok(messages)
+    }
+
+    pub fn load_dm_message(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+    ) -> Result<Option<DirectMessage>, io::Error> {
+        let conn = self.open_conn()?;
+        let message = conn
+            .query_row(
+                "SELECT id, from_peer, to_peer, from_display_name, content, timestamp,
+                        reply_to, attachments, edited
+                 FROM dm_messages
+                 WHERE conversation_id = ?1 AND id = ?2",
+                params![conversation_id, message_id],
+                direct_message_from_row,
+            )
+            .optional()
+            .map_err(sqlite_to_io_error)?;
+        let Some(message) = message else {
+            return Ok(None);
+        };
+        let mut messages = [message];
+        self.open_dm_messages(&mut messages)?;
+        attach_dm_reactions(&conn, conversation_id, &mut messages)?;
+        let [message] = messages;
+        Ok(Some(message))
+    }
+
+    // replace the text of a dm written by `author`, None when there is no
+    // such message of theirs
+    pub fn edit_dm_message(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+        author: &str,
+        content: &str,
+    ) -> Result<Option<DirectMessage>, io::Error> {
+        let stored = self.seal_text(content)?;
+        let conn = self.open_conn()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
+
+        let updated = tx
+            .execute(
+                "UPDATE dm_messages SET content = ?4, edited = 1
+                 WHERE conversation_id = ?1 AND id = ?2 AND from_peer = ?3",
+                params![conversation_id, message_id, author, stored],
+            )
+            .map_err(sqlite_to_io_error)?;
+        if updated == 0 {
+            return Ok(None);
+        }
+
+        if self.fts_active() {
+            let attachments: Option<String> = tx
+                .query_row(
+                    "SELECT attachments FROM dm_messages WHERE conversation_id = ?1 AND id = ?2",
+                    params![conversation_id, message_id],
+                    |row| row.get(0),
+                )
+                .map_err(sqlite_to_io_error)?;
+            tx.execute(
+                "UPDATE dm_message_fts SET content = ?2 WHERE message_id = ?1",
+                params![
+                    message_id,
+                    dm_search_text(content, &parse_attachments(attachments))
+                ],
+            )
+            .map_err(sqlite_to_io_error)?;
+        }
+
+        // the conversation list shows the latest text
+        tx.execute(
+            "UPDATE dm_conversations SET last_message = (
+                SELECT content FROM dm_messages
+                WHERE conversation_id = ?1
+                ORDER BY timestamp DESC, id DESC
+                LIMIT 1
+             )
+             WHERE conversation_id = ?1",
+            params![conversation_id],
         )
         .map_err(sqlite_to_io_error)?;
-        Ok(())
+
+        tx.commit().map_err(sqlite_to_io_error)?;
+        drop(conn);
+        self.load_dm_message(conversation_id, message_id)
     }
 
-    // toggle friend status for a peer
-    pub fn set_friend_status(&self, peer_id: &str, is_friend: bool) -> Result<(), io::Error> {
+    // add or take back one peer's reaction to a dm. returns the message's
+    // reactions afterwards, None when the message isn't in the conversation
+    pub fn set_dm_reaction(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+        peer_id: &str,
+        emoji: &str,
+        active: bool,
+        now: u64,
+    ) -> Result<Option<Vec<DMReaction>>, io::Error> {
         let conn = self.open_conn()?;
-        let changed = conn
-            .execute(
-                "UPDATE directory_entries
-                 SET is_friend = ?2
-                 WHERE peer_id = ?1",
-                params![peer_id, if is_friend { 1_i64 } else { 0_i64 }],
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM dm_messages WHERE conversation_id = ?1 AND id = ?2",
+                params![conversation_id, message_id],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(sqlite_to_io_error)?
+            .is_some();
+        if !exists {
+            return Ok(None);
+        }
+
+        if active {
+            conn.execute(
+                "INSERT OR IGNORE INTO dm_reactions (
+                    conversation_id, message_id, peer_id, emoji, created_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![conversation_id, message_id, peer_id, emoji, now as i64],
+            )
+            .map_err(sqlite_to_io_error)?;
+        } else {
+            conn.execute(
+                "DELETE FROM dm_reactions WHERE message_id = ?1 AND peer_id = ?2 AND emoji = ?3",
+                params![message_id, peer_id, emoji],
             )
             .map_err(sqlite_to_io_error)?;
+        }
+        drop(conn);
 
-        if changed == 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "peer not found in directory",
-            ));
+        Ok(self
+            .load_dm_message(conversation_id, message_id)?
+            .map(|message| message.reactions))
+    }
+
+    // delete specific messages from a conversation, returns the ids that existed
+    pub fn delete_dm_messages(
+        &self,
+        conversation_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<String>, io::Error> {
+        let deleted_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let conn = self.open_conn()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
+
+        let mut deleted = Vec::new();
+        for message_id in message_ids {
+            let removed = tx
+                .execute(
+                    "DELETE FROM dm_messages WHERE conversation_id = ?1 AND id = ?2",
+                    params![conversation_id, message_id],
+                )
+                .map_err(sqlite_to_io_error)?;
+            if removed == 0 {
+                continue;
+            }
+            tx.execute(
+                "INSERT OR IGNORE INTO dm_tombstones (conversation_id, message_id, deleted_at)
+                 VALUES (?1, ?2, ?3)",
+                params![conversation_id, message_id, deleted_at],
+            )
+            .map_err(sqlite_to_io_error)?;
+            tx.execute(
+                "DELETE FROM dm_reactions WHERE message_id = ?1",
+                params![message_id],
+            )
+            .map_err(sqlite_to_io_error)?;
+            if self.fts_enabled() {
+                tx.execute(
+                    "DELETE FROM dm_message_fts WHERE message_id = ?1",
+                    params![message_id],
+                )
+                .map_err(sqlite_to_io_error)?;
+            }
+            deleted.push(message_id.clone());
         }
 
-        Ok(())
+        // the conversation list must not keep showing deleted text
+        if !deleted.is_empty() {
+            tx.execute(
+                "UPDATE dm_conversations SET last_message = (
+                    SELECT content FROM dm_messages
+                    WHERE conversation_id = ?1
+                    ORDER BY timestamp DESC, id DESC
+                    LIMIT 1
+                 )
+                 WHERE conversation_id = ?1",
+                params![conversation_id],
+            )
+            .map_err(sqlite_to_io_error)?;
+        }
+
+        tx.commit().map_err(sqlite_to_io_error)?;
+        Ok(deleted)
     }
 
-    // -- direct messages --
+    // -- disappearing dm messages --
 
-    // save a dm conversation's metadata
-    pub fn save_dm_conversation(
+    pub fn load_dm_ephemeral_policy(
         &self,
         conversation_id: &str,
-        meta: &DMConversationMeta,
+    ) -> Result<Option<DMEphemeralPolicy>, io::Error> {
+        let conn = self.open_conn()?;
+        conn.query_row(
+            "SELECT peer_id, ttl_secs, pending_ttl_secs, pending_from, updated_at
+             FROM dm_ephemeral_policies
+             WHERE conversation_id = ?1",
+            params![conversation_id],
+            |row| {
+                let ttl_secs: Option<i64> = row.get(1)?;
+                let pending_ttl_secs: Option<i64> = row.get(2)?;
+                let updated_at: i64 = row.get(4)?;
+                Ok(DMEphemeralPolicy {
+                    peer_id: row.get(0)?,
+                    ttl_secs: ttl_secs.map(|t| t.max(0) as u64),
+                    pending_ttl_secs: pending_ttl_secs.map(|t| t.max(0) as u64),
+                    pending_from: row.get(3)?,
+                    updated_at: updated_at.max(0) as u64,
+                })
+            },
+        )
+        .optional()
+        .map_err(sqlite_to_io_error)
+    }
+
+    pub fn save_dm_ephemeral_policy(
+        &self,
+        conversation_id: &str,
+        policy: &DMEphemeralPolicy,
     ) -> Result<(), io::Error> {
         let conn = self.open_conn()?;
         conn.execute(
-            "INSERT INTO dm_conversations (
-                conversation_id, peer_id, display_name, last_message, last_message_time, unread_count
-            )
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-             ON CONFLICT(conversation_id) DO UPDATE SET
+            "INSERT INTO dm_ephemeral_policies (
+                conversation_id, peer_id, ttl_secs, pending_ttl_secs, pending_from, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT(conversation_id) DO UPDATE SET
                 peer_id = excluded.peer_id,
-                display_name = excluded.display_name,
-                last_message = excluded.last_message,
-                last_message_time = excluded.last_message_time,
-                unread_count = excluded.unread_count",
+                ttl_secs = excluded.ttl_secs,
+                pending_ttl_secs = excluded.pending_ttl_secs,
+                pending_from = excluded.pending_from,
+                updated_at = excluded.updated_at",
             params![
                 conversation_id,
-                meta.peer_id,
-                meta.display_name,
-                meta.last_message,
-                meta.last_message_time.map(|ts| ts as i64),
-                meta.unread_count as i64
+                policy.peer_id,
+                policy.ttl_secs.map(|t| t as i64),
+                policy.pending_ttl_secs.map(|t| t as i64),
+                policy.pending_from,
+                policy.updated_at as i64
             ],
         )
         .map_err(sqlite_to_io_error)?;
-
         Ok(())
     }
 
-    // load a single dm conversation's metadata
-    pub fn load_dm_conversation(
+    // append a signed policy step to the conversation's hash-chained policy
+    // log, as its author sent it
+    pub fn append_dm_policy_log(
         &self,
         conversation_id: &str,
-    ) -> Result<DMConversationMeta, io::Error> {
+        signal: &DMEphemeralSignal,
+    ) -> Result<DMPolicyLogEntry, io::Error> {
+        let (action, ttl_secs) = signal
+            .kind
+            .policy_step()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a policy step"))?;
+        let actor = signal.from_peer.as_str();
+        let timestamp = signal.timestamp;
         let conn = self.open_conn()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
 
-        let meta = conn
+        let last: Option<(i64, String)> = tx
             .query_row(
-                "SELECT peer_id, display_name, last_message, last_message_time, unread_count
-                 FROM dm_conversations
-                 WHERE conversation_id = ?1",
+                "SELECT seq, hash FROM dm_policy_log
+                 WHERE conversation_id = ?1
+                 ORDER BY seq DESC
+                 LIMIT 1",
                 params![conversation_id],
-                |row| {
-                    let last_message_time: Option<i64> = row.get(3)?;
-                    let unread_count: i64 = row.get(4)?;
-
-                    Ok(DMConversationMeta {
-                        peer_id: row.get(0)?,
-                        display_name: row.get(1)?,
-                        last_message: row.get(2)?,
-                        last_message_time: last_message_time.map(|ts| ts.max(0) as u64),
-                        unread_count: unread_count.max(0) as u32,
-                    })
-                },
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )
             .optional()
             .map_err(sqlite_to_io_error)?;
+        let (seq, prev_hash) = match last {
+            Some((seq, hash)) => (seq.max(0) as u64 + 1, hash),
+            None => (0, String::new()),
+        };
+        let hash = DMPolicyLogEntry::compute_hash(&prev_hash, seq, actor, action, ttl_secs, timestamp);
+
+        tx.execute(
+            "INSERT INTO dm_policy_log (
+                conversation_id, seq, actor, action, ttl_secs, timestamp, prev_hash, hash,
+                public_key, signature
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                conversation_id,
+                seq as i64,
+                actor,
+                action,
+                ttl_secs.map(|t| t as i64),
+                timestamp as i64,
+                prev_hash,
+                hash,
+                signal.public_key,
+                signal.signature
+            ],
+        )
+        .map_err(sqlite_to_io_error)?;
+        tx.commit().map_err(sqlite_to_io_error)?;
 
-        meta.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "dm conversation not found"))
+        Ok(DMPolicyLogEntry {
+            seq,
+            actor: actor.to_string(),
+            action: action.to_string(),
+            ttl_secs,
+            timestamp,
+            prev_hash,
+            hash,
+            public_key: signal.public_key.clone(),
+            signature: signal.signature.clone(),
+        })
     }
 
-    // load all dm conversations
-    pub fn load_all_dm_conversations(
-        &self,
-    ) -> Result<Vec<(String, DMConversationMeta)>, io::Error> {
+    pub fn load_dm_policy_log(&self, conversation_id: &str) -> Result<Vec<DMPolicyLogEntry>, io::Error> {
         let conn = self.open_conn()?;
         let mut stmt = conn
             .prepare(
-                "SELECT
-                    conversation_id,
-                    peer_id,
-                    display_name,
-                    last_message,
-                    last_message_time,
-                    unread_count
-                 FROM dm_conversations
-                 ORDER BY COALESCE(last_message_time, 0) DESC, display_name ASC",
+                "SELECT seq, actor, action, ttl_secs, timestamp, prev_hash, hash, public_key,
+                        signature
+                 FROM dm_policy_log
+                 WHERE conversation_id = ?1
+                 ORDER BY seq ASC",
             )
             .map_err(sqlite_to_io_error)?;
-
         let rows = stmt
-            .query_map([], |row| {
-                let last_message_time: Option<i64> = row.get(4)?;
-                let unread_count: i64 = row.get(5)?;
-
-                Ok((
-                    row.get::<_, String>(0)?,
-                    DMConversationMeta {
-                        peer_id: row.get(1)?,
-                        display_name: row.get(2)?,
-                        last_message: row.get(3)?,
-                        last_message_time: last_message_time.map(|ts| ts.max(0) as u64),
-                        unread_count: unread_count.max(0) as u32,
-                    },
-                ))
+            .query_map(params![conversation_id], |row| {
+                let seq: i64 = row.get(0)?;
+                let ttl_secs: Option<i64> = row.get(3)?;
+                let timestamp: i64 = row.get(4)?;
+                Ok(DMPolicyLogEntry {
+                    seq: seq.max(0) as u64,
+                    actor: row.get(1)?,
+                    action: row.get(2)?,
+                    ttl_secs: ttl_secs.map(|t| t.max(0) as u64),
+                    timestamp: timestamp.max(0) as u64,
+                    prev_hash: row.get(5)?,
+                    hash: row.get(6)?,
+                    public_key: row.get(7)?,
+                    signature: row.get(8)?,
+                })
             })
             .map_err(sqlite_to_io_error)?;
 
-        let mut conversations = Vec::new();
+        let mut entries = Vec::new();
         for row in rows {
-            conversations.push(row.map_err(sqlite_to_io_error)?);
+            entries.push(row.map_err(sqlite_to_io_error)?);
         }
-
-        Ok(conversations)
+        Ok(entries)
     }
 
-    // remove a dm conversation and all its messages
-    pub fn remove_dm_conversation(&self, conversation_id: &str) -> Result<(), io::Error> {
+    // start the disappearing timer for messages from one side of a conversation,
+    // only while the conversation has an agreed timer
+    pub fn mark_dm_messages_read(
+        &self,
+        conversation_id: &str,
+        from_peer: &str,
+        up_to: u64,
+        read_at: u64,
+    ) -> Result<usize, io::Error> {
         let conn = self.open_conn()?;
-        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
-
-        tx.execute(
-            "DELETE FROM dm_messages WHERE conversation_id = ?1",
-            params![conversation_id],
-        )
-        .map_err(sqlite_to_io_error)?;
-
-        tx.execute(
-            "DELETE FROM dm_conversations WHERE conversation_id = ?1",
-            params![conversation_id],
-        )
-        .map_err(sqlite_to_io_error)?;
-
-        if self.fts_enabled {
-            tx.execute(
-                "DELETE FROM dm_message_fts WHERE conversation_id = ?1",
-                params![conversation_id],
+        let updated = conn
+            .execute(
+                "UPDATE dm_messages SET read_at = ?4
+                 WHERE conversation_id = ?1
+                   AND from_peer = ?2
+                   AND timestamp <= ?3
+                   AND read_at IS NULL
+                   AND EXISTS (
+                       SELECT 1 FROM dm_ephemeral_policies p
+                       WHERE p.conversation_id = ?1 AND p.ttl_secs IS NOT NULL
+                   )",
+                params![conversation_id, from_peer, up_to as i64, read_at as i64],
             )
             .map_err(sqlite_to_io_error)?;
+        Ok(updated)
+    }
+
+    // delete every read message whose timer ran out, grouped per conversation
+    // as (conversation id, peer id, deleted message ids)
+    pub fn expire_dm_messages(&self, now: u64) -> Result<Vec<(String, String, Vec<String>)>, io::Error> {
+        let due: Vec<(String, String, String)> = {
+            let conn = self.open_conn()?;
+            let mut stmt = conn
+                .prepare(
+                    "SELECT m.conversation_id, p.peer_id, m.id
+                     FROM dm_messages m
+                     JOIN dm_ephemeral_policies p ON p.conversation_id = m.conversation_id
+                     WHERE p.ttl_secs IS NOT NULL
+                       AND m.read_at IS NOT NULL
+                       AND m.read_at + p.ttl_secs * 1000 <= ?1
+                     ORDER BY m.conversation_id",
+                )
+                .map_err(sqlite_to_io_error)?;
+            let rows = stmt
+                .query_map(params![now as i64], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })
+                .map_err(sqlite_to_io_error)?;
+            let mut due = Vec::new();
+            for row in rows {
+                due.push(row.map_err(sqlite_to_io_error)?);
+            }
+            due
+        };
+
+        let mut grouped: Vec<(String, String, Vec<String>)> = Vec::new();
+        for (conversation_id, peer_id, message_id) in due {
+            match grouped.last_mut() {
+                Some((cid, _, ids)) if *cid == conversation_id => ids.push(message_id),
+                _ => grouped.push((conversation_id, peer_id, vec![message_id])),
+            }
         }
 
-        tx.commit().map_err(sqlite_to_io_error)?;
-        Ok(())
+        let mut expired = Vec::new();
+        for (conversation_id, peer_id, ids) in grouped {
+            let deleted = self.delete_dm_messages(&conversation_id, &ids)?;
+            if !deleted.is_empty() {
+                expired.push((conversation_id, peer_id, deleted));
+            }
+        }
+        Ok(expired)
     }
 
-    // append a message to a dm conversation's message log
-    pub fn append_dm_message(
-        &self,
-        conversation_id: &str,
-        message: &DirectMessage,
-    ) -> Result<(), io::Error> {
+    // start a call log entry, returns false when the call is already known
+    // (invites arrive on both the pair and the inbox topic)
+    pub fn insert_call(&self, record: &CallRecord) -> Result<bool, io::Error> {
         let conn = self.open_conn()?;
-        let tx = conn.unchecked_transaction().map_err(sqlite_to_io_error)?;
-
-        // ensure a placeholder conversation exists so writes never fail on first contact
-        tx.execute(
-            "INSERT INTO dm_conversations (
-                conversation_id, peer_id, display_name, last_message, last_message_time, unread_count
-            ) VALUES (?1, ?2, ?3, NULL, NULL, 0)
-            ON CONFLICT(conversation_id) DO NOTHING",
-            params![conversation_id, message.from_peer, message.from_display_name],
-        )
-        .map_err(sqlite_to_io_error)?;
-
-        let inserted = tx
+        let inserted = conn
             .execute(
-                "INSERT OR IGNORE INTO dm_messages (
-                    id, conversation_id, from_peer, to_peer, from_display_name, content, timestamp
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                "INSERT OR IGNORE INTO call_history (
+                    call_id, peer_id, display_name, direction, status, started_at, answered_at, ended_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, NULL, NULL)",
                 params![
-                    message.id,
-                    conversation_id,
-                    message.from_peer,
-                    message.to_peer,
-                    message.from_display_name,
-                    message.content,
-                    message.timestamp as i64
+                    record.call_id,
+                    record.peer_id,
+                    record.display_name,
+                    record.direction.as_str(),
+                    record.status.as_str(),
+                    record.started_at as i64
                 ],
             )
             .map_err(sqlite_to_io_error)?;
+        Ok(inserted > 0)
+    }
 
-        if inserted > 0 && self.fts_enabled {
-            tx.execute(
-                "INSERT INTO dm_message_fts (message_id, conversation_id, content)
-                 VALUES (?1, ?2, ?3)",
-                params![message.id, conversation_id, message.content],
+    pub fn load_call(&self, call_id: &str) -> Result<Option<CallRecord>, io::Error> {
+        let conn = self.open_conn()?;
+        conn.query_row(
+            "SELECT call_id, peer_id, display_name, direction, status, started_at, answered_at, ended_at
+             FROM call_history
+             WHERE call_id = ?1",
+            params![call_id],
+            call_record_from_row,
+        )
+        .optional()
+        .map_err(sqlite_to_io_error)
+    }
+
+    // returns false when the call was already answered or has ended
+    pub fn mark_call_answered(&self, call_id: &str, answered_at: u64) -> Result<bool, io::Error> {
+        let conn = self.open_conn()?;
+        let updated = conn
+            .execute(
+                "UPDATE call_history SET status = ?2, answered_at = ?3
+                 WHERE call_id = ?1 AND answered_at IS NULL AND ended_at IS NULL",
+                params![call_id, CallStatus::Answered.as_str(), answered_at as i64],
             )
             .map_err(sqlite_to_io_error)?;
-        }
-
-        tx.commit().map_err(sqlite_to_io_error)?;
-        Ok(())
+        Ok(updated > 0)
     }
 
-    // load dm messages with optional pagination
-    pub fn load_dm_messages(
+    // close out a call. answered calls become completed, anything still ringing
+    // takes the given status. returns None when the call is unknown or already over
+    pub fn finish_call(
         &self,
-        conversation_id: &str,
-        before: Option<u64>,
-        limit: usize,
-    ) -> Result<Vec<DirectMessage>, io::Error> {
-        if limit == 0 {
-            return Ok(Vec::new());
-        }
-
+        call_id: &str,
+        ended_at: u64,
+        unanswered: CallStatus,
+    ) -> Result<Option<CallRecord>, io::Error> {
         let conn = self.open_conn()?;
-        let mut sql = String::from(
-            "SELECT id, from_peer, to_peer, from_display_name, content, timestamp
-             FROM dm_messages
-             WHERE conversation_id = ?1",
-        );
-
-        let mut values: Vec<SqlValue> = vec![SqlValue::Text(conversation_id.to_string())];
+        let updated = conn
+            .execute(
+                "UPDATE call_history
+                 SET status = CASE WHEN answered_at IS NULL THEN ?2 ELSE ?3 END,
+                     ended_at = ?4
+                 WHERE call_id = ?1 AND ended_at IS NULL",
+                params![
+                    call_id,
+                    unanswered.as_str(),
+                    CallStatus::Completed.as_str(),
+                    ended_at as i64
+                ],
+            )
+            .map_err(sqlite_to_io_error)?;
+        drop(conn);
 
-        if let Some(before_ts) = before {
-            sql.push_str(" AND timestamp < ?2");
-            values.push(SqlValue::Integer(before_ts as i64));
+        if updated == 0 {
+            return Ok(None);
         }
+        self.load_call(call_id)
+    }
 
-        sql.push_str(" ORDER BY timestamp DESC LIMIT ?");
-        values.push(SqlValue::Integer(limit as i64));
-
-        let mut stmt = conn.prepare(&sql).map_err(sqlite_to_io_error)?;
+    // most recent calls first
+    pub fn load_call_history(&self, limit: usize) -> Result<Vec<CallRecord>, io::Error> {
+        let conn = self.open_conn()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT call_id, peer_id, display_name, direction, status, started_at, answered_at, ended_at
+                 FROM call_history
+                 ORDER BY started_at DESC
+                 LIMIT ?1",
+            )
+            .map_err(sqlite_to_io_error)?;
         let rows = stmt
-            .query_map(params_from_iter(values.iter()), direct_message_from_row)
+            .query_map(params![limit as i64], call_record_from_row)
             .map_err(sqlite_to_io_error)?;
 
-        let mut messages = Vec::new();
+        let mut calls = Vec::new();
         for row in rows {
-            messages.push(row.map_err(sqlite_to_io_error)?);
+            calls.push(row.map_err(sqlite_to_io_error)?);
         }
-
-        // keep frontend contract stable with ascending timestamps
-        messages.reverse();
-        Ok(messages)
+        Ok(calls)
     }
 
-    // search dm messages with filters and indexed query execution
-    pub fn search_dm_messages(
-        &self,
-        conversation_id: &str,
-        params: &DmSearchParams,
-    ) -> Result<Vec<DirectMessage>, io::Error> {
-        let limit = params.limit.clamp(1, 1000);
-        let query = params
-            .query
-            .as_deref()
-            .map(str::trim)
-            .filter(|q| !q.is_empty());
-
-        let fts_query = query.and_then(build_fts_query);
-
+    // close calls left open by a crash or force quit. the real end time is
+    // unknown, so answered calls get a zero duration rather than a huge one
+    pub fn close_open_calls(&self) -> Result<usize, io::Error> {
         let conn = self.open_conn()?;
-        let mut sql;
-        let mut values: Vec<SqlValue> = Vec::new();
-
-        if self.fts_enabled && fts_query.is_some() {
-            sql = String::from(
-                "SELECT
-                    m.id,
-                    m.from_peer,
-                    m.to_peer,
-                    m.from_display_name,
-                    m.content,
-                    m.timestamp
-                 FROM dm_messages m
-                 JOIN dm_message_fts f ON f.message_id = m.id
-                 WHERE m.conversation_id = ?1
-                   AND f.conversation_id = ?2
-                   AND f.content MATCH ?3",
-            );
-            values.push(SqlValue::Text(conversation_id.to_string()));
-            values.push(SqlValue::Text(conversation_id.to_string()));
-            values.push(SqlValue::Text(fts_query.unwrap_or_default()));
-        } else {
-            sql = String::from(
-                "SELECT
-                    m.id,
-                    m.from_peer,
-                    m.to_peer,
-                    m.from_display_name,
-                    m.content,
-                    m.timestamp
-                 FROM dm_messages m
-                 WHERE m.conversation_id = ?1",
-            );
-            values.push(SqlValue::Text(conversation_id.to_string()));
+        conn.execute(
+            "UPDATE call_history
+             SET status = CASE
+                     WHEN answered_at IS NOT NULL THEN ?1
+                     WHEN direction = ?2 THEN ?3
+                     ELSE ?4
+                 END,
+                 ended_at = COALESCE(answered_at, started_at)
+             WHERE ended_at IS NULL",
+            params![
+                CallStatus::Completed.as_str(),
+                CallDirection::Incoming.as_str(),
+                CallStatus::Missed.as_str(),
+                CallStatus::NoAnswer.as_str()
+            ],
+        )
+        .map_err(sqlite_to_io_error)
+    }
 
-            if let Some(text_query) = query {
-                sql.push_str(" AND lower(m.content) LIKE lower(?)");
-                values.push(SqlValue::Text(format!("%{}%", text_query)));
-            }
-        }
+    pub fn data_dir(&self) -> &Path {
+        &self.base_dir
+    }
 
-        if let Some(from_peer) = params
-            .from_peer
-            .as_deref()
-            .map(str::trim)
-            .filter(|id| !id.is_empty())
-        {
-            sql.push_str(" AND m.from_peer = ?");
-            values.push(SqlValue::Text(from_peer.to_string()));
+    // machine-specific caches, kept inside the data dir when portable so
+    // nothing is left behind on the host
+    pub fn cache_dir(&self) -> PathBuf {
+        if portable_mode() {
+            self.base_dir.join("cache")
+        } else {
+            std::env::temp_dir()
         }
+    }
 
-        if params.mentions_only {
-            sql.push_str(" AND m.content LIKE '%<@%'");
-        }
+    // copy all data to a new directory and point future launches at it. the
+    // database is copied with VACUUM INTO so the copy is consistent even with
+    // the wal open, and the destination is removed again if anything fails.
+    // the old files are only deleted once the new location is recorded
+    pub fn migrate_data_dir<F>(&self, dest: &Path, mut progress: F) -> Result<(), io::Error>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut files = Vec::new();
+        collect_files(&self.base_dir, &mut files)?;
+        files.retain(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            !name.starts_with(DB_FILE) && !name.starts_with(DATA_DIR_POINTER)
+        });
+        let total = files.len() + 1;
+
+        let created = !dest.exists();
+        fs::create_dir_all(dest)?;
+
+        let mut copy = || -> Result<(), io::Error> {
+            let conn = self.open_conn()?;
+            let key = match &*self.db_cipher.read().unwrap() {
+                DbCipher::Open(key) => Some(key.clone()),
+                _ => None,
+            };
+            match key {
+                // vacuum into would write the copy in plaintext
+                Some(key) => export_database(&conn, &dest.join(DB_FILE), Some(&key)),
+                None => conn
+                    .execute(
+                        "VACUUM INTO ?1",
+                        params![dest.join(DB_FILE).to_string_lossy().to_string()],
+                    )
+                    .map(|_| ()),
+            }
+            .map_err(sqlite_to_io_error)?;
+            drop(conn);
+            progress(1, total);
+
+            for (i, path) in files.iter().enumerate() {
+                let relative = path
+                    .strip_prefix(&self.base_dir)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                let target = dest.join(relative);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(path, &target)?;
+                progress(i + 2, total);
+            }
+            Ok(())
+        };
 
-        if let Some(after) = params.date_after {
-            sql.push_str(" AND m.timestamp >= ?");
-            values.push(SqlValue::Integer(after as i64));
+        if let Err(e) = copy() {
+            // roll back: leave the destination as we found it
+            if created {
+                let _ = fs::remove_dir_all(dest);
+            } else {
+                let _ = clear_dir(dest.to_path_buf());
+            }
+            return Err(e);
         }
 
-        if let Some(before) = params.date_before {
-            sql.push_str(" AND m.timestamp <= ?");
-            values.push(SqlValue::Integer(before as i64));
+        // write the pointer atomically so a crash never leaves a half path
+        let config_dir = project_dirs()?.config_dir().to_path_buf();
+        fs::create_dir_all(&config_dir)?;
+        let pointer = config_dir.join(DATA_DIR_POINTER);
+        let tmp = config_dir.join(format!("{}.tmp", DATA_DIR_POINTER));
+        fs::write(&tmp, dest.to_string_lossy().as_bytes())?;
+        fs::rename(&tmp, &pointer)?;
+
+        // only remove what we copied, the pointer may share this directory
+        for path in files {
+            let _ = fs::remove_file(path);
         }
-
-        if let Some(media_filter) = params.media_filter.as_deref() {
-            append_media_filter(&mut sql, &mut values, media_filter);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(self.base_dir.join(format!("{}{}", DB_FILE, suffix)));
         }
 
-        sql.push_str(" ORDER BY m.timestamp DESC LIMIT ?");
-        values.push(SqlValue::Integer(limit as i64));
-
-        let mut stmt = conn.prepare(&sql).map_err(sqlite_to_io_error)?;
-        let rows = stmt
-            .query_map(params_from_iter(values.iter()), direct_message_from_row)
-            .map_err(sqlite_to_io_error)?;
+        Ok(())
+    }
 
-        let mut messages = Vec::new();
-        for row in rows {
-            messages.push(row.map_err(sqlite_to_io_error)?);
-        }
+    // panic dumps live next to the database but outside of it, so a corrupt
+    // database cannot prevent them from being written
+    pub fn crash_dir(&self) -> PathBuf {
+        self.base_dir.join("crashes")
+    }
 
-        messages.reverse();
-        Ok(messages)
+    // rotating log files, like crash dumps kept out of the database
+    pub fn log_dir(&self) -> PathBuf {
+        self.base_dir.join("logs")
     }
 
     // wipe all user data
     // used when resetting identity to leave no traces on this client
     pub fn wipe_all_data(&self) -> Result<(), io::Error> {
+        // a database nobody can unlock is replaced whole, an unlocked one goes
+        // back to plaintext along with everything else
+        if self.is_database_locked() {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = fs::remove_file(self.base_dir.join(format!("{}{}", DB_FILE, suffix)));
+            }
+            *self.db_cipher.write().unwrap() = DbCipher::Plain;
+            self.prepare()?;
+        } else if self.is_database_encrypted() {
+            self.set_database_encryption(None)
+                .map_err(io::Error::other)?;
+        }
+
         let conn = self.open_conn()?;
 
         conn.execute("DELETE FROM key_value", [])
@@ -1186,24 +4457,67 @@ impl DiskStorage {
             .map_err(sqlite_to_io_error)?;
         conn.execute("DELETE FROM settings", [])
             .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM settings_profiles", [])
+            .map_err(sqlite_to_io_error)?;
         conn.execute("DELETE FROM verification_proof", [])
             .map_err(sqlite_to_io_error)?;
         conn.execute("DELETE FROM community_documents", [])
             .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM note_documents", [])
+            .map_err(sqlite_to_io_error)?;
         conn.execute("DELETE FROM community_meta", [])
             .map_err(sqlite_to_io_error)?;
         conn.execute("DELETE FROM directory_entries", [])
             .map_err(sqlite_to_io_error)?;
         conn.execute("DELETE FROM dm_messages", [])
             .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM dm_reactions", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM dm_tombstones", [])
+            .map_err(sqlite_to_io_error)?;
         conn.execute("DELETE FROM dm_conversations", [])
             .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM call_history", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM community_profiles", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM dm_ephemeral_policies", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM dm_policy_log", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM key_conflicts", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM community_departures", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM peer_addresses", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM channel_interest", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM event_log", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM channel_follows", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM community_folders", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM archived_communities", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM profile_hidden_communities", [])
+            .map_err(sqlite_to_io_error)?;
+        conn.execute("DELETE FROM community_placements", [])
+            .map_err(sqlite_to_io_error)?;
+        // nothing is left that the key protected
+        conn.execute("DELETE FROM storage_key", [])
+            .map_err(sqlite_to_io_error)?;
+        *self.at_rest.write().unwrap() = AtRest::Off;
 
-        if self.fts_enabled {
+        if self.fts_enabled() {
             conn.execute("DELETE FROM dm_message_fts", [])
                 .map_err(sqlite_to_io_error)?;
         }
 
+        // revoked_peers is intentionally kept, a local reset must not let
+        // replayed announcements from dead identities back into the directory
+
         // keep migration marker enabled so wiped clients do not re-import old json files
         conn.execute(
             "INSERT INTO app_meta (key, value) VALUES ('legacy_migrated', '1')
@@ -1212,10 +4526,42 @@ impl DiskStorage {
         )
         .map_err(sqlite_to_io_error)?;
 
+        // crash dumps carry recent logs, drop them with everything else
+        remove_if_exists(self.crash_dir())?;
+        // the current log file stays open, it is emptied instead
+        crate::logging::clear_files(&self.log_dir());
+
         self.cleanup_legacy_files()
     }
 }
 
+// add a column to an existing table when upgrading older databases
+fn ensure_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), io::Error> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(sqlite_to_io_error)?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(sqlite_to_io_error)?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .map_err(sqlite_to_io_error)?;
+    }
+
+    Ok(())
+}
+
 fn clear_dir(path: PathBuf) -> Result<(), io::Error> {
     if !path.exists() {
         return Ok(());
@@ -1233,6 +4579,20 @@ fn clear_dir(path: PathBuf) -> Result<(), io::Error> {
     Ok(())
 }
 
+// every regular file below dir, recursively
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), out)?;
+        } else if file_type.is_file() {
+            out.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
 fn remove_if_exists(path: PathBuf) -> Result<(), io::Error> {
     if !path.exists() {
         return Ok(());
@@ -1247,6 +4607,68 @@ fn remove_if_exists(path: PathBuf) -> Result<(), io::Error> {
     Ok(())
 }
 
+fn channel_follow_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ChannelFollow> {
+    Ok(ChannelFollow {
+        source_community_id: row.get(0)?,
+        source_channel_id: row.get(1)?,
+        target_community_id: row.get(2)?,
+        target_channel_id: row.get(3)?,
+        created_at: row.get::<_, i64>(4)? as u64,
+    })
+}
+
+fn directory_entry_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<DirectoryEntry> {
+    let last_seen: i64 = row.get(4)?;
+    let is_friend: i64 = row.get(5)?;
+    let verified: i64 = row.get(6)?;
+    let key_verified: i64 = row.get(10)?;
+    let placeholder: i64 = row.get(11)?;
+    Ok(DirectoryEntry {
+        peer_id: row.get(0)?,
+        display_name: row.get(1)?,
+        bio: row.get(2)?,
+        public_key: row.get(3)?,
+        last_seen: last_seen.max(0) as u64,
+        is_friend: is_friend != 0,
+        verified: verified != 0,
+        activity: row.get(7)?,
+        status_message: row.get(8)?,
+        status_emoji: row.get(9)?,
+        key_verified: key_verified != 0,
+        placeholder: placeholder != 0,
+    })
+}
+
+fn dm_conversation_from_row(
+    row: &rusqlite::Row<'_>,
+) -> rusqlite::Result<(String, DMConversationMeta)> {
+    let last_message_time: Option<i64> = row.get(4)?;
+    let unread_count: i64 = row.get(5)?;
+    Ok((
+        row.get(0)?,
+        DMConversationMeta {
+            peer_id: row.get(1)?,
+            display_name: row.get(2)?,
+            last_message: row.get(3)?,
+            last_message_time: last_message_time.map(|ts| ts.max(0) as u64),
+            unread_count: unread_count.max(0) as u32,
+            pinned: row.get::<_, i64>(6)? != 0,
+            archived: row.get::<_, i64>(7)? != 0,
+        },
+    ))
+}
+
+// cursors are pinned:last_message_time:conversation_id:display_name, the
+// name goes last since it may contain the separator
+fn parse_dm_conversation_cursor(cursor: &str) -> Option<(i64, i64, &str, &str)> {
+    let mut parts = cursor.splitn(4, ':');
+    let pinned = parts.next()?.parse().ok()?;
+    let time = parts.next()?.parse().ok()?;
+    let conversation_id = parts.next()?;
+    let display_name = parts.next()?;
+    Some((pinned, time, conversation_id, display_name))
+}
+
 fn direct_message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<DirectMessage> {
     let timestamp: i64 = row.get(5)?;
     Ok(DirectMessage {
@@ -1256,6 +4678,312 @@ fn direct_message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<DirectMe
         from_display_name: row.get(3)?,
         content: row.get(4)?,
         timestamp: timestamp.max(0) as u64,
+        reply_to: row.get(6)?,
+        attachments: parse_attachments(row.get(7)?),
+        edited: row.get(8)?,
+        reactions: Vec::new(),
+    })
+}
+
+// the salt sqlcipher keeps at the start of an encrypted database, none for a
+// plaintext or missing one
+fn database_salt(db_path: &Path) -> Option<Vec<u8>> {
+    let mut header = [0u8; 16];
+    let mut file = fs::File::open(db_path).ok()?;
+    io::Read::read_exact(&mut file, &mut header).ok()?;
+    (header[..] != *SQLITE_HEADER).then(|| header.to_vec())
+}
+
+// the raw key sqlcipher opens the database with. the salt is part of it so
+// sqlcipher writes it to the file header, where database_salt finds it again
+fn database_key(passphrase: &str, salt: &[u8]) -> Result<String, String> {
+    let (key, _) = keystore::storage_key(passphrase, salt)?;
+    Ok(format!("x'{}{}'", hex::encode(key), hex::encode(salt)))
+}
+
+// copy the database into a new file with sqlcipher, encrypted under key or
+// in plaintext without one
+fn export_database(conn: &Connection, dest: &Path, key: Option<&str>) -> rusqlite::Result<()> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS export KEY ?2",
+        params![dest.to_string_lossy().to_string(), key.unwrap_or("")],
+    )?;
+    let exported = conn.query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()));
+    conn.execute("DETACH DATABASE export", [])?;
+    exported
+}
+
+fn locked_error() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, STORAGE_LOCKED)
+}
+
+fn sealed_text_record(stored: &str) -> Option<Vec<u8>> {
+    stored
+        .strip_prefix(SEALED_TEXT_PREFIX)
+        .and_then(|encoded| hex::decode(encoded).ok())
+        .filter(|record| keystore::is_sealed_record(record))
+}
+
+fn open_sealed_text(key: &Key, record: &[u8]) -> Option<String> {
+    keystore::open_record(key, record)
+        .ok()
+        .and_then(|plaintext| String::from_utf8(plaintext).ok())
+}
+
+// seal every community document, dm text and conversation preview under
+// `key`. rows sealed under `old_key` are opened first, the rest are taken as
+// plaintext. returns how many rows were written
+// the wal still holds the pages the reseal overwrote, and the database file
+// can keep plaintext in pages freed before secure_delete was on. fold the wal
+// in, rebuild the file and truncate the wal so neither copy survives
+fn scrub_plaintext(conn: &Connection) -> rusqlite::Result<()> {
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    conn.execute_batch("VACUUM;")?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+}
+
+fn reseal_rows(conn: &Connection, old_key: Option<&Key>, key: &Key) -> Result<usize, String> {
+    let sql_err = |e: rusqlite::Error| format!("failed to encrypt storage: {}", e);
+    let reseal_text = |stored: String| -> Result<String, String> {
+        let plaintext = sealed_text_record(&stored)
+            .zip(old_key)
+            .and_then(|(record, old)| open_sealed_text(old, &record))
+            .unwrap_or(stored);
+        let record = keystore::seal_record(key, plaintext.as_bytes())?;
+        Ok(format!("{}{}", SEALED_TEXT_PREFIX, hex::encode(record)))
+    };
+    let reseal_bytes = |stored: Vec<u8>| -> Result<Vec<u8>, String> {
+        let plaintext = match old_key {
+            Some(old) if keystore::is_sealed_record(&stored) => {
+                keystore::open_record(old, &stored)?
+            }
+            _ => stored,
+        };
+        keystore::seal_record(key, &plaintext)
+    };
+    let mut resealed = 0;
+
+    let documents: Vec<(String, Vec<u8>)> = conn
+        .prepare("SELECT community_id, document FROM community_documents")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(sql_err)?;
+    for (community_id, stored) in documents {
+        conn.execute(
+            "UPDATE community_documents SET document = ?2 WHERE community_id = ?1",
+            params![community_id, reseal_bytes(stored)?],
+        )
+        .map_err(sql_err)?;
+        resealed += 1;
+    }
+
+    let notes: Vec<(String, Vec<u8>)> = conn
+        .prepare("SELECT note_id, document FROM note_documents")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(sql_err)?;
+    for (note_id, stored) in notes {
+        conn.execute(
+            "UPDATE note_documents SET document = ?2 WHERE note_id = ?1",
+            params![note_id, reseal_bytes(stored)?],
+        )
+        .map_err(sql_err)?;
+        resealed += 1;
+    }
+
+    let messages: Vec<(String, String)> = conn
+        .prepare("SELECT id, content FROM dm_messages")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(sql_err)?;
+    for (id, content) in messages {
+        conn.execute(
+            "UPDATE dm_messages SET content = ?2 WHERE id = ?1",
+            params![id, reseal_text(content)?],
+        )
+        .map_err(sql_err)?;
+        resealed += 1;
+    }
+
+    let previews: Vec<(String, String)> = conn
+        .prepare(
+            "SELECT conversation_id, last_message FROM dm_conversations
+             WHERE last_message IS NOT NULL",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(sql_err)?;
+    for (conversation_id, last_message) in previews {
+        conn.execute(
+            "UPDATE dm_conversations SET last_message = ?2 WHERE conversation_id = ?1",
+            params![conversation_id, reseal_text(last_message)?],
+        )
+        .map_err(sql_err)?;
+        resealed += 1;
+    }
+
+    let events: Vec<(i64, Vec<u8>)> = conn
+        .prepare("SELECT id, data FROM event_log")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(sql_err)?;
+    for (id, stored) in events {
+        conn.execute(
+            "UPDATE event_log SET data = ?2 WHERE id = ?1",
+            params![id, reseal_bytes(stored)?],
+        )
+        .map_err(sql_err)?;
+        resealed += 1;
+    }
+
+    Ok(resealed)
+}
+
+// the link and file filters of append_media_filter, for text sql can't see
+fn media_filter_matches(message: &DirectMessage, content: &str, media_filter: &str) -> bool {
+    let with_extension = |exts: &[&str], kind: AttachmentKind| {
+        message.attachments.iter().any(|a| a.kind == kind)
+            || exts.iter().any(|ext| {
+                content.ends_with(&format!(".{}", ext)) || content.contains(&format!(".{}?", ext))
+            })
+    };
+    match media_filter.to_lowercase().as_str() {
+        "images" => with_extension(
+            &[
+                "png", "jpg", "jpeg", "gif", "webp", "svg", "bmp", "ico", "avif",
+            ],
+            AttachmentKind::Image,
+        ),
+        "videos" => with_extension(&["mp4", "webm", "mov", "avi", "mkv"], AttachmentKind::Video),
+        "links" => content.contains("http://") || content.contains("https://"),
+        "files" => with_extension(
+            &[
+                "pdf", "doc", "docx", "xls", "xlsx", "zip", "rar", "7z", "tar", "gz",
+            ],
+            AttachmentKind::File,
+        ),
+        _ => true,
+    }
+}
+
+fn attachments_json(attachments: &[Attachment]) -> Option<String> {
+    if attachments.is_empty() {
+        return None;
+    }
+    serde_json::to_string(attachments).ok()
+}
+
+fn parse_attachments(json: Option<String>) -> Vec<Attachment> {
+    json.and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+// what the search index holds for a dm, attachment filenames are searchable
+// alongside the text
+fn dm_search_text(content: &str, attachments: &[Attachment]) -> String {
+    let mut text = content.to_string();
+    for attachment in attachments {
+        text.push('\n');
+        text.push_str(&attachment.filename);
+    }
+    text
+}
+
+// fill in reactions for messages of one conversation
+fn attach_dm_reactions(
+    conn: &Connection,
+    conversation_id: &str,
+    messages: &mut [DirectMessage],
+) -> Result<(), io::Error> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let sql = format!(
+        "SELECT message_id, emoji, peer_id
+         FROM dm_reactions
+         WHERE conversation_id = ? AND message_id IN ({})
+         ORDER BY created_at ASC",
+        vec!["?"; messages.len()].join(", ")
+    );
+    let mut values = vec![SqlValue::Text(conversation_id.to_string())];
+    values.extend(messages.iter().map(|m| SqlValue::Text(m.id.clone())));
+
+    let mut stmt = conn.prepare(&sql).map_err(sqlite_to_io_error)?;
+    let rows = stmt
+        .query_map(params_from_iter(values.iter()), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(sqlite_to_io_error)?;
+
+    let mut by_message: HashMap<String, Vec<DMReaction>> = HashMap::new();
+    for row in rows {
+        let (message_id, emoji, peer_id) = row.map_err(sqlite_to_io_error)?;
+        let reactions = by_message.entry(message_id).or_default();
+        match reactions.iter_mut().find(|r| r.emoji == emoji) {
+            Some(reaction) => reaction.peers.push(peer_id),
+            None => reactions.push(DMReaction {
+                emoji,
+                peers: vec![peer_id],
+            }),
+        }
+    }
+    for message in messages.iter_mut() {
+        if let Some(reactions) = by_message.remove(&message.id) {
+            message.reactions = reactions;
+        }
+    }
+    Ok(())
+}
+
+fn community_profile_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<CommunityProfile> {
+    let updated_at: i64 = row.get(4)?;
+    Ok(CommunityProfile {
+        community_id: row.get(0)?,
+        peer_id: row.get(1)?,
+        display_name: row.get(2)?,
+        avatar_seed: row.get(3)?,
+        timestamp: updated_at.max(0) as u64,
+    })
+}
+
+fn call_record_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<CallRecord> {
+    let direction: String = row.get(3)?;
+    let status: String = row.get(4)?;
+    let started_at: i64 = row.get(5)?;
+    let answered_at: Option<i64> = row.get(6)?;
+    let ended_at: Option<i64> = row.get(7)?;
+
+    let answered_at = answered_at.map(|ts| ts.max(0) as u64);
+    let ended_at = ended_at.map(|ts| ts.max(0) as u64);
+    let duration_secs = match (answered_at, ended_at) {
+        (Some(answered), Some(ended)) => ended.saturating_sub(answered) / 1000,
+        _ => 0,
+    };
+
+    Ok(CallRecord {
+        call_id: row.get(0)?,
+        peer_id: row.get(1)?,
+        display_name: row.get(2)?,
+        direction: CallDirection::parse(&direction),
+        status: CallStatus::parse(&status),
+        started_at: started_at.max(0) as u64,
+        answered_at,
+        ended_at,
+        duration_secs,
     })
 }
 
@@ -1270,10 +4998,11 @@ fn append_media_filter(sql: &mut String, values: &mut Vec<SqlValue>, media_filte
                 &[
                     "png", "jpg", "jpeg", "gif", "webp", "svg", "bmp", "ico", "avif",
                 ],
+                "image",
             );
         }
         "videos" => {
-            append_extension_filter(sql, values, &["mp4", "webm", "mov", "avi", "mkv"]);
+            append_extension_filter(sql, values, &["mp4", "webm", "mov", "avi", "mkv"], "video");
         }
         "links" => {
             sql.push_str(" AND (lower(m.content) LIKE ? OR lower(m.content) LIKE ?)");
@@ -1287,26 +5016,28 @@ fn append_media_filter(sql: &mut String, values: &mut Vec<SqlValue>, media_filte
                 &[
                     "pdf", "doc", "docx", "xls", "xlsx", "zip", "rar", "7z", "tar", "gz",
                 ],
+                "file",
             );
         }
         _ => {}
     }
 }
 
-fn append_extension_filter(sql: &mut String, values: &mut Vec<SqlValue>, exts: &[&str]) {
-    if exts.is_empty() {
-        return;
-    }
-
-    sql.push_str(" AND (");
+// links in the text with one of the extensions, or an attachment of the kind
+fn append_extension_filter(
+    sql: &mut String,
+    values: &mut Vec<SqlValue>,
+    exts: &[&str],
+    attachment_kind: &str,
+) {
+    sql.push_str(" AND (m.attachments LIKE ?");
+    values.push(SqlValue::Text(format!(
+        "%\"kind\":\"{}\"%",
+        attachment_kind
+    )));
 
-    let mut first = true;
     for ext in exts {
-        if !first {
-            sql.push_str(" OR ");
-        }
-        first = false;
-        sql.push_str("lower(m.content) LIKE ? OR lower(m.content) LIKE ?");
+        sql.push_str(" OR lower(m.content) LIKE ? OR lower(m.content) LIKE ?");
         values.push(SqlValue::Text(format!("%.{}", ext)));
         values.push(SqlValue::Text(format!("%.{}?%", ext)));
     }
@@ -1314,25 +5045,37 @@ fn append_extension_filter(sql: &mut String, values: &mut Vec<SqlValue>, exts: &
     sql.push(')');
 }
 
-fn build_fts_query(query: &str) -> Option<String> {
+// terms are quoted so punctuation kept inside them can't be read as query syntax
+fn build_fts_query(query: &str, tokenizer: SearchTokenizer) -> Option<String> {
     let terms: Vec<String> = query
         .split_whitespace()
         .map(|raw| {
             raw.chars()
-                .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
                 .collect::<String>()
         })
         .filter(|token| !token.is_empty())
-        .map(|token| format!("{}*", token))
         .collect();
 
     if terms.is_empty() {
         return None;
     }
 
+    let terms: Vec<String> = match tokenizer {
+        SearchTokenizer::Unicode61 => terms.iter().map(|t| format!("\"{}\"*", t)).collect(),
+        // trigrams can't match anything shorter than three characters, those
+        // searches fall back to a plain substring scan
+        SearchTokenizer::Trigram => {
+            if terms.iter().any(|t| t.chars().count() < 3) {
+                return None;
+            }
+            terms.iter().map(|t| format!("\"{}\"", t)).collect()
+        }
+    };
+
     Some(terms.join(" AND "))
 }
 
-fn sqlite_to_io_error(err: rusqlite::Error) -> io::Error {
+pub(super) fn sqlite_to_io_error(err: rusqlite::Error) -> io::Error {
     io::Error::new(io::ErrorKind::Other, err)
 }
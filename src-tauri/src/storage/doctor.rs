@@ -0,0 +1,215 @@
+use std::io;
+
+use serde::Serialize;
+
+use super::disk::{sqlite_to_io_error, DiskStorage};
+use crate::protocol::community::CommunityMeta;
+
+// integrity_check stops after this many problems
+const MAX_INTEGRITY_ERRORS: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+// result of a single check
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorFinding {
+    pub check: String,
+    pub severity: Severity,
+    pub message: String,
+    // true when this run fixed the problem
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub findings: Vec<DoctorFinding>,
+    // no warnings or errors left unrepaired
+    pub healthy: bool,
+}
+
+impl DoctorFinding {
+    fn ok(check: &str, message: impl Into<String>) -> Self {
+        Self {
+            check: check.to_string(),
+            severity: Severity::Ok,
+            message: message.into(),
+            repaired: false,
+        }
+    }
+
+    fn problem(check: &str, severity: Severity, message: impl Into<String>, repaired: bool) -> Self {
+        Self {
+            check: check.to_string(),
+            severity,
+            message: message.into(),
+            repaired,
+        }
+    }
+}
+
+// run every check, fixing what can be fixed when repair is set.
+// derive_meta rebuilds a community's cached meta from its crdt document,
+// which lives above the storage layer
+pub fn run<F>(storage: &DiskStorage, repair: bool, derive_meta: F) -> Result<DoctorReport, io::Error>
+where
+    F: Fn(&str) -> Result<CommunityMeta, String>,
+{
+    // a corrupt database makes every later check meaningless
+    let integrity = check_integrity(storage)?;
+    let corrupt = integrity.severity == Severity::Error;
+    let mut findings = vec![integrity];
+
+    if !corrupt {
+        findings.push(check_legacy_migration(storage, repair));
+        findings.push(check_fts_index(storage, repair)?);
+        findings.extend(check_community_meta(storage, repair, derive_meta)?);
+    }
+
+    let healthy = findings
+        .iter()
+        .all(|f| f.severity == Severity::Ok || f.repaired);
+    Ok(DoctorReport { findings, healthy })
+}
+
+fn check_integrity(storage: &DiskStorage) -> Result<DoctorFinding, io::Error> {
+    let conn = storage.open_conn()?;
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA integrity_check({})", MAX_INTEGRITY_ERRORS))
+        .map_err(sqlite_to_io_error)?;
+    let problems: Vec<String> = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(sqlite_to_io_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sqlite_to_io_error)?
+        .into_iter()
+        .filter(|line| line != "ok")
+        .collect();
+
+    if problems.is_empty() {
+        return Ok(DoctorFinding::ok("integrity", "database passed integrity check"));
+    }
+    // sqlite cannot repair pages in place, the user has to restore or reset
+    Ok(DoctorFinding::problem(
+        "integrity",
+        Severity::Error,
+        format!("database is corrupt: {}", problems.join("; ")),
+        false,
+    ))
+}
+
+// the migration marker is written before the best-effort cleanup, so leftover
+// json files mean a previous run stopped part way through
+fn check_legacy_migration(storage: &DiskStorage, repair: bool) -> DoctorFinding {
+    let remaining = storage.legacy_files_remaining();
+    if remaining.is_empty() {
+        return DoctorFinding::ok("legacy_migration", "no legacy files left");
+    }
+
+    let message = format!("{} legacy file(s) left from an unfinished migration", remaining.len());
+    if !repair {
+        return DoctorFinding::problem("legacy_migration", Severity::Warning, message, false);
+    }
+
+    // re-importing is idempotent, existing rows are upserted or deduplicated
+    match storage
+        .migrate_legacy_files()
+        .and_then(|_| storage.cleanup_legacy_files())
+    {
+        Ok(()) => DoctorFinding::problem("legacy_migration", Severity::Warning, message, true),
+        Err(e) => DoctorFinding::problem(
+            "legacy_migration",
+            Severity::Warning,
+            format!("{}, repair failed: {}", message, e),
+            false,
+        ),
+    }
+}
+
+fn check_fts_index(storage: &DiskStorage, repair: bool) -> Result<DoctorFinding, io::Error> {
+    if !storage.fts_enabled() {
+        return Ok(DoctorFinding::problem(
+            "fts_index",
+            Severity::Warning,
+            "full-text search is unavailable in this sqlite build, search falls back to LIKE",
+            false,
+        ));
+    }
+
+    let conn = storage.open_conn()?;
+    let messages: i64 = conn
+        .query_row("SELECT COUNT(*) FROM dm_messages", [], |row| row.get(0))
+        .map_err(sqlite_to_io_error)?;
+    let indexed: i64 = conn
+        .query_row("SELECT COUNT(*) FROM dm_message_fts", [], |row| row.get(0))
+        .map_err(sqlite_to_io_error)?;
+    drop(conn);
+
+    if messages == indexed {
+        return Ok(DoctorFinding::ok(
+            "fts_index",
+            format!("{} messages indexed", indexed),
+        ));
+    }
+
+    let message = format!("search index has {} of {} messages", indexed, messages);
+    if !repair {
+        return Ok(DoctorFinding::problem("fts_index", Severity::Warning, message, false));
+    }
+    match storage.rebuild_dm_fts_index() {
+        Ok(()) => Ok(DoctorFinding::problem("fts_index", Severity::Warning, message, true)),
+        Err(e) => Ok(DoctorFinding::problem(
+            "fts_index",
+            Severity::Warning,
+            format!("{}, rebuild failed: {}", message, e),
+            false,
+        )),
+    }
+}
+
+fn check_community_meta<F>(
+    storage: &DiskStorage,
+    repair: bool,
+    derive_meta: F,
+) -> Result<Vec<DoctorFinding>, io::Error>
+where
+    F: Fn(&str) -> Result<CommunityMeta, String>,
+{
+    let mut findings = Vec::new();
+    let mut missing = 0;
+
+    for community_id in storage.list_communities()? {
+        if storage.load_community_meta(&community_id).is_ok() {
+            continue;
+        }
+        missing += 1;
+
+        let message = format!("community {} has no cached meta", community_id);
+        if !repair {
+            findings.push(DoctorFinding::problem("community_meta", Severity::Warning, message, false));
+            continue;
+        }
+
+        let rebuilt = derive_meta(&community_id)
+            .and_then(|meta| storage.save_community_meta(&meta).map_err(|e| e.to_string()));
+        findings.push(match rebuilt {
+            Ok(()) => DoctorFinding::problem("community_meta", Severity::Warning, message, true),
+            Err(e) => DoctorFinding::problem(
+                "community_meta",
+                Severity::Error,
+                format!("{}, document could not be read: {}", message, e),
+                false,
+            ),
+        });
+    }
+
+    if missing == 0 {
+        findings.push(DoctorFinding::ok("community_meta", "every community has cached meta"));
+    }
+    Ok(findings)
+}
@@ -0,0 +1,147 @@
+use chacha20poly1305::aead::{Aead, KeyInit};
+pub use chacha20poly1305::Key;
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+// sealed keypairs start with this so plaintext ones from older installs still load
+const SEALED_MAGIC: &[u8] = b"DSK1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+// pbkdf2-hmac-sha256 rounds, slow enough to make offline guessing expensive
+const KDF_ROUNDS: u32 = 310_000;
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(SEALED_MAGIC)
+}
+
+// -- at-rest records --
+
+// records encrypted at rest start with this, rows written before the
+// storage was encrypted read as they are
+const RECORD_MAGIC: &[u8] = b"DSR1";
+// sealed under the storage key so a wrong passphrase is caught on unlock
+// instead of on the first record
+const KEY_CHECK: &[u8] = b"dusk-storage-key";
+
+pub fn new_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+// derive the key records are sealed with, done once per unlock since it is
+// deliberately slow. returns the key and a check value to store beside the salt
+pub fn storage_key(passphrase: &str, salt: &[u8]) -> Result<(Key, Vec<u8>), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        ));
+    }
+    let key = derive_key(passphrase, salt);
+    let check = seal_record(&key, KEY_CHECK)?;
+    Ok((key, check))
+}
+
+// true when `check` was produced under `key` by storage_key
+pub fn key_matches(key: &Key, check: &[u8]) -> bool {
+    open_record(key, check).is_ok_and(|plain| plain == KEY_CHECK)
+}
+
+pub fn is_sealed_record(bytes: &[u8]) -> bool {
+    bytes.starts_with(RECORD_MAGIC)
+}
+
+// magic | nonce | ciphertext
+pub fn seal_record(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let ciphertext = ChaCha20Poly1305::new(key)
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "failed to encrypt record".to_string())?;
+
+    let mut out = Vec::with_capacity(RECORD_MAGIC.len() + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(RECORD_MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+pub fn open_record(key: &Key, sealed: &[u8]) -> Result<Vec<u8>, String> {
+    let header = RECORD_MAGIC.len() + NONCE_LEN;
+    if !is_sealed_record(sealed) || sealed.len() <= header {
+        return Err("record is not sealed".to_string());
+    }
+
+    let nonce = &sealed[RECORD_MAGIC.len()..header];
+    ChaCha20Poly1305::new(key)
+        .decrypt(Nonce::from_slice(nonce), &sealed[header..])
+        .map_err(|_| "failed to decrypt record".to_string())
+}
+
+// encrypt keypair bytes under a passphrase: magic | salt | nonce | ciphertext
+pub fn seal(passphrase: &str, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        ));
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "failed to encrypt keypair".to_string())?;
+
+    let mut out = Vec::with_capacity(SEALED_MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(SEALED_MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+pub fn open(passphrase: &str, sealed: &[u8]) -> Result<Vec<u8>, String> {
+    let header = SEALED_MAGIC.len() + SALT_LEN + NONCE_LEN;
+    if !is_sealed(sealed) || sealed.len() <= header {
+        return Err("keypair is not sealed".to_string());
+    }
+
+    let salt = &sealed[SEALED_MAGIC.len()..SEALED_MAGIC.len() + SALT_LEN];
+    let nonce = &sealed[SEALED_MAGIC.len() + SALT_LEN..header];
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), &sealed[header..])
+        .map_err(|_| "wrong passphrase".to_string())
+}
+
+// single-block pbkdf2, the derived key is exactly one sha256 output
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key {
+    let mac = Hmac::<Sha256>::new_from_slice(passphrase.as_bytes()).expect("hmac accepts any key length");
+
+    let mut first = mac.clone();
+    first.update(salt);
+    first.update(&1u32.to_be_bytes());
+    let mut block = first.finalize().into_bytes();
+    let mut key = block;
+
+    for _ in 1..KDF_ROUNDS {
+        let mut round = mac.clone();
+        round.update(&block);
+        block = round.finalize().into_bytes();
+        for (k, b) in key.iter_mut().zip(block.iter()) {
+            *k ^= b;
+        }
+    }
+
+    *Key::from_slice(&key)
+}
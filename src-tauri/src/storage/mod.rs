@@ -1,5 +1,14 @@
 mod disk;
+pub mod doctor;
+pub mod keystore;
 
+pub use disk::data_dir_overridden;
+pub use disk::portable_mode;
 pub use disk::DiskStorage;
 pub use disk::DmSearchParams;
+pub use disk::RecordedEvent;
+pub use disk::RecordedEventKind;
+pub use disk::SearchTokenizer;
+pub use disk::SettingsProfile;
 pub use disk::UserSettings;
+pub use disk::{MAX_SETTINGS_PROFILES, MAX_SETTINGS_PROFILE_NAME_LEN};
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// override the release manifest url, e.g. for a self-hosted mirror
+pub const UPDATE_ENDPOINT_ENV: &str = "DUSK_UPDATE_ENDPOINT";
+
+// {channel} is filled in by us, the double-braced fields by the tauri updater
+const DEFAULT_UPDATE_ENDPOINT: &str =
+    "https://releases.duskchat.app/{channel}/{{target}}/{{arch}}/{{current_version}}";
+
+// minisign public key baked in by release builds, dev builds cannot update
+pub const UPDATER_PUBKEY: Option<&str> = option_env!("DUSK_UPDATER_PUBKEY");
+
+// release channel the user follows
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+}
+
+impl UpdateChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "stable",
+            UpdateChannel::Beta => "beta",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "stable" => Ok(UpdateChannel::Stable),
+            "beta" => Ok(UpdateChannel::Beta),
+            other => Err(format!("unknown update channel: {}", other)),
+        }
+    }
+}
+
+// an available release as shown to the user
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub channel: UpdateChannel,
+    pub notes: Option<String>,
+    pub date: Option<String>,
+    // share of installs the release is currently offered to
+    pub rollout_percent: u8,
+}
+
+// manifest url for a channel, the env override may also use {channel}
+pub fn endpoint(channel: UpdateChannel) -> String {
+    std::env::var(UPDATE_ENDPOINT_ENV)
+        .unwrap_or_else(|_| DEFAULT_UPDATE_ENDPOINT.to_string())
+        .replace("{channel}", channel.as_str())
+}
+
+// staged rollout percentage from the release manifest, absent means everyone
+pub fn rollout_percent(raw_json: &serde_json::Value) -> u8 {
+    raw_json
+        .get("rollout")
+        .and_then(|v| v.as_u64())
+        .map(|v| v.min(100) as u8)
+        .unwrap_or(100)
+}
+
+// stable 0-99 bucket for this install and release, rehashed per version so
+// the same installs are not always first in line
+pub fn rollout_bucket(install_id: &str, version: &str) -> u8 {
+    let digest = Sha256::digest(format!("dusk-rollout||{}||{}", install_id, version).as_bytes());
+    (u16::from_be_bytes([digest[0], digest[1]]) % 100) as u8
+}
+
+pub fn in_rollout(install_id: &str, version: &str, percent: u8) -> bool {
+    rollout_bucket(install_id, version) < percent
+}
@@ -1,11 +1,20 @@
+pub mod pow;
+
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use libp2p::identity;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-
-use crate::protocol::identity::VerificationProof;
-use crate::protocol::messages::{ProfileAnnouncement, ProfileRevocation};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::protocol::community::{
+    CapabilityToken, DocCheckpoint, InviteCode, MembershipAction, MembershipEvent, MembershipOrder,
+    MAX_CAPABILITY_DEPTH,
+};
+use crate::protocol::identity::{ContactCard, VerificationProof};
+use crate::protocol::messages::{
+    DMDeleteRequest, DMEphemeralSignal, DMPolicyLogEntry, ProfileAnnouncement, ProfileRevocation,
+};
+use crate::protocol::notes::{PageEditRole, PageMeta, PageRefSignature};
 
 // -- challenge data structures received from the frontend --
 
@@ -37,20 +46,111 @@ pub struct TargetCircle {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct ChallengeSubmission {
+pub struct MouseChallenge {
     pub segments: Vec<SegmentData>,
     pub circles: Vec<TargetCircle>,
     pub total_start_time: f64,
     pub total_end_time: f64,
 }
 
+// a single key press captured while transcribing the prompt, times in ms
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeystrokeSample {
+    pub key: String,
+    pub down: f64,
+    pub up: f64,
+}
+
+// keyboard-only alternative: the user types a short prompt phrase and the
+// rhythm of their key presses is analyzed instead of pointer movement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeystrokeChallenge {
+    pub prompt: String,
+    pub typed: String,
+    pub keystrokes: Vec<KeystrokeSample>,
+    pub total_start_time: f64,
+    pub total_end_time: f64,
+}
+
+// a touch point sampled while dragging a finger between targets. force and
+// radii come from the pointer events api and read 0 on hardware without them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TouchSample {
+    pub x: f64,
+    pub y: f64,
+    pub t: f64,
+    #[serde(default)]
+    pub force: f64,
+    #[serde(default)]
+    pub radius_x: f64,
+    #[serde(default)]
+    pub radius_y: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TouchStroke {
+    pub from_target: u32,
+    pub to_target: u32,
+    pub samples: Vec<TouchSample>,
+}
+
+// touch variant of the target challenge, the finger is swiped from circle to circle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TouchChallenge {
+    pub strokes: Vec<TouchStroke>,
+    pub circles: Vec<TargetCircle>,
+    pub total_start_time: f64,
+    pub total_end_time: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChallengeSubmission {
+    Mouse(MouseChallenge),
+    Keystroke(KeystrokeChallenge),
+    Touch(TouchChallenge),
+}
+
+// which input method a challenge or proof was made with
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeKind {
+    Mouse,
+    Keystroke,
+    Touch,
+}
+
+impl ChallengeKind {
+    // touch hardware reports far less signal than a mouse, so its bar is lower
+    fn human_threshold(&self) -> f64 {
+        match self {
+            ChallengeKind::Mouse => 0.35,
+            ChallengeKind::Keystroke => 0.35,
+            ChallengeKind::Touch => 0.30,
+        }
+    }
+}
+
+impl ChallengeSubmission {
+    pub fn kind(&self) -> ChallengeKind {
+        match self {
+            ChallengeSubmission::Mouse(_) => ChallengeKind::Mouse,
+            ChallengeSubmission::Keystroke(_) => ChallengeKind::Keystroke,
+            ChallengeSubmission::Touch(_) => ChallengeKind::Touch,
+        }
+    }
+}
+
 pub struct AnalysisResult {
     pub is_human: bool,
     pub score: f64,
 }
 
-const HUMAN_THRESHOLD: f64 = 0.35;
-
 // -- behavioral analysis functions --
 // these mirror the typescript implementations exactly, running in compiled rust
 // so the analysis logic is not exposed in the inspectable webview
@@ -263,6 +363,14 @@ fn score_overall_timing(total_start: f64, total_end: f64) -> f64 {
 }
 
 pub fn analyze_challenge(data: &ChallengeSubmission) -> AnalysisResult {
+    match data {
+        ChallengeSubmission::Mouse(mouse) => analyze_mouse_challenge(mouse),
+        ChallengeSubmission::Keystroke(keys) => analyze_keystroke_challenge(keys),
+        ChallengeSubmission::Touch(touch) => analyze_touch_challenge(touch),
+    }
+}
+
+fn analyze_mouse_challenge(data: &MouseChallenge) -> AnalysisResult {
     let timing = score_timing_variance(&data.segments);
     let curvature = score_path_curvature(&data.segments);
     let speed = score_speed_variance(&data.segments);
@@ -272,7 +380,323 @@ pub fn analyze_challenge(data: &ChallengeSubmission) -> AnalysisResult {
     let score = timing * 0.25 + curvature * 0.25 + speed * 0.20 + jitter * 0.20 + overall * 0.10;
 
     AnalysisResult {
-        is_human: score >= HUMAN_THRESHOLD,
+        is_human: score >= ChallengeKind::Mouse.human_threshold(),
+        score,
+    }
+}
+
+// -- keystroke analysis --
+
+// phrases offered for the keystroke challenge, a submission must use one of these
+// so the prompt cannot be chosen to suit a scripted typist
+const KEYSTROKE_PROMPTS: &[&str] = &[
+    "the quiet river bends past the old mill",
+    "seven lanterns glow along the harbor wall",
+    "a warm breeze carries the smell of rain",
+    "the fox jumped over a sleeping garden gnome",
+    "bright kites drift above the windy hill",
+    "fresh bread cools on the kitchen windowsill",
+];
+
+// pick a prompt for the frontend to display
+pub fn keystroke_prompt() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .subsec_nanos() as usize;
+    KEYSTROKE_PROMPTS[nanos % KEYSTROKE_PROMPTS.len()].to_string()
+}
+
+// coefficient of variation, none when there is too little data
+fn coefficient_of_variation(values: &[f64]) -> Option<f64> {
+    if values.len() < 3 {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    if mean <= 0.0 {
+        return None;
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    Some(variance.sqrt() / mean)
+}
+
+fn score_flight_variance(keys: &[KeystrokeSample]) -> f64 {
+    let flights: Vec<f64> = keys.windows(2).map(|w| w[1].down - w[0].down).collect();
+
+    // humans pause between words and speed up on familiar letter pairs,
+    // scripted input arrives at a near constant cadence
+    match coefficient_of_variation(&flights) {
+        None => 0.0,
+        Some(cv) if cv < 0.05 => 0.0,
+        Some(cv) if cv < 0.15 => 0.3,
+        Some(cv) if cv < 0.25 => 0.7,
+        Some(_) => 1.0,
+    }
+}
+
+fn score_dwell_variance(keys: &[KeystrokeSample]) -> f64 {
+    let dwells: Vec<f64> = keys.iter().map(|k| k.up - k.down).collect();
+
+    // synthetic key events are often released instantly or after a fixed delay
+    if dwells.iter().any(|d| *d <= 0.0) {
+        return 0.0;
+    }
+    match coefficient_of_variation(&dwells) {
+        None => 0.0,
+        Some(cv) if cv < 0.03 => 0.0,
+        Some(cv) if cv < 0.1 => 0.4,
+        Some(_) => 1.0,
+    }
+}
+
+fn score_rollover(keys: &[KeystrokeSample]) -> f64 {
+    if keys.len() < 2 {
+        return 0.5;
+    }
+
+    // fluent typists press the next key before releasing the previous one,
+    // its absence is common for careful typists so it only ever adds
+    let overlaps = keys.windows(2).filter(|w| w[1].down < w[0].up).count();
+    if overlaps > 0 {
+        1.0
+    } else {
+        0.5
+    }
+}
+
+fn score_typing_speed(data: &KeystrokeChallenge) -> f64 {
+    let minutes = (data.total_end_time - data.total_start_time) / 60_000.0;
+    if minutes <= 0.0 {
+        return 0.0;
+    }
+    let chars_per_minute = data.typed.chars().count() as f64 / minutes;
+
+    // the fastest human typists sit well below 1000 characters per minute,
+    // very slow input is still plausible for assistive setups
+    if chars_per_minute > 1500.0 {
+        0.0
+    } else if chars_per_minute > 1000.0 {
+        0.3
+    } else {
+        1.0
+    }
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+fn score_transcription(data: &KeystrokeChallenge) -> f64 {
+    let prompt = data.prompt.trim().to_lowercase();
+    let typed = data.typed.trim().to_lowercase();
+    let distance = levenshtein(&prompt, &typed) as f64;
+    let error_rate = distance / prompt.chars().count().max(1) as f64;
+
+    // a few typos are human, a wildly different string means the prompt was ignored
+    if error_rate <= 0.1 {
+        1.0
+    } else if error_rate <= 0.25 {
+        0.5
+    } else {
+        0.0
+    }
+}
+
+fn analyze_keystroke_challenge(data: &KeystrokeChallenge) -> AnalysisResult {
+    // unknown prompts or keystroke logs that cannot have produced the text fail outright
+    let known_prompt = KEYSTROKE_PROMPTS.contains(&data.prompt.as_str());
+    let enough_keys = data.keystrokes.len() >= data.prompt.chars().count() / 2;
+    if !known_prompt || !enough_keys {
+        return AnalysisResult {
+            is_human: false,
+            score: 0.0,
+        };
+    }
+
+    let flight = score_flight_variance(&data.keystrokes);
+    let dwell = score_dwell_variance(&data.keystrokes);
+    let rollover = score_rollover(&data.keystrokes);
+    let speed = score_typing_speed(data);
+    let transcription = score_transcription(data);
+
+    let score =
+        flight * 0.30 + dwell * 0.25 + speed * 0.20 + transcription * 0.15 + rollover * 0.10;
+
+    AnalysisResult {
+        is_human: score >= ChallengeKind::Keystroke.human_threshold() && transcription > 0.0,
+        score,
+    }
+}
+
+// -- touch analysis --
+
+// swipes a touch submission needs, each with enough samples to judge, before
+// it is scored. an empty one would otherwise pass on the neutral defaults
+const MIN_TOUCH_STROKES: usize = 3;
+const MIN_STROKE_SAMPLES: usize = 5;
+
+// true when the device reports a real, varying value rather than a constant
+fn varies(values: &[f64]) -> bool {
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    max - min > f64::EPSILON
+}
+
+fn score_touch_pressure(strokes: &[TouchStroke]) -> f64 {
+    let forces: Vec<f64> = strokes
+        .iter()
+        .flat_map(|s| s.samples.iter().map(|p| p.force))
+        .collect();
+
+    // many phones report no pressure at all, treat that as no evidence either way
+    if forces.is_empty() || forces.iter().all(|f| *f == 0.0 || *f == 1.0) {
+        return 0.5;
+    }
+
+    // a real finger presses harder mid-swipe and eases off, synthetic events stay flat
+    match coefficient_of_variation(&forces) {
+        None => 0.5,
+        Some(cv) if cv < 0.01 => 0.1,
+        Some(cv) if cv < 0.05 => 0.5,
+        Some(_) => 1.0,
+    }
+}
+
+fn score_touch_radius(strokes: &[TouchStroke]) -> f64 {
+    let radii: Vec<f64> = strokes
+        .iter()
+        .flat_map(|s| s.samples.iter().map(|p| p.radius_x.max(p.radius_y)))
+        .collect();
+
+    if radii.is_empty() || radii.iter().all(|r| *r == 0.0) {
+        return 0.4;
+    }
+
+    // the contact patch of a finger changes as it rolls across the screen
+    if varies(&radii) {
+        1.0
+    } else {
+        0.3
+    }
+}
+
+fn score_swipe_dynamics(strokes: &[TouchStroke]) -> f64 {
+    let mut scores = Vec::new();
+
+    for stroke in strokes {
+        if stroke.samples.len() < 5 {
+            continue;
+        }
+
+        let mut speeds = Vec::new();
+        for i in 1..stroke.samples.len() {
+            let dx = stroke.samples[i].x - stroke.samples[i - 1].x;
+            let dy = stroke.samples[i].y - stroke.samples[i - 1].y;
+            let dt = stroke.samples[i].t - stroke.samples[i - 1].t;
+            if dt > 0.0 {
+                speeds.push((dx * dx + dy * dy).sqrt() / dt);
+            }
+        }
+        if speeds.len() < 3 {
+            continue;
+        }
+
+        // swipes accelerate then slow down into the target, so the fastest
+        // point should fall somewhere in the middle of the stroke
+        let peak = speeds
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, _)| i as f64 / (speeds.len() - 1) as f64)
+            .unwrap_or(0.0);
+        let bell_shaped = (0.15..=0.85).contains(&peak);
+
+        let speed_score = match coefficient_of_variation(&speeds) {
+            None => 0.0,
+            Some(cv) if cv < 0.1 => 0.0,
+            Some(cv) if cv < 0.25 => 0.5,
+            Some(_) => 1.0,
+        };
+
+        scores.push(if bell_shaped { speed_score } else { speed_score * 0.5 });
+    }
+
+    if scores.is_empty() {
+        return 0.0;
+    }
+    scores.iter().sum::<f64>() / scores.len() as f64
+}
+
+fn score_touch_curvature(strokes: &[TouchStroke]) -> f64 {
+    let mut ratios = Vec::new();
+
+    for stroke in strokes {
+        if stroke.samples.len() < 3 {
+            continue;
+        }
+        let first = &stroke.samples[0];
+        let last = &stroke.samples[stroke.samples.len() - 1];
+        let straight = ((last.x - first.x).powi(2) + (last.y - first.y).powi(2)).sqrt();
+        if straight < 10.0 {
+            continue;
+        }
+
+        let path: f64 = stroke
+            .samples
+            .windows(2)
+            .map(|w| ((w[1].x - w[0].x).powi(2) + (w[1].y - w[0].y).powi(2)).sqrt())
+            .sum();
+        ratios.push(path / straight);
+    }
+
+    if ratios.is_empty() {
+        return 0.0;
+    }
+
+    // touch input is sampled more coarsely than a mouse, so paths look straighter
+    let avg_ratio = ratios.iter().sum::<f64>() / ratios.len() as f64;
+    if avg_ratio < 1.005 {
+        0.0
+    } else if avg_ratio < 1.03 {
+        0.5
+    } else {
+        1.0
+    }
+}
+
+fn analyze_touch_challenge(data: &TouchChallenge) -> AnalysisResult {
+    let full_strokes = data
+        .strokes
+        .iter()
+        .filter(|s| s.samples.len() >= MIN_STROKE_SAMPLES)
+        .count();
+    if full_strokes < MIN_TOUCH_STROKES {
+        return AnalysisResult {
+            is_human: false,
+            score: 0.0,
+        };
+    }
+
+    let swipe = score_swipe_dynamics(&data.strokes);
+    let curvature = score_touch_curvature(&data.strokes);
+    let pressure = score_touch_pressure(&data.strokes);
+    let radius = score_touch_radius(&data.strokes);
+    let overall = score_overall_timing(data.total_start_time, data.total_end_time);
+
+    let score = swipe * 0.30 + curvature * 0.20 + pressure * 0.20 + radius * 0.15 + overall * 0.15;
+
+    AnalysisResult {
+        is_human: score >= ChallengeKind::Touch.human_threshold(),
         score,
     }
 }
@@ -312,25 +736,101 @@ pub fn generate_proof(
         signature: hex::encode(signature),
         timestamp,
         score: result.score,
+        pow: None,
+        kind: Some(challenge.kind()),
+    })
+}
+
+// fallback proof for users the behavioral challenge keeps rejecting, the work
+// replaces the metrics and is signed the same way
+pub fn generate_pow_proof(
+    keypair: &identity::Keypair,
+    peer_id: &str,
+    difficulty: u32,
+) -> Result<VerificationProof, String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    let (solution, hash) = pow::solve(peer_id, timestamp, difficulty);
+    let metrics_hash = hex::encode(hash);
+
+    let sign_payload = format!("{}||{}||{}", metrics_hash, peer_id, timestamp);
+    let signature = keypair
+        .sign(sign_payload.as_bytes())
+        .map_err(|e| format!("failed to sign proof: {}", e))?;
+
+    Ok(VerificationProof {
+        metrics_hash,
+        signature: hex::encode(signature),
+        timestamp,
+        score: 0.0,
+        pow: Some(solution),
+        kind: None,
     })
 }
 
+// proofs without work are accepted on presence as before, proofs carrying work
+// must actually solve the puzzle for this peer
+pub fn verify_proof_work(proof: &VerificationProof, peer_id: &str) -> bool {
+    match &proof.pow {
+        Some(solution) => pow::verify(solution, peer_id, proof.timestamp),
+        None => true,
+    }
+}
+
 // -- profile announcement signing --
 
+// signed profile messages dated further ahead than this are rejected. the
+// directory keeps the newest announcement, so a far future one would pin
+// an entry until that date
+const MAX_ANNOUNCE_FUTURE_SKEW_MS: u64 = 10 * 60 * 1000;
+
+// the embedded key must be the one the claimed peer id is derived from,
+// otherwise anyone could sign for any peer id with their own key
+fn key_for_peer(public_key_hex: &str, peer_id: &str) -> Option<identity::PublicKey> {
+    let pk_bytes = hex::decode(public_key_hex).ok()?;
+    let public_key = identity::PublicKey::try_decode_protobuf(&pk_bytes).ok()?;
+    if public_key.to_peer_id().to_string() != peer_id {
+        return None;
+    }
+    Some(public_key)
+}
+
+// whether a hex encoded key is the one a peer id is derived from
+pub fn key_matches_peer(public_key_hex: &str, peer_id: &str) -> bool {
+    key_for_peer(public_key_hex, peer_id).is_some()
+}
+
+fn too_far_ahead(timestamp: u64) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    timestamp > now + MAX_ANNOUNCE_FUTURE_SKEW_MS
+}
+
 // build the canonical payload that gets signed for an announcement
-fn announcement_sign_payload(
-    peer_id: &str,
-    display_name: &str,
-    bio: &str,
-    public_key: &str,
-    timestamp: u64,
-    metrics_hash: &str,
-) -> Vec<u8> {
-    format!(
+fn announcement_sign_payload(announcement: &ProfileAnnouncement, metrics_hash: &str) -> Vec<u8> {
+    let mut payload = format!(
         "dusk-announce||{}||{}||{}||{}||{}||{}",
-        peer_id, display_name, bio, public_key, timestamp, metrics_hash
-    )
-    .into_bytes()
+        announcement.peer_id,
+        announcement.display_name,
+        announcement.bio,
+        announcement.public_key,
+        announcement.timestamp,
+        metrics_hash
+    );
+    // only appended when set, so announcements without a custom status keep
+    // the payload older clients verify against
+    if !announcement.status_message.is_empty() || !announcement.status_emoji.is_empty() {
+        payload.push_str(&format!(
+            "||status||{}||{}",
+            announcement.status_message, announcement.status_emoji
+        ));
+    }
+    payload.into_bytes()
 }
 
 pub fn sign_announcement(
@@ -343,14 +843,7 @@ pub fn sign_announcement(
         .map(|p| p.metrics_hash.as_str())
         .unwrap_or("");
 
-    let payload = announcement_sign_payload(
-        &announcement.peer_id,
-        &announcement.display_name,
-        &announcement.bio,
-        &announcement.public_key,
-        announcement.timestamp,
-        metrics_hash,
-    );
+    let payload = announcement_sign_payload(announcement, metrics_hash);
 
     match keypair.sign(&payload) {
         Ok(sig) => hex::encode(sig),
@@ -362,15 +855,12 @@ pub fn sign_announcement(
 }
 
 pub fn verify_announcement(public_key_hex: &str, announcement: &ProfileAnnouncement) -> bool {
-    let pk_bytes = match hex::decode(public_key_hex) {
-        Ok(b) => b,
-        Err(_) => return false,
-    };
-
-    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
-        Ok(pk) => pk,
-        Err(_) => return false,
+    let Some(public_key) = key_for_peer(public_key_hex, &announcement.peer_id) else {
+        return false;
     };
+    if too_far_ahead(announcement.timestamp) {
+        return false;
+    }
 
     let sig_bytes = match hex::decode(&announcement.signature) {
         Ok(b) => b,
@@ -383,14 +873,7 @@ pub fn verify_announcement(public_key_hex: &str, announcement: &ProfileAnnouncem
         .map(|p| p.metrics_hash.as_str())
         .unwrap_or("");
 
-    let payload = announcement_sign_payload(
-        &announcement.peer_id,
-        &announcement.display_name,
-        &announcement.bio,
-        &announcement.public_key,
-        announcement.timestamp,
-        metrics_hash,
-    );
+    let payload = announcement_sign_payload(announcement, metrics_hash);
 
     public_key.verify(&payload, &sig_bytes)
 }
@@ -418,15 +901,12 @@ pub fn sign_revocation(keypair: &identity::Keypair, revocation: &ProfileRevocati
 }
 
 pub fn verify_revocation(public_key_hex: &str, revocation: &ProfileRevocation) -> bool {
-    let pk_bytes = match hex::decode(public_key_hex) {
-        Ok(b) => b,
-        Err(_) => return false,
-    };
-
-    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
-        Ok(pk) => pk,
-        Err(_) => return false,
+    let Some(public_key) = key_for_peer(public_key_hex, &revocation.peer_id) else {
+        return false;
     };
+    if too_far_ahead(revocation.timestamp) {
+        return false;
+    }
 
     let sig_bytes = match hex::decode(&revocation.signature) {
         Ok(b) => b,
@@ -441,3 +921,697 @@ pub fn verify_revocation(public_key_hex: &str, revocation: &ProfileRevocation) -
 
     public_key.verify(&payload, &sig_bytes)
 }
+
+// -- community checkpoint signing --
+
+fn checkpoint_sign_payload(community_id: &str, checkpoint: &DocCheckpoint) -> Vec<u8> {
+    format!(
+        "dusk-checkpoint||{}||{}||{}||{}",
+        community_id,
+        checkpoint.heads.join(","),
+        checkpoint.created_at,
+        checkpoint.signer
+    )
+    .into_bytes()
+}
+
+pub fn sign_checkpoint(
+    keypair: &identity::Keypair,
+    community_id: &str,
+    checkpoint: &DocCheckpoint,
+) -> String {
+    let payload = checkpoint_sign_payload(community_id, checkpoint);
+
+    match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign checkpoint: {}", e);
+            String::new()
+        }
+    }
+}
+
+// checks the signature and that the embedded key actually belongs to the signer
+pub fn verify_checkpoint(community_id: &str, checkpoint: &DocCheckpoint) -> bool {
+    let pk_bytes = match hex::decode(&checkpoint.public_key) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
+        Ok(pk) => pk,
+        Err(_) => return false,
+    };
+
+    if public_key.to_peer_id().to_string() != checkpoint.signer {
+        return false;
+    }
+
+    let sig_bytes = match hex::decode(&checkpoint.signature) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let payload = checkpoint_sign_payload(community_id, checkpoint);
+
+    public_key.verify(&payload, &sig_bytes)
+}
+
+// -- membership log signing --
+
+fn membership_event_sign_payload(community_id: &str, event: &MembershipEvent) -> Vec<u8> {
+    let mut payload = format!(
+        "dusk-membership||{}||{}||{}||{}||{}||{}||{}",
+        community_id,
+        event.action.as_str(),
+        event.peer_id,
+        event.actor,
+        event.roles.join(","),
+        event.display_name,
+        event.timestamp
+    );
+    // only kicks made under a capability carry one, older events sign without it
+    if let Some(ref token) = event.capability {
+        payload.push_str("||");
+        payload.push_str(&token.id);
+    }
+    payload.into_bytes()
+}
+
+// build and sign a membership event with the keypair as actor
+pub fn sign_membership_event(
+    keypair: &identity::Keypair,
+    community_id: &str,
+    action: MembershipAction,
+    peer_id: &str,
+    roles: &[String],
+    display_name: &str,
+    timestamp: u64,
+) -> MembershipEvent {
+    let mut event = MembershipEvent {
+        action,
+        peer_id: peer_id.to_string(),
+        actor: keypair.public().to_peer_id().to_string(),
+        actor_key: hex::encode(keypair.public().encode_protobuf()),
+        roles: roles.to_vec(),
+        display_name: display_name.to_string(),
+        timestamp,
+        signature: String::new(),
+        capability: None,
+    };
+    event.signature = sign_membership_payload(keypair, community_id, &event);
+    event
+}
+
+// a kick by a peer that is neither owner nor admin, backed by its token
+pub fn sign_capability_kick(
+    keypair: &identity::Keypair,
+    community_id: &str,
+    peer_id: &str,
+    display_name: &str,
+    timestamp: u64,
+    token: &CapabilityToken,
+) -> MembershipEvent {
+    let mut event = sign_membership_event(
+        keypair,
+        community_id,
+        MembershipAction::Kick,
+        peer_id,
+        &[],
+        display_name,
+        timestamp,
+    );
+    event.capability = Some(token.clone());
+    event.signature = sign_membership_payload(keypair, community_id, &event);
+    event
+}
+
+fn sign_membership_payload(
+    keypair: &identity::Keypair,
+    community_id: &str,
+    event: &MembershipEvent,
+) -> String {
+    let payload = membership_event_sign_payload(community_id, event);
+    match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign membership event: {}", e);
+            String::new()
+        }
+    }
+}
+
+// identifies a membership event by everything its signature covers and the
+// signature itself, so a forged event can't pass for one already checked
+pub fn membership_event_key(community_id: &str, event: &MembershipEvent) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(membership_event_sign_payload(community_id, event));
+    hasher.update(format!("||{}||{}", event.actor_key, event.signature));
+    hex::encode(hasher.finalize())
+}
+
+// checks the signature and that the embedded key actually belongs to the actor
+pub fn verify_membership_event(community_id: &str, event: &MembershipEvent) -> bool {
+    let pk_bytes = match hex::decode(&event.actor_key) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
+        Ok(pk) => pk,
+        Err(_) => return false,
+    };
+
+    if public_key.to_peer_id().to_string() != event.actor {
+        return false;
+    }
+
+    let sig_bytes = match hex::decode(&event.signature) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let payload = membership_event_sign_payload(community_id, event);
+
+    public_key.verify(&payload, &sig_bytes)
+}
+
+// -- membership order signing --
+
+fn membership_order_sign_payload(community_id: &str, order: &MembershipOrder) -> Vec<u8> {
+    format!(
+        "dusk-membership-order||{}||{}||{}||{}||{}",
+        community_id, order.event_key, order.seq, order.placed_at, order.signer
+    )
+    .into_bytes()
+}
+
+// place a membership event at seq in the community's log, as its founder
+pub fn sign_membership_order(
+    keypair: &identity::Keypair,
+    community_id: &str,
+    event_key: &str,
+    seq: u64,
+    placed_at: u64,
+) -> MembershipOrder {
+    let mut order = MembershipOrder {
+        event_key: event_key.to_string(),
+        seq,
+        placed_at,
+        signer: keypair.public().to_peer_id().to_string(),
+        public_key: hex::encode(keypair.public().encode_protobuf()),
+        signature: String::new(),
+    };
+    let payload = membership_order_sign_payload(community_id, &order);
+    order.signature = match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign membership order: {}", e);
+            String::new()
+        }
+    };
+    order
+}
+
+// same idea as membership_event_key, for caching checked orders
+pub fn membership_order_key(community_id: &str, order: &MembershipOrder) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(membership_order_sign_payload(community_id, order));
+    hasher.update(format!("||{}||{}", order.public_key, order.signature));
+    hex::encode(hasher.finalize())
+}
+
+// checks the signature and that the embedded key actually belongs to the signer
+pub fn verify_membership_order(community_id: &str, order: &MembershipOrder) -> bool {
+    let pk_bytes = match hex::decode(&order.public_key) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
+        Ok(pk) => pk,
+        Err(_) => return false,
+    };
+
+    if public_key.to_peer_id().to_string() != order.signer {
+        return false;
+    }
+
+    let sig_bytes = match hex::decode(&order.signature) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let payload = membership_order_sign_payload(community_id, order);
+
+    public_key.verify(&payload, &sig_bytes)
+}
+
+// -- wiki page signing --
+
+fn page_ref_sign_payload(community_id: &str, page: &PageMeta, signer: &str) -> Vec<u8> {
+    let edit_role = match page.edit_role {
+        PageEditRole::Member => "member",
+        PageEditRole::Admin => "admin",
+    };
+    format!(
+        "dusk-page||{}||{}||{}||{}||{}||{}||{}",
+        community_id, page.id, edit_role, page.position, page.created_by, page.created_at, signer
+    )
+    .into_bytes()
+}
+
+// sign a page's entry in the community doc as it should read
+pub fn sign_page_ref(
+    keypair: &identity::Keypair,
+    community_id: &str,
+    page: &PageMeta,
+) -> PageRefSignature {
+    let signer = keypair.public().to_peer_id().to_string();
+    let payload = page_ref_sign_payload(community_id, page, &signer);
+    let signature = match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign page: {}", e);
+            String::new()
+        }
+    };
+    PageRefSignature {
+        signer,
+        public_key: hex::encode(keypair.public().encode_protobuf()),
+        signature,
+    }
+}
+
+// checks the signature and that the embedded key actually belongs to the signer
+pub fn verify_page_ref(community_id: &str, page: &PageMeta, signature: &PageRefSignature) -> bool {
+    let pk_bytes = match hex::decode(&signature.public_key) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
+        Ok(pk) => pk,
+        Err(_) => return false,
+    };
+
+    if public_key.to_peer_id().to_string() != signature.signer {
+        return false;
+    }
+
+    let sig_bytes = match hex::decode(&signature.signature) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let payload = page_ref_sign_payload(community_id, page, &signature.signer);
+
+    public_key.verify(&payload, &sig_bytes)
+}
+
+// -- moderation capability signing --
+
+fn capability_sign_payload(token: &CapabilityToken) -> Vec<u8> {
+    format!(
+        "dusk-capability||{}||{}||{}||{}||{}||{}||{}||{}",
+        token.id,
+        token.community_id,
+        token.capability.as_str(),
+        token.channel_id.as_deref().unwrap_or(""),
+        token.holder,
+        token.issuer,
+        token.expires_at,
+        token.parent.as_ref().map(|p| p.id.as_str()).unwrap_or("")
+    )
+    .into_bytes()
+}
+
+pub fn sign_capability_token(keypair: &identity::Keypair, token: &CapabilityToken) -> String {
+    let payload = capability_sign_payload(token);
+
+    match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign capability token: {}", e);
+            String::new()
+        }
+    }
+}
+
+// checks every link from the token back to one issued by a peer `is_owner`
+// accepts. each delegation may only narrow the one above it: same community
+// and capability, the same channel or a channel inside an unscoped parent,
+// and no later expiry. the token must not have expired at `at`
+pub fn verify_capability_token(
+    token: &CapabilityToken,
+    at: u64,
+    is_owner: &dyn Fn(&str) -> bool,
+) -> bool {
+    let mut current = token;
+    for _ in 0..=MAX_CAPABILITY_DEPTH {
+        if current.expires_at <= at || !verify_capability_signature(current) {
+            return false;
+        }
+        let Some(ref parent) = current.parent else {
+            return is_owner(&current.issuer);
+        };
+        let narrows = parent.holder == current.issuer
+            && parent.community_id == current.community_id
+            && parent.capability == current.capability
+            && parent.expires_at >= current.expires_at
+            && (parent.channel_id.is_none() || parent.channel_id == current.channel_id);
+        if !narrows {
+            return false;
+        }
+        current = parent;
+    }
+    false
+}
+
+// checks the signature and that the embedded key actually belongs to the issuer
+fn verify_capability_signature(token: &CapabilityToken) -> bool {
+    let pk_bytes = match hex::decode(&token.issuer_key) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
+        Ok(pk) => pk,
+        Err(_) => return false,
+    };
+
+    if public_key.to_peer_id().to_string() != token.issuer {
+        return false;
+    }
+
+    let sig_bytes = match hex::decode(&token.signature) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let payload = capability_sign_payload(token);
+
+    public_key.verify(&payload, &sig_bytes)
+}
+
+// -- dm deletion signing --
+
+fn dm_delete_sign_payload(request: &DMDeleteRequest) -> Vec<u8> {
+    format!(
+        "dusk-dm-delete||{}||{}||{}||{}",
+        request.message_id, request.from_peer, request.to_peer, request.timestamp
+    )
+    .into_bytes()
+}
+
+pub fn sign_dm_delete(keypair: &identity::Keypair, request: &DMDeleteRequest) -> String {
+    let payload = dm_delete_sign_payload(request);
+
+    match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign dm deletion: {}", e);
+            String::new()
+        }
+    }
+}
+
+// checks the signature and that the embedded key belongs to the requester
+pub fn verify_dm_delete(request: &DMDeleteRequest) -> bool {
+    let pk_bytes = match hex::decode(&request.public_key) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
+        Ok(pk) => pk,
+        Err(_) => return false,
+    };
+
+    if public_key.to_peer_id().to_string() != request.from_peer {
+        return false;
+    }
+
+    let sig_bytes = match hex::decode(&request.signature) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    public_key.verify(&dm_delete_sign_payload(request), &sig_bytes)
+}
+
+// -- dm policy step signing --
+
+// the conversation id is the same on both ends, so one signature holds in
+// the author's log and the peer's
+fn dm_policy_sign_payload(
+    conversation_id: &str,
+    actor: &str,
+    action: &str,
+    ttl_secs: Option<u64>,
+    timestamp: u64,
+) -> Vec<u8> {
+    format!(
+        "dusk-dm-policy||{}||{}||{}||{}||{}",
+        conversation_id,
+        actor,
+        action,
+        ttl_secs.map(|t| t.to_string()).unwrap_or_default(),
+        timestamp
+    )
+    .into_bytes()
+}
+
+// empty for receipts, only policy steps are signed
+pub fn sign_dm_policy_step(
+    keypair: &identity::Keypair,
+    conversation_id: &str,
+    signal: &DMEphemeralSignal,
+) -> String {
+    let Some((action, ttl_secs)) = signal.kind.policy_step() else {
+        return String::new();
+    };
+    let payload = dm_policy_sign_payload(
+        conversation_id,
+        &signal.from_peer,
+        action,
+        ttl_secs,
+        signal.timestamp,
+    );
+
+    match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign dm policy step: {}", e);
+            String::new()
+        }
+    }
+}
+
+pub fn verify_dm_policy_signal(conversation_id: &str, signal: &DMEphemeralSignal) -> bool {
+    let Some((action, ttl_secs)) = signal.kind.policy_step() else {
+        return false;
+    };
+    let payload = dm_policy_sign_payload(
+        conversation_id,
+        &signal.from_peer,
+        action,
+        ttl_secs,
+        signal.timestamp,
+    );
+    verify_dm_policy_signature(
+        &payload,
+        &signal.from_peer,
+        &signal.public_key,
+        &signal.signature,
+    )
+}
+
+pub fn verify_dm_policy_entry(conversation_id: &str, entry: &DMPolicyLogEntry) -> bool {
+    let payload = dm_policy_sign_payload(
+        conversation_id,
+        &entry.actor,
+        &entry.action,
+        entry.ttl_secs,
+        entry.timestamp,
+    );
+    verify_dm_policy_signature(&payload, &entry.actor, &entry.public_key, &entry.signature)
+}
+
+// checks the signature and that the embedded key belongs to the actor
+fn verify_dm_policy_signature(
+    payload: &[u8],
+    actor: &str,
+    public_key_hex: &str,
+    signature_hex: &str,
+) -> bool {
+    let pk_bytes = match hex::decode(public_key_hex) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    let public_key = match identity::PublicKey::try_decode_protobuf(&pk_bytes) {
+        Ok(pk) => pk,
+        Err(_) => return false,
+    };
+
+    if public_key.to_peer_id().to_string() != actor {
+        return false;
+    }
+
+    let sig_bytes = match hex::decode(signature_hex) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    public_key.verify(payload, &sig_bytes)
+}
+
+// -- contact card signing --
+
+fn contact_card_sign_payload(card: &ContactCard) -> Vec<u8> {
+    format!(
+        "dusk-contact||{}||{}||{}||{}||{}",
+        card.peer_id, card.public_key, card.display_name, card.signer, card.timestamp
+    )
+    .into_bytes()
+}
+
+pub fn sign_contact_card(keypair: &identity::Keypair, card: &ContactCard) -> String {
+    let payload = contact_card_sign_payload(card);
+
+    match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign contact card: {}", e);
+            String::new()
+        }
+    }
+}
+
+// checks that both keys belong to their peer ids and that the signer signed the card
+pub fn verify_contact_card(card: &ContactCard) -> bool {
+    let key_matches = |key_hex: &str, peer_id: &str| {
+        hex::decode(key_hex)
+            .ok()
+            .and_then(|b| identity::PublicKey::try_decode_protobuf(&b).ok())
+            .filter(|pk| pk.to_peer_id().to_string() == peer_id)
+    };
+
+    if key_matches(&card.public_key, &card.peer_id).is_none() {
+        return false;
+    }
+
+    let signer_key = match key_matches(&card.signer_key, &card.signer) {
+        Some(pk) => pk,
+        None => return false,
+    };
+
+    let sig_bytes = match hex::decode(&card.signature) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    signer_key.verify(&contact_card_sign_payload(card), &sig_bytes)
+}
+
+// -- invite signing --
+
+// the community name is left out, it is only a hint for the joiner
+fn invite_sign_payload(invite: &InviteCode) -> Vec<u8> {
+    format!(
+        "dusk-invite||{}||{}||{}||{}",
+        invite.community_id, invite.owner_id, invite.inviter, invite.expires_at
+    )
+    .into_bytes()
+}
+
+pub fn sign_invite(keypair: &identity::Keypair, invite: &InviteCode) -> String {
+    let payload = invite_sign_payload(invite);
+
+    match keypair.sign(&payload) {
+        Ok(sig) => hex::encode(sig),
+        Err(e) => {
+            log::error!("failed to sign invite: {}", e);
+            String::new()
+        }
+    }
+}
+
+// checks the signature and that the embedded key belongs to the inviter.
+// whether the inviter is a member is up to the caller
+pub fn verify_invite(invite: &InviteCode) -> bool {
+    let inviter_key = match hex::decode(&invite.inviter_key)
+        .ok()
+        .and_then(|b| identity::PublicKey::try_decode_protobuf(&b).ok())
+    {
+        Some(pk) if pk.to_peer_id().to_string() == invite.inviter => pk,
+        _ => return false,
+    };
+
+    let sig_bytes = match hex::decode(&invite.signature) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+
+    inviter_key.verify(&invite_sign_payload(invite), &sig_bytes)
+}
+
+// -- safety numbers --
+
+// hash rounds per fingerprint, makes grinding a key with a colliding number expensive
+const SAFETY_NUMBER_ITERATIONS: usize = 5200;
+
+// short authentication string for a pair of peers, compared out of band to
+// rule out a key substitution. both ends compute the same number
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyNumber {
+    pub peer_id: String,
+    // twelve groups of five digits separated by spaces
+    pub number: String,
+    pub key_verified: bool,
+}
+
+// thirty digits for one side, from the key and the peer id it stands for
+fn safety_fingerprint(public_key: &[u8], peer_id: &str) -> String {
+    let mut hash = Sha512::new()
+        .chain_update(b"dusk-safety-number")
+        .chain_update(public_key)
+        .chain_update(peer_id.as_bytes())
+        .finalize();
+    for _ in 1..SAFETY_NUMBER_ITERATIONS {
+        hash = Sha512::new()
+            .chain_update(hash)
+            .chain_update(public_key)
+            .finalize();
+    }
+
+    hash[..30]
+        .chunks(5)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            format!("{:05}", value % 100_000)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// none when either key isn't a valid hex encoded public key
+pub fn safety_number(
+    local_key_hex: &str,
+    local_peer_id: &str,
+    remote_key_hex: &str,
+    remote_peer_id: &str,
+) -> Option<String> {
+    let local_key = hex::decode(local_key_hex).ok()?;
+    let remote_key = hex::decode(remote_key_hex).ok()?;
+    identity::PublicKey::try_decode_protobuf(&local_key).ok()?;
+    identity::PublicKey::try_decode_protobuf(&remote_key).ok()?;
+
+    let mut halves = [
+        safety_fingerprint(&local_key, local_peer_id),
+        safety_fingerprint(&remote_key, remote_peer_id),
+    ];
+    halves.sort();
+    Some(halves.join(" "))
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// proof-of-work fallback for people who repeatedly fail behavioral analysis.
+// a hashcash style puzzle over sha-256, bound to the peer id and proof timestamp
+// so a solution cannot be reused for another identity
+
+// difficulty is the number of leading zero bits the winning hash must have
+pub const DEFAULT_POW_DIFFICULTY: u32 = 22;
+// peers will not accept solutions below this, whatever the solver configured
+pub const MIN_POW_DIFFICULTY: u32 = 18;
+pub const MAX_POW_DIFFICULTY: u32 = 28;
+
+// behavioral failures needed before the fallback is offered
+pub const POW_FALLBACK_AFTER_FAILURES: u32 = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowSolution {
+    pub difficulty: u32,
+    pub nonce: u64,
+}
+
+fn pow_hash(peer_id: &str, timestamp: u64, nonce: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"dusk-pow||");
+    hasher.update(peer_id.as_bytes());
+    hasher.update(b"||");
+    hasher.update(timestamp.to_le_bytes());
+    hasher.update(nonce.to_le_bytes());
+    hasher.finalize().into()
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+// search for a nonce meeting the difficulty, cpu bound so run it off the async runtime
+pub fn solve(peer_id: &str, timestamp: u64, difficulty: u32) -> (PowSolution, [u8; 32]) {
+    let difficulty = difficulty.clamp(MIN_POW_DIFFICULTY, MAX_POW_DIFFICULTY);
+    let mut nonce = 0u64;
+    loop {
+        let hash = pow_hash(peer_id, timestamp, nonce);
+        if leading_zero_bits(&hash) >= difficulty {
+            return (PowSolution { difficulty, nonce }, hash);
+        }
+        nonce += 1;
+    }
+}
+
+// check a solution against the identity it claims to belong to
+pub fn verify(solution: &PowSolution, peer_id: &str, timestamp: u64) -> bool {
+    if solution.difficulty < MIN_POW_DIFFICULTY {
+        return false;
+    }
+    let hash = pow_hash(peer_id, timestamp, solution.nonce);
+    leading_zero_bits(&hash) >= solution.difficulty
+}
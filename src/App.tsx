@@ -464,6 +464,15 @@ const App: Component = () => {
       case "sync_complete":
         void handleSyncComplete(event.payload.community_id);
         break;
+      case "history_backfilled":
+        // older messages arrived from a peer after the channel was opened
+        if (event.payload.channel_id === activeChannelId()) {
+          void tauri
+            .getMessages(event.payload.channel_id)
+            .then(setMessages)
+            .catch(() => {});
+        }
+        break;
       case "profile_received":
         // update our local directory cache when a peer announces their profile
         updatePeerProfile(
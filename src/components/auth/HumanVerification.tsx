@@ -376,6 +376,7 @@ const HumanVerification: Component<HumanVerificationProps> = (props) => {
           setPhase("passed");
           // package raw challenge data for the backend to re-validate
           const exportData: ChallengeExport = {
+            kind: "mouse",
             segments: challengeData.segments.map((s) => ({
               fromTarget: s.fromTarget,
               toTarget: s.toTarget,
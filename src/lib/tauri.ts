@@ -3,19 +3,71 @@ import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import type {
   PublicIdentity,
   CommunityMeta,
+  MessageRevision,
+  ChannelFollow,
+  CommunityLayout,
+  CommunityFolder,
+  CommunityPlacement,
   ChannelMeta,
   CategoryMeta,
+  Note,
+  NoteEdit,
+  NoteRevision,
+  PageEditRole,
+  PageMeta,
+  TaskBoard,
+  TaskCard,
+  TaskCardPatch,
+  TaskList,
   ChatMessage,
+  Attachment,
+  Embed,
+  MessageReaction,
+  MessageThread,
+  ThreadSummary,
   Member,
+  MembershipLogEntry,
+  CapabilityToken,
+  ModerationCapability,
+  CommunityProfile,
+  DocConflict,
+  PeerScore,
+  ConnectionReport,
+  ForegroundSync,
+  VerificationPolicy,
+  NetworkProfile,
+  NetworkProfileStatus,
+  CoverTrafficStatus,
+  UpdateChannel,
+  SearchTokenizer,
+  UpdateInfo,
+  CrashReport,
+  LogConfig,
+  ResolvedLocale,
+  LogLevel,
+  DoctorReport,
+  UsageStats,
+  CommunityAnalytics,
   DuskEvent,
+  BootPhase,
+  DatabaseEncryption,
   UserSettings,
+  SettingsProfile,
+  LatencyStats,
   DirectoryEntry,
+  SafetyNumber,
+  KeyConflict,
   ChallengeExport,
   VoiceParticipant,
   VoiceMediaState,
   DirectMessage,
+  DMReaction,
   DMConversationMeta,
+  DMConversationPage,
+  DMEphemeralPolicy,
+  DMPolicyLog,
   DMSearchFilters,
+  CallRecord,
   GifResponse,
 } from "./types";
 
@@ -69,8 +121,67 @@ export async function createIdentity(
   displayName: string,
   bio?: string,
   challengeData?: ChallengeExport,
+  passphrase?: string,
 ): Promise<PublicIdentity> {
-  return invoke("create_identity", { displayName, bio, challengeData });
+  return invoke("create_identity", {
+    displayName,
+    bio,
+    challengeData,
+    passphrase,
+  });
+}
+
+// fallback once the behavioral challenge has failed several times
+export async function createIdentityWithPow(
+  displayName: string,
+  bio?: string,
+  passphrase?: string,
+): Promise<PublicIdentity> {
+  return invoke("create_identity_with_pow", { displayName, bio, passphrase });
+}
+
+export async function isPortableMode(): Promise<boolean> {
+  return invoke("is_portable_mode");
+}
+
+export async function isIdentityLocked(): Promise<boolean> {
+  return invoke("is_identity_locked");
+}
+
+export async function getBootPhase(): Promise<BootPhase> {
+  return invoke("get_boot_phase");
+}
+
+export async function unlockIdentity(passphrase: string): Promise<PublicIdentity> {
+  return invoke("unlock_identity", { passphrase });
+}
+
+export async function setIdentityPassphrase(passphrase?: string): Promise<void> {
+  return invoke("set_identity_passphrase", { passphrase });
+}
+
+export async function isStorageEncrypted(): Promise<boolean> {
+  return invoke("is_storage_encrypted");
+}
+
+// returns how many stored rows were encrypted
+export async function encryptStorage(passphrase: string): Promise<number> {
+  return invoke("encrypt_storage", { passphrase });
+}
+
+export async function getDatabaseEncryption(): Promise<DatabaseEncryption> {
+  return invoke("get_database_encryption");
+}
+
+// without a passphrase the database goes back to plaintext
+export async function setDatabaseEncryption(
+  passphrase?: string,
+): Promise<DatabaseEncryption> {
+  return invoke("set_database_encryption", { passphrase });
+}
+
+export async function getVerificationPrompt(): Promise<string> {
+  return invoke("get_verification_prompt");
 }
 
 export async function updateDisplayName(name: string): Promise<void> {
@@ -94,6 +205,68 @@ export async function saveSettings(settings: UserSettings): Promise<void> {
   return invoke("save_settings", { settings });
 }
 
+// json file contents with settings and the community folder layout
+export async function exportSettings(): Promise<string> {
+  return invoke("export_settings");
+}
+
+// restores the folder layout, the returned settings still need saveSettings
+export async function importSettings(data: string): Promise<UserSettings> {
+  return invoke("import_settings", { data });
+}
+
+// -- settings profiles --
+
+export async function listSettingsProfiles(): Promise<SettingsProfile[]> {
+  return invoke("list_settings_profiles");
+}
+
+// saves the current appearance and notification settings under name and
+// makes it the active profile
+export async function saveSettingsProfile(name: string): Promise<SettingsProfile> {
+  return invoke("save_settings_profile", { name });
+}
+
+// returns the full settings with the profile applied
+export async function switchSettingsProfile(name: string): Promise<UserSettings> {
+  return invoke("switch_settings_profile", { name });
+}
+
+export async function deleteSettingsProfile(name: string): Promise<void> {
+  return invoke("delete_settings_profile", { name });
+}
+
+// -- community folders and ordering --
+
+export async function getCommunityLayout(): Promise<CommunityLayout> {
+  return invoke("get_community_layout");
+}
+
+export async function createFolder(name: string): Promise<CommunityFolder> {
+  return invoke("create_folder", { name });
+}
+
+export async function updateFolder(
+  folderId: string,
+  changes: { name?: string; collapsed?: boolean },
+): Promise<CommunityFolder> {
+  return invoke("update_folder", { folderId, ...changes });
+}
+
+export async function deleteFolder(folderId: string): Promise<void> {
+  return invoke("delete_folder", { folderId });
+}
+
+export async function setFolderOrder(folderIds: string[]): Promise<CommunityLayout> {
+  return invoke("set_folder_order", { folderIds });
+}
+
+export async function setCommunityOrder(
+  communities: CommunityPlacement[],
+): Promise<CommunityLayout> {
+  return invoke("set_community_order", { communities });
+}
+
 // -- node lifecycle --
 
 export async function startNode(): Promise<void> {
@@ -106,11 +279,14 @@ export async function stopNode(): Promise<void> {
 
 // -- community --
 
+// idempotencyKey is reused when retrying, the original result comes back
+// instead of the action running twice
 export async function createCommunity(
   name: string,
   description: string,
+  idempotencyKey?: string,
 ): Promise<CommunityMeta> {
-  return invoke("create_community", { name, description });
+  return invoke("create_community", { name, description, idempotencyKey });
 }
 
 export async function joinCommunity(
@@ -119,8 +295,12 @@ export async function joinCommunity(
   return invoke("join_community", { inviteCode });
 }
 
-export async function leaveCommunity(communityId: string): Promise<void> {
-  return invoke("leave_community", { communityId });
+// keepHistory leaves a read-only archive behind instead of removing everything
+export async function leaveCommunity(
+  communityId: string,
+  keepHistory = false,
+): Promise<void> {
+  return invoke("leave_community", { communityId, keepHistory });
 }
 
 export async function getCommunities(): Promise<CommunityMeta[]> {
@@ -135,6 +315,7 @@ export async function createChannel(
   topic: string,
   kind?: string,
   categoryId?: string | null,
+  idempotencyKey?: string,
 ): Promise<ChannelMeta> {
   return invoke("create_channel", {
     communityId,
@@ -142,6 +323,7 @@ export async function createChannel(
     topic,
     kind,
     categoryId,
+    idempotencyKey,
   });
 }
 
@@ -149,11 +331,134 @@ export async function getChannels(communityId: string): Promise<ChannelMeta[]> {
   return invoke("get_channels", { communityId });
 }
 
+// -- notes --
+
+export async function createNote(
+  communityId: string,
+  title: string,
+  idempotencyKey?: string,
+): Promise<Note> {
+  return invoke("create_note", { communityId, title, idempotencyKey });
+}
+
+export async function applyNoteChanges(
+  communityId: string,
+  noteId: string,
+  edits: NoteEdit[],
+): Promise<Note> {
+  return invoke("apply_note_changes", { communityId, noteId, edits });
+}
+
+export async function getNote(communityId: string, noteId: string): Promise<Note> {
+  return invoke("get_note", { communityId, noteId });
+}
+
+export async function getNotes(communityId: string): Promise<Note[]> {
+  return invoke("get_notes", { communityId });
+}
+
+// -- wiki pages --
+
+export async function createPage(
+  communityId: string,
+  title: string,
+  editRole?: PageEditRole,
+  idempotencyKey?: string,
+): Promise<PageMeta> {
+  return invoke("create_page", { communityId, title, editRole, idempotencyKey });
+}
+
+export async function editPage(
+  communityId: string,
+  pageId: string,
+  edits: NoteEdit[],
+): Promise<Note> {
+  return invoke("edit_page", { communityId, pageId, edits });
+}
+
+export async function getPages(communityId: string): Promise<PageMeta[]> {
+  return invoke("get_pages", { communityId });
+}
+
+// the page now, or as it read right after a revision from getPageHistory
+export async function getPage(
+  communityId: string,
+  pageId: string,
+  revision?: string,
+): Promise<Note> {
+  return invoke("get_page", { communityId, pageId, revision });
+}
+
+export async function getPageHistory(
+  communityId: string,
+  pageId: string,
+): Promise<NoteRevision[]> {
+  return invoke("get_page_history", { communityId, pageId });
+}
+
+export async function setPageEditRole(
+  communityId: string,
+  pageId: string,
+  editRole: PageEditRole,
+): Promise<PageMeta[]> {
+  return invoke("set_page_edit_role", { communityId, pageId, editRole });
+}
+
+// -- task board --
+
+export async function getTaskBoard(communityId: string): Promise<TaskBoard> {
+  return invoke("get_task_board", { communityId });
+}
+
+export async function createTaskList(
+  communityId: string,
+  name: string,
+  idempotencyKey?: string,
+): Promise<TaskList> {
+  return invoke("create_task_list", { communityId, name, idempotencyKey });
+}
+
+export async function updateTaskList(
+  communityId: string,
+  listId: string,
+  name?: string,
+  position?: number,
+): Promise<TaskBoard> {
+  return invoke("update_task_list", { communityId, listId, name, position });
+}
+
+export async function deleteTaskList(communityId: string, listId: string): Promise<TaskBoard> {
+  return invoke("delete_task_list", { communityId, listId });
+}
+
+export async function createTaskCard(
+  communityId: string,
+  listId: string,
+  title: string,
+  description?: string,
+  idempotencyKey?: string,
+): Promise<TaskCard> {
+  return invoke("create_task_card", { communityId, listId, title, description, idempotencyKey });
+}
+
+export async function updateTaskCard(
+  communityId: string,
+  cardId: string,
+  patch: TaskCardPatch,
+): Promise<TaskBoard> {
+  return invoke("update_task_card", { communityId, cardId, patch });
+}
+
+export async function deleteTaskCard(communityId: string, cardId: string): Promise<TaskBoard> {
+  return invoke("delete_task_card", { communityId, cardId });
+}
+
 export async function createCategory(
   communityId: string,
   name: string,
+  idempotencyKey?: string,
 ): Promise<CategoryMeta> {
-  return invoke("create_category", { communityId, name });
+  return invoke("create_category", { communityId, name, idempotencyKey });
 }
 
 export async function getCategories(
@@ -188,6 +493,15 @@ export async function updateChannel(
   return invoke("update_channel", { communityId, channelId, name, topic });
 }
 
+// null lifts the cap
+export async function setVoiceChannelCapacity(
+  communityId: string,
+  channelId: string,
+  maxParticipants: number | null,
+): Promise<ChannelMeta> {
+  return invoke("set_voice_channel_capacity", { communityId, channelId, maxParticipants });
+}
+
 export async function deleteChannel(
   communityId: string,
   channelId: string,
@@ -217,6 +531,69 @@ export async function reorderCategories(
   return invoke("reorder_categories", { communityId, categoryIds });
 }
 
+export async function setCommunityVerificationPolicy(
+  communityId: string,
+  policy: VerificationPolicy,
+): Promise<CommunityMeta> {
+  return invoke("set_community_verification_policy", { communityId, policy });
+}
+
+export async function setCommunityEditHistory(
+  communityId: string,
+  enabled: boolean,
+): Promise<CommunityMeta> {
+  return invoke("set_community_edit_history", { communityId, enabled });
+}
+
+// oldest revision first, fails unless the community has edit history on
+export async function getMessageEditHistory(
+  communityId: string,
+  messageId: string,
+): Promise<MessageRevision[]> {
+  return invoke("get_message_edit_history", { communityId, messageId });
+}
+
+export async function followChannel(
+  sourceCommunityId: string,
+  sourceChannelId: string,
+  targetCommunityId: string,
+  targetChannelId: string,
+): Promise<ChannelFollow> {
+  return invoke("follow_channel", {
+    sourceCommunityId,
+    sourceChannelId,
+    targetCommunityId,
+    targetChannelId,
+  });
+}
+
+export async function unfollowChannel(
+  sourceChannelId: string,
+  targetChannelId: string,
+): Promise<void> {
+  return invoke("unfollow_channel", { sourceChannelId, targetChannelId });
+}
+
+export async function getChannelFollows(): Promise<ChannelFollow[]> {
+  return invoke("get_channel_follows");
+}
+
+export async function setCommunityProfile(
+  communityId: string,
+  displayName: string,
+  avatarSeed?: string,
+): Promise<CommunityProfile> {
+  return invoke("set_community_profile", { communityId, displayName, avatarSeed });
+}
+
+export async function clearCommunityProfile(communityId: string): Promise<void> {
+  return invoke("clear_community_profile", { communityId });
+}
+
+export async function getCommunityProfiles(): Promise<CommunityProfile[]> {
+  return invoke("get_community_profiles");
+}
+
 export async function setMemberRole(
   communityId: string,
   memberPeerId: string,
@@ -232,13 +609,62 @@ export async function transferOwnership(
   return invoke("transfer_ownership", { communityId, newOwnerPeerId });
 }
 
+export async function getCommunityConflicts(
+  communityId: string,
+): Promise<DocConflict[]> {
+  return invoke("get_community_conflicts", { communityId });
+}
+
+export async function resolveCommunityConflict(
+  communityId: string,
+  key: string,
+  value: string,
+): Promise<void> {
+  return invoke("resolve_community_conflict", { communityId, key, value });
+}
+
 // -- messages --
 
 export async function sendMessage(
   channelId: string,
   content: string,
+  idempotencyKey?: string,
+  attachments?: Attachment[],
+  embeds?: Embed[],
+  replyTo?: string,
+): Promise<ChatMessage> {
+  return invoke("send_message", {
+    channelId,
+    content,
+    attachments,
+    embeds,
+    replyTo,
+    idempotencyKey,
+  });
+}
+
+export async function getLatencyStats(): Promise<LatencyStats> {
+  return invoke("get_latency_stats");
+}
+
+// returns right away with the message pending, message_committed or
+// message_failed follows once it is stored and published
+export async function sendMessageOptimistic(
+  channelId: string,
+  content: string,
+  idempotencyKey?: string,
+  attachments?: Attachment[],
+  embeds?: Embed[],
+  replyTo?: string,
 ): Promise<ChatMessage> {
-  return invoke("send_message", { channelId, content });
+  return invoke("send_message_optimistic", {
+    channelId,
+    content,
+    attachments,
+    embeds,
+    replyTo,
+    idempotencyKey,
+  });
 }
 
 export async function getMessages(
@@ -249,16 +675,62 @@ export async function getMessages(
   return invoke("get_messages", { channelId, before, limit });
 }
 
+export async function getThread(
+  channelId: string,
+  rootId: string,
+): Promise<MessageThread> {
+  return invoke("get_thread", { channelId, rootId });
+}
+
+// threads in a channel, most recently replied to first
+export async function getThreadSummaries(
+  channelId: string,
+): Promise<ThreadSummary[]> {
+  return invoke("get_thread_summaries", { channelId });
+}
+
 // -- members --
 
 export async function getMembers(communityId: string): Promise<Member[]> {
   return invoke("get_members", { communityId });
 }
 
+export async function getMembershipLog(
+  communityId: string,
+): Promise<MembershipLogEntry[]> {
+  return invoke("get_membership_log", { communityId });
+}
+
+export async function grantCapability(
+  communityId: string,
+  holderPeerId: string,
+  capability: ModerationCapability,
+  channelId: string | null,
+  expiresAt: number,
+): Promise<CapabilityToken> {
+  return invoke("grant_capability", {
+    communityId,
+    holderPeerId,
+    capability,
+    channelId,
+    expiresAt,
+  });
+}
+
+export async function getCapabilities(
+  communityId: string,
+): Promise<CapabilityToken[]> {
+  return invoke("get_capabilities", { communityId });
+}
+
 export async function sendTypingIndicator(channelId: string): Promise<void> {
   return invoke("send_typing", { channelId });
 }
 
+export async function openChannel(communityId: string, channelId: string): Promise<void> {
+  return invoke("open_channel", { communityId, channelId });
+}
+
 export async function broadcastPresence(status: string): Promise<void> {
   return invoke("broadcast_presence", { status });
 }
@@ -273,6 +745,22 @@ export async function editMessage(
   return invoke("edit_message", { communityId, messageId, newContent });
 }
 
+export async function addReaction(
+  communityId: string,
+  messageId: string,
+  emoji: string,
+): Promise<MessageReaction[]> {
+  return invoke("add_reaction", { communityId, messageId, emoji });
+}
+
+export async function removeReaction(
+  communityId: string,
+  messageId: string,
+  emoji: string,
+): Promise<MessageReaction[]> {
+  return invoke("remove_reaction", { communityId, messageId, emoji });
+}
+
 export async function deleteMessage(
   communityId: string,
   messageId: string,
@@ -280,6 +768,14 @@ export async function deleteMessage(
   return invoke("delete_message", { communityId, messageId });
 }
 
+// only works for 30 seconds after delete_message, returns the restored message
+export async function undoDeleteMessage(
+  communityId: string,
+  messageId: string,
+): Promise<ChatMessage> {
+  return invoke("undo_delete_message", { communityId, messageId });
+}
+
 export async function kickMember(
   communityId: string,
   memberPeerId: string,
@@ -297,10 +793,24 @@ export async function getKnownPeers(): Promise<DirectoryEntry[]> {
   return invoke("get_known_peers");
 }
 
+// friends, dm peers and community members are kept, returns how many went
+export async function pruneDirectory(olderThanDays?: number): Promise<number> {
+  return invoke("prune_directory", { olderThanDays });
+}
+
+// rendezvous placeholders are left out unless asked for
 export async function searchDirectory(
   query: string,
+  includePlaceholders?: boolean,
+  limit?: number,
+  offset?: number,
 ): Promise<DirectoryEntry[]> {
-  return invoke("search_directory", { query });
+  return invoke("search_directory", {
+    query,
+    includePlaceholders,
+    limit,
+    offset,
+  });
 }
 
 export async function getFriends(): Promise<DirectoryEntry[]> {
@@ -315,6 +825,39 @@ export async function removeFriend(peerId: string): Promise<void> {
   return invoke("remove_friend", { peerId });
 }
 
+// contact cards are base58 strings, suitable for text or a qr code
+export async function exportMyContactCard(): Promise<string> {
+  return invoke("export_my_contact_card");
+}
+
+export async function exportContactCard(peerId: string): Promise<string> {
+  return invoke("export_contact_card", { peerId });
+}
+
+export async function importContactCard(card: string): Promise<DirectoryEntry> {
+  return invoke("import_contact_card", { card });
+}
+
+export async function getSafetyNumber(peerId: string): Promise<SafetyNumber> {
+  return invoke("get_safety_number", { peerId });
+}
+
+export async function markPeerVerified(peerId: string, verified: boolean): Promise<void> {
+  return invoke("mark_peer_verified", { peerId, verified });
+}
+
+export async function getKeyConflicts(): Promise<KeyConflict[]> {
+  return invoke("get_key_conflicts");
+}
+
+export async function acceptNewKey(peerId: string): Promise<DirectoryEntry> {
+  return invoke("accept_new_key", { peerId });
+}
+
+export async function rejectNewKey(peerId: string): Promise<void> {
+  return invoke("reject_new_key", { peerId });
+}
+
 export async function discoverGlobalPeers(): Promise<void> {
   return invoke("discover_global_peers");
 }
@@ -323,6 +866,60 @@ export async function setRelayDiscoverable(enabled: boolean): Promise<void> {
   return invoke("set_relay_discoverable", { enabled });
 }
 
+// communities our profile announcements stay out of
+export async function getProfileHiddenCommunities(): Promise<string[]> {
+  return invoke("get_profile_hidden_communities");
+}
+
+export async function setCommunityProfileVisibility(
+  communityId: string,
+  visible: boolean,
+): Promise<void> {
+  return invoke("set_community_profile_visibility", { communityId, visible });
+}
+
+// announces our profile right away, also in manual-only mode
+export async function announceProfileNow(): Promise<void> {
+  return invoke("announce_profile_now");
+}
+
+// removes our entry from the relay's directory and opts out of it
+export async function purgeRelayDirectoryEntry(): Promise<void> {
+  return invoke("purge_relay_directory_entry");
+}
+
+// heartbeat to the relay so friends outside shared communities see each other online
+export async function setRelayPresence(enabled: boolean): Promise<void> {
+  return invoke("set_relay_presence", { enabled });
+}
+
+export async function setNetworkProfile(
+  profile: NetworkProfile,
+): Promise<NetworkProfileStatus> {
+  return invoke("set_network_profile", { profile });
+}
+
+export async function getNetworkProfile(): Promise<NetworkProfileStatus> {
+  return invoke("get_network_profile");
+}
+
+export async function setCoverTraffic(enabled: boolean): Promise<CoverTrafficStatus> {
+  return invoke("set_cover_traffic", { enabled });
+}
+
+export async function getCoverTraffic(): Promise<CoverTrafficStatus> {
+  return invoke("get_cover_traffic");
+}
+
+// rich presence such as "Playing Factorio", null or "" clears it
+export async function setActivity(activity: string | null): Promise<string | null> {
+  return invoke("set_activity", { activity });
+}
+
+export async function getActivity(): Promise<string | null> {
+  return invoke("get_activity");
+}
+
 export async function setRelayAddress(relayAddr: string): Promise<void> {
   return invoke("set_relay_address", { relayAddr });
 }
@@ -344,6 +941,100 @@ export async function checkInternetConnectivity(): Promise<boolean> {
   return invoke("check_internet_connectivity");
 }
 
+export async function getPeerScores(): Promise<PeerScore[]> {
+  return invoke("get_peer_scores");
+}
+
+export async function getConnectionReport(): Promise<ConnectionReport> {
+  return invoke("get_connection_report");
+}
+
+export async function enterBackgroundMode(): Promise<void> {
+  return invoke("enter_background_mode");
+}
+
+export async function syncOnForeground(): Promise<ForegroundSync> {
+  return invoke("sync_on_foreground");
+}
+
+// -- updates --
+
+export async function checkForUpdates(): Promise<UpdateInfo | null> {
+  return invoke("check_for_updates");
+}
+
+export async function installUpdate(): Promise<void> {
+  return invoke("install_update");
+}
+
+export async function setUpdateChannel(channel: UpdateChannel): Promise<void> {
+  return invoke("set_update_channel", { channel });
+}
+
+// -- storage --
+
+export async function runStorageDoctor(repair: boolean): Promise<DoctorReport> {
+  return invoke("run_storage_doctor", { repair });
+}
+
+export async function getUsageStats(days?: number): Promise<UsageStats> {
+  return invoke("get_usage_stats", { days });
+}
+
+// needs the community_analytics setting and owner or admin role
+export async function getCommunityAnalytics(communityId: string): Promise<CommunityAnalytics> {
+  return invoke("get_community_analytics", { communityId });
+}
+
+// resolves to false when the index already used this tokenizer
+export async function setSearchTokenizer(tokenizer: SearchTokenizer): Promise<boolean> {
+  return invoke("set_search_tokenizer", { tokenizer });
+}
+
+export async function setDataDirectory(path: string): Promise<void> {
+  return invoke("set_data_directory", { path });
+}
+
+// -- crash reports --
+
+export async function listCrashReports(): Promise<CrashReport[]> {
+  return invoke("list_crash_reports");
+}
+
+export async function submitCrashReport(id: string): Promise<void> {
+  return invoke("submit_crash_report", { id });
+}
+
+// without a module this sets the default level, without a level the
+// module's own setting is dropped
+export async function setLogLevel(
+  module?: string,
+  level?: LogLevel,
+): Promise<LogConfig> {
+  return invoke("set_log_level", { module, level });
+}
+
+// writes a redacted report with the current log file to path
+export async function exportDiagnostics(path: string): Promise<void> {
+  return invoke("export_diagnostics", { path });
+}
+
+// -- locale --
+
+// report Intl's locale and time zone and the current offset, east of utc
+// (the negation of Date.getTimezoneOffset)
+export async function setSystemLocale(
+  locale: string,
+  timeZone: string,
+  utcOffsetMinutes: number,
+): Promise<ResolvedLocale> {
+  return invoke("set_system_locale", { locale, timeZone, utcOffsetMinutes });
+}
+
+export async function getLocale(): Promise<ResolvedLocale> {
+  return invoke("get_locale");
+}
+
 // -- events --
 
 export function onDuskEvent(
@@ -380,6 +1071,40 @@ export async function updateVoiceMediaState(
   });
 }
 
+// 48khz mono microphone samples in, denoised and gain-normalized samples out.
+// raw bytes skip json and the logging wrapper, this runs every few milliseconds
+export async function processAudioFrame(samples: Float32Array): Promise<Float32Array> {
+  const bytes = new Uint8Array(samples.buffer, samples.byteOffset, samples.byteLength);
+  const out = await tauriInvoke<ArrayBuffer>("process_audio_frame", bytes);
+  return new Float32Array(out);
+}
+
+// png bytes of a qr code for an invite or contact card
+export async function generateQr(data: string): Promise<Uint8Array> {
+  const out = await tauriInvoke<ArrayBuffer>("generate_qr", { data });
+  return new Uint8Array(out);
+}
+
+export async function saveQr(data: string, path: string): Promise<void> {
+  return invoke("save_qr", { data, path });
+}
+
+export async function setAudioProcessing(
+  noiseSuppression: boolean,
+  autoGainControl: boolean,
+): Promise<void> {
+  return invoke("set_audio_processing", { noiseSuppression, autoGainControl });
+}
+
+// level is the local microphone rms in 0..1, sent at a steady rate while in a call
+export async function reportVoiceActivity(
+  communityId: string,
+  channelId: string,
+  level: number,
+): Promise<void> {
+  return invoke("report_voice_activity", { communityId, channelId, level });
+}
+
 export async function sendVoiceSdp(
   communityId: string,
   channelId: string,
@@ -421,6 +1146,40 @@ export async function getVoiceParticipants(
   return invoke("get_voice_participants", { communityId, channelId });
 }
 
+export async function disconnectVoiceParticipant(
+  communityId: string,
+  channelId: string,
+  peerId: string,
+): Promise<void> {
+  return invoke("disconnect_voice_participant", { communityId, channelId, peerId });
+}
+
+export async function moveVoiceParticipant(
+  communityId: string,
+  channelId: string,
+  peerId: string,
+  targetChannelId: string,
+): Promise<void> {
+  return invoke("move_voice_participant", { communityId, channelId, peerId, targetChannelId });
+}
+
+export async function raiseStageHand(
+  communityId: string,
+  channelId: string,
+  raised: boolean,
+): Promise<void> {
+  return invoke("raise_stage_hand", { communityId, channelId, raised });
+}
+
+export async function setStageRole(
+  communityId: string,
+  channelId: string,
+  peerId: string,
+  speaker: boolean,
+): Promise<void> {
+  return invoke("set_stage_role", { communityId, channelId, peerId, speaker });
+}
+
 // -- turn credentials --
 
 export interface TurnCredentials {
@@ -439,8 +1198,11 @@ export async function getTurnCredentials(): Promise<TurnCredentials> {
 export async function sendDM(
   peerId: string,
   content: string,
+  idempotencyKey?: string,
+  replyTo?: string,
+  attachments?: Attachment[],
 ): Promise<DirectMessage> {
-  return invoke("send_dm", { peerId, content });
+  return invoke("send_dm", { peerId, content, idempotencyKey, replyTo, attachments });
 }
 
 export async function getDMMessages(
@@ -467,14 +1229,85 @@ export async function searchDMMessages(
   });
 }
 
-export async function getDMConversations(): Promise<DMConversationMeta[]> {
-  return invoke("get_dm_conversations");
+export async function getDMConversations(
+  includeArchived?: boolean,
+): Promise<DMConversationMeta[]> {
+  return invoke("get_dm_conversations", { includeArchived });
+}
+
+export async function getDMConversationPage(options: {
+  cursor?: string;
+  limit?: number;
+  unreadOnly?: boolean;
+  includeArchived?: boolean;
+}): Promise<DMConversationPage> {
+  return invoke("get_dm_conversation_page", options);
+}
+
+export async function setDMPinned(
+  peerId: string,
+  pinned: boolean,
+): Promise<DMConversationMeta> {
+  return invoke("set_dm_pinned", { peerId, pinned });
+}
+
+export async function setDMArchived(
+  peerId: string,
+  archived: boolean,
+): Promise<DMConversationMeta> {
+  return invoke("set_dm_archived", { peerId, archived });
 }
 
 export async function markDMRead(peerId: string): Promise<void> {
   return invoke("mark_dm_read", { peerId });
 }
 
+// propose a disappearing message timer, null turns it off for both sides
+export async function setDMEphemeral(
+  peerId: string,
+  ttlSecs: number | null,
+): Promise<DMEphemeralPolicy> {
+  return invoke("set_dm_ephemeral", { peerId, ttlSecs });
+}
+
+export async function respondDMEphemeral(
+  peerId: string,
+  accept: boolean,
+): Promise<DMEphemeralPolicy> {
+  return invoke("respond_dm_ephemeral", { peerId, accept });
+}
+
+export async function getDMEphemeral(peerId: string): Promise<DMEphemeralPolicy> {
+  return invoke("get_dm_ephemeral", { peerId });
+}
+
+export async function getDMPolicyLog(peerId: string): Promise<DMPolicyLog> {
+  return invoke("get_dm_policy_log", { peerId });
+}
+
+// remove one of our own messages on both ends, within 48 hours of sending
+export async function deleteDMForEveryone(peerId: string, messageId: string): Promise<void> {
+  return invoke("delete_dm_for_everyone", { peerId, messageId });
+}
+
+// change the text of one of our own messages on both ends
+export async function editDM(
+  peerId: string,
+  messageId: string,
+  content: string,
+): Promise<DirectMessage> {
+  return invoke("edit_dm", { peerId, messageId, content });
+}
+
+export async function reactDM(
+  peerId: string,
+  messageId: string,
+  emoji: string,
+  active: boolean,
+): Promise<DMReaction[]> {
+  return invoke("react_dm", { peerId, messageId, emoji, active });
+}
+
 export async function deleteDMConversation(peerId: string): Promise<void> {
   return invoke("delete_dm_conversation", { peerId });
 }
@@ -490,6 +1323,46 @@ export async function openDMConversation(
   return invoke("open_dm_conversation", { peerId, displayName });
 }
 
+// writes the conversation to disk, progress arrives as export_progress events.
+// inlineImages fetches linked images into an html export, which reveals the
+// user's ip to every image host
+export async function exportDMConversation(
+  peerId: string,
+  format: "json" | "text" | "html",
+  path: string,
+  inlineImages = false,
+): Promise<number> {
+  return invoke("export_dm_conversation", { peerId, format, path, inlineImages });
+}
+
+// -- dm calls --
+
+// media is then negotiated with sendVoiceSdp / sendVoiceIceCandidate using
+// "dm" as the community id and the call id as the channel id. an unanswered
+// call rings for 45 seconds before call_timed_out fires
+export async function startDMCall(peerId: string): Promise<CallRecord> {
+  return invoke("start_dm_call", { peerId });
+}
+
+export async function acceptDMCall(callId: string): Promise<void> {
+  return invoke("accept_dm_call", { callId });
+}
+
+export async function endDMCall(callId: string): Promise<CallRecord> {
+  return invoke("end_dm_call", { callId });
+}
+
+export async function getCallHistory(limit?: number): Promise<CallRecord[]> {
+  return invoke("get_call_history", { limit });
+}
+
+// -- privacy --
+
+// plain-text report of what this client stores about you and optionally a peer
+export async function generateDataReport(peerId?: string): Promise<string> {
+  return invoke("generate_data_report", { peerId });
+}
+
 // -- gifs --
 
 export async function searchGifs(
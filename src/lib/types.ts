@@ -6,6 +6,9 @@ export interface VerificationProof {
   signature: string;
   timestamp: number;
   score: number;
+  // present when the identity used the proof-of-work fallback
+  pow?: { difficulty: number; nonce: number } | null;
+  kind?: ChallengeKind | null;
 }
 
 export interface PublicIdentity {
@@ -38,13 +41,61 @@ export interface TargetCircleExport {
   y: number;
 }
 
-export interface ChallengeExport {
+export interface MouseChallengeExport {
+  kind: "mouse";
   segments: SegmentExport[];
   circles: TargetCircleExport[];
   totalStartTime: number;
   totalEndTime: number;
 }
 
+// keyboard-only challenge, the user transcribes a prompt phrase
+export interface KeystrokeSampleExport {
+  key: string;
+  down: number;
+  up: number;
+}
+
+export interface KeystrokeChallengeExport {
+  kind: "keystroke";
+  prompt: string;
+  typed: string;
+  keystrokes: KeystrokeSampleExport[];
+  totalStartTime: number;
+  totalEndTime: number;
+}
+
+// touch challenge, the finger is swiped between targets
+export interface TouchSampleExport {
+  x: number;
+  y: number;
+  t: number;
+  force: number;
+  radiusX: number;
+  radiusY: number;
+}
+
+export interface TouchStrokeExport {
+  fromTarget: number;
+  toTarget: number;
+  samples: TouchSampleExport[];
+}
+
+export interface TouchChallengeExport {
+  kind: "touch";
+  strokes: TouchStrokeExport[];
+  circles: TargetCircleExport[];
+  totalStartTime: number;
+  totalEndTime: number;
+}
+
+export type ChallengeKind = ChallengeExport["kind"];
+
+export type ChallengeExport =
+  | MouseChallengeExport
+  | KeystrokeChallengeExport
+  | TouchChallengeExport;
+
 export type UserStatus = "online" | "idle" | "dnd" | "invisible";
 
 export interface UserSettings {
@@ -52,6 +103,7 @@ export interface UserSettings {
   display_name: string;
   status: UserStatus;
   status_message: string;
+  status_emoji?: string;
 
   // notifications
   enable_sounds: boolean;
@@ -61,16 +113,195 @@ export interface UserSettings {
   // privacy
   show_online_status: boolean;
   allow_dms_from_anyone: boolean;
+  cover_traffic?: boolean;
 
   // appearance
   message_display: "cozy" | "compact";
   font_size: "small" | "default" | "large";
+  theme?: Theme;
+
+  // settings profile last switched to, saving settings updates it
+  active_profile?: string | null;
 
   // network
   custom_relay_addr?: string;
+  network_profile?: NetworkProfile;
+  update_channel?: UpdateChannel;
+  crash_report_endpoint?: string | null;
+  mdns_enabled?: boolean;
+  max_connections?: number;
+  interest_subscriptions?: boolean;
+  // dial peers on confirmed public addresses before the relay, shows them our ip
+  direct_connections?: boolean;
+
+  // voice
+  noise_suppression?: boolean;
+  auto_gain_control?: boolean;
 
   // discovery
   relay_discoverable: boolean;
+  // strangers unseen for this many days leave the directory, 0 keeps them
+  directory_ttl_days?: number;
+  // off, only communities we stay visible to learn our profile
+  announce_globally?: boolean;
+  announce_policy?: AnnouncePolicy;
+
+  // verification
+  verification_policy?: VerificationPolicy;
+  pow_difficulty?: number;
+
+  // search
+  search_tokenizer?: SearchTokenizer;
+
+  // community analytics
+  community_analytics?: boolean;
+
+  // debugging, bounded local log of inbound gossip for replay in dev builds
+  record_events?: boolean;
+
+  // friend online status through the relay, opt-in
+  relay_presence?: boolean;
+
+  // logging
+  logging?: LogConfig;
+
+  // locale and time zone for dates in exports and reports
+  locale?: LocaleSettings;
+}
+
+export type LogLevel = "off" | "error" | "warn" | "info" | "debug" | "trace";
+
+// log verbosity and destinations. modules are rust module paths, the
+// longest matching path decides
+export interface LogConfig {
+  level: LogLevel;
+  modules: Record<string, LogLevel>;
+  // also write to rotating files in the data dir
+  file: boolean;
+}
+
+export type HourCycle = "h12" | "h24";
+
+// unset overrides follow the system as the ui last reported it
+export interface LocaleSettings {
+  locale?: string;
+  utc_offset_minutes?: number;
+  hour_cycle?: HourCycle;
+  system_locale?: string;
+  system_time_zone?: string;
+  system_utc_offset_minutes?: number;
+}
+
+// what dates in exports and reports are written with
+export interface ResolvedLocale {
+  locale: string;
+  time_zone: string;
+  utc_offset_minutes: number;
+  hour_cycle: HourCycle;
+}
+
+export type Theme = "system" | "dark" | "light";
+
+// named set of the ui-facing settings, e.g. work and gaming
+export interface SettingsProfile {
+  name: string;
+  theme: Theme;
+  font_size: UserSettings["font_size"];
+  message_display: UserSettings["message_display"];
+  enable_sounds: boolean;
+  enable_desktop_notifications: boolean;
+  enable_message_preview: boolean;
+  updated_at: number;
+}
+
+export interface LatencyPercentiles {
+  count: number;
+  p50_ms: number;
+  p90_ms: number;
+  p99_ms: number;
+  max_ms: number;
+}
+
+// timings of our own channel messages this session
+export interface LatencyStats {
+  // send called until stored and handed to the node
+  commit: LatencyPercentiles;
+  // send called until the first member's receipt came back, a round trip
+  receipt: LatencyPercentiles;
+  pending: number;
+  unacknowledged: number;
+}
+
+export type VerificationPolicy = "require" | "warn" | "allow";
+
+export type NetworkProfile = "auto" | "normal" | "low_power";
+
+// which events announce our profile and how often
+export interface AnnouncePolicy {
+  manual_only: boolean;
+  on_startup: boolean;
+  on_profile_change: boolean;
+  on_lan_peer: boolean;
+  on_relay_connect: boolean;
+  on_peer_connect: boolean;
+  min_interval_secs: number;
+  jitter_secs: number;
+}
+
+export type UpdateChannel = "stable" | "beta";
+
+export type SearchTokenizer = "unicode61" | "trigram";
+
+export interface DoctorFinding {
+  check: string;
+  severity: "ok" | "warning" | "error";
+  message: string;
+  repaired: boolean;
+}
+
+export interface DoctorReport {
+  findings: DoctorFinding[];
+  healthy: boolean;
+}
+
+export interface CrashReport {
+  id: string;
+  created_at: number;
+  app_version: string;
+  os: string;
+  arch: string;
+  thread: string;
+  message: string;
+  location: string | null;
+  backtrace: string;
+  recent_logs: string[];
+  submitted: boolean;
+}
+
+export interface UpdateInfo {
+  version: string;
+  current_version: string;
+  channel: UpdateChannel;
+  notes: string | null;
+  date: string | null;
+  rollout_percent: number;
+}
+
+export interface NetworkProfileStatus {
+  profile: NetworkProfile;
+  low_power: boolean;
+  on_battery: boolean;
+  metered: boolean;
+  defer_attachments: boolean;
+}
+
+// session totals for cover traffic mode, shown as its bandwidth cost
+export interface CoverTrafficStatus {
+  enabled: boolean;
+  padding_bytes: number;
+  cover_bytes: number;
+  cover_messages: number;
+  estimated_bytes_per_hour: number;
 }
 
 export interface CommunityMeta {
@@ -79,6 +310,9 @@ export interface CommunityMeta {
   description: string;
   created_by: string;
   created_at: number;
+  verification_policy?: VerificationPolicy;
+  edit_history?: boolean;
+  archived?: boolean;
 }
 
 export interface ChannelMeta {
@@ -86,9 +320,103 @@ export interface ChannelMeta {
   community_id: string;
   name: string;
   topic: string;
-  kind: "Text" | "Voice";
+  kind: "Text" | "Voice" | "Announcement" | "Stage";
   position: number;
   category_id: string | null;
+  // voice channels only, unset admits anyone
+  max_participants?: number;
+}
+
+// a column of the community's task board
+export interface TaskList {
+  id: string;
+  community_id: string;
+  name: string;
+  position: number;
+  created_by: string;
+  created_at: number;
+}
+
+export interface TaskCard {
+  id: string;
+  community_id: string;
+  list_id: string;
+  title: string;
+  description: string;
+  position: number;
+  assignees: string[];
+  due_at: number | null;
+  done: boolean;
+  created_by: string;
+  created_at: number;
+  updated_at: number;
+}
+
+export interface TaskBoard {
+  lists: TaskList[];
+  cards: TaskCard[];
+}
+
+// fields left out stay as they are, clear_due drops the due date
+export interface TaskCardPatch {
+  title?: string;
+  description?: string;
+  list_id?: string;
+  position?: number;
+  assignees?: string[];
+  due_at?: number;
+  clear_due?: boolean;
+  done?: boolean;
+}
+
+// collaborative note, positions count unicode code points
+export interface Note {
+  id: string;
+  community_id: string;
+  title: string;
+  text: string;
+  marks: NoteMark[];
+  created_by: string;
+  created_at: number;
+  updated_by: string;
+  updated_at: number;
+}
+
+// formatting over [start, end), e.g. bold = true or link = url
+export interface NoteMark {
+  start: number;
+  end: number;
+  name: string;
+  value: boolean | string | number | null;
+}
+
+export type NoteEdit =
+  | { op: "splice"; index: number; delete: number; insert: string }
+  | { op: "mark"; start: number; end: number; name: string; value: boolean | string | number | null }
+  | { op: "unmark"; start: number; end: number; name: string }
+  | { op: "set_title"; title: string };
+
+// one change in a note's history. the author is whatever the editing
+// client claimed, nothing signs it
+export interface NoteRevision {
+  hash: string;
+  claimed_author: string;
+  timestamp: number;
+}
+
+export type PageEditRole = "member" | "admin";
+
+// wiki page, its body is a note read with getPage
+export interface PageMeta {
+  id: string;
+  community_id: string;
+  title: string;
+  edit_role: PageEditRole;
+  position: number;
+  created_by: string;
+  created_at: number;
+  updated_by: string;
+  updated_at: number;
 }
 
 // user-defined grouping for channels within a community
@@ -107,6 +435,105 @@ export interface ChatMessage {
   content: string;
   timestamp: number;
   edited: boolean;
+  hlc?: Hlc;
+  forwarded_from?: MessageOrigin;
+  attachments?: Attachment[];
+  embeds?: Embed[];
+  reactions?: MessageReaction[];
+  // root of the thread this message replies in
+  reply_to?: string;
+}
+
+// a thread root and every reply to it, oldest first. root is missing once
+// the root message was deleted
+export interface MessageThread {
+  root_id: string;
+  root?: ChatMessage;
+  replies: ChatMessage[];
+}
+
+export interface ThreadSummary {
+  root_id: string;
+  reply_count: number;
+  last_reply_at: number;
+  participants: string[];
+}
+
+// everyone who reacted to a channel message with one emoji
+export interface MessageReaction {
+  emoji: string;
+  peers: string[];
+}
+
+export type AttachmentKind = "image" | "video" | "audio" | "file";
+
+// file shared in a message, the bytes are fetched separately by hash
+export interface Attachment {
+  kind: AttachmentKind;
+  hash: string;
+  size: number;
+  filename: string;
+  width?: number;
+  height?: number;
+}
+
+// link preview shown under a message
+export interface Embed {
+  url: string;
+  title?: string;
+  description?: string;
+  thumbnail_hash?: string;
+}
+
+// where a mirrored announcement was first posted
+export interface MessageOrigin {
+  community_id: string;
+  community_name: string;
+  channel_id: string;
+  channel_name: string;
+  message_id: string;
+  author_id: string;
+  author_name: string;
+}
+
+// local sidebar arrangement, never shared with peers
+export interface CommunityLayout {
+  folders: CommunityFolder[];
+  communities: CommunityPlacement[];
+}
+
+export interface CommunityFolder {
+  id: string;
+  name: string;
+  position: number;
+  collapsed: boolean;
+}
+
+export interface CommunityPlacement {
+  community_id: string;
+  folder_id?: string | null;
+  position?: number;
+}
+
+// an announcement channel from another community mirrored into one of ours
+export interface ChannelFollow {
+  source_community_id: string;
+  source_channel_id: string;
+  target_community_id: string;
+  target_channel_id: string;
+  created_at: number;
+}
+
+// content a message had before one of its edits
+export interface MessageRevision {
+  revision: number;
+  content: string;
+}
+
+// hybrid logical clock used by the backend to order channel messages
+export interface Hlc {
+  wall: number;
+  counter: number;
 }
 
 // a direct message between two peers
@@ -117,6 +544,16 @@ export interface DirectMessage {
   from_display_name: string;
   content: string;
   timestamp: number;
+  reply_to?: string;
+  attachments?: Attachment[];
+  edited: boolean;
+  reactions?: DMReaction[];
+}
+
+// everyone who reacted to a dm with one emoji
+export interface DMReaction {
+  emoji: string;
+  peers: string[];
 }
 
 // metadata for a persisted dm conversation
@@ -126,6 +563,70 @@ export interface DMConversationMeta {
   last_message: string | null;
   last_message_time: number | null;
   unread_count: number;
+  pinned?: boolean;
+  archived?: boolean;
+}
+
+export interface DMConversationPage {
+  conversations: DMConversationMeta[];
+  next_cursor: string | null;
+  total_unread: number;
+  unread_conversations: number;
+}
+
+// disappearing messages for a dm conversation, the timer counts from when a
+// message was read on both ends
+export interface DMEphemeralPolicy {
+  peer_id: string;
+  ttl_secs: number | null;
+  pending_ttl_secs: number | null;
+  pending_from: string | null;
+  updated_at: number;
+}
+
+export interface DMPolicyLogEntry {
+  seq: number;
+  actor: string;
+  action: "propose" | "accept" | "decline" | "disable";
+  ttl_secs: number | null;
+  timestamp: number;
+  prev_hash: string;
+  hash: string;
+  // the actor's key and signature over the step, empty on older entries
+  public_key: string;
+  signature: string;
+}
+
+export interface DMPolicyLog {
+  entries: DMPolicyLogEntry[];
+  // false when the hash chain no longer matches its entries
+  intact: boolean;
+  // false when an entry lacks a valid signature from its actor
+  signed: boolean;
+}
+
+export type CallDirection = "incoming" | "outgoing";
+
+export type CallStatus =
+  | "ringing"
+  | "answered"
+  | "completed"
+  | "missed"
+  | "declined"
+  | "cancelled"
+  | "busy"
+  | "no_answer";
+
+export interface CallRecord {
+  call_id: string;
+  peer_id: string;
+  display_name: string;
+  direction: CallDirection;
+  status: CallStatus;
+  started_at: number;
+  answered_at: number | null;
+  ended_at: number | null;
+  duration_secs: number;
 }
 
 export type DMSearchFrom = "anyone" | "me" | "them";
@@ -148,6 +649,88 @@ export interface Member {
   roles: string[];
   trust_level: number;
   joined_at: number;
+  activity?: string | null;
+  avatar_seed?: string | null;
+}
+
+export type MembershipAction = "join" | "leave" | "kick" | "set_roles";
+
+// signed entry in a community's membership log
+export interface MembershipLogEntry {
+  action: MembershipAction;
+  peer_id: string;
+  actor: string;
+  actor_key: string;
+  roles: string[];
+  display_name: string;
+  timestamp: number;
+  signature: string;
+  capability?: CapabilityToken;
+  verified: boolean;
+  // one line describing the event in the user's locale
+  summary: string;
+}
+
+export type ModerationCapability = "delete_messages" | "kick_members" | "move_members";
+
+// signed, delegable grant of one moderation power until expires_at
+export interface CapabilityToken {
+  id: string;
+  community_id: string;
+  capability: ModerationCapability;
+  channel_id: string | null;
+  holder: string;
+  issuer: string;
+  issuer_key: string;
+  expires_at: number;
+  parent: CapabilityToken | null;
+  signature: string;
+}
+
+// a name and avatar used in one community instead of the global profile
+export interface CommunityProfile {
+  community_id: string;
+  peer_id: string;
+  display_name: string;
+  avatar_seed: string | null;
+  timestamp: number;
+}
+
+// a key holding concurrent values after partitioned halves merged
+export interface DocConflict {
+  key: string;
+  values: string[];
+}
+
+// gossipsub score for a connected peer, score is null until gossipsub has seen it
+export interface PeerScore {
+  peer_id: string;
+  score: number | null;
+  app_score: number;
+}
+
+export type PeerPriority = "stranger" | "member" | "friend";
+
+export interface ConnectionEviction {
+  peer_id: string;
+  priority: PeerPriority;
+  evicted_at: number;
+}
+
+export interface ConnectionReport {
+  max_connections: number;
+  friends: number;
+  members: number;
+  strangers: number;
+  // newest first
+  evictions: ConnectionEviction[];
+}
+
+export interface ForegroundSync {
+  background_secs: number;
+  unread_dms: number;
+  dm_conversations: DMConversationMeta[];
+  mentions: ChatMessage[];
 }
 
 export interface NodeStatus {
@@ -164,6 +747,81 @@ export interface DirectoryEntry {
   public_key: string;
   last_seen: number;
   is_friend: boolean;
+  verified?: boolean;
+  activity?: string | null;
+  status_message?: string;
+  status_emoji?: string;
+  key_verified?: boolean;
+  // only the peer id is known so far, from rendezvous discovery
+  placeholder?: boolean;
+}
+
+// short authentication string compared with a peer out of band
+export interface SafetyNumber {
+  peer_id: string;
+  number: string;
+  key_verified: boolean;
+}
+
+// an announcement held back because it carries a key other than the pinned one
+export interface KeyConflict {
+  peer_id: string;
+  display_name: string;
+  pinned_key: string;
+  offered_key: string;
+  offered_display_name: string;
+  key_verified: boolean;
+  received_at: number;
+}
+
+export interface CommunityUsage {
+  community_id: string;
+  name: string;
+  channel_count: number;
+  member_count: number;
+  message_count: number;
+  storage_bytes: number;
+}
+
+export interface ChannelActivity {
+  community_id: string;
+  community_name: string;
+  channel_id: string;
+  channel_name: string;
+  message_count: number;
+}
+
+// local activity for the insights view
+export interface UsageStats {
+  communities: CommunityUsage[];
+  top_channels: ChannelActivity[];
+  dm_message_count: number;
+  dm_conversation_count: number;
+  dm_daily: { day: number; count: number }[];
+  database_bytes: number;
+}
+
+export interface ChannelHealth {
+  channel_id: string;
+  name: string;
+  message_count: number;
+  messages_7d: number;
+  messages_30d: number;
+}
+
+// owner-facing community health, computed locally
+export interface CommunityAnalytics {
+  community_id: string;
+  member_count: number;
+  active_members_7d: number;
+  active_members_30d: number;
+  messages_7d: number;
+  messages_30d: number;
+  joins_7d: number;
+  joins_30d: number;
+  leaves_7d: number;
+  leaves_30d: number;
+  channels: ChannelHealth[];
 }
 
 // media state for a participant in a voice channel
@@ -175,10 +833,15 @@ export interface VoiceMediaState {
 }
 
 // a peer currently connected to a voice channel
+export type StageRole = "speaker" | "listener";
+
 export interface VoiceParticipant {
   peer_id: string;
   display_name: string;
   media_state: VoiceMediaState;
+  // always "speaker" outside stage channels
+  stage_role: StageRole;
+  hand_raised: boolean;
 }
 
 // gif search result from the relay klipy proxy
@@ -194,24 +857,68 @@ export interface GifResponse {
   results: GifResult[];
 }
 
+// whole-database encryption with sqlcipher. available is false for builds
+// without it, which can't open an encrypted database either
+export interface DatabaseEncryption {
+  available: boolean;
+  enabled: boolean;
+  // encrypted and waiting for unlockIdentity
+  locked: boolean;
+}
+
+// startup progress, "locked" and "onboarding" wait on the user and "node"
+// waits on startNode
+export type BootPhase =
+  | "documents"
+  | "identity"
+  | "locked"
+  | "onboarding"
+  | "node"
+  | "ready";
+
 // discriminated union for events emitted from rust
 export type DuskEvent =
   | { kind: "message_received"; payload: ChatMessage }
+  | { kind: "message_committed"; payload: ChatMessage }
+  | {
+      kind: "message_failed";
+      payload: { message_id: string; channel_id: string; error: string };
+    }
   | {
       kind: "message_edited";
       payload: { message_id: string; new_content: string };
     }
   | { kind: "message_deleted"; payload: { message_id: string } }
+  | {
+      kind: "reaction_updated";
+      payload: {
+        community_id: string;
+        message_id: string;
+        reactions: MessageReaction[];
+      };
+    }
   | { kind: "member_kicked"; payload: { peer_id: string } }
   | { kind: "peer_connected"; payload: { peer_id: string } }
   | { kind: "peer_disconnected"; payload: { peer_id: string } }
   | {
       kind: "presence_updated";
-      payload: { peer_id: string; status: string };
+      payload: {
+        peer_id: string;
+        status: string;
+        activity: string | null;
+        status_message: string;
+        status_emoji: string;
+      };
     }
+  | { kind: "friend_presence"; payload: { peer_id: string; online: boolean } }
+  | { kind: "local_activity_changed"; payload: { activity: string | null } }
   | { kind: "typing"; payload: { peer_id: string; channel_id: string } }
   | { kind: "node_status"; payload: NodeStatus }
   | { kind: "sync_complete"; payload: { community_id: string } }
+  | {
+      kind: "history_backfilled";
+      payload: { community_id: string; channel_id: string };
+    }
   | {
       kind: "profile_received";
       payload: {
@@ -219,6 +926,7 @@ export type DuskEvent =
         display_name: string;
         bio: string;
         public_key: string;
+        verified?: boolean;
         effects?: {
           click?: unknown;
           hover?: unknown;
@@ -227,7 +935,38 @@ export type DuskEvent =
       };
     }
   | { kind: "profile_revoked"; payload: { peer_id: string } }
+  | { kind: "community_profile_updated"; payload: CommunityProfile }
+  | { kind: "note_updated"; payload: Note }
   | { kind: "relay_status"; payload: { connected: boolean } }
+  | {
+      kind: "network_changed";
+      payload: {
+        mode: "lan_only" | "full";
+        internet_reachable: boolean;
+        relay_connected: boolean;
+      };
+    }
+  | { kind: "node_resumed"; payload: { slept_secs: number } }
+  | { kind: "network_profile_changed"; payload: NetworkProfileStatus }
+  | { kind: "clock_skew_detected"; payload: { peer_id: string; skew_ms: number } }
+  | {
+      kind: "community_diverged";
+      payload: {
+        community_id: string;
+        partition_secs: number | null;
+        conflicts: DocConflict[];
+      };
+    }
+  | {
+      kind: "update_progress";
+      payload: { version: string; downloaded: number; total: number | null };
+    }
+  | { kind: "update_downloaded"; payload: { version: string } }
+  | { kind: "data_migration_progress"; payload: { copied: number; total: number } }
+  | {
+      kind: "export_progress";
+      payload: { conversation_id: string; written: number; total: number };
+    }
   | {
       kind: "voice_participant_joined";
       payload: {
@@ -236,6 +975,7 @@ export type DuskEvent =
         peer_id: string;
         display_name: string;
         media_state: VoiceMediaState;
+        stage_role: StageRole;
       };
     }
   | {
@@ -246,6 +986,38 @@ export type DuskEvent =
         peer_id: string;
       };
     }
+  | {
+      kind: "voice_join_rejected";
+      payload: { community_id: string; channel_id: string; max_participants: number };
+    }
+  | {
+      kind: "voice_disconnected";
+      payload: {
+        community_id: string;
+        channel_id: string;
+        by: string;
+        move_to: string | null;
+      };
+    }
+  | {
+      kind: "stage_hand_raised";
+      payload: {
+        community_id: string;
+        channel_id: string;
+        peer_id: string;
+        raised: boolean;
+      };
+    }
+  | {
+      kind: "stage_role_changed";
+      payload: {
+        community_id: string;
+        channel_id: string;
+        peer_id: string;
+        role: StageRole;
+        by: string;
+      };
+    }
   | {
       kind: "voice_media_state_changed";
       payload: {
@@ -255,6 +1027,15 @@ export type DuskEvent =
         media_state: VoiceMediaState;
       };
     }
+  | {
+      kind: "voice_speaking_changed";
+      payload: {
+        community_id: string;
+        channel_id: string;
+        peer_id: string;
+        speaking: boolean;
+      };
+    }
   | {
       kind: "voice_sdp_received";
       payload: {
@@ -277,4 +1058,26 @@ export type DuskEvent =
       };
     }
   | { kind: "dm_received"; payload: DirectMessage }
-  | { kind: "dm_typing"; payload: { peer_id: string } };
+  | { kind: "dm_typing"; payload: { peer_id: string } }
+  | {
+      kind: "incoming_call";
+      payload: { call_id: string; peer_id: string; display_name: string };
+    }
+  | { kind: "call_accepted"; payload: { call_id: string; peer_id: string } }
+  | { kind: "call_ended"; payload: CallRecord }
+  | { kind: "call_cancelled"; payload: CallRecord }
+  | { kind: "call_busy"; payload: CallRecord }
+  | { kind: "call_timed_out"; payload: CallRecord }
+  | { kind: "dm_ephemeral_policy_changed"; payload: DMEphemeralPolicy }
+  | { kind: "dm_messages_expired"; payload: { peer_id: string; message_ids: string[] } }
+  | { kind: "dm_deletion_confirmed"; payload: { peer_id: string; message_ids: string[] } }
+  | { kind: "dm_deleted"; payload: { peer_id: string; message_id: string } }
+  | { kind: "dm_edited"; payload: DirectMessage }
+  | {
+      kind: "dm_reactions_changed";
+      payload: { peer_id: string; message_id: string; reactions: DMReaction[] };
+    }
+  | { kind: "verified_key_changed"; payload: { peer_id: string; display_name: string } }
+  | { kind: "key_conflict"; payload: KeyConflict }
+  | { kind: "boot_phase"; payload: { phase: BootPhase } }
+  | { kind: "launch_forwarded"; payload: { args: string[] } };